@@ -17,6 +17,8 @@ contract TradeExecutor is IUniswapV3SwapCallback {
     address[] public tokenLookup;
 
     error Loss(uint);
+    // the trade returned less than the min. amount out e.g. the opportunity was taken
+    error Slippage(uint);
 
     address private constant UNISWAP_V3_ROUTER = 0x68b3465833fb72A70ecDF485E0e4C7bD8665Fc45;
     address private constant CAMELOT_V2_ROUTER = 0xc873fEcbd354f5A56E00E710B90EF4201db2448d;
@@ -33,13 +35,16 @@ contract TradeExecutor is IUniswapV3SwapCallback {
     uint8 private constant SUSHI_ID = 2;
     uint8 private constant CHRONOS_ID = 3;
 
+    // marks callback data as a `flashSwapPath` payload
+    uint256 private constant PATH_FLAG = 1 << 255;
+
     event SetLookup(uint8 id, address token);
 
     constructor(address _payee) {
         payee = _payee;
         gateway = _payee;
         // init token id to address mapping
-        // the indexes are expected to be 1:1 with the fulcrum client `Token` enum
+        // the indexes are expected to be 1:1 with the fulcrum client token registry ids (see `setTokenId`)
         tokenLookup = [
             0xFF970A61A04b1cA14834A43f5dE4533eBDDB5CC8, // usdc
             0x82aF49447D8a07e3bd95BD0d56f35241523fBab1, // weth
@@ -115,6 +120,36 @@ contract TradeExecutor is IUniswapV3SwapCallback {
         return ([exchange0Id, exchange1Id, exchange2Id], [token0Id, token1Id, token2Id], [fee0, fee1, fee2]);
     }
 
+    /// decodes fulcrum trade data extensions, ignored by `decode`
+    /// pool kind per trade (2 bits) from bit 96 | min. amount out, 20 bit mantissa then 6 bit exponent from bit 102
+    /// pool kinds: 0 constant product, 1 solidly stable, 2 concentrated liquidity
+    function decodeExt(uint128 payload) public pure returns (uint8[3] memory kinds, uint256 minAmountOut) {
+        for (uint256 i = 0; i < 3; i++) {
+            kinds[i] = uint8(payload >> (96 + 2 * i)) & 3;
+        }
+        minAmountOut = uint256((payload >> 102) & 0xfffff) << uint8(payload >> 122);
+    }
+
+    /// decodes fulcrum trade path data of any length
+    /// hop count (8 bits) | start token (8 bits) | per trade: exchange (8 bits), token out (8 bits), fee (16 bits)
+    function decodePath(uint256 payload)
+        public
+        pure
+        returns (uint8[] memory exchanges, uint8[] memory tokens, uint16[] memory fees)
+    {
+        uint256 hops = uint8(payload);
+        exchanges = new uint8[](hops);
+        tokens = new uint8[](hops + 1);
+        fees = new uint16[](hops);
+        tokens[0] = uint8(payload >> 8);
+        for (uint256 i = 0; i < hops; i++) {
+            uint256 hop = payload >> (16 + 32 * i);
+            exchanges[i] = uint8(hop);
+            tokens[i + 1] = uint8(hop >> 8);
+            fees[i] = uint16(hop >> 16);
+        }
+    }
+
     // delegate approval for 'who' to spend tokens from this contract e.g. to a router contract
     function setPayee(address who) external {
         require(msg.sender == payee);
@@ -176,8 +211,9 @@ contract TradeExecutor is IUniswapV3SwapCallback {
     }
 
     // Execute a flash swap across up to 3 exchanges and 3 pools
+    // reverts if the trade returns less than `minAmountOut`
     // @dev the ABI is an attempt at optimizing for tx creation & transmission speed
-    function flashSwap(uint128 amountIn, uint128 payload) external {
+    function flashSwap(uint128 amountIn, uint128 payload, uint128 minAmountOut) external {
         require(msg.sender == gateway);
         (uint8[3] memory exchanges, uint8[3] memory tokens, uint16[3] memory fees) = decode(payload);
         address token0 = tokenLookup[tokens[0]];
@@ -190,11 +226,7 @@ contract TradeExecutor is IUniswapV3SwapCallback {
             IUniswapV3Pool pool0 = IUniswapV3Pool(
                 PoolAddress.computeAddress(UNISWAP_V3_FACTORY, PoolAddress.getPoolKey(token0, token1, fees[0]))
             );
-            // convert payload to bytes for callback abi
-            bytes memory callbackData = new bytes(32);
-            assembly {
-                mstore(add(callbackData, 32), payload)
-            }
+            bytes memory callbackData = abi.encode(payload, minAmountOut);
             if (token0 < token1) {
                 // pool0.flash(address(this), amountIn, 0, callbackData);
                 pool0.swap(address(this), true, int128(amountIn), MIN_SQRT_RATIO + 1, callbackData);
@@ -220,7 +252,7 @@ contract TradeExecutor is IUniswapV3SwapCallback {
                 // but we know there's a profitable trade so we skip the first trade, borrow the output then trade and
                 // return different token by swapping back to the pool
                 // lock modifier prevents retrading on this pool...
-                bytes memory callbackData = abi.encode(payload, amountIn);
+                bytes memory callbackData = abi.encode(payload, amountIn, minAmountOut);
                 // a,b b,a
                 // pull b, must paybcak
                 if (token0 < token1) {
@@ -234,9 +266,67 @@ contract TradeExecutor is IUniswapV3SwapCallback {
         }
     }
 
+    // Execute a flash swap along a path of any length e.g. 4+ trades
+    // @dev the loan is taken from the first trade's pool as in `flashSwap`, remaining trades run in the callback
+    function flashSwapPath(uint128 amountIn, uint256 payload, uint128 minAmountOut) external {
+        require(msg.sender == gateway);
+        (uint8[] memory exchanges, uint8[] memory tokens, uint16[] memory fees) = decodePath(payload);
+        address token0 = tokenLookup[tokens[0]];
+        address token1 = tokenLookup[tokens[1]];
+        uint8 exchangeId0 = exchanges[0];
+
+        if (exchangeId0 == UNISWAP_V3_ID) {
+            IUniswapV3Pool pool0 = IUniswapV3Pool(
+                PoolAddress.computeAddress(UNISWAP_V3_FACTORY, PoolAddress.getPoolKey(token0, token1, fees[0]))
+            );
+            bytes memory callbackData = abi.encode(payload | PATH_FLAG, minAmountOut);
+            if (token0 < token1) {
+                pool0.swap(address(this), true, int128(amountIn), MIN_SQRT_RATIO + 1, callbackData);
+            } else {
+                pool0.swap(address(this), false, int128(amountIn), MAX_SQRT_RATIO - 1, callbackData);
+            }
+        } else {
+            address pool0;
+            if (exchangeId0 == CHRONOS_ID) {
+                pool0 = IChronosFactory(CHRONOS_FACTORY).getPair(token0, token1, false);
+            } else if (exchangeId0 == CAMELOT_ID) {
+                pool0 = IUniswapV2Factory(CAMELOT_V2_FACTORY).getPair(token0, token1);
+            } else if (exchangeId0 == SUSHI_ID) {
+                pool0 = IUniswapV2Factory(SUSHI_FACTORY).getPair(token0, token1);
+            }
+            (uint256 reserve0, uint256 reserve1,) = IUniswapV2Pair(pool0).getReserves();
+            bytes memory callbackData = abi.encode(payload | PATH_FLAG, amountIn, minAmountOut);
+            if (token0 < token1) {
+                uint256 amountOut = UniswapV2Math.getAmountOut(uint256(amountIn), reserve0, reserve1);
+                IUniswapV2Pair(pool0).swap(0, amountOut, address(this), callbackData);
+            } else {
+                uint256 amountOut = UniswapV2Math.getAmountOut(uint256(amountIn), reserve1, reserve0);
+                IUniswapV2Pair(pool0).swap(amountOut, 0, address(this), callbackData);
+            }
+        }
+    }
+
+    // run trades 1..n of a `flashSwapPath` payload, returns the start token and the final amount out
+    function runPath(uint256 payload, uint256 amountIn) private returns (address token0, uint256 amountOut) {
+        (uint8[] memory exchanges, uint8[] memory tokens, uint16[] memory fees) = decodePath(payload);
+        token0 = tokenLookup[tokens[0]];
+        amountOut = amountIn;
+        for (uint256 i = 1; i < exchanges.length; i++) {
+            amountOut =
+                swapExactIn(exchanges[i], amountOut, tokenLookup[tokens[i]], tokenLookup[tokens[i + 1]], fees[i]);
+        }
+    }
+
     function uniswapV2Call(address, uint256 amount0, uint256 amount1, bytes calldata data) external {
         // we have entered a flash position, now finish the arb
-        (uint128 payload, uint128 amountInOwed) = abi.decode(data, (uint128, uint128));
+        (uint256 rawPayload, uint128 amountInOwed, uint128 minAmountOut) =
+            abi.decode(data, (uint256, uint128, uint128));
+        if (rawPayload & PATH_FLAG != 0) {
+            (address loanToken, uint256 earned) = runPath(rawPayload, amount0 > 0 ? amount0 : amount1);
+            payback(amountInOwed, earned, minAmountOut, loanToken);
+            return;
+        }
+        uint128 payload = uint128(rawPayload);
 
         (uint8[3] memory exchanges, uint8[3] memory tokens, uint16[3] memory fees) = decode(payload);
         address token0 = tokenLookup[tokens[0]];
@@ -258,12 +348,17 @@ contract TradeExecutor is IUniswapV3SwapCallback {
         }
 
         // always payback in the starting token(0)
-        payback(amountInOwed, netAmountOut, token0);
+        payback(amountInOwed, netAmountOut, minAmountOut, token0);
     }
 
     function uniswapV3SwapCallback(int256 amount0Delta, int256 amount1Delta, bytes calldata data) external {
         // we have entered a flash position, now finish the arb
-        uint128 payload = uint128(uint256(bytes32(data)));
+        (uint256 rawPayload, uint128 minAmountOut) = abi.decode(data, (uint256, uint128));
+        if (rawPayload & PATH_FLAG != 0) {
+            uniswapV3PathCallback(amount0Delta, amount1Delta, rawPayload, minAmountOut);
+            return;
+        }
+        uint128 payload = uint128(rawPayload);
         (uint8[3] memory exchanges, uint8[3] memory tokens, uint16[3] memory fees) = decode(payload);
 
         // scoping to fix stack depth
@@ -300,7 +395,19 @@ contract TradeExecutor is IUniswapV3SwapCallback {
             amountInputEarned = swapExactIn(exchanges[2], amountInputEarned, token2, token0, fees[2]);
             console.logUint(amountInputEarned);
         }
-        payback(amountInputOwed, amountInputEarned, token0);
+        payback(amountInputOwed, amountInputEarned, minAmountOut, token0);
+    }
+
+    // finish a `flashSwapPath` arb entered via a uniswap v3 pool
+    function uniswapV3PathCallback(int256 amount0Delta, int256 amount1Delta, uint256 payload, uint128 minAmountOut)
+        private
+    {
+        // the pool is paid in the start token and loans the first trade's output
+        (uint256 amountInputOwed, uint256 amountInputEarned) = amount0Delta > 0
+            ? (uint256(amount0Delta), uint256(-amount1Delta))
+            : (uint256(amount1Delta), uint256(-amount0Delta));
+        (address loanToken, uint256 earned) = runPath(payload, amountInputEarned);
+        payback(amountInputOwed, earned, minAmountOut, loanToken);
     }
 
     function swapExactIn(uint8 exchangeId, uint256 amountIn, address tokenIn, address tokenOut, uint16 fee)
@@ -358,7 +465,8 @@ contract TradeExecutor is IUniswapV3SwapCallback {
     }
 
     // payback the flash swap loan and send profits to 'payee'
-    function payback(uint256 loanAmount, uint256 earnedAmount, address loanToken) private {
+    function payback(uint256 loanAmount, uint256 earnedAmount, uint256 minAmountOut, address loanToken) private {
+        if (earnedAmount < minAmountOut) revert Slippage(minAmountOut - earnedAmount);
         if (earnedAmount <= loanAmount) revert Loss(loanAmount - earnedAmount);
 
         IERC20(loanToken).transfer(msg.sender, loanAmount);
@@ -7,8 +7,65 @@ interface IUniswapV2Pool {
     function getReserves() external view returns (uint128, uint128, uint256);
 }
 
+interface ICamelotPair {
+    function getReserves()
+        external
+        view
+        returns (uint112 reserve0, uint112 reserve1, uint16 token0FeePercent, uint16 token1FeePercent);
+    function stableSwap() external view returns (bool);
+}
+
+interface IBalancerVault {
+    function getPoolTokens(bytes32 poolId)
+        external
+        view
+        returns (address[] memory tokens, uint256[] memory balances, uint256 lastChangeBlock);
+}
+
+interface IBalancerWeightedPool {
+    function getVault() external view returns (address);
+    function getPoolId() external view returns (bytes32);
+    function getNormalizedWeights() external view returns (uint256[] memory);
+    function getSwapFeePercentage() external view returns (uint256);
+}
+
+interface ICurvePool {
+    function coins(uint256 i) external view returns (address);
+    function balances(uint256 i) external view returns (uint256);
+    function A() external view returns (uint256);
+    function fee() external view returns (uint256);
+}
+
+interface ISolidlyPair {
+    function getReserves() external view returns (uint256 reserve0, uint256 reserve1, uint256 blockTimestampLast);
+    function stable() external view returns (bool);
+}
+
+interface IKyberElasticPool {
+    function getPoolState()
+        external
+        view
+        returns (uint160 sqrtP, int24 currentTick, int24 nearestCurrentTick, bool locked);
+    function getLiquidityState() external view returns (uint128 baseL, uint128 reinvestL, uint128 reinvestLLast);
+}
+
 interface IUniswapV3Pool {
     function liquidity() external view returns (uint128);
+    function tickSpacing() external view returns (int24);
+    function tickBitmap(int16 wordPosition) external view returns (uint256);
+    function ticks(int24 tick)
+        external
+        view
+        returns (
+            uint128 liquidityGross,
+            int128 liquidityNet,
+            uint256 feeGrowthOutside0X128,
+            uint256 feeGrowthOutside1X128,
+            int56 tickCumulativeOutside,
+            uint160 secondsPerLiquidityOutsideX128,
+            uint32 secondsOutside,
+            bool initialized
+        );
     function slot0()
         external
         view
@@ -43,6 +100,228 @@ contract V3PoolViewer {
         return (v3PoolData, v2PoolData);
     }
 
+    // Query the given pools as `getPoolData` plus the initialized ticks of the UniswapV3 pools
+    // @dev input and return data is tightly packed
+    function getPoolDataWithTicks(bytes calldata v3Pools, bytes calldata v2Pools, uint8 wordRadius)
+        public
+        view
+        returns (bytes memory v3PoolData, bytes memory v2PoolData, bytes memory v3TickData)
+    {
+        (v3PoolData, v2PoolData) = getPoolData(v3Pools, v2Pools);
+        if (v3Pools.length > 0) {
+            v3TickData = getTicksV3(v3Pools, wordRadius);
+        }
+
+        return (v3PoolData, v2PoolData, v3TickData);
+    }
+
+    // Query the given pools as `getPoolDataWithTicks` plus Camelot pools with their directional fees and curve
+    // @dev input and return data is tightly packed, tick data is empty unless `withTicks`
+    function getAllPoolData(
+        bytes calldata v3Pools,
+        bytes calldata v2Pools,
+        bytes calldata camelotPools,
+        bool withTicks,
+        uint8 wordRadius
+    )
+        public
+        view
+        returns (bytes memory v3PoolData, bytes memory v2PoolData, bytes memory v3TickData, bytes memory camelotPoolData)
+    {
+        (v3PoolData, v2PoolData) = getPoolData(v3Pools, v2Pools);
+        if (withTicks && v3Pools.length > 0) {
+            v3TickData = getTicksV3(v3Pools, wordRadius);
+        }
+        if (camelotPools.length > 0) {
+            camelotPoolData = getReservesCamelot(camelotPools);
+        }
+
+        return (v3PoolData, v2PoolData, v3TickData, camelotPoolData);
+    }
+
+    // Query the given pools as `getAllPoolData` plus Balancer weighted pools
+    // @dev input and return data is tightly packed, see `getBalancesBalancer`
+    function getAllPoolDataWithBalancer(
+        bytes calldata v3Pools,
+        bytes calldata v2Pools,
+        bytes calldata camelotPools,
+        bytes calldata balancerPools,
+        bool withTicks,
+        uint8 wordRadius
+    )
+        public
+        view
+        returns (
+            bytes memory v3PoolData,
+            bytes memory v2PoolData,
+            bytes memory v3TickData,
+            bytes memory camelotPoolData,
+            bytes memory balancerPoolData
+        )
+    {
+        (v3PoolData, v2PoolData, v3TickData, camelotPoolData) =
+            getAllPoolData(v3Pools, v2Pools, camelotPools, withTicks, wordRadius);
+        if (balancerPools.length > 0) {
+            balancerPoolData = getBalancesBalancer(balancerPools);
+        }
+
+        return (v3PoolData, v2PoolData, v3TickData, camelotPoolData, balancerPoolData);
+    }
+
+    // Query the given pools as `getAllPoolDataWithBalancer` plus Curve stable pools
+    // @dev input and return data is tightly packed, see `getBalancesCurve`
+    function getAllPoolDataWithCurve(
+        bytes calldata v3Pools,
+        bytes calldata v2Pools,
+        bytes calldata camelotPools,
+        bytes calldata balancerPools,
+        bytes calldata curvePools,
+        bool withTicks,
+        uint8 wordRadius
+    )
+        public
+        view
+        returns (
+            bytes memory v3PoolData,
+            bytes memory v2PoolData,
+            bytes memory v3TickData,
+            bytes memory camelotPoolData,
+            bytes memory balancerPoolData,
+            bytes memory curvePoolData
+        )
+    {
+        (v3PoolData, v2PoolData, v3TickData, camelotPoolData, balancerPoolData) =
+            getAllPoolDataWithBalancer(v3Pools, v2Pools, camelotPools, balancerPools, withTicks, wordRadius);
+        if (curvePools.length > 0) {
+            curvePoolData = getBalancesCurve(curvePools);
+        }
+
+        return (v3PoolData, v2PoolData, v3TickData, camelotPoolData, balancerPoolData, curvePoolData);
+    }
+
+    // Query the given pools as `getAllPoolDataWithCurve` plus Solidly pairs
+    // @dev input and return data is tightly packed, see `getReservesSolidly`
+    function getAllPoolDataWithSolidly(
+        bytes calldata v3Pools,
+        bytes calldata v2Pools,
+        bytes calldata camelotPools,
+        bytes calldata balancerPools,
+        bytes calldata curvePools,
+        bytes calldata solidlyPools,
+        bool withTicks,
+        uint8 wordRadius
+    )
+        public
+        view
+        returns (
+            bytes memory v3PoolData,
+            bytes memory v2PoolData,
+            bytes memory v3TickData,
+            bytes memory camelotPoolData,
+            bytes memory balancerPoolData,
+            bytes memory curvePoolData,
+            bytes memory solidlyPoolData
+        )
+    {
+        (v3PoolData, v2PoolData, v3TickData, camelotPoolData, balancerPoolData, curvePoolData) = getAllPoolDataWithCurve(
+            v3Pools, v2Pools, camelotPools, balancerPools, curvePools, withTicks, wordRadius
+        );
+        if (solidlyPools.length > 0) {
+            solidlyPoolData = getReservesSolidly(solidlyPools);
+        }
+
+        return (v3PoolData, v2PoolData, v3TickData, camelotPoolData, balancerPoolData, curvePoolData, solidlyPoolData);
+    }
+
+    // Query the given pools as `getAllPoolDataWithSolidly` plus KyberSwap Elastic pools
+    // @dev input and return data is tightly packed, see `getStateKyberElastic`
+    function getAllPoolDataWithKyberElastic(
+        bytes calldata v3Pools,
+        bytes calldata v2Pools,
+        bytes calldata camelotPools,
+        bytes calldata balancerPools,
+        bytes calldata curvePools,
+        bytes calldata solidlyPools,
+        bytes calldata kyberElasticPools,
+        bool withTicks,
+        uint8 wordRadius
+    )
+        public
+        view
+        returns (
+            bytes memory v3PoolData,
+            bytes memory v2PoolData,
+            bytes memory v3TickData,
+            bytes memory camelotPoolData,
+            bytes memory balancerPoolData,
+            bytes memory curvePoolData,
+            bytes memory solidlyPoolData,
+            bytes memory kyberElasticPoolData
+        )
+    {
+        (v3PoolData, v2PoolData, v3TickData, camelotPoolData, balancerPoolData, curvePoolData, solidlyPoolData) =
+            getAllPoolDataWithSolidly(
+                v3Pools, v2Pools, camelotPools, balancerPools, curvePools, solidlyPools, withTicks, wordRadius
+            );
+        if (kyberElasticPools.length > 0) {
+            kyberElasticPoolData = getStateKyberElastic(kyberElasticPools);
+        }
+
+        return (
+            v3PoolData,
+            v2PoolData,
+            v3TickData,
+            camelotPoolData,
+            balancerPoolData,
+            curvePoolData,
+            solidlyPoolData,
+            kyberElasticPoolData
+        );
+    }
+
+    // Query the given UniswapV3 pools for initialized ticks within `wordRadius` tick bitmap words of the current tick
+    // @dev return data is tightly packed, per pool: tick count (uint16) then (tick (int24), liquidityNet (int128)) for each tick
+    function getTicksV3(bytes calldata pools, uint8 wordRadius) public view returns (bytes memory results) {
+        uint256 poolCount = pools.length / 20;
+        uint256 wordCount = 2 * uint256(wordRadius) + 1;
+        Buffer.buffer memory buf;
+        Buffer.init(buf, poolCount * 2);
+
+        for (uint256 i = 0; i < poolCount; ++i) {
+            IUniswapV3Pool pool = IUniswapV3Pool(bytesToAddress(pools[i * 20:(i + 1) * 20]));
+            int24 tickSpacing = pool.tickSpacing();
+            (, int24 tick,,,,,) = pool.slot0();
+            // as TickBitmap.position, rounding towards negative infinity
+            int24 compressed = tick / tickSpacing;
+            if (tick < 0 && tick % tickSpacing != 0) compressed--;
+            int256 firstWord = int256(compressed >> 8) - int256(uint256(wordRadius));
+
+            uint256[] memory bitmaps = new uint256[](wordCount);
+            uint256 tickCount;
+            for (uint256 w = 0; w < wordCount; ++w) {
+                uint256 bitmap = pool.tickBitmap(int16(firstWord + int256(w)));
+                bitmaps[w] = bitmap;
+                for (; bitmap != 0; bitmap &= bitmap - 1) {
+                    ++tickCount;
+                }
+            }
+
+            buf.appendInt(tickCount, 2);
+            for (uint256 w = 0; w < wordCount; ++w) {
+                if (bitmaps[w] == 0) continue;
+                for (uint256 bit = 0; bit < 256; ++bit) {
+                    if (bitmaps[w] & (1 << bit) == 0) continue;
+                    int24 initializedTick = int24(((firstWord + int256(w)) * 256 + int256(bit)) * tickSpacing);
+                    (, int128 liquidityNet,,,,,,) = pool.ticks(initializedTick);
+                    buf.appendInt(uint256(uint24(initializedTick)), 3);
+                    buf.appendBytes16(bytes16(uint128(liquidityNet)));
+                }
+            }
+        }
+
+        return buf.buf;
+    }
+
     // Query the given UniswapV2 style pools for reserves
     function getReservesV2(bytes calldata pools) public view returns (bytes memory results) {
         uint256 poolCount = pools.length / 20;
@@ -59,6 +338,113 @@ contract V3PoolViewer {
         return buf.buf;
     }
 
+    // Query the given Camelot pools for reserves, fees (per input token), and curve
+    // @dev return data is tightly packed, per pool: reserve0 (uint128), reserve1 (uint128), token0Fee (uint16), token1Fee (uint16), stable (uint8)
+    function getReservesCamelot(bytes calldata pools) public view returns (bytes memory results) {
+        uint256 poolCount = pools.length / 20;
+        Buffer.buffer memory buf;
+        Buffer.init(buf, poolCount * 37);
+
+        for (uint256 i = 0; i < poolCount; ++i) {
+            ICamelotPair pool = ICamelotPair(bytesToAddress(pools[i * 20:(i + 1) * 20]));
+            (uint112 reserve0, uint112 reserve1, uint16 token0Fee, uint16 token1Fee) = pool.getReserves();
+            buf.appendBytes16(bytes16(uint128(reserve0)));
+            buf.appendBytes16(bytes16(uint128(reserve1)));
+            buf.appendInt(token0Fee, 2);
+            buf.appendInt(token1Fee, 2);
+            buf.appendUint8(pool.stableSwap() ? 1 : 0);
+        }
+
+        return buf.buf;
+    }
+
+    // Query the given Solidly pairs for reserves and curve
+    // @dev input and return data is tightly packed, per pool: reserve0 (uint128), reserve1 (uint128), stable (bool)
+    function getReservesSolidly(bytes calldata pools) public view returns (bytes memory results) {
+        uint256 poolCount = pools.length / 20;
+        Buffer.buffer memory buf;
+        Buffer.init(buf, poolCount * 33);
+
+        for (uint256 i = 0; i < poolCount; ++i) {
+            ISolidlyPair pair = ISolidlyPair(bytesToAddress(pools[i * 20:(i + 1) * 20]));
+            (uint256 r0, uint256 r1,) = pair.getReserves();
+            buf.appendBytes16(bytes16(uint128(r0)));
+            buf.appendBytes16(bytes16(uint128(r1)));
+            buf.appendUint8(pair.stable() ? 1 : 0);
+        }
+
+        return buf.buf;
+    }
+
+    // Query the given KyberSwap Elastic pools for price and liquidity fields
+    // @dev input and return data is tightly packed, per pool: sqrtP (uint160), baseL (uint128), reinvestL (uint128)
+    function getStateKyberElastic(bytes calldata pools) public view returns (bytes memory results) {
+        uint256 poolCount = pools.length / 20;
+        Buffer.buffer memory buf;
+        Buffer.init(buf, poolCount * 52);
+
+        for (uint256 i = 0; i < poolCount; ++i) {
+            IKyberElasticPool pool = IKyberElasticPool(bytesToAddress(pools[i * 20:(i + 1) * 20]));
+            (uint160 sqrtP,,,) = pool.getPoolState();
+            (uint128 baseL, uint128 reinvestL,) = pool.getLiquidityState();
+            buf.appendBytes20(bytes20(sqrtP));
+            buf.appendBytes16(bytes16(baseL));
+            buf.appendBytes16(bytes16(reinvestL));
+        }
+
+        return buf.buf;
+    }
+
+    // Query the given Balancer weighted pools for the balances, weights, and swap fee of a token pair
+    // @dev input is tightly packed, per pool: pool (address), token0 (address), token1 (address)
+    // return data is tightly packed, per pool: balance0 (uint128), balance1 (uint128), weight0 (uint64), weight1 (uint64), swapFee (uint64)
+    function getBalancesBalancer(bytes calldata pools) public view returns (bytes memory results) {
+        uint256 poolCount = pools.length / 60;
+        Buffer.buffer memory buf;
+        Buffer.init(buf, poolCount * 56);
+
+        for (uint256 i = 0; i < poolCount; ++i) {
+            uint256 offset = i * 60;
+            IBalancerWeightedPool pool = IBalancerWeightedPool(bytesToAddress(pools[offset:offset + 20]));
+            address token0 = bytesToAddress(pools[offset + 20:offset + 40]);
+            address token1 = bytesToAddress(pools[offset + 40:offset + 60]);
+            (address[] memory tokens, uint256[] memory balances,) =
+                IBalancerVault(pool.getVault()).getPoolTokens(pool.getPoolId());
+            uint256[] memory weights = pool.getNormalizedWeights();
+            uint256 index0 = tokenIndex(tokens, token0);
+            uint256 index1 = tokenIndex(tokens, token1);
+            buf.appendBytes16(bytes16(uint128(balances[index0])));
+            buf.appendBytes16(bytes16(uint128(balances[index1])));
+            buf.appendInt(weights[index0], 8);
+            buf.appendInt(weights[index1], 8);
+            buf.appendInt(pool.getSwapFeePercentage(), 8);
+        }
+
+        return buf.buf;
+    }
+
+    // Query the given Curve stable pools for the balances, amplification coefficient, and fee of a token pair
+    // @dev input is tightly packed, per pool: pool (address), token0 (address), token1 (address)
+    // return data is tightly packed, per pool: balance0 (uint128), balance1 (uint128), A (uint32), fee (uint32)
+    function getBalancesCurve(bytes calldata pools) public view returns (bytes memory results) {
+        uint256 poolCount = pools.length / 60;
+        Buffer.buffer memory buf;
+        Buffer.init(buf, poolCount * 40);
+
+        for (uint256 i = 0; i < poolCount; ++i) {
+            uint256 offset = i * 60;
+            ICurvePool pool = ICurvePool(bytesToAddress(pools[offset:offset + 20]));
+            uint256 index0 = coinIndex(pool, bytesToAddress(pools[offset + 20:offset + 40]));
+            uint256 index1 = coinIndex(pool, bytesToAddress(pools[offset + 40:offset + 60]));
+            buf.appendBytes16(bytes16(uint128(pool.balances(index0))));
+            buf.appendBytes16(bytes16(uint128(pool.balances(index1))));
+            buf.appendInt(pool.A(), 4);
+            buf.appendInt(pool.fee(), 4);
+        }
+
+        return buf.buf;
+    }
+
     // Query the given UniswapV3 pools for price and liquidity fields
     // @dev input and return data is tightly packed
     function getPriceAndLiquidityV3(bytes calldata pools) public view returns (bytes memory results) {
@@ -84,6 +470,25 @@ contract V3PoolViewer {
         return (sqrtPX96, liquidity);
     }
 
+    function tokenIndex(address[] memory tokens, address token) private pure returns (uint256) {
+        for (uint256 i = 0; i < tokens.length; ++i) {
+            if (tokens[i] == token) return i;
+        }
+        revert("token not in pool");
+    }
+
+    function coinIndex(ICurvePool pool, address token) private view returns (uint256) {
+        // pools revert reading past their last coin
+        for (uint256 i = 0; i < 8; ++i) {
+            try pool.coins(i) returns (address coin) {
+                if (coin == token) return i;
+            } catch {
+                break;
+            }
+        }
+        revert("token not in pool");
+    }
+
     function bytesToAddress(bytes calldata data) private pure returns (address addr) {
         bytes memory b = data;
         assembly {
@@ -31,6 +31,39 @@ contract TradeExecutorTest is Test {
         }
     }
 
+    function testDecodeExt() public {
+        // kinds [concentrated liquidity, constant product, solidly stable], min. amount out 625625 << 4
+        uint128 payload = 0x1262f65200000bb801f4ff0201000101;
+        (uint8[3] memory kinds, uint256 minAmountOut) = executor.decodeExt(payload);
+
+        uint8[3] memory expectedKinds = [2, 0, 1];
+        for (uint8 i = 0; i < 3; i++) {
+            assertEq(uint256(expectedKinds[i]), uint256(kinds[i]));
+        }
+        assertEq(minAmountOut, 10_010_000);
+        // the extensions don't change the trade data
+        (uint8[3] memory exchanges,,) = executor.decode(payload);
+        assertEq(uint256(exchanges[0]), 1);
+    }
+
+    function testDecodePath() public {
+        // uniswap (usdc, weth) -> sushi (weth, arb) -> camelot (arb, usdt) -> uniswap (usdt, usdc)
+        uint256 payload = 0x00640000000004010000030201f401000004;
+        (uint8[] memory exchanges, uint8[] memory tokens, uint16[] memory fees) = executor.decodePath(payload);
+
+        assertEq(exchanges.length, 4);
+        assertEq(tokens.length, 5);
+        uint8[4] memory expectedExchanges = [0, 2, 1, 0];
+        uint8[5] memory expectedTokens = [0, 1, 3, 4, 0];
+        uint16[4] memory expectedFees = [500, 0, 0, 100];
+        for (uint8 i = 0; i < 4; i++) {
+            assertEq(uint256(expectedExchanges[i]), uint256(exchanges[i]));
+            assertEq(uint256(expectedTokens[i + 1]), uint256(tokens[i + 1]));
+            assertEq(uint256(expectedFees[i]), uint256(fees[i]));
+        }
+        assertEq(uint256(expectedTokens[0]), uint256(tokens[0]));
+    }
+
     // 384,414 gas
     function testSwap2Step() public {
         // TODO: add encode side in solidity
@@ -79,7 +112,19 @@ contract TradeExecutorTest is Test {
         );
         vm.prank(payee);
 
-        executor.flashSwap(amountIn, payload);
+        executor.flashSwap(amountIn, payload, 0);
+    }
+
+    function testFlashSwapSlippage() public {
+        // univ3 usdc/weth 500 <> univ3 weth/usdc 3000
+        uint128 payload = 0x00000bb801f4ff0100000000;
+        uint128 amountIn = 10000 * 1e6;
+
+        // the trade can't return the min. amount out
+        vm.expectRevert();
+        vm.prank(payee);
+
+        executor.flashSwap(amountIn, payload, type(uint128).max);
     }
 
     // 235,690 gas
@@ -93,7 +138,7 @@ contract TradeExecutorTest is Test {
         );
         vm.prank(payee);
 
-        executor.flashSwap(amountIn, payload);
+        executor.flashSwap(amountIn, payload, 0);
     }
 
     // 348,667 gas
@@ -107,7 +152,7 @@ contract TradeExecutorTest is Test {
         );
         vm.prank(payee);
 
-        executor.flashSwap(amountIn, payload);
+        executor.flashSwap(amountIn, payload, 0);
     }
 
     // gas for crossing uni v3 price ticks
@@ -121,7 +166,7 @@ contract TradeExecutorTest is Test {
         );
         vm.prank(payee);
 
-        executor.flashSwap(amountIn, payload);
+        executor.flashSwap(amountIn, payload, 0);
     }
 
     function testFlashSwapEntryAtV2Dex() public {
@@ -134,7 +179,7 @@ contract TradeExecutorTest is Test {
         );
         vm.prank(payee);
 
-        executor.flashSwap(amountIn, payload);
+        executor.flashSwap(amountIn, payload, 0);
     }
 
     function testFlashSwapEntryAtV2DexOneForZero() public {
@@ -147,7 +192,7 @@ contract TradeExecutorTest is Test {
         );
         vm.prank(payee);
 
-        executor.flashSwap(amountIn, payload);
+        executor.flashSwap(amountIn, payload, 0);
     }
 
     // 348,667 gas
@@ -161,7 +206,7 @@ contract TradeExecutorTest is Test {
         );
         vm.prank(payee);
 
-        executor.flashSwap(amountIn, payload);
+        executor.flashSwap(amountIn, payload, 0);
     }
 
     function testWithdrawToken() public {
@@ -204,13 +249,13 @@ contract TradeExecutorTest is Test {
         uint128 amountIn = uint128(0x0000000000000000000000000000000000000000000000000de0b6b3a7640000);
         uint128 payload = uint128(0x0000000000000000000000000000000000000000000000b401f4ff0401000300);
         vm.prank(payee);
-        executor.flashSwap(amountIn, payload);
+        executor.flashSwap(amountIn, payload, 0);
     }
 
     function testChronosDex2() public {
         uint128 amountIn = uint128(0x0000000000000000000000000000000000000000000000000de0b6b3a7640000);
         uint128 payload = uint128(0x000000000000000000000000000000000000000000b4006401f4000401030000);
         vm.prank(payee);
-        executor.flashSwap(amountIn, payload);
+        executor.flashSwap(amountIn, payload, 0);
     }
 }
\ No newline at end of file
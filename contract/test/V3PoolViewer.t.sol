@@ -7,6 +7,14 @@ import "../lib/forge-std/src/console.sol";
 
 import "../src/V3PoolViewer.sol";
 
+interface ISolidlyFactory {
+    function getPair(address tokenA, address tokenB, bool stable) external view returns (address);
+}
+
+interface IKyberElasticFactory {
+    function getPool(address tokenA, address tokenB, uint24 swapFeeUnits) external view returns (address);
+}
+
 contract ExecutorTest is Test {
     V3PoolViewer public viewer;
 
@@ -56,6 +64,167 @@ contract ExecutorTest is Test {
         this.assertDecodedPoolDataV2(v2PoolData, reserves0, reserves1);
     }
 
+    function testViewerTicks() public {
+        viewer = new V3PoolViewer();
+
+        address[] memory v3Addresses = new address[](1);
+        v3Addresses[0] = 0xC31E54c7a869B9FcBEcc14363CF510d1c41fa443;
+        bytes memory v3Pools = packAddresses(v3Addresses);
+
+        (,, bytes memory v3TickData) = viewer.getPoolDataWithTicks(v3Pools, new bytes(0), 1);
+
+        this.assertDecodedTicksV3(v3TickData, IUniswapV3Pool(v3Addresses[0]));
+    }
+
+    function testViewerCamelot() public {
+        viewer = new V3PoolViewer();
+
+        address[] memory camelotAddresses = new address[](1);
+        camelotAddresses[0] = 0x84652bb2539513BAf36e225c930Fdd8eaa63CE27;
+        bytes memory camelotPools = packAddresses(camelotAddresses);
+
+        (,,, bytes memory camelotPoolData) =
+            viewer.getAllPoolData(new bytes(0), new bytes(0), camelotPools, false, 0);
+
+        this.assertDecodedPoolDataCamelot(camelotPoolData, ICamelotPair(camelotAddresses[0]));
+    }
+
+    function testViewerBalancer() public {
+        viewer = new V3PoolViewer();
+
+        // 33WETH-33WBTC-33USDC weighted pool
+        IBalancerWeightedPool pool = IBalancerWeightedPool(0x64541216bAFFFEec8ea535BB71Fbc927831d0595);
+        address weth = 0x82aF49447D8a07e3bd95BD0d56f35241523fBab1;
+        address usdc = 0xFF970A61A04b1cA14834A43f5dE4533eBDDB5CC8;
+        bytes memory balancerPools = abi.encodePacked(address(pool), weth, usdc);
+
+        (,,,, bytes memory balancerPoolData) =
+            viewer.getAllPoolDataWithBalancer(new bytes(0), new bytes(0), new bytes(0), balancerPools, false, 0);
+
+        this.assertDecodedPoolDataBalancer(balancerPoolData, pool, weth, usdc);
+    }
+
+    function assertDecodedPoolDataBalancer(bytes calldata data, IBalancerWeightedPool pool, address token0, address token1)
+        public
+        view
+    {
+        (address[] memory tokens, uint256[] memory balances,) =
+            IBalancerVault(pool.getVault()).getPoolTokens(pool.getPoolId());
+        uint256[] memory weights = pool.getNormalizedWeights();
+        assert(data.length == 56);
+        for (uint256 i; i < tokens.length; i++) {
+            if (tokens[i] == token0) {
+                assert(uint128(bytes16(data[0:16])) == balances[i]);
+                assert(uint64(bytes8(data[32:40])) == weights[i]);
+            } else if (tokens[i] == token1) {
+                assert(uint128(bytes16(data[16:32])) == balances[i]);
+                assert(uint64(bytes8(data[40:48])) == weights[i]);
+            }
+        }
+        assert(uint64(bytes8(data[48:56])) == pool.getSwapFeePercentage());
+    }
+
+    function testViewerCurve() public {
+        viewer = new V3PoolViewer();
+
+        // USDC.e/USDT 2pool
+        ICurvePool pool = ICurvePool(0x7f90122BF0700F9E7e1F688fe926940E8839F353);
+        address usdc = 0xFF970A61A04b1cA14834A43f5dE4533eBDDB5CC8;
+        address usdt = 0xFd086bC7CD5C481DCC9C85ebE478A1C0b69FCbb9;
+        // token order differs from coin order
+        bytes memory curvePools = abi.encodePacked(address(pool), usdt, usdc);
+
+        (,,,,, bytes memory curvePoolData) = viewer.getAllPoolDataWithCurve(
+            new bytes(0), new bytes(0), new bytes(0), new bytes(0), curvePools, false, 0
+        );
+
+        this.assertDecodedPoolDataCurve(curvePoolData, pool);
+    }
+
+    function assertDecodedPoolDataCurve(bytes calldata data, ICurvePool pool) public view {
+        assert(data.length == 40);
+        assert(uint128(bytes16(data[0:16])) == pool.balances(1));
+        assert(uint128(bytes16(data[16:32])) == pool.balances(0));
+        assert(uint32(bytes4(data[32:36])) == pool.A());
+        assert(uint32(bytes4(data[36:40])) == pool.fee());
+    }
+
+    function testViewerSolidly() public {
+        viewer = new V3PoolViewer();
+
+        // Ramses WETH/USDC volatile pair
+        address weth = 0x82aF49447D8a07e3bd95BD0d56f35241523fBab1;
+        address usdc = 0xFF970A61A04b1cA14834A43f5dE4533eBDDB5CC8;
+        ISolidlyPair pair = ISolidlyPair(ISolidlyFactory(0xAAA20D08e59F6561f242b08513D36266C5A29415).getPair(weth, usdc, false));
+        bytes memory solidlyPools = abi.encodePacked(address(pair));
+
+        (,,,,,, bytes memory solidlyPoolData) = viewer.getAllPoolDataWithSolidly(
+            new bytes(0), new bytes(0), new bytes(0), new bytes(0), new bytes(0), solidlyPools, false, 0
+        );
+
+        this.assertDecodedPoolDataSolidly(solidlyPoolData, pair);
+    }
+
+    function testViewerKyberElastic() public {
+        viewer = new V3PoolViewer();
+
+        // KyberSwap Elastic WETH/USDC 0.04% pool
+        address weth = 0x82aF49447D8a07e3bd95BD0d56f35241523fBab1;
+        address usdc = 0xFF970A61A04b1cA14834A43f5dE4533eBDDB5CC8;
+        IKyberElasticPool pool =
+            IKyberElasticPool(IKyberElasticFactory(0x5F1dddbf348aC2fbe22a163e30F99F9ECE3DD50a).getPool(weth, usdc, 40));
+        bytes memory kyberElasticPools = abi.encodePacked(address(pool));
+
+        (,,,,,,, bytes memory kyberElasticPoolData) = viewer.getAllPoolDataWithKyberElastic(
+            new bytes(0), new bytes(0), new bytes(0), new bytes(0), new bytes(0), new bytes(0), kyberElasticPools, false, 0
+        );
+
+        this.assertDecodedPoolDataKyberElastic(kyberElasticPoolData, pool);
+    }
+
+    function assertDecodedPoolDataKyberElastic(bytes calldata data, IKyberElasticPool pool) public view {
+        (uint160 sqrtP,,,) = pool.getPoolState();
+        (uint128 baseL, uint128 reinvestL,) = pool.getLiquidityState();
+        assert(data.length == 52);
+        assert(uint160(bytes20(data[0:20])) == sqrtP);
+        assert(uint128(bytes16(data[20:36])) == baseL);
+        assert(uint128(bytes16(data[36:52])) == reinvestL);
+    }
+
+    function assertDecodedPoolDataSolidly(bytes calldata data, ISolidlyPair pair) public view {
+        (uint256 r0, uint256 r1,) = pair.getReserves();
+        assert(data.length == 33);
+        assert(uint128(bytes16(data[0:16])) == r0);
+        assert(uint128(bytes16(data[16:32])) == r1);
+        assert((uint8(data[32]) == 1) == pair.stable());
+    }
+
+    function assertDecodedPoolDataCamelot(bytes calldata data, ICamelotPair pool) public view {
+        (uint112 r0, uint112 r1, uint16 fee0, uint16 fee1) = pool.getReserves();
+        assert(data.length == 37);
+        assert(uint128(bytes16(data[0:16])) == r0);
+        assert(uint128(bytes16(data[16:32])) == r1);
+        assert(uint16(bytes2(data[32:34])) == fee0);
+        assert(uint16(bytes2(data[34:36])) == fee1);
+        assert((uint8(data[36]) == 1) == pool.stableSwap());
+    }
+
+    function assertDecodedTicksV3(bytes calldata data, IUniswapV3Pool pool) public view {
+        uint256 tickCount = uint16(bytes2(data[0:2]));
+        assert(tickCount > 0);
+        assert(data.length == 2 + tickCount * 19);
+        int24 tickSpacing = pool.tickSpacing();
+        for(uint i; i < tickCount; i++) {
+            uint offset = 2 + i * 19;
+            int24 tick = int24(uint24(bytes3(data[offset: offset + 3])));
+            int128 liquidityNet = int128(uint128(bytes16(data[offset + 3: offset + 19])));
+            (, int128 expectedLiquidityNet,,,,,, bool initialized) = pool.ticks(tick);
+            assert(tick % tickSpacing == 0);
+            assert(initialized);
+            assert(liquidityNet == expectedLiquidityNet);
+        }
+    }
+
     // https://ethereum.stackexchange.com/questions/103437/converting-bytes-memory-to-bytes-calldata
     function assertDecodedPoolDataV3(bytes calldata data, uint160[2] calldata price, uint128[2] calldata liquidity) public pure {
         for(uint i; i < 2; i++) {
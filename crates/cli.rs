@@ -1,7 +1,7 @@
 //! Terminal cli stuff
 use argh::FromArgs;
 use ethers_middleware::core::types::Chain;
-use fulcrum_engine::types::Address;
+use fulcrum_engine::{types::Address, ShadowMode};
 
 #[derive(FromArgs)]
 /// Low latency arbitrage engine
@@ -9,11 +9,23 @@ pub struct FulcrumCli {
     #[argh(subcommand)]
     pub sub_command: SubCommand,
     #[argh(option)]
-    /// websocket connection string
-    pub ws: String,
+    /// JSON-RPC endpoint, websocket ('ws(s)://') or HTTP ('http(s)://') for endpoints without ws support
+    pub ws: Option<String>,
+    #[argh(option)]
+    /// IPC socket path of a colocated node e.g. /path/geth.ipc, used instead of --ws
+    pub ipc: Option<String>,
+    #[argh(option, default = "10")]
+    /// ws keepalive ping interval (seconds), the connection is re-established if a ping goes unanswered. 0 disables pings
+    pub ws_keepalive: u64,
     #[argh(option, from_str_fn(parse_chain))]
     /// network/chain to connect with
     pub chain: Chain,
+    #[argh(option)]
+    /// trading universe config (TOML) path, defaults to the bundled arbitrum one config
+    pub config: Option<String>,
+    #[argh(switch)]
+    /// discover the pools of every configured token pair from the exchange factories (known for decoding trades)
+    pub discover_pools: bool,
 }
 
 #[derive(FromArgs)]
@@ -21,6 +33,10 @@ pub struct FulcrumCli {
 pub enum SubCommand {
     Run(RunCommand),
     Prices(PricesCommand),
+    Ledger(LedgerCommand),
+    Decode(DecodeCommand),
+    Tap(TapCommand),
+    Capture(CaptureCommand),
 }
 
 #[derive(FromArgs)]
@@ -32,6 +48,54 @@ pub struct PricesCommand {
     pub at: u64,
 }
 
+#[derive(FromArgs)]
+#[argh(subcommand, name = "ledger")]
+/// Report the per-trade and cumulative PnL of a trade ledger
+pub struct LedgerCommand {
+    #[argh(positional)]
+    /// trade ledger path, as written by `run --ledger`
+    pub path: String,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "decode")]
+/// Decode offline payloads and their trades, printed as JSON lines
+pub struct DecodeCommand {
+    #[argh(option)]
+    /// file of raw sequencer feed JSON messages, one per line
+    pub file: Option<String>,
+    #[argh(option, from_str_fn(parse_hex))]
+    /// hex encoded signed tx e.g. from 'eth_getRawTransactionByHash'
+    pub hex: Option<Vec<u8>>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "tap")]
+/// Stream matching txs from the live sequencer feed and their trades, printed as JSON lines
+pub struct TapCommand {
+    #[argh(option, from_str_fn(parse_address))]
+    /// only txs calling this address (repeatable)
+    pub to: Vec<Address>,
+    #[argh(option, from_str_fn(parse_selector))]
+    /// only txs calling this 4-byte method selector e.g. 'b858183f' (repeatable)
+    pub selector: Vec<[u8; 4]>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "capture")]
+/// Export matching txs from the live sequencer feed with their block numbers and timestamps, until ctrl-c
+pub struct CaptureCommand {
+    #[argh(positional)]
+    /// output path, Parquet if it ends '.parquet' (requires the 'parquet' feature) otherwise CSV
+    pub path: String,
+    #[argh(option, from_str_fn(parse_address))]
+    /// only txs calling this address (repeatable)
+    pub to: Vec<Address>,
+    #[argh(option, from_str_fn(parse_selector))]
+    /// only txs calling this 4-byte method selector e.g. 'b858183f' (repeatable)
+    pub selector: Vec<[u8; 4]>,
+}
+
 #[derive(FromArgs)]
 #[argh(subcommand, name = "run")]
 /// Run the fulcrum trade engine
@@ -40,7 +104,7 @@ pub struct RunCommand {
     /// the private key for tx execution account
     pub key: Option<String>,
     #[argh(option, from_str_fn(parse_min_profit))]
-    /// minimum profit required for trade execution
+    /// minimum profit required for trade execution, after gas costs
     pub min_profit: f64,
     #[argh(switch)]
     /// activate listen only mode
@@ -48,6 +112,91 @@ pub struct RunCommand {
     #[argh(option, from_str_fn(parse_address))]
     /// deployed executor contract address
     pub executor: Address,
+    #[argh(option)]
+    /// trade decision event sink: 'log' (default), 'stdout', or a file path for JSON lines
+    pub events: Option<String>,
+    #[argh(option)]
+    /// record submitted orders and their realized PnL to this trade ledger file (JSON lines)
+    pub ledger: Option<String>,
+    #[argh(option, from_str_fn(parse_bps))]
+    /// tolerated shortfall of a trade's output vs. its simulated output in basis points (default: 10)
+    pub slippage_bps: Option<u32>,
+    #[argh(option, from_str_fn(parse_bps))]
+    /// skip batches with a trade moving a pool's price more than this in basis points (default: 2000)
+    pub max_price_impact_bps: Option<u32>,
+    #[argh(option)]
+    /// drop trades waiting on in-flight orders for longer than this many ms (default: 500)
+    pub order_max_age_ms: Option<u64>,
+    #[argh(option)]
+    /// pre-sign order txs of the last n orders between blocks, an order repeating one is submitted without signing
+    pub presign: Option<usize>,
+    #[argh(option)]
+    /// verify order txs with eth_call before sending, skipping reverting orders, if within this many ms of receiving the feed message
+    pub preflight_ms: Option<u64>,
+    #[argh(option, from_str_fn(parse_shadow))]
+    /// record simulated fills of dry-run orders to the ledger: 'graph' (price graph output) or 'estimate' (unless eth_estimateGas reverts)
+    pub shadow: Option<ShadowMode>,
+    #[argh(option)]
+    /// pause order submission after this many reverted order txs in a row, resume with SIGUSR1
+    pub max_reverts: Option<u32>,
+    #[argh(option)]
+    /// pause order submission once order txs have lost this much (wei) after gas, resume with SIGUSR1
+    pub loss_budget: Option<u128>,
+    #[argh(option)]
+    /// secondary JSON-RPC endpoint queried for prices alongside the primary, the first response is used (repeatable)
+    pub price_ws: Vec<String>,
+    #[argh(option)]
+    /// save the price graph to this file on shutdown (ctrl-c) and price the first batch with it on restart, if fresh
+    pub warm_start: Option<String>,
+    #[argh(option)]
+    /// sync initialized uniswap v3 ticks within this many tick bitmap words of the current price
+    pub tick_words: Option<u8>,
+    #[argh(option, from_str_fn(parse_min_pool_tvl))]
+    /// drop synced pools with less value locked than this (in ETH) at startup
+    pub min_pool_tvl: Option<u128>,
+    #[argh(option)]
+    /// L1 JSON-RPC (ws) endpoint, emits events for messages delivered to the L1 delayed inbox before they are sequenced
+    pub l1_ws: Option<String>,
+    #[argh(switch)]
+    /// watch the JSON-RPC node's heads for reorgs, resyncing prices on one as on feed resequencing
+    pub watch_reorgs: bool,
+    #[argh(option)]
+    /// skip this many feed blocks after a reorg or feed resequencing, submitting no orders (default: 0)
+    pub reorg_pause: Option<u64>,
+    #[argh(option)]
+    /// compare the feed's block numbers to the JSON-RPC node's every this many seconds, emitting drifts
+    pub block_check: Option<u64>,
+    #[argh(switch)]
+    /// with --block-check, shift the feed's block numbers by a drift found on consecutive checks
+    pub calibrate_blocks: bool,
+    #[argh(option)]
+    /// once the feed delivers no messages for this many ms, advance prices from the JSON-RPC node's block receipts until it recovers
+    pub receipt_fallback: Option<u64>,
+    #[argh(option)]
+    /// audit simulated pool states against the JSON-RPC node's swap logs, counting divergences beyond this many bps
+    pub audit_bps: Option<f64>,
+    #[argh(option)]
+    /// count calls of router methods without a decoder, logging the top 10 with sample calldata hashes every this many seconds
+    pub selector_stats: Option<u64>,
+    #[argh(option)]
+    /// serve price quotes from the engine's live price graph at this address e.g. 127.0.0.1:9101, `GET /quote`
+    pub quote_addr: Option<std::net::SocketAddr>,
+    #[argh(option)]
+    /// serve a status page (feed and price sync state, recent arbs, ledger PnL) at this address e.g. 127.0.0.1:9102, `GET /`
+    pub status_addr: Option<std::net::SocketAddr>,
+    #[argh(option)]
+    /// serve runtime control commands (min. profit, dry-run, disabled paths) on a Unix socket at this path
+    pub control_socket: Option<String>,
+    #[argh(option)]
+    /// apply the control commands in this file at start and again on SIGHUP
+    pub control_file: Option<String>,
+    #[argh(option)]
+    /// save the order nonce state to this file on shutdown (ctrl-c), on restart warn of order txs which may be pending
+    pub nonce_state: Option<String>,
+    #[cfg(feature = "metrics")]
+    #[argh(option)]
+    /// serve prometheus metrics at this address e.g. 127.0.0.1:9100
+    pub metrics: Option<std::net::SocketAddr>,
 }
 
 fn parse_block_number(s: &str) -> Result<u64, String> {
@@ -68,6 +217,20 @@ fn parse_address(raw_address: &str) -> Result<Address, String> {
     Ok(Address::from(dst))
 }
 
+fn parse_hex(raw_hex: &str) -> Result<Vec<u8>, String> {
+    let raw_hex = raw_hex.strip_prefix("0x").unwrap_or(raw_hex);
+    let mut dst = vec![0_u8; raw_hex.len() / 2];
+    faster_hex::hex_decode(raw_hex.as_bytes(), &mut dst).map_err(|_| "valid hex".to_string())?;
+
+    Ok(dst)
+}
+
+fn parse_selector(raw_selector: &str) -> Result<[u8; 4], String> {
+    parse_hex(raw_selector)?
+        .try_into()
+        .map_err(|_| "4-byte selector".to_string())
+}
+
 fn parse_min_profit(raw_min_profit: &str) -> Result<f64, String> {
     let min_profit = raw_min_profit.parse::<f64>().expect("it is a float");
     if min_profit > 1.0 {
@@ -77,6 +240,33 @@ fn parse_min_profit(raw_min_profit: &str) -> Result<f64, String> {
     Ok(min_profit)
 }
 
+/// Parse an ETH amount to wei
+fn parse_min_pool_tvl(raw_min_pool_tvl: &str) -> Result<u128, String> {
+    match raw_min_pool_tvl.parse::<f64>() {
+        Ok(eth) if eth >= 0.0 => Ok((eth * 1e18) as u128),
+        _ => Err("valid ETH amount".to_string()),
+    }
+}
+
+fn parse_shadow(raw_mode: &str) -> Result<ShadowMode, String> {
+    match raw_mode {
+        "graph" => Ok(ShadowMode::PriceGraph),
+        "estimate" => Ok(ShadowMode::EstimateGas),
+        _ => Err("use 'graph' or 'estimate'".to_string()),
+    }
+}
+
+fn parse_bps(raw_bps: &str) -> Result<u32, String> {
+    let bps = raw_bps
+        .parse::<u32>()
+        .map_err(|_| "valid basis points".to_string())?;
+    if bps > 10_000 {
+        return Err("use a value <= 10000".to_string());
+    }
+
+    Ok(bps)
+}
+
 fn parse_chain(raw_chain: &str) -> Result<Chain, String> {
     match raw_chain.to_lowercase().as_str() {
         "optimisim" => Ok(Chain::Optimism),
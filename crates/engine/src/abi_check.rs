@@ -0,0 +1,185 @@
+//! ABI offset validation
+//!
+//! `DecodeStatic` follows a dynamic field's head offset wherever it points, an offset into the head or past the
+//! buffer decodes some other field's words as the value e.g. a path of garbage. Each struct decoded from calldata
+//! declares its head layout, builds with the `strict-decode` feature check the offsets of its dynamic fields before
+//! decoding: in bounds, past the head, and ascending in field order as encoders lay out tails
+use ethabi_static::Tuple;
+
+/// Bytes of an ABI word
+const WORD: usize = 32;
+
+/// The tail of a dynamic field
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tail {
+    /// `bytes`, a length word and the bytes
+    Bytes,
+    /// `T[]`, a length word and a word (or more) per element
+    Array,
+    /// A tuple with dynamic fields, its head starts at the offset
+    Tuple,
+}
+
+/// A dynamic field offset which can't point at the field's encoding
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OffsetError {
+    /// The buffer is shorter than the head (`len` bytes)
+    ShortHead { len: usize },
+    /// The offset in head `word` points into the head or not past the prior dynamic field's offset
+    OutOfOrder { word: usize, offset: usize },
+    /// The tail of head `word` overruns the buffer
+    OutOfBounds { word: usize },
+}
+
+/// The ABI head layout of a struct decoded from calldata
+///
+/// Only the declared fields are described, trailing fields the struct doesn't decode are unchecked
+pub trait AbiLayout {
+    /// Head words of the declared fields
+    const HEAD: usize;
+    /// Head word and tail of each declared dynamic field, in field order
+    const TAILS: &'static [(usize, Tail)];
+
+    /// Check the dynamic field offsets of the struct encoded in `buf`
+    fn check_offsets(buf: &[u8]) -> Result<(), OffsetError> {
+        check_head(buf, Self::HEAD, Self::TAILS)
+    }
+}
+
+/// A dynamic tuple is an offset to its head, offsets within it are relative to its head
+impl<T: AbiLayout> AbiLayout for Tuple<T> {
+    const HEAD: usize = 1;
+    const TAILS: &'static [(usize, Tail)] = &[(0, Tail::Tuple)];
+
+    fn check_offsets(buf: &[u8]) -> Result<(), OffsetError> {
+        check_head(buf, Self::HEAD, Self::TAILS)?;
+        let offset = read_word(buf, 0).ok_or(OffsetError::OutOfBounds { word: 0 })?;
+        T::check_offsets(&buf[offset..])
+    }
+}
+
+/// Declare `AbiLayout`s, `Struct: head words, [head word: Tail, ...];`
+macro_rules! abi_layout {
+    ($($ty:ty: $head:literal, [$($word:literal: $tail:ident),* $(,)?];)*) => {
+        $(
+            impl $crate::abi_check::AbiLayout for $ty {
+                const HEAD: usize = $head;
+                const TAILS: &'static [(usize, $crate::abi_check::Tail)] =
+                    &[$(($word, $crate::abi_check::Tail::$tail)),*];
+            }
+        )*
+    };
+}
+pub(crate) use abi_layout;
+
+/// The `idx`th word of `buf` as a length or offset, `None` if it's out of `buf` or too large to be either
+fn read_word(buf: &[u8], idx: usize) -> Option<usize> {
+    let word = buf.get(idx * WORD..(idx + 1) * WORD)?;
+    if word[..24].iter().any(|b| *b != 0) {
+        return None;
+    }
+    usize::try_from(u64::from_be_bytes(word[24..].try_into().unwrap())).ok()
+}
+
+/// Check the `tails` offsets of a `head` word struct encoded in `buf`
+fn check_head(buf: &[u8], head: usize, tails: &[(usize, Tail)]) -> Result<(), OffsetError> {
+    if buf.len() < head * WORD {
+        return Err(OffsetError::ShortHead { len: buf.len() });
+    }
+    // tails start after the head, each after the last
+    let mut min_offset = head * WORD;
+    for &(word, tail) in tails {
+        let offset = read_word(buf, word).ok_or(OffsetError::OutOfBounds { word })?;
+        if offset < min_offset {
+            return Err(OffsetError::OutOfOrder { word, offset });
+        }
+        let tail_len = match tail {
+            Tail::Bytes => {
+                read_word(&buf[offset.min(buf.len())..], 0).and_then(|len| len.checked_add(WORD))
+            }
+            Tail::Array => read_word(&buf[offset.min(buf.len())..], 0)
+                .and_then(|len| len.checked_add(1)?.checked_mul(WORD)),
+            Tail::Tuple => Some(WORD),
+        };
+        match tail_len.and_then(|tail_len| offset.checked_add(tail_len)) {
+            Some(end) if end <= buf.len() => {}
+            _ => return Err(OffsetError::OutOfBounds { word }),
+        }
+        min_offset = offset + WORD;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn word(value: usize) -> [u8; 32] {
+        let mut word = [0_u8; 32];
+        word[24..].copy_from_slice(&(value as u64).to_be_bytes());
+        word
+    }
+
+    #[test]
+    fn checks_offsets() {
+        // (uint256, bytes, uint256[])
+        let tails = [(1, Tail::Bytes), (2, Tail::Array)];
+        let encoded = [
+            word(7),
+            word(0x60),
+            word(0xa0),
+            // 3 bytes
+            word(3),
+            [0xaa; 32],
+            // 2 elements
+            word(2),
+            word(1),
+            word(2),
+        ]
+        .concat();
+        assert_eq!(check_head(&encoded, 3, &tails), Ok(()));
+        assert_eq!(
+            check_head(&encoded[..64], 3, &tails),
+            Err(OffsetError::ShortHead { len: 64 })
+        );
+        // an array element is missing
+        assert_eq!(
+            check_head(&encoded[..encoded.len() - 32], 3, &tails),
+            Err(OffsetError::OutOfBounds { word: 2 })
+        );
+
+        let with_offsets = |bytes: usize, array: usize| {
+            let mut buf = encoded.clone();
+            buf[32..64].copy_from_slice(&word(bytes));
+            buf[64..96].copy_from_slice(&word(array));
+            buf
+        };
+        // the bytes length reads a head word
+        assert_eq!(
+            check_head(&with_offsets(0x20, 0xa0), 3, &tails),
+            Err(OffsetError::OutOfOrder {
+                word: 1,
+                offset: 0x20
+            })
+        );
+        // tails swapped
+        assert_eq!(
+            check_head(&with_offsets(0xa0, 0x60), 3, &tails),
+            Err(OffsetError::OutOfOrder {
+                word: 2,
+                offset: 0x60
+            })
+        );
+        assert_eq!(
+            check_head(&with_offsets(0x60, 0x1_0000), 3, &tails),
+            Err(OffsetError::OutOfBounds { word: 2 })
+        );
+        let mut huge = with_offsets(0x60, 0xa0);
+        huge[32] = 1;
+        assert_eq!(
+            check_head(&huge, 3, &tails),
+            Err(OffsetError::OutOfBounds { word: 1 })
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Feed decoding arena sizing
+//!
+//! Each feed message is decoded into a `TxArena` preallocated for the hot loop, a message outgrowing it (e.g. a block
+//! of NFT mints) costs an allocation or a reallocation of its tx buffer while decoding. The arena is sized by the
+//! `[arena]` config section, its high-water mark is tracked and messages near either limit emit a warning
+use serde::Deserialize;
+
+use fulcrum_sequencer_feed::{TxArena, DEFAULT_TX_ARENA_CAPACITY, DEFAULT_TX_BUFFER_CAPACITY};
+
+use crate::events::EngineEvent;
+
+/// Default usage of either limit which warns (percent)
+pub const DEFAULT_WARN_PERCENT: u8 = 80;
+
+/// Decoding arena sizing, the `[arena]` config section
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub struct ArenaConfig {
+    /// Bytes preallocated for decoding a feed message
+    #[serde(default = "default_capacity_bytes")]
+    pub capacity_bytes: usize,
+    /// Txs preallocated per feed message
+    #[serde(default = "default_txs")]
+    pub txs: usize,
+    /// Usage of either limit a message warns at (percent)
+    #[serde(default = "default_warn_percent")]
+    pub warn_percent: u8,
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        Self {
+            capacity_bytes: DEFAULT_TX_ARENA_CAPACITY,
+            txs: DEFAULT_TX_BUFFER_CAPACITY,
+            warn_percent: DEFAULT_WARN_PERCENT,
+        }
+    }
+}
+
+fn default_capacity_bytes() -> usize {
+    DEFAULT_TX_ARENA_CAPACITY
+}
+
+fn default_txs() -> usize {
+    DEFAULT_TX_BUFFER_CAPACITY
+}
+
+fn default_warn_percent() -> u8 {
+    DEFAULT_WARN_PERCENT
+}
+
+impl ArenaConfig {
+    /// A new arena of the configured size
+    pub fn arena(&self) -> TxArena {
+        TxArena::with_capacity(self.capacity_bytes).with_tx_capacity(self.txs)
+    }
+}
+
+/// Memory used decoding a feed message
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ArenaUsage {
+    /// Bytes allocated from the arena
+    pub bytes: usize,
+    pub txs: usize,
+}
+
+/// Tracks the arena's high-water mark, warning of messages near its limits
+#[derive(Debug)]
+pub struct ArenaWatch {
+    config: ArenaConfig,
+    /// The most bytes and txs of any message
+    high_water: ArenaUsage,
+    /// Block number of the last warning, warnings are emitted once per block
+    warned_block: u64,
+}
+
+impl ArenaWatch {
+    pub fn new(config: ArenaConfig) -> Self {
+        Self {
+            config,
+            high_water: ArenaUsage::default(),
+            warned_block: 0,
+        }
+    }
+    /// The most bytes and txs of any message so far
+    pub fn high_water(&self) -> ArenaUsage {
+        self.high_water
+    }
+    /// Record `usage` decoding a message of `block_number`, returning a warning if it nears the arena's limits
+    pub fn record(&mut self, block_number: u64, usage: ArenaUsage) -> Option<EngineEvent> {
+        self.high_water.bytes = self.high_water.bytes.max(usage.bytes);
+        self.high_water.txs = self.high_water.txs.max(usage.txs);
+        let near = |used: usize, limit: usize| {
+            used.saturating_mul(100) >= limit.saturating_mul(self.config.warn_percent as usize)
+        };
+        if block_number == self.warned_block
+            || !(near(usage.bytes, self.config.capacity_bytes) || near(usage.txs, self.config.txs))
+        {
+            return None;
+        }
+        self.warned_block = block_number;
+
+        Some(EngineEvent::ArenaNearLimit {
+            block_number,
+            bytes: usage.bytes,
+            capacity_bytes: self.config.capacity_bytes,
+            txs: usage.txs,
+            tx_capacity: self.config.txs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn warns_near_limits_once_per_block() {
+        let mut watch = ArenaWatch::new(ArenaConfig {
+            capacity_bytes: 1_000,
+            txs: 10,
+            warn_percent: 80,
+        });
+        let usage = |bytes, txs| ArenaUsage { bytes, txs };
+        assert_eq!(watch.record(1, usage(799, 7)), None);
+        assert_eq!(
+            watch.record(1, usage(800, 2)),
+            Some(EngineEvent::ArenaNearLimit {
+                block_number: 1,
+                bytes: 800,
+                capacity_bytes: 1_000,
+                txs: 2,
+                tx_capacity: 10,
+            })
+        );
+        // once per block
+        assert_eq!(watch.record(1, usage(2_000, 20)), None);
+        assert!(watch.record(2, usage(100, 8)).is_some());
+        assert_eq!(watch.high_water(), usage(2_000, 20));
+    }
+}
@@ -0,0 +1,323 @@
+//! Simulation audit
+//!
+//! Simulating feed txs trades accuracy for speed e.g. approximate pool math or txs which can't be decoded. The auditor
+//! measures the tradeoff: for each simulated block the touched pools' states are compared to their states after the
+//! block, from the block's `Swap`/`Sync` logs ('eth_getBlockReceipts'). Swaps of monitored pools the simulation never
+//! touched are counted as missed, a pool missed or diverging on every block points at a broken decoder.
+//! Stats are logged periodically and counted in `fulcrum_sim_pools_*_total`
+use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
+
+use ethers::types::Address;
+use ethers_providers::Provider;
+use log::{debug, info, warn};
+use tokio::sync::mpsc;
+
+use fulcrum_ws_cli::FastClient;
+
+#[cfg(feature = "metrics")]
+use crate::metrics;
+use crate::{
+    fallback::{block_swaps, MonitoredPools, PoolStateDiff},
+    price_graph::PriceGraph,
+    types::Pair,
+    util::u256_to_f64,
+};
+
+/// Default divergence (bps) beyond which a simulated pool state counts as diverged
+pub const DEFAULT_DIVERGENCE_BPS: f64 = 10.0;
+/// Audited blocks between stats reports
+const REPORT_BLOCKS: u64 = 100;
+/// Max. simulated blocks queued for audit, later ones are dropped until the auditor catches up
+const AUDIT_QUEUE_SIZE: usize = 64;
+/// Receipt fetches per block, the node may not have the block yet
+const RECEIPT_ATTEMPTS: u32 = 8;
+/// Delay between receipt fetches
+const RECEIPT_RETRY: Duration = Duration::from_millis(250);
+
+/// The simulated states of the pools touched in a block
+#[derive(Debug, Default)]
+pub struct SimulatedBlock {
+    pub block_number: u64,
+    pub pools: Vec<PoolStateDiff>,
+}
+
+/// Divergence of simulated pool states from the chain's
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AuditStats {
+    /// Simulated blocks audited
+    pub blocks: u64,
+    /// Simulated pool states compared to the pool's state after the block
+    pub compared: u64,
+    /// Compared states diverging beyond the threshold
+    pub diverged: u64,
+    /// Swaps of monitored pools the simulation didn't touch
+    pub missed: u64,
+    /// Sum of the divergence (bps) of compared states
+    pub total_bps: f64,
+    /// Max. divergence (bps) of a compared state
+    pub max_bps: f64,
+}
+
+impl AuditStats {
+    /// Record a block's `simulated` pool states against the pool states after its `swaps` (in log order)
+    ///
+    /// Returns the pools diverging by more than `threshold_bps`
+    pub fn record(
+        &mut self,
+        simulated: &[PoolStateDiff],
+        swaps: &[PoolStateDiff],
+        threshold_bps: f64,
+    ) -> Vec<(Address, f64)> {
+        self.blocks += 1;
+        // the latest swap of each pool is its state after the block
+        let actual: HashMap<Address, &PoolStateDiff> =
+            swaps.iter().map(|swap| (swap.pool(), swap)).collect();
+        let mut diverged = Vec::new();
+        for state in simulated {
+            // an untouched pool of a touched token pair or a reverted trade
+            let Some(divergence) = actual
+                .get(&state.pool())
+                .and_then(|actual| divergence_bps(state, actual))
+            else {
+                continue;
+            };
+            self.compared += 1;
+            self.total_bps += divergence;
+            self.max_bps = self.max_bps.max(divergence);
+            if divergence > threshold_bps {
+                self.diverged += 1;
+                diverged.push((state.pool(), divergence));
+            }
+        }
+        self.missed += actual
+            .keys()
+            .filter(|pool| !simulated.iter().any(|state| state.pool() == **pool))
+            .count() as u64;
+
+        diverged
+    }
+    /// Mean divergence (bps) of compared states
+    pub fn mean_bps(&self) -> f64 {
+        if self.compared == 0 {
+            return 0.0;
+        }
+        self.total_bps / self.compared as f64
+    }
+}
+
+impl fmt::Display for AuditStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} blocks, {} pools compared, {} diverged, {} swaps missed, divergence mean: {:.2}bps max: {:.2}bps",
+            self.blocks,
+            self.compared,
+            self.diverged,
+            self.missed,
+            self.mean_bps(),
+            self.max_bps,
+        )
+    }
+}
+
+/// Divergence (bps) of a `simulated` pool state from the `actual`, `None` if they're of different kinds
+///
+/// Uniswap V3 states diverge by their sqrt price, Uniswap V2 states by the reserve diverging most
+pub fn divergence_bps(simulated: &PoolStateDiff, actual: &PoolStateDiff) -> Option<f64> {
+    let relative_bps = |simulated: f64, actual: f64| {
+        if actual == 0.0 {
+            return 0.0;
+        }
+        (simulated - actual).abs() / actual * 10_000.0
+    };
+    match (simulated, actual) {
+        (
+            PoolStateDiff::UniswapV3 { sqrt_p_x96, .. },
+            PoolStateDiff::UniswapV3 {
+                sqrt_p_x96: actual_sqrt_p_x96,
+                ..
+            },
+        ) => Some(relative_bps(
+            u256_to_f64(sqrt_p_x96),
+            u256_to_f64(actual_sqrt_p_x96),
+        )),
+        (
+            PoolStateDiff::UniswapV2 {
+                reserve_0,
+                reserve_1,
+                ..
+            },
+            PoolStateDiff::UniswapV2 {
+                reserve_0: actual_reserve_0,
+                reserve_1: actual_reserve_1,
+                ..
+            },
+        ) => Some(
+            relative_bps(*reserve_0 as f64, *actual_reserve_0 as f64)
+                .max(relative_bps(*reserve_1 as f64, *actual_reserve_1 as f64)),
+        ),
+        _ => None,
+    }
+}
+
+/// Compares simulated pool states to the chain's after each simulated block
+pub struct SimulationAuditor {
+    /// Price source node (ws)
+    provider: Provider<FastClient>,
+    pools: Arc<MonitoredPools>,
+    /// Divergence (bps) beyond which a simulated pool state counts as diverged
+    threshold_bps: f64,
+}
+
+impl SimulationAuditor {
+    /// Audit the `v3_pairs` and `v2_pairs` pools via `provider`
+    pub fn new(
+        provider: Provider<FastClient>,
+        v3_pairs: &[(Pair, Address)],
+        v2_pairs: &[(Pair, Address)],
+    ) -> Self {
+        Self {
+            provider,
+            pools: Arc::new(MonitoredPools::new(v3_pairs, v2_pairs)),
+            threshold_bps: DEFAULT_DIVERGENCE_BPS,
+        }
+    }
+    /// Count simulated pool states diverging by more than `bps` as diverged (default: 10)
+    pub fn with_threshold_bps(mut self, bps: f64) -> Self {
+        self.threshold_bps = bps;
+        self
+    }
+    /// Start auditing in the background, returning the queue of simulated blocks to audit
+    pub fn start(self) -> Audits {
+        let (tx, mut rx) = mpsc::channel::<SimulatedBlock>(AUDIT_QUEUE_SIZE);
+        let pools = Arc::clone(&self.pools);
+        tokio::spawn(async move {
+            info!("auditing simulations 🔍");
+            let mut stats = AuditStats::default();
+            while let Some(simulated) = rx.recv().await {
+                let Some(swaps) = self.swaps(simulated.block_number).await else {
+                    continue;
+                };
+                #[cfg(feature = "metrics")]
+                let before = stats;
+                let diverged = stats.record(&simulated.pools, &swaps, self.threshold_bps);
+                for (pool, divergence) in diverged.iter() {
+                    warn!(
+                        "simulation diverged #{}: {pool:?} by {divergence:.2}bps",
+                        simulated.block_number
+                    );
+                }
+                #[cfg(feature = "metrics")]
+                {
+                    metrics::SIM_POOLS_COMPARED.inc_by(stats.compared - before.compared);
+                    metrics::SIM_POOLS_DIVERGED.inc_by(stats.diverged - before.diverged);
+                    metrics::SIM_POOLS_MISSED.inc_by(stats.missed - before.missed);
+                }
+                if stats.blocks % REPORT_BLOCKS == 0 {
+                    info!("simulation audit 🔍: {stats}");
+                }
+            }
+        });
+
+        Audits { pools, tx }
+    }
+    /// The swaps of monitored pools in `block_number`, retrying until the node has the block
+    async fn swaps(&self, block_number: u64) -> Option<Vec<PoolStateDiff>> {
+        for _ in 0..RECEIPT_ATTEMPTS {
+            match block_swaps(&self.provider, block_number).await {
+                // no swaps at all, the node may not have the block yet
+                Ok(mut swaps) if !swaps.is_empty() => {
+                    swaps.retain(|swap| self.pools.contains(swap));
+                    return Some(swaps);
+                }
+                Ok(_) => {}
+                Err(err) => debug!("audit receipts #{block_number}: {:?}", err),
+            }
+            tokio::time::sleep(RECEIPT_RETRY).await;
+        }
+        debug!("audit: no swaps #{block_number}");
+        None
+    }
+}
+
+/// Queues simulated blocks for the `SimulationAuditor`
+pub struct Audits {
+    pools: Arc<MonitoredPools>,
+    tx: mpsc::Sender<SimulatedBlock>,
+}
+
+impl Audits {
+    /// Queue the pools touched in `price_graph` by simulating `block_number` for audit
+    pub fn record(&self, price_graph: &PriceGraph, block_number: u64) {
+        let pools = self.pools.touched(price_graph);
+        if pools.is_empty() {
+            return;
+        }
+        if self
+            .tx
+            .try_send(SimulatedBlock {
+                block_number,
+                pools,
+            })
+            .is_err()
+        {
+            debug!("audit queue full, drop: #{block_number}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::types::U256;
+
+    use super::*;
+
+    fn v3(pool: u64, sqrt_p_x96: u128) -> PoolStateDiff {
+        PoolStateDiff::UniswapV3 {
+            pool: Address::from_low_u64_be(pool),
+            sqrt_p_x96: U256::from(sqrt_p_x96),
+            liquidity: 1_000_000,
+        }
+    }
+
+    fn v2(pool: u64, reserve_0: u128, reserve_1: u128) -> PoolStateDiff {
+        PoolStateDiff::UniswapV2 {
+            pool: Address::from_low_u64_be(pool),
+            reserve_0,
+            reserve_1,
+        }
+    }
+
+    #[test]
+    fn audit_stats() {
+        let mut stats = AuditStats::default();
+        let simulated = [
+            v3(1, 1_000_000),
+            // a pool of the touched pair which didn't swap
+            v3(2, 2_000_000),
+            v2(3, 10_000, 20_100),
+        ];
+        let swaps = [
+            // an earlier swap of the block
+            v3(1, 900_000),
+            v3(1, 1_000_500),
+            v2(3, 10_000, 20_000),
+            // never simulated
+            v2(4, 1, 1),
+        ];
+        let diverged = stats.record(&simulated, &swaps, 10.0);
+        assert_eq!(diverged.len(), 1);
+        assert_eq!(diverged[0].0, Address::from_low_u64_be(3));
+        assert!((diverged[0].1 - 50.0).abs() < 1e-9);
+        assert_eq!(stats.blocks, 1);
+        assert_eq!(stats.compared, 2);
+        assert_eq!(stats.diverged, 1);
+        assert_eq!(stats.missed, 1);
+        assert_eq!(stats.max_bps, diverged[0].1);
+        assert!((stats.mean_bps() - 27.49).abs() < 0.01);
+
+        // pools of different kinds at the same address aren't compared
+        assert_eq!(divergence_bps(&v3(1, 1), &v2(1, 1, 1)), None);
+        assert_eq!(divergence_bps(&v2(1, 0, 0), &v2(1, 0, 0)), Some(0.0));
+    }
+}
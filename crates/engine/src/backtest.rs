@@ -0,0 +1,417 @@
+//! Backtest the engine against recorded sequencer feed and price data
+//!
+//! - feed recordings are raw sequencer feed JSON messages, one per line
+//! - price recordings are `<block number> <hex encoded pool viewer return data>`, one snapshot per line
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::Path as FilePath,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use async_trait::async_trait;
+use ethers::utils::hex;
+use log::{info, warn};
+use thingbuf::mpsc::{Receiver, Sender};
+use tokio::sync::oneshot;
+
+use fulcrum_sequencer_feed::{decode_feed_message, FeedError, FeedEvent, FeedSource, TxBuffer};
+
+use crate::{
+    engine::Engine,
+    order::{Order, OrderExecutor},
+    price::{bootstrap_price_graph, PoolStates, PriceSource, PriceUpdate},
+    price_graph::{CompositeTrade, Path, PriceGraph},
+    types::{Pair, Position, Token},
+};
+
+/// Run the engine over the recorded `feed` and `prices`, returning a report of the trades it would have made
+///
+/// `search_paths` and `min_profit` are as `Engine::run`
+pub async fn backtest(
+    feed: RecordedFeed,
+    prices: RecordedPrices,
+    search_paths: &[(Position, &[Path])],
+    min_profit: f64,
+) -> BacktestReport {
+    let (executor, report) = BacktestExecutor::new();
+    Engine::new(prices, executor, feed)
+        .run(search_paths, min_profit, true)
+        .await;
+
+    report.await.expect("backtest report")
+}
+
+/// Replays recorded sequencer feed messages
+pub struct RecordedFeed {
+    messages: std::vec::IntoIter<Vec<u8>>,
+}
+
+impl RecordedFeed {
+    /// Create a feed replaying `messages` in order
+    pub fn new(messages: Vec<Vec<u8>>) -> Self {
+        Self {
+            messages: messages.into_iter(),
+        }
+    }
+    /// Load a feed recording from `path`
+    pub fn from_file<P: AsRef<FilePath>>(path: P) -> io::Result<Self> {
+        let messages = fs::read(path)?
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_vec())
+            .collect();
+
+        Ok(Self::new(messages))
+    }
+}
+
+#[async_trait(?Send)]
+impl FeedSource for RecordedFeed {
+    type Message = Vec<u8>;
+    async fn next_message(&mut self) -> Result<Self::Message, FeedError> {
+        self.messages.next().ok_or(FeedError::Closed)
+    }
+    async fn handle_message<'bump: 'a, 'a>(
+        &mut self,
+        message: &'a mut Self::Message,
+        tx_buffer: &mut TxBuffer<'bump, 'a>,
+    ) -> Result<FeedEvent<'a>, FeedError> {
+        let event = decode_feed_message(message.as_mut_slice(), tx_buffer)?;
+        tx_buffer.set_block_number(event.block_number());
+        Ok(event)
+    }
+}
+
+/// Provides price graphs from recorded pool data snapshots
+///
+/// A request for block `N` is served by the latest snapshot at or before block `N - 1`
+/// i.e. the state before the feed txs of block `N` are applied
+pub struct RecordedPrices {
+    /// Uniswap v2 (style) pools, in the order of the recorded data
+    v2_pairs: Vec<Pair>,
+    /// Uniswap v3 pools, in the order of the recorded data
+    v3_pairs: Vec<Pair>,
+    /// Camelot v2 pools, in the order of the recorded data
+    camelot_v2_pairs: Vec<Pair>,
+    /// Balancer weighted pools, in the order of the recorded data
+    balancer_pairs: Vec<Pair>,
+    /// Curve stable pools, in the order of the recorded data
+    curve_pairs: Vec<Pair>,
+    /// Solidly pairs, in the order of the recorded data
+    solidly_pairs: Vec<Pair>,
+    /// KyberSwap Elastic pools, in the order of the recorded data
+    kyber_elastic_pairs: Vec<Pair>,
+    /// Raw pool data by block number
+    snapshots: Arc<BTreeMap<u64, Vec<u8>>>,
+    /// Block number of the last served snapshot
+    block_number: Arc<AtomicU64>,
+}
+
+impl RecordedPrices {
+    /// Create recorded prices from `snapshots` of pool data for the given pairs
+    pub fn new(v2_pairs: &[Pair], v3_pairs: &[Pair], snapshots: BTreeMap<u64, Vec<u8>>) -> Self {
+        Self {
+            v2_pairs: v2_pairs.to_vec(),
+            v3_pairs: v3_pairs.to_vec(),
+            camelot_v2_pairs: Default::default(),
+            balancer_pairs: Default::default(),
+            curve_pairs: Default::default(),
+            solidly_pairs: Default::default(),
+            kyber_elastic_pairs: Default::default(),
+            snapshots: Arc::new(snapshots),
+            block_number: Default::default(),
+        }
+    }
+    /// Load a price recording from `path`
+    pub fn from_file<P: AsRef<FilePath>>(
+        path: P,
+        v2_pairs: &[Pair],
+        v3_pairs: &[Pair],
+    ) -> io::Result<Self> {
+        let mut snapshots = BTreeMap::<u64, Vec<u8>>::new();
+        for line in fs::read_to_string(path)?.lines() {
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid price snapshot");
+            let (block_number, data) = match line.trim().split_once(' ') {
+                Some(parts) => parts,
+                None => continue,
+            };
+            snapshots.insert(
+                block_number.parse().map_err(|_| invalid())?,
+                hex::decode(data.trim_start_matches("0x")).map_err(|_| invalid())?,
+            );
+        }
+
+        Ok(Self::new(v2_pairs, v3_pairs, snapshots))
+    }
+    /// Set the recorded Camelot v2 pools
+    pub fn with_camelot_v2(mut self, camelot_v2_pairs: &[Pair]) -> Self {
+        self.camelot_v2_pairs = camelot_v2_pairs.to_vec();
+        self
+    }
+    /// Set the recorded Balancer weighted pools
+    pub fn with_balancer(mut self, balancer_pairs: &[Pair]) -> Self {
+        self.balancer_pairs = balancer_pairs.to_vec();
+        self
+    }
+    /// Set the recorded Curve stable pools
+    pub fn with_curve(mut self, curve_pairs: &[Pair]) -> Self {
+        self.curve_pairs = curve_pairs.to_vec();
+        self
+    }
+    /// Set the recorded Solidly pairs
+    pub fn with_solidly(mut self, solidly_pairs: &[Pair]) -> Self {
+        self.solidly_pairs = solidly_pairs.to_vec();
+        self
+    }
+    /// Set the recorded KyberSwap Elastic pools
+    pub fn with_kyber_elastic(mut self, kyber_elastic_pairs: &[Pair]) -> Self {
+        self.kyber_elastic_pairs = kyber_elastic_pairs.to_vec();
+        self
+    }
+}
+
+#[async_trait]
+impl PriceSource for RecordedPrices {
+    async fn block_number(&self) -> u64 {
+        self.block_number.load(Ordering::Relaxed)
+    }
+    async fn start(&self) -> (Sender<u64>, Receiver<PriceUpdate>) {
+        let (price_sync_tx, price_sync_rx) = thingbuf::mpsc::channel(5);
+        let (price_queue_tx, price_queue_rx) = thingbuf::mpsc::channel(5);
+
+        let snapshots = Arc::clone(&self.snapshots);
+        let block_number = Arc::clone(&self.block_number);
+        let v2_pairs = self.v2_pairs.clone();
+        let v3_pairs = self.v3_pairs.clone();
+        let camelot_v2_pairs = self.camelot_v2_pairs.clone();
+        let balancer_pairs = self.balancer_pairs.clone();
+        let curve_pairs = self.curve_pairs.clone();
+        let solidly_pairs = self.solidly_pairs.clone();
+        let kyber_elastic_pairs = self.kyber_elastic_pairs.clone();
+
+        tokio::spawn(async move {
+            let mut pools = PoolStates::default();
+            while let Some(target_block) = price_sync_rx.recv().await {
+                let mut price_update_ref = price_queue_tx.send_ref().await.expect("capacity");
+                price_update_ref.block_number = target_block;
+                let snapshot = snapshots.range(..target_block).next_back();
+                let (snapshot_block, pool_data) = match snapshot {
+                    Some(snapshot) => snapshot,
+                    None => {
+                        warn!("no price snapshot for #{target_block}");
+                        price_update_ref.graph = None;
+                        continue;
+                    }
+                };
+                block_number.store(*snapshot_block, Ordering::Relaxed);
+                pools.clear();
+                pools.decode(pool_data);
+
+                let price_graph = price_update_ref.graph.get_or_insert_with(PriceGraph::empty);
+                price_graph.reset(target_block);
+                bootstrap_price_graph(
+                    price_graph,
+                    v2_pairs.as_slice(),
+                    v3_pairs.as_slice(),
+                    camelot_v2_pairs.as_slice(),
+                    balancer_pairs.as_slice(),
+                    curve_pairs.as_slice(),
+                    solidly_pairs.as_slice(),
+                    kyber_elastic_pairs.as_slice(),
+                    &pools,
+                );
+            }
+        });
+
+        (price_sync_tx, price_queue_rx)
+    }
+}
+
+/// A trade the engine would have executed
+#[derive(Clone, Debug, PartialEq)]
+pub struct BacktestTrade {
+    /// Block number of the prices used for the trade
+    pub block_number: u64,
+    /// The start token of the trade
+    pub token: Token,
+    /// Amount of `token` in
+    pub amount_in: u128,
+    /// Expected amount of `token` out
+    pub amount_out: u128,
+    /// The trade path
+    pub trade: CompositeTrade,
+}
+
+impl BacktestTrade {
+    /// The hypothetical profit (or loss) of the trade in `token` units
+    pub fn pnl(&self) -> i128 {
+        self.amount_out as i128 - self.amount_in as i128
+    }
+}
+
+/// Summary of a backtest run
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BacktestReport {
+    /// All trades in order of execution
+    pub trades: Vec<BacktestTrade>,
+}
+
+impl BacktestReport {
+    /// Record an order as a trade
+    pub fn record(&mut self, order: &Order) {
+        self.trades.push(BacktestTrade {
+            block_number: order.block_number,
+            token: Token::from_usize(order.trade.path[0].token_in as usize),
+            amount_in: order.amount_in,
+            amount_out: order.amount_out,
+            trade: order.trade,
+        });
+    }
+    /// Total hypothetical PnL and trade count by start token
+    pub fn pnl_by_token(&self) -> BTreeMap<Token, (i128, usize)> {
+        let mut pnl = BTreeMap::<Token, (i128, usize)>::new();
+        for trade in &self.trades {
+            let entry = pnl.entry(trade.token).or_default();
+            entry.0 += trade.pnl();
+            entry.1 += 1;
+        }
+        pnl
+    }
+}
+
+impl fmt::Display for BacktestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "backtest: {} trades", self.trades.len())?;
+        for trade in &self.trades {
+            writeln!(
+                f,
+                "#{} {:?} {} -> {} ({}) {}",
+                trade.block_number,
+                trade.token,
+                trade.amount_in,
+                trade.amount_out,
+                trade.pnl(),
+                trade.trade,
+            )?;
+        }
+        for (token, (pnl, count)) in self.pnl_by_token() {
+            writeln!(
+                f,
+                "{token:?}: {count} trades, pnl: {}",
+                pnl as f64 / token.one() as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Simulated order executor, records all orders into a `BacktestReport`
+pub struct BacktestExecutor {
+    /// Receives the final report once the order stream ends
+    report: oneshot::Sender<BacktestReport>,
+}
+
+impl BacktestExecutor {
+    /// Create a new executor, returning it with a handle to the final report
+    pub fn new() -> (Self, oneshot::Receiver<BacktestReport>) {
+        let (tx, rx) = oneshot::channel();
+        (Self { report: tx }, rx)
+    }
+}
+
+#[async_trait]
+impl OrderExecutor for BacktestExecutor {
+    async fn start(self, _dry_run: bool) -> Sender<Order> {
+        let (tx, rx) = thingbuf::mpsc::channel::<Order>(5);
+        tokio::spawn(async move {
+            let mut report = BacktestReport::default();
+            while let Some(order) = rx.recv_ref().await {
+                info!("backtest trade 📈: {}", order.trade);
+                report.record(&order);
+            }
+            let _ = self.report.send(report);
+        });
+
+        tx
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hex_literal::hex;
+
+    use crate::{
+        price_graph::{CompositeTrade, Trade},
+        types::{ExchangeId, Fee},
+    };
+
+    use super::*;
+
+    fn order(token: Token, amount_in: u128, amount_out: u128) -> Order {
+        Order {
+            amount_in,
+            amount_out,
+            trade: CompositeTrade::new(&[
+                Trade::new(token.id(), Token::ARB.id(), Fee::from_pips(500), 0),
+                Trade::new(Token::ARB.id(), token.id(), Fee::from_pips(3000), 0),
+            ]),
+            block_number: 1,
+            value_in_wei: 0,
+            timestamps: Default::default(),
+        }
+    }
+
+    #[test]
+    fn report_pnl_by_token() {
+        let mut report = BacktestReport::default();
+        report.record(&order(Token::WETH, 100, 105));
+        report.record(&order(Token::USDC, 1_000, 990));
+        report.record(&order(Token::WETH, 100, 102));
+
+        assert_eq!(report.trades[1].pnl(), -10);
+        let pnl = report.pnl_by_token();
+        assert_eq!(pnl.get(&Token::WETH), Some(&(7, 2)));
+        assert_eq!(pnl.get(&Token::USDC), Some(&(-10, 1)));
+    }
+
+    #[tokio::test]
+    async fn executor_reports_orders() {
+        let (executor, report) = BacktestExecutor::new();
+        let orders = executor.start(true).await;
+        orders.send(order(Token::WETH, 100, 105)).await.unwrap();
+        drop(orders);
+
+        assert_eq!(report.await.unwrap().trades.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn recorded_prices_uses_prior_snapshot() {
+        // one v2 pool, no v3 pools
+        let pool_data = hex!("0000000000000000000000000000000000000000000000000000000000000040000000000000000000000000000000000000000000000000000000000000006000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000020000000000000000000000000000003e8000000000000000000000000000007d0");
+        let pairs = [Pair::new(
+            Token::WETH,
+            Token::USDC,
+            Fee::ZERO,
+            ExchangeId::Sushi,
+        )];
+        let prices = RecordedPrices::new(
+            &pairs,
+            &[],
+            BTreeMap::from([(10, pool_data.to_vec()), (20, pool_data.to_vec())]),
+        );
+        let (requests, graphs) = prices.start().await;
+
+        requests.send(15).await.unwrap();
+        let graph = graphs.recv().await.unwrap().expect("price graph");
+        assert_eq!(graph.block_number(), 15);
+        assert_eq!(prices.block_number().await, 10);
+
+        // no snapshot before block 10
+        requests.send(10).await.unwrap();
+        assert!(graphs.recv().await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,224 @@
+//! Balancer V2 weighted pool price source
+//!
+//! Weighted pools price with the weighted product invariant (∏ balance_i ^ weight_i = k),
+//! weights are 1e18 fixed point and the swap fee is charged on the input token
+use crate::{
+    types::{Fee, U256},
+    util::saturating_u128,
+};
+
+/// 1e18, fixed point precision of weights and fees
+pub const ONE: u128 = 1_000_000_000_000_000_000;
+
+/// Max. amount in as a ratio of the input balance (30%), larger swaps revert
+const MAX_IN_RATIO: u128 = 300_000_000_000_000_000;
+
+/// Max. amount out as a ratio of the output balance (30%), larger swaps revert
+const MAX_OUT_RATIO: u128 = 300_000_000_000_000_000;
+
+/// Mirror `WeightedMath._calcOutGivenIn` (less the swap fee)
+///
+/// - `fee` the swap fee charged on the input token
+///
+/// Returns 0 if the pool would reject the swap i.e. `amount_in` exceeds 30% of `balance_in`
+pub fn get_amount_out(
+    fee: Fee,
+    amount_in: u128,
+    balance_in: u128,
+    weight_in: u64,
+    balance_out: u128,
+    weight_out: u64,
+) -> u128 {
+    let amount_in = U256::from(amount_in);
+    let balance_in = U256::from(balance_in);
+    let amount_in = amount_in.saturating_sub(mul_up(amount_in, fixed_point(fee)));
+    if amount_in > mul_down(balance_in, U256::from(MAX_IN_RATIO)) || balance_in.is_zero() {
+        return 0;
+    }
+    let base = div_up(balance_in, balance_in + amount_in);
+    let power = pow_up(base, weight_in, weight_out);
+    let one = U256::from(ONE);
+    if power >= one {
+        return 0;
+    }
+
+    saturating_u128(mul_down(U256::from(balance_out), one - power))
+}
+
+/// Mirror `WeightedMath._calcInGivenOut` (plus the swap fee)
+///
+/// - `fee` the swap fee charged on the input token
+///
+/// Returns `u128::MAX` i.e. an unfillable amount, if the pool would reject the swap i.e. `amount_out` exceeds 30% of `balance_out`
+pub fn get_amount_in(
+    fee: Fee,
+    amount_out: u128,
+    balance_in: u128,
+    weight_in: u64,
+    balance_out: u128,
+    weight_out: u64,
+) -> u128 {
+    let amount_out = U256::from(amount_out);
+    let balance_out = U256::from(balance_out);
+    if amount_out > mul_down(balance_out, U256::from(MAX_OUT_RATIO)) || balance_out.is_zero() {
+        return u128::MAX;
+    }
+    let one = U256::from(ONE);
+    if fixed_point(fee) >= one {
+        return u128::MAX;
+    }
+    let base = div_up(balance_out, balance_out - amount_out);
+    let power = pow_up(base, weight_out, weight_in);
+    let amount_in = mul_up(U256::from(balance_in), power.saturating_sub(one));
+
+    // the fee is charged on the (gross) input amount
+    saturating_u128(div_up(amount_in, one - fixed_point(fee)))
+}
+
+/// `get_amount_out` with float (speed > precision)
+pub fn get_amount_out_f(
+    fee: Fee,
+    amount_in: u128,
+    balance_in: u128,
+    weight_in: u64,
+    balance_out: u128,
+    weight_out: u64,
+) -> f64 {
+    let amount_in = amount_in as f64 * (1.0 - fee.as_f64());
+    let balance_in = balance_in as f64;
+    if amount_in > balance_in * (MAX_IN_RATIO as f64 / ONE as f64) {
+        return 0.0;
+    }
+    let power = (balance_in / (balance_in + amount_in)).powf(weight_in as f64 / weight_out as f64);
+
+    balance_out as f64 * (1.0 - power)
+}
+
+/// `fee` as 1e18 fixed point
+fn fixed_point(fee: Fee) -> U256 {
+    U256::from(fee.pips()) * U256::from(ONE / Fee::PIPS as u128)
+}
+
+/// `base` ^ (`weight_a` / `weight_b`) rounded up, exact for equal weights
+fn pow_up(base: U256, weight_a: u64, weight_b: u64) -> U256 {
+    if weight_a == weight_b {
+        return base;
+    }
+    // `LogExpMath.pow` is accurate to ~1e-18 relative, round up by at least as much as the pool does
+    let power = (base.low_u128() as f64 / ONE as f64).powf(weight_a as f64 / weight_b as f64);
+    let power = U256::from((power * ONE as f64) as u128);
+    power + mul_up(power, U256::from(MAX_POW_RELATIVE_ERROR)) + 1
+}
+
+/// `LogExpMath.pow` max. relative error, reduced to f64 precision
+const MAX_POW_RELATIVE_ERROR: u128 = 10_000;
+
+fn mul_down(a: U256, b: U256) -> U256 {
+    a * b / U256::from(ONE)
+}
+
+fn mul_up(a: U256, b: U256) -> U256 {
+    let product = a * b;
+    if product.is_zero() {
+        return product;
+    }
+    (product - 1) / U256::from(ONE) + 1
+}
+
+fn div_up(a: U256, b: U256) -> U256 {
+    if a.is_zero() {
+        return a;
+    }
+    (a * U256::from(ONE) - 1) / b + 1
+}
+
+/// Balancer weighted pool state of a token pair as returned by the viewer contract
+#[derive(Debug, PartialEq)]
+pub struct BalancerWeightedBalances {
+    pub balance_0: u128,
+    pub balance_1: u128,
+    /// normalized weight of token0 (1e18 fixed point)
+    pub weight_0: u64,
+    /// normalized weight of token1 (1e18 fixed point)
+    pub weight_1: u64,
+    /// swap fee (1e18 fixed point)
+    pub fee: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::uniswap_v2;
+
+    /// 0.3%
+    const FEE: Fee = Fee::from_units(300);
+
+    #[test]
+    fn get_amount_out_equal_weights() {
+        // 50/50 pools price as constant product
+        let half = (ONE / 2) as u64;
+        let amount_out = get_amount_out(FEE, 5_000_000, 2_000_000_000, half, 3_000_000_000, half);
+        let expected = uniswap_v2::get_amount_out(
+            Fee::from_units(300),
+            5_000_000,
+            2_000_000_000,
+            3_000_000_000,
+        );
+        assert!(amount_out.abs_diff(expected) <= 2);
+
+        let amount_in = get_amount_in(FEE, amount_out, 2_000_000_000, half, 3_000_000_000, half);
+        assert!(amount_in.abs_diff(5_000_000) <= 2);
+    }
+
+    #[test]
+    fn get_amount_out_weighted() {
+        // 80/20 pool, 800 A / 200 B i.e. spot price 1 B/A
+        let (weight_a, weight_b) = ((ONE * 4 / 5) as u64, (ONE / 5) as u64);
+        let (balance_a, balance_b) = (800 * ONE, 200 * ONE);
+        let amount_in = ONE / 1_000;
+
+        let amount_out = get_amount_out(
+            Fee::ZERO,
+            amount_in,
+            balance_a,
+            weight_a,
+            balance_b,
+            weight_b,
+        );
+        assert!(amount_out < amount_in);
+        assert!(amount_out > amount_in * 999 / 1_000);
+        let amount_out_f = get_amount_out_f(
+            Fee::ZERO,
+            amount_in,
+            balance_a,
+            weight_a,
+            balance_b,
+            weight_b,
+        );
+        assert!((amount_out as f64 - amount_out_f).abs() / amount_out_f < 1e-6);
+
+        // rounding favours the pool
+        let amount_in_required = get_amount_in(
+            Fee::ZERO,
+            amount_out,
+            balance_a,
+            weight_a,
+            balance_b,
+            weight_b,
+        );
+        assert!(amount_in_required >= amount_in);
+        assert!(amount_in_required - amount_in < amount_in / 1_000_000);
+    }
+
+    #[test]
+    fn max_ratios() {
+        let half = (ONE / 2) as u64;
+        assert_eq!(get_amount_out(Fee::ZERO, 301, 1_000, half, 1_000, half), 0);
+        assert!(get_amount_out(Fee::ZERO, 300, 1_000, half, 1_000, half) > 0);
+        assert_eq!(
+            get_amount_in(Fee::ZERO, 301, 1_000, half, 1_000, half),
+            u128::MAX
+        );
+        assert!(get_amount_in(Fee::ZERO, 300, 1_000, half, 1_000, half) < u128::MAX);
+    }
+}
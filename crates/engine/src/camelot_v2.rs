@@ -0,0 +1,337 @@
+//! Camelot V2 price source
+//!
+//! Camelot pairs charge a fee per input token (set dynamically by the fee manager)
+//! and price with either the constant product or the stable swap (x³y + y³x = k) invariant
+use crate::{
+    types::{Fee, U256},
+    uniswap_v2,
+    util::saturating_u128,
+};
+
+/// Fees are applied in pips (camelot quotes them in 1/100_000ths)
+const FEE_DENOMINATOR: u128 = Fee::PIPS as u128;
+
+/// 1e18, fixed point precision of the stable swap math
+const E18: u128 = 1_000_000_000_000_000_000;
+
+/// Mirror pair 'getAmountOut' calculation
+///
+/// - `fee` the fee charged on the input token
+/// - `stable` the (input, output) token decimals of stable swap pairs, `None` for constant product pairs
+///
+/// Returns 0 if the stable swap math overflows i.e. the pair would revert
+pub fn get_amount_out(
+    fee: Fee,
+    amount_in: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    stable: Option<(u8, u8)>,
+) -> u128 {
+    match stable {
+        Some((decimals_in, decimals_out)) => stable_amount_out(
+            fee,
+            amount_in,
+            reserve_in,
+            reserve_out,
+            decimals_in,
+            decimals_out,
+        )
+        .unwrap_or(0),
+        None => uniswap_v2::get_amount_out(fee, amount_in, reserve_in, reserve_out),
+    }
+}
+
+/// Calculate the input amount required to receive `amount_out`
+///
+/// - `fee` the fee charged on the input token
+/// - `stable` the (input, output) token decimals of stable swap pairs, `None` for constant product pairs
+///
+/// Returns `u128::MAX` i.e. an unfillable amount, if `amount_out` would drain the reserves or the stable swap math overflows
+pub fn get_amount_in(
+    fee: Fee,
+    amount_out: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    stable: Option<(u8, u8)>,
+) -> u128 {
+    match stable {
+        Some((decimals_in, decimals_out)) => stable_amount_in(
+            fee,
+            amount_out,
+            reserve_in,
+            reserve_out,
+            decimals_in,
+            decimals_out,
+        )
+        .unwrap_or(u128::MAX),
+        None => uniswap_v2::get_amount_in(fee, amount_out, reserve_in, reserve_out),
+    }
+}
+
+/// `get_amount_out` with float (speed > precision)
+pub fn get_amount_out_f(
+    fee: Fee,
+    amount_in: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    stable: Option<(u8, u8)>,
+) -> f64 {
+    match stable {
+        Some(_) => get_amount_out(fee, amount_in, reserve_in, reserve_out, stable) as f64,
+        None => uniswap_v2::get_amount_out_f(fee, amount_in, reserve_in, reserve_out),
+    }
+}
+
+/// Stable swap 'getAmountOut', `None` on overflow
+fn stable_amount_out(
+    fee: Fee,
+    amount_in: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    decimals_in: u8,
+    decimals_out: u8,
+) -> Option<u128> {
+    let (precision_in, precision_out) = (precision(decimals_in)?, precision(decimals_out)?);
+
+    let amount_in = U256::from(amount_in);
+    let amount_in = amount_in.checked_sub(mul_div(
+        amount_in,
+        U256::from(fee.pips()),
+        U256::from(FEE_DENOMINATOR),
+    )?)?;
+    let x = mul_div(U256::from(reserve_in), e18(), precision_in)?;
+    let y = mul_div(U256::from(reserve_out), e18(), precision_out)?;
+    let xy = k(x, y)?;
+
+    let amount_in = mul_div(amount_in, e18(), precision_in)?;
+    let amount_out = y.checked_sub(get_y(amount_in.checked_add(x)?, xy, y)?)?;
+
+    Some(saturating_u128(mul_div(amount_out, precision_out, e18())?))
+}
+
+/// Stable swap input amount required to receive `amount_out`, `None` if unfillable or on overflow
+fn stable_amount_in(
+    fee: Fee,
+    amount_out: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    decimals_in: u8,
+    decimals_out: u8,
+) -> Option<u128> {
+    let (precision_in, precision_out) = (precision(decimals_in)?, precision(decimals_out)?);
+
+    let x = mul_div(U256::from(reserve_in), e18(), precision_in)?;
+    let y = mul_div(U256::from(reserve_out), e18(), precision_out)?;
+    let xy = k(x, y)?;
+
+    // the invariant is symmetric, solve for the input reserve given the output reserve
+    let y_next = y.checked_sub(mul_div(U256::from(amount_out), e18(), precision_out)?)?;
+    if y_next.is_zero() {
+        return None;
+    }
+    let amount_in_less_fee =
+        mul_div(get_y(y_next, xy, x)?.checked_sub(x)?, precision_in, e18())? + 1;
+    let amount_in = mul_div(
+        amount_in_less_fee,
+        U256::from(FEE_DENOMINATOR),
+        U256::from(FEE_DENOMINATOR.checked_sub(fee.pips() as u128)?),
+    )?;
+
+    Some(saturating_u128(amount_in).saturating_add(1))
+}
+
+/// 1e18 as U256
+fn e18() -> U256 {
+    U256::from(E18)
+}
+
+/// `a * b / denominator`, `None` on overflow or a zero `denominator`
+fn mul_div(a: U256, b: U256, denominator: U256) -> Option<U256> {
+    a.checked_mul(b)?.checked_div(denominator)
+}
+
+/// Precision multiplier of a token with `decimals`, `None` if it exceeds U256
+fn precision(decimals: u8) -> Option<U256> {
+    U256::from(10).checked_pow(U256::from(decimals))
+}
+
+/// The stable swap invariant given 1e18 normalized reserves
+fn k(x: U256, y: U256) -> Option<U256> {
+    let a = mul_div(x, y, e18())?;
+    let b = mul_div(x, x, e18())?.checked_add(mul_div(y, y, e18())?)?;
+    mul_div(a, b, e18()) // x³y + y³x >= k
+}
+
+/// Solve the stable swap invariant for y given `x0` and `xy` (newton's method) as `CamelotPair._get_y`
+///
+/// `None` where the pair's checked math would revert
+fn get_y(x0: U256, xy: U256, mut y: U256) -> Option<U256> {
+    for _ in 0..255 {
+        let y_prev = y;
+        let k = f(x0, y)?;
+        if k < xy {
+            y = y.checked_add(mul_div(xy - k, e18(), d(x0, y)?)?)?;
+        } else {
+            y = y.checked_sub(mul_div(k - xy, e18(), d(x0, y)?)?)?;
+        }
+        if y.abs_diff(y_prev) <= U256::one() {
+            return Some(y);
+        }
+    }
+    Some(y)
+}
+
+fn f(x0: U256, y: U256) -> Option<U256> {
+    let y3 = mul_div(mul_div(y, y, e18())?, y, e18())?;
+    let x3 = mul_div(mul_div(x0, x0, e18())?, x0, e18())?;
+    mul_div(x0, y3, e18())?.checked_add(mul_div(x3, y, e18())?)
+}
+
+fn d(x0: U256, y: U256) -> Option<U256> {
+    let y2 = mul_div(y, y, e18())?;
+    let x3 = mul_div(mul_div(x0, x0, e18())?, x0, e18())?;
+    mul_div(U256::from(3).checked_mul(x0)?, y2, e18())?.checked_add(x3)
+}
+
+/// Camelot pair state as returned by the viewer contract
+#[derive(Debug, PartialEq)]
+pub struct CamelotV2Reserves {
+    pub reserve_0: u128,
+    pub reserve_1: u128,
+    /// fee charged on token0 input (1/100_000ths)
+    pub token_0_fee: u16,
+    /// fee charged on token1 input (1/100_000ths)
+    pub token_1_fee: u16,
+    /// The pair prices with the stable swap invariant
+    pub stable: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn get_amount_out_volatile() {
+        assert_eq!(
+            get_amount_out(
+                Fee::from_units(300),
+                5000000000000000000,
+                2757113099049556297952,
+                5176991819833,
+                None
+            ),
+            uniswap_v2::get_amount_out(
+                Fee::from_units(300),
+                5000000000000000000,
+                2757113099049556297952,
+                5176991819833
+            ),
+        );
+        let amount_out = get_amount_out(
+            Fee::from_units(300),
+            5_000_000,
+            2_000_000_000,
+            3_000_000_000,
+            None,
+        );
+        let amount_in = get_amount_in(
+            Fee::from_units(300),
+            amount_out,
+            2_000_000_000,
+            3_000_000_000,
+            None,
+        );
+        assert!(amount_in.abs_diff(5_000_000) <= 2);
+    }
+
+    #[test]
+    fn get_amount_out_stable() {
+        // 1m USDC (6 decimals) / 1m DAI (18 decimals)
+        let reserve_in = 1_000_000 * 10_u128.pow(6);
+        let reserve_out = 1_000_000 * 10_u128.pow(18);
+        let amount_in = 1_000 * 10_u128.pow(6);
+        let decimals = Some((6, 18));
+
+        let amount_out = get_amount_out(
+            Fee::from_units(40),
+            amount_in,
+            reserve_in,
+            reserve_out,
+            decimals,
+        );
+        // stable curve has less slippage than constant product
+        assert!(
+            amount_out
+                > get_amount_out(
+                    Fee::from_units(40),
+                    amount_in * 10_u128.pow(12),
+                    reserve_out,
+                    reserve_out,
+                    None
+                )
+        );
+        assert!(amount_out < 1_000 * 10_u128.pow(18));
+        assert!(amount_out > 999 * 10_u128.pow(18));
+
+        let amount_in_required = get_amount_in(
+            Fee::from_units(40),
+            amount_out,
+            reserve_in,
+            reserve_out,
+            decimals,
+        );
+        assert!(amount_in_required.abs_diff(amount_in) <= 2);
+    }
+
+    #[test]
+    fn stable_extreme_values_do_not_panic() {
+        let decimals = Some((18, 18));
+        let reserve = 1_000_000 * 10_u128.pow(18);
+        let fee = Fee::from_units(40);
+
+        // an amount in near u128::MAX overflows the invariant, as the pair would revert
+        assert_eq!(
+            get_amount_out(fee, u128::MAX, reserve, reserve, decimals),
+            0
+        );
+        assert_eq!(
+            get_amount_out(fee, 1_000, u128::MAX, u128::MAX, decimals),
+            0
+        );
+        assert_eq!(get_amount_out(fee, 1_000, 0, 0, decimals), 0);
+        // fees of 100% or more leave nothing to swap
+        assert_eq!(
+            get_amount_out(
+                Fee::from_pips(Fee::PIPS + 1),
+                1_000,
+                reserve,
+                reserve,
+                decimals
+            ),
+            0
+        );
+        // decimals beyond U256 precision
+        assert_eq!(
+            get_amount_out(fee, 1_000, reserve, reserve, Some((255, 18))),
+            0
+        );
+
+        // draining the reserves is unfillable
+        assert_eq!(
+            get_amount_in(fee, reserve, reserve, reserve, decimals),
+            u128::MAX
+        );
+        assert_eq!(
+            get_amount_in(fee, u128::MAX, reserve, reserve, decimals),
+            u128::MAX
+        );
+        assert_eq!(
+            get_amount_in(fee, 1_000, u128::MAX, u128::MAX, decimals),
+            u128::MAX
+        );
+        assert_eq!(
+            get_amount_in(Fee::from_pips(Fee::PIPS), 1_000, reserve, reserve, decimals),
+            u128::MAX
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Chain reorg and resequencing detection
+//!
+//! Prices are synced from a node while txs are simulated from the sequencer feed, both must follow the same chain.
+//! The node's heads are tracked by hash, a head which does not extend the tracked chain is a reorg. Feed blocks only
+//! advance, a block older than one the feed already delivered was resequenced. Either way prices synced since may be
+//! of another chain view, the engine resyncs them (see `Engine::with_chain_watch`)
+//!
+//! Feed block numbers are derived from sequence numbers assuming the nitro genesis offset, they're cross-checked
+//! against the node's latest block periodically and optionally re-calibrated (see `Engine::with_block_check`)
+use std::{collections::VecDeque, fmt};
+
+use ethers::types::H256;
+use ethers_providers::{Middleware, Provider};
+use futures::StreamExt;
+use log::{info, warn};
+use thingbuf::mpsc::{channel, Receiver};
+
+use fulcrum_ws_cli::FastClient;
+
+/// Heads tracked by hash, reorgs deeper than this are detected at the parent of the new head only
+const HEAD_WINDOW: usize = 64;
+/// Max. inconsistencies queued for the engine, later ones are dropped until it catches up
+const INCONSISTENCY_QUEUE_SIZE: usize = 16;
+/// Default max. blocks the feed's block numbers may differ from the node's
+pub const DEFAULT_MAX_BLOCK_DRIFT: u64 = 20;
+
+/// The chain views of the price source and feed disagree
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChainInconsistency {
+    /// The node replaced its blocks from `block_number`, `depth` blocks were replaced
+    Reorg { block_number: u64, depth: u64 },
+    /// The feed delivered `block_number` after the later block `latest`
+    Resequenced { block_number: u64, latest: u64 },
+}
+
+impl Default for ChainInconsistency {
+    /// Placeholder of queue slots
+    fn default() -> Self {
+        Self::Reorg {
+            block_number: 0,
+            depth: 0,
+        }
+    }
+}
+
+impl fmt::Display for ChainInconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reorg {
+                block_number,
+                depth,
+            } => write!(f, "reorg from #{block_number} ({depth} blocks)"),
+            Self::Resequenced {
+                block_number,
+                latest,
+            } => write!(f, "#{block_number} resequenced after #{latest}"),
+        }
+    }
+}
+
+/// Tracks the recent heads of a node by hash
+#[derive(Debug, Default)]
+pub struct HeadTracker {
+    /// (block number, hash) ascending
+    heads: VecDeque<(u64, H256)>,
+}
+
+impl HeadTracker {
+    /// Record a new head, returning the reorg if it does not extend the tracked chain
+    ///
+    /// Re-announced heads are ignored
+    pub fn record(
+        &mut self,
+        block_number: u64,
+        hash: H256,
+        parent_hash: H256,
+    ) -> Option<ChainInconsistency> {
+        let latest = self.heads.back().map(|(number, _)| *number);
+        let fork = match self.hash_at(block_number) {
+            Some(known) if known == hash => return None,
+            Some(_) => Some(block_number),
+            // an older head which was not tracked e.g. missed by the subscription
+            None if latest.is_some_and(|latest| block_number <= latest) => Some(block_number),
+            None => self
+                .hash_at(block_number.saturating_sub(1))
+                .filter(|parent| *parent != parent_hash)
+                .map(|_| block_number - 1),
+        };
+        // forget the replaced heads, the new head's parent is known from it
+        let first_replaced = fork
+            .unwrap_or(block_number)
+            .min(block_number.saturating_sub(1));
+        self.heads.retain(|(number, _)| *number < first_replaced);
+        if block_number > 0 {
+            self.heads.push_back((block_number - 1, parent_hash));
+        }
+        self.heads.push_back((block_number, hash));
+        while self.heads.len() > HEAD_WINDOW {
+            self.heads.pop_front();
+        }
+
+        fork.map(|fork| ChainInconsistency::Reorg {
+            block_number: fork,
+            depth: latest.unwrap_or(fork).max(fork) - fork + 1,
+        })
+    }
+    /// Hash of the tracked head at `block_number`, if any
+    fn hash_at(&self, block_number: u64) -> Option<H256> {
+        self.heads
+            .iter()
+            .rev()
+            .find(|(number, _)| *number == block_number)
+            .map(|(_, hash)| *hash)
+    }
+}
+
+/// Tracks the block numbers delivered by the feed
+#[derive(Debug, Default)]
+pub struct FeedSequence {
+    /// Latest block delivered by the feed
+    latest: u64,
+}
+
+impl FeedSequence {
+    /// Latest block delivered by the feed, `0` before the first
+    pub fn latest(&self) -> u64 {
+        self.latest
+    }
+    /// Record a block delivered by the feed, returning the resequencing if it is older than the latest
+    ///
+    /// Repeats of the latest block are expected e.g. a block's txs then its end
+    pub fn record(&mut self, block_number: u64) -> Option<ChainInconsistency> {
+        if block_number < self.latest {
+            return Some(ChainInconsistency::Resequenced {
+                block_number,
+                latest: std::mem::replace(&mut self.latest, block_number),
+            });
+        }
+        self.latest = block_number;
+        None
+    }
+}
+
+/// Cross-checks the block numbers derived from the feed against the node's latest block
+///
+/// The node lags the feed by a few blocks at most, a larger difference means the numbering is off e.g. it changed
+/// or the feed is of another chain. The same difference on consecutive checks is a numbering offset to calibrate,
+/// rather than e.g. a node catching up
+#[derive(Debug)]
+pub struct BlockNumberCheck {
+    /// Max. blocks the feed's block numbers may differ from the node's
+    max_drift: u64,
+    /// Difference (node - feed) found by the previous check, if misaligned
+    last_offset: Option<i64>,
+}
+
+impl Default for BlockNumberCheck {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_BLOCK_DRIFT)
+    }
+}
+
+impl BlockNumberCheck {
+    /// Check the feed's block numbers are within `max_drift` blocks of the node's
+    pub fn new(max_drift: u64) -> Self {
+        Self {
+            max_drift,
+            last_offset: None,
+        }
+    }
+    /// Check the latest `feed_block` against the node's latest `node_block`
+    ///
+    /// Returns the misalignment, with the offset to shift feed block numbers by if it was also found by the
+    /// previous check
+    pub fn check(&mut self, feed_block: u64, node_block: u64) -> Option<BlockMisalignment> {
+        let offset = node_block as i64 - feed_block as i64;
+        if offset.unsigned_abs() <= self.max_drift {
+            self.last_offset = None;
+            return None;
+        }
+        let calibration = self
+            .last_offset
+            .filter(|last| last.abs_diff(offset) <= self.max_drift)
+            .map(|_| offset);
+        // calibrated offsets are applied, checks start over
+        self.last_offset = calibration.is_none().then_some(offset);
+        Some(BlockMisalignment {
+            feed_block,
+            node_block,
+            calibration,
+        })
+    }
+}
+
+/// The feed's block numbers differ from the node's beyond the max. drift
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlockMisalignment {
+    pub feed_block: u64,
+    pub node_block: u64,
+    /// Offset to shift feed block numbers by, if the difference is consistent
+    pub calibration: Option<i64>,
+}
+
+/// Watches the heads of the price source node for reorgs via 'eth_subscribe' newHeads
+pub struct ChainWatcher {
+    /// Price source node (ws)
+    provider: Provider<FastClient>,
+}
+
+impl ChainWatcher {
+    /// Watch the heads of `provider`
+    pub fn new(provider: Provider<FastClient>) -> Self {
+        Self { provider }
+    }
+    /// Start watching in the background, returning the queue of detected reorgs
+    ///
+    /// The queue closes if the subscription fails or ends
+    pub fn start(self) -> Receiver<ChainInconsistency> {
+        let (tx, rx) = channel::<ChainInconsistency>(INCONSISTENCY_QUEUE_SIZE);
+        tokio::spawn(async move {
+            let mut blocks = match self.provider.subscribe_blocks().await {
+                Ok(blocks) => blocks,
+                Err(err) => {
+                    warn!("chain watch subscription: {:?}", err);
+                    return;
+                }
+            };
+            info!("watching chain heads 🔭");
+            let mut heads = HeadTracker::default();
+            while let Some(block) = blocks.next().await {
+                let (Some(block_number), Some(hash)) = (block.number, block.hash) else {
+                    continue;
+                };
+                if let Some(reorg) = heads.record(block_number.as_u64(), hash, block.parent_hash) {
+                    if tx.try_send(reorg).is_err() {
+                        warn!("chain watch queue full, drop: {reorg}");
+                    }
+                }
+            }
+            warn!("chain watch subscription ended");
+        });
+
+        rx
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hash(block_number: u64, fork: u8) -> H256 {
+        let mut hash = H256::from_low_u64_be(block_number);
+        hash.0[0] = fork;
+        hash
+    }
+
+    #[test]
+    fn head_tracker_detects_reorgs() {
+        let mut heads = HeadTracker::default();
+        for number in 100..110 {
+            assert_eq!(
+                heads.record(number, hash(number, 0), hash(number - 1, 0)),
+                None
+            );
+        }
+        // re-announced
+        assert_eq!(heads.record(109, hash(109, 0), hash(108, 0)), None);
+        // a new head on another parent replaces #109
+        assert_eq!(
+            heads.record(110, hash(110, 1), hash(109, 1)),
+            Some(ChainInconsistency::Reorg {
+                block_number: 109,
+                depth: 1
+            })
+        );
+        // the new chain is tracked
+        assert_eq!(heads.record(111, hash(111, 1), hash(110, 1)), None);
+        // the node switches back to a fork from #106
+        assert_eq!(
+            heads.record(106, hash(106, 2), hash(105, 0)),
+            Some(ChainInconsistency::Reorg {
+                block_number: 106,
+                depth: 6
+            })
+        );
+        assert_eq!(heads.record(107, hash(107, 2), hash(106, 2)), None);
+        // gaps are not reorgs
+        assert_eq!(heads.record(120, hash(120, 2), hash(119, 2)), None);
+        assert_eq!(heads.record(121, hash(121, 2), hash(120, 2)), None);
+    }
+
+    #[test]
+    fn head_tracker_window() {
+        let mut heads = HeadTracker::default();
+        for number in 1..=1_000 {
+            assert_eq!(
+                heads.record(number, hash(number, 0), hash(number - 1, 0)),
+                None
+            );
+        }
+        assert_eq!(heads.heads.len(), HEAD_WINDOW);
+        assert_eq!(heads.heads.back(), Some(&(1_000, hash(1_000, 0))));
+    }
+
+    #[test]
+    fn feed_sequence_detects_resequencing() {
+        let mut feed = FeedSequence::default();
+        assert_eq!(feed.record(91_146_328), None);
+        // a block's end repeats it
+        assert_eq!(feed.record(91_146_328), None);
+        assert_eq!(feed.record(91_146_330), None);
+        assert_eq!(
+            feed.record(91_146_329),
+            Some(ChainInconsistency::Resequenced {
+                block_number: 91_146_329,
+                latest: 91_146_330
+            })
+        );
+        assert_eq!(feed.latest(), 91_146_329);
+        assert_eq!(feed.record(91_146_330), None);
+    }
+
+    #[test]
+    fn block_number_check() {
+        let mut check = BlockNumberCheck::new(20);
+        // the node lags the feed
+        assert_eq!(check.check(91_146_330, 91_146_328), None);
+        assert_eq!(check.check(91_146_330, 91_146_349), None);
+        // a node catching up is misaligned once
+        assert_eq!(
+            check.check(91_146_330, 91_146_000),
+            Some(BlockMisalignment {
+                feed_block: 91_146_330,
+                node_block: 91_146_000,
+                calibration: None,
+            })
+        );
+        assert_eq!(check.check(91_146_340, 91_146_330), None);
+
+        // numbering off by 1_000 blocks
+        assert_eq!(
+            check.check(91_147_330, 91_146_330).unwrap().calibration,
+            None
+        );
+        assert_eq!(
+            check.check(91_147_400, 91_146_402).unwrap().calibration,
+            Some(-998)
+        );
+        // starts over after calibrating
+        assert_eq!(
+            check.check(91_147_400, 91_146_402).unwrap().calibration,
+            None
+        );
+    }
+}
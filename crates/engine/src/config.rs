@@ -0,0 +1,952 @@
+//! Engine configuration
+//!
+//! The trading universe (tokens, pools, routers, and search positions) is loaded from TOML at startup
+//! and builds the engine's address lookup maps
+use std::{fmt, fs, io, path::Path as FilePath};
+
+use ethers::types::Address;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+
+#[cfg(feature = "notify")]
+use crate::notify::NotifyConfig;
+use crate::{
+    arena::ArenaConfig,
+    constant::arbitrum::{UNISWAP_V3_FACTORY, UNISWAP_V3_INIT_CODE_HASH},
+    encoder::DEFAULT_ORDER_ENCODER,
+    price::PriceSyncMode,
+    price_graph::MAX_HOPS,
+    registry::{RegistryError, TokenInfo, TokenRegistry, MAX_TOKENS},
+    risk::RiskLimits,
+    solidly::SolidlyFactory,
+    submission::SubmissionConfig,
+    types::{one_token, ExchangeId, Fee, OpaqueRouterPolicy, Pair, Position, RouterId, Token},
+    uniswap_v3,
+    util::AddressMap,
+};
+
+/// The bundled Arbitrum One config
+const ARBITRUM_ONE: &str = include_str!("../config/arbitrum-one.toml");
+
+/// The installed config
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Error reading the config file
+    Io(io::Error),
+    /// Error parsing the config TOML
+    Parse(toml::de::Error),
+    /// A token could not be registered
+    Token(RegistryError),
+    /// A pool or position references a token which is not configured
+    UnknownToken(String),
+    /// A pool has no address and it can't be derived
+    MissingPoolAddress(Pair),
+    /// Arb cycle length is outside 2..=MAX_HOPS
+    MaxHops(usize),
+    /// The submission config has no endpoints
+    NoSubmissionEndpoints,
+    /// A config was already installed
+    AlreadyInstalled,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "config read: {err}"),
+            Self::Parse(err) => write!(f, "config parse: {err}"),
+            Self::Token(err) => write!(f, "token registry: {err:?}"),
+            Self::UnknownToken(symbol) => write!(f, "token {symbol} not configured"),
+            Self::MissingPoolAddress(pair) => write!(f, "pool {pair:?} requires an address"),
+            Self::MaxHops(hops) => write!(f, "max_hops {hops} not in 2..={MAX_HOPS}"),
+            Self::NoSubmissionEndpoints => write!(f, "submission requires an endpoint"),
+            Self::AlreadyInstalled => write!(f, "config already installed"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A tradeable token
+#[derive(Clone, Debug, Deserialize)]
+struct TokenConfig {
+    /// The token Id, must match the executor contract's token lookup
+    id: u8,
+    symbol: String,
+    address: Address,
+    decimals: u8,
+}
+
+/// A trading pool
+#[derive(Clone, Debug, Deserialize)]
+struct PoolConfig {
+    exchange: ExchangeId,
+    /// Token symbols, curve pools list tokens in pool coin order
+    tokens: (String, String),
+    /// Pool fee as quoted by the exchange, uniswap v3 fee tiers in pips (1/1_000_000ths) and others in 1/100_000ths
+    /// (camelot, balancer, and curve pool fees are dynamic and synced with prices)
+    #[serde(default)]
+    fee: u16,
+    /// The pool contract address, derived for uniswap v3 pools (and ramses pairs given the `[ramses]` factory) if omitted
+    address: Option<Address>,
+    /// Solidly stable pair, used to derive ramses pair addresses
+    #[serde(default)]
+    stable: bool,
+    /// Sync the pool's prices, otherwise the pool is only known for decoding trades
+    #[serde(default = "default_sync")]
+    sync: bool,
+}
+
+fn default_sync() -> bool {
+    true
+}
+
+/// A known router contract
+#[derive(Clone, Debug, Deserialize)]
+struct RouterConfig {
+    id: RouterId,
+    address: Address,
+}
+
+/// A contract whose calls can't be decoded e.g. intent settlement or RFQ flow
+#[derive(Clone, Debug, Deserialize)]
+struct OpaqueRouterConfig {
+    address: Address,
+    policy: OpaqueRouterPolicy,
+}
+
+/// An arb search position
+#[derive(Clone, Debug, Deserialize)]
+struct PositionConfig {
+    /// Token symbol
+    token: String,
+    /// Max. position size in whole tokens, arbs are sized up to it
+    size: u32,
+}
+
+/// Order risk limits
+#[derive(Clone, Debug, Deserialize)]
+struct RiskConfig {
+    /// Max. orders submitted at the prices of a block
+    max_trades_per_block: Option<u32>,
+    #[serde(default)]
+    tokens: Vec<TokenRiskConfig>,
+}
+
+/// Order risk limits of an input token
+#[derive(Clone, Debug, Deserialize)]
+struct TokenRiskConfig {
+    /// Token symbol
+    token: String,
+    /// Max. amount in of a single order in whole tokens
+    max_amount_in: Option<u32>,
+    /// Max. total amount in of orders per (UTC) day in whole tokens
+    max_daily_notional: Option<u32>,
+}
+
+/// The config file layout
+#[derive(Deserialize)]
+struct ConfigFile {
+    tokens: Vec<TokenConfig>,
+    pools: Vec<PoolConfig>,
+    #[serde(default)]
+    routers: Vec<RouterConfig>,
+    /// Routers without a calldata decoder and how their calls are handled
+    #[serde(default)]
+    opaque_routers: Vec<OpaqueRouterConfig>,
+    /// How calls to unknown contracts are handled
+    #[serde(default)]
+    unknown_router_policy: OpaqueRouterPolicy,
+    positions: Vec<PositionConfig>,
+    /// Max. trades per arb cycle
+    #[serde(default = "default_max_hops")]
+    max_hops: usize,
+    /// Order tx submission endpoints and strategy
+    #[serde(default)]
+    submission: SubmissionConfig,
+    /// How pool prices are synced
+    #[serde(default)]
+    price_sync: PriceSyncMode,
+    /// Ramses pair factory, derives pair addresses
+    ramses: Option<SolidlyFactory>,
+    /// Order risk limits
+    risk: Option<RiskConfig>,
+    /// Name of the order encoder for the executor contract
+    #[serde(default = "default_order_encoder")]
+    order_encoder: String,
+    /// Alert destinations
+    #[cfg(feature = "notify")]
+    notify: Option<NotifyConfig>,
+    /// Feed decoding arena sizing
+    #[serde(default)]
+    arena: ArenaConfig,
+}
+
+fn default_max_hops() -> usize {
+    3
+}
+
+fn default_order_encoder() -> String {
+    DEFAULT_ORDER_ENCODER.to_string()
+}
+
+/// A configured trading pool
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pool {
+    pub pair: Pair,
+    pub address: Address,
+    /// Sync the pool's prices, otherwise the pool is only known for decoding trades
+    pub sync: bool,
+}
+
+/// The engine's trading universe
+#[derive(Clone, Debug)]
+pub struct Config {
+    registry: TokenRegistry,
+    pools: Vec<Pool>,
+    routers: Vec<(Address, RouterId)>,
+    opaque_routers: Vec<(Address, OpaqueRouterPolicy)>,
+    /// How calls to unknown contracts are handled
+    unknown_router_policy: OpaqueRouterPolicy,
+    /// Curve pool coins by pool address (coin 0, coin 1)
+    curve_coins: Vec<(Address, (Token, Token))>,
+    /// Search positions (token, max. size in whole tokens)
+    positions: Vec<(Token, u32)>,
+    /// Max. trades per arb cycle
+    max_hops: usize,
+    /// Order tx submission endpoints and strategy
+    submission: SubmissionConfig,
+    /// How pool prices are synced
+    price_sync: PriceSyncMode,
+    /// Ramses pair factory
+    ramses: Option<SolidlyFactory>,
+    /// Order risk limits
+    risk: Option<RiskLimits>,
+    /// Name of the order encoder for the executor contract
+    order_encoder: String,
+    /// Alert destinations
+    #[cfg(feature = "notify")]
+    notify: Option<NotifyConfig>,
+    /// Feed decoding arena sizing
+    arena: ArenaConfig,
+}
+
+impl Config {
+    /// The bundled Arbitrum One config
+    pub fn arbitrum_one() -> Self {
+        Self::from_toml(ARBITRUM_ONE).expect("bundled config is valid")
+    }
+    /// Load a config from the TOML file at `path`
+    pub fn from_file<P: AsRef<FilePath>>(path: P) -> Result<Self, ConfigError> {
+        let raw = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Self::from_toml(&raw)
+    }
+    /// Parse and validate a config from `raw` TOML
+    pub fn from_toml(raw: &str) -> Result<Self, ConfigError> {
+        let file: ConfigFile = toml::from_str(raw).map_err(ConfigError::Parse)?;
+        if !(2..=MAX_HOPS).contains(&file.max_hops) {
+            return Err(ConfigError::MaxHops(file.max_hops));
+        }
+        if file.submission.endpoints.is_empty() {
+            return Err(ConfigError::NoSubmissionEndpoints);
+        }
+
+        let mut registry = TokenRegistry::default();
+        for token in file.tokens {
+            let info = TokenInfo {
+                symbol: token.symbol,
+                address: token.address,
+                decimals: token.decimals,
+            };
+            registry
+                .register(token.id, info)
+                .map_err(ConfigError::Token)?;
+        }
+        let resolve = |symbol: &str| {
+            registry
+                .by_symbol(symbol)
+                .ok_or_else(|| ConfigError::UnknownToken(symbol.to_string()))
+        };
+
+        let mut pools = Vec::with_capacity(file.pools.len());
+        let mut curve_coins = Vec::new();
+        for pool in file.pools {
+            let (a, b) = (resolve(&pool.tokens.0)?, resolve(&pool.tokens.1)?);
+            let fee = Fee::from_quoted(pool.exchange, pool.fee as u32);
+            let pair = registry.pair(a, b, fee, pool.exchange);
+            let address = match pool.address {
+                Some(address) => address,
+                None if pool.exchange == ExchangeId::Uniswap => uniswap_v3::pool_address_for(
+                    registry.address(pair.token0),
+                    registry.address(pair.token1),
+                    fee.pips(),
+                    UNISWAP_V3_FACTORY.into(),
+                    &UNISWAP_V3_INIT_CODE_HASH,
+                ),
+                None if pool.exchange == ExchangeId::Ramses => match file.ramses {
+                    Some(factory) => {
+                        factory.pair_address(registry.address(a), registry.address(b), pool.stable)
+                    }
+                    None => return Err(ConfigError::MissingPoolAddress(pair)),
+                },
+                None => return Err(ConfigError::MissingPoolAddress(pair)),
+            };
+            if pool.exchange == ExchangeId::Curve {
+                curve_coins.push((address, (a, b)));
+            }
+            pools.push(Pool {
+                pair,
+                address,
+                sync: pool.sync,
+            });
+        }
+        let positions = file
+            .positions
+            .iter()
+            .map(|p| resolve(&p.token).map(|token| (token, p.size)))
+            .collect::<Result<_, _>>()?;
+        let risk = match file.risk {
+            Some(risk) => {
+                let mut limits = RiskLimits::default();
+                if let Some(trades) = risk.max_trades_per_block {
+                    limits = limits.with_max_trades_per_block(trades);
+                }
+                for token_risk in risk.tokens.iter() {
+                    let token = resolve(&token_risk.token)?;
+                    let one = one_token(registry.decimals(token));
+                    if let Some(amount) = token_risk.max_amount_in {
+                        limits = limits.with_max_amount_in(token, amount as u128 * one);
+                    }
+                    if let Some(amount) = token_risk.max_daily_notional {
+                        limits = limits.with_max_daily_notional(token, amount as u128 * one);
+                    }
+                }
+                Some(limits)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            pools,
+            positions,
+            routers: file.routers.iter().map(|r| (r.address, r.id)).collect(),
+            opaque_routers: file
+                .opaque_routers
+                .iter()
+                .map(|r| (r.address, r.policy))
+                .collect(),
+            unknown_router_policy: file.unknown_router_policy,
+            curve_coins,
+            registry,
+            max_hops: file.max_hops,
+            submission: file.submission,
+            price_sync: file.price_sync,
+            ramses: file.ramses,
+            risk,
+            order_encoder: file.order_encoder,
+            #[cfg(feature = "notify")]
+            notify: file.notify,
+            arena: file.arena,
+        })
+    }
+    /// The configured tokens
+    pub fn registry(&self) -> &TokenRegistry {
+        &self.registry
+    }
+    /// The configured pools
+    pub fn pools(&self) -> &[Pool] {
+        &self.pools
+    }
+    /// Add `pools` e.g. from discovery, pools at already configured addresses are skipped
+    ///
+    /// Returns the number of pools added
+    pub fn add_pools(&mut self, pools: Vec<Pool>) -> usize {
+        let configured = self.pools.len();
+        for pool in pools {
+            if !self.pools.iter().any(|p| p.address == pool.address) {
+                self.pools.push(pool);
+            }
+        }
+        self.pools.len() - configured
+    }
+    /// Price synced uniswap v2 style pairs and their pool addresses
+    pub fn uniswap_v2_pairs(&self) -> Vec<(Pair, Address)> {
+        self.synced_pairs(|exchange| {
+            exchange != ExchangeId::Uniswap
+                && exchange != ExchangeId::Camelot
+                && exchange != ExchangeId::UniswapV4
+                && exchange != ExchangeId::Balancer
+                && exchange != ExchangeId::Curve
+                && exchange != ExchangeId::Ramses
+                && exchange != ExchangeId::KyberElastic
+        })
+    }
+    /// Price synced camelot v2 pairs and their pool addresses
+    pub fn camelot_v2_pairs(&self) -> Vec<(Pair, Address)> {
+        self.synced_pairs(|exchange| exchange == ExchangeId::Camelot)
+    }
+    /// Price synced balancer weighted pool pairs and their pool addresses
+    ///
+    /// Pools of more than two tokens are configured once per traded pair
+    pub fn balancer_pairs(&self) -> Vec<(Pair, Address)> {
+        self.synced_pairs(|exchange| exchange == ExchangeId::Balancer)
+    }
+    /// Price synced curve stable pool pairs and their pool addresses
+    pub fn curve_pairs(&self) -> Vec<(Pair, Address)> {
+        self.synced_pairs(|exchange| exchange == ExchangeId::Curve)
+    }
+    /// Price synced solidly (ramses) pairs and their pool addresses
+    pub fn solidly_pairs(&self) -> Vec<(Pair, Address)> {
+        self.synced_pairs(|exchange| exchange == ExchangeId::Ramses)
+    }
+    /// Price synced kyberswap elastic pools and their pool addresses
+    pub fn kyber_elastic_pairs(&self) -> Vec<(Pair, Address)> {
+        self.synced_pairs(|exchange| exchange == ExchangeId::KyberElastic)
+    }
+    /// Price synced uniswap v3 pairs and their pool addresses
+    pub fn uniswap_v3_pairs(&self) -> Vec<(Pair, Address)> {
+        self.synced_pairs(|exchange| exchange == ExchangeId::Uniswap)
+    }
+    fn synced_pairs(&self, f: impl Fn(ExchangeId) -> bool) -> Vec<(Pair, Address)> {
+        self.pools
+            .iter()
+            .filter(|p| p.sync && f(p.pair.exchange_id))
+            .map(|p| (p.pair, p.address))
+            .collect()
+    }
+    /// Arb search positions
+    pub fn positions(&self) -> Vec<Position> {
+        self.positions
+            .iter()
+            .map(|(token, size)| Position::new(self.units(*token, *size), *token))
+            .collect()
+    }
+    /// Max. trades per arb cycle searched from each position
+    pub fn max_hops(&self) -> usize {
+        self.max_hops
+    }
+    /// Order tx submission endpoints and strategy
+    pub fn submission(&self) -> &SubmissionConfig {
+        &self.submission
+    }
+    /// How pool prices are synced
+    pub fn price_sync(&self) -> PriceSyncMode {
+        self.price_sync
+    }
+    /// The ramses pair factory, if configured
+    pub fn ramses(&self) -> Option<SolidlyFactory> {
+        self.ramses
+    }
+    /// Order risk limits, if configured
+    pub fn risk_limits(&self) -> Option<&RiskLimits> {
+        self.risk.as_ref()
+    }
+    /// Name of the order encoder for the executor contract, see `OrderEncoders`
+    pub fn order_encoder(&self) -> &str {
+        &self.order_encoder
+    }
+    /// Alert destinations, if configured
+    #[cfg(feature = "notify")]
+    pub fn notify(&self) -> Option<&NotifyConfig> {
+        self.notify.as_ref()
+    }
+    /// Feed decoding arena sizing
+    pub fn arena(&self) -> ArenaConfig {
+        self.arena
+    }
+    /// Map from pool address to its pair (all exchanges)
+    pub fn pool_lookup(&self) -> AddressMap<Pair> {
+        self.pools.iter().map(|p| (p.address.0, p.pair)).collect()
+    }
+    /// Map from router address to router Id
+    pub fn router_lookup(&self) -> AddressMap<RouterId> {
+        self.routers
+            .iter()
+            .map(|(address, id)| (address.0, *id))
+            .collect()
+    }
+    /// Map from opaque router address to the handling of its calls
+    pub fn opaque_router_lookup(&self) -> AddressMap<OpaqueRouterPolicy> {
+        self.opaque_routers
+            .iter()
+            .map(|(address, policy)| (address.0, *policy))
+            .collect()
+    }
+    /// How calls to contracts which are neither known nor opaque routers are handled
+    pub fn unknown_router_policy(&self) -> OpaqueRouterPolicy {
+        self.unknown_router_policy
+    }
+    /// Map from curve pool address to its coins (coin 0, coin 1)
+    pub fn curve_coins(&self) -> AddressMap<(Token, Token)> {
+        self.curve_coins
+            .iter()
+            .map(|(address, coins)| (address.0, *coins))
+            .collect()
+    }
+    /// Heuristic trade amounts by token Id, the position size or otherwise one whole token
+    pub fn one_lookup_table(&self) -> [u128; MAX_TOKENS] {
+        let mut lookup_table = [0_u128; MAX_TOKENS];
+        for (token, _) in self.registry.tokens() {
+            lookup_table[token.index()] = self
+                .positions
+                .iter()
+                .find(|(t, _)| *t == token)
+                .map(|(_, size)| self.units(token, *size))
+                .unwrap_or_else(|| self.units(token, 1));
+        }
+        lookup_table
+    }
+    /// `size` whole tokens in units
+    fn units(&self, token: Token, size: u32) -> u128 {
+        size as u128 * one_token(self.registry.decimals(token))
+    }
+}
+
+/// Install `config` as the engine's trading universe
+///
+/// Must be called before the engine starts, lookups use the bundled Arbitrum One config otherwise
+pub fn install(config: Config) -> Result<&'static Config, ConfigError> {
+    CONFIG
+        .set(config)
+        .map_err(|_| ConfigError::AlreadyInstalled)?;
+    Ok(active())
+}
+
+/// The installed config
+pub(crate) fn active() -> &'static Config {
+    CONFIG.get_or_init(Config::arbitrum_one)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        arena::DEFAULT_WARN_PERCENT,
+        constant::arbitrum::{ARB, DAI, GMX, USDC, USDT, WBTC, WETH},
+        submission::SubmitStrategy,
+    };
+
+    #[test]
+    fn arbitrum_one_config() {
+        let config = Config::arbitrum_one();
+        assert_eq!(config.uniswap_v3_pairs().len(), 10);
+        assert_eq!(config.uniswap_v2_pairs().len(), 4);
+        assert_eq!(config.camelot_v2_pairs().len(), 2);
+        assert_eq!(config.pool_lookup().len(), 23);
+        assert_eq!(config.curve_coins().len(), 1);
+        assert_eq!(config.router_lookup().len(), 15);
+        assert_eq!(config.max_hops(), 3);
+        assert_eq!(config.submission(), &SubmissionConfig::default());
+        assert_eq!(config.price_sync(), PriceSyncMode::Poll);
+        // ids match the executor contract
+        let registry = config.registry();
+        for (token, address) in [
+            (Token::USDC, USDC),
+            (Token::WETH, WETH),
+            (Token::WBTC, WBTC),
+            (Token::ARB, ARB),
+            (Token::USDT, USDT),
+            (Token::DAI, DAI),
+            (Token::GMX, GMX),
+        ] {
+            assert_eq!(registry.by_address(&address), Some(token));
+        }
+        // configured uniswap addresses match the derived pool addresses
+        for pool in config
+            .pools()
+            .iter()
+            .filter(|p| p.pair.exchange_id == ExchangeId::Uniswap)
+        {
+            assert_eq!(
+                uniswap_v3::pool_address_from_pair(
+                    pool.pair,
+                    UNISWAP_V3_FACTORY.into(),
+                    &UNISWAP_V3_INIT_CODE_HASH
+                ),
+                pool.address
+            );
+        }
+        let positions = config.positions();
+        assert_eq!(positions[1].token, Token::WETH);
+        assert_eq!(positions[1].amount, 3 * 10_u128.pow(18));
+        let table = config.one_lookup_table();
+        assert_eq!(table[Token::USDC.index()], 5_000 * 10_u128.pow(6));
+        assert_eq!(table[Token::WBTC.index()], 10_u128.pow(8));
+        assert_eq!(table[MAX_TOKENS - 1], 0);
+    }
+
+    #[test]
+    fn config_registers_tokens() {
+        let config = Config::from_toml(
+            r#"
+            tokens = [
+                { id = 0, symbol = "AAA", address = "0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", decimals = 6 },
+                { id = 9, symbol = "BBB", address = "0x0bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb", decimals = 18 },
+            ]
+            pools = [{ exchange = "sushi", tokens = ["AAA", "BBB"], fee = 300, address = "0x1111111111111111111111111111111111111111" }]
+            positions = [{ token = "BBB", size = 2 }]
+        "#,
+        )
+        .unwrap();
+        let registry = config.registry();
+        let bbb = registry.by_symbol("BBB").unwrap();
+        assert_eq!(bbb.id(), 9);
+        // ordered by address
+        assert_eq!(config.uniswap_v2_pairs()[0].0.token0, bbb);
+        assert_eq!(config.positions()[0].amount, 2 * 10_u128.pow(18));
+        assert_eq!(config.one_lookup_table()[0], 10_u128.pow(6));
+    }
+
+    #[test]
+    fn config_validates_tokens() {
+        let unknown = r#"
+            tokens = [{ id = 1, symbol = "WETH", address = "0x82af49447d8a07e3bd95bd0d56f35241523fbab1", decimals = 18 }]
+            pools = [{ exchange = "uniswap", tokens = ["WETH", "ARB"], fee = 500 }]
+            positions = []
+        "#;
+        assert!(matches!(
+            Config::from_toml(unknown),
+            Err(ConfigError::UnknownToken(symbol)) if symbol == "ARB"
+        ));
+        let duplicate = r#"
+            tokens = [
+                { id = 1, symbol = "WETH", address = "0x82af49447d8a07e3bd95bd0d56f35241523fbab1", decimals = 18 },
+                { id = 1, symbol = "ARB", address = "0x912ce59144191c1204e64559fe8253a0e49e6548", decimals = 18 },
+            ]
+            pools = []
+            positions = []
+        "#;
+        assert!(matches!(
+            Config::from_toml(duplicate),
+            Err(ConfigError::Token(RegistryError::Duplicate(_)))
+        ));
+        let no_address = r#"
+            tokens = [
+                { id = 1, symbol = "WETH", address = "0x82af49447d8a07e3bd95bd0d56f35241523fbab1", decimals = 18 },
+                { id = 3, symbol = "ARB", address = "0x912ce59144191c1204e64559fe8253a0e49e6548", decimals = 18 },
+            ]
+            pools = [{ exchange = "sushi", tokens = ["WETH", "ARB"], fee = 300 }]
+            positions = [{ token = "WETH", size = 1 }]
+        "#;
+        assert!(matches!(
+            Config::from_toml(no_address),
+            Err(ConfigError::MissingPoolAddress(_))
+        ));
+        let max_hops = r#"
+            tokens = []
+            pools = []
+            positions = []
+            max_hops = 6
+        "#;
+        assert!(matches!(
+            Config::from_toml(max_hops),
+            Err(ConfigError::MaxHops(6))
+        ));
+        let no_endpoints = r#"
+            tokens = []
+            pools = []
+            positions = []
+            [submission]
+            endpoints = []
+        "#;
+        assert!(matches!(
+            Config::from_toml(no_endpoints),
+            Err(ConfigError::NoSubmissionEndpoints)
+        ));
+    }
+
+    #[test]
+    fn submission_config() {
+        let config = Config::from_toml(
+            r#"
+            tokens = []
+            pools = []
+            positions = []
+            price_sync = "new_heads"
+            [submission]
+            endpoints = ["http://localhost:8547"]
+            strategy = "fallback"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.price_sync(), PriceSyncMode::NewHeads);
+        assert_eq!(
+            config.submission(),
+            &SubmissionConfig {
+                endpoints: vec!["http://localhost:8547".to_string()],
+                strategy: SubmitStrategy::Fallback,
+            }
+        );
+    }
+
+    #[test]
+    fn pipelined_price_sync() {
+        let config = Config::from_toml(
+            r#"
+            tokens = []
+            pools = []
+            positions = []
+            price_sync = "pipelined"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.price_sync(), PriceSyncMode::Pipelined);
+    }
+
+    #[test]
+    fn risk_limits_config() {
+        let config = Config::from_toml(
+            r#"
+            tokens = [{ id = 1, symbol = "WETH", address = "0x82af49447d8a07e3bd95bd0d56f35241523fbab1", decimals = 18 }]
+            pools = []
+            positions = []
+
+            [risk]
+            max_trades_per_block = 2
+            tokens = [{ token = "WETH", max_amount_in = 5, max_daily_notional = 100 }]
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.risk_limits(),
+            Some(
+                &RiskLimits::default()
+                    .with_max_trades_per_block(2)
+                    .with_max_amount_in(Token::WETH, 5 * 10_u128.pow(18))
+                    .with_max_daily_notional(Token::WETH, 100 * 10_u128.pow(18))
+            )
+        );
+        assert!(Config::arbitrum_one().risk_limits().is_none());
+    }
+
+    #[test]
+    fn order_encoder_config() {
+        let config = Config::from_toml(
+            r#"
+            order_encoder = "fulcrum_path"
+            tokens = []
+            pools = []
+            positions = []
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.order_encoder(), "fulcrum_path");
+        assert_eq!(
+            Config::arbitrum_one().order_encoder(),
+            DEFAULT_ORDER_ENCODER
+        );
+    }
+
+    #[test]
+    fn arena_config() {
+        let config = Config::from_toml(
+            r#"
+            tokens = []
+            pools = []
+            positions = []
+
+            [arena]
+            capacity_bytes = 4_194_304
+            txs = 1_000
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.arena(),
+            ArenaConfig {
+                capacity_bytes: 4_194_304,
+                txs: 1_000,
+                warn_percent: DEFAULT_WARN_PERCENT,
+            }
+        );
+        assert_eq!(Config::arbitrum_one().arena(), ArenaConfig::default());
+    }
+
+    #[cfg(feature = "notify")]
+    #[test]
+    fn notify_config() {
+        use crate::notify::{TelegramConfig, DEFAULT_FEED_DOWN_SECS};
+
+        let config = Config::from_toml(
+            r#"
+            tokens = []
+            pools = []
+            positions = []
+
+            [notify]
+            discord = "https://discord.com/api/webhooks/1/a"
+            telegram = { bot_token = "123:abc", chat_id = "@fulcrum" }
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.notify(),
+            Some(&NotifyConfig {
+                discord: Some("https://discord.com/api/webhooks/1/a".to_string()),
+                slack: None,
+                telegram: Some(TelegramConfig {
+                    bot_token: "123:abc".to_string(),
+                    chat_id: "@fulcrum".to_string(),
+                }),
+                feed_down_secs: DEFAULT_FEED_DOWN_SECS,
+            })
+        );
+        assert!(Config::arbitrum_one().notify().is_none());
+    }
+
+    #[test]
+    fn balancer_pools() {
+        let config = Config::from_toml(
+            r#"
+            tokens = [
+                { id = 0, symbol = "USDC", address = "0xff970a61a04b1ca14834a43f5de4533ebddb5cc8", decimals = 6 },
+                { id = 1, symbol = "WETH", address = "0x82af49447d8a07e3bd95bd0d56f35241523fbab1", decimals = 18 },
+                { id = 2, symbol = "WBTC", address = "0x2f2a2543b76a4166549f7aab2e75bef0aefc5b0f", decimals = 8 },
+            ]
+            pools = [
+                { exchange = "balancer", tokens = ["WETH", "USDC"], address = "0x64541216bafffeec8ea535bb71fbc927831d0595" },
+                { exchange = "balancer", tokens = ["WBTC", "USDC"], address = "0x64541216bafffeec8ea535bb71fbc927831d0595" },
+            ]
+            positions = []
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.balancer_pairs().len(), 2);
+        assert!(config.uniswap_v2_pairs().is_empty());
+        assert_eq!(
+            config.balancer_pairs()[0].0.exchange_id,
+            ExchangeId::Balancer
+        );
+    }
+
+    #[test]
+    fn curve_pools() {
+        let config = Config::from_toml(
+            r#"
+            tokens = [
+                { id = 0, symbol = "USDC", address = "0xff970a61a04b1ca14834a43f5de4533ebddb5cc8", decimals = 6 },
+                { id = 4, symbol = "USDT", address = "0xfd086bc7cd5c481dcc9c85ebe478a1c0b69fcbb9", decimals = 6 },
+            ]
+            pools = [
+                { exchange = "curve", tokens = ["USDC", "USDT"], address = "0x7f90122bf0700f9e7e1f688fe926940e8839f353" },
+            ]
+            positions = []
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.curve_pairs().len(), 1);
+        assert!(config.uniswap_v2_pairs().is_empty());
+        // pairs are sorted, coins keep the configured order
+        let (pair, address) = config.curve_pairs()[0];
+        assert_eq!(pair.token0, Token::USDT);
+        assert_eq!(
+            config.curve_coins().get(&address.0),
+            Some(&(Token::USDC, Token::USDT))
+        );
+    }
+
+    #[test]
+    fn ramses_pools() {
+        let config = Config::from_toml(
+            r#"
+            tokens = [
+                { id = 0, symbol = "USDC", address = "0xff970a61a04b1ca14834a43f5de4533ebddb5cc8", decimals = 6 },
+                { id = 1, symbol = "WETH", address = "0x82af49447d8a07e3bd95bd0d56f35241523fbab1", decimals = 18 },
+                { id = 4, symbol = "USDT", address = "0xfd086bc7cd5c481dcc9c85ebe478a1c0b69fcbb9", decimals = 6 },
+            ]
+            pools = [
+                { exchange = "ramses", tokens = ["WETH", "USDC"], fee = 200 },
+                { exchange = "ramses", tokens = ["USDC", "USDT"], fee = 50, stable = true },
+                { exchange = "ramses", tokens = ["WETH", "USDT"], fee = 200, address = "0x1111111111111111111111111111111111111111" },
+            ]
+            positions = []
+
+            [ramses]
+            factory = "0xaaa20d08e59f6561f242b08513d36266c5a29415"
+            init_code_hash = "0x2222222222222222222222222222222222222222222222222222222222222222"
+        "#,
+        )
+        .unwrap();
+        let factory = config.ramses().unwrap();
+        let pairs = config.solidly_pairs();
+        assert_eq!(pairs.len(), 3);
+        assert!(config.uniswap_v2_pairs().is_empty());
+        // addresses are derived unless configured
+        assert_eq!(
+            pairs[0].1,
+            factory.pair_address(WETH.into(), USDC.into(), false)
+        );
+        assert_eq!(
+            pairs[1].1,
+            factory.pair_address(USDC.into(), USDT.into(), true)
+        );
+        assert_eq!(pairs[2].1, Address::repeat_byte(0x11));
+
+        // no factory to derive from
+        let err = Config::from_toml(
+            r#"
+            tokens = [
+                { id = 0, symbol = "USDC", address = "0xff970a61a04b1ca14834a43f5de4533ebddb5cc8", decimals = 6 },
+                { id = 1, symbol = "WETH", address = "0x82af49447d8a07e3bd95bd0d56f35241523fbab1", decimals = 18 },
+            ]
+            pools = [{ exchange = "ramses", tokens = ["WETH", "USDC"], fee = 200 }]
+            positions = []
+        "#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ConfigError::MissingPoolAddress(_)));
+    }
+
+    #[test]
+    fn kyber_elastic_pools() {
+        let config = Config::from_toml(
+            r#"
+            tokens = [
+                { id = 0, symbol = "USDC", address = "0xff970a61a04b1ca14834a43f5de4533ebddb5cc8", decimals = 6 },
+                { id = 1, symbol = "WETH", address = "0x82af49447d8a07e3bd95bd0d56f35241523fbab1", decimals = 18 },
+            ]
+            pools = [
+                { exchange = "kyberelastic", tokens = ["WETH", "USDC"], fee = 40, address = "0x1111111111111111111111111111111111111111" },
+            ]
+            positions = []
+        "#,
+        )
+        .unwrap();
+        let pairs = config.kyber_elastic_pairs();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].0.fee, Fee::from_units(40));
+        assert!(config.uniswap_v2_pairs().is_empty());
+        assert!(config.uniswap_v3_pairs().is_empty());
+    }
+
+    #[test]
+    fn opaque_routers() {
+        let config = Config::from_toml(
+            r#"
+            tokens = []
+            pools = []
+            positions = []
+            unknown_router_policy = "tokens"
+            opaque_routers = [
+                { address = "0x1111111111111111111111111111111111111111", policy = "skip_round" },
+                { address = "0x2222222222222222222222222222222222222222", policy = "ignore" },
+            ]
+        "#,
+        )
+        .unwrap();
+        let lookup = config.opaque_router_lookup();
+        assert_eq!(lookup.len(), 2);
+        assert_eq!(
+            lookup.get(&[0x11; 20]),
+            Some(&OpaqueRouterPolicy::SkipRound)
+        );
+        assert_eq!(lookup.get(&[0x22; 20]), Some(&OpaqueRouterPolicy::Ignore));
+        assert_eq!(config.unknown_router_policy(), OpaqueRouterPolicy::Tokens);
+        // unknown routers are ignored by default
+        assert_eq!(
+            Config::arbitrum_one().unknown_router_policy(),
+            OpaqueRouterPolicy::Ignore
+        );
+    }
+}
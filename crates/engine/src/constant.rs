@@ -26,6 +26,7 @@ pub mod arbitrum {
         hex!("4C60051384bd2d3C01bfc845Cf5F4b44bcbE9de5");
     pub const UNISWAP_V3_ROUTER_V1: [u8; 20] = hex!("E592427A0AEce92De3Edee1F18E0157C05861564");
     pub const UNISWAP_V3_ROUTER_V2: [u8; 20] = hex!("68b3465833fb72A70ecDF485E0e4C7bD8665Fc45");
+    pub const UNISWAP_V3_QUOTER_V2: [u8; 20] = hex!("61fFE014bA17989E743c5F6cB21bF9697530B21e");
     pub const LAYER_ZERO_SWAP_BRIDGE: [u8; 20] = hex!("0A9f824C05A74F577A536A8A0c673183a872Dff4");
     pub const PARASWAP_AUGUSTUS: [u8; 20] = hex!("DEF171Fe48CF0115B1d80b88dc8eAB59176FEe57");
     pub const ONE_INCH_ROUTER_V5: [u8; 20] = hex!("1111111254eeb25477b68fb85ed929f73a960582");
@@ -34,6 +35,8 @@ pub mod arbitrum {
     pub const CHRONOS_ROUTER: [u8; 20] = hex!("E708aA9E887980750C040a6A2Cb901c37Aa34f3b");
     pub const GMX_ROUTER: [u8; 20] = hex!("aBBc5F99639c9B6bCb58544ddf04EFA6802F4064");
     pub const ODOS_ROUTER: [u8; 20] = hex!("dd94018F54e565dbfc939F7C44a16e163FaAb331");
+    pub const BALANCER_VAULT: [u8; 20] = hex!("BA12222222228d8Ba445958a75a0704d566BF2C8");
+    pub const CURVE_2POOL: [u8; 20] = hex!("7f90122BF0700F9E7e1F688fe926940E8839F353");
 
     /// Arbitrum WETH token address
     pub const WETH: [u8; 20] = hex!("82aF49447D8a07e3bd95BD0d56f35241523fBab1");
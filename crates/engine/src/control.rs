@@ -0,0 +1,420 @@
+//! Runtime engine controls
+//!
+//! The min. profit, dry-run, and searched paths can be changed while the engine runs, keeping the feed connection.
+//! Controls are changed by line commands sent to a Unix socket (see `serve`) or read from a control file on `SIGHUP`
+//! (see `reload_on_signal`):
+//!
+//! - `min_profit <ratio>` e.g. `min_profit 0.002`
+//! - `dry_run on|off`
+//! - `disable <path>`/`enable <path>` a path by token symbols e.g. `disable WETH>USDC>ARB>WETH`
+//! - `status`
+use std::{
+    collections::BTreeSet,
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use log::{info, warn};
+
+use crate::{config, price_graph::Path, types::Token};
+
+/// A control command
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Set the min. profit of executed arbs e.g 0.007 = 0.007%
+    MinProfit(f64),
+    /// Turn dry-run on or off
+    DryRun(bool),
+    /// Stop searching the path of these tokens
+    Disable(Vec<Token>),
+    /// Resume searching the path of these tokens
+    Enable(Vec<Token>),
+    /// Report the current controls
+    Status,
+}
+
+impl FromStr for Command {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let (command, arg) = (words.next().unwrap_or_default(), words.next());
+        if words.next().is_some() {
+            return Err(format!("unexpected args: {s}"));
+        }
+        match (command, arg) {
+            ("min_profit", Some(arg)) => match arg.parse::<f64>() {
+                Ok(min_profit) if (0.0..=1.0).contains(&min_profit) => {
+                    Ok(Self::MinProfit(min_profit))
+                }
+                _ => Err(format!("min. profit {arg} not in 0.0..=1.0")),
+            },
+            ("dry_run", Some("on")) => Ok(Self::DryRun(true)),
+            ("dry_run", Some("off")) => Ok(Self::DryRun(false)),
+            ("disable", Some(arg)) => parse_path(arg).map(Self::Disable),
+            ("enable", Some(arg)) => parse_path(arg).map(Self::Enable),
+            ("status", None) => Ok(Self::Status),
+            _ => Err(format!("unknown command: {s}")),
+        }
+    }
+}
+
+/// Parse a path of token symbols e.g. `WETH>USDC>WETH`, it must end at its start token
+fn parse_path(s: &str) -> Result<Vec<Token>, String> {
+    let registry = config::active().registry();
+    let tokens = s
+        .split('>')
+        .map(|symbol| {
+            registry
+                .by_symbol(symbol)
+                .ok_or_else(|| format!("unknown token: {symbol}"))
+        })
+        .collect::<Result<Vec<Token>, String>>()?;
+    if tokens.len() < 3 || tokens.first() != tokens.last() {
+        return Err(format!("not a cycle: {s}"));
+    }
+    Ok(tokens)
+}
+
+/// Controls of a running engine, shared with the control socket and signal handler
+#[derive(Debug)]
+pub struct Controls {
+    /// Min. profit (`f64` bits)
+    min_profit: AtomicU64,
+    dry_run: AtomicBool,
+    /// Paths not searched, by tokens
+    disabled_paths: Mutex<BTreeSet<Vec<Token>>>,
+    /// Bumped on each change, the engine re-reads the controls once it changes
+    version: AtomicU64,
+    /// Min. profit and dry-run at start, restored by a reload
+    initial: (f64, bool),
+}
+
+impl Controls {
+    /// Controls starting with `min_profit` and `dry_run`, all paths enabled
+    pub fn new(min_profit: f64, dry_run: bool) -> Self {
+        Self {
+            min_profit: AtomicU64::new(min_profit.to_bits()),
+            dry_run: AtomicBool::new(dry_run),
+            disabled_paths: Mutex::default(),
+            version: AtomicU64::new(0),
+            initial: (min_profit, dry_run),
+        }
+    }
+    /// Min. profit of executed arbs
+    pub fn min_profit(&self) -> f64 {
+        f64::from_bits(self.min_profit.load(Ordering::Relaxed))
+    }
+    /// True if order txs are built but not submitted
+    pub fn dry_run(&self) -> bool {
+        self.dry_run.load(Ordering::Relaxed)
+    }
+    /// True if `path` is searched
+    pub fn is_enabled(&self, path: &Path) -> bool {
+        let tokens: Vec<Token> = path.tokens().collect();
+        !self
+            .disabled_paths
+            .lock()
+            .expect("controls lock")
+            .contains(&tokens)
+    }
+    /// Changes with each control change
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+    /// Apply `command`, returning its response
+    pub fn apply(&self, command: Command) -> String {
+        match command {
+            Command::MinProfit(min_profit) => {
+                self.min_profit
+                    .store(min_profit.to_bits(), Ordering::Relaxed);
+            }
+            Command::DryRun(dry_run) => self.dry_run.store(dry_run, Ordering::Relaxed),
+            Command::Disable(tokens) => {
+                self.disabled_paths
+                    .lock()
+                    .expect("controls lock")
+                    .insert(tokens);
+            }
+            Command::Enable(tokens) => {
+                self.disabled_paths
+                    .lock()
+                    .expect("controls lock")
+                    .remove(&tokens);
+            }
+            Command::Status => return self.to_string(),
+        }
+        self.version.fetch_add(1, Ordering::Release);
+        info!("controls changed 🎛️: {self}");
+        "ok".to_string()
+    }
+    /// Reset the controls to their initial values then apply the `commands` (one per line, `#` comments)
+    ///
+    /// Nothing is changed if any command is invalid
+    pub fn reload(&self, commands: &str) -> Result<(), String> {
+        let commands = commands
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty())
+            .map(Command::from_str)
+            .collect::<Result<Vec<Command>, String>>()?;
+        self.disabled_paths.lock().expect("controls lock").clear();
+        self.min_profit
+            .store(self.initial.0.to_bits(), Ordering::Relaxed);
+        self.dry_run.store(self.initial.1, Ordering::Relaxed);
+        for command in commands {
+            self.apply(command);
+        }
+        self.version.fetch_add(1, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl fmt::Display for Controls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min_profit: {} dry_run: {} disabled:",
+            self.min_profit(),
+            self.dry_run()
+        )?;
+        for tokens in self.disabled_paths.lock().expect("controls lock").iter() {
+            let symbols: Vec<String> = tokens.iter().map(|token| format!("{token:?}")).collect();
+            write!(f, " {}", symbols.join(">"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Serve control commands on the Unix socket at `path`, one response line per command line
+#[cfg(unix)]
+pub fn serve(controls: Arc<Controls>, path: PathBuf) {
+    use std::{
+        fs::{self, Permissions},
+        os::unix::fs::{FileTypeExt, PermissionsExt},
+    };
+
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::UnixListener,
+    };
+
+    use crate::http_server::ACCEPT_BACKOFF;
+
+    // a stale socket of a previous run, anything else at the path is left alone
+    match fs::symlink_metadata(&path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            if let Err(err) = fs::remove_file(&path) {
+                warn!("control socket: {:?}", err);
+                return;
+            }
+        }
+        Ok(_) => {
+            warn!(
+                "control socket: {} exists and is not a socket",
+                path.display()
+            );
+            return;
+        }
+        Err(_) => (),
+    }
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            warn!("control socket: {:?}", err);
+            return;
+        }
+    };
+    // commands change trading, owner only
+    if let Err(err) = fs::set_permissions(&path, Permissions::from_mode(0o600)) {
+        warn!("control socket: {:?}", err);
+        return;
+    }
+    info!("control socket 🎛️: {}", path.display());
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    warn!("control socket accept: {:?}", err);
+                    tokio::time::sleep(ACCEPT_BACKOFF).await;
+                    continue;
+                }
+            };
+            let controls = Arc::clone(&controls);
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let response = match line.parse::<Command>() {
+                        Ok(command) => controls.apply(command),
+                        Err(err) => format!("error: {err}"),
+                    };
+                    if writer
+                        .write_all(format!("{response}\n").as_bytes())
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn serve(_controls: Arc<Controls>, _path: PathBuf) {
+    warn!("control socket is not supported on this platform");
+}
+
+/// Reload `controls` from the control file at `path` now and whenever the process receives `SIGHUP`
+#[cfg(unix)]
+pub fn reload_on_signal(controls: Arc<Controls>, path: PathBuf) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let reload = move || match std::fs::read_to_string(&path) {
+        Ok(commands) => {
+            if let Err(err) = controls.reload(&commands) {
+                warn!("control file {}: {err}", path.display());
+            }
+        }
+        Err(err) => warn!("control file {}: {:?}", path.display(), err),
+    };
+    reload();
+    tokio::spawn(async move {
+        let mut signals = match signal(SignalKind::hangup()) {
+            Ok(signals) => signals,
+            Err(err) => {
+                warn!("control file signal: {:?}", err);
+                return;
+            }
+        };
+        while signals.recv().await.is_some() {
+            reload();
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn reload_on_signal(controls: Arc<Controls>, path: PathBuf) {
+    warn!("control file can't be reloaded by signal on this platform");
+    if let Ok(commands) = std::fs::read_to_string(&path) {
+        if let Err(err) = controls.reload(&commands) {
+            warn!("control file {}: {err}", path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        price_graph::PriceGraph,
+        types::{ExchangeId, Fee, Pair},
+    };
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(
+            "min_profit 0.002".parse::<Command>(),
+            Ok(Command::MinProfit(0.002))
+        );
+        assert_eq!("dry_run off".parse::<Command>(), Ok(Command::DryRun(false)));
+        assert_eq!(
+            " disable WETH>USDC>WETH ".parse::<Command>(),
+            Ok(Command::Disable(vec![
+                Token::WETH,
+                Token::USDC,
+                Token::WETH
+            ]))
+        );
+        assert_eq!("status".parse::<Command>(), Ok(Command::Status));
+        assert!("min_profit 2".parse::<Command>().is_err());
+        assert!("dry_run maybe".parse::<Command>().is_err());
+        assert!("enable WETH>USDC".parse::<Command>().is_err());
+        assert!("enable WETH>NOPE>WETH".parse::<Command>().is_err());
+        assert!("status now".parse::<Command>().is_err());
+        assert!("".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn controls_apply_and_reload() {
+        let pairs = &[
+            Pair::new(Token::USDC, Token::WETH, Fee::ZERO, ExchangeId::Camelot),
+            Pair::new(Token::USDC, Token::ARB, Fee::ZERO, ExchangeId::Sushi),
+            Pair::new(Token::WETH, Token::ARB, Fee::ZERO, ExchangeId::Sushi),
+        ];
+        let paths = PriceGraph::find_paths(Token::WETH, pairs, 3);
+        let controls = Controls::new(0.007, true);
+        let version = controls.version();
+        assert!(paths.iter().all(|path| controls.is_enabled(path)));
+
+        assert_eq!(controls.apply(Command::MinProfit(0.002)), "ok");
+        assert_eq!(controls.apply(Command::DryRun(false)), "ok");
+        assert_eq!(
+            controls.apply("disable WETH>USDC>WETH".parse().unwrap()),
+            "ok"
+        );
+        assert_ne!(controls.version(), version);
+        assert_eq!(controls.min_profit(), 0.002);
+        assert!(!controls.dry_run());
+        let enabled: Vec<Vec<Token>> = paths
+            .iter()
+            .filter(|path| controls.is_enabled(path))
+            .map(|path| path.tokens().collect())
+            .collect();
+        assert_eq!(enabled.len(), paths.len() - 1);
+        assert!(!enabled.contains(&vec![Token::WETH, Token::USDC, Token::WETH]));
+        assert_eq!(
+            controls.apply(Command::Status),
+            "min_profit: 0.002 dry_run: false disabled: WETH>USDC>WETH"
+        );
+
+        // invalid files change nothing
+        assert!(controls.reload("dry_run on\nmin_profit x").is_err());
+        assert!(!controls.dry_run());
+        // a reload starts from the initial controls
+        controls
+            .reload("# trade the ARB paths only\ndisable WETH>USDC>WETH\ndisable WETH>USDC>ARB>WETH # thin\n")
+            .unwrap();
+        assert_eq!(controls.min_profit(), 0.007);
+        assert!(controls.dry_run());
+        assert_eq!(
+            paths
+                .iter()
+                .filter(|path| controls.is_enabled(path))
+                .count(),
+            paths.len() - 2
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn serve_replaces_stale_sockets_only() {
+        use std::{
+            fs,
+            os::unix::{fs::PermissionsExt, net::UnixListener},
+        };
+
+        let path = std::env::temp_dir().join("fulcrum-control-serve.sock");
+        let _ = fs::remove_file(&path);
+
+        // not a socket, left alone
+        fs::write(&path, "keep").unwrap();
+        serve(Arc::new(Controls::new(0.007, true)), path.clone());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "keep");
+        fs::remove_file(&path).unwrap();
+
+        // a stale socket is replaced, owner only
+        drop(UnixListener::bind(&path).unwrap());
+        serve(Arc::new(Controls::new(0.007, true)), path.clone());
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+//! Curve StableSwap price source
+//!
+//! Plain 2 coin pools price with the StableSwap invariant (A·n^n·Σx + D = A·D·n^n + D^(n+1) / (n^n·Πx)),
+//! balances are normalized to 18 decimals and the fee is charged on the output token
+use crate::{
+    types::{Fee, U256},
+    util::saturating_u128,
+};
+
+/// Curve fees are in 1/10_000_000_000ths
+pub const FEE_DENOMINATOR: u128 = 10_000_000_000;
+
+/// Precision of the amplification coefficient in the invariant math, as `StableSwap.A_PRECISION`
+const A_PRECISION: u128 = 100;
+
+/// Number of coins in the pool
+const N: u128 = 2;
+
+/// Mirror pool 'get_dy' calculation
+///
+/// - `amp` the amplification coefficient as `StableSwap.A()`
+/// - `fee` the fee charged on the output token
+/// - `decimals` the (input, output) token decimals
+pub fn get_amount_out(
+    amp: u32,
+    fee: Fee,
+    amount_in: u128,
+    balance_in: u128,
+    balance_out: u128,
+    decimals: (u8, u8),
+) -> u128 {
+    if amp == 0 {
+        return 0;
+    }
+    let (precision_in, precision_out) = (precision(decimals.0), precision(decimals.1));
+    let (x, y) = (
+        U256::from(balance_in) * precision_in,
+        U256::from(balance_out) * precision_out,
+    );
+    let ann = ann(amp);
+    let d = get_d(x, y, ann);
+    let y_next = get_y(x + U256::from(amount_in) * precision_in, d, ann);
+    if y_next + 1 >= y {
+        return 0;
+    }
+
+    let dy = (y - y_next - 1) / precision_out;
+    let fee = dy * U256::from(fee_units(fee)) / U256::from(FEE_DENOMINATOR);
+    saturating_u128(dy.saturating_sub(fee))
+}
+
+/// Calculate the input amount required to receive `amount_out`
+///
+/// - `amp` the amplification coefficient as `StableSwap.A()`
+/// - `fee` the fee charged on the output token
+/// - `decimals` the (input, output) token decimals
+///
+/// Returns `u128::MAX` i.e. an unfillable amount, if `amount_out` would drain the pool
+pub fn get_amount_in(
+    amp: u32,
+    fee: Fee,
+    amount_out: u128,
+    balance_in: u128,
+    balance_out: u128,
+    decimals: (u8, u8),
+) -> u128 {
+    let (precision_in, precision_out) = (precision(decimals.0), precision(decimals.1));
+    let (x, y) = (
+        U256::from(balance_in) * precision_in,
+        U256::from(balance_out) * precision_out,
+    );
+    // the fee is charged on the (gross) output amount
+    let fee = fee_units(fee);
+    if fee >= FEE_DENOMINATOR || amp == 0 {
+        return u128::MAX;
+    }
+    let dy = (U256::from(amount_out) * U256::from(FEE_DENOMINATOR)
+        + U256::from(FEE_DENOMINATOR - fee - 1))
+        / U256::from(FEE_DENOMINATOR - fee);
+    let dy = (dy + 1) * precision_out;
+    if dy >= y {
+        return u128::MAX;
+    }
+    let ann = ann(amp);
+    let d = get_d(x, y, ann);
+    // the invariant is symmetric, solve for the input balance given the output balance
+    let x_next = get_y(y - dy, d, ann);
+    if x_next <= x {
+        return 0;
+    }
+
+    saturating_u128((x_next - x) / precision_in).saturating_add(1)
+}
+
+/// `get_amount_out` with float (speed > precision)
+pub fn get_amount_out_f(
+    amp: u32,
+    fee: Fee,
+    amount_in: u128,
+    balance_in: u128,
+    balance_out: u128,
+    decimals: (u8, u8),
+) -> f64 {
+    get_amount_out(amp, fee, amount_in, balance_in, balance_out, decimals) as f64
+}
+
+/// `fee` in 1/10_000_000_000ths
+fn fee_units(fee: Fee) -> u128 {
+    fee.pips() as u128 * (FEE_DENOMINATOR / Fee::PIPS as u128)
+}
+
+/// Multiplier normalizing a token with `decimals` to 18 decimals
+fn precision(decimals: u8) -> U256 {
+    U256::exp10(18_u8.saturating_sub(decimals) as usize)
+}
+
+/// A·n^(n-1) scaled by `A_PRECISION`, as `StableSwap.get_D` `Ann`
+fn ann(amp: u32) -> U256 {
+    U256::from(amp as u128 * A_PRECISION * N)
+}
+
+/// Solve the invariant for D given normalized balances `x`, `y` (newton's method) as `StableSwap.get_D`
+fn get_d(x: U256, y: U256, ann: U256) -> U256 {
+    let s = x + y;
+    if s.is_zero() || x.is_zero() || y.is_zero() {
+        return U256::zero();
+    }
+    let (n, a_precision) = (U256::from(N), U256::from(A_PRECISION));
+    let mut d = s;
+    for _ in 0..255 {
+        let d_p = d * d / (x * n) * d / (y * n);
+        let d_prev = d;
+        d = (ann * s / a_precision + d_p * n) * d
+            / ((ann - a_precision) * d / a_precision + (n + 1) * d_p);
+        if converged(d, d_prev) {
+            return d;
+        }
+    }
+    d
+}
+
+/// Solve the invariant for the other normalized balance given `x` and `d` (newton's method) as `StableSwap.get_y`
+fn get_y(x: U256, d: U256, ann: U256) -> U256 {
+    if x.is_zero() || d.is_zero() {
+        return U256::zero();
+    }
+    let (n, a_precision) = (U256::from(N), U256::from(A_PRECISION));
+    let c = d * d / (x * n) * d * a_precision / (ann * n);
+    let b = x + d * a_precision / ann;
+    let mut y = d;
+    for _ in 0..255 {
+        let y_prev = y;
+        y = (y * y + c) / (U256::from(2) * y + b - d);
+        if converged(y, y_prev) {
+            return y;
+        }
+    }
+    y
+}
+
+/// Newton's method converges within 1 wei
+fn converged(a: U256, b: U256) -> bool {
+    (if a > b { a - b } else { b - a }) <= U256::one()
+}
+
+/// Curve pool state of a token pair as returned by the viewer contract
+#[derive(Debug, PartialEq)]
+pub struct CurveStableBalances {
+    pub balance_0: u128,
+    pub balance_1: u128,
+    /// amplification coefficient
+    pub amp: u32,
+    /// fee charged on the output token
+    pub fee: u32,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::uniswap_v2;
+
+    /// 0.04%
+    const FEE: Fee = Fee::from_units(40);
+
+    #[test]
+    fn get_amount_out_balanced() {
+        // 10m USDC / 10m USDT, A = 1000
+        let balance = 10_000_000 * 10_u128.pow(6);
+        let amount_in = 100_000 * 10_u128.pow(6);
+        let amount_out = get_amount_out(1_000, FEE, amount_in, balance, balance, (6, 6));
+        // ~1:1 less the fee
+        assert!(amount_out < amount_in * 9_996 / 10_000);
+        assert!(amount_out > amount_in * 9_990 / 10_000);
+        // far less slippage than constant product
+        assert!(amount_out > uniswap_v2::get_amount_out(Fee::ZERO, amount_in, balance, balance));
+
+        let amount_in_required = get_amount_in(1_000, FEE, amount_out, balance, balance, (6, 6));
+        assert!(amount_in_required >= amount_in);
+        assert!(amount_in_required - amount_in <= 2);
+    }
+
+    #[test]
+    fn get_amount_out_decimals() {
+        // 1m USDC (6 decimals) / 1m DAI (18 decimals)
+        let amount_out = get_amount_out(
+            200,
+            Fee::ZERO,
+            1_000 * 10_u128.pow(6),
+            1_000_000 * 10_u128.pow(6),
+            1_000_000 * 10_u128.pow(18),
+            (6, 18),
+        );
+        assert!(amount_out < 1_000 * 10_u128.pow(18));
+        assert!(amount_out > 999 * 10_u128.pow(18));
+    }
+
+    #[test]
+    fn drained_pool() {
+        assert_eq!(
+            get_amount_in(1_000, FEE, 1_000, 1_000_000, 1_000, (6, 6)),
+            u128::MAX
+        );
+        assert_eq!(get_amount_out(1_000, FEE, 1_000, 0, 0, (6, 6)), 0);
+    }
+}
@@ -0,0 +1,187 @@
+//! Pool discovery
+//!
+//! Queries the Uniswap V3, Sushi, and Camelot factories for the pools of every configured token pair at startup.
+//! Pools are verified against the factory's CREATE2 init code hash i.e. they were deployed with the expected code
+//!
+//! NB: runs before the config is installed, so tokens are resolved via the given registry only
+use std::sync::Arc;
+
+use ethers::{prelude::abigen, types::Address};
+use ethers_providers::Middleware;
+use futures::future::join_all;
+use log::{info, warn};
+
+use crate::{
+    config::Pool,
+    constant::arbitrum::{
+        CAMELOT_FACTORY, CAMELOT_INIT_CODE_HASH, SUSHI_FACTORY, SUSHI_INIT_CODE_HASH,
+        UNISWAP_V3_FACTORY, UNISWAP_V3_INIT_CODE_HASH,
+    },
+    registry::TokenRegistry,
+    types::{ExchangeId, Fee, Pair, Token},
+    uniswap_v2::pair_address_for_tokens,
+    uniswap_v3::pool_address_for,
+};
+
+abigen!(
+    UniswapV3Factory,
+    r#"[
+        function getPool(address tokenA, address tokenB, uint24 fee) external view returns (address pool)
+    ]"#,
+);
+
+abigen!(
+    UniswapV2Factory,
+    r#"[
+        function getPair(address tokenA, address tokenB) external view returns (address pair)
+    ]"#,
+);
+
+/// Uniswap V3 fee tiers
+const UNISWAP_V3_FEE_TIERS: [Fee; 4] = [
+    Fee::from_pips(100),
+    Fee::from_pips(500),
+    Fee::from_pips(3_000),
+    Fee::from_pips(10_000),
+];
+/// Sushi's protocol wide pair fee
+const SUSHI_FEE: Fee = Fee::from_units(300);
+/// Uniswap V2 style exchanges to discover and their pair fee (camelot fees are synced from the pair)
+const V2_EXCHANGES: [(ExchangeId, Fee); 2] = [
+    (ExchangeId::Sushi, SUSHI_FEE),
+    (ExchangeId::Camelot, Fee::ZERO),
+];
+
+/// The canonical address of the `exchange_id` pool with sorted tokens `token_0`/`token_1` and `fee`
+///
+/// Returns `None` if pools of `exchange_id` can't be discovered
+pub fn expected_address(
+    exchange_id: ExchangeId,
+    token_0: Address,
+    token_1: Address,
+    fee: Fee,
+) -> Option<Address> {
+    match exchange_id {
+        ExchangeId::Uniswap => Some(pool_address_for(
+            token_0,
+            token_1,
+            fee.pips(),
+            UNISWAP_V3_FACTORY.into(),
+            &UNISWAP_V3_INIT_CODE_HASH,
+        )),
+        ExchangeId::Sushi => Some(pair_address_for_tokens(
+            token_0,
+            token_1,
+            SUSHI_FACTORY.into(),
+            &SUSHI_INIT_CODE_HASH,
+        )),
+        ExchangeId::Camelot => Some(pair_address_for_tokens(
+            token_0,
+            token_1,
+            CAMELOT_FACTORY.into(),
+            &CAMELOT_INIT_CODE_HASH,
+        )),
+        _ => None,
+    }
+}
+
+/// Discover the pools of every token pair in `registry` from the exchange factories
+///
+/// Discovered pools are not price synced, they are known for decoding trades
+pub async fn discover_pools<M: Middleware + 'static>(
+    client: Arc<M>,
+    registry: &TokenRegistry,
+) -> Vec<Pool> {
+    let tokens: Vec<Token> = registry.tokens().map(|(token, _)| token).collect();
+    let mut candidates = Vec::<Pair>::new();
+    for (idx, a) in tokens.iter().enumerate() {
+        for b in tokens[idx + 1..].iter() {
+            for fee in UNISWAP_V3_FEE_TIERS {
+                candidates.push(registry.pair(*a, *b, fee, ExchangeId::Uniswap));
+            }
+            for (exchange_id, fee) in V2_EXCHANGES {
+                candidates.push(registry.pair(*a, *b, fee, exchange_id));
+            }
+        }
+    }
+
+    let uniswap_v3 = UniswapV3Factory::new(UNISWAP_V3_FACTORY, Arc::clone(&client));
+    let sushi = UniswapV2Factory::new(SUSHI_FACTORY, Arc::clone(&client));
+    let camelot = UniswapV2Factory::new(CAMELOT_FACTORY, Arc::clone(&client));
+    let lookups = candidates.iter().map(|pair| {
+        let (token_0, token_1) = (registry.address(pair.token0), registry.address(pair.token1));
+        let call = match pair.exchange_id {
+            ExchangeId::Uniswap => uniswap_v3.get_pool(token_0, token_1, pair.fee.pips()),
+            ExchangeId::Sushi => sushi.get_pair(token_0, token_1),
+            _ => camelot.get_pair(token_0, token_1),
+        };
+        async move { call.call().await }
+    });
+    let results = join_all(lookups).await;
+
+    let mut pools = Vec::new();
+    for (pair, result) in candidates.into_iter().zip(results) {
+        let address = match result {
+            Ok(address) if address.is_zero() => continue,
+            Ok(address) => address,
+            Err(err) => {
+                warn!("pool discovery ({:?}): {:?}", pair.exchange_id, err);
+                continue;
+            }
+        };
+        let expected = expected_address(
+            pair.exchange_id,
+            registry.address(pair.token0),
+            registry.address(pair.token1),
+            pair.fee,
+        );
+        if expected != Some(address) {
+            warn!("unverified pool: {:?}", address);
+            continue;
+        }
+        pools.push(Pool {
+            pair,
+            address,
+            sync: false,
+        });
+    }
+    info!("discovered pools 🔭: {}", pools.len());
+
+    pools
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{config::Config, types::ExchangeId};
+
+    use super::expected_address;
+
+    #[test]
+    fn expected_addresses_match_config() {
+        let config = Config::arbitrum_one();
+        let registry = config.registry();
+        let mut checked = 0;
+        for pool in config.pools() {
+            let (token_0, token_1) = (
+                registry.address(pool.pair.token0),
+                registry.address(pool.pair.token1),
+            );
+            match expected_address(pool.pair.exchange_id, token_0, token_1, pool.pair.fee) {
+                Some(expected) => {
+                    assert_eq!(expected, pool.address);
+                    checked += 1;
+                }
+                None => assert!(matches!(
+                    pool.pair.exchange_id,
+                    ExchangeId::Chronos | ExchangeId::Balancer | ExchangeId::Curve
+                )),
+            }
+        }
+        assert_eq!(checked, config.pools().len() - 5);
+
+        let mut config = Config::arbitrum_one();
+        let pools = config.pools().to_vec();
+        // configured pools are kept
+        assert_eq!(config.add_pools(pools), 0);
+    }
+}
@@ -0,0 +1,231 @@
+//! Order tx calldata encoding
+//!
+//! Orders are encoded into the executor contract call by an `OrderEncoder`, executors with another ABI
+//! (e.g. a bytes payload or multicall) are supported by implementing one and registering it by name,
+//! the config's `order_encoder` selects it at startup
+use std::{collections::HashMap, fmt};
+
+use ethers::{
+    abi::AbiEncode,
+    types::{Bytes, U256},
+};
+use log::trace;
+
+use crate::{
+    order::{FlashSwapCall, FlashSwapPathCall},
+    payload::{self, Payload},
+    price_graph::CompositeTrade,
+    types::Fee,
+};
+
+/// Name of the encoder for the bundled executor contract, the default
+pub const DEFAULT_ORDER_ENCODER: &str = "fulcrum";
+
+/// An order the executor can't represent, it is not sent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EncodeError {
+    /// A trade fee exceeds the executor's 16 bit fee field (pips)
+    FeeOverflow(Fee),
+}
+
+/// Encodes orders as executor contract calldata
+pub trait OrderEncoder: Send + Sync {
+    /// Calldata of the executor call loaning `amount_in` to execute `trade`
+    /// - `min_amount_out` the contract must revert if the trade returns less e.g. the opportunity was taken
+    fn encode(
+        &self,
+        amount_in: u128,
+        min_amount_out: u128,
+        trade: &CompositeTrade,
+    ) -> Result<Bytes, EncodeError>;
+}
+
+/// Encodes orders for the bundled executor contract (see `contract/src/TradeExecutor.sol`)
+///
+/// Paths of up to 3 trades call `flashSwap` with a packed u128 `Payload`, longer paths call `flashSwapPath`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FulcrumEncoder {
+    /// Call `flashSwapPath` for all paths e.g. for executors without `flashSwap`
+    path_only: bool,
+}
+
+impl FulcrumEncoder {
+    /// Encode all paths for `flashSwapPath`
+    pub fn path_only() -> Self {
+        Self { path_only: true }
+    }
+    /// Pack a trade path of any length for `flashSwapPath`
+    ///
+    /// hop count (8 bits) | start token (8 bits) | per trade: exchange (8 bits), token out (8 bits), fee tier (16 bits)
+    ///
+    /// Errors if a fee doesn't fit its 16 bits
+    pub fn encode_path(trade: &CompositeTrade) -> Result<U256, EncodeError> {
+        let hops = trade.hops();
+        let mut payload = U256::from(hops.len()) | U256::from(hops[0].token_in) << 8;
+        for (idx, hop) in hops.iter().enumerate() {
+            let packed = hop.exchange_id as u32
+                | (hop.token_out as u32) << 8
+                | (payload::pack_fee(hop.fee)? as u32) << 16;
+            payload |= U256::from(packed) << (16 + 32 * idx);
+        }
+        trace!("path payload: {:064x}", payload);
+        Ok(payload)
+    }
+}
+
+impl OrderEncoder for FulcrumEncoder {
+    fn encode(
+        &self,
+        amount_in: u128,
+        min_amount_out: u128,
+        trade: &CompositeTrade,
+    ) -> Result<Bytes, EncodeError> {
+        if self.path_only || trade.len > 3 {
+            return Ok(FlashSwapPathCall {
+                amount_in,
+                payload: Self::encode_path(trade)?,
+                min_amount_out,
+            }
+            .encode()
+            .into());
+        }
+        // somewhat pathological attempt at optimizing for encoding speed e.g vs using RLP crate and typical solidity ABI
+        // pack the trade path as a u128 (see `Payload` for the layout)
+        let payload = Payload::new(trade, min_amount_out).encode()?;
+        trace!("payload: {:032x}", payload);
+        Ok(FlashSwapCall {
+            amount_in,
+            payload,
+            min_amount_out,
+        }
+        .encode()
+        .into())
+    }
+}
+
+/// Builds an order encoder
+pub type EncoderFactory = fn() -> Box<dyn OrderEncoder>;
+
+/// Order encoders by name, selectable from config
+///
+/// Includes the bundled executor's `fulcrum` and `fulcrum_path` (i.e. `flashSwapPath` only) encoders
+pub struct OrderEncoders {
+    factories: HashMap<String, EncoderFactory>,
+}
+
+impl Default for OrderEncoders {
+    fn default() -> Self {
+        Self {
+            factories: HashMap::default(),
+        }
+        .with_encoder(DEFAULT_ORDER_ENCODER, || Box::<FulcrumEncoder>::default())
+        .with_encoder("fulcrum_path", || Box::new(FulcrumEncoder::path_only()))
+    }
+}
+
+impl fmt::Debug for OrderEncoders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.factories.keys()).finish()
+    }
+}
+
+impl OrderEncoders {
+    /// Register the encoder built by `factory` as `name`, replacing any encoder of the same name
+    pub fn with_encoder(mut self, name: &str, factory: EncoderFactory) -> Self {
+        self.factories.insert(name.to_string(), factory);
+        self
+    }
+    /// Build the encoder registered as `name`, if any
+    pub fn build(&self, name: &str) -> Option<Box<dyn OrderEncoder>> {
+        self.factories.get(name).map(|factory| factory())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::{price_graph::Trade, types::Fee};
+
+    #[test]
+    fn encode_long_path() {
+        let trade = CompositeTrade::new(&[
+            Trade::new(0, 1, Fee::from_pips(500), 0),
+            Trade::new(1, 3, Fee::ZERO, 2),
+            Trade::new(3, 4, Fee::ZERO, 1),
+            Trade::new(4, 0, Fee::from_pips(100), 0),
+        ]);
+        assert_eq!(
+            FulcrumEncoder::encode_path(&trade).unwrap(),
+            // 4 hops from token 0, then 0 > 1 > 3 > 4 > 0
+            // fee/token/exchange per hop from the high bits, then start token, hop count
+            U256::from_str_radix("00640000000004010000030201f401000004", 16).unwrap()
+        );
+    }
+
+    #[test]
+    fn encoders_by_name() {
+        let trade = CompositeTrade::new(&[
+            Trade::new(1, 2, Fee::from_pips(500), 1),
+            Trade::new(2, 1, Fee::from_pips(3000), 1),
+        ]);
+        let encoders = OrderEncoders::default();
+        let flash_swap = encoders
+            .build(DEFAULT_ORDER_ENCODER)
+            .unwrap()
+            .encode(1, 1, &trade)
+            .unwrap();
+        let flash_swap_path = encoders
+            .build("fulcrum_path")
+            .unwrap()
+            .encode(1, 1, &trade)
+            .unwrap();
+        // `flashSwap` and `flashSwapPath` selectors
+        assert_eq!(flash_swap[..4], hex!("a969b753"));
+        assert_ne!(flash_swap_path[..4], flash_swap[..4]);
+        assert_eq!(
+            flash_swap_path[36..68],
+            AbiEncode::encode(FulcrumEncoder::encode_path(&trade).unwrap())
+        );
+
+        struct Custom;
+        impl OrderEncoder for Custom {
+            fn encode(
+                &self,
+                amount_in: u128,
+                _: u128,
+                _: &CompositeTrade,
+            ) -> Result<Bytes, EncodeError> {
+                Ok(amount_in.to_be_bytes().to_vec().into())
+            }
+        }
+        let encoders = encoders.with_encoder("custom", || Box::new(Custom));
+        assert_eq!(
+            encoders
+                .build("custom")
+                .unwrap()
+                .encode(7, 0, &trade)
+                .unwrap()
+                .as_ref(),
+            7_u128.to_be_bytes()
+        );
+        assert!(encoders.build("unknown").is_none());
+    }
+
+    #[test]
+    fn encode_fee_overflow() {
+        // e.g. a 10% balancer pool fee
+        let fee = Fee::from_pips(100_000);
+        let trade = CompositeTrade::new(&[
+            Trade::new(1, 2, Fee::from_pips(500), 1),
+            Trade::new(2, 1, fee, 1),
+        ]);
+        for encoder in [FulcrumEncoder::default(), FulcrumEncoder::path_only()] {
+            assert_eq!(
+                encoder.encode(1, 1, &trade),
+                Err(EncodeError::FeeOverflow(fee))
+            );
+        }
+    }
+}
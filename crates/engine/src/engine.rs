@@ -1,48 +1,240 @@
 //! Engine provides main loop
-use std::time::Instant;
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use bumpalo::Bump;
-use ethers_providers::Middleware;
+use futures::FutureExt;
 use log::{debug, error, info, warn};
+use thingbuf::mpsc::Receiver;
+
+use fulcrum_sequencer_feed::{BatchLimits, FeedError, FeedEvent, FeedSource, Timestamps};
 
-use fulcrum_sequencer_feed::{SequencerFeed, TxBuffer};
-use fulcrum_ws_cli::FastWsClient;
+#[cfg(feature = "metrics")]
+use crate::metrics;
+#[cfg(feature = "notify")]
+use crate::notify::Alerts;
 
 use crate::{
-    order::OrderService, price::PriceService, price_graph::Path, trade_simulator::TradeSimulator,
-    types::Position,
+    arena::{ArenaConfig, ArenaUsage, ArenaWatch},
+    audit::SimulationAuditor,
+    chain::{BlockNumberCheck, ChainInconsistency, FeedSequence},
+    control::Controls,
+    events::{EngineEvent, EventSink, SkipReason},
+    fallback::{ReceiptFallback, MAX_FALLBACK_BLOCKS},
+    inbox::DelayedMessage,
+    order::{Order, OrderExecutor},
+    pending::PendingOrders,
+    price::{self, PriceSource, PriceUpdate},
+    price_graph::{Arb, Path, PriceGraph},
+    profit::ProfitModel,
+    quote::PriceView,
+    selector_stats::SelectorStats,
+    shutdown::{shutdown_of, ShutdownSignal},
+    supervisor::{self, Restarts, Subsystem},
+    trade_simulator::{TradeSimulator, DEFAULT_MAX_PRICE_IMPACT_BPS},
+    types::{Position, Token},
+    warm_start::WarmStart,
 };
 
+/// Interval the receipt fallback checks the feed's lag and the node's new blocks
+const FALLBACK_POLL: Duration = Duration::from_millis(250);
+
 /// The Fulcrum trading engine
-pub struct Engine<M: Middleware + 'static> {
+pub struct Engine<P, O, F> {
     /// Provides price information
-    price_service: PriceService<M>,
+    price_service: P,
     /// Provide trade order execution
-    order_service: OrderService<M>,
+    order_service: O,
     /// Sequencer tx feed
-    sequencer_feed: SequencerFeed,
+    sequencer_feed: F,
+    /// Receives trade decision events
+    events: EventSink,
+    /// Max. price impact of any simulated trade (bps), batches exceeding it are skipped
+    max_price_impact_bps: u32,
+    /// Saves the price graph on shutdown, reloading it on start
+    warm_start: Option<WarmStart>,
+    /// Messages delivered to the L1 delayed inbox, not yet sequenced
+    delayed_messages: Option<Receiver<DelayedMessage>>,
+    /// Limits on feed message batches, messages exceeding them are dropped
+    batch_limits: BatchLimits,
+    /// Sizing of the feed decoding arena
+    arena: ArenaConfig,
+    /// Reorgs of the price source node
+    chain_events: Option<Receiver<ChainInconsistency>>,
+    /// Feed blocks skipped after a reorg or resequencing
+    reorg_pause_blocks: u64,
+    /// Changes the min. profit and searched paths at runtime
+    controls: Option<Arc<Controls>>,
+    /// Stops the loop on shutdown
+    shutdown: Option<ShutdownSignal>,
+    /// Cross-checks feed block numbers against the price source's (interval, check)
+    block_check: Option<(Duration, BlockNumberCheck)>,
+    /// Shift feed block numbers by a consistent drift from the price source's
+    calibrate_blocks: bool,
+    /// Advances prices from the node's receipts while the feed lags
+    receipt_fallback: Option<ReceiptFallback>,
+    /// Compares simulated pool states to the chain's
+    auditor: Option<SimulationAuditor>,
+    /// Receives the latest price graph for queries
+    price_view: Option<Arc<PriceView>>,
+    /// Counts calls of unhandled router methods
+    selector_stats: Option<SelectorStats>,
+    /// Alerts feed outages
+    #[cfg(feature = "notify")]
+    alerts: Option<Alerts>,
 }
 
-impl<M> Engine<M>
+impl<P, O, F> Engine<P, O, F>
 where
-    M: Middleware<Provider = FastWsClient> + 'static,
+    P: PriceSource,
+    O: OrderExecutor,
+    F: FeedSource,
 {
     /// Initialize a new trading engine
-    pub fn new(
-        price_service: PriceService<M>,
-        order_service: OrderService<M>,
-        sequencer_feed: SequencerFeed,
-    ) -> Self {
+    pub fn new(price_service: P, order_service: O, sequencer_feed: F) -> Self {
         Self {
             sequencer_feed,
             price_service,
             order_service,
+            events: EventSink::default(),
+            max_price_impact_bps: DEFAULT_MAX_PRICE_IMPACT_BPS,
+            warm_start: None,
+            delayed_messages: None,
+            batch_limits: BatchLimits::default(),
+            arena: ArenaConfig::default(),
+            chain_events: None,
+            reorg_pause_blocks: 0,
+            controls: None,
+            shutdown: None,
+            block_check: None,
+            calibrate_blocks: false,
+            receipt_fallback: None,
+            auditor: None,
+            price_view: None,
+            selector_stats: None,
+            #[cfg(feature = "notify")]
+            alerts: None,
         }
     }
+    /// Emit trade decision events to `sink` (default: the logger)
+    pub fn with_event_sink(mut self, sink: EventSink) -> Self {
+        self.events = sink;
+        self
+    }
+    /// Skip batches with a trade moving any pool's price by more than `bps` (default: 2000)
+    pub fn with_max_price_impact_bps(mut self, bps: u32) -> Self {
+        self.max_price_impact_bps = bps;
+        self
+    }
+    /// Price the first batch with the graph saved by `warm_start` (if fresh) and save the latest graph on shutdown (ctrl-c)
+    pub fn with_warm_start(mut self, warm_start: WarmStart) -> Self {
+        self.warm_start = Some(warm_start);
+        self
+    }
+    /// Emit messages delivered to the L1 delayed inbox (e.g. by an `L1InboxWatcher`) as events, alongside the feed
+    pub fn with_delayed_messages(mut self, delayed_messages: Receiver<DelayedMessage>) -> Self {
+        self.delayed_messages = Some(delayed_messages);
+        self
+    }
+    /// Drop feed messages exceeding `batch_limits` (default: 1024 txs, 256KiB per batch entry)
+    pub fn with_batch_limits(mut self, batch_limits: BatchLimits) -> Self {
+        self.batch_limits = batch_limits;
+        self
+    }
+    /// Decode feed messages into an arena sized by `arena` (default: 1,024,000 bytes, 100 txs)
+    pub fn with_arena(mut self, arena: ArenaConfig) -> Self {
+        self.arena = arena;
+        self
+    }
+    /// Resync prices on reorgs of the price source node (e.g. from a `ChainWatcher`), as on feed resequencing
+    pub fn with_chain_watch(mut self, chain_events: Receiver<ChainInconsistency>) -> Self {
+        self.chain_events = Some(chain_events);
+        self
+    }
+    /// Skip the next `blocks` feed blocks after a reorg or resequencing, no orders are submitted (default: 0)
+    pub fn with_reorg_pause(mut self, blocks: u64) -> Self {
+        self.reorg_pause_blocks = blocks;
+        self
+    }
+    /// Take the min. profit and searched paths from `controls`, re-read whenever they change
+    ///
+    /// `controls` should also be given to the order executor to switch dry-run
+    pub fn with_controls(mut self, controls: Arc<Controls>) -> Self {
+        self.controls = Some(controls);
+        self
+    }
+    /// Stop the loop on `shutdown` rather than ctrl-c, the feed is closed and the warm start graph (if any) saved
+    pub fn with_shutdown(mut self, shutdown: ShutdownSignal) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+    /// Compare the latest feed block number to the price source's every `interval`, emitting drifts beyond `check`'s max.
+    ///
+    /// With `calibrate` a drift found on consecutive checks shifts the feed's block numbers (and prices are resynced)
+    pub fn with_block_check(
+        mut self,
+        interval: Duration,
+        check: BlockNumberCheck,
+        calibrate: bool,
+    ) -> Self {
+        self.block_check = Some((interval, check));
+        self.calibrate_blocks = calibrate;
+        self
+    }
+    /// Advance prices from the node's block receipts with `fallback` while the feed lags, the feed's next block is priced
+    /// from them once it recovers
+    pub fn with_receipt_fallback(mut self, fallback: ReceiptFallback) -> Self {
+        self.receipt_fallback = Some(fallback);
+        self
+    }
+    /// Audit the pool states of each simulated batch against the chain's with `auditor`, off the hot path
+    pub fn with_auditor(mut self, auditor: SimulationAuditor) -> Self {
+        self.auditor = Some(auditor);
+        self
+    }
+    /// Publish the price graph to `view` after each simulated batch e.g. for `quote::serve`
+    pub fn with_price_view(mut self, view: Arc<PriceView>) -> Self {
+        self.price_view = Some(view);
+        self
+    }
+    /// Count calls of unhandled router methods in the feed with `stats`, reporting the top methods periodically
+    pub fn with_selector_stats(mut self, stats: SelectorStats) -> Self {
+        self.selector_stats = Some(stats);
+        self
+    }
+    /// Alert outages of the feed to `alerts`
+    #[cfg(feature = "notify")]
+    pub fn with_alerts(mut self, alerts: Alerts) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+    /// Emit `inconsistency` of the chain views, returning the feed block batches are paused until
+    fn chain_inconsistent(&mut self, inconsistency: ChainInconsistency, feed_block: u64) -> u64 {
+        self.events.emit(&match inconsistency {
+            ChainInconsistency::Reorg {
+                block_number,
+                depth,
+            } => EngineEvent::Reorg {
+                block_number,
+                depth,
+                feed_block,
+            },
+            ChainInconsistency::Resequenced {
+                block_number,
+                latest,
+            } => EngineEvent::Resequenced {
+                block_number,
+                latest,
+            },
+        });
+        feed_block + self.reorg_pause_blocks
+    }
     /// Start the trading engine loop
     ///
     /// `search_paths` - trade paths to search for arbitrage opportunities (given some start position)
-    /// `min_profit` the minimum profit required for trade execution, expressed as a percent e.g 0.007f64 = 0.007%
+    /// `min_profit` the minimum profit required for trade execution, net of order gas costs, expressed as a percent e.g 0.007f64 = 0.007%
     /// `dry_run` when true runs passive mode/disallows tx submission for trades
     pub async fn run(
         mut self,
@@ -50,27 +242,211 @@ where
         min_profit: f64,
         dry_run: bool,
     ) {
-        let min_profit_threshold = 1.0_f64 + min_profit;
-        let bump = Bump::with_capacity(1024 * 1_000); // 1mib bump allocator for hot loop
+        let mut profit_model = ProfitModel::new(
+            min_profit,
+            self.order_service.max_fee_per_gas(),
+            self.order_service.gas_model(),
+        );
+        let mut tx_arena = self.arena.arena().with_batch_limits(self.batch_limits);
+        let mut arena_watch = ArenaWatch::new(self.arena);
         let mut syncing = false;
+        // L1 delayed inbox messages sequenced as of the latest feed message
+        let mut delayed_messages_read = 0_u64;
+        let delayed_messages = self.delayed_messages.take();
+        // prices synced before a reorg or resequencing may be of another chain view
+        let chain_events = self.chain_events.take();
+        let mut feed_sequence = FeedSequence::default();
+        // feed block numbers assume the nitro genesis offset, cross-checked against the price source
+        let mut block_check = self
+            .block_check
+            .take()
+            .map(|(interval, check)| (tokio::time::interval(interval), check));
+        // batches up to this feed block are skipped
+        let mut paused_until = 0_u64;
+        // while the feed lags prices follow the node's receipts
+        let receipt_fallback = self.receipt_fallback.take();
+        let mut fallback_ticks = tokio::time::interval(FALLBACK_POLL);
+        let mut fallback_graph: Option<PriceGraph> = None;
+        let mut last_message_at = Instant::now();
+        let audits = self.auditor.take().map(SimulationAuditor::start);
+        let mut selector_stats = self.selector_stats.take();
+        // arbs found during a search, emitted after order submission to keep them off the hot path
+        let mut arb_events = Vec::<EngineEvent>::with_capacity(search_paths.len());
+        // profitable arbs found during a search (net profit ratio, arb) and the independent orders chosen from them
+        let mut arbs = Vec::<(f64, Arb)>::with_capacity(search_paths.len());
+        let mut orders = Vec::<Order>::with_capacity(search_paths.len());
+        // search paths less any disabled by the controls
+        let mut active_paths: Vec<(&Position, Vec<Path>)> = search_paths
+            .iter()
+            .map(|(position, paths)| (position, paths.to_vec()))
+            .collect();
+        let mut controls_version = None;
+
+        // our submitted orders are applied to price graphs until included, if the executor reports their progress
+        let mut pending_orders = PendingOrders::default();
+        let order_updates = self.order_service.updates();
 
         let (price_requests, price_queue) = self.price_service.start().await;
         let trade_requests = self.order_service.start(dry_run).await;
 
-        while let Ok(frame) = self.sequencer_feed.next_message().await {
+        // the saved price graph is used at most once, for the first batch
+        let mut warm_graph = self.warm_start.as_ref().and_then(WarmStart::load);
+        let mut shutdown = self.shutdown.take();
+        // panics in decoding or simulating a feed message restart the feed loop from a resync
+        let task_restarts = supervisor::restarts();
+        let mut feed_restarts = Restarts::new(Subsystem::Feed);
+        // with a warm start, ctrl-c stops the loop to save the graph rather than killing the process
+        let stop_on_ctrl_c = shutdown.is_none() && self.warm_start.is_some();
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+
+        loop {
+            let mut message = tokio::select! {
+                biased;
+                _ = shutdown_of(&mut shutdown), if shutdown.is_some() => break,
+                _ = &mut ctrl_c, if stop_on_ctrl_c => break,
+                // delayed messages are rare and land ahead of the feed's txs, handle them first
+                Some(message) = async { delayed_messages.as_ref()?.recv().await }, if delayed_messages.is_some() => {
+                    self.events.emit(&EngineEvent::DelayedMessage {
+                        message_index: message.message_index,
+                        kind: message.kind,
+                        sender: message.sender,
+                        l1_block: message.l1_block,
+                        delayed_messages_read,
+                    });
+                    continue;
+                }
+                Some(restart) = task_restarts.recv() => {
+                    self.events.emit(&EngineEvent::TaskRestarted {
+                        subsystem: restart.subsystem,
+                        restarts: restart.restarts,
+                        backoff: restart.backoff,
+                        panic: restart.panic,
+                    });
+                    continue;
+                }
+                Some(inconsistency) = async { chain_events.as_ref()?.recv().await }, if chain_events.is_some() => {
+                    paused_until = self.chain_inconsistent(inconsistency, feed_sequence.latest());
+                    warm_graph = None;
+                    fallback_graph = None;
+                    syncing = true;
+                    continue;
+                }
+                Some(_) = async { Some(block_check.as_mut()?.0.tick().await) }, if block_check.is_some() => {
+                    let feed_block = feed_sequence.latest();
+                    let Some((_, check)) = block_check.as_mut().filter(|_| feed_block > 0) else {
+                        continue;
+                    };
+                    let node_block = self.price_service.block_number().await;
+                    if let Some(drift) = check.check(feed_block, node_block) {
+                        let calibration = drift.calibration.filter(|_| self.calibrate_blocks);
+                        self.events.emit(&EngineEvent::BlockNumberDrift {
+                            feed_block,
+                            node_block,
+                            calibration,
+                        });
+                        if let Some(offset) = calibration {
+                            self.sequencer_feed.shift_block_numbers(offset);
+                            // shifted blocks would look resequenced
+                            feed_sequence = FeedSequence::default();
+                            warm_graph = None;
+                            fallback_graph = None;
+                            syncing = true;
+                        }
+                    }
+                    continue;
+                }
+                _ = fallback_ticks.tick(), if receipt_fallback.is_some() => {
+                    let Some(fallback) = receipt_fallback
+                        .as_ref()
+                        .filter(|fallback| last_message_at.elapsed() >= fallback.lag())
+                    else {
+                        continue;
+                    };
+                    let node_block = self.price_service.block_number().await;
+                    // a graph too far behind is synced at the node's block, then advanced from receipts
+                    if !fallback_graph.as_ref().is_some_and(|graph| {
+                        node_block.saturating_sub(graph.block_number()) <= MAX_FALLBACK_BLOCKS
+                    }) {
+                        let _ = price_requests.send(node_block).await;
+                        fallback_graph = loop {
+                            match price_queue.recv_ref().await {
+                                Some(price_update) if price_update.block_number < node_block => continue,
+                                Some(price_update) => break price_update.graph.clone(),
+                                None => break None,
+                            }
+                        };
+                        continue;
+                    }
+                    let Some(graph) = fallback_graph.as_mut().filter(|graph| graph.block_number() < node_block) else {
+                        continue;
+                    };
+                    match fallback.advance(graph, node_block).await {
+                        Ok(pool_updates) => self.events.emit(&EngineEvent::FeedFallback {
+                            block_number: graph.block_number(),
+                            pool_updates,
+                        }),
+                        Err(err) => warn!("receipt fallback: {:?}", err),
+                    }
+                    continue;
+                }
+                message = self.sequencer_feed.next_message() => match message {
+                    Ok(message) => message,
+                    Err(_) => break,
+                },
+            };
+            #[cfg(feature = "metrics")]
+            metrics::FEED_MESSAGES.inc();
             let mut t0 = Instant::now();
-            // handling frame here is strange but need the ownership of the received message at the top level
+            last_message_at = t0;
+            #[cfg(feature = "notify")]
+            if let Some(alerts) = self.alerts.as_ref() {
+                alerts.feed_alive();
+            }
+            // handling message here is strange but need the ownership of the received message at the top level
             // to avoid copying
-            let (header, mut payload) = frame.parts();
-            let mut tx_buffer = TxBuffer::new(&bump);
-            if let Err(err) = self
-                .sequencer_feed
-                .handle_frame(&header, payload.as_mut(), &mut tx_buffer)
-                .await
+            // free the previous message's txs, the arena retains its memory
+            tx_arena.reset();
+            let mut tx_buffer = tx_arena.tx_buffer();
+            let handled = AssertUnwindSafe(
+                self.sequencer_feed
+                    .handle_message(&mut message, &mut tx_buffer),
+            )
+            .catch_unwind()
+            .await;
+            let event = match handled {
+                Ok(Ok(event)) => event,
+                // prices simulated from here would miss the dropped txs, resync
+                Ok(Err(FeedError::Gap { from, to })) => {
+                    self.events.emit(&EngineEvent::FeedGap { from, to });
+                    syncing = true;
+                    continue;
+                }
+                Ok(Err(err)) => {
+                    error!("tx feed: {:?}", err);
+                    syncing = true;
+                    continue;
+                }
+                Err(panic) => {
+                    feed_restarts.restart(panic.as_ref()).await;
+                    syncing = true;
+                    continue;
+                }
+            };
+            delayed_messages_read = delayed_messages_read.max(tx_buffer.delayed_messages_read());
+            let usage = ArenaUsage {
+                bytes: tx_arena.used_bytes(),
+                txs: tx_buffer.as_slice().len(),
+            };
+            if let Some(warning) = arena_watch.record(tx_buffer.block_number(), usage) {
+                self.events.emit(&warning);
+            }
+            #[cfg(feature = "metrics")]
             {
-                error!("tx feed: {:?}", err);
-                syncing = true;
-                continue;
+                metrics::FEED_DECODE.observe(Instant::now() - t0);
+                let timestamps = tx_buffer.timestamps();
+                metrics::LATENCY_DECODED
+                    .observe_some(timestamps.since_received(timestamps.decoded));
             }
 
             // feed message is not useful
@@ -78,6 +454,27 @@ where
                 debug!("nothing to simulate, skip");
                 continue;
             }
+            if let Some(inconsistency) = feed_sequence.record(tx_buffer.block_number()) {
+                paused_until = self.chain_inconsistent(inconsistency, tx_buffer.block_number());
+                warm_graph = None;
+                fallback_graph = None;
+                syncing = true;
+                continue;
+            }
+
+            // a frame of many messages (e.g. catching up after a reconnect) spans several blocks
+            // while prices are synced per block, resync from its latest block
+            if let FeedEvent::Messages(_) = event {
+                syncing = true;
+            }
+            // the graph followed the node while the feed lagged, it prices the feed's next block in place of a resync
+            let resumed_graph = fallback_graph.take().filter(|graph| {
+                !matches!(event, FeedEvent::Messages(_))
+                    && graph.block_number() + 1 == tx_buffer.block_number()
+            });
+            if resumed_graph.is_some() {
+                syncing = false;
+            }
 
             // drive the sequencer feed until it is syncing in time with the price source
             // assuming a fast local, full node this can be improved to use an event driven setup, for now this is effective for syncing a remote full node
@@ -85,103 +482,502 @@ where
                 let price_service_block = self.price_service.block_number().await;
                 let _ = price_queue.try_recv(); // ensure price queue is empty
                 if tx_buffer.block_number() <= price_service_block {
-                    info!(
-                        "awaiting feed <> price sync 🔄: {}/{}",
-                        tx_buffer.block_number(),
-                        price_service_block,
-                    );
+                    self.events.emit(&EngineEvent::AwaitingSync {
+                        feed_block: tx_buffer.block_number(),
+                        price_block: price_service_block,
+                    });
                     continue;
                 }
                 // we got update for block B, price source already processed update at block B
                 // so we are lagging slightly
-                info!("price feed sync'd ⚡️⚡️⚡️: {}", tx_buffer.block_number());
+                self.events.emit(&EngineEvent::Synced {
+                    block_number: tx_buffer.block_number(),
+                });
                 let _ = price_requests.send(tx_buffer.block_number()).await;
                 syncing = false;
                 continue;
             }
 
+            // the block boundary is explicit, a block without user txs needs neither prices nor simulation
+            if let FeedEvent::EndOfBlock { block_number } = event {
+                self.events.emit(&EngineEvent::BlockEnded { block_number });
+                continue;
+            }
+
+            if tx_buffer.block_number() <= paused_until {
+                self.events.emit(&EngineEvent::BatchSkipped {
+                    block_number: tx_buffer.block_number(),
+                    reason: SkipReason::ChainPaused,
+                });
+                continue;
+            }
+
             // acting as minimal light client, simulate all txs we care about based on the sequencer feed
             // for feed block N, requires price information for block N - 1
             // - execute any arbs
             // - sync real prices from a proper full node for next round (concurrently)
-            let _ = price_requests.send(tx_buffer.block_number()).await;
-            // check if prices for current block ready
-            let mut price_graph_ref = price_queue.recv_ref().await.expect("price graph ready");
-            let price_graph = match price_graph_ref.as_mut() {
-                Some(price_graph) => price_graph,
+            let mut fresh_warm_graph = warm_graph
+                .take()
+                .filter(|graph| {
+                    self.warm_start.as_ref().is_some_and(|warm_start| {
+                        warm_start.is_fresh(graph, tx_buffer.block_number())
+                    })
+                })
+                .or(resumed_graph);
+            let mut price_update_ref;
+            let price_graph = match fresh_warm_graph.as_mut() {
+                Some(price_graph) => {
+                    info!("resume from #{}", price_graph.block_number());
+                    price_graph
+                }
                 None => {
-                    // prices were not fetched, either due to error or deadline
-                    // its likely we can't execute arbs fast enough at this point, skip the price sync for this block
-                    info!(
-                        "skip batch: #{} unable to fetch block: #{}",
-                        tx_buffer.block_number(),
-                        tx_buffer.block_number() - 1,
-                    );
-                    // if here, the queued price graph ref is probably wasted
-                    syncing = true;
-                    continue;
+                    let _ = price_requests.send(tx_buffer.block_number()).await;
+                    // check if prices for current block ready, updates of earlier requests are stale e.g. left over from syncing
+                    price_update_ref = loop {
+                        let price_update = price_queue.recv_ref().await.expect("price graph ready");
+                        if price_update.block_number >= tx_buffer.block_number() {
+                            break price_update;
+                        }
+                        debug!("drop stale prices: #{}", price_update.block_number);
+                    };
+                    match price_update_ref.graph.as_mut() {
+                        Some(price_graph) => price_graph,
+                        None => {
+                            // prices were not fetched, either due to error or deadline
+                            // its likely we can't execute arbs fast enough at this point, skip the price sync for this block
+                            self.events.emit(&EngineEvent::BatchSkipped {
+                                block_number: tx_buffer.block_number(),
+                                reason: SkipReason::NoPrices,
+                            });
+                            // if here, the queued price graph ref is probably wasted
+                            syncing = true;
+                            continue;
+                        }
+                    }
                 }
             };
 
-            info!(
-                "🛠️ applying txs from batch: #{} to block: #{} {:?}",
+            debug!(
+                "batch #{} ready: {:?}",
                 tx_buffer.block_number(),
-                price_graph.block_number(),
                 Instant::now() - t0
             );
 
             // try simulate new trades
             t0 = Instant::now();
-            let mut trade_simulator = TradeSimulator::new(price_graph);
-            for tx in tx_buffer.as_slice() {
-                trade_simulator.wrangle_transaction(tx);
-                // we can't faithfully simulate all the transactions, skip this round
-                if trade_simulator.skipped() {
-                    warn!("skipped trade simulation");
-                    break;
+            if let Some(order_updates) = order_updates.as_ref() {
+                while let Ok(update) = order_updates.try_recv() {
+                    pending_orders.update(update);
+                }
+                // our orders are sequenced ahead of the feed's txs
+                pending_orders.apply(price_graph);
+            }
+            let mut trade_simulator = TradeSimulator::new(price_graph)
+                .with_max_price_impact_bps(self.max_price_impact_bps);
+            if let Some(stats) = selector_stats.as_mut() {
+                trade_simulator = trade_simulator.with_selector_stats(stats);
+            }
+            let simulated = panic::catch_unwind(AssertUnwindSafe(|| {
+                for tx in tx_buffer.as_slice() {
+                    trade_simulator.wrangle_transaction(tx);
+                    // we can't faithfully simulate all the transactions, skip this round
+                    if trade_simulator.skipped() {
+                        break;
+                    }
+                }
+            }));
+            // the price graph may be part way through a trade, resync
+            if let Err(panic) = simulated {
+                feed_restarts.restart(panic.as_ref()).await;
+                syncing = true;
+                continue;
+            }
+            let simulated_at = Instant::now();
+            let simulation_elapsed = simulated_at - t0;
+            if trade_simulator.skipped() {
+                self.events.emit(&EngineEvent::BatchSkipped {
+                    block_number: tx_buffer.block_number(),
+                    reason: if trade_simulator.price_impact_exceeded() {
+                        SkipReason::PriceImpact
+                    } else {
+                        SkipReason::Unsimulatable
+                    },
+                });
+                continue;
+            }
+            let timestamps = Timestamps {
+                simulated: Some(simulated_at),
+                ..tx_buffer.timestamps()
+            };
+            #[cfg(feature = "metrics")]
+            metrics::LATENCY_SIMULATED
+                .observe_some(timestamps.since_received(timestamps.simulated));
+
+            if let Some(controls) = self.controls.as_ref() {
+                let version = controls.version();
+                if controls_version != Some(version) {
+                    profit_model.set_min_profit(controls.min_profit());
+                    for ((_, active), (_, paths)) in active_paths.iter_mut().zip(search_paths) {
+                        *active = paths
+                            .iter()
+                            .filter(|path| controls.is_enabled(path))
+                            .cloned()
+                            .collect();
+                    }
+                    controls_version = Some(version);
                 }
             }
-            debug!("simulated txs ⚙️: {:?}", Instant::now() - t0);
 
             t0 = Instant::now();
-            if !trade_simulator.skipped() && price_graph.touched() {
-                let mut best_trade_percent = min_profit_threshold;
-                let mut best_trade = None;
-                // TODO: only consider 'touched' paths
-                for (position, path) in search_paths {
-                    if let Some((amount_out, trade_path)) = price_graph.find_arb(position, path) {
-                        let profit_percent = amount_out as f64 / position.amount as f64;
-                        if profit_percent > best_trade_percent {
-                            info!("arb found 💵: {profit_percent}%\n{}", &trade_path);
-                            best_trade_percent = profit_percent;
-                            best_trade = Some((position.amount, trade_path));
-                        }
+            let touched = price_graph.touched();
+            if touched {
+                arbs.clear();
+                // only paths through edges touched by the simulated trades can have new arbs
+                let dirty = price_graph.dirty();
+                for (position, paths) in active_paths.iter() {
+                    if let Some(arb) = price_graph.find_arb(position, paths, dirty) {
+                        // arbs losing money after gas are dropped
+                        let profit_percent = match profit_model.net_profit_ratio(
+                            price_graph,
+                            &arb,
+                            position.token,
+                        ) {
+                            Some(profit_percent) => profit_percent,
+                            None => continue,
+                        };
+                        arb_events.push(EngineEvent::ArbFound {
+                            block_number: price_graph.block_number(),
+                            token: position.token,
+                            amount_in: arb.amount_in,
+                            amount_out: arb.amount_out,
+                            path: arb.trade,
+                        });
+                        arbs.push((profit_percent, arb));
+                    }
+                }
+                // the most profitable arb and any others independent of it i.e. not trading the same tokens
+                arbs.sort_unstable_by(|(a, _), (b, _)| b.total_cmp(a));
+                orders.clear();
+                for (_, arb) in arbs.iter() {
+                    if orders.iter().any(|order| order.trade.intersects(arb.trade)) {
+                        continue;
                     }
+                    orders.push(Order {
+                        amount_in: arb.amount_in,
+                        amount_out: arb.amount_out,
+                        trade: arb.trade,
+                        block_number: price_graph.block_number(),
+                        value_in_wei: price::value_in_weth(
+                            price_graph,
+                            Token::new(arb.trade.path[0].token_in),
+                            arb.amount_in,
+                        )
+                        .unwrap_or_default(),
+                        timestamps,
+                    });
                 }
-                if let Some((amount, path)) = best_trade {
-                    trade_requests
-                        .send((amount, path))
-                        .await
-                        .expect("trade sent");
+                for order in orders.drain(..) {
+                    #[cfg(feature = "metrics")]
+                    metrics::ARBS_FOUND.inc();
+                    let order_event = EngineEvent::OrderSubmitted {
+                        block_number: order.block_number,
+                        token: Token::from_usize(order.trade.path[0].token_in as usize),
+                        amount_in: order.amount_in,
+                        amount_out: order.amount_out,
+                        path: order.trade,
+                    };
+                    trade_requests.send(order).await.expect("trade sent");
                     // trace!("{}", price_graph);
+                    arb_events.push(order_event);
+                }
+                arb_events.push(EngineEvent::ArbSearch {
+                    block_number: price_graph.block_number(),
+                    elapsed: Instant::now() - t0,
+                });
+            }
+
+            // events are emitted once any order is away
+            self.events.emit(&EngineEvent::BatchSimulated {
+                block_number: tx_buffer.block_number(),
+                price_block: price_graph.block_number(),
+                txs: tx_buffer.as_slice().len(),
+                touched,
+                elapsed: simulation_elapsed,
+            });
+            for event in arb_events.drain(..) {
+                self.events.emit(&event);
+            }
+            if let Some(audits) = audits.as_ref().filter(|_| touched) {
+                audits.record(price_graph, tx_buffer.block_number());
+            }
+            if let Some(view) = self.price_view.as_ref() {
+                view.publish(price_graph);
+            }
+            if let Some(stats) = selector_stats.as_mut() {
+                stats.report_if_due();
+            }
+        }
+
+        if let Some(stats) = selector_stats.as_mut() {
+            stats.report();
+        }
+        let high_water = arena_watch.high_water();
+        info!(
+            "decoding arena high-water 📈: {}/{} bytes, {}/{} txs",
+            high_water.bytes, self.arena.capacity_bytes, high_water.txs, self.arena.txs
+        );
+
+        if let Some(warm_start) = self.warm_start.as_ref() {
+            // sync the latest prices to save, dropping any unconsumed graphs
+            while price_queue.try_recv().is_ok() {}
+            let block_number = self.price_service.block_number().await;
+            let _ = price_requests.send(block_number).await;
+            match price_queue
+                .recv_ref()
+                .await
+                .as_ref()
+                .and_then(|price_update| price_update.graph.as_ref())
+            {
+                Some(price_graph) => {
+                    if let Err(err) = warm_start.save(price_graph) {
+                        warn!("warm start save: {:?}", err);
+                    }
                 }
-                info!(
-                    "checked arbs 🔎 (#{}): {:?}",
-                    price_graph.block_number(),
-                    Instant::now() - t0
-                );
+                _ => warn!("warm start: no prices to save (#{block_number})"),
             }
         }
+        self.sequencer_feed.close().await;
     }
 }
 
 /// Utility method for building a price graph at block and dumping the output
-pub async fn prices_at<M: Middleware<Provider = FastWsClient> + 'static>(
-    price_service: PriceService<M>,
-    at: u64,
-) {
+pub async fn prices_at<P: PriceSource>(price_service: P, at: u64) {
     let (price_requests, price_queue) = price_service.start().await;
     price_requests.send(at).await.expect("price sync request");
-    let price_graph = price_queue.recv_ref().await.expect("price graph ready");
-    println!("{}", price_graph.as_ref().expect("price graph built"));
+    let price_update = price_queue.recv_ref().await.expect("price graph ready");
+    println!(
+        "{}",
+        price_update.graph.as_ref().expect("price graph built")
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+    use thingbuf::mpsc::{Receiver, Sender};
+
+    use crate::{
+        backtest::{BacktestExecutor, RecordedFeed},
+        price_graph::PriceGraph,
+    };
+
+    use super::*;
+
+    /// Serves empty price graphs, recording the requested blocks
+    #[derive(Default)]
+    struct MockPrices {
+        requests: Arc<Mutex<Vec<u64>>>,
+    }
+
+    #[async_trait]
+    impl PriceSource for MockPrices {
+        async fn block_number(&self) -> u64 {
+            0
+        }
+        async fn start(&self) -> (Sender<u64>, Receiver<PriceUpdate>) {
+            let (price_sync_tx, price_sync_rx) = thingbuf::mpsc::channel::<u64>(5);
+            let (price_queue_tx, price_queue_rx) = thingbuf::mpsc::channel(5);
+            let requests = Arc::clone(&self.requests);
+            tokio::spawn(async move {
+                while let Some(block_number) = price_sync_rx.recv().await {
+                    requests.lock().unwrap().push(block_number);
+                    let mut price_graph = PriceGraph::empty();
+                    price_graph.reset(block_number);
+                    let _ = price_queue_tx
+                        .send(PriceUpdate {
+                            block_number,
+                            graph: Some(price_graph),
+                        })
+                        .await;
+                }
+            });
+
+            (price_sync_tx, price_queue_rx)
+        }
+    }
+
+    #[tokio::test]
+    async fn run_requests_prices_per_feed_block() {
+        let prices = Arc::new(MockPrices::default());
+        let (executor, report) = BacktestExecutor::new();
+        let feed = RecordedFeed::new(vec![
+            include_bytes!("../../sequencer-feed/res/batch.json").to_vec(),
+            include_bytes!("../../sequencer-feed/res/small.json").to_vec(),
+        ]);
+
+        // returns once the feed is exhausted
+        Engine::new(Arc::clone(&prices), executor, feed)
+            .run(&[], 0.0, true)
+            .await;
+
+        assert_eq!(
+            prices.requests.lock().unwrap().as_slice(),
+            &[88_416_071, 91_146_328]
+        );
+        assert!(report.await.unwrap().trades.is_empty());
+    }
+
+    #[tokio::test]
+    async fn run_emits_batch_events() {
+        let events_path = std::env::temp_dir().join("fulcrum-run-emits-batch-events.jsonl");
+        let _ = std::fs::remove_file(&events_path);
+        let (executor, _report) = BacktestExecutor::new();
+        let feed = RecordedFeed::new(vec![
+            include_bytes!("../../sequencer-feed/res/batch.json").to_vec()
+        ]);
+
+        Engine::new(MockPrices::default(), executor, feed)
+            .with_event_sink(EventSink::file(&events_path).unwrap())
+            .run(&[], 0.0, true)
+            .await;
+
+        let events = std::fs::read_to_string(&events_path).unwrap();
+        let events: Vec<serde_json::Value> = events
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["event"], "batch_simulated");
+        assert_eq!(events[0]["block_number"], 88_416_071);
+        assert!(events[0]["txs"].as_u64().unwrap() > 0);
+        let _ = std::fs::remove_file(&events_path);
+    }
+
+    #[tokio::test]
+    async fn run_ends_empty_blocks_without_prices() {
+        let events_path = std::env::temp_dir().join("fulcrum-run-ends-empty-blocks.jsonl");
+        let _ = std::fs::remove_file(&events_path);
+        let prices = Arc::new(MockPrices::default());
+        let (executor, _report) = BacktestExecutor::new();
+        let feed = RecordedFeed::new(vec![
+            include_bytes!("../../sequencer-feed/res/batch.json").to_vec(),
+            include_bytes!("../../sequencer-feed/res/end-of-block.json").to_vec(),
+        ]);
+
+        Engine::new(Arc::clone(&prices), executor, feed)
+            .with_event_sink(EventSink::file(&events_path).unwrap())
+            .run(&[], 0.0, true)
+            .await;
+
+        // the empty block is not priced
+        assert_eq!(prices.requests.lock().unwrap().as_slice(), &[88_416_071]);
+        let events = std::fs::read_to_string(&events_path).unwrap();
+        let events: Vec<serde_json::Value> = events
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["event"], "batch_simulated");
+        assert_eq!(events[1]["event"], "block_ended");
+        assert_eq!(events[1]["block_number"], 91_146_332);
+        let _ = std::fs::remove_file(&events_path);
+    }
+
+    #[tokio::test]
+    async fn run_emits_delayed_messages() {
+        let events_path = std::env::temp_dir().join("fulcrum-run-emits-delayed-messages.jsonl");
+        let _ = std::fs::remove_file(&events_path);
+        let (executor, _report) = BacktestExecutor::new();
+        let feed = RecordedFeed::new(vec![include_bytes!(
+            "../../sequencer-feed/res/deposit.json"
+        )
+        .to_vec()]);
+        let (delayed_tx, delayed_rx) = thingbuf::mpsc::channel(2);
+        delayed_tx
+            .try_send(DelayedMessage {
+                message_index: 852_410,
+                kind: 12,
+                l1_block: 17_269_600,
+                ..Default::default()
+            })
+            .unwrap();
+
+        Engine::new(MockPrices::default(), executor, feed)
+            .with_event_sink(EventSink::file(&events_path).unwrap())
+            .with_delayed_messages(delayed_rx)
+            .run(&[], 0.0, true)
+            .await;
+
+        let events = std::fs::read_to_string(&events_path).unwrap();
+        let events: Vec<serde_json::Value> = events
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        // delivered on L1 before the feed has sequenced it
+        assert_eq!(events[0]["event"], "delayed_message");
+        assert_eq!(events[0]["message_index"], 852_410);
+        assert_eq!(events[0]["kind"], 12);
+        assert_eq!(events[0]["delayed_messages_read"], 0);
+        let _ = std::fs::remove_file(&events_path);
+    }
+
+    #[tokio::test]
+    async fn run_resyncs_resequenced_feed() {
+        let events_path = std::env::temp_dir().join("fulcrum-run-resyncs-resequenced-feed.jsonl");
+        let _ = std::fs::remove_file(&events_path);
+        let prices = Arc::new(MockPrices::default());
+        let (executor, _report) = BacktestExecutor::new();
+        // the feed goes back to an earlier block
+        let feed = RecordedFeed::new(vec![
+            include_bytes!("../../sequencer-feed/res/small.json").to_vec(),
+            include_bytes!("../../sequencer-feed/res/batch.json").to_vec(),
+        ]);
+
+        Engine::new(Arc::clone(&prices), executor, feed)
+            .with_event_sink(EventSink::file(&events_path).unwrap())
+            .run(&[], 0.0, true)
+            .await;
+
+        // the earlier block is not priced
+        assert_eq!(prices.requests.lock().unwrap().as_slice(), &[91_146_328]);
+        let events = std::fs::read_to_string(&events_path).unwrap();
+        let events: Vec<serde_json::Value> = events
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1]["event"], "resequenced");
+        assert_eq!(events[1]["block_number"], 88_416_071);
+        assert_eq!(events[1]["latest"], 91_146_328);
+        let _ = std::fs::remove_file(&events_path);
+    }
+
+    #[tokio::test]
+    async fn run_pauses_after_reorg() {
+        let prices = Arc::new(MockPrices::default());
+        let (executor, _report) = BacktestExecutor::new();
+        let feed = RecordedFeed::new(vec![
+            include_bytes!("../../sequencer-feed/res/batch.json").to_vec(),
+            include_bytes!("../../sequencer-feed/res/small.json").to_vec(),
+        ]);
+        let (chain_tx, chain_rx) = thingbuf::mpsc::channel(2);
+        chain_tx
+            .try_send(ChainInconsistency::Reorg {
+                block_number: 88_416_070,
+                depth: 2,
+            })
+            .unwrap();
+
+        // the reorg is handled before the feed, pausing beyond its last block
+        Engine::new(Arc::clone(&prices), executor, feed)
+            .with_chain_watch(chain_rx)
+            .with_reorg_pause(u64::MAX / 2)
+            .run(&[], 0.0, true)
+            .await;
+
+        // prices are resynced from the first block, the paused block is not priced
+        assert_eq!(prices.requests.lock().unwrap().as_slice(), &[88_416_071]);
+    }
 }
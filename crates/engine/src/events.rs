@@ -0,0 +1,423 @@
+//! Structured engine events
+//!
+//! Trade decisions in the engine loop are emitted as typed events to a configurable sink,
+//! either the logger (human readable) or JSON lines for post-hoc analysis.
+//! Events may also update the status page (see `EventSink::with_status`)
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::Path as FilePath,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ethers::types::Address;
+use log::{info, warn};
+use serde::{Serialize, Serializer};
+
+use crate::{
+    price_graph::CompositeTrade,
+    status::EngineStatus,
+    supervisor::Subsystem,
+    types::{Position, Token},
+};
+
+/// A trade decision made by the engine
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EngineEvent {
+    /// The feed is ahead of the price source, waiting for it to catch up
+    AwaitingSync { feed_block: u64, price_block: u64 },
+    /// The feed and price source are in sync
+    Synced { block_number: u64 },
+    /// Feed messages `from..=to` (sequence numbers) were missed, prices are resynced
+    FeedGap { from: u64, to: u64 },
+    /// The price source node replaced `depth` blocks from `block_number`, prices are resynced
+    Reorg {
+        block_number: u64,
+        depth: u64,
+        /// Latest block delivered by the feed
+        feed_block: u64,
+    },
+    /// The feed delivered `block_number` after the later block `latest`, prices are resynced
+    Resequenced { block_number: u64, latest: u64 },
+    /// The feed's block numbers differ from the node's, the feed's are shifted by `calibration` (if any) and prices
+    /// resynced
+    BlockNumberDrift {
+        feed_block: u64,
+        node_block: u64,
+        calibration: Option<i64>,
+    },
+    /// The feed lags, prices were advanced to `block_number` from the node's receipts
+    FeedFallback {
+        block_number: u64,
+        /// Pool states updated from swap logs
+        pool_updates: usize,
+    },
+    /// A subsystem panicked, it restarts after `backoff`
+    TaskRestarted {
+        subsystem: Subsystem,
+        /// Restarts of the subsystem so far
+        restarts: u32,
+        #[serde(serialize_with = "as_micros")]
+        backoff: Duration,
+        panic: String,
+    },
+    /// The feed ended a block without user txs, nothing to simulate
+    BlockEnded { block_number: u64 },
+    /// A message was delivered to the L1 delayed inbox, it is sequenced once the feed reads past `message_index`
+    DelayedMessage {
+        message_index: u64,
+        /// L1 message kind e.g. 3 = L2 message (force-included tx), 12 = ETH deposit
+        kind: u8,
+        sender: Address,
+        l1_block: u64,
+        /// Delayed messages read by the feed so far, `0` before the first feed message
+        delayed_messages_read: u64,
+    },
+    /// A feed batch was not simulated
+    BatchSkipped {
+        block_number: u64,
+        reason: SkipReason,
+    },
+    /// Txs from a feed batch were simulated against the price graph
+    BatchSimulated {
+        block_number: u64,
+        /// Block of the prices the txs were applied to
+        price_block: u64,
+        txs: usize,
+        /// True if any simulated trade changed prices
+        touched: bool,
+        #[serde(serialize_with = "as_micros")]
+        elapsed: Duration,
+    },
+    /// Decoding a feed message of `block_number` neared the arena's preallocated bytes or txs
+    ArenaNearLimit {
+        block_number: u64,
+        bytes: usize,
+        capacity_bytes: usize,
+        txs: usize,
+        tx_capacity: usize,
+    },
+    /// A trade path profitable after gas
+    ArbFound {
+        block_number: u64,
+        #[serde(serialize_with = "as_debug")]
+        token: Token,
+        amount_in: u128,
+        amount_out: u128,
+        #[serde(serialize_with = "as_display")]
+        path: CompositeTrade,
+    },
+    /// Trade paths were searched for arbs
+    ArbSearch {
+        block_number: u64,
+        #[serde(serialize_with = "as_micros")]
+        elapsed: Duration,
+    },
+    /// An arb was sent for execution, the best arb and any independent of it
+    OrderSubmitted {
+        block_number: u64,
+        #[serde(serialize_with = "as_debug")]
+        token: Token,
+        amount_in: u128,
+        amount_out: u128,
+        #[serde(serialize_with = "as_display")]
+        path: CompositeTrade,
+    },
+}
+
+/// Reason a feed batch was not simulated
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Prices for the batch could not be fetched in time
+    NoPrices,
+    /// The batch has trades which could not be simulated
+    Unsimulatable,
+    /// A trade of the batch moved a pool's price beyond the max. price impact
+    PriceImpact,
+    /// Batches are paused after a reorg or resequencing
+    ChainPaused,
+}
+
+impl fmt::Display for EngineEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AwaitingSync {
+                feed_block,
+                price_block,
+            } => write!(f, "awaiting feed <> price sync 🔄: {feed_block}/{price_block}"),
+            Self::Synced { block_number } => write!(f, "price feed sync'd ⚡️⚡️⚡️: {block_number}"),
+            Self::FeedGap { from, to } => write!(f, "feed gap 🕳️: missed {from}..={to}, resyncing"),
+            Self::Reorg {
+                block_number,
+                depth,
+                feed_block,
+            } => write!(
+                f,
+                "chain reorg 🔀: {depth} blocks from #{block_number} (feed: #{feed_block}), resyncing"
+            ),
+            Self::Resequenced {
+                block_number,
+                latest,
+            } => write!(
+                f,
+                "feed resequenced 🔀: #{block_number} after #{latest}, resyncing"
+            ),
+            Self::BlockNumberDrift {
+                feed_block,
+                node_block,
+                calibration: Some(offset),
+            } => write!(
+                f,
+                "block numbers drifted 📐: feed #{feed_block} node #{node_block}, shifting feed by {offset} blocks, resyncing"
+            ),
+            Self::BlockNumberDrift {
+                feed_block,
+                node_block,
+                calibration: None,
+            } => write!(
+                f,
+                "block numbers drifted 📐: feed #{feed_block} node #{node_block}"
+            ),
+            Self::FeedFallback {
+                block_number,
+                pool_updates,
+            } => write!(
+                f,
+                "feed lagging ⛑: prices advanced to #{block_number} from receipts ({pool_updates} pool updates)"
+            ),
+            Self::TaskRestarted {
+                subsystem,
+                restarts,
+                backoff,
+                panic,
+            } => write!(
+                f,
+                "{subsystem} restarted 🩹: panicked with '{panic}', restart #{restarts} in {backoff:?}"
+            ),
+            Self::BlockEnded { block_number } => write!(f, "end of block ⛓: #{block_number}"),
+            Self::DelayedMessage {
+                message_index,
+                kind,
+                sender,
+                l1_block,
+                delayed_messages_read,
+            } => write!(
+                f,
+                "L1 delayed message 📬: #{message_index} kind: {kind} from: {sender:?} (L1 #{l1_block}), feed read: {delayed_messages_read}"
+            ),
+            Self::BatchSkipped {
+                block_number,
+                reason: SkipReason::NoPrices,
+            } => write!(
+                f,
+                "skip batch: #{block_number} unable to fetch block: #{}",
+                block_number - 1
+            ),
+            Self::BatchSkipped {
+                block_number,
+                reason: SkipReason::Unsimulatable,
+            } => write!(f, "skip batch: #{block_number} unable to simulate trades"),
+            Self::BatchSkipped {
+                block_number,
+                reason: SkipReason::PriceImpact,
+            } => write!(
+                f,
+                "skip batch: #{block_number} trade exceeds the max. price impact"
+            ),
+            Self::BatchSkipped {
+                block_number,
+                reason: SkipReason::ChainPaused,
+            } => write!(
+                f,
+                "skip batch: #{block_number} paused after a chain inconsistency"
+            ),
+            Self::BatchSimulated {
+                block_number,
+                price_block,
+                txs,
+                elapsed,
+                ..
+            } => write!(
+                f,
+                "🛠️ applied {txs} txs from batch: #{block_number} to block: #{price_block} {elapsed:?}"
+            ),
+            Self::ArenaNearLimit {
+                block_number,
+                bytes,
+                capacity_bytes,
+                txs,
+                tx_capacity,
+            } => write!(
+                f,
+                "⚠️ decoding arena near limit (#{block_number}): {bytes}/{capacity_bytes} bytes, {txs}/{tx_capacity} txs"
+            ),
+            Self::ArbFound {
+                amount_in,
+                amount_out,
+                path,
+                ..
+            } => write!(
+                f,
+                "arb found 💵: {}%\n{path}",
+                *amount_out as f64 / *amount_in as f64
+            ),
+            Self::ArbSearch {
+                block_number,
+                elapsed,
+            } => write!(f, "checked arbs 🔎 (#{block_number}): {elapsed:?}"),
+            Self::OrderSubmitted {
+                block_number,
+                token,
+                amount_in,
+                amount_out,
+                path,
+            } => write!(
+                f,
+                "order submitted 📤 (#{block_number}): {} -> {}\n{path}",
+                Position::new(*amount_in, *token),
+                Position::new(*amount_out, *token),
+            ),
+        }
+    }
+}
+
+/// Output of an event sink
+#[derive(Debug, Default)]
+enum Output {
+    /// Human readable events to the logger
+    #[default]
+    Log,
+    /// JSON lines to stdout
+    Stdout,
+    /// JSON lines appended to a file
+    File(BufWriter<File>),
+}
+
+/// Destination for engine events
+#[derive(Debug, Default)]
+pub struct EventSink {
+    output: Output,
+    /// Updated from events, if the status page is served
+    status: Option<Arc<EngineStatus>>,
+}
+
+impl EventSink {
+    /// Open a sink by name, `log`, `stdout`, or otherwise a file path
+    pub fn open(target: &str) -> io::Result<Self> {
+        match target {
+            "log" => Ok(Self::default()),
+            "stdout" => Ok(Self {
+                output: Output::Stdout,
+                status: None,
+            }),
+            path => Self::file(path),
+        }
+    }
+    /// A sink appending JSON lines to the file at `path`
+    pub fn file<P: AsRef<FilePath>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            output: Output::File(BufWriter::new(file)),
+            status: None,
+        })
+    }
+    /// Also update `status` from emitted events
+    pub fn with_status(mut self, status: Arc<EngineStatus>) -> Self {
+        self.status = Some(status);
+        self
+    }
+    /// Emit `event` to the sink
+    pub fn emit(&mut self, event: &EngineEvent) {
+        if let Some(status) = self.status.as_ref() {
+            status.record(event);
+        }
+        let result = match &mut self.output {
+            Output::Log => {
+                info!("{event}");
+                Ok(())
+            }
+            Output::Stdout => write_record(&mut io::stdout().lock(), event),
+            Output::File(writer) => write_record(writer, event).and_then(|_| writer.flush()),
+        };
+        if let Err(err) = result {
+            warn!("event sink: {:?}", err);
+        }
+    }
+}
+
+/// Write `event` as a JSON line, prefixed with its unix timestamp (µs) `ts`
+fn write_record<W: Write>(writer: &mut W, event: &EngineEvent) -> io::Result<()> {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros();
+    let json = serde_json::to_vec(event)?;
+    // splice `ts` into the event object: {"ts":..,"event":..}
+    write!(writer, "{{\"ts\":{ts},")?;
+    writer.write_all(&json[1..])?;
+    writer.write_all(b"\n")
+}
+
+fn as_micros<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_micros() as u64)
+}
+
+fn as_debug<T: fmt::Debug, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{value:?}"))
+}
+
+fn as_display<T: fmt::Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{price_graph::Trade, types::Fee};
+
+    use super::*;
+
+    #[test]
+    fn event_json_lines() {
+        let mut buf = Vec::<u8>::new();
+        write_record(
+            &mut buf,
+            &EngineEvent::BatchSkipped {
+                block_number: 100,
+                reason: SkipReason::NoPrices,
+            },
+        )
+        .unwrap();
+        write_record(
+            &mut buf,
+            &EngineEvent::OrderSubmitted {
+                block_number: 100,
+                token: Token::WETH,
+                amount_in: 1_000,
+                amount_out: 1_010,
+                path: CompositeTrade::new(&[
+                    Trade::new(Token::WETH.id(), Token::ARB.id(), Fee::from_pips(500), 0),
+                    Trade::new(Token::ARB.id(), Token::WETH.id(), Fee::from_pips(3000), 0),
+                ]),
+            },
+        )
+        .unwrap();
+
+        let lines: Vec<serde_json::Value> = buf
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "batch_skipped");
+        assert_eq!(lines[0]["reason"], "no_prices");
+        assert!(lines[0]["ts"].as_u64().unwrap() > 0);
+        assert_eq!(lines[1]["event"], "order_submitted");
+        assert_eq!(lines[1]["token"], "WETH");
+        assert_eq!(lines[1]["amount_out"], 1_010);
+        assert!(lines[1]["path"].is_string());
+    }
+}
@@ -0,0 +1,302 @@
+//! Receipt fallback
+//!
+//! While the sequencer feed lags or is disconnected no txs are simulated, prices would stall at the last synced block.
+//! The fallback follows the node instead: each new block's receipts ('eth_getBlockReceipts') are scanned for logs of
+//! the monitored pools, Uniswap V3 `Swap` logs carry the pool's price and liquidity after the swap and Uniswap V2 style
+//! `Sync` logs its reserves, so the price graph is advanced by a state diff rather than a full sync.
+//! Once the feed recovers its next block is priced from the advanced graph (see `Engine::with_receipt_fallback`)
+use std::{collections::HashMap, time::Duration};
+
+use ethers::types::{Address, Log, H256, U256};
+use ethers_providers::{Middleware, Provider, ProviderError};
+use hex_literal::hex;
+
+use fulcrum_ws_cli::FastClient;
+
+use crate::{
+    price_graph::{Edge, PriceGraph},
+    types::{ExchangeId, Pair},
+};
+
+/// `Swap(address,address,int256,int256,uint160,uint128,int24)`
+const UNISWAP_V3_SWAP_TOPIC: H256 = H256(hex!(
+    "c42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67"
+));
+/// `Sync(uint112,uint112)`
+const UNISWAP_V2_SYNC_TOPIC: H256 = H256(hex!(
+    "1c411e9a96e071241c2f21f7726b17ae89e3cab4c78be50e062b03a9fffbbad1"
+));
+/// Default time without feed messages before the fallback follows the node
+pub const DEFAULT_FEED_LAG: Duration = Duration::from_secs(2);
+/// Max. blocks advanced from receipts, a graph further behind is resynced
+pub const MAX_FALLBACK_BLOCKS: u64 = 32;
+
+/// The state of a pool after a swap e.g. from its log
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PoolStateDiff {
+    UniswapV3 {
+        pool: Address,
+        sqrt_p_x96: U256,
+        liquidity: u128,
+    },
+    UniswapV2 {
+        pool: Address,
+        reserve_0: u128,
+        reserve_1: u128,
+    },
+}
+
+impl PoolStateDiff {
+    /// Decode a Uniswap V3 `Swap` or Uniswap V2 `Sync` log, `None` if it is neither
+    pub fn from_log(log: &Log) -> Option<Self> {
+        let topic = log.topics.first()?;
+        if *topic == UNISWAP_V3_SWAP_TOPIC && log.data.len() >= 160 {
+            // data: amount0, amount1, sqrtPriceX96, liquidity, tick
+            Some(Self::UniswapV3 {
+                pool: log.address,
+                sqrt_p_x96: U256::from_big_endian(&log.data[64..96]),
+                liquidity: u128::from_be_bytes(log.data[112..128].try_into().ok()?),
+            })
+        } else if *topic == UNISWAP_V2_SYNC_TOPIC && log.data.len() >= 64 {
+            // data: reserve0, reserve1
+            Some(Self::UniswapV2 {
+                pool: log.address,
+                reserve_0: u128::from_be_bytes(log.data[16..32].try_into().ok()?),
+                reserve_1: u128::from_be_bytes(log.data[48..64].try_into().ok()?),
+            })
+        } else {
+            None
+        }
+    }
+    /// The pool's address
+    pub fn pool(&self) -> Address {
+        match self {
+            Self::UniswapV3 { pool, .. } | Self::UniswapV2 { pool, .. } => *pool,
+        }
+    }
+}
+
+/// The monitored pools whose state is carried by their logs
+#[derive(Debug, Default)]
+pub struct MonitoredPools {
+    /// Uniswap V3 pools by address
+    v3: HashMap<Address, Pair>,
+    /// Uniswap V2 style pools by address
+    v2: HashMap<Address, Pair>,
+}
+
+impl MonitoredPools {
+    /// Monitor the `v3_pairs` and `v2_pairs` pools
+    pub fn new(v3_pairs: &[(Pair, Address)], v2_pairs: &[(Pair, Address)]) -> Self {
+        Self {
+            v3: v3_pairs.iter().map(|(pair, pool)| (*pool, *pair)).collect(),
+            v2: v2_pairs.iter().map(|(pair, pool)| (*pool, *pair)).collect(),
+        }
+    }
+    /// Returns true if the pool of `diff` is monitored
+    pub fn contains(&self, diff: &PoolStateDiff) -> bool {
+        match diff {
+            PoolStateDiff::UniswapV3 { pool, .. } => self.v3.contains_key(pool),
+            PoolStateDiff::UniswapV2 { pool, .. } => self.v2.contains_key(pool),
+        }
+    }
+    /// Apply `diff` to the pool's edge, returns false if the pool is not monitored
+    pub fn apply(&self, price_graph: &mut PriceGraph, diff: PoolStateDiff) -> bool {
+        match diff {
+            PoolStateDiff::UniswapV3 {
+                pool,
+                sqrt_p_x96,
+                liquidity,
+            } => {
+                let Some(pair) = self.v3.get(&pool) else {
+                    return false;
+                };
+                let edge = Edge::new_v3(sqrt_p_x96, liquidity.into(), pair.fee, true);
+                price_graph.add_edge(pair.token0, pair.token1, edge);
+            }
+            PoolStateDiff::UniswapV2 {
+                pool,
+                reserve_0,
+                reserve_1,
+            } => {
+                let Some(pair) = self.v2.get(&pool) else {
+                    return false;
+                };
+                let edge = Edge::new_v2(reserve_0, reserve_1, pair.fee, pair.exchange_id);
+                price_graph.add_edge(pair.token0, pair.token1, edge);
+            }
+        }
+        true
+    }
+    /// The states of the monitored pools touched since the graph's last reset e.g. by simulated trades
+    pub fn touched(&self, price_graph: &PriceGraph) -> Vec<PoolStateDiff> {
+        let dirty = price_graph.dirty();
+        let v3 = self.v3.iter().filter_map(|(pool, pair)| {
+            if !dirty.contains(pair.token0, pair.token1) {
+                return None;
+            }
+            let edge_id = Edge::hash(
+                pair.token0.id(),
+                pair.token1.id(),
+                ExchangeId::Uniswap as u8,
+                pair.fee,
+            );
+            match price_graph.edge_by_id(edge_id)? {
+                Edge::UniV3 {
+                    sqrt_p_x96,
+                    liquidity,
+                    ..
+                } => Some(PoolStateDiff::UniswapV3 {
+                    pool: *pool,
+                    sqrt_p_x96: *sqrt_p_x96,
+                    liquidity: liquidity.low_u128(),
+                }),
+                _ => None,
+            }
+        });
+        let v2 = self.v2.iter().filter_map(|(pool, pair)| {
+            if !dirty.contains(pair.token0, pair.token1) {
+                return None;
+            }
+            let edge_id = Edge::hash(
+                pair.token0.id(),
+                pair.token1.id(),
+                pair.exchange_id as u8,
+                pair.fee,
+            );
+            match price_graph.edge_by_id(edge_id)? {
+                Edge::UniV2 {
+                    reserve_in,
+                    reserve_out,
+                    ..
+                } => Some(PoolStateDiff::UniswapV2 {
+                    pool: *pool,
+                    reserve_0: *reserve_in,
+                    reserve_1: *reserve_out,
+                }),
+                _ => None,
+            }
+        });
+        v3.chain(v2).collect()
+    }
+}
+
+/// Advances price graphs from block receipts while the feed lags
+pub struct ReceiptFallback {
+    /// Price source node (ws)
+    provider: Provider<FastClient>,
+    pools: MonitoredPools,
+    /// Time without feed messages before the fallback follows the node
+    lag: Duration,
+}
+
+impl ReceiptFallback {
+    /// Follow the `v3_pairs` and `v2_pairs` pools via `provider`
+    pub fn new(
+        provider: Provider<FastClient>,
+        v3_pairs: &[(Pair, Address)],
+        v2_pairs: &[(Pair, Address)],
+    ) -> Self {
+        Self {
+            provider,
+            pools: MonitoredPools::new(v3_pairs, v2_pairs),
+            lag: DEFAULT_FEED_LAG,
+        }
+    }
+    /// Follow the node once the feed delivers no messages for `lag` (default: 2s)
+    pub fn with_lag(mut self, lag: Duration) -> Self {
+        self.lag = lag;
+        self
+    }
+    /// Time without feed messages before the fallback follows the node
+    pub fn lag(&self) -> Duration {
+        self.lag
+    }
+    /// Advance `price_graph` block by block to `block_number` from the blocks' receipts
+    ///
+    /// Returns the pool updates applied, the graph is left at the last block advanced on error
+    pub async fn advance(
+        &self,
+        price_graph: &mut PriceGraph,
+        block_number: u64,
+    ) -> Result<usize, ProviderError> {
+        let mut updates = 0;
+        for next in price_graph.block_number() + 1..=block_number {
+            for diff in block_swaps(&self.provider, next).await? {
+                updates += usize::from(self.pools.apply(price_graph, diff));
+            }
+            price_graph.set_block_number(next);
+        }
+
+        Ok(updates)
+    }
+}
+
+/// The pool states after each swap of `block_number`, in log order
+pub(crate) async fn block_swaps(
+    provider: &Provider<FastClient>,
+    block_number: u64,
+) -> Result<Vec<PoolStateDiff>, ProviderError> {
+    let receipts = provider.get_block_receipts(block_number).await?;
+    Ok(receipts
+        .iter()
+        .flat_map(|receipt| receipt.logs.iter())
+        .filter(|log| log.removed != Some(true))
+        .filter_map(PoolStateDiff::from_log)
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::types::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn pool_state_diff_from_log() {
+        let pool = Address::from(hex!("C6962004f452bE9203591991D15f6b388e09E8D0"));
+        let mut data = vec![0_u8; 160];
+        // sqrtPriceX96
+        data[84..96].copy_from_slice(&hex!("03e5ef3b4d5c4ee3c0a1b2c3"));
+        // liquidity
+        data[120..128].copy_from_slice(&1_234_567_890_u64.to_be_bytes());
+        let mut log = Log {
+            address: pool,
+            topics: vec![
+                UNISWAP_V3_SWAP_TOPIC,
+                H256::repeat_byte(0x11),
+                H256::repeat_byte(0x22),
+            ],
+            data: Bytes::from(data),
+            ..Default::default()
+        };
+        assert_eq!(
+            PoolStateDiff::from_log(&log),
+            Some(PoolStateDiff::UniswapV3 {
+                pool,
+                sqrt_p_x96: U256::from_big_endian(&hex!("03e5ef3b4d5c4ee3c0a1b2c3")),
+                liquidity: 1_234_567_890,
+            })
+        );
+
+        let mut data = vec![0_u8; 64];
+        data[24..32].copy_from_slice(&5_000_u64.to_be_bytes());
+        data[56..64].copy_from_slice(&7_000_u64.to_be_bytes());
+        log.topics = vec![UNISWAP_V2_SYNC_TOPIC];
+        log.data = Bytes::from(data);
+        assert_eq!(
+            PoolStateDiff::from_log(&log),
+            Some(PoolStateDiff::UniswapV2 {
+                pool,
+                reserve_0: 5_000,
+                reserve_1: 7_000,
+            })
+        );
+
+        // a transfer
+        log.topics[0] = H256(hex!(
+            "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+        ));
+        assert_eq!(PoolStateDiff::from_log(&log), None);
+    }
+}
@@ -0,0 +1,139 @@
+//! Fuzzing entry points (see /fuzz)
+//!
+//! Exposes the crate private calldata decoders to the fuzz targets, each must accept arbitrary input without panicking
+use ethabi_static::{DecodeStatic, Tuple};
+use fulcrum_sequencer_feed::TransactionInfo;
+
+use crate::{
+    constant::arbitrum::CURVE_2POOL,
+    paraswap::*,
+    router_decoder::{decode_call, decoder, CurvePoolDecoder, RouterDecoder},
+    trade_router::*,
+    types::RouterId,
+    zero_ex::*,
+};
+
+/// Every router with a calldata decoder
+const ROUTER_IDS: [RouterId; 16] = [
+    RouterId::UniswapV3RouterV1,
+    RouterId::UniswapV3RouterV2,
+    RouterId::UniswapV3UniversalRouter,
+    RouterId::SushiRouterV2,
+    RouterId::CamelotRouterV2,
+    RouterId::Gmx,
+    RouterId::ParaswapAugustus,
+    RouterId::OneInch,
+    RouterId::ZeroEx,
+    RouterId::Odos,
+    RouterId::SushiRouteProcessor3,
+    RouterId::SushiRouteProcessor4,
+    RouterId::BalancerVault,
+    RouterId::RamsesRouter,
+    RouterId::KyberSwap,
+    RouterId::OdosV2,
+];
+
+/// Decode `data` as each ABI type, bare and as a tuple
+macro_rules! decode_all {
+    ($data:expr, $($ty:ident),* $(,)?) => {
+        $(
+            let _ = $ty::decode($data);
+            let _ = Tuple::<$ty>::decode($data);
+        )*
+    };
+}
+
+/// Decode `data` as every router call and payload ABI type
+pub fn decode_abi(data: &[u8]) {
+    decode_all!(
+        data,
+        SwapExactTokensForETH,
+        SwapExactETHForTokens,
+        SwapExactETHForTokensSFOTT,
+        SwapExactTokensForEthSFOTT,
+        GmxSwap,
+        GmxSwapETHToTokens,
+        ProcessRoute,
+        TransferValueAndProcessRoute,
+        BalancerSingleSwap,
+        BalancerBatchSwap,
+        BalancerBatchSwapStep,
+        CurveExchange,
+        SolidlyRoute,
+        SolidlySwapExactTokensFor,
+        SolidlySwapExactETHForTokens,
+        KyberSwapDescription,
+        KyberSwapExecution,
+        KyberSwapSimpleMode,
+        InputTokenOdos,
+        OutputTokenOdos,
+        OdosSwap,
+        OdosV2Swap,
+        OneInchSwap,
+        OneInchUniswapV3Swap,
+        OneInchUniswapV3SwapTWP,
+        UniswapV3ExactOutputSingleParamsV1,
+        UniswapV3ExactOutputSingleParamsV2,
+        UniswapV3ExactOutputParamsV2,
+        UniswapV3ExactOutputParamsV1,
+        UniswapV3ExactInputParamsV2,
+        UniswapV3ExactInputSingleParamsV2,
+        UniswapV3ExactInputParamsV1,
+        UniswapV3ExactInputSingleParamsV1,
+        UniswapV3UniversalExecuteParams,
+        UniswapV3UniversalExecuteDeadlineParams,
+        UniswapV3UniversalRouterSwapExactIn,
+        UniswapV3UniversalRouterSwapExactOut,
+        UniswapV2UniversalRouterSwapExactIn,
+        UniswapV2UniversalRouterSwapExactOut,
+        UniversalRouterPermit2TransferFrom,
+        UniversalRouterPayPortion,
+        UniswapV4Swap,
+        UniswapV4SwapSingleParams,
+        UniswapV4SwapParams,
+        UniswapV4PathKey,
+        UniswapV3MultiCall,
+        UniswapV3MultiCallDeadline,
+        LimitOrderInfo,
+        RfqOrderInfo,
+        OtcOrderInfo,
+        Transformation,
+        TransformErc20,
+        BridgeOrder,
+        FillQuoteTransformData,
+        UniswapV3Mixin,
+        KyberElasticMixin,
+        UniswapV2Mixin,
+        SimpleData,
+        SellData,
+        MegaSwapSellData,
+        MegaSwapPath,
+        Path,
+        Adapter,
+        Route,
+        UniswapV2Data,
+        UniswapV3Data,
+    );
+}
+
+/// Decode `input` as a call to every router, malformed calls should be skipped
+pub fn decode_router_call(input: &[u8]) {
+    let tx = TransactionInfo {
+        to: Default::default(),
+        value: Default::default(),
+        input,
+        nonce: 0,
+        gas_limit: 0,
+        max_fee_per_gas: 0,
+    };
+    for router_id in ROUTER_IDS {
+        let _ = decoder(router_id).decode(&tx);
+    }
+    // curve pools are called directly
+    let _ = CurvePoolDecoder.decode(&TransactionInfo {
+        to: CURVE_2POOL.into(),
+        ..tx
+    });
+    // unknown router
+    assert!(decode_call(&tx).steps.is_empty());
+}
@@ -0,0 +1,139 @@
+//! Order tx gas model
+//!
+//! Order tx gas is modelled per path as the flash loan and executor overhead plus a cost per trade by pool kind,
+//! scaled by a calibration factor learned from `eth_estimateGas` of built order txs and the gas used by included ones
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{price_graph::CompositeTrade, types::ExchangeId};
+
+/// Gas of the flash loan, executor dispatch, and payout, from foundry gas reports
+const DEFAULT_BASE_GAS: u64 = 150_000;
+/// Gas of a concentrated liquidity (uniswap v3 style), curve, or balancer trade, from foundry gas reports
+const DEFAULT_V3_TRADE_GAS: u64 = 165_000;
+/// Gas of a constant product (uniswap v2 style) trade, from foundry gas reports
+const DEFAULT_V2_TRADE_GAS: u64 = 95_000;
+/// Order tx gas limit over the modelled gas (basis points)
+const GAS_LIMIT_MARGIN_BPS: u64 = 15_000;
+/// Calibration scale of the uncalibrated model (ppm)
+const UNIT_SCALE_PPM: u64 = 1_000_000;
+/// Bounds of a single calibration sample (ppm), outliers e.g. a partial revert are clamped
+const SAMPLE_SCALE_PPM: (u64, u64) = (250_000, 4_000_000);
+
+/// Models the gas of order txs by trade path
+#[derive(Debug)]
+pub struct GasModel {
+    /// Gas of an order with no trades
+    base: u64,
+    /// Gas per concentrated liquidity, curve, or balancer trade
+    v3_trade: u64,
+    /// Gas per constant product trade
+    v2_trade: u64,
+    /// Ratio of observed to modelled gas (ppm), updated by `calibrate`
+    scale_ppm: AtomicU64,
+}
+
+impl Default for GasModel {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_GAS, DEFAULT_V3_TRADE_GAS, DEFAULT_V2_TRADE_GAS)
+    }
+}
+
+impl GasModel {
+    /// Model order txs costing `base` gas plus `v3_trade` or `v2_trade` gas per trade by pool kind
+    pub fn new(base: u64, v3_trade: u64, v2_trade: u64) -> Self {
+        Self {
+            base,
+            v3_trade,
+            v2_trade,
+            scale_ppm: AtomicU64::new(UNIT_SCALE_PPM),
+        }
+    }
+    /// Uncalibrated gas of an order tx for `trade`
+    fn modelled(&self, trade: &CompositeTrade) -> u64 {
+        trade.hops().iter().fold(self.base, |gas, hop| {
+            gas + match hop.exchange_id {
+                id if id == ExchangeId::Uniswap as u8
+                    || id == ExchangeId::UniswapV4 as u8
+                    || id == ExchangeId::KyberElastic as u8
+                    || id == ExchangeId::Balancer as u8
+                    || id == ExchangeId::Curve as u8 =>
+                {
+                    self.v3_trade
+                }
+                _ => self.v2_trade,
+            }
+        })
+    }
+    /// Expected gas used by an order tx for `trade`
+    pub fn estimate(&self, trade: &CompositeTrade) -> u64 {
+        self.modelled(trade) * self.scale_ppm.load(Ordering::Relaxed) / UNIT_SCALE_PPM
+    }
+    /// Gas limit of an order tx for `trade`
+    pub fn gas_limit(&self, trade: &CompositeTrade) -> u64 {
+        self.estimate(trade) * GAS_LIMIT_MARGIN_BPS / 10_000
+    }
+    /// Calibrate the model with `gas` observed for an order tx for `trade` e.g. by `eth_estimateGas`
+    ///
+    /// Samples are averaged (exponentially weighted) so a single outlier does not swing the model
+    pub fn calibrate(&self, trade: &CompositeTrade, gas: u64) {
+        let sample = (gas * UNIT_SCALE_PPM / self.modelled(trade))
+            .clamp(SAMPLE_SCALE_PPM.0, SAMPLE_SCALE_PPM.1);
+        let _ = self
+            .scale_ppm
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |scale| {
+                Some((scale * 3 + sample) / 4)
+            });
+    }
+    /// Ratio of observed to modelled gas
+    pub fn scale(&self) -> f64 {
+        self.scale_ppm.load(Ordering::Relaxed) as f64 / UNIT_SCALE_PPM as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{price_graph::Trade, types::Fee};
+
+    #[test]
+    fn gas_by_path() {
+        let model = GasModel::default();
+        let v2_2hop = CompositeTrade::new(&[
+            Trade::new(0, 1, Fee::ZERO, ExchangeId::Sushi as u8),
+            Trade::new(1, 0, Fee::ZERO, ExchangeId::Camelot as u8),
+        ]);
+        let v3_2hop = CompositeTrade::new(&[
+            Trade::new(0, 1, Fee::from_pips(500), ExchangeId::Uniswap as u8),
+            Trade::new(1, 0, Fee::from_pips(3_000), ExchangeId::Uniswap as u8),
+        ]);
+        let v3_3hop = CompositeTrade::new(&[
+            Trade::new(0, 1, Fee::from_pips(500), ExchangeId::Uniswap as u8),
+            Trade::new(1, 3, Fee::ZERO, ExchangeId::Sushi as u8),
+            Trade::new(3, 0, Fee::from_pips(3_000), ExchangeId::Uniswap as u8),
+        ]);
+        assert_eq!(model.estimate(&v2_2hop), 150_000 + 2 * 95_000);
+        assert_eq!(model.estimate(&v3_2hop), 150_000 + 2 * 165_000);
+        assert_eq!(model.estimate(&v3_3hop), 150_000 + 2 * 165_000 + 95_000);
+        assert_eq!(model.gas_limit(&v3_2hop), 720_000);
+    }
+
+    #[test]
+    fn calibrate_converges() {
+        let model = GasModel::default();
+        let trade = CompositeTrade::new(&[
+            Trade::new(0, 1, Fee::from_pips(500), ExchangeId::Uniswap as u8),
+            Trade::new(1, 0, Fee::from_pips(3_000), ExchangeId::Uniswap as u8),
+        ]);
+        // the chain uses 20% less gas than modelled
+        for _ in 0..32 {
+            model.calibrate(&trade, 384_000);
+        }
+        assert!((model.scale() - 0.8).abs() < 0.001);
+        assert!(model.estimate(&trade).abs_diff(384_000) < 500);
+
+        // outliers are clamped
+        let model = GasModel::default();
+        model.calibrate(&trade, 0);
+        assert_eq!(model.scale(), 0.8125);
+    }
+}
@@ -0,0 +1,96 @@
+//! Minimal HTTP server for the engine's local endpoints (metrics, price queries, status page)
+//!
+//! Each connection is answered from its request line and closed, enough for scrapers, dashboards, and curl
+use std::{sync::Arc, time::Duration};
+
+use log::{debug, warn};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+/// Delay before accepting again after an accept error e.g. too many open files
+pub(crate) const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// An HTTP response (status code, content type, body)
+pub(crate) type Response = (u16, &'static str, String);
+
+/// Serve connections on `listener`, answering each request line with `handler`
+///
+/// Accept errors are logged and the listener keeps serving, `name` labels the logs
+pub(crate) async fn serve<H>(name: &'static str, listener: TcpListener, handler: H)
+where
+    H: Fn(&str) -> Response + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    loop {
+        let (mut stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(err) => {
+                warn!("{name} accept: {:?}", err);
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let handler = Arc::clone(&handler);
+        tokio::spawn(async move {
+            let mut request = [0_u8; 1024];
+            let n = stream.read(&mut request).await.unwrap_or_default();
+            let request = String::from_utf8_lossy(&request[..n]);
+            let (status, content_type, body) = handler(request.lines().next().unwrap_or_default());
+            let response = format!(
+                "HTTP/1.1 {status} {}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                reason(status),
+                body.len()
+            );
+            if let Err(err) = stream.write_all(response.as_bytes()).await {
+                debug!("{name} response ({peer}): {:?}", err);
+            }
+        });
+    }
+}
+
+/// The reason phrase of `status`
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::net::TcpStream;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn serves_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve("test", listener, |request| match request {
+            "GET /ping HTTP/1.1" => (200, "text/plain", "pong".to_string()),
+            _ => (404, "text/plain", String::new()),
+        }));
+
+        for (request, expected) in [
+            (
+                "GET /ping HTTP/1.1\r\n\r\n",
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 4\r\nConnection: close\r\n\r\npong",
+            ),
+            (
+                "GET / HTTP/1.1\r\n\r\n",
+                "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            ),
+        ] {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            assert_eq!(response, expected);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! L1 delayed inbox watcher
+//!
+//! Deposits, retryables, and force-included txs are delivered to the Arbitrum bridge on L1 and sequenced on L2
+//! some time later, watching the bridge gives notice of them before they appear in L2 state
+use ethers::types::{Address, Filter, Log, H256, U256};
+use ethers_providers::{Middleware, Provider};
+use futures::StreamExt;
+use hex_literal::hex;
+use log::{info, warn};
+use thingbuf::mpsc::{channel, Receiver};
+
+use fulcrum_ws_cli::FastClient;
+
+/// Arbitrum One bridge (L1), emits `MessageDelivered` for each delayed inbox message
+pub const ARBITRUM_ONE_BRIDGE: [u8; 20] = hex!("8315177aB297bA92A06054cE80a67Ed4DBd7ed3a");
+/// `MessageDelivered(uint256,bytes32,address,uint8,address,bytes32,uint256,uint64)`
+const MESSAGE_DELIVERED_TOPIC: H256 = H256(hex!(
+    "5e3c1311ea442664e8b1611bfabef659120ea7a0a2cfc0667700bebc69cbffe1"
+));
+/// Max. delivered messages queued for the engine, later messages are dropped until it catches up
+const DELAYED_MESSAGE_QUEUE_SIZE: usize = 64;
+
+/// A message delivered to the L1 delayed inbox
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DelayedMessage {
+    /// Index in the delayed inbox, the message is in L2 state once the feed's delayed messages read exceeds it
+    pub message_index: u64,
+    /// L1 message kind e.g. 3 = L2 message (force-included tx), 9 = retryable, 12 = ETH deposit
+    pub kind: u8,
+    /// The L1 sender
+    pub sender: Address,
+    /// L1 block the message was delivered in
+    pub l1_block: u64,
+}
+
+impl DelayedMessage {
+    /// Decode a bridge `MessageDelivered` log, `None` if it is not one
+    pub fn from_log(log: &Log) -> Option<Self> {
+        // data: inbox, kind, sender, messageDataHash, baseFeeL1, timestamp
+        if log.topics.len() != 3 || log.topics[0] != MESSAGE_DELIVERED_TOPIC || log.data.len() < 192
+        {
+            return None;
+        }
+        Some(Self {
+            message_index: U256::from_big_endian(log.topics[1].as_bytes()).low_u64(),
+            kind: log.data[63],
+            sender: Address::from_slice(&log.data[76..96]),
+            l1_block: log.block_number?.as_u64(),
+        })
+    }
+}
+
+/// Watches the L1 delayed inbox for delivered messages via an L1 node ('eth_subscribe' logs)
+pub struct L1InboxWatcher {
+    /// L1 node (ws)
+    provider: Provider<FastClient>,
+    /// Bridge contract emitting delivered messages
+    bridge: Address,
+}
+
+impl L1InboxWatcher {
+    /// Watch the Arbitrum One bridge via `provider`
+    pub fn new(provider: Provider<FastClient>) -> Self {
+        Self {
+            provider,
+            bridge: ARBITRUM_ONE_BRIDGE.into(),
+        }
+    }
+    /// Watch `bridge` rather than the Arbitrum One bridge
+    pub fn with_bridge(mut self, bridge: Address) -> Self {
+        self.bridge = bridge;
+        self
+    }
+    /// Start watching in the background, returning the queue of delivered messages
+    ///
+    /// The queue closes if the subscription fails or ends, reorged logs are ignored
+    pub fn start(self) -> Receiver<DelayedMessage> {
+        let (tx, rx) = channel::<DelayedMessage>(DELAYED_MESSAGE_QUEUE_SIZE);
+        tokio::spawn(async move {
+            let filter = Filter::new()
+                .address(self.bridge)
+                .topic0(MESSAGE_DELIVERED_TOPIC);
+            let mut logs = match self.provider.subscribe_logs(&filter).await {
+                Ok(logs) => logs,
+                Err(err) => {
+                    warn!("L1 inbox subscription: {:?}", err);
+                    return;
+                }
+            };
+            info!("watching L1 inbox 📬: {:?}", self.bridge);
+            while let Some(log) = logs.next().await {
+                if log.removed == Some(true) {
+                    continue;
+                }
+                if let Some(message) = DelayedMessage::from_log(&log) {
+                    if tx.try_send(message).is_err() {
+                        warn!("L1 inbox queue full, drop: #{}", message.message_index);
+                    }
+                }
+            }
+            warn!("L1 inbox subscription ended");
+        });
+
+        rx
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::types::{Bytes, U64};
+
+    use super::*;
+
+    #[test]
+    fn delayed_message_from_log() {
+        let sender = Address::from(hex!("1d3e2ab9b4ad0e1bd2ec1dd1b2e0cf9cb8b0e1b2"));
+        let mut data = vec![0_u8; 192];
+        data[12..32].copy_from_slice(&hex!("4Dbd4fc535Ac27206064B68FfCf827b0A60BAB3f"));
+        data[63] = 12;
+        data[76..96].copy_from_slice(sender.as_bytes());
+        let mut log = Log {
+            address: ARBITRUM_ONE_BRIDGE.into(),
+            topics: vec![
+                MESSAGE_DELIVERED_TOPIC,
+                H256::from_low_u64_be(852_410),
+                H256::repeat_byte(0xaa),
+            ],
+            data: Bytes::from(data),
+            block_number: Some(U64::from(17_269_600_u64)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            DelayedMessage::from_log(&log),
+            Some(DelayedMessage {
+                message_index: 852_410,
+                kind: 12,
+                sender,
+                l1_block: 17_269_600,
+            })
+        );
+
+        log.topics[0] = H256::zero();
+        assert_eq!(DelayedMessage::from_log(&log), None);
+    }
+}
@@ -0,0 +1,188 @@
+//! Payload decoding tools
+//!
+//! Runs the sequencer feed and router calldata decoders over recorded payloads or the live feed, printing what
+//! the engine would see, so decode issues can be reproduced without writing a test
+use std::io::{self, Write};
+
+use bumpalo::Bump;
+use ethers::{types::Address, utils::hex};
+use futures::{pin_mut, StreamExt};
+use log::{info, warn};
+use serde_json::{json, Value};
+
+use fulcrum_sequencer_feed::{
+    decode_feed_message, decode_typed_tx, SequencerFeed, TransactionInfo, TxBuffer, TxExporter,
+};
+
+use crate::router_decoder::decode_call;
+
+/// Decode a raw sequencer feed JSON `message`, its txs and their trades
+pub fn decode_feed_payload(message: &mut [u8]) -> Value {
+    let bump = Bump::new();
+    let mut tx_buffer = TxBuffer::new(&bump);
+    match decode_feed_message(message, &mut tx_buffer) {
+        Ok(event) => json!({
+            "block_number": event.block_number(),
+            "event": format!("{event:?}"),
+            "txs": tx_buffer.as_slice().iter().map(tx_json).collect::<Vec<_>>(),
+        }),
+        Err(err) => json!({ "error": format!("{err:?}") }),
+    }
+}
+
+/// Decode a signed, RLP encoded tx `raw_tx` (as returned by `eth_getRawTransactionByHash`) and its trades
+pub fn decode_raw_tx(raw_tx: &[u8]) -> Value {
+    match decode_typed_tx(raw_tx) {
+        Ok(typed_tx) => match typed_tx.into_info() {
+            Some(tx) => tx_json(&tx),
+            None => json!({ "error": "not a contract call" }),
+        },
+        Err(err) => json!({ "error": format!("{err:?}") }),
+    }
+}
+
+/// Selects txs by destination and method selector, empty criteria match any tx
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TxFilter {
+    /// Match calls to any of these addresses
+    pub to: Vec<Address>,
+    /// Match calls of any of these 4-byte method selectors
+    pub selectors: Vec<[u8; 4]>,
+}
+
+impl TxFilter {
+    /// Returns true if `tx` matches the filter
+    pub fn matches(&self, tx: &TransactionInfo) -> bool {
+        (self.to.is_empty() || self.to.contains(&tx.to))
+            && (self.selectors.is_empty()
+                || tx
+                    .input
+                    .get(..4)
+                    .is_some_and(|selector| self.selectors.iter().any(|s| s == selector)))
+    }
+}
+
+/// Stream txs from the live `feed` matching `filter` to stdout as JSON lines, with their decoded trades
+///
+/// Returns when the feed closes or stdout does
+pub async fn tap(feed: SequencerFeed, filter: &TxFilter) {
+    let batches = feed.stream();
+    pin_mut!(batches);
+    while let Some(batch) = batches.next().await {
+        let mut stdout = io::stdout().lock();
+        for tx in batch.txs().iter().filter(|tx| filter.matches(tx)) {
+            let mut decoded = tx_json(tx);
+            decoded["block_number"] = batch.block_number().into();
+            if let Err(err) = writeln!(stdout, "{decoded}") {
+                warn!("tap: {:?}", err);
+                return;
+            }
+        }
+    }
+}
+
+/// Export txs from the live `feed` matching `filter` with `exporter`, until the feed closes or ctrl-c
+pub async fn capture(feed: SequencerFeed, filter: &TxFilter, exporter: TxExporter) {
+    let batches = feed.stream();
+    pin_mut!(batches);
+    loop {
+        let batch = tokio::select! {
+            batch = batches.next() => match batch {
+                Some(batch) => batch,
+                None => break,
+            },
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        exporter.record(
+            batch.block_number(),
+            batch.txs().iter().filter(|tx| filter.matches(tx)),
+        );
+    }
+    let dropped = exporter.dropped();
+    match exporter.finish() {
+        Ok(txs) => info!("captured {txs} txs, {dropped} feed messages dropped"),
+        Err(err) => warn!("capture: {:?}", err),
+    }
+}
+
+/// A decoded tx and the trades of its router call, if any
+fn tx_json(tx: &TransactionInfo) -> Value {
+    json!({
+        "to": tx.to,
+        "value": tx.value,
+        "nonce": tx.nonce,
+        "gas_limit": tx.gas_limit,
+        "max_fee_per_gas": tx.max_fee_per_gas.to_string(),
+        "input": format!("0x{}", hex::encode(tx.input)),
+        "trades": decode_call(tx),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use bumpalo::Bump;
+    use fulcrum_sequencer_feed::{decode_feed_message, TxBuffer};
+    use hex_literal::hex;
+    use serde_json::json;
+
+    use super::{decode_feed_payload, decode_raw_tx, TxFilter};
+
+    #[test]
+    fn decode_feed_batch() {
+        let mut batch_json = include_bytes!("../../sequencer-feed/res/batch.json").to_owned();
+        let decoded = decode_feed_payload(batch_json.as_mut_slice());
+        assert_eq!(decoded["block_number"], 88_416_071);
+        let txs = decoded["txs"].as_array().unwrap();
+        assert!(!txs.is_empty());
+        assert_eq!(txs[0]["to"], "0x64fe52bccd0035daa698ab504631f98e0972c340");
+        assert!(txs[0]["input"].as_str().unwrap().starts_with("0x095ea7b3"));
+        assert!(txs.iter().all(|tx| tx["trades"]["steps"].is_array()));
+
+        // no sequence number
+        let decoded = decode_feed_payload(b"{}".to_vec().as_mut_slice());
+        assert_eq!(decoded["event"], "Empty");
+    }
+
+    #[test]
+    fn decode_signed_tx() {
+        // WETH withdraw
+        let raw_tx = hex!("01f8a482a4b1038405f5e10082ea609482af49447d8a07e3bd95bd0d56f35241523fbab180842e1a7d4df838f79482af49447d8a07e3bd95bd0d56f35241523fbab1e1a0000000000000000000000000000000000000000000000000000000000000000001a01111111111111111111111111111111111111111111111111111111111111111a02222222222222222222222222222222222222222222222222222222222222222");
+        let decoded = decode_raw_tx(&raw_tx);
+        assert_eq!(decoded["to"], "0x82af49447d8a07e3bd95bd0d56f35241523fbab1");
+        assert_eq!(decoded["nonce"], 3);
+        assert_eq!(decoded["input"], "0x2e1a7d4d");
+        assert_eq!(decoded["trades"]["steps"], json!([]));
+
+        assert!(decode_raw_tx(&[]).get("error").is_some());
+        // not a tx envelope
+        assert!(decode_raw_tx(&[0x04, 0x01]).get("error").is_some());
+    }
+
+    #[test]
+    fn tx_filter_matches() {
+        let mut batch_json = include_bytes!("../../sequencer-feed/res/batch.json").to_owned();
+        let bump = Bump::new();
+        let mut tx_buffer = TxBuffer::new(&bump);
+        decode_feed_message(batch_json.as_mut_slice(), &mut tx_buffer).unwrap();
+        let txs = tx_buffer.as_slice();
+        let matching = |filter: &TxFilter| txs.iter().filter(|tx| filter.matches(tx)).count();
+
+        assert_eq!(matching(&TxFilter::default()), txs.len());
+        let to = TxFilter {
+            to: vec![txs[0].to],
+            ..Default::default()
+        };
+        assert!(matching(&to) >= 1);
+        // ERC-20 approve
+        let approve = TxFilter {
+            selectors: vec![hex!("095ea7b3")],
+            ..Default::default()
+        };
+        assert!(matching(&approve) >= 1);
+        let none = TxFilter {
+            to: vec![txs[0].to],
+            selectors: vec![hex!("ffffffff")],
+        };
+        assert_eq!(matching(&none), 0);
+    }
+}
@@ -0,0 +1,347 @@
+//! KyberSwap Elastic price source
+//!
+//! Elastic pools are concentrated liquidity pools (as Uniswap V3) which reinvest swap fees as liquidity in range of the
+//! current price. The fee of a swap mints reinvestment liquidity (`deltaL`) that moves the price as the swap fills,
+//! swaps price with the base + reinvestment liquidity
+use ethers::types::U512;
+
+use crate::{
+    types::{Fee, U256},
+    uniswap_v3::X96,
+    util::saturating_u128,
+};
+
+/// Elastic fees are in 1/100_000ths ('fee units')
+pub const FEE_UNITS: u32 = 100_000;
+
+/// `a * b / denominator` in U512, saturating at `U256::MAX`
+fn mul_div(a: U256, b: U256, denominator: U256, round_up: bool) -> U256 {
+    let (quotient, remainder) = (U512::from(a) * U512::from(b)).div_mod(U512::from(denominator));
+    let quotient = if round_up && !remainder.is_zero() {
+        quotient + U512::one()
+    } else {
+        quotient
+    };
+    U256::try_from(quotient).unwrap_or(U256::MAX)
+}
+
+/// Reinvestment liquidity minted by the fee of an exact input swap as `SwapMath.estimateIncrementalLiquidity`
+fn incremental_liquidity_in(
+    amount_in: U256,
+    sqrt_p_x96: U256,
+    fee_units: u32,
+    zero_for_one: bool,
+) -> U256 {
+    let amount_fee = amount_in.saturating_mul(U256::from(fee_units));
+    if zero_for_one {
+        // deltaL = fee * amount_in * √P / 2
+        mul_div(
+            sqrt_p_x96,
+            amount_fee,
+            U256::from(2 * FEE_UNITS) << 96,
+            false,
+        )
+    } else {
+        // deltaL = fee * amount_in / (√P * 2)
+        mul_div(
+            *X96,
+            amount_fee,
+            U256::from(2 * FEE_UNITS) * sqrt_p_x96,
+            false,
+        )
+    }
+}
+
+/// Reinvestment liquidity minted by the fee of an exact output swap as `SwapMath.estimateIncrementalLiquidity`
+/// i.e. the smaller root of `fee·x² - 2bx + c = 0`
+///
+/// `None` if `amount_out` exceeds the pool's virtual reserves
+fn incremental_liquidity_out(
+    amount_out: U256,
+    sqrt_p_x96: U256,
+    liquidity: U256,
+    fee_units: u32,
+    zero_for_one: bool,
+) -> Option<U256> {
+    if fee_units == 0 {
+        return Some(U256::zero());
+    }
+    let (a, amount_out) = (U512::from(fee_units), U512::from(amount_out));
+    let (sqrt_p_x96, liquidity, x96) = (
+        U512::from(sqrt_p_x96),
+        U512::from(liquidity),
+        U512::from(*X96),
+    );
+    // the output token's value in the other token, at the current price
+    let value_out = if zero_for_one {
+        amount_out * x96 / sqrt_p_x96
+    } else {
+        amount_out * sqrt_p_x96 / x96
+    };
+    let b = (U512::from(FEE_UNITS - fee_units) * liquidity)
+        .checked_sub(U512::from(FEE_UNITS) * value_out)?;
+    let c = a * liquidity * value_out;
+    let root = (b * b).checked_sub(a * c)?.integer_sqrt();
+
+    U256::try_from((b - root) / a).ok()
+}
+
+/// Get the amount out given some amount in, within the current tick range
+///
+/// - `current_sqrt_p_x96` The √P.96
+/// - `liquidity` The base + reinvestment liquidity
+/// - `fee` the pool fee, applied in 1/100_000ths as the pool
+///
+/// Returns the new √P.96, the reinvestment liquidity minted, and the amount of tokens output
+pub fn get_amount_out(
+    amount_in: u128,
+    current_sqrt_p_x96: &U256,
+    liquidity: &U256,
+    fee: Fee,
+    zero_for_one: bool,
+) -> (U256, U256, u128) {
+    let (sqrt_p_x96, liquidity, fee_units) = (*current_sqrt_p_x96, *liquidity, fee.units());
+    if sqrt_p_x96.is_zero() || liquidity.is_zero() {
+        return (sqrt_p_x96, U256::zero(), 0);
+    }
+    let amount_in = U256::from(amount_in);
+    let delta_l = incremental_liquidity_in(amount_in, sqrt_p_x96, fee_units, zero_for_one);
+    // as `SwapMath.calcFinalPrice` and `SwapMath.calcReturnedAmount`
+    if zero_for_one {
+        // √P' = (L + ΔL) / (L / √P + amount_in)
+        let value_in = mul_div(amount_in, sqrt_p_x96, *X96, false);
+        let next_sqrt_p_x96 = mul_div(
+            liquidity.saturating_add(delta_l),
+            sqrt_p_x96,
+            liquidity.saturating_add(value_in),
+            true,
+        );
+        // amount_out = L·(√P - √P') - ΔL·√P'
+        let amount_out = mul_div(liquidity, sqrt_p_x96 - next_sqrt_p_x96, *X96, false)
+            .saturating_sub(mul_div(delta_l, next_sqrt_p_x96, *X96, true));
+        (next_sqrt_p_x96, delta_l, saturating_u128(amount_out))
+    } else {
+        // √P' = (L·√P + amount_in) / (L + ΔL)
+        let value_in = mul_div(amount_in, *X96, sqrt_p_x96, false);
+        let next_sqrt_p_x96 = mul_div(
+            liquidity.saturating_add(value_in),
+            sqrt_p_x96,
+            liquidity.saturating_add(delta_l),
+            false,
+        );
+        // amount_out = L / √P - (L + ΔL) / √P'
+        let amount_out = mul_div(liquidity, *X96, sqrt_p_x96, false).saturating_sub(mul_div(
+            liquidity.saturating_add(delta_l),
+            *X96,
+            next_sqrt_p_x96,
+            true,
+        ));
+        (next_sqrt_p_x96, delta_l, saturating_u128(amount_out))
+    }
+}
+
+/// `get_amount_out` with float (speed > precision)
+pub fn get_amount_out_f(
+    amount_in: u128,
+    current_sqrt_p_x96: f64,
+    liquidity: f64,
+    fee: Fee,
+    zero_for_one: bool,
+) -> f64 {
+    if current_sqrt_p_x96 == 0.0 || liquidity == 0.0 {
+        return 0.0;
+    }
+    let x96 = 2_f64.powi(96);
+    let (amount_in, fee) = (amount_in as f64, fee.as_f64());
+    if zero_for_one {
+        let delta_l = fee * amount_in * current_sqrt_p_x96 / (2.0 * x96);
+        let next_sqrt_p_x96 = (liquidity + delta_l) * current_sqrt_p_x96
+            / (liquidity + amount_in * current_sqrt_p_x96 / x96);
+        ((liquidity * (current_sqrt_p_x96 - next_sqrt_p_x96) - delta_l * next_sqrt_p_x96) / x96)
+            .max(0.0)
+    } else {
+        let delta_l = fee * amount_in * x96 / (2.0 * current_sqrt_p_x96);
+        let next_sqrt_p_x96 = (liquidity + amount_in * x96 / current_sqrt_p_x96)
+            * current_sqrt_p_x96
+            / (liquidity + delta_l);
+        (liquidity * x96 / current_sqrt_p_x96 - (liquidity + delta_l) * x96 / next_sqrt_p_x96)
+            .max(0.0)
+    }
+}
+
+/// Get the amount in given some amount out, within the current tick range
+///
+/// - `current_sqrt_p_x96` The √P.96
+/// - `liquidity` The base + reinvestment liquidity
+/// - `fee` the pool fee, applied in 1/100_000ths as the pool
+///
+/// The amount in saturates at `u128::MAX` if `amount_out` exceeds the pool's virtual reserves
+///
+/// Returns the new √P.96, the reinvestment liquidity minted, and the amount of tokens to input
+pub fn get_amount_in(
+    amount_out: u128,
+    current_sqrt_p_x96: &U256,
+    liquidity: &U256,
+    fee: Fee,
+    zero_for_one: bool,
+) -> (U256, U256, u128) {
+    let (sqrt_p_x96, liquidity, fee_units) = (*current_sqrt_p_x96, *liquidity, fee.units());
+    let unfillable = (sqrt_p_x96, U256::zero(), u128::MAX);
+    if sqrt_p_x96.is_zero() || liquidity.is_zero() {
+        return unfillable;
+    }
+    let amount_out = U256::from(amount_out);
+    let Some(delta_l) =
+        incremental_liquidity_out(amount_out, sqrt_p_x96, liquidity, fee_units, zero_for_one)
+    else {
+        return unfillable;
+    };
+    if zero_for_one {
+        // √P' = (L - amount_out / √P) · √P / (L + ΔL)
+        let value_out = mul_div(amount_out, *X96, sqrt_p_x96, false);
+        if value_out >= liquidity {
+            return unfillable;
+        }
+        let next_sqrt_p_x96 = mul_div(
+            liquidity - value_out,
+            sqrt_p_x96,
+            liquidity.saturating_add(delta_l),
+            true,
+        );
+        if next_sqrt_p_x96.is_zero() {
+            return unfillable;
+        }
+        // amount_in = (L + ΔL) / √P' - L / √P
+        let amount_in = mul_div(
+            liquidity.saturating_add(delta_l),
+            *X96,
+            next_sqrt_p_x96,
+            true,
+        )
+        .saturating_sub(mul_div(liquidity, *X96, sqrt_p_x96, false));
+        (next_sqrt_p_x96, delta_l, saturating_u128(amount_in))
+    } else {
+        // √P' = (L + ΔL) · √P / (L - amount_out · √P)
+        let value_out = mul_div(amount_out, sqrt_p_x96, *X96, false);
+        if value_out >= liquidity {
+            return unfillable;
+        }
+        let next_sqrt_p_x96 = mul_div(
+            liquidity.saturating_add(delta_l),
+            sqrt_p_x96,
+            liquidity - value_out,
+            false,
+        );
+        // amount_in = ΔL·√P' + L·(√P' - √P)
+        let amount_in = mul_div(delta_l, next_sqrt_p_x96, *X96, true).saturating_add(mul_div(
+            liquidity,
+            next_sqrt_p_x96.saturating_sub(sqrt_p_x96),
+            *X96,
+            true,
+        ));
+        (next_sqrt_p_x96, delta_l, saturating_u128(amount_in))
+    }
+}
+
+/// Elastic pool state as returned by the viewer contract
+#[derive(Debug, PartialEq)]
+pub struct KyberElasticState {
+    pub sqrt_p_x96: U256,
+    /// liquidity of positions in range of the current price
+    pub base_liquidity: u128,
+    /// liquidity from reinvested fees, always in range
+    pub reinvest_liquidity: u128,
+}
+
+impl KyberElasticState {
+    /// The liquidity a swap prices with
+    pub fn liquidity(&self) -> U256 {
+        U256::from(self.base_liquidity) + U256::from(self.reinvest_liquidity)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::uniswap_v3;
+
+    /// √P.96 of a 1:1 pool
+    fn one() -> U256 {
+        *X96
+    }
+
+    #[test]
+    fn get_amount_out_reinvests_fee() {
+        let liquidity = U256::from(10_u128.pow(24));
+        let amount_in = 10_u128.pow(20);
+        for zero_for_one in [true, false] {
+            let (next_sqrt_p_x96, delta_l, amount_out) = get_amount_out(
+                amount_in,
+                &one(),
+                &liquidity,
+                Fee::from_units(40),
+                zero_for_one,
+            );
+            assert!(!delta_l.is_zero());
+            assert_eq!(next_sqrt_p_x96 < one(), zero_for_one);
+            // 0.04% fee, the reinvested fee moves the price less than a uniswap v3 swap with the same fee
+            let (_, uniswap_out) =
+                uniswap_v3::get_amount_out(amount_in, &one(), &liquidity, 400, zero_for_one);
+            assert!(amount_out < amount_in);
+            assert!(amount_out.abs_diff(uniswap_out) < amount_in / 10_000);
+
+            let amount_out_f = get_amount_out_f(
+                amount_in,
+                2_f64.powi(96),
+                10_f64.powi(24),
+                Fee::from_units(40),
+                zero_for_one,
+            );
+            assert!((amount_out_f - amount_out as f64).abs() / (amount_out as f64) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn get_amount_in_inverts_amount_out() {
+        let liquidity = U256::from(10_u128.pow(24));
+        let amount_in = 10_u128.pow(20);
+        for zero_for_one in [true, false] {
+            let (_, _, amount_out) = get_amount_out(
+                amount_in,
+                &one(),
+                &liquidity,
+                Fee::from_units(300),
+                zero_for_one,
+            );
+            let (_, delta_l, amount_in_required) = get_amount_in(
+                amount_out,
+                &one(),
+                &liquidity,
+                Fee::from_units(300),
+                zero_for_one,
+            );
+            assert!(!delta_l.is_zero());
+            assert!(amount_in_required.abs_diff(amount_in) < amount_in / 1_000_000);
+        }
+    }
+
+    #[test]
+    fn drained_pool() {
+        let liquidity = U256::from(10_u128.pow(18));
+        assert_eq!(
+            get_amount_in(
+                10_u128.pow(20),
+                &one(),
+                &liquidity,
+                Fee::from_units(40),
+                true
+            )
+            .2,
+            u128::MAX
+        );
+        assert_eq!(
+            get_amount_out(1_000, &one(), &U256::zero(), Fee::from_units(40), true).2,
+            0
+        );
+    }
+}
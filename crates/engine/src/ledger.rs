@@ -0,0 +1,480 @@
+//! Persistent trade ledger
+//!
+//! Submitted order txs and their reconciled receipts are appended to a JSON lines file,
+//! the records are replayed to compute per-trade and cumulative PnL.
+//! Shadow executed (dry-run) orders are recorded as simulated fills and summed apart from real trades
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path as FilePath,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ethers::types::{Address, Bytes, Log, TxHash, H256, U256};
+use hex_literal::hex;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::receipt::RevertReason;
+
+/// ERC-20 `Transfer(address,address,uint256)` event topic
+const TRANSFER_TOPIC: H256 = H256(hex!(
+    "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+));
+
+/// A ledger entry
+///
+/// Externally tagged, serde can't buffer the `u128` amounts of internally tagged enums
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerRecord {
+    /// An order tx was accepted by the network, replacements are recorded at the same nonce
+    Submitted {
+        /// Unix timestamp (s)
+        ts: u64,
+        nonce: u64,
+        tx_hash: TxHash,
+        /// Block number of the prices used for the order
+        block_number: u64,
+        /// Symbol of the start token
+        token: String,
+        /// The trade path
+        path: String,
+        /// The order tx calldata
+        payload: Bytes,
+        amount_in: u128,
+        /// Expected amount out, as priced by the engine
+        amount_out: u128,
+        min_amount_out: u128,
+        max_fee_per_gas: U256,
+    },
+    /// An order tx was included on chain
+    Reconciled {
+        /// Unix timestamp (s)
+        ts: u64,
+        nonce: u64,
+        tx_hash: TxHash,
+        /// Block number the tx was included in
+        block_number: u64,
+        /// False if the tx reverted
+        success: bool,
+        gas_used: u64,
+        /// Gas paid by the tx (wei)
+        gas_cost: u128,
+        /// Start token paid out to the executor's payee
+        profit: u128,
+        /// Why the tx reverted, if it could be analyzed
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        revert: Option<RevertReason>,
+    },
+    /// A hypothetical fill of a dry-run order, it was built but not sent
+    Simulated {
+        /// Unix timestamp (s)
+        ts: u64,
+        /// Block number of the prices used for the order
+        block_number: u64,
+        /// Symbol of the start token
+        token: String,
+        /// The trade path
+        path: String,
+        /// The order tx calldata
+        payload: Bytes,
+        amount_in: u128,
+        /// Amount out simulated on the price graph
+        amount_out: u128,
+        min_amount_out: u128,
+        /// Gas cost (wei) at the estimated gas, or the order tx gas limit if not estimated
+        gas_cost: u128,
+        /// False if the order tx would revert against the latest state
+        filled: bool,
+        /// Why the order tx would revert, if known
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        revert: Option<RevertReason>,
+    },
+}
+
+impl LedgerRecord {
+    /// The current unix timestamp (s)
+    pub fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+}
+
+/// Append-only ledger file of submitted orders and their receipts
+#[derive(Debug)]
+pub struct Ledger {
+    writer: BufWriter<File>,
+}
+
+impl Ledger {
+    /// Open the ledger at `path` for appending, creating it if missing
+    pub fn open<P: AsRef<FilePath>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+        })
+    }
+    /// Append `record` to the ledger
+    pub fn record(&mut self, record: &LedgerRecord) {
+        if let Err(err) = write_record(&mut self.writer, record).and_then(|_| self.writer.flush()) {
+            warn!("ledger: {:?}", err);
+        }
+    }
+    /// Read all records of the ledger at `path`, skipping malformed lines
+    pub fn load<P: AsRef<FilePath>>(path: P) -> io::Result<Vec<LedgerRecord>> {
+        read_records(BufReader::new(File::open(path)?))
+    }
+}
+
+/// Write `record` as a JSON line
+fn write_record<W: Write>(writer: &mut W, record: &LedgerRecord) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, record)?;
+    writer.write_all(b"\n")
+}
+
+/// Read JSON line records from `reader`
+fn read_records<R: BufRead>(reader: R) -> io::Result<Vec<LedgerRecord>> {
+    let mut records = Vec::new();
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(err) => warn!("ledger line {}: {:?}", idx + 1, err),
+        }
+    }
+    Ok(records)
+}
+
+/// Sum of `token` transferred from `from` to `to` by ERC-20 `Transfer` events in `logs`
+pub fn transferred(logs: &[Log], token: Address, from: Address, to: Address) -> u128 {
+    logs.iter()
+        .filter(|log| {
+            log.address == token
+                && log.topics.len() == 3
+                && log.topics[0] == TRANSFER_TOPIC
+                && log.topics[1] == H256::from(from)
+                && log.topics[2] == H256::from(to)
+        })
+        .map(|log| U256::from_big_endian(&log.data).low_u128())
+        .sum()
+}
+
+/// Realized PnL of an included order tx
+#[derive(Clone, Debug, PartialEq)]
+pub struct TradePnl {
+    pub nonce: u64,
+    pub tx_hash: TxHash,
+    /// Block number the tx was included in
+    pub block_number: u64,
+    /// Symbol of the start token
+    pub token: String,
+    pub amount_in: u128,
+    /// Profit expected by the engine
+    pub expected_profit: u128,
+    /// Profit paid out, 0 if the tx reverted
+    pub profit: u128,
+    /// Gas paid by the tx (wei)
+    pub gas_cost: u128,
+    pub success: bool,
+    /// Why the tx reverted, if it could be analyzed
+    pub revert: Option<RevertReason>,
+}
+
+/// Cumulative PnL of simulated fills
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimulatedPnl {
+    /// Orders which would fill
+    pub fills: usize,
+    /// Orders which would revert
+    pub reverted: usize,
+    /// Cumulative simulated profit by start token symbol
+    pub profit: BTreeMap<String, u128>,
+    /// Cumulative estimated gas cost of fills (wei)
+    pub gas_cost: u128,
+}
+
+/// Per-trade and cumulative PnL of a ledger
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pnl {
+    /// Included order txs, in ledger order
+    pub trades: Vec<TradePnl>,
+    /// Number of submitted orders not yet reconciled
+    pub pending: usize,
+    /// Cumulative profit by start token symbol
+    pub profit: BTreeMap<String, u128>,
+    /// Cumulative gas paid (wei)
+    pub gas_cost: u128,
+    /// Simulated fills of dry-run orders
+    pub simulated: SimulatedPnl,
+}
+
+impl Pnl {
+    /// Compute PnL from ledger `records`
+    pub fn from_records(records: &[LedgerRecord]) -> Self {
+        let mut submitted = HashMap::<TxHash, &LedgerRecord>::new();
+        let mut unreconciled = HashSet::<u64>::new();
+        let mut pnl = Self::default();
+        for record in records {
+            match record {
+                LedgerRecord::Submitted { nonce, tx_hash, .. } => {
+                    submitted.insert(*tx_hash, record);
+                    unreconciled.insert(*nonce);
+                }
+                LedgerRecord::Reconciled {
+                    nonce,
+                    tx_hash,
+                    block_number,
+                    success,
+                    gas_cost,
+                    profit,
+                    revert,
+                    ..
+                } => {
+                    unreconciled.remove(nonce);
+                    let (token, amount_in, amount_out) = match submitted.get(tx_hash) {
+                        Some(LedgerRecord::Submitted {
+                            token,
+                            amount_in,
+                            amount_out,
+                            ..
+                        }) => (token.clone(), *amount_in, *amount_out),
+                        _ => {
+                            warn!("ledger: unknown tx {:?}", tx_hash);
+                            continue;
+                        }
+                    };
+                    *pnl.profit.entry(token.clone()).or_default() += profit;
+                    pnl.gas_cost += gas_cost;
+                    pnl.trades.push(TradePnl {
+                        nonce: *nonce,
+                        tx_hash: *tx_hash,
+                        block_number: *block_number,
+                        token,
+                        amount_in,
+                        expected_profit: amount_out.saturating_sub(amount_in),
+                        profit: *profit,
+                        gas_cost: *gas_cost,
+                        success: *success,
+                        revert: revert.clone(),
+                    });
+                }
+                LedgerRecord::Simulated {
+                    token,
+                    amount_in,
+                    amount_out,
+                    gas_cost,
+                    filled,
+                    ..
+                } => {
+                    let simulated = &mut pnl.simulated;
+                    if !filled {
+                        simulated.reverted += 1;
+                        continue;
+                    }
+                    simulated.fills += 1;
+                    *simulated.profit.entry(token.clone()).or_default() +=
+                        amount_out.saturating_sub(*amount_in);
+                    simulated.gas_cost += gas_cost;
+                }
+            }
+        }
+        pnl.pending = unreconciled.len();
+        pnl
+    }
+    /// Number of included order txs which reverted
+    pub fn reverted(&self) -> usize {
+        self.trades.iter().filter(|trade| !trade.success).count()
+    }
+}
+
+impl fmt::Display for Pnl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for trade in self.trades.iter() {
+            write!(
+                f,
+                "#{} {:?} (block: {}) {} {}: profit {}/{} gas {}wei",
+                trade.nonce,
+                trade.tx_hash,
+                trade.block_number,
+                if trade.success { "✅" } else { "❌" },
+                trade.token,
+                trade.profit,
+                trade.expected_profit,
+                trade.gas_cost,
+            )?;
+            match trade.revert.as_ref() {
+                Some(revert) => writeln!(f, " ({})", revert.label())?,
+                None => writeln!(f)?,
+            }
+        }
+        writeln!(
+            f,
+            "trades: {} (reverted: {}, pending: {})",
+            self.trades.len(),
+            self.reverted(),
+            self.pending
+        )?;
+        for (token, profit) in self.profit.iter() {
+            writeln!(f, "profit 💵: {profit} {token}")?;
+        }
+        write!(f, "gas ⛽: {}wei", self.gas_cost)?;
+        let simulated = &self.simulated;
+        if simulated.fills + simulated.reverted > 0 {
+            write!(
+                f,
+                "\nsimulated fills 🧪: {} (reverted: {})",
+                simulated.fills, simulated.reverted
+            )?;
+            for (token, profit) in simulated.profit.iter() {
+                write!(f, "\nsimulated profit: {profit} {token}")?;
+            }
+            write!(f, "\nsimulated gas: {}wei", simulated.gas_cost)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::types::{Address, Bytes, Log, TxHash, H256, U256};
+
+    use super::{
+        read_records, transferred, write_record, LedgerRecord, Pnl, RevertReason, TRANSFER_TOPIC,
+    };
+
+    fn submitted(nonce: u64, tx_hash: TxHash, token: &str) -> LedgerRecord {
+        LedgerRecord::Submitted {
+            ts: 1,
+            nonce,
+            tx_hash,
+            block_number: 100,
+            token: token.to_string(),
+            path: "Trade: ".to_string(),
+            payload: Bytes::from_static(&[1, 2, 3]),
+            amount_in: 1_000,
+            amount_out: 1_010,
+            min_amount_out: 1_005,
+            max_fee_per_gas: U256::from(100_000_000),
+        }
+    }
+
+    fn reconciled(nonce: u64, tx_hash: TxHash, success: bool, profit: u128) -> LedgerRecord {
+        LedgerRecord::Reconciled {
+            ts: 2,
+            nonce,
+            tx_hash,
+            block_number: 101,
+            success,
+            gas_used: 500_000,
+            gas_cost: 50_000_000_000_000,
+            profit,
+            revert: (!success).then_some(RevertReason::Slippage(3)),
+        }
+    }
+
+    #[test]
+    fn ledger_json_lines() {
+        let records = vec![
+            submitted(1, TxHash::repeat_byte(1), "WETH"),
+            reconciled(1, TxHash::repeat_byte(1), true, 8),
+        ];
+        let mut buf = Vec::<u8>::new();
+        for record in records.iter() {
+            write_record(&mut buf, record).unwrap();
+        }
+        // malformed lines are skipped
+        buf.extend_from_slice(b"{\"submitted\":{}}\n\n");
+        assert_eq!(read_records(buf.as_slice()).unwrap(), records);
+    }
+
+    #[test]
+    fn pnl_from_records() {
+        let records = [
+            submitted(1, TxHash::repeat_byte(1), "WETH"),
+            // replaced with a bumped fee
+            submitted(1, TxHash::repeat_byte(2), "WETH"),
+            submitted(2, TxHash::repeat_byte(3), "USDC"),
+            submitted(3, TxHash::repeat_byte(4), "WETH"),
+            reconciled(1, TxHash::repeat_byte(2), true, 8),
+            reconciled(2, TxHash::repeat_byte(3), false, 0),
+        ];
+        let pnl = Pnl::from_records(&records);
+        assert_eq!(pnl.trades.len(), 2);
+        assert_eq!(pnl.pending, 1);
+        assert_eq!(pnl.reverted(), 1);
+        assert_eq!(pnl.trades[1].revert, Some(RevertReason::Slippage(3)));
+        assert_eq!(pnl.trades[0].expected_profit, 10);
+        assert_eq!(pnl.trades[0].profit, 8);
+        assert_eq!(pnl.profit["WETH"], 8);
+        assert_eq!(pnl.profit["USDC"], 0);
+        assert_eq!(pnl.gas_cost, 100_000_000_000_000);
+        assert_eq!(pnl.simulated, Default::default());
+    }
+
+    #[test]
+    fn pnl_simulated_fills() {
+        let simulated = |filled: bool| LedgerRecord::Simulated {
+            ts: 1,
+            block_number: 100,
+            token: "WETH".to_string(),
+            path: "Trade: ".to_string(),
+            payload: Bytes::from_static(&[1, 2, 3]),
+            amount_in: 1_000,
+            amount_out: 1_010,
+            min_amount_out: 1_005,
+            gas_cost: 7,
+            filled,
+            revert: (!filled).then_some(RevertReason::Slippage(3)),
+        };
+        let records = [
+            submitted(1, TxHash::repeat_byte(1), "WETH"),
+            simulated(true),
+            simulated(false),
+            simulated(true),
+        ];
+        let pnl = Pnl::from_records(&records);
+        // simulated fills are not trades
+        assert!(pnl.trades.is_empty());
+        assert_eq!(pnl.pending, 1);
+        assert_eq!(pnl.simulated.fills, 2);
+        assert_eq!(pnl.simulated.reverted, 1);
+        assert_eq!(pnl.simulated.profit["WETH"], 20);
+        assert_eq!(pnl.simulated.gas_cost, 14);
+
+        let mut buf = Vec::<u8>::new();
+        write_record(&mut buf, &records[2]).unwrap();
+        assert_eq!(read_records(buf.as_slice()).unwrap(), [simulated(false)]);
+    }
+
+    #[test]
+    fn transferred_to_payee() {
+        let (token, executor, payee) = (
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+            Address::repeat_byte(3),
+        );
+        let transfer = |token: Address, from: Address, to: Address, amount: u64| Log {
+            address: token,
+            topics: vec![TRANSFER_TOPIC, H256::from(from), H256::from(to)],
+            data: Bytes::from(H256::from_low_u64_be(amount).0.to_vec()),
+            ..Default::default()
+        };
+        let logs = [
+            // loan repayment
+            transfer(token, executor, Address::repeat_byte(4), 1_000),
+            transfer(token, executor, payee, 7),
+            // another token
+            transfer(Address::repeat_byte(5), executor, payee, 100),
+        ];
+        assert_eq!(transferred(&logs, token, executor, payee), 7);
+        assert_eq!(transferred(&logs, token, payee, executor), 0);
+    }
+}
@@ -1,21 +1,99 @@
 // enable unstable bench feature when `--features="bench"`
 #![cfg_attr(feature = "bench", feature(test))]
 #![allow(non_snake_case)]
+pub mod abi_check;
+pub mod arena;
+pub mod audit;
+pub mod backtest;
+pub mod balancer;
+pub mod camelot_v2;
+pub mod chain;
+pub mod config;
 pub mod constant;
+pub mod control;
+pub mod curve;
+pub mod discovery;
+mod encoder;
 mod engine;
+pub mod events;
+pub mod fallback;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+mod gas;
+mod http_server;
+pub mod inbox;
+pub mod inspect;
+pub mod kyber_elastic;
+pub mod ledger;
+#[cfg(all(test, feature = "live-tests"))]
+mod live_test;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 // mod logger;
+mod nonce;
+#[cfg(feature = "notify")]
+pub mod notify;
 mod order;
+mod order_queue;
+mod paraswap;
+mod payload;
+mod pending;
+mod presign;
 mod price;
 mod price_graph;
+mod profit;
+pub mod quote;
+mod raw_tx;
+pub mod receipt;
+pub mod registry;
+pub mod risk;
+mod router_decoder;
+pub mod selector_stats;
+pub mod shutdown;
+pub mod signer;
+pub mod solidly;
+pub mod status;
+mod submission;
+pub mod supervisor;
 mod trade_router;
 mod trade_simulator;
 pub mod types;
 pub mod uniswap_v2;
 pub mod uniswap_v3;
 mod util;
+pub mod warm_start;
 mod zero_ex;
 
+pub use audit::SimulationAuditor;
+pub use chain::{BlockMisalignment, BlockNumberCheck, ChainInconsistency, ChainWatcher};
+pub use config::Config;
+pub use control::Controls;
+pub use encoder::{EncodeError, EncoderFactory, FulcrumEncoder, OrderEncoder, OrderEncoders};
 pub use engine::{prices_at, Engine};
-pub use order::{FulcrumExecutor, OrderService};
-pub use price::PriceService;
-pub use price_graph::PriceGraph;
+pub use events::{EngineEvent, EventSink};
+pub use fallback::ReceiptFallback;
+pub use gas::GasModel;
+pub use inbox::{DelayedMessage, L1InboxWatcher};
+pub use ledger::{Ledger, Pnl};
+pub use nonce::{InflightOrder, InflightStatus, NonceManager, NonceState};
+#[cfg(feature = "notify")]
+pub use notify::{Alert, Alerts, Notifier};
+pub use order::{
+    FulcrumExecutor, Order, OrderError, OrderExecutor, OrderService, OrderStatus, OrderUpdate,
+    ShadowMode,
+};
+pub use price::{PriceService, PriceSource, PriceSyncMode, PriceUpdate};
+pub use price_graph::{CompositeTrade, PriceGraph, Quote, Trade};
+pub use quote::PriceView;
+pub use receipt::RevertReason;
+pub use registry::TokenRegistry;
+pub use risk::{CircuitBreaker, RiskLimits};
+pub use selector_stats::SelectorStats;
+pub use shutdown::{Shutdown, ShutdownSignal};
+#[cfg(feature = "secp256k1")]
+pub use signer::Secp256k1Signer;
+pub use signer::{AsyncSigner, TxSigner};
+pub use status::EngineStatus;
+pub use submission::{Endpoint, SubmissionConfig, SubmitStrategy};
+pub use supervisor::{Subsystem, TaskRestart};
+pub use warm_start::WarmStart;
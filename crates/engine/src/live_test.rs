@@ -0,0 +1,174 @@
+//! Differential tests of the edge math against onchain contracts
+//!
+//! Recorded swaps are simulated from pool state at their block and compared to the Uniswap QuoterV2 via `eth_call`
+//! at the same block, catching drift between our math and the contracts.
+//! Requires `FULCRUM_LIVE_RPC` (an archive node), `FULCRUM_LIVE_MAX_DEVIATION_BPS` sets the max. deviation allowed
+use ethers::{
+    abi::{encode, Token as AbiToken},
+    providers::{Http, Middleware, Provider},
+    types::{
+        transaction::eip2718::TypedTransaction, Address, BlockId, Bytes, TransactionRequest, U256,
+    },
+};
+use hex_literal::hex;
+use log::info;
+
+use crate::{
+    constant::arbitrum::{
+        ARB, UNISWAP_V3_FACTORY, UNISWAP_V3_INIT_CODE_HASH, UNISWAP_V3_QUOTER_V2, USDC, USDT, WBTC,
+        WETH,
+    },
+    price_graph::Edge,
+    types::Fee,
+    uniswap_v3::pool_address_for,
+};
+
+/// `quoteExactInputSingle((address,address,uint256,uint24,uint160))`
+const QUOTE_EXACT_INPUT_SINGLE: [u8; 4] = hex!("c6a5026a");
+/// UniswapV3Pool `slot0()`
+const SLOT0: [u8; 4] = hex!("3850c7bd");
+/// UniswapV3Pool `liquidity()`
+const LIQUIDITY: [u8; 4] = hex!("1a686502");
+/// Max. deviation of simulated from quoted amounts (bps), unless set by `FULCRUM_LIVE_MAX_DEVIATION_BPS`
+const DEFAULT_MAX_DEVIATION_BPS: u128 = 10;
+
+/// An exact input swap on a Uniswap V3 pool
+struct RecordedSwap {
+    block_number: u64,
+    token_in: [u8; 20],
+    token_out: [u8; 20],
+    fee: u32,
+    amount_in: u128,
+}
+
+const RECORDED_SWAPS: &[RecordedSwap] = &[
+    RecordedSwap {
+        block_number: 100_000_000,
+        token_in: WETH,
+        token_out: USDC,
+        fee: 500,
+        amount_in: 1_000_000_000_000_000_000,
+    },
+    RecordedSwap {
+        block_number: 100_000_000,
+        token_in: USDC,
+        token_out: WETH,
+        fee: 500,
+        amount_in: 2_000_000000,
+    },
+    RecordedSwap {
+        block_number: 100_000_000,
+        token_in: ARB,
+        token_out: WETH,
+        fee: 500,
+        amount_in: 1_000_000_000_000_000_000_000,
+    },
+    RecordedSwap {
+        block_number: 100_000_000,
+        token_in: WBTC,
+        token_out: WETH,
+        fee: 500,
+        amount_in: 10_000000,
+    },
+    RecordedSwap {
+        block_number: 100_000_000,
+        token_in: USDT,
+        token_out: USDC,
+        fee: 100,
+        amount_in: 10_000_000000,
+    },
+];
+
+/// `eth_call` `data` to `to` at `block_number`
+async fn call(provider: &Provider<Http>, to: [u8; 20], data: Vec<u8>, block_number: u64) -> Bytes {
+    let tx: TypedTransaction = TransactionRequest::new()
+        .to(Address::from(to))
+        .data(data)
+        .into();
+    provider
+        .call(&tx, Some(BlockId::from(block_number)))
+        .await
+        .expect("eth_call")
+}
+
+/// The first word of an ABI encoded result
+fn first_word(result: &Bytes) -> U256 {
+    U256::from_big_endian(&result[..32])
+}
+
+/// The amount out of `swap` simulated by an edge synced from the pool at the swap's block
+async fn simulated_amount_out(provider: &Provider<Http>, swap: &RecordedSwap) -> u128 {
+    let zero_for_one = swap.token_in < swap.token_out;
+    let (token_0, token_1) = if zero_for_one {
+        (swap.token_in, swap.token_out)
+    } else {
+        (swap.token_out, swap.token_in)
+    };
+    let pool = pool_address_for(
+        token_0.into(),
+        token_1.into(),
+        swap.fee,
+        UNISWAP_V3_FACTORY.into(),
+        &UNISWAP_V3_INIT_CODE_HASH,
+    );
+    let sqrt_p_x96 = first_word(&call(provider, pool.0, SLOT0.to_vec(), swap.block_number).await);
+    let liquidity =
+        first_word(&call(provider, pool.0, LIQUIDITY.to_vec(), swap.block_number).await);
+
+    Edge::new_v3(
+        sqrt_p_x96,
+        liquidity,
+        Fee::from_pips(swap.fee),
+        zero_for_one,
+    )
+    .calculate_amount_out(swap.amount_in)
+}
+
+/// The amount out of `swap` quoted by the Uniswap QuoterV2 at the swap's block
+async fn quoted_amount_out(provider: &Provider<Http>, swap: &RecordedSwap) -> u128 {
+    let params = encode(&[AbiToken::Tuple(vec![
+        AbiToken::Address(swap.token_in.into()),
+        AbiToken::Address(swap.token_out.into()),
+        AbiToken::Uint(swap.amount_in.into()),
+        AbiToken::Uint(swap.fee.into()),
+        AbiToken::Uint(U256::zero()),
+    ])]);
+    let data = [QUOTE_EXACT_INPUT_SINGLE.as_slice(), &params].concat();
+    let result = call(provider, UNISWAP_V3_QUOTER_V2, data, swap.block_number).await;
+
+    first_word(&result).as_u128()
+}
+
+#[tokio::test]
+async fn edge_amount_out_matches_quoter() {
+    let rpc = std::env::var("FULCRUM_LIVE_RPC").expect("FULCRUM_LIVE_RPC set to an archive node");
+    let max_deviation_bps = std::env::var("FULCRUM_LIVE_MAX_DEVIATION_BPS")
+        .map(|bps| bps.parse().expect("deviation in bps"))
+        .unwrap_or(DEFAULT_MAX_DEVIATION_BPS);
+    let provider = Provider::<Http>::try_from(rpc).expect("valid rpc url");
+
+    let mut failures = Vec::<String>::new();
+    for swap in RECORDED_SWAPS {
+        let simulated = simulated_amount_out(&provider, swap).await;
+        let quoted = quoted_amount_out(&provider, swap).await;
+        let deviation_bps = simulated.abs_diff(quoted) * 10_000 / quoted.max(1);
+        let summary = format!(
+            "#{} {} {:02x?} -> {:02x?}/{}: simulated: {simulated}, quoted: {quoted} ({deviation_bps}bps)",
+            swap.block_number,
+            swap.amount_in,
+            &swap.token_in[..4],
+            &swap.token_out[..4],
+            swap.fee,
+        );
+        info!("{summary}");
+        if deviation_bps > max_deviation_bps {
+            failures.push(summary);
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "deviation > {max_deviation_bps}bps:\n{}",
+        failures.join("\n")
+    );
+}
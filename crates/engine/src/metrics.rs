@@ -0,0 +1,426 @@
+//! Engine metrics with a Prometheus exporter
+//!
+//! Metrics are plain atomics recorded from the hot path, rendered on request by a minimal HTTP `/metrics` endpoint
+use std::{
+    fmt::Write as _,
+    io,
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use log::info;
+use tokio::net::TcpListener;
+
+use crate::http_server;
+
+/// Sequencer feed messages received
+pub static FEED_MESSAGES: Counter = Counter::new(
+    "fulcrum_feed_messages_total",
+    "Sequencer feed messages received",
+);
+/// Sequencer feed message decode latency
+pub static FEED_DECODE: Histogram = Histogram::new(
+    "fulcrum_feed_decode_seconds",
+    "Sequencer feed message decode latency",
+);
+/// Feed message receive to txs decoded latency
+pub static LATENCY_DECODED: Histogram = Histogram::new(
+    "fulcrum_latency_decoded_seconds",
+    "Feed message receive to txs decoded latency",
+);
+/// Feed message receive to txs simulated latency
+pub static LATENCY_SIMULATED: Histogram = Histogram::new(
+    "fulcrum_latency_simulated_seconds",
+    "Feed message receive to txs simulated latency",
+);
+/// Feed message receive to order tx submitted latency
+pub static LATENCY_SUBMITTED: Histogram = Histogram::new(
+    "fulcrum_latency_submitted_seconds",
+    "Feed message receive to order tx submitted latency",
+);
+/// Price sync latency
+pub static PRICE_SYNC: Histogram =
+    Histogram::new("fulcrum_price_sync_seconds", "Price graph sync latency");
+/// Failed price syncs
+pub static PRICE_SYNC_ERRORS: Counter = Counter::new(
+    "fulcrum_price_sync_errors_total",
+    "Failed price graph syncs",
+);
+/// Price syncs where a secondary price source's pool states differed from the first response
+pub static PRICE_SOURCE_DIVERGENCES: Counter = Counter::new(
+    "fulcrum_price_source_divergences_total",
+    "Price source responses differing from the first",
+);
+/// Simulated pool states compared to the chain's by the auditor
+pub static SIM_POOLS_COMPARED: Counter = Counter::new(
+    "fulcrum_sim_pools_compared_total",
+    "Simulated pool states compared to the chain's",
+);
+/// Simulated pool states diverging from the chain's beyond the audit threshold
+pub static SIM_POOLS_DIVERGED: Counter = Counter::new(
+    "fulcrum_sim_pools_diverged_total",
+    "Simulated pool states diverging from the chain's",
+);
+/// Swaps of monitored pools the simulation didn't touch
+pub static SIM_POOLS_MISSED: Counter = Counter::new(
+    "fulcrum_sim_pools_missed_total",
+    "Swaps of monitored pools missed by the simulation",
+);
+/// Feed trades applied to the price graph
+pub static TRADES_SIMULATED: Counter = Counter::new(
+    "fulcrum_trades_simulated_total",
+    "Feed trades applied to the price graph",
+);
+/// Feed trades which could not be simulated
+pub static TRADES_SKIPPED: Counter = Counter::new(
+    "fulcrum_trades_skipped_total",
+    "Feed trades which could not be simulated",
+);
+/// Profitable arbs found
+pub static ARBS_FOUND: Counter = Counter::new("fulcrum_arbs_found_total", "Profitable arbs found");
+/// Order txs submitted
+pub static TXS_SUBMITTED: Counter =
+    Counter::new("fulcrum_txs_submitted_total", "Order txs submitted");
+/// Order txs submitted pre-signed
+pub static TXS_PRESIGNED: Counter = Counter::new(
+    "fulcrum_txs_presigned_total",
+    "Order txs submitted pre-signed",
+);
+/// Stuck order txs replaced with a bumped fee
+pub static TXS_REPLACED: Counter =
+    Counter::new("fulcrum_txs_replaced_total", "Stuck order txs replaced");
+/// Order txs included successfully
+pub static TXS_SUCCEEDED: Counter = Counter::new(
+    "fulcrum_txs_succeeded_total",
+    "Order txs included successfully",
+);
+/// Order txs which failed submission, inclusion, or execution
+pub static TXS_FAILED: Counter = Counter::new(
+    "fulcrum_txs_failed_total",
+    "Order txs which failed submission, inclusion, or execution",
+);
+/// Reverted order txs by reason
+pub static TXS_REVERTED: CounterVec<6> = CounterVec::new(
+    "fulcrum_txs_reverted_total",
+    "Reverted order txs by reason",
+    "reason",
+    [
+        "slippage",
+        "loss",
+        "already_arbed",
+        "insufficient_liquidity",
+        "out_of_gas",
+        "unknown",
+    ],
+);
+/// Orders skipped as their tx reverts `eth_call` pre-flight, by reason
+pub static ORDERS_PREFLIGHT_REVERTED: CounterVec<6> = CounterVec::new(
+    "fulcrum_orders_preflight_reverted_total",
+    "Orders skipped as their tx reverts pre-flight, by reason",
+    "reason",
+    [
+        "slippage",
+        "loss",
+        "already_arbed",
+        "insufficient_liquidity",
+        "out_of_gas",
+        "unknown",
+    ],
+);
+/// Panicked tasks restarted, by subsystem
+pub static TASK_RESTARTS: CounterVec<3> = CounterVec::new(
+    "fulcrum_task_restarts_total",
+    "Panicked tasks restarted, by subsystem",
+    "subsystem",
+    ["feed", "price_sync", "order_loop"],
+);
+
+/// All counters, in render order
+static COUNTERS: [&Counter; 14] = [
+    &FEED_MESSAGES,
+    &PRICE_SYNC_ERRORS,
+    &PRICE_SOURCE_DIVERGENCES,
+    &SIM_POOLS_COMPARED,
+    &SIM_POOLS_DIVERGED,
+    &SIM_POOLS_MISSED,
+    &TRADES_SIMULATED,
+    &TRADES_SKIPPED,
+    &ARBS_FOUND,
+    &TXS_SUBMITTED,
+    &TXS_PRESIGNED,
+    &TXS_REPLACED,
+    &TXS_SUCCEEDED,
+    &TXS_FAILED,
+];
+/// All histograms, in render order
+static HISTOGRAMS: [&Histogram; 5] = [
+    &FEED_DECODE,
+    &LATENCY_DECODED,
+    &LATENCY_SIMULATED,
+    &LATENCY_SUBMITTED,
+    &PRICE_SYNC,
+];
+
+/// Histogram bucket upper bounds (ns)
+const BUCKETS_NS: [u64; 11] = [
+    10_000,
+    50_000,
+    100_000,
+    500_000,
+    1_000_000,
+    5_000_000,
+    10_000_000,
+    50_000_000,
+    100_000_000,
+    500_000_000,
+    1_000_000_000,
+];
+
+/// A monotonically increasing count
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            value: AtomicU64::new(0),
+        }
+    }
+    /// Increment the count by 1
+    pub fn inc(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Increment the count by `n`
+    pub fn inc_by(&self, n: u64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+    /// The current count
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+        let _ = writeln!(out, "{} {}", self.name, self.get());
+    }
+}
+
+/// Counts partitioned by the values of a label
+pub struct CounterVec<const N: usize> {
+    name: &'static str,
+    help: &'static str,
+    label: &'static str,
+    values: [&'static str; N],
+    counts: [AtomicU64; N],
+}
+
+impl<const N: usize> CounterVec<N> {
+    const fn new(
+        name: &'static str,
+        help: &'static str,
+        label: &'static str,
+        values: [&'static str; N],
+    ) -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        Self {
+            name,
+            help,
+            label,
+            values,
+            counts: [ZERO; N],
+        }
+    }
+    /// Increment the count of label `value` by 1, unknown values are ignored
+    pub fn inc(&self, value: &str) {
+        if let Some(idx) = self.values.iter().position(|v| *v == value) {
+            self.counts[idx].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    /// The current count of label `value`
+    pub fn get(&self, value: &str) -> u64 {
+        self.values
+            .iter()
+            .position(|v| *v == value)
+            .map_or(0, |idx| self.counts[idx].load(Ordering::Relaxed))
+    }
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+        for (value, count) in self.values.iter().zip(self.counts.iter()) {
+            let _ = writeln!(
+                out,
+                "{}{{{}=\"{value}\"}} {}",
+                self.name,
+                self.label,
+                count.load(Ordering::Relaxed)
+            );
+        }
+    }
+}
+
+/// A latency distribution over fixed buckets
+pub struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    /// Observations by bucket (non-cumulative), the last bucket is `+Inf`
+    buckets: [AtomicU64; BUCKETS_NS.len() + 1],
+    /// Sum of all observations (ns)
+    sum_ns: AtomicU64,
+}
+
+impl Histogram {
+    const fn new(name: &'static str, help: &'static str) -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        Self {
+            name,
+            help,
+            buckets: [ZERO; BUCKETS_NS.len() + 1],
+            sum_ns: ZERO,
+        }
+    }
+    /// Record an observation of `duration`
+    pub fn observe(&self, duration: Duration) {
+        let ns = duration.as_nanos() as u64;
+        let bucket = BUCKETS_NS
+            .iter()
+            .position(|bound| ns <= *bound)
+            .unwrap_or(BUCKETS_NS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_ns.fetch_add(ns, Ordering::Relaxed);
+    }
+    /// Record an observation of `duration` if it was measured
+    pub fn observe_some(&self, duration: Option<Duration>) {
+        if let Some(duration) = duration {
+            self.observe(duration);
+        }
+    }
+    /// The number of observations
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).sum()
+    }
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", self.name);
+        let mut cumulative = 0;
+        for (bound, bucket) in BUCKETS_NS.iter().zip(self.buckets.iter()) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {cumulative}",
+                self.name,
+                *bound as f64 / 1e9
+            );
+        }
+        cumulative += self.buckets[BUCKETS_NS.len()].load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {cumulative}", self.name);
+        let _ = writeln!(
+            out,
+            "{}_sum {}",
+            self.name,
+            self.sum_ns.load(Ordering::Relaxed) as f64 / 1e9
+        );
+        let _ = writeln!(out, "{}_count {cumulative}", self.name);
+    }
+}
+
+/// Render all metrics in the Prometheus text format
+pub fn render() -> String {
+    let mut out = String::with_capacity(4 * 1024);
+    for counter in COUNTERS {
+        counter.render(&mut out);
+    }
+    TXS_REVERTED.render(&mut out);
+    ORDERS_PREFLIGHT_REVERTED.render(&mut out);
+    TASK_RESTARTS.render(&mut out);
+    for histogram in HISTOGRAMS {
+        histogram.render(&mut out);
+    }
+    out
+}
+
+/// Serve metrics over HTTP at `addr`, `GET /metrics`
+pub async fn serve(addr: SocketAddr) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("metrics 📊: http://{addr}/metrics");
+    http_server::serve("metrics", listener, respond).await;
+    Ok(())
+}
+
+/// Answer the HTTP request line `request`
+fn respond(request: &str) -> http_server::Response {
+    if request.starts_with("GET /metrics") {
+        (200, "text/plain; version=0.0.4", render())
+    } else {
+        (404, "text/plain", String::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    use super::*;
+
+    #[test]
+    fn histogram_renders_cumulative_buckets() {
+        let histogram = Histogram::new("test_seconds", "test");
+        histogram.observe(Duration::from_micros(5));
+        histogram.observe(Duration::from_micros(80));
+        histogram.observe(Duration::from_secs(2));
+
+        let mut out = String::new();
+        histogram.render(&mut out);
+        assert_eq!(histogram.count(), 3);
+        assert!(out.contains("test_seconds_bucket{le=\"0.00001\"} 1\n"));
+        assert!(out.contains("test_seconds_bucket{le=\"0.0001\"} 2\n"));
+        assert!(out.contains("test_seconds_bucket{le=\"1\"} 2\n"));
+        assert!(out.contains("test_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("test_seconds_count 3\n"));
+    }
+
+    #[test]
+    fn counter_vec_renders_labels() {
+        let counter = CounterVec::new("test_total", "test", "reason", ["a", "b"]);
+        counter.inc("b");
+        counter.inc("b");
+        counter.inc("c");
+
+        let mut out = String::new();
+        counter.render(&mut out);
+        assert_eq!(counter.get("b"), 2);
+        assert!(out.contains("test_total{reason=\"a\"} 0\n"));
+        assert!(out.contains("test_total{reason=\"b\"} 2\n"));
+        assert!(!out.contains("reason=\"c\""));
+    }
+
+    #[tokio::test]
+    async fn serves_metrics() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(http_server::serve("metrics", listener, respond));
+        ARBS_FOUND.inc();
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("# TYPE fulcrum_arbs_found_total counter\n"));
+        assert!(response.contains("fulcrum_feed_decode_seconds_bucket{le=\"+Inf\"}"));
+    }
+}
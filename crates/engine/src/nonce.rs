@@ -0,0 +1,432 @@
+//! Order tx nonce manager
+//!
+//! Assigns nonces to order txs and tracks them until they are included, txs stuck in the mempool are replaced
+//! at the same nonce with a bumped fee
+//!
+//! The nonce state is saved on shutdown so a restart can tell of order txs which may still be pending
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use ethers::types::{TxHash, U256};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::order::{Order, OrderError};
+
+/// Max. orders in-flight at once
+const MAX_INFLIGHT: usize = 4;
+/// Duration after which an unconfirmed order tx is considered stuck
+const STUCK_AFTER: Duration = Duration::from_secs(2);
+/// Max. replacements of a stuck order tx before it is abandoned
+const MAX_REPLACEMENTS: u8 = 3;
+/// Fee bump of a replacement tx (percent), nodes require at least 10%
+const FEE_BUMP_PERCENT: u64 = 25;
+
+/// Status of an in-flight order tx
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InflightStatus {
+    /// The nonce is reserved, the tx is being built and sent
+    Reserved,
+    /// Order tx sent to the network, no response yet
+    Submitted,
+    /// Order tx accepted by the network with hash
+    Pending(TxHash),
+}
+
+/// An order tx awaiting inclusion
+#[derive(Clone, Debug)]
+pub struct InflightOrder {
+    pub nonce: U256,
+    pub status: InflightStatus,
+    /// The order being executed
+    pub order: Order,
+    /// Max. fee per gas of the latest tx
+    pub max_fee_per_gas: U256,
+    /// Time the latest tx was sent
+    pub sent_at: Instant,
+    /// Number of times the tx was replaced
+    pub replacements: u8,
+}
+
+/// Assigns order tx nonces and tracks in-flight orders until inclusion
+#[derive(Debug, Default)]
+pub struct NonceManager {
+    /// The next nonce to assign
+    next_nonce: U256,
+    /// In-flight orders by nonce
+    inflight: BTreeMap<U256, InflightOrder>,
+}
+
+impl NonceManager {
+    /// Create a new nonce manager assigning nonces from `nonce` i.e. the account's tx count
+    pub fn new(nonce: U256) -> Self {
+        Self {
+            next_nonce: nonce,
+            inflight: Default::default(),
+        }
+    }
+    /// The next nonce to assign
+    pub fn next_nonce(&self) -> U256 {
+        self.next_nonce
+    }
+    /// In-flight orders, by ascending nonce
+    pub fn inflight(&self) -> impl Iterator<Item = &InflightOrder> {
+        self.inflight.values()
+    }
+    /// The in-flight order at `nonce`, if any
+    pub fn get(&self, nonce: U256) -> Option<&InflightOrder> {
+        self.inflight.get(&nonce)
+    }
+    /// Returns true if no orders are in-flight
+    pub fn is_empty(&self) -> bool {
+        self.inflight.is_empty()
+    }
+    /// Reserve the next nonce for `order` paying `max_fee_per_gas`
+    ///
+    /// Returns `OrderError::Busy` if too many orders are in-flight or `OrderError::Conflict` if `order` intersects
+    /// an in-flight order i.e. trades any of the same tokens, its prices are stale until that order is included
+    pub fn reserve(&mut self, order: &Order, max_fee_per_gas: U256) -> Result<U256, OrderError> {
+        if self.inflight.len() >= MAX_INFLIGHT {
+            return Err(OrderError::Busy);
+        }
+        if self
+            .inflight
+            .values()
+            .any(|inflight| inflight.order.trade.intersects(order.trade))
+        {
+            return Err(OrderError::Conflict);
+        }
+        let nonce = self.next_nonce;
+        self.inflight.insert(
+            nonce,
+            InflightOrder {
+                nonce,
+                status: InflightStatus::Reserved,
+                order: order.clone(),
+                max_fee_per_gas,
+                sent_at: Instant::now(),
+                replacements: 0,
+            },
+        );
+        self.next_nonce += U256::one();
+        Ok(nonce)
+    }
+    /// Release the reserved `nonce`, its tx was never sent
+    pub fn release(&mut self, nonce: U256) {
+        if self.inflight.remove(&nonce).is_none() {
+            return;
+        }
+        if nonce + U256::one() == self.next_nonce {
+            self.next_nonce = nonce;
+        } else {
+            // a later nonce is in use, the gap must be filled
+            warn!("nonce gap #{}", nonce.as_u64());
+        }
+    }
+    /// Mark the order tx at `nonce` as sent at `sent_at`
+    pub fn submitted(&mut self, nonce: U256, sent_at: Instant) {
+        if let Some(inflight) = self.inflight.get_mut(&nonce) {
+            inflight.status = InflightStatus::Submitted;
+            inflight.sent_at = sent_at;
+        }
+    }
+    /// Mark the order tx at `nonce` as accepted by the network with `tx_hash`
+    pub fn pending(&mut self, nonce: U256, tx_hash: TxHash) {
+        if let Some(inflight) = self.inflight.get_mut(&nonce) {
+            inflight.status = InflightStatus::Pending(tx_hash);
+        }
+    }
+    /// Remove orders included on chain i.e. with nonces below the account's `tx_count`
+    ///
+    /// Returns the included orders
+    pub fn confirm(&mut self, tx_count: U256) -> Vec<InflightOrder> {
+        let pending = self.inflight.split_off(&tx_count);
+        let confirmed = std::mem::replace(&mut self.inflight, pending);
+        if self.next_nonce < tx_count {
+            // txs were sent from this account by another client
+            self.next_nonce = tx_count;
+        }
+        confirmed.into_values().collect()
+    }
+    /// Nonces of order txs sent longer than `STUCK_AFTER` before `now` and not yet included
+    pub fn stuck(&self, now: Instant) -> Vec<U256> {
+        self.inflight
+            .values()
+            .filter(|inflight| {
+                inflight.status != InflightStatus::Reserved
+                    && now.duration_since(inflight.sent_at) >= STUCK_AFTER
+            })
+            .map(|inflight| inflight.nonce)
+            .collect()
+    }
+    /// Prepare a replacement for the stuck order tx at `nonce`, bumping its fee
+    ///
+    /// Returns the order, bumped max. fee per gas, and the status prior to replacement or `None` if replacements
+    /// are exhausted, the order is then abandoned and nonces from it are re-assigned
+    pub fn replace(&mut self, nonce: U256) -> Option<(Order, U256, InflightStatus)> {
+        let inflight = self.inflight.get_mut(&nonce)?;
+        if inflight.replacements >= MAX_REPLACEMENTS {
+            warn!("abandon order #{}", nonce.as_u64());
+            // later txs can't be included until the nonce is used
+            let _ = self.inflight.split_off(&nonce);
+            self.next_nonce = nonce;
+            return None;
+        }
+        inflight.replacements += 1;
+        let status = std::mem::replace(&mut inflight.status, InflightStatus::Reserved);
+        inflight.max_fee_per_gas = bump_fee(inflight.max_fee_per_gas);
+        debug!(
+            "replace order #{}: {:?}",
+            nonce.as_u64(),
+            inflight.max_fee_per_gas
+        );
+        Some((inflight.order.clone(), inflight.max_fee_per_gas, status))
+    }
+    /// The replacement of the order tx at `nonce` failed, restore its prior `status` if the replacement was never sent
+    ///
+    /// The order tx is then retried by `stuck` and counts toward `MAX_REPLACEMENTS`
+    pub fn replace_failed(&mut self, nonce: U256, status: InflightStatus) {
+        if let Some(inflight) = self.inflight.get_mut(&nonce) {
+            if inflight.status == InflightStatus::Reserved {
+                inflight.status = status;
+            }
+        }
+    }
+}
+
+/// Nonce state saved on shutdown
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct NonceState {
+    /// The next nonce to assign
+    pub next_nonce: U256,
+    /// (nonce, tx hash) of in-flight orders, the hash is unknown if no tx was accepted
+    pub inflight: Vec<(U256, Option<TxHash>)>,
+}
+
+impl NonceState {
+    /// The state of `nonces`
+    pub fn of(nonces: &NonceManager) -> Self {
+        Self {
+            next_nonce: nonces.next_nonce,
+            inflight: nonces
+                .inflight()
+                .map(|inflight| match inflight.status {
+                    InflightStatus::Pending(tx_hash) => (inflight.nonce, Some(tx_hash)),
+                    _ => (inflight.nonce, None),
+                })
+                .collect(),
+        }
+    }
+    /// Save the state to `path`, replacing any previously saved state
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        // write aside and rename so a crash mid-write leaves the previous state intact
+        let tmp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        fs::rename(tmp_path, path)?;
+        info!(
+            "saved nonce state #{} ({} in-flight) to {:?}",
+            self.next_nonce.as_u64(),
+            self.inflight.len(),
+            path
+        );
+        Ok(())
+    }
+    /// Load the state saved at `path`, `None` if there is none or it is unreadable
+    pub fn load(path: &Path) -> Option<Self> {
+        File::open(path)
+            .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?))
+            .map_err(|err: io::Error| match err.kind() {
+                io::ErrorKind::NotFound => info!("no saved nonce state at {:?}", path),
+                _ => warn!("nonce state {:?}: {:?}", path, err),
+            })
+            .ok()
+    }
+}
+
+/// Bump `max_fee_per_gas` for a replacement tx
+fn bump_fee(max_fee_per_gas: U256) -> U256 {
+    // round up so small fees are still bumped
+    max_fee_per_gas + (max_fee_per_gas * FEE_BUMP_PERCENT + 99) / 100
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use ethers::types::{TxHash, U256};
+
+    use super::{
+        InflightStatus, NonceManager, NonceState, MAX_INFLIGHT, MAX_REPLACEMENTS, STUCK_AFTER,
+    };
+    use crate::{
+        order::{Order, OrderError},
+        price_graph::{CompositeTrade, Trade},
+        types::Fee,
+    };
+
+    fn order(amount_in: u128) -> Order {
+        Order {
+            amount_in,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reserve_and_release() {
+        let fee = U256::from(100);
+        let mut nonces = NonceManager::new(U256::from(7));
+        for i in 0..MAX_INFLIGHT {
+            assert_eq!(nonces.reserve(&order(1), fee), Ok(U256::from(7 + i)));
+        }
+        assert_eq!(nonces.reserve(&order(1), fee), Err(OrderError::Busy));
+
+        // the latest nonce is re-assigned
+        nonces.release(U256::from(10));
+        assert_eq!(nonces.next_nonce(), U256::from(10));
+        // an earlier nonce leaves a gap
+        nonces.release(U256::from(8));
+        assert_eq!(nonces.next_nonce(), U256::from(10));
+        assert_eq!(
+            nonces.inflight().map(|o| o.nonce).collect::<Vec<_>>(),
+            [U256::from(7), U256::from(9)]
+        );
+    }
+
+    #[test]
+    fn reserve_independent_orders() {
+        let fee = U256::from(100);
+        let cycle = |a: u8, b: u8| Order {
+            trade: CompositeTrade::new(&[
+                Trade::new(a, b, Fee::from_pips(500), 0),
+                Trade::new(b, a, Fee::ZERO, 1),
+            ]),
+            ..Default::default()
+        };
+        let mut nonces = NonceManager::new(U256::zero());
+        assert_eq!(nonces.reserve(&cycle(0, 1), fee), Ok(U256::zero()));
+        assert_eq!(nonces.reserve(&cycle(3, 4), fee), Ok(U256::one()));
+        assert_eq!(nonces.reserve(&cycle(1, 3), fee), Err(OrderError::Conflict));
+        // conflicts clear once the order is included
+        nonces.confirm(U256::one());
+        assert_eq!(nonces.reserve(&cycle(0, 1), fee), Ok(U256::from(2)));
+    }
+
+    #[test]
+    fn confirm_included_orders() {
+        let fee = U256::from(100);
+        let mut nonces = NonceManager::new(U256::from(1));
+        for amount_in in 1..=3 {
+            let nonce = nonces.reserve(&order(amount_in), fee).unwrap();
+            nonces.submitted(nonce, Instant::now());
+        }
+        nonces.pending(U256::from(2), TxHash::repeat_byte(2));
+        assert_eq!(
+            nonces.get(U256::from(2)).unwrap().status,
+            InflightStatus::Pending(TxHash::repeat_byte(2))
+        );
+
+        let confirmed = nonces.confirm(U256::from(3));
+        assert_eq!(
+            confirmed
+                .iter()
+                .map(|o| o.order.amount_in)
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert_eq!(nonces.inflight().count(), 1);
+        // another client used the account
+        assert!(nonces.confirm(U256::from(10)).len() == 1);
+        assert!(nonces.is_empty());
+        assert_eq!(nonces.next_nonce(), U256::from(10));
+    }
+
+    #[test]
+    fn replace_stuck_orders() {
+        let mut nonces = NonceManager::new(U256::zero());
+        let t0 = Instant::now();
+        let first = nonces.reserve(&order(1), U256::from(100)).unwrap();
+        let second = nonces.reserve(&order(2), U256::from(100)).unwrap();
+        nonces.submitted(first, t0);
+        // the second is not yet sent
+        assert_eq!(nonces.stuck(t0 + STUCK_AFTER), [first]);
+        nonces.submitted(second, t0 + Duration::from_secs(1));
+        assert!(nonces.stuck(t0 + Duration::from_millis(500)).is_empty());
+        assert_eq!(nonces.stuck(t0 + STUCK_AFTER), [first]);
+
+        let mut fee = U256::from(100);
+        for _ in 0..MAX_REPLACEMENTS {
+            let (replacement, bumped_fee, _) = nonces.replace(first).unwrap();
+            assert_eq!(replacement.amount_in, 1);
+            assert!(bumped_fee * 100 >= fee * 110);
+            fee = bumped_fee;
+            nonces.submitted(first, t0);
+        }
+        // 100 > 125 > 157 > 197 rounding up
+        assert_eq!(fee, U256::from(197));
+        // abandoned, its nonce and later ones are re-assigned
+        assert_eq!(nonces.replace(first), None);
+        assert!(nonces.is_empty());
+        assert_eq!(nonces.reserve(&order(3), fee), Ok(first));
+    }
+
+    #[test]
+    fn failed_replacement_is_retried() {
+        let mut nonces = NonceManager::new(U256::zero());
+        let t0 = Instant::now();
+        let nonce = nonces.reserve(&order(1), U256::from(100)).unwrap();
+        nonces.submitted(nonce, t0);
+        nonces.pending(nonce, TxHash::repeat_byte(1));
+
+        // the replacement fails before it is sent e.g. preflight reverts
+        let (_, _, status) = nonces.replace(nonce).unwrap();
+        assert_eq!(status, InflightStatus::Pending(TxHash::repeat_byte(1)));
+        assert!(nonces.stuck(t0 + STUCK_AFTER).is_empty());
+        nonces.replace_failed(nonce, status);
+        assert_eq!(nonces.get(nonce).unwrap().status, status);
+        assert_eq!(nonces.stuck(t0 + STUCK_AFTER), [nonce]);
+
+        // the replacement fails after it is sent, the sent tx is retried in turn
+        let (_, _, status) = nonces.replace(nonce).unwrap();
+        nonces.submitted(nonce, t0 + STUCK_AFTER);
+        nonces.replace_failed(nonce, status);
+        assert_eq!(nonces.get(nonce).unwrap().status, InflightStatus::Submitted);
+        assert_eq!(nonces.stuck(t0 + STUCK_AFTER * 2), [nonce]);
+
+        // failed replacements count toward the limit
+        let _ = nonces.replace(nonce).unwrap();
+        assert_eq!(nonces.replace(nonce), None);
+        assert!(nonces.is_empty());
+    }
+
+    #[test]
+    fn save_and_load_nonce_state() {
+        let mut nonces = NonceManager::new(U256::from(5));
+        let first = nonces.reserve(&order(1), U256::from(100)).unwrap();
+        let _second = nonces.reserve(&order(2), U256::from(100)).unwrap();
+        nonces.pending(first, TxHash::repeat_byte(5));
+        let state = NonceState::of(&nonces);
+        assert_eq!(
+            state,
+            NonceState {
+                next_nonce: U256::from(7),
+                inflight: vec![
+                    (U256::from(5), Some(TxHash::repeat_byte(5))),
+                    (U256::from(6), None)
+                ],
+            }
+        );
+
+        let path = std::env::temp_dir().join("fulcrum-nonce-state-save-and-load.json");
+        state.save(&path).unwrap();
+        assert_eq!(NonceState::load(&path), Some(state));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(NonceState::load(&path), None);
+    }
+}
@@ -0,0 +1,370 @@
+//! Alert notifications
+//!
+//! Key events are posted as chat messages to Discord or Slack webhooks and/or a Telegram bot, as configured by the
+//! `[notify]` config section: executed order txs with their PnL, circuit breaker trips, and feed outages longer than
+//! `feed_down_secs`. Alerts are queued and posted in the background, off the hot path
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use ethers::types::TxHash;
+use log::{debug, info, warn};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use fulcrum_ws_cli::HttpClient;
+
+use crate::{
+    receipt::RevertReason,
+    types::{Position, Token},
+};
+
+/// Default seconds without feed messages before the feed is reported down
+pub const DEFAULT_FEED_DOWN_SECS: u64 = 30;
+/// Max. alerts queued for posting, later ones are dropped until the notifier catches up
+const ALERT_QUEUE_SIZE: usize = 32;
+/// Interval to check the feed's liveness
+const FEED_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Duration to keep alive alert connections
+const HTTP_KEEP_ALIVE: Duration = Duration::from_secs(60);
+
+/// Alert destinations, the `[notify]` config section
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct NotifyConfig {
+    /// Discord webhook URL
+    pub discord: Option<String>,
+    /// Slack incoming webhook URL
+    pub slack: Option<String>,
+    pub telegram: Option<TelegramConfig>,
+    /// Seconds without feed messages before the feed is reported down
+    #[serde(default = "default_feed_down_secs")]
+    pub feed_down_secs: u64,
+}
+
+fn default_feed_down_secs() -> u64 {
+    DEFAULT_FEED_DOWN_SECS
+}
+
+/// A Telegram bot posting to a chat
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct TelegramConfig {
+    pub bot_token: String,
+    /// Chat Id or `@channel` name
+    pub chat_id: String,
+}
+
+/// An event worth a notification
+#[derive(Clone, Debug, PartialEq)]
+pub enum Alert {
+    /// An order tx was included on chain
+    TradeExecuted {
+        nonce: u64,
+        tx_hash: TxHash,
+        /// Block number the tx was included in
+        block_number: u64,
+        /// The start token
+        token: Token,
+        /// Start token paid out to the executor's payee
+        profit: u128,
+        /// Gas paid by the tx (wei)
+        gas_cost: u128,
+        /// False if the tx reverted
+        success: bool,
+        /// Why the tx reverted, if it could be analyzed
+        revert: Option<RevertReason>,
+    },
+    /// The circuit breaker paused order submission
+    CircuitBreakerTripped {
+        consecutive_reverts: u32,
+        /// Net loss (wei) of order txs since resuming
+        loss: u128,
+    },
+    /// The feed delivered no messages for `down`
+    FeedDown { down: Duration },
+    /// The feed delivers messages again, after `down` without
+    FeedRecovered { down: Duration },
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TradeExecuted {
+                nonce,
+                tx_hash,
+                block_number,
+                token,
+                profit,
+                gas_cost,
+                success: true,
+                ..
+            } => write!(
+                f,
+                "✅ trade #{nonce} (block: {block_number}): profit {}, gas {gas_cost}wei\n{tx_hash:?}",
+                Position::new(*profit, *token),
+            ),
+            Self::TradeExecuted {
+                nonce,
+                tx_hash,
+                block_number,
+                token,
+                gas_cost,
+                revert,
+                ..
+            } => write!(
+                f,
+                "❌ trade #{nonce} (block: {block_number}) {token:?} reverted ({}), gas {gas_cost}wei\n{tx_hash:?}",
+                revert.as_ref().map_or("unknown", |revert| revert.label()),
+            ),
+            Self::CircuitBreakerTripped {
+                consecutive_reverts,
+                loss,
+            } => write!(
+                f,
+                "🛑 circuit breaker tripped: {consecutive_reverts} reverts in a row, order txs lost {loss}wei after gas, order submission paused"
+            ),
+            Self::FeedDown { down } => write!(f, "📡 feed down: no messages for {}s", down.as_secs()),
+            Self::FeedRecovered { down } => {
+                write!(f, "📡 feed recovered after {}s", down.as_secs())
+            }
+        }
+    }
+}
+
+/// A chat alerts are posted to
+#[derive(Clone, Debug, PartialEq)]
+enum Destination {
+    Discord(String),
+    Slack(String),
+    Telegram { bot_token: String, chat_id: String },
+}
+
+impl Destination {
+    /// Name of the destination for logs, URLs carry secrets
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Discord(_) => "discord",
+            Self::Slack(_) => "slack",
+            Self::Telegram { .. } => "telegram",
+        }
+    }
+    /// The request posting `text`, (URL, JSON body)
+    fn request(&self, text: &str) -> (String, String) {
+        match self {
+            Self::Discord(url) => (url.clone(), json!({ "content": text }).to_string()),
+            Self::Slack(url) => (url.clone(), json!({ "text": text }).to_string()),
+            Self::Telegram { bot_token, chat_id } => (
+                format!("https://api.telegram.org/bot{bot_token}/sendMessage"),
+                json!({ "chat_id": chat_id, "text": text }).to_string(),
+            ),
+        }
+    }
+}
+
+/// Tracks the feed's liveness, reporting each outage once when it starts and once it ends
+#[derive(Debug)]
+struct FeedWatch {
+    /// Time without feed messages before the feed is down (ms)
+    down_after_ms: u64,
+    /// Time of the last message before the current outage (ms), if down
+    down_since_ms: Option<u64>,
+}
+
+impl FeedWatch {
+    fn new(down_after: Duration) -> Self {
+        Self {
+            down_after_ms: down_after.as_millis() as u64,
+            down_since_ms: None,
+        }
+    }
+    /// Check the time of the latest feed message `last_message_ms` at `now_ms`, returning the alert if the feed
+    /// went down or recovered
+    fn check(&mut self, last_message_ms: u64, now_ms: u64) -> Option<Alert> {
+        match self.down_since_ms {
+            None if now_ms.saturating_sub(last_message_ms) >= self.down_after_ms => {
+                self.down_since_ms = Some(last_message_ms);
+                Some(Alert::FeedDown {
+                    down: Duration::from_millis(now_ms - last_message_ms),
+                })
+            }
+            Some(since) if last_message_ms > since => {
+                self.down_since_ms = None;
+                Some(Alert::FeedRecovered {
+                    down: Duration::from_millis(last_message_ms - since),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Posts alerts to the configured chats
+pub struct Notifier {
+    destinations: Vec<Destination>,
+    /// Time without feed messages before the feed is reported down
+    feed_down_after: Duration,
+    client: HttpClient,
+}
+
+impl Notifier {
+    /// Post alerts to the destinations of `config`
+    pub fn new(config: &NotifyConfig) -> Self {
+        let mut destinations = Vec::new();
+        if let Some(url) = config.discord.as_ref() {
+            destinations.push(Destination::Discord(url.clone()));
+        }
+        if let Some(url) = config.slack.as_ref() {
+            destinations.push(Destination::Slack(url.clone()));
+        }
+        if let Some(telegram) = config.telegram.as_ref() {
+            destinations.push(Destination::Telegram {
+                bot_token: telegram.bot_token.clone(),
+                chat_id: telegram.chat_id.clone(),
+            });
+        }
+        Self {
+            destinations,
+            feed_down_after: Duration::from_secs(config.feed_down_secs),
+            client: fulcrum_ws_cli::make_http_client(HTTP_KEEP_ALIVE),
+        }
+    }
+    /// Start posting alerts in the background, returning the handle to raise them
+    pub fn start(self) -> Alerts {
+        let (tx, mut rx) = mpsc::channel::<Alert>(ALERT_QUEUE_SIZE);
+        let alerts = Alerts {
+            tx,
+            started: Instant::now(),
+            feed_at_ms: Default::default(),
+        };
+        let feed_at_ms = Arc::clone(&alerts.feed_at_ms);
+        let started = alerts.started;
+        tokio::spawn(async move {
+            info!(
+                "alerts 🔔: {:?}",
+                self.destinations
+                    .iter()
+                    .map(Destination::label)
+                    .collect::<Vec<_>>()
+            );
+            let mut feed = FeedWatch::new(self.feed_down_after);
+            let mut feed_check = tokio::time::interval(FEED_CHECK_INTERVAL);
+            loop {
+                let alert = tokio::select! {
+                    alert = rx.recv() => match alert {
+                        Some(alert) => alert,
+                        None => break,
+                    },
+                    _ = feed_check.tick() => {
+                        let now_ms = started.elapsed().as_millis() as u64;
+                        match feed.check(feed_at_ms.load(Ordering::Relaxed), now_ms) {
+                            Some(alert) => alert,
+                            None => continue,
+                        }
+                    }
+                };
+                self.post(&alert).await;
+            }
+        });
+
+        alerts
+    }
+    /// Post `alert` to every destination
+    async fn post(&self, alert: &Alert) {
+        let text = alert.to_string();
+        for destination in self.destinations.iter() {
+            let (url, body) = destination.request(&text);
+            match self.client.post_async(url.as_str(), body).await {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => warn!("alert {}: {}", destination.label(), response.status()),
+                Err(err) => warn!("alert {}: {:?}", destination.label(), err),
+            }
+        }
+    }
+}
+
+/// Raises alerts for the `Notifier`
+#[derive(Clone, Debug)]
+pub struct Alerts {
+    tx: mpsc::Sender<Alert>,
+    started: Instant,
+    /// Time of the latest feed message since `started` (ms)
+    feed_at_ms: Arc<AtomicU64>,
+}
+
+impl Alerts {
+    /// Queue `alert` for posting
+    pub fn send(&self, alert: Alert) {
+        if self.tx.try_send(alert).is_err() {
+            debug!("alert queue full, drop");
+        }
+    }
+    /// Record a feed message was received now
+    pub fn feed_alive(&self) {
+        self.feed_at_ms
+            .store(self.started.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn feed_watch_reports_outages_once() {
+        let mut feed = FeedWatch::new(Duration::from_secs(30));
+        assert_eq!(feed.check(0, 29_999), None);
+        assert_eq!(
+            feed.check(0, 30_000),
+            Some(Alert::FeedDown {
+                down: Duration::from_secs(30)
+            })
+        );
+        assert_eq!(feed.check(0, 60_000), None);
+        assert_eq!(
+            feed.check(75_000, 75_500),
+            Some(Alert::FeedRecovered {
+                down: Duration::from_secs(75)
+            })
+        );
+        assert_eq!(feed.check(76_000, 76_000), None);
+    }
+
+    #[test]
+    fn alert_requests() {
+        let alert = Alert::CircuitBreakerTripped {
+            consecutive_reverts: 3,
+            loss: 1_000,
+        };
+        let text = alert.to_string();
+        assert_eq!(
+            Destination::Discord("https://discord.com/api/webhooks/1/a".to_string()).request(&text),
+            (
+                "https://discord.com/api/webhooks/1/a".to_string(),
+                json!({ "content": text }).to_string()
+            )
+        );
+        assert_eq!(
+            Destination::Slack("https://hooks.slack.com/services/a".to_string())
+                .request(&text)
+                .1,
+            json!({ "text": text }).to_string()
+        );
+        let (url, body) = Destination::Telegram {
+            bot_token: "123:abc".to_string(),
+            chat_id: "@fulcrum".to_string(),
+        }
+        .request(&text);
+        assert_eq!(url, "https://api.telegram.org/bot123:abc/sendMessage");
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["chat_id"], "@fulcrum");
+        assert!(body["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("🛑 circuit breaker tripped: 3 reverts"));
+    }
+}
@@ -1,41 +1,71 @@
 //! Order execution service
 use std::{
-    sync::Arc,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
     time::{Duration, Instant},
 };
 
+use async_trait::async_trait;
 use ethers::{
-    contract::FunctionCall,
     prelude::abigen,
-    types::{BlockNumber, Bytes, Chain, TxHash, U256},
+    types::{
+        transaction::eip2718::TypedTransaction, Address, BlockNumber, Bytes, Chain,
+        Eip1559TransactionRequest, Signature, TxHash, U256,
+    },
 };
-use ethers_providers::{Middleware, PendingTransaction};
-use ethers_signers::{LocalWallet, Signer};
-use futures::{
-    future::{select_all, select_ok},
-    AsyncReadExt,
-};
-use log::{debug, error, info, trace};
-use thingbuf::mpsc::{channel, Sender};
-use tokio::select;
+use ethers_providers::{Middleware, MiddlewareError};
+use futures::AsyncReadExt;
+use log::{debug, error, info, warn};
+use thingbuf::mpsc::{channel, Receiver, Sender};
+use tokio::{select, time::timeout};
 
-use crate::price_graph::CompositeTrade;
-use fulcrum_ws_cli::{serialize_hex, HttpClient, Response, SendRawTxResponse};
+#[cfg(feature = "metrics")]
+use crate::metrics;
+#[cfg(feature = "notify")]
+use crate::notify::{Alert, Alerts};
+use crate::{
+    control::Controls,
+    encoder::{EncodeError, FulcrumEncoder, OrderEncoder},
+    gas::GasModel,
+    ledger::{self, Ledger, LedgerRecord},
+    nonce::{InflightOrder, InflightStatus, NonceManager, NonceState},
+    order_queue::{OrderQueue, DEFAULT_MAX_AGE},
+    presign::PresignCache,
+    price_graph::CompositeTrade,
+    raw_tx::RawTxEncoder,
+    receipt::{analyze_revert, revert_data, RevertReason},
+    risk::{self, CircuitBreaker, RiskGuard, RiskLimits},
+    shutdown::{shutdown_of, ShutdownSignal},
+    signer::TxSigner,
+    submission::{Endpoint, SubmissionConfig, Submitter},
+    supervisor::{supervise, Subsystem},
+    types::Token,
+};
+use fulcrum_sequencer_feed::Timestamps;
+use fulcrum_ws_cli::{Response, SendRawTxResponse};
 
-/// Official sequencer rpc endpoint
-const ARB_SEQUENCER_HTTPS: &str = "https://arb1-sequencer.arbitrum.io/rpc";
-/// Arbitrum public rpc endpoint
-const ARB_FULL_HTTPS: &str = "https://arb1.arbitrum.io/rpc";
 /// Duration to keep alive tx submission connections
 const HTTP_KEEP_ALIVE_S: Duration = Duration::from_secs(10);
 /// Base fee per gas to use by default for order txs
 const DEFAULT_BASE_FEE_PER_GAS: u64 = 200_000_000_u64;
+/// Interval to poll in-flight order txs for inclusion
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Interval to calibrate the gas model with `eth_estimateGas` of the latest order
+const GAS_CALIBRATION_INTERVAL: Duration = Duration::from_secs(60);
+/// Default tolerated shortfall of an order's output vs. its simulated output (basis points)
+const DEFAULT_SLIPPAGE_BPS: u32 = 10;
+/// Max. order updates buffered for the engine, updates are dropped when full
+const ORDER_UPDATE_QUEUE_SIZE: usize = 32;
+/// Max. duration to await the inclusion of in-flight orders on shutdown
+const SHUTDOWN_DRAIN: Duration = Duration::from_secs(5);
 
 abigen!(
     FulcrumExecutor,
     r#"[
         function swap(uint128 amountIn, uint128 payload) external
-        function flashSwap(uint128 amountIn, uint128 payload) external
+        function flashSwap(uint128 amountIn, uint128 payload, uint128 minAmountOut) external
+        function flashSwapPath(uint128 amountIn, uint256 payload, uint128 minAmountOut) external
+        function payee() external view returns (address)
     ]"#,
 );
 
@@ -49,17 +79,86 @@ pub enum OrderError {
     TxSubmitResponse,
     /// Error while waiting for tx to be included in the chain
     TxInclusion,
-    /// Another tx is pending
+    /// Too many order txs are in-flight
     Busy,
+    /// The order trades tokens of an in-flight order
+    Conflict,
+    /// The order exceeds a risk limit
+    RiskLimit,
+    /// The order tx reverts `eth_call` pre-flight
+    Preflight(RevertReason),
+    /// The order can't be encoded for the executor e.g. a fee too large for its payload
+    Encode(EncodeError),
+}
+
+/// A trade order for execution
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Order {
+    /// Amount of the first token in the trade path
+    pub amount_in: u128,
+    /// Expected amount out of the trade, as priced by the engine
+    pub amount_out: u128,
+    /// The trade path
+    pub trade: CompositeTrade,
+    /// Block number of the prices used for the order
+    pub block_number: u64,
+    /// Value (wei) of `amount_in` at the order's prices, 0 if it can't be priced in WETH
+    pub value_in_wei: u128,
+    /// Pipeline timestamps of the feed message which triggered the order
+    pub timestamps: Timestamps,
+}
+
+/// Execution status of a submitted order
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum OrderStatus {
+    /// The order tx was accepted by the network
+    #[default]
+    Sent,
+    /// The order tx executed successfully in the block
+    Included(u64),
+    /// The order tx reverted or was dropped, it has no price impact
+    Failed,
+}
+
+/// How dry-run orders are shadow executed, recording simulated fills to the ledger
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ShadowMode {
+    /// Fill at the amount out simulated on the price graph
+    PriceGraph,
+    /// Fill as `PriceGraph` unless the order tx reverts `eth_estimateGas` against the latest state
+    EstimateGas,
 }
 
-/// Status of an order tx
-#[derive(Copy, Clone)]
-pub enum OrderTxStatus {
-    // Order submitted to the network
-    Submitted(Instant),
-    // Order submitted to the network and response received
-    Received(TxHash),
+/// Progress of a submitted order, reported to the engine
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OrderUpdate {
+    pub order: Order,
+    pub status: OrderStatus,
+}
+
+/// Provides execution of trade orders
+#[async_trait]
+pub trait OrderExecutor: Sized {
+    /// Start the order executor
+    /// `dry_run` - if true do not submit the built order txs
+    ///
+    /// Returns a handle for issuing orders
+    async fn start(self, dry_run: bool) -> Sender<Order>;
+    /// Max. fee per gas (wei) paid by order txs, used to net gas costs from arb profits
+    /// (default: 0 i.e. orders are gas free)
+    fn max_fee_per_gas(&self) -> u64 {
+        0
+    }
+    /// Subscribe to the progress of submitted orders, called before `start`
+    /// (default: `None` i.e. order progress is not reported)
+    fn updates(&mut self) -> Option<Receiver<OrderUpdate>> {
+        None
+    }
+    /// Order tx gas by path, used to net gas costs from arb profits
+    /// (default: the uncalibrated model)
+    fn gas_model(&self) -> Arc<GasModel> {
+        Default::default()
+    }
 }
 
 /// Provides trade order execution service
@@ -67,13 +166,52 @@ pub struct OrderService<M: Middleware + 'static> {
     /// Ethereum JSON-RPC client (ws)
     client: Arc<M>,
     /// Tx signer
-    wallet: LocalWallet,
+    wallet: Box<dyn TxSigner>,
     /// Contract entrypoint for executing orders
     contract: FulcrumExecutor<M>,
+    /// Encodes orders as `contract` calldata
+    order_encoder: Box<dyn OrderEncoder>,
     /// Latest known 'max fee per gas'
     max_fee_per_gas: U256,
-    /// Http conn to sequencer RPC
-    sequencer_client: HttpClient,
+    /// Tolerated shortfall of an order's output vs. its simulated output (basis points)
+    slippage_bps: u32,
+    /// Order tx nonces and in-flight orders
+    nonces: Arc<Mutex<NonceManager>>,
+    /// Sends order txs to the submission endpoints
+    submitter: Submitter,
+    /// Records submitted orders and their receipts
+    ledger: Option<Mutex<Ledger>>,
+    /// Recipient of the executor contract's profits, queried on start if PnL is reported
+    payee: Address,
+    /// Max. duration an order may wait for in-flight orders to resolve
+    order_max_age: Duration,
+    /// Reports the progress of submitted orders, if subscribed
+    updates: Option<Sender<OrderUpdate>>,
+    /// Order txs pre-signed between blocks, if enabled
+    presigned: Option<Mutex<PresignCache>>,
+    /// Reused order tx request buffers, taken while a request is in-flight
+    encoder: Mutex<Option<RawTxEncoder>>,
+    /// Pauses order submission when realized results go bad, if enabled
+    breaker: Option<Arc<Mutex<CircuitBreaker>>>,
+    /// Rejects orders exceeding size and rate limits, if enabled
+    risk: Option<Mutex<RiskGuard>>,
+    /// Records simulated fills of dry-run orders, if enabled
+    shadow: Option<ShadowMode>,
+    /// Time budget from receiving an order's feed message to verify its tx pre-flight, if enabled
+    preflight_budget: Option<Duration>,
+    /// Order tx gas by path, shared with the engine's profit model
+    gas: Arc<GasModel>,
+    /// Path and calldata of the latest order, estimated to calibrate the gas model
+    latest_order: Mutex<Option<(CompositeTrade, Bytes)>>,
+    /// Switches dry-run at runtime, if enabled
+    controls: Option<Arc<Controls>>,
+    /// Stops taking orders on shutdown, in-flight orders are drained
+    shutdown: Option<ShutdownSignal>,
+    /// Nonce state file saved on shutdown, if enabled
+    nonce_state: Option<PathBuf>,
+    /// Raises alerts for executed orders and circuit breaker trips, if enabled
+    #[cfg(feature = "notify")]
+    alerts: Option<Alerts>,
 }
 
 impl<M> OrderService<M>
@@ -88,12 +226,12 @@ where
     /// Instantiate a new `OrderService`
     /// - `contract` where to send order txs (i.e smart contract)
     /// - `order_fee` the uniswap v3 pool fee tier for flash loans
-    /// - `wallet` account to execute transactions, wrapped in ethers-signer implementation
+    /// - `wallet` account to execute transactions e.g. a `LocalWallet` or any ethers `Signer` wrapped in `AsyncSigner`
     pub async fn new(
         client: Arc<M>,
         chain: Chain,
         contract: FulcrumExecutor<M>,
-        wallet: LocalWallet,
+        wallet: impl TxSigner + 'static,
     ) -> OrderService<M> {
         assert_eq!(chain as u64, wallet.chain_id(), "incompatible chain IDs");
         assert_eq!(
@@ -103,57 +241,316 @@ where
         );
 
         Self {
-            sequencer_client: fulcrum_ws_cli::make_http_client(HTTP_KEEP_ALIVE_S),
+            submitter: Submitter::new(
+                SubmissionConfig::default(),
+                fulcrum_ws_cli::make_http_client(HTTP_KEEP_ALIVE_S),
+            ),
             client,
             contract,
-            wallet,
+            order_encoder: Box::<FulcrumEncoder>::default(),
+            wallet: Box::new(wallet),
             max_fee_per_gas: DEFAULT_BASE_FEE_PER_GAS.into(),
+            slippage_bps: DEFAULT_SLIPPAGE_BPS,
+            nonces: Default::default(),
+            ledger: None,
+            payee: Address::zero(),
+            order_max_age: DEFAULT_MAX_AGE,
+            updates: None,
+            presigned: None,
+            encoder: Mutex::new(Some(RawTxEncoder::default())),
+            breaker: None,
+            risk: None,
+            shadow: None,
+            preflight_budget: None,
+            gas: Default::default(),
+            latest_order: Mutex::new(None),
+            controls: None,
+            shutdown: None,
+            nonce_state: None,
+            #[cfg(feature = "notify")]
+            alerts: None,
         }
     }
-    /// Start the order service
-    /// `dry_run` - if true do not submit the built order txs
-    pub async fn start(self, dry_run: bool) -> Sender<(u128, CompositeTrade)> {
-        let mut nonce = self
-            .client
-            .get_transaction_count(self.wallet.address(), None)
-            .await
-            .expect("nonce fetched");
-        info!(
-            "config: order account: {:?}, nonce: {:?}",
-            self.wallet.address(),
-            nonce
-        );
-
-        let (tx, rx) = channel(5);
-        let mut warm_interval = tokio::time::interval(HTTP_KEEP_ALIVE_S - Duration::from_secs(5)); // ensure slightly less than timeout
-                                                                                                   // The ideal interval for base fee update (unused for now as simply over-estimating is fine i.e tx submitted, min fee charged)
-        tokio::spawn({
-            let mut inflight_guard = None;
-            async move {
-                loop {
-                    select! {
-                        biased;
-                        trade_request = rx.recv() => {
-                            if let Some((amount_in, ref trade)) = trade_request {
-                                match self.flash_swap(nonce, amount_in, trade, &mut inflight_guard, dry_run).await {
-                                    Err(OrderError::Busy) => info!("another tx is pending: #{:?}", nonce.as_u32()),
-                                    _ => nonce += U256::one(),
-                                }
-                            }
-                        }
-                        _ = warm_interval.tick() => self.warm_connections(),
+    /// Set the order tx submission endpoints and strategy (default: race the Arbitrum One sequencer and public RPC)
+    pub fn with_submission(mut self, config: SubmissionConfig) -> Self {
+        self.submitter =
+            Submitter::new(config, fulcrum_ws_cli::make_http_client(HTTP_KEEP_ALIVE_S));
+        self
+    }
+    /// The order tx submission endpoints and their latency stats
+    pub fn submission_endpoints(&self) -> Arc<[Endpoint]> {
+        self.submitter.endpoints()
+    }
+    /// Handle for querying in-flight orders, nonces are assigned once the service is started
+    pub fn nonce_manager(&self) -> Arc<Mutex<NonceManager>> {
+        Arc::clone(&self.nonces)
+    }
+    fn lock_nonces(&self) -> MutexGuard<'_, NonceManager> {
+        // the nonces stay valid if the order loop panicked holding the lock, it's restarted with them
+        self.nonces.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    /// Encode orders with `encoder` e.g. for an executor contract with another ABI (default: `FulcrumEncoder`)
+    pub fn with_order_encoder(mut self, encoder: Box<dyn OrderEncoder>) -> Self {
+        self.order_encoder = encoder;
+        self
+    }
+    /// Record submitted orders and their realized PnL to `ledger`
+    pub fn with_ledger(mut self, ledger: Ledger) -> Self {
+        self.ledger = Some(Mutex::new(ledger));
+        self
+    }
+    /// Alert executed orders with their PnL and circuit breaker trips to `alerts`
+    #[cfg(feature = "notify")]
+    pub fn with_alerts(mut self, alerts: Alerts) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+    /// True if realized PnL is reported, to the ledger or alerts
+    fn reports_pnl(&self) -> bool {
+        #[cfg(feature = "notify")]
+        if self.alerts.is_some() {
+            return true;
+        }
+        self.ledger.is_some()
+    }
+    /// Append the record built by `f` to the ledger, if any
+    fn record(&self, f: impl FnOnce() -> LedgerRecord) {
+        if let Some(ledger) = self.ledger.as_ref() {
+            ledger.lock().expect("ledger lock").record(&f());
+        }
+    }
+    /// Set the tolerated shortfall of an order's output vs. its simulated output (basis points)
+    /// the executor contract reverts orders returning less
+    pub fn with_slippage_bps(mut self, slippage_bps: u32) -> Self {
+        assert!(slippage_bps <= 10_000, "slippage exceeds 100%");
+        self.slippage_bps = slippage_bps;
+        self
+    }
+    /// Set the max. duration an order may wait for in-flight orders to resolve before it is dropped as stale (default: 500ms)
+    pub fn with_order_max_age(mut self, order_max_age: Duration) -> Self {
+        self.order_max_age = order_max_age;
+        self
+    }
+    /// Pause order submission (as in dry-run) when `breaker` trips, resumed by `SIGUSR1`
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = Some(Arc::new(Mutex::new(breaker)));
+        self
+    }
+    /// Take dry-run from `controls` rather than the engine, it can be switched at runtime
+    pub fn with_controls(mut self, controls: Arc<Controls>) -> Self {
+        self.controls = Some(controls);
+        self
+    }
+    /// Stop taking orders on `shutdown`, awaiting the inclusion of in-flight orders (up to 5s)
+    pub fn with_shutdown(mut self, shutdown: ShutdownSignal) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+    /// Save the nonce state to `path` on shutdown, on start warn of order txs it shows may still be pending
+    pub fn with_nonce_state<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.nonce_state = Some(path.into());
+        self
+    }
+    /// True if order submission is paused by the circuit breaker
+    fn is_paused(&self) -> bool {
+        self.breaker
+            .as_ref()
+            .is_some_and(|breaker| breaker.lock().expect("breaker lock").is_tripped())
+    }
+    /// Reject orders exceeding `limits`
+    pub fn with_risk_limits(mut self, limits: RiskLimits) -> Self {
+        self.risk = Some(Mutex::new(RiskGuard::new(limits)));
+        self
+    }
+    /// Verify order txs with `eth_call` against the latest state before sending them, skipping orders which revert
+    ///
+    /// The call is made only within `budget` of receiving the order's feed message and is abandoned (i.e. the order is
+    /// sent unverified) once the budget runs out
+    pub fn with_preflight(mut self, budget: Duration) -> Self {
+        self.preflight_budget = Some(budget);
+        self
+    }
+    /// Verify the order `tx` with `eth_call` if time budget remains
+    ///
+    /// Returns `OrderError::Preflight` if the tx reverts
+    async fn preflight(&self, order: &Order, tx: &TypedTransaction) -> Result<(), OrderError> {
+        let budget = match self.preflight_budget {
+            Some(budget) => budget,
+            None => return Ok(()),
+        };
+        let elapsed = order
+            .timestamps
+            .since_received(Some(Instant::now()))
+            .unwrap_or_default();
+        let remaining = match budget.checked_sub(elapsed) {
+            Some(remaining) if !remaining.is_zero() => remaining,
+            _ => {
+                debug!("pre-flight skipped, {:?} since received", elapsed);
+                return Ok(());
+            }
+        };
+        let mut call = tx.clone();
+        call.set_from(self.wallet.address());
+        match timeout(remaining, self.client.call(&call, None)).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(err)) if err.as_error_response().is_some() => {
+                let revert = revert_data(&err).map_or(RevertReason::Unknown(None), |data| {
+                    RevertReason::from_revert_data(&data)
+                });
+                info!("order reverts pre-flight ✈️: {:?}, skipped", revert);
+                #[cfg(feature = "metrics")]
+                metrics::ORDERS_PREFLIGHT_REVERTED.inc(revert.label());
+                Err(OrderError::Preflight(revert))
+            }
+            // not a revert e.g. the connection dropped, send anyway
+            Ok(Err(err)) => {
+                warn!("pre-flight: {:?}", err);
+                Ok(())
+            }
+            Err(_) => {
+                debug!("pre-flight out of time budget, sending unverified");
+                Ok(())
+            }
+        }
+    }
+    /// Record simulated fills of dry-run (or paused) orders to the ledger
+    pub fn with_shadow_execution(mut self, mode: ShadowMode) -> Self {
+        self.shadow = Some(mode);
+        self
+    }
+    /// Record a simulated fill of the dry-run `order` with order `tx` to the ledger
+    async fn shadow_fill(
+        &self,
+        mode: ShadowMode,
+        order: &Order,
+        tx: &TypedTransaction,
+        max_fee_per_gas: U256,
+    ) {
+        let (gas, revert) = match mode {
+            ShadowMode::PriceGraph => (U256::from(self.gas.estimate(&order.trade)), None),
+            ShadowMode::EstimateGas => {
+                let mut call = tx.clone();
+                call.set_from(self.wallet.address());
+                match self.client.estimate_gas(&call, None).await {
+                    Ok(gas) => {
+                        self.gas.calibrate(&order.trade, gas.low_u64());
+                        (gas, None)
+                    }
+                    Err(err) if err.as_error_response().is_some() => {
+                        let revert = revert_data(&err)
+                            .map_or(RevertReason::Unknown(None), |data| {
+                                RevertReason::from_revert_data(&data)
+                            });
+                        debug!("shadow fill reverts: {:?}", revert);
+                        (U256::zero(), Some(revert))
+                    }
+                    // not a revert e.g. the connection dropped, nothing to record
+                    Err(err) => {
+                        warn!("shadow fill: {:?}", err);
+                        return;
                     }
                 }
             }
+        };
+        self.record(|| LedgerRecord::Simulated {
+            ts: LedgerRecord::now(),
+            block_number: order.block_number,
+            token: format!("{:?}", Token::new(order.trade.path[0].token_in)),
+            path: order.trade.to_string(),
+            payload: tx.data().cloned().unwrap_or_default(),
+            amount_in: order.amount_in,
+            amount_out: order.amount_out,
+            min_amount_out: self.min_amount_out(order.amount_out),
+            gas_cost: (gas * max_fee_per_gas).low_u128(),
+            filled: revert.is_none(),
+            revert,
         });
-
-        tx
     }
-    /// Provide some local estimation of transaction `gas_limit`
-    const fn calculate_gas() -> u64 {
-        // from foundry gas reports + 100%
-        (613_827_u64 + 50_124) * 2
+    /// Pre-sign the order txs of the last `templates` orders between blocks, orders repeating one skip signing
+    pub fn with_presigning(mut self, templates: usize) -> Self {
+        self.presigned = Some(Mutex::new(PresignCache::new(templates)));
+        self
+    }
+    /// Pre-sign the txs of recent orders at the next nonce and current fee, off the hot path
+    async fn presign(&self) {
+        let cache = match self.presigned.as_ref() {
+            Some(cache) => cache,
+            None => return,
+        };
+        let nonce = self.lock_nonces().next_nonce();
+        let unsigned = cache
+            .lock()
+            .expect("presign lock")
+            .unsigned(nonce, self.max_fee_per_gas);
+        for tx in unsigned.iter() {
+            match self.wallet.sign(tx).await {
+                Ok(signature) => cache
+                    .lock()
+                    .expect("presign lock")
+                    .insert(tx, tx.rlp_signed(&signature)),
+                Err(err) => debug!("presign tx #{}: {:?}", nonce.as_u64(), err),
+            }
+        }
+    }
+    /// Sign `tx`, local signers skip the await
+    async fn sign(&self, tx: &TypedTransaction) -> Result<Signature, OrderError> {
+        match self.wallet.sign_sync(tx) {
+            Some(signature) => signature,
+            None => self.wallet.sign(tx).await,
+        }
+    }
+    /// Keep the tx of `order` as a pre-signing template (if enabled) and for gas calibration
+    fn observe_order_tx(&self, order: &Order, tx: &TypedTransaction) {
+        if let Some(cache) = self.presigned.as_ref() {
+            cache.lock().expect("presign lock").observe(tx);
+        }
+        *self.latest_order.lock().expect("latest order lock") =
+            Some((order.trade, tx.data().cloned().unwrap_or_default()));
+    }
+    /// Calibrate the gas model with `eth_estimateGas` of the latest order against the latest state
+    ///
+    /// Orders which no longer fill (i.e. the estimate reverts) are skipped
+    async fn calibrate_gas(&self) {
+        let (trade, calldata) = match self.latest_order.lock().expect("latest order lock").take() {
+            Some(latest_order) => latest_order,
+            None => return,
+        };
+        let call: TypedTransaction = Eip1559TransactionRequest::new()
+            .from(self.wallet.address())
+            .to(self.contract.address())
+            .data(calldata)
+            .gas(self.gas.gas_limit(&trade))
+            .into();
+        match self.client.estimate_gas(&call, None).await {
+            Ok(gas) => {
+                self.gas.calibrate(&trade, gas.low_u64());
+                debug!(
+                    "gas calibrated ⛽: {} for {}, scale {:.3}",
+                    gas,
+                    trade,
+                    self.gas.scale()
+                );
+            }
+            Err(err) => debug!("gas calibration: {:?}", err),
+        }
+    }
+    /// Report the progress of `order`, updates are dropped if the subscriber lags
+    fn update(&self, order: &Order, status: OrderStatus) {
+        if let Some(updates) = self.updates.as_ref() {
+            let update = OrderUpdate {
+                order: order.clone(),
+                status,
+            };
+            if updates.try_send(update).is_err() {
+                debug!("order update dropped: {:?}", status);
+            }
+        }
     }
+    /// The min. acceptable output of an order simulated to return `amount_out`
+    fn min_amount_out(&self, amount_out: u128) -> u128 {
+        amount_out - amount_out / 10_000 * self.slippage_bps as u128
+    }
+    /// Provide some local estimation of transaction `gas_limit`
     /// Update gas price querying the configured chain
     pub async fn sync_base_fee(&mut self) {
         let t0 = Instant::now();
@@ -170,174 +567,501 @@ where
     }
     /// Keep the order submission connections warm
     pub fn warm_connections(&self) {
-        tokio::spawn({
-            let http_client = self.sequencer_client.clone();
-            async move {
-                let t0 = Instant::now();
-                let warm_futs = [
-                    http_client.post_async(
-                        ARB_SEQUENCER_HTTPS,
-                        r#"{"method":"eth_chainId","params":[]}"#,
-                    ),
-                    http_client
-                        .post_async(ARB_FULL_HTTPS, r#"{"method":"eth_chainId","params":[]}"#),
-                ];
-                // mark trade as in flight
-                let (res1, _, other) = select_all(warm_futs).await;
-                if let Err(err) = res1 {
-                    error!("warm seq conn(1): {:?}", err);
-                }
-                let (res2, _, _) = select_all(other).await;
-                if let Err(err) = res2 {
-                    error!("warm seq conn(2): {:?}", err);
-                }
-                debug!("warm conns 🔥: {:?}", Instant::now() - t0);
-            }
-        });
+        self.submitter.warm();
     }
     /// Returns current max fee per gas for the configured chain
     pub fn max_fee_per_gas(&self) -> u64 {
         self.max_fee_per_gas.as_u64()
     }
-    /// Construct contract call for order execution given the trade `path`
-    /// - `fee_tier` the fee tier for the initial loan pool denoted by `path[0]`
-    fn build_call(&self, amount_in: u128, trade: &CompositeTrade) -> FunctionCall<Arc<M>, M, ()> {
-        // somewhat pathological attempt at optimizing for encoding speed e.g vs using RLP crate and typical solidity ABI
-        // pack the trade path as a u128, contract uses lookup tables with mirrored enums and addresses
-        // used by this client
-        // ~50 dead bits in `payload`
-        //  32 unused bits + ~18 bits reclaimable if use some tighter assumptions about ranges
-
-        let path = &trade.path;
-        // dex/exchange Id 8 (bits)
-        let mut payload = path[0].exchange_id as u128;
-        payload |= (path[1].exchange_id as u128) << 8;
-        payload |= (path[2].exchange_id as u128) << 16;
-
-        // token path a,b,c (8 bits)
-        payload |= (path[0].token_in as u128) << 24;
-        payload |= (path[0].token_out as u128) << 32;
-        if path[0].token_in != path[1].token_out {
-            payload |= (path[1].token_out as u128) << 40;
-        } else {
-            // an unused number that will map to the 0 address
-            payload |= 255_u128 << 40;
-        }
+    /// Construct the executor contract call for order execution given the `trade` path
+    /// - `min_amount_out` the contract reverts if the trade returns less e.g. the opportunity was taken
+    ///
+    /// Returns `OrderError::Encode` if the order encoder can't represent `trade`
+    fn build_call(
+        &self,
+        amount_in: u128,
+        min_amount_out: u128,
+        trade: &CompositeTrade,
+    ) -> Result<TypedTransaction, OrderError> {
+        let data = self
+            .order_encoder
+            .encode(amount_in, min_amount_out, trade)
+            .map_err(|err| {
+                warn!("order not encodable 🚧: {:?}", err);
+                OrderError::Encode(err)
+            })?;
+        Ok(Eip1559TransactionRequest::new()
+            .to(self.contract.address())
+            .data(data)
+            .into())
+    }
 
-        // pair fee tiers 16 bits each
-        payload |= (path[0].fee_tier as u128) << 48;
-        payload |= (path[1].fee_tier as u128) << 64;
-        payload |= (path[2].fee_tier as u128) << 80;
-        // 3 + 3 + 6 bytes = 24 hex chars, 32 bits unused
-        trace!("payload: {:032x}", payload);
-
-        /*
-            let method = [235, 51, 224, 234];
-            let data = encode_function_data(function, args)?;
-            let tx = Eip1559TransactionRequest {
-                to: Some(self.address.into()),
-                data: Some(data),
-                ..Default::default()
-            };
-            let tx: TypedTransaction = tx.into();
+    /// Execute `order` at the next nonce, concurrently with any in-flight orders it does not intersect
+    ///
+    /// Returns `OrderError::Busy` if too many orders are in-flight, `OrderError::Conflict` if it intersects one,
+    /// or `OrderError::RiskLimit` if it exceeds a risk limit
+    async fn submit_order(&self, order: &Order, dry_run: bool) -> Result<(), OrderError> {
+        if let Some(risk) = self.risk.as_ref() {
+            if let Err(violation) = risk.lock().expect("risk lock").check(order) {
+                warn!("order rejected 🚧: {violation}");
+                return Err(OrderError::RiskLimit);
+            }
+        }
+        // orders are still built while paused
+        let dry_run = self
+            .controls
+            .as_ref()
+            .map_or(dry_run, |controls| controls.dry_run())
+            || self.is_paused();
+        let max_fee_per_gas = self.max_fee_per_gas;
+        let nonce = self.lock_nonces().reserve(order, max_fee_per_gas)?;
+        let result = self
+            .flash_swap(nonce, max_fee_per_gas, order, dry_run)
+            .await;
+        let timestamps = Timestamps {
+            submitted: Some(Instant::now()),
+            ..order.timestamps
+        };
+        debug!(
+            "order latency ⏱️: {:?}",
+            timestamps.since_received(timestamps.submitted)
+        );
+        #[cfg(feature = "metrics")]
+        if result.is_ok() {
+            metrics::LATENCY_SUBMITTED
+                .observe_some(timestamps.since_received(timestamps.submitted));
+        }
+        match result {
+            Ok(Some(tx_hash)) => {
+                self.lock_nonces().pending(nonce, tx_hash);
+                if let Some(risk) = self.risk.as_ref() {
+                    risk.lock().expect("risk lock").record(order);
+                }
+                self.update(order, OrderStatus::Sent);
+            }
+            // the tx was never sent
+            Ok(None)
+            | Err(OrderError::TxSigning | OrderError::Preflight(_) | OrderError::Encode(_)) => {
+                self.lock_nonces().release(nonce)
+            }
+            // the tx may have been sent, its inclusion is checked when polling
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+    /// Submit `order`, queueing it if in-flight orders block it
+    async fn submit_or_queue(&self, order: Order, queue: &mut OrderQueue, dry_run: bool) {
+        match self.submit_order(&order, dry_run).await {
+            Err(OrderError::Busy) => {
+                debug!(
+                    "too many txs in-flight, order queued: #{:?}",
+                    self.lock_nonces().next_nonce().as_u32()
+                );
+                queue.push(order, Instant::now());
+            }
+            Err(OrderError::Conflict) => {
+                debug!("order conflicts with an in-flight order, queued");
+                queue.push(order, Instant::now());
+            }
+            _ => {}
         }
-        */
-        // TODO: simplify to the above
-        self.contract.flash_swap(amount_in, payload)
     }
+    /// Submit queued orders, most profitable first, until in-flight orders block them again
+    async fn submit_queued(&self, queue: &mut OrderQueue, dry_run: bool) {
+        // conflicting orders wait for their in-flight order, others may still go ahead
+        let mut conflicting = Vec::new();
+        while let Some((order, queued_at)) = queue.pop(Instant::now()) {
+            match self.submit_order(&order, dry_run).await {
+                Err(OrderError::Busy) => {
+                    queue.push(order, queued_at);
+                    break;
+                }
+                Err(OrderError::Conflict) => conflicting.push((order, queued_at)),
+                _ => debug!("queued order away: {:?}", Instant::now() - queued_at),
+            }
+        }
+        for (order, queued_at) in conflicting {
+            queue.push(order, queued_at);
+        }
+    }
+    /// Poll in-flight order txs, removing included ones and replacing stuck ones with a bumped fee
+    async fn poll_inflight(&self) {
+        if self.lock_nonces().is_empty() {
+            return;
+        }
+        let tx_count = match self
+            .client
+            .get_transaction_count(self.wallet.address(), None)
+            .await
+        {
+            Ok(tx_count) => tx_count,
+            Err(err) => {
+                error!("nonce poll: {:?}", err);
+                return;
+            }
+        };
+        let confirmed = self.lock_nonces().confirm(tx_count);
+        for inflight in confirmed.iter() {
+            self.check_receipt(inflight).await;
+        }
 
-    /// Execute a flash swap along `path` loaning `amount_in` from the uniswap v3 pool specified with `path[0]`
-    async fn flash_swap(
-        &self,
-        nonce: U256,
-        amount_in: u128,
-        trade: &CompositeTrade,
-        inflight: &mut Option<OrderTxStatus>,
-        dry_run: bool,
-    ) -> Result<(), OrderError> {
-        let t0 = Instant::now();
-        match inflight {
-            None => {}
-            Some(OrderTxStatus::Submitted(timestamp)) => {
-                if t0.duration_since(*timestamp) < Duration::from_secs(2) {
-                    return Err(OrderError::Busy);
+        let stuck = self.lock_nonces().stuck(Instant::now());
+        for nonce in stuck {
+            let replacement = self.lock_nonces().replace(nonce);
+            if let Some((order, max_fee_per_gas, status)) = replacement {
+                #[cfg(feature = "metrics")]
+                metrics::TXS_REPLACED.inc();
+                match self.flash_swap(nonce, max_fee_per_gas, &order, false).await {
+                    Ok(Some(tx_hash)) => self.lock_nonces().pending(nonce, tx_hash),
+                    Ok(None) => self.lock_nonces().replace_failed(nonce, status),
+                    Err(err) => {
+                        error!("replace tx #{}: {:?}", nonce.as_u64(), err);
+                        self.lock_nonces().replace_failed(nonce, status);
+                    }
+                }
+            }
+        }
+    }
+    /// Submit `orders` and poll in-flight orders, restarted from here if it panics
+    ///
+    /// Queued orders are dropped on a restart, in-flight orders are kept
+    async fn run(self: Arc<Self>, orders: Arc<Receiver<Order>>, dry_run: bool) {
+        let mut queue = OrderQueue::new(self.order_max_age);
+        let mut warm_interval = tokio::time::interval(HTTP_KEEP_ALIVE_S - Duration::from_secs(5)); // ensure slightly less than timeout
+                                                                                                   // The ideal interval for base fee update (unused for now as simply over-estimating is fine i.e tx submitted, min fee charged)
+        let mut poll_interval = tokio::time::interval(POLL_INTERVAL);
+        let mut gas_calibration_interval = tokio::time::interval(GAS_CALIBRATION_INTERVAL);
+        loop {
+            select! {
+                biased;
+                trade_request = orders.recv() => {
+                    if let Some(order) = trade_request {
+                        self.submit_or_queue(order, &mut queue, dry_run).await;
+                    }
+                }
+                _ = poll_interval.tick() => {
+                    self.poll_inflight().await;
+                    // in-flight orders may have resolved
+                    if !queue.is_empty() {
+                        self.submit_queued(&mut queue, dry_run).await;
+                    }
+                    self.presign().await;
+                }
+                _ = gas_calibration_interval.tick() => self.calibrate_gas().await,
+                _ = warm_interval.tick() => self.warm_connections(),
+            }
+        }
+    }
+    /// Await the inclusion of in-flight orders for up to `SHUTDOWN_DRAIN` then save the nonce state, if enabled
+    async fn drain(&self) {
+        let deadline = Instant::now() + SHUTDOWN_DRAIN;
+        while !self.lock_nonces().is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            self.poll_inflight().await;
+        }
+        let state = NonceState::of(&self.lock_nonces());
+        if !state.inflight.is_empty() {
+            warn!("shutdown with {} orders in-flight", state.inflight.len());
+        }
+        if let Some(path) = self.nonce_state.as_ref() {
+            if let Err(err) = state.save(path) {
+                error!("save nonce state {:?}: {:?}", path, err);
+            }
+        }
+    }
+    /// Record the execution outcome of an included order tx
+    async fn check_receipt(&self, inflight: &InflightOrder) {
+        let tx_hash = match inflight.status {
+            InflightStatus::Pending(tx_hash) => tx_hash,
+            _ => {
+                debug!("tx #{} included", inflight.nonce.as_u64());
+                // outcome unknown, stop accounting for its price impact
+                self.update(&inflight.order, OrderStatus::Failed);
+                return;
+            }
+        };
+        match self.client.get_transaction_receipt(tx_hash).await {
+            Ok(Some(receipt)) => {
+                debug!("tx execution\n{:?}", receipt);
+                let success = receipt.status.is_some_and(|status| status.as_u64() == 1);
+                let block_number = receipt.block_number.unwrap_or_default().as_u64();
+                if success {
+                    self.update(&inflight.order, OrderStatus::Included(block_number));
+                    if let Some(gas_used) = receipt.gas_used {
+                        self.gas
+                            .calibrate(&inflight.order.trade, gas_used.low_u64());
+                    }
+                } else {
+                    self.update(&inflight.order, OrderStatus::Failed);
+                }
+                // replays the tx, after the engine is updated
+                let revert = if success {
+                    None
+                } else {
+                    analyze_revert(self.client.as_ref(), tx_hash, &receipt).await
+                };
+                if let Some(revert) = revert.as_ref() {
+                    info!("tx #{} reverted: {:?}", inflight.nonce.as_u64(), revert);
+                }
+                let gas_used = receipt.gas_used.unwrap_or_default();
+                let gas_cost =
+                    (gas_used * receipt.effective_gas_price.unwrap_or_default()).as_u128();
+                let start_token = Token::new(inflight.order.trade.path[0].token_in);
+                let profit = ledger::transferred(
+                    &receipt.logs,
+                    start_token.address(),
+                    self.contract.address(),
+                    self.payee,
+                );
+                if let Some(breaker) = self.breaker.as_ref() {
+                    let mut breaker = breaker.lock().expect("breaker lock");
+                    #[cfg(feature = "notify")]
+                    let was_tripped = breaker.is_tripped();
+                    breaker.record(success, gas_cost, profit_in_wei(&inflight.order, profit));
+                    #[cfg(feature = "notify")]
+                    if let Some(alerts) = self.alerts.as_ref() {
+                        if !was_tripped && breaker.is_tripped() {
+                            alerts.send(Alert::CircuitBreakerTripped {
+                                consecutive_reverts: breaker.consecutive_reverts(),
+                                loss: breaker.loss(),
+                            });
+                        }
+                    }
+                }
+                #[cfg(feature = "metrics")]
+                if success {
+                    metrics::TXS_SUCCEEDED.inc();
                 } else {
-                    debug!("removing stale tx");
-                    let _ = inflight.take();
+                    metrics::TXS_FAILED.inc();
+                    metrics::TXS_REVERTED.inc(revert.as_ref().map_or("unknown", |r| r.label()));
                 }
+                #[cfg(feature = "notify")]
+                if let Some(alerts) = self.alerts.as_ref() {
+                    alerts.send(Alert::TradeExecuted {
+                        nonce: inflight.nonce.as_u64(),
+                        tx_hash,
+                        block_number,
+                        token: start_token,
+                        profit,
+                        gas_cost,
+                        success,
+                        revert: revert.clone(),
+                    });
+                }
+                self.record(|| LedgerRecord::Reconciled {
+                    ts: LedgerRecord::now(),
+                    nonce: inflight.nonce.as_u64(),
+                    tx_hash,
+                    block_number,
+                    success,
+                    gas_used: gas_used.as_u64(),
+                    gas_cost,
+                    profit,
+                    revert,
+                });
+            }
+            // an earlier tx at the nonce was included
+            Ok(None) => {
+                debug!("tx #{} replaced", inflight.nonce.as_u64());
+                self.update(&inflight.order, OrderStatus::Failed);
             }
-            Some(OrderTxStatus::Received(_)) => {
-                return Err(OrderError::Busy);
+            Err(err) => {
+                error!("tx receipt: {:?}", err);
+                self.update(&inflight.order, OrderStatus::Failed);
             }
         }
+    }
+    /// Send the `order` tx at `nonce` paying `max_fee_per_gas`, loaning `order.amount_in` from the pool of the first trade
+    /// `order.amount_out` is the simulated output, the order reverts if it falls short by more than the slippage tolerance
+    ///
+    /// Returns the tx hash once accepted by the network (`None` if `dry_run`)
+    async fn flash_swap(
+        &self,
+        nonce: U256,
+        max_fee_per_gas: U256,
+        order: &Order,
+        dry_run: bool,
+    ) -> Result<Option<TxHash>, OrderError> {
+        let t0 = Instant::now();
 
         // Build tx
-        let mut flash_swap_call = self.build_call(amount_in, trade);
-        let tx = flash_swap_call
-            .tx
+        let mut flash_swap_tx = self.build_call(
+            order.amount_in,
+            self.min_amount_out(order.amount_out),
+            &order.trade,
+        )?;
+        let tx = flash_swap_tx
             .set_chain_id(self.wallet.chain_id())
             .set_nonce(nonce)
-            .set_gas_price(self.max_fee_per_gas)
-            .set_gas(Self::calculate_gas())
-            .set_to((*self.contract).address());
-        let signature = self
-            .wallet
-            // TODO(optimization):
-            // EC math causing most of slowness need special hardware
-            // some unnecessary copy and mem-move in here
-            .sign_transaction_sync(tx)
-            .map_err(|_| OrderError::TxSigning)?;
-        // TODO(optimization):
-        // rlp encodes the tx, allocs a string+vec each time
-        let request = create_send_raw_tx_json(&tx.rlp_signed(&signature));
-        let send_raw_tx_futs = [
-            self.sequencer_client
-                .post_async(ARB_SEQUENCER_HTTPS, request.as_str()),
-            self.sequencer_client
-                .post_async(ARB_FULL_HTTPS, request.as_str()),
-        ];
+            .set_gas_price(max_fee_per_gas)
+            .set_gas(self.gas.gas_limit(&order.trade));
+        if !dry_run {
+            self.preflight(order, tx).await?;
+        }
+        let presigned = self
+            .presigned
+            .as_ref()
+            .and_then(|cache| cache.lock().expect("presign lock").take(tx));
+        let signature = match presigned {
+            Some(_) => None,
+            None => Some(self.sign(tx).await?),
+        };
+        // encode into the reused buffers, returned once the request is sent
+        let mut encoder = self
+            .encoder
+            .lock()
+            .expect("encoder lock")
+            .take()
+            .unwrap_or_default();
+        let request = match signature {
+            Some(signature) => encoder.signed_tx_request(tx, &signature),
+            None => {
+                #[cfg(feature = "metrics")]
+                metrics::TXS_PRESIGNED.inc();
+                encoder.raw_tx_request(presigned.as_deref().unwrap_or_default())
+            }
+        };
         if dry_run {
             info!("built tx: {:?}", Instant::now() - t0);
             debug!("{request}");
-            return Ok(());
+            *self.encoder.lock().expect("encoder lock") = Some(encoder);
+            self.observe_order_tx(order, &flash_swap_tx);
+            if let Some(mode) = self.shadow {
+                self.shadow_fill(mode, order, &flash_swap_tx, max_fee_per_gas)
+                    .await;
+            }
+            return Ok(None);
         }
 
         // sending tx
         // mark trade as in flight
-        *inflight = Some(OrderTxStatus::Submitted(t0));
-        let result = select_ok(send_raw_tx_futs).await;
+        self.lock_nonces().submitted(nonce, t0);
+        #[cfg(feature = "metrics")]
+        metrics::TXS_SUBMITTED.inc();
+        let result = self.submitter.send(request).await;
         info!("sent tx #{}: {:?}", nonce.as_u32(), Instant::now() - t0);
 
         // we are less performance critical after the order is submitted
+        *self.encoder.lock().expect("encoder lock") = Some(encoder);
+        self.observe_order_tx(order, &flash_swap_tx);
         let tx_hash = match result {
-            Ok((response, _)) => {
+            Some(response) => {
                 // the tx sent ok, inc local nonce
-                decode_send_raw_tx_response(response)
-                    .await
-                    .map_err(|_| OrderError::TxSubmitResponse)
+                decode_send_raw_tx_response(response).await.map_err(|_| {
+                    #[cfg(feature = "metrics")]
+                    metrics::TXS_FAILED.inc();
+                    OrderError::TxSubmitResponse
+                })
             }
-            Err(err) => {
-                error!("tx submit #{}: {:?}", nonce.as_u32(), err);
+            None => {
+                error!("tx submit #{}: no endpoint accepted", nonce.as_u32());
+                #[cfg(feature = "metrics")]
+                metrics::TXS_FAILED.inc();
                 Err(OrderError::TxSubmit)
             }
         }?;
-        // mark trade as received
-        *inflight = Some(OrderTxStatus::Received(tx_hash));
         debug!("watching tx: {:?}", tx_hash);
-        // on error we could await the other future
-        let receipt = PendingTransaction::new(tx_hash, self.client.provider())
+        self.record(|| LedgerRecord::Submitted {
+            ts: LedgerRecord::now(),
+            nonce: nonce.as_u64(),
+            tx_hash,
+            block_number: order.block_number,
+            token: format!("{:?}", Token::new(order.trade.path[0].token_in)),
+            path: order.trade.to_string(),
+            payload: flash_swap_tx.data().cloned().unwrap_or_default(),
+            amount_in: order.amount_in,
+            amount_out: order.amount_out,
+            min_amount_out: self.min_amount_out(order.amount_out),
+            max_fee_per_gas,
+        });
+
+        Ok(Some(tx_hash))
+    }
+}
+
+#[async_trait]
+impl<M> OrderExecutor for OrderService<M>
+where
+    M: Middleware + 'static,
+{
+    async fn start(mut self, dry_run: bool) -> Sender<Order> {
+        let nonce = self
+            .client
+            .get_transaction_count(self.wallet.address(), None)
             .await
-            .map_err(|err| {
-                error!("tx inclusion: {:?}", err);
-                OrderError::TxInclusion
-            })?;
-        debug!("tx execution\n{:?}", receipt);
+            .expect("nonce fetched");
+        info!(
+            "config: order account: {:?}, nonce: {:?}",
+            self.wallet.address(),
+            nonce
+        );
+        if let Some(saved) = self.nonce_state.as_deref().and_then(NonceState::load) {
+            // the saved txs at or above the account's nonce were not (yet) included
+            let pending: Vec<_> = saved
+                .inflight
+                .iter()
+                .filter(|(saved_nonce, _)| *saved_nonce >= nonce)
+                .collect();
+            if saved.next_nonce > nonce {
+                warn!(
+                    "saved nonce #{} is ahead of the account's, order txs may be pending: {:?}",
+                    saved.next_nonce.as_u64(),
+                    pending
+                );
+            }
+        }
+        *self.lock_nonces() = NonceManager::new(nonce);
+        if let Some(breaker) = self.breaker.as_ref() {
+            risk::resume_on_signal(Arc::clone(breaker));
+        }
+        if self.reports_pnl() {
+            self.payee = self.contract.payee().call().await.expect("payee fetched");
+            info!("config: payee: {:?}", self.payee);
+        }
 
-        *inflight = None;
-        Ok(())
+        let (tx, rx) = channel::<Order>(5);
+        let orders = Arc::new(rx);
+        let mut shutdown = self.shutdown.take();
+        let this = Arc::new(self);
+        tokio::spawn(async move {
+            select! {
+                biased;
+                _ = shutdown_of(&mut shutdown) => {
+                    info!("order service stopping, queued orders dropped");
+                    this.drain().await;
+                }
+                _ = supervise(Subsystem::OrderLoop, || {
+                    Arc::clone(&this).run(Arc::clone(&orders), dry_run)
+                }) => {}
+            }
+        });
+
+        tx
+    }
+    fn max_fee_per_gas(&self) -> u64 {
+        self.max_fee_per_gas.as_u64()
+    }
+    fn gas_model(&self) -> Arc<GasModel> {
+        Arc::clone(&self.gas)
+    }
+    fn updates(&mut self) -> Option<Receiver<OrderUpdate>> {
+        let (tx, rx) = channel::<OrderUpdate>(ORDER_UPDATE_QUEUE_SIZE);
+        self.updates = Some(tx);
+        Some(rx)
     }
 }
 
+/// The value (wei) of `profit` in `order`'s start token, at the order's prices
+///
+/// Unpriced orders value their profit at 0 so they count their gas in full
+fn profit_in_wei(order: &Order, profit: u128) -> u128 {
+    if order.amount_in == 0 {
+        return 0;
+    }
+    let value = U256::from(profit) * U256::from(order.value_in_wei) / U256::from(order.amount_in);
+    value.min(U256::from(u128::MAX)).as_u128()
+}
+
 /// Decode an Ethereum JSON-RPC 'eth_sendRawTransaction' response payload, returning the tx hash
 async fn decode_send_raw_tx_response(response: Response) -> Result<TxHash, ()> {
     // TODO: fix this
@@ -359,15 +1083,6 @@ async fn decode_send_raw_tx_response(response: Response) -> Result<TxHash, ()> {
     }
 }
 
-/// Encode an Ethereum JSON-RPC 'eth_sendRawTransaction' payload
-fn create_send_raw_tx_json(signed_tx: &Bytes) -> String {
-    let hexed_tx = serialize_hex(signed_tx);
-    format!(
-        r#"{{"id":1337,"jsonrpc":"2.0","method":"eth_sendRawTransaction","params":["0x{}"]}}"#,
-        hexed_tx
-    )
-}
-
 #[cfg(test)]
 mod test {
     use std::{str::FromStr, sync::Arc};
@@ -379,13 +1094,16 @@ mod test {
         },
         utils::rlp::Rlp,
     };
-    use ethers_providers::{MockProvider, Provider};
+    use ethers_providers::{JsonRpcError, MockProvider, MockResponse, Provider};
     use ethers_signers::{LocalWallet, Signer};
     use hex_literal::hex;
 
     use fulcrum_ws_cli::AsyncBody;
 
-    use crate::price_graph::{CompositeTrade, Trade};
+    use crate::{
+        price_graph::{CompositeTrade, Trade},
+        types::Fee,
+    };
 
     use super::*;
 
@@ -396,8 +1114,8 @@ mod test {
             .unwrap()
             .with_chain_id(Chain::Arbitrum);
 
-        let provider =
-            Provider::<MockProvider>::new(MockProvider::new()).with_sender(wallet.address());
+        let provider = Provider::<MockProvider>::new(MockProvider::new())
+            .with_sender(Signer::address(&wallet));
         let provider = Arc::new(provider);
 
         (*(provider.clone()))
@@ -411,14 +1129,6 @@ mod test {
         return service;
     }
 
-    #[test]
-    fn encode_send_raw_tx_json() {
-        assert_eq!(
-            create_send_raw_tx_json(&Bytes::from_static(b"10334551124512451245012343241234")),
-            r#"{"id":1337,"jsonrpc":"2.0","method":"eth_sendRawTransaction","params":["0x3130333334353531313234353132343531323435303132333433323431323334"]}"#,
-        );
-    }
-
     #[tokio::test]
     async fn decode_send_raw_tx_response_to_tx_hash() {
         let body = AsyncBody::from(
@@ -442,29 +1152,105 @@ mod test {
     async fn build_call_works() {
         let service = make_service().await;
 
-        let path = CompositeTrade::new([
-            Trade::new(1, 2, 500, 1),
-            Trade::new(2, 1, 3000, 1),
-            Trade::default(),
+        let path = CompositeTrade::new(&[
+            Trade::new(1, 2, Fee::from_pips(500), 1),
+            Trade::new(2, 1, Fee::from_pips(3000), 1),
         ]);
-        let call = service.build_call(10_000000_u128, &path);
+        let call = service
+            .build_call(10_000000_u128, 10_010000_u128, &path)
+            .unwrap();
 
-        assert_eq!(call.tx.rlp(), Bytes::from_static(
-            hex!("02f862808080808094000000000000000000000000ffffffffffffffff80b844eb33e0ea0000000000000000000000000000000000000000000000000000000000989680000000000000000000000000000000000000000000000bb801f4ff0201000101c0").as_slice()
+        assert_eq!(call.rlp(), Bytes::from_static(
+            hex!("02f882808080808094000000000000000000000000ffffffffffffffff80b864a969b7530000000000000000000000000000000000000000000000000000000000989680000000000000000000000000000000001262f64000000bb801f4ff0201000101000000000000000000000000000000000000000000000000000000000098bd90c0").as_slice()
         ));
 
-        let path2 = CompositeTrade::new([
-            Trade::new(3, 2, 3_000, 0),
-            Trade::new(2, 1, 500, 1),
-            Trade::new(1, 3, 0, 1),
+        let path2 = CompositeTrade::new(&[
+            Trade::new(3, 2, Fee::from_pips(3_000), 0),
+            Trade::new(2, 1, Fee::from_pips(500), 1),
+            Trade::new(1, 3, Fee::ZERO, 1),
         ]);
-        let call2 = service.build_call(10_000000_u128, &path2);
+        let call2 = service
+            .build_call(10_000000_u128, 10_010000_u128, &path2)
+            .unwrap();
 
-        assert_eq!(call2.tx.rlp(), Bytes::from_static(
-            hex!("02f862808080808094000000000000000000000000ffffffffffffffff80b844eb33e0ea00000000000000000000000000000000000000000000000000000000009896800000000000000000000000000000000000000000000001f40bb8010203010100c0").as_slice()
+        assert_eq!(call2.rlp(), Bytes::from_static(
+            hex!("02f882808080808094000000000000000000000000ffffffffffffffff80b864a969b7530000000000000000000000000000000000000000000000000000000000989680000000000000000000000000000000001262f642000001f40bb8010203010100000000000000000000000000000000000000000000000000000000000098bd90c0").as_slice()
         ));
     }
 
+    #[test]
+    fn profit_valued_at_order_prices() {
+        let order = Order {
+            amount_in: 2_000_000000,
+            value_in_wei: 1_000000000000000000,
+            ..Default::default()
+        };
+        assert_eq!(profit_in_wei(&order, 10_000000), 5000000000000000);
+        assert_eq!(profit_in_wei(&order, u128::MAX), u128::MAX);
+        // unpriced
+        assert_eq!(profit_in_wei(&Order::default(), 10_000000), 0);
+        let order = Order {
+            value_in_wei: 0,
+            ..order
+        };
+        assert_eq!(profit_in_wei(&order, 10_000000), 0);
+    }
+
+    #[tokio::test]
+    async fn min_amount_out_slippage() {
+        let service = make_service().await;
+        assert_eq!(service.min_amount_out(10_000000_u128), 9_990000_u128);
+        let service = service.with_slippage_bps(0);
+        assert_eq!(service.min_amount_out(10_000000_u128), 10_000000_u128);
+        let service = service.with_slippage_bps(10_000);
+        assert_eq!(service.min_amount_out(10_000000_u128), 0);
+    }
+
+    #[tokio::test]
+    async fn preflight_skips_reverting_orders() {
+        let service = make_service().await.with_preflight(Duration::from_secs(60));
+        let order = Order {
+            amount_in: 10_000000_u128,
+            amount_out: 10_010000_u128,
+            trade: CompositeTrade::new(&[
+                Trade::new(1, 2, Fee::from_pips(500), 1),
+                Trade::new(2, 1, Fee::from_pips(3000), 1),
+            ]),
+            ..Default::default()
+        };
+        let tx = service
+            .build_call(order.amount_in, order.amount_out, &order.trade)
+            .unwrap();
+
+        // `Slippage(7)`
+        let mut revert = hex!("5188f80d").to_vec();
+        revert.extend_from_slice(&[0_u8; 31]);
+        revert.push(7);
+        service
+            .provider()
+            .as_ref()
+            .push_response(MockResponse::Error(JsonRpcError {
+                code: 3,
+                message: "execution reverted".to_string(),
+                data: Some(format!("0x{}", hex::encode(revert)).into()),
+            }));
+        assert_eq!(
+            service.preflight(&order, &tx).await,
+            Err(OrderError::Preflight(RevertReason::Slippage(7)))
+        );
+
+        service
+            .provider()
+            .as_ref()
+            .push(Bytes::default())
+            .expect("response mocked");
+        assert_eq!(service.preflight(&order, &tx).await, Ok(()));
+
+        // no time budget left, the call is not made
+        let service = service.with_preflight(Duration::ZERO);
+        assert_eq!(service.preflight(&order, &tx).await, Ok(()));
+    }
+
     #[tokio::test]
     async fn sync_base_fee_works() {
         let mut service = make_service().await;
@@ -479,29 +1265,31 @@ mod test {
 
     #[tokio::test]
     async fn bench_flash_swap_presend() {
-        // try rust-secpk256k1 (btc core bindings) or needs some AVX hardware
+        // EC signing dominates, see `signer::bench` for k256 vs. libsecp256k1 (`--features=secp256k1,bench`)
         // ~55-75µs
         let service = make_service().await;
-        let trade = CompositeTrade::new([
-            Trade::new(3, 2, 3_000, 0),
-            Trade::new(2, 1, 500, 1),
-            Trade::new(1, 3, 0, 1),
+        let trade = CompositeTrade::new(&[
+            Trade::new(3, 2, Fee::from_pips(3_000), 0),
+            Trade::new(2, 1, Fee::from_pips(500), 1),
+            Trade::new(1, 3, Fee::ZERO, 1),
         ]);
 
+        let order = Order {
+            amount_in: 100_000000_u128,
+            amount_out: 100_100000_u128,
+            trade,
+            block_number: 0,
+            value_in_wei: 0,
+            timestamps: Default::default(),
+        };
+
         let mut total = Duration::ZERO;
-        let mut inflight_status = None;
         for i in 0..100 {
             let start = Instant::now();
             let result = service
-                .flash_swap(
-                    U256::one(),
-                    100_000000_u128,
-                    &trade,
-                    &mut inflight_status,
-                    true,
-                )
+                .flash_swap(U256::one(), service.max_fee_per_gas, &order, true)
                 .await;
-            assert_eq!(result, Ok(()));
+            assert_eq!(result, Ok(None));
             total += Instant::now().duration_since(start);
         }
         println!("mean: {:?}", total.as_micros() as f64 / 100_f64);
@@ -515,10 +1303,10 @@ mod test {
     //     let provider = service.provider();
     //     assert_eq!(service.nonce, U256::from(5));
 
-    //     let trade = CompositeTrade::new([
-    //         Trade::new(3, 2, 3_000, 0),
-    //         Trade::new(2, 1, 500, 1),
-    //         Trade::new(1, 3, 0, 1),
+    //     let trade = CompositeTrade::new(&[
+    //         Trade::new(3, 2, Fee::from_pips(3_000), 0),
+    //         Trade::new(2, 1, Fee::from_pips(500), 1),
+    //         Trade::new(1, 3, Fee::ZERO, 1),
     //     ]);
     //     // push eth_sendRawTransaction response
     //     let fake_tx_hash = TxHash(hex!(
@@ -0,0 +1,169 @@
+//! Pending order queue
+//!
+//! Orders which can't be submitted while others are in-flight wait here rather than being dropped.
+//! Once an in-flight order resolves, the most profitable order which is still fresh is submitted next
+use std::time::{Duration, Instant};
+
+use crate::order::Order;
+
+/// Max. orders queued, the least profitable are evicted
+const MAX_QUEUED: usize = 8;
+/// Default max. duration an order may wait in the queue
+pub(crate) const DEFAULT_MAX_AGE: Duration = Duration::from_millis(500);
+
+/// A queued order
+#[derive(Debug)]
+struct QueuedOrder {
+    order: Order,
+    /// Time the order was first queued
+    queued_at: Instant,
+    /// Expected profit ratio, comparable across start tokens
+    profit: f64,
+}
+
+/// Orders awaiting submission, by descending expected profit
+///
+/// Orders are stale (and evicted) once queued longer than `max_age` or when an order priced at a newer block arrives
+#[derive(Debug)]
+pub struct OrderQueue {
+    /// Queued orders, most profitable first
+    queued: Vec<QueuedOrder>,
+    /// Max. duration an order may wait in the queue
+    max_age: Duration,
+    /// Newest price block of any order queued
+    latest_block: u64,
+}
+
+impl Default for OrderQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_AGE)
+    }
+}
+
+impl OrderQueue {
+    /// Create a new queue holding orders for at most `max_age`
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            queued: Vec::with_capacity(MAX_QUEUED + 1),
+            max_age,
+            latest_block: 0,
+        }
+    }
+    #[cfg(test)]
+    /// Number of queued orders
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+    /// Returns true if no orders are queued
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+    /// Queue `order`, first queued at `queued_at`
+    ///
+    /// Orders priced at older blocks than the newest queued are dropped
+    pub fn push(&mut self, order: Order, queued_at: Instant) {
+        if order.block_number < self.latest_block {
+            return;
+        }
+        if order.block_number > self.latest_block {
+            self.latest_block = order.block_number;
+            let latest_block = self.latest_block;
+            self.queued
+                .retain(|queued| queued.order.block_number >= latest_block);
+        }
+        let profit = profit_ratio(&order);
+        let idx = self
+            .queued
+            .iter()
+            .position(|queued| profit > queued.profit)
+            .unwrap_or(self.queued.len());
+        self.queued.insert(
+            idx,
+            QueuedOrder {
+                order,
+                queued_at,
+                profit,
+            },
+        );
+        self.queued.truncate(MAX_QUEUED);
+    }
+    /// Take the most profitable order which is still fresh at `now` and the time it was queued
+    pub fn pop(&mut self, now: Instant) -> Option<(Order, Instant)> {
+        let max_age = self.max_age;
+        self.queued
+            .retain(|queued| now.saturating_duration_since(queued.queued_at) <= max_age);
+        if self.queued.is_empty() {
+            return None;
+        }
+        let queued = self.queued.remove(0);
+        Some((queued.order, queued.queued_at))
+    }
+}
+
+/// Expected profit of `order` relative to its size
+fn profit_ratio(order: &Order) -> f64 {
+    if order.amount_in == 0 {
+        return 0_f64;
+    }
+    (order.amount_out as f64 - order.amount_in as f64) / order.amount_in as f64
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{OrderQueue, MAX_QUEUED};
+    use crate::order::Order;
+
+    fn order(block_number: u64, amount_out: u128) -> Order {
+        Order {
+            amount_in: 1_000,
+            amount_out,
+            block_number,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pops_most_profitable_first() {
+        let mut queue = OrderQueue::new(Duration::from_millis(100));
+        let t0 = Instant::now();
+        queue.push(order(1, 1_010), t0);
+        queue.push(order(1, 1_030), t0);
+        queue.push(order(1, 1_020), t0);
+        let popped: Vec<u128> = std::iter::from_fn(|| queue.pop(t0))
+            .map(|(order, _)| order.amount_out)
+            .collect();
+        assert_eq!(popped, [1_030, 1_020, 1_010]);
+
+        // bounded, the least profitable are evicted
+        for amount_out in 0..(MAX_QUEUED as u128 + 2) {
+            queue.push(order(1, 1_001 + amount_out), t0);
+        }
+        assert_eq!(queue.len(), MAX_QUEUED);
+        assert_eq!(
+            queue.pop(t0).map(|(order, _)| order.amount_out),
+            Some(1_001 + MAX_QUEUED as u128 + 1)
+        );
+    }
+
+    #[test]
+    fn evicts_stale_orders() {
+        let mut queue = OrderQueue::new(Duration::from_millis(100));
+        let t0 = Instant::now();
+        queue.push(order(1, 1_050), t0);
+        queue.push(order(1, 1_010), t0 + Duration::from_millis(80));
+        // the more profitable order expired
+        let (popped, queued_at) = queue.pop(t0 + Duration::from_millis(150)).unwrap();
+        assert_eq!(popped.amount_out, 1_010);
+        assert_eq!(queued_at, t0 + Duration::from_millis(80));
+        assert!(queue.is_empty());
+
+        // orders priced at older blocks are dropped
+        queue.push(order(2, 1_050), t0);
+        queue.push(order(3, 1_010), t0);
+        queue.push(order(2, 1_090), t0);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.pop(t0).unwrap().0.block_number, 3);
+    }
+}
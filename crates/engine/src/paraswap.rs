@@ -0,0 +1,138 @@
+//! Paraswap Augustus utilities
+
+use ethabi_static::{AddressZcp, Bytes32, BytesZcp, DecodeStatic, Tuples};
+use ethers::types::U256;
+
+use crate::abi_check::abi_layout;
+
+/// Adapter and route percentages are in basis points
+pub const PERCENT_DENOMINATOR: u128 = 10_000;
+
+/// Top-level Augustus 'simpleSwap' call
+#[derive(DecodeStatic, Debug, PartialEq)]
+pub struct SimpleData<'a> {
+    pub from_token: AddressZcp<'a>,
+    pub to_token: AddressZcp<'a>,
+    pub from_amount: U256,
+    #[ethabi(skip)]
+    _to_amount: U256,
+    #[ethabi(skip)]
+    _expected_amount: U256,
+    // contracts called in order with `exchange_data[start_indexes[i]..start_indexes[i + 1]]`
+    pub callees: Vec<AddressZcp<'a>>,
+    pub exchange_data: BytesZcp<'a>,
+    pub start_indexes: Vec<u32>,
+    // uint256[] values;
+    // address beneficiary;
+    // address partner;
+    // uint256 feePercent;
+    // bytes permit;
+    // uint256 deadline;
+    // bytes16 uuid;
+}
+
+/// Top-level Augustus 'multiSwap' call
+#[derive(DecodeStatic, Debug, PartialEq)]
+pub struct SellData<'a> {
+    pub from_token: AddressZcp<'a>,
+    pub from_amount: U256,
+    #[ethabi(skip)]
+    _to_amount: U256,
+    #[ethabi(skip)]
+    _expected_amount: U256,
+    #[ethabi(skip)]
+    _beneficiary: U256,
+    pub path: Tuples<Path<'a>>,
+    // address partner;
+    // uint256 feePercent;
+    // bytes permit;
+    // uint256 deadline;
+    // bytes16 uuid;
+}
+
+/// Top-level Augustus 'megaSwap' call
+#[derive(DecodeStatic, Debug, PartialEq)]
+pub struct MegaSwapSellData<'a> {
+    pub from_token: AddressZcp<'a>,
+    pub from_amount: U256,
+    #[ethabi(skip)]
+    _to_amount: U256,
+    #[ethabi(skip)]
+    _expected_amount: U256,
+    #[ethabi(skip)]
+    _beneficiary: U256,
+    pub path: Tuples<MegaSwapPath<'a>>,
+    // address partner;
+    // uint256 feePercent;
+    // bytes permit;
+    // uint256 deadline;
+    // bytes16 uuid;
+}
+
+/// A share of the 'megaSwap' input sold along its own path
+#[derive(DecodeStatic, Debug, PartialEq)]
+pub struct MegaSwapPath<'a> {
+    pub from_amount_percent: u32,
+    pub path: Tuples<Path<'a>>,
+}
+
+/// A step of a swap path, selling the previous step's token for `to`
+#[derive(DecodeStatic, Debug, PartialEq)]
+pub struct Path<'a> {
+    pub to: AddressZcp<'a>,
+    #[ethabi(skip)]
+    _total_network_fee: U256,
+    pub adapters: Tuples<Adapter<'a>>,
+}
+
+/// The `percent` of a path step routed through an adapter contract
+#[derive(DecodeStatic, Debug, PartialEq)]
+pub struct Adapter<'a> {
+    #[ethabi(skip)]
+    _adapter: U256,
+    pub percent: u32,
+    #[ethabi(skip)]
+    _network_fee: U256,
+    pub route: Tuples<Route<'a>>,
+}
+
+/// The `percent` of an adapter's amount swapped on `target_exchange`
+#[derive(DecodeStatic, Debug, PartialEq)]
+pub struct Route<'a> {
+    // the adapter's exchange index, differs per adapter deployment
+    #[ethabi(skip)]
+    _index: U256,
+    pub target_exchange: AddressZcp<'a>,
+    pub percent: u32,
+    // exchange specific data
+    pub payload: BytesZcp<'a>,
+    // uint256 networkFee;
+}
+
+/// Route payload of the uniswap V2 (and forks) exchange
+#[derive(DecodeStatic, Debug, PartialEq)]
+pub struct UniswapV2Data<'a> {
+    #[ethabi(skip)]
+    _weth: U256,
+    // pair address in the low 160 bits, direction and fee packed above
+    pub pools: Vec<Bytes32<'a>>,
+}
+
+/// Route payload of the uniswap V3 exchange
+#[derive(DecodeStatic, Debug, PartialEq)]
+pub struct UniswapV3Data<'a> {
+    // uniswap compliant path bytes
+    pub path: BytesZcp<'a>,
+    // uint256 deadline;
+}
+
+abi_layout! {
+    SimpleData<'_>: 8, [5: Array, 6: Bytes, 7: Array];
+    SellData<'_>: 6, [5: Array];
+    MegaSwapSellData<'_>: 6, [5: Array];
+}
+
+/// Share `percent` (in basis points) of `amount`, at most all of it
+pub fn share(amount: u128, percent: u32) -> u128 {
+    (U256::from(amount) * percent.min(PERCENT_DENOMINATOR as u32) / PERCENT_DENOMINATOR).as_u128()
+}
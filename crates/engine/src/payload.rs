@@ -0,0 +1,262 @@
+//! Executor contract `swap`/`flashSwap` payloads
+//!
+//! Paths of up to 3 trades are packed into a u128, the contract uses lookup tables with mirrored enums and addresses
+//! (see `TradeExecutor.decode`/`decodeExt`). Bit layout, least significant first:
+//!
+//! | bits     | field                                                              |
+//! |----------|--------------------------------------------------------------------|
+//! | 0..24    | exchange Id per trade (8 bits)                                     |
+//! | 24..48   | token path a, b, c (8 bits), c is 255 (no token) for 2 trade paths |
+//! | 48..96   | fee per trade (16 bits, pips)                                      |
+//! | 96..102  | pool kind per trade (2 bits)                                       |
+//! | 102..128 | min. amount out, 20 bit mantissa then 6 bit exponent               |
+//!
+//! Executors predating pool kinds and the packed min. amount out ignore bits 96.. so the layout is backwards compatible
+use crate::{
+    encoder::EncodeError,
+    price_graph::CompositeTrade,
+    types::{Fee, PoolKind},
+};
+
+/// Token Id of the third token of 2 trade paths, maps to the 0 address
+const NO_TOKEN: u8 = 255;
+const KINDS_OFFSET: u32 = 96;
+const MIN_OUT_OFFSET: u32 = 102;
+const MANTISSA_BITS: u32 = 20;
+const EXPONENT_BITS: u32 = 6;
+/// Max. packed min. amount out
+pub const MAX_PACKED_AMOUNT: u128 = ((1 << MANTISSA_BITS) - 1) << ((1 << EXPONENT_BITS) - 1);
+
+/// An order of up to 3 trades as packed for the executor contract
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Payload {
+    /// Exchange Id per trade
+    pub exchanges: [u8; 3],
+    /// Token path, the last is `NO_TOKEN` for 2 trade paths
+    pub tokens: [u8; 3],
+    /// Fee per trade, packed as 16 bit pips (see `pack_fee`)
+    pub fees: [Fee; 3],
+    /// Pool kind per trade
+    pub pool_kinds: [PoolKind; 3],
+    /// Min. amount out, rounded to 20 significant bits (see `pack_amount`)
+    pub min_amount_out: u128,
+}
+
+impl Payload {
+    /// The payload of `trade` (at most 3 trades) returning at least `min_amount_out`
+    ///
+    /// `min_amount_out` is rounded down to 20 significant bits, the executor must still be passed the exact amount
+    pub fn new(trade: &CompositeTrade, min_amount_out: u128) -> Self {
+        let path = &trade.path;
+        Self {
+            exchanges: [
+                path[0].exchange_id,
+                path[1].exchange_id,
+                path[2].exchange_id,
+            ],
+            tokens: [
+                path[0].token_in,
+                path[0].token_out,
+                if path[0].token_in != path[1].token_out {
+                    path[1].token_out
+                } else {
+                    NO_TOKEN
+                },
+            ],
+            fees: [path[0].fee, path[1].fee, path[2].fee],
+            pool_kinds: [path[0].pool_kind, path[1].pool_kind, path[2].pool_kind],
+            min_amount_out: unpack_amount(pack_amount(min_amount_out)),
+        }
+    }
+    /// Pack the payload
+    ///
+    /// Errors if a fee doesn't fit its 16 bits
+    pub fn encode(&self) -> Result<u128, EncodeError> {
+        let mut payload = 0_u128;
+        for idx in 0..3 {
+            payload |= (self.exchanges[idx] as u128) << (8 * idx);
+            payload |= (self.tokens[idx] as u128) << (24 + 8 * idx);
+            payload |= (pack_fee(self.fees[idx])? as u128) << (48 + 16 * idx);
+            payload |= (self.pool_kinds[idx] as u128) << (KINDS_OFFSET as usize + 2 * idx);
+        }
+        Ok(payload | (pack_amount(self.min_amount_out) as u128) << MIN_OUT_OFFSET)
+    }
+    /// Unpack `payload`, `None` if it has an unknown pool kind
+    pub fn decode(payload: u128) -> Option<Self> {
+        let mut decoded = Self::default();
+        for idx in 0..3 {
+            decoded.exchanges[idx] = (payload >> (8 * idx)) as u8;
+            decoded.tokens[idx] = (payload >> (24 + 8 * idx)) as u8;
+            decoded.fees[idx] = Fee::from_pips(u32::from((payload >> (48 + 16 * idx)) as u16));
+            decoded.pool_kinds[idx] =
+                PoolKind::from_id((payload >> (KINDS_OFFSET as usize + 2 * idx)) as u8 & 0b11)?;
+        }
+        decoded.min_amount_out = unpack_amount((payload >> MIN_OUT_OFFSET) as u32);
+        Some(decoded)
+    }
+}
+
+/// Pack `fee` into 16 bits (pips) as the executor expects, errors on fees above 6.5535%
+pub fn pack_fee(fee: Fee) -> Result<u16, EncodeError> {
+    u16::try_from(fee.pips()).map_err(|_| EncodeError::FeeOverflow(fee))
+}
+
+/// Pack `amount` into 26 bits (20 bit mantissa, 6 bit exponent) rounding down, saturating at `MAX_PACKED_AMOUNT`
+pub fn pack_amount(amount: u128) -> u32 {
+    if amount >= MAX_PACKED_AMOUNT {
+        return (1 << (MANTISSA_BITS + EXPONENT_BITS)) - 1;
+    }
+    let exponent = (128 - amount.leading_zeros()).saturating_sub(MANTISSA_BITS);
+    (amount >> exponent) as u32 | exponent << MANTISSA_BITS
+}
+
+/// Unpack an amount packed by `pack_amount`
+pub fn unpack_amount(packed: u32) -> u128 {
+    let mantissa = packed & ((1 << MANTISSA_BITS) - 1);
+    let exponent = (packed >> MANTISSA_BITS) & ((1 << EXPONENT_BITS) - 1);
+    (mantissa as u128) << exponent
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{price_graph::Trade, types::Fee};
+
+    const POOL_KINDS: [PoolKind; 3] = [
+        PoolKind::ConstantProduct,
+        PoolKind::SolidlyStable,
+        PoolKind::ConcentratedLiquidity,
+    ];
+
+    #[test]
+    fn field_masks_partition_payload() {
+        // each field set to all ones covers exactly its bits
+        let masks = [
+            (0, 8),
+            (8, 8),
+            (16, 8),
+            (24, 8),
+            (32, 8),
+            (40, 8),
+            (48, 16),
+            (64, 16),
+            (80, 16),
+            (96, 2),
+            (98, 2),
+            (100, 2),
+            (102, 26),
+        ];
+        let full = |(offset, bits): (u32, u32)| ((1_u128 << bits) - 1) << offset;
+        let mut covered = 0_u128;
+        for (idx, mask) in masks.into_iter().enumerate() {
+            let mut payload = Payload::default();
+            match idx {
+                0..=2 => payload.exchanges[idx] = u8::MAX,
+                3..=5 => payload.tokens[idx - 3] = u8::MAX,
+                6..=8 => payload.fees[idx - 6] = Fee::from_pips(u16::MAX as u32),
+                // 0b11 is reserved, the widest kind tells the mask apart from its neighbours
+                9..=11 => payload.pool_kinds[idx - 9] = PoolKind::ConcentratedLiquidity,
+                _ => payload.min_amount_out = MAX_PACKED_AMOUNT,
+            }
+            let expected = if (9..=11).contains(&idx) {
+                (PoolKind::ConcentratedLiquidity as u128) << mask.0
+            } else {
+                full(mask)
+            };
+            assert_eq!(payload.encode().unwrap(), expected, "field {idx}");
+            assert_eq!(covered & full(mask), 0, "field {idx} overlaps");
+            covered |= full(mask);
+        }
+        assert_eq!(covered, u128::MAX);
+    }
+
+    #[test]
+    fn payload_round_trip() {
+        for value in 0..=u8::MAX {
+            for idx in 0..3 {
+                let mut payload = Payload::default();
+                payload.exchanges[idx] = value;
+                payload.tokens[(idx + 1) % 3] = value;
+                assert_eq!(Payload::decode(payload.encode().unwrap()), Some(payload));
+            }
+        }
+        for fee in 0..=u16::MAX {
+            let payload = Payload {
+                fees: [fee, fee.rotate_left(5), !fee].map(|fee| Fee::from_pips(fee as u32)),
+                ..Default::default()
+            };
+            assert_eq!(Payload::decode(payload.encode().unwrap()), Some(payload));
+        }
+        for a in POOL_KINDS {
+            for b in POOL_KINDS {
+                for c in POOL_KINDS {
+                    let payload = Payload {
+                        pool_kinds: [a, b, c],
+                        exchanges: [1, 2, 3],
+                        min_amount_out: MAX_PACKED_AMOUNT,
+                        ..Default::default()
+                    };
+                    assert_eq!(Payload::decode(payload.encode().unwrap()), Some(payload));
+                }
+            }
+        }
+        // reserved pool kind
+        assert_eq!(Payload::decode(0b11 << KINDS_OFFSET), None);
+    }
+
+    #[test]
+    fn fee_overflow() {
+        assert_eq!(pack_fee(Fee::from_pips(u16::MAX as u32)), Ok(u16::MAX));
+        let fee = Fee::from_pips(u16::MAX as u32 + 1);
+        assert_eq!(pack_fee(fee), Err(EncodeError::FeeOverflow(fee)));
+        let payload = Payload {
+            fees: [Fee::from_pips(500), fee, Fee::ZERO],
+            ..Default::default()
+        };
+        assert_eq!(payload.encode(), Err(EncodeError::FeeOverflow(fee)));
+    }
+
+    #[test]
+    fn packed_amount_round_trip() {
+        for exponent in 0..(1 << EXPONENT_BITS) {
+            for mantissa in [0, 1, 0x5_5555, 0xa_aaaa, (1 << MANTISSA_BITS) - 1] {
+                let packed = mantissa | exponent << MANTISSA_BITS;
+                let amount = unpack_amount(packed);
+                // normalized encodings round trip exactly
+                assert_eq!(unpack_amount(pack_amount(amount)), amount);
+                assert!(pack_amount(amount) < 1 << (MANTISSA_BITS + EXPONENT_BITS));
+            }
+        }
+        // small amounts are exact
+        for amount in 0..(1 << MANTISSA_BITS) {
+            assert_eq!(unpack_amount(pack_amount(amount)), amount);
+        }
+        // larger amounts round down by less than 2^-19
+        for amount in [10_010_000_u128, 3 * 10_u128.pow(18) + 7, u64::MAX as u128] {
+            let rounded = unpack_amount(pack_amount(amount));
+            assert!(rounded <= amount);
+            assert!(amount - rounded < amount >> (MANTISSA_BITS - 1));
+        }
+        assert_eq!(unpack_amount(pack_amount(u128::MAX)), MAX_PACKED_AMOUNT);
+    }
+
+    #[test]
+    fn payload_of_trade() {
+        let trade = CompositeTrade::new(&[
+            Trade::new(1, 2, Fee::from_pips(500), 0),
+            Trade::new(2, 1, Fee::ZERO, 9).with_pool_kind(PoolKind::SolidlyStable),
+        ]);
+        let payload = Payload::new(&trade, 10_010_000);
+        assert_eq!(payload.tokens, [1, 2, NO_TOKEN]);
+        assert_eq!(
+            payload.pool_kinds,
+            [
+                PoolKind::ConcentratedLiquidity,
+                PoolKind::SolidlyStable,
+                PoolKind::ConstantProduct
+            ]
+        );
+        assert_eq!(payload.min_amount_out, 10_010_000);
+        assert_eq!(Payload::decode(payload.encode().unwrap()), Some(payload));
+    }
+}
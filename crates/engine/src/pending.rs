@@ -0,0 +1,187 @@
+//! Our own pending orders
+//!
+//! Price graphs are synced from chain state which does not reflect our order txs until they are included.
+//! Pending orders are applied to each new price graph optimistically so simulations account for their price impact,
+//! orders which fail are rolled back i.e. no longer applied
+use log::debug;
+
+use crate::{
+    order::{Order, OrderStatus, OrderUpdate},
+    PriceGraph,
+};
+
+/// Max. blocks an order is applied without being reported included, its updates may have been dropped
+const MAX_PENDING_BLOCKS: u64 = 20;
+
+/// A submitted order awaiting inclusion
+#[derive(Debug)]
+struct PendingOrder {
+    order: Order,
+    /// Block the order tx was included in, if known
+    included: Option<u64>,
+}
+
+/// Tracks submitted orders until chain state reflects them
+#[derive(Debug, Default)]
+pub struct PendingOrders {
+    orders: Vec<PendingOrder>,
+}
+
+impl PendingOrders {
+    /// Track the progress of a submitted order
+    pub fn update(&mut self, update: OrderUpdate) {
+        let idx = self
+            .orders
+            .iter()
+            .position(|pending| pending.order == update.order);
+        match (update.status, idx) {
+            (OrderStatus::Sent, None) => self.orders.push(PendingOrder {
+                order: update.order,
+                included: None,
+            }),
+            (OrderStatus::Included(block_number), Some(idx)) => {
+                self.orders[idx].included = Some(block_number)
+            }
+            (OrderStatus::Failed, Some(idx)) => {
+                debug!("roll back order: {}", update.order.trade);
+                self.orders.remove(idx);
+            }
+            _ => {}
+        }
+    }
+    /// Apply pending orders to `price_graph`, orders already reflected in its chain state are dropped
+    ///
+    /// A price graph for block N is synced from the state after block N - 1
+    pub fn apply(&mut self, price_graph: &mut PriceGraph) {
+        let block_number = price_graph.block_number();
+        self.orders.retain(|pending| match pending.included {
+            Some(included) => block_number <= included,
+            None => block_number <= pending.order.block_number + MAX_PENDING_BLOCKS,
+        });
+        for pending in &self.orders {
+            if price_graph
+                .apply_trade(&pending.order.trade, pending.order.amount_in)
+                .is_none()
+            {
+                debug!("pending order missing edge: {}", pending.order.trade);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PendingOrders, MAX_PENDING_BLOCKS};
+    use crate::{
+        order::{Order, OrderStatus, OrderUpdate},
+        price_graph::{CompositeTrade, Edge, Trade},
+        types::{ExchangeId, Fee, Token},
+        PriceGraph,
+    };
+
+    fn graph(block_number: u64) -> PriceGraph {
+        let mut graph = PriceGraph::empty();
+        graph.reset(block_number);
+        graph.add_edge(
+            Token::USDC,
+            Token::WETH,
+            Edge::new_v2(
+                2_000_000_000000,
+                1_000 * 10_u128.pow(18),
+                Fee::from_units(30),
+                ExchangeId::Sushi,
+            ),
+        );
+        graph.add_edge(
+            Token::USDC,
+            Token::WETH,
+            Edge::new_v2(
+                2_010_000_000000,
+                1_000 * 10_u128.pow(18),
+                Fee::from_units(30),
+                ExchangeId::Chronos,
+            ),
+        );
+        graph
+    }
+
+    fn order(block_number: u64) -> Order {
+        Order {
+            amount_in: 1_000_000000,
+            amount_out: 1_001_000000,
+            trade: CompositeTrade::new(&[
+                Trade::new(
+                    Token::USDC.id(),
+                    Token::WETH.id(),
+                    Fee::from_units(30),
+                    ExchangeId::Sushi as u8,
+                ),
+                Trade::new(
+                    Token::WETH.id(),
+                    Token::USDC.id(),
+                    Fee::from_units(30),
+                    ExchangeId::Chronos as u8,
+                ),
+            ]),
+            block_number,
+            ..Default::default()
+        }
+    }
+
+    fn update(order: &Order, status: OrderStatus) -> OrderUpdate {
+        OrderUpdate {
+            order: order.clone(),
+            status,
+        }
+    }
+
+    #[test]
+    fn applies_orders_until_included() {
+        let mut pending = PendingOrders::default();
+        let order = order(10);
+        pending.update(update(&order, OrderStatus::Sent));
+        assert_eq!(pending.orders.len(), 1);
+
+        let mut applied = graph(11);
+        pending.apply(&mut applied);
+        assert!(applied.touched());
+        // the order's impact leaves its path less profitable
+        let mut expected = graph(11);
+        expected.apply_trade(&order.trade, order.amount_in).unwrap();
+        assert_eq!(
+            applied.amount_out(Token::USDC, Token::WETH, 1_000000),
+            expected.amount_out(Token::USDC, Token::WETH, 1_000000)
+        );
+        assert!(
+            applied.amount_out(Token::USDC, Token::WETH, 1_000000)
+                < graph(11).amount_out(Token::USDC, Token::WETH, 1_000000)
+        );
+
+        // included at block 12, state from block 13 reflects it
+        pending.update(update(&order, OrderStatus::Included(12)));
+        let mut at_12 = graph(12);
+        pending.apply(&mut at_12);
+        assert!(at_12.touched());
+        let mut at_13 = graph(13);
+        pending.apply(&mut at_13);
+        assert!(!at_13.touched());
+        assert!(pending.orders.is_empty());
+    }
+
+    #[test]
+    fn rolls_back_failed_orders() {
+        let mut pending = PendingOrders::default();
+        let failed = order(10);
+        let unreported = order(11);
+        pending.update(update(&failed, OrderStatus::Sent));
+        pending.update(update(&unreported, OrderStatus::Sent));
+        pending.update(update(&failed, OrderStatus::Failed));
+        assert_eq!(pending.orders.len(), 1);
+
+        // orders never reported included expire
+        let mut expired = graph(11 + MAX_PENDING_BLOCKS + 1);
+        pending.apply(&mut expired);
+        assert!(!expired.touched());
+        assert!(pending.orders.is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+//! Pre-signed order tx cache
+//!
+//! Order txs of recent orders are pre-signed between blocks at the next nonce and current fee, an order repeating one
+//! exactly (e.g. an arb re-found before it closes) is submitted without signing on the hot path.
+//! A signature covers the whole tx so any other order falls back to live signing, including a repeat whose gas limit
+//! changed since (the limit varies by path and with gas calibration)
+use std::collections::{HashMap, VecDeque};
+
+use ethers::types::{transaction::eip2718::TypedTransaction, Bytes, U256};
+
+/// Identifies a signed order tx, other fields are fixed by the order service
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct TemplateKey {
+    pub nonce: U256,
+    pub max_fee_per_gas: U256,
+    pub gas_limit: U256,
+    /// The executor contract call
+    pub calldata: Bytes,
+}
+
+impl TemplateKey {
+    /// The key of `tx`
+    pub fn of(tx: &TypedTransaction) -> Self {
+        Self {
+            nonce: tx.nonce().copied().unwrap_or_default(),
+            max_fee_per_gas: tx.gas_price().unwrap_or_default(),
+            gas_limit: tx.gas().copied().unwrap_or_default(),
+            calldata: tx.data().cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// Recent order txs and their pre-signed encodings
+pub(crate) struct PresignCache {
+    /// Unsigned txs of recent orders, most recent last
+    templates: VecDeque<TypedTransaction>,
+    /// Max. templates kept
+    capacity: usize,
+    /// RLP encoded signed txs, ready to submit
+    signed: HashMap<TemplateKey, Bytes>,
+}
+
+impl PresignCache {
+    /// A cache of the last `capacity` orders
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            templates: VecDeque::with_capacity(capacity),
+            capacity,
+            signed: HashMap::with_capacity(capacity),
+        }
+    }
+    /// Keep the tx of an order as a template, replacing the least recent
+    ///
+    /// A template with the same calldata is replaced, refreshing its gas limit
+    pub fn observe(&mut self, tx: &TypedTransaction) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.templates
+            .retain(|template| template.data() != tx.data());
+        if self.templates.len() == self.capacity {
+            self.templates.pop_front();
+        }
+        self.templates.push_back(tx.clone());
+    }
+    /// Templates not yet signed at `nonce` paying `max_fee_per_gas`, dropping signed txs for any other nonce or fee
+    pub fn unsigned(&mut self, nonce: U256, max_fee_per_gas: U256) -> Vec<TypedTransaction> {
+        self.signed
+            .retain(|key, _| key.nonce == nonce && key.max_fee_per_gas == max_fee_per_gas);
+        self.templates
+            .iter()
+            .rev()
+            .filter_map(|template| {
+                let mut tx = template.clone();
+                tx.set_nonce(nonce).set_gas_price(max_fee_per_gas);
+                (!self.signed.contains_key(&TemplateKey::of(&tx))).then_some(tx)
+            })
+            .collect()
+    }
+    /// Add the signed encoding of `tx`
+    pub fn insert(&mut self, tx: &TypedTransaction, signed_tx: Bytes) {
+        self.signed.insert(TemplateKey::of(tx), signed_tx);
+    }
+    /// Take the signed encoding of `tx` if it was pre-signed, each nonce is used once
+    pub fn take(&mut self, tx: &TypedTransaction) -> Option<Bytes> {
+        self.signed.remove(&TemplateKey::of(tx))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::types::{Address, Eip1559TransactionRequest};
+
+    use super::*;
+
+    fn order_tx(calldata: &'static [u8]) -> TypedTransaction {
+        Eip1559TransactionRequest::new()
+            .to(Address::repeat_byte(0x11))
+            .data(Bytes::from_static(calldata))
+            .into()
+    }
+
+    #[test]
+    fn presign_cache() {
+        let mut cache = PresignCache::new(2);
+        cache.observe(&order_tx(b"a"));
+        cache.observe(&order_tx(b"b"));
+        cache.observe(&order_tx(b"a"));
+        cache.observe(&order_tx(b"c"));
+
+        // "b" is least recent
+        let unsigned = cache.unsigned(U256::from(7_u64), U256::from(100_u64));
+        assert_eq!(unsigned.len(), 2);
+        assert_eq!(unsigned[0].data(), Some(&Bytes::from_static(b"c")));
+        assert_eq!(unsigned[1].data(), Some(&Bytes::from_static(b"a")));
+        for tx in unsigned.iter() {
+            cache.insert(tx, Bytes::from_static(b"signed"));
+        }
+        assert!(cache
+            .unsigned(U256::from(7_u64), U256::from(100_u64))
+            .is_empty());
+
+        let mut tx = order_tx(b"a");
+        tx.set_nonce(7_u64).set_gas_price(100_u64);
+        assert_eq!(cache.take(&tx), Some(Bytes::from_static(b"signed")));
+        assert_eq!(cache.take(&tx), None);
+        // a different fee misses
+        let mut tx = order_tx(b"c");
+        tx.set_nonce(7_u64).set_gas_price(101_u64);
+        assert_eq!(cache.take(&tx), None);
+        // a different gas limit misses e.g. after gas calibration
+        tx.set_gas_price(100_u64).set_gas(500_000_u64);
+        assert_eq!(cache.take(&tx), None);
+        tx.set_gas(U256::zero());
+
+        // the next nonce invalidates signed txs
+        assert_eq!(
+            cache.unsigned(U256::from(8_u64), U256::from(100_u64)).len(),
+            2
+        );
+        tx.set_nonce(7_u64).set_gas_price(100_u64);
+        assert_eq!(cache.take(&tx), None);
+    }
+}
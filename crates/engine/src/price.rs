@@ -1,7 +1,14 @@
 //! Price service provides queries for onchain token data
 
-use std::{ops::DerefMut, sync::Arc, time::Duration};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
+use async_trait::async_trait;
 use ethabi_static::{BytesZcp, DecodeStatic};
 use ethers::{
     prelude::abigen,
@@ -9,19 +16,31 @@ use ethers::{
     utils::serialize,
 };
 use ethers_providers::{Middleware, WsClientError};
+use futures::{stream::FuturesUnordered, StreamExt};
 use hex_literal::hex;
-use log::{debug, warn};
-use serde::Serialize;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
 use serde_json::{value::to_raw_value, Value};
 use thingbuf::mpsc::{Receiver, Sender};
+use tokio::{sync::watch, task::JoinHandle};
 
-use fulcrum_ws_cli::FastWsClient;
+use fulcrum_ws_cli::FastClient;
 
+#[cfg(feature = "metrics")]
+use crate::metrics;
 use crate::{
+    balancer::{self, BalancerWeightedBalances},
+    camelot_v2::CamelotV2Reserves,
+    curve::{self, CurveStableBalances},
+    kyber_elastic::KyberElasticState,
     price_graph::{Edge, PriceGraph},
-    types::Pair,
+    shutdown::ShutdownSignal,
+    solidly::SolidlyReserves,
+    supervisor::{supervise, Subsystem},
+    types::{Fee, Pair, Token},
     uniswap_v2::UniswapV2Reserves,
-    uniswap_v3::UniswapV3Slot0,
+    uniswap_v3::{TickLiquidity, UniswapV3Slot0, MAX_TICK, MIN_TICK},
+    util::saturating_u128,
 };
 
 #[cfg(target_os = "linux")]
@@ -36,24 +55,94 @@ abigen!(
     UniswapPoolViewer,
     r#"[
         function getPoolData(bytes calldata v3Pools, bytes calldata v2Pools) public view returns (bytes memory v3PoolData, bytes memory v2PoolData)
+        function getPoolDataWithTicks(bytes calldata v3Pools, bytes calldata v2Pools, uint8 wordRadius) public view returns (bytes memory v3PoolData, bytes memory v2PoolData, bytes memory v3TickData)
+        function getAllPoolData(bytes calldata v3Pools, bytes calldata v2Pools, bytes calldata camelotPools, bool withTicks, uint8 wordRadius) public view returns (bytes memory v3PoolData, bytes memory v2PoolData, bytes memory v3TickData, bytes memory camelotPoolData)
+        function getAllPoolDataWithBalancer(bytes calldata v3Pools, bytes calldata v2Pools, bytes calldata camelotPools, bytes calldata balancerPools, bool withTicks, uint8 wordRadius) public view returns (bytes memory v3PoolData, bytes memory v2PoolData, bytes memory v3TickData, bytes memory camelotPoolData, bytes memory balancerPoolData)
+        function getAllPoolDataWithCurve(bytes calldata v3Pools, bytes calldata v2Pools, bytes calldata camelotPools, bytes calldata balancerPools, bytes calldata curvePools, bool withTicks, uint8 wordRadius) public view returns (bytes memory v3PoolData, bytes memory v2PoolData, bytes memory v3TickData, bytes memory camelotPoolData, bytes memory balancerPoolData, bytes memory curvePoolData)
+        function getAllPoolDataWithSolidly(bytes calldata v3Pools, bytes calldata v2Pools, bytes calldata camelotPools, bytes calldata balancerPools, bytes calldata curvePools, bytes calldata solidlyPools, bool withTicks, uint8 wordRadius) public view returns (bytes memory v3PoolData, bytes memory v2PoolData, bytes memory v3TickData, bytes memory camelotPoolData, bytes memory balancerPoolData, bytes memory curvePoolData, bytes memory solidlyPoolData)
+        function getAllPoolDataWithKyberElastic(bytes calldata v3Pools, bytes calldata v2Pools, bytes calldata camelotPools, bytes calldata balancerPools, bytes calldata curvePools, bytes calldata solidlyPools, bytes calldata kyberElasticPools, bool withTicks, uint8 wordRadius) public view returns (bytes memory v3PoolData, bytes memory v2PoolData, bytes memory v3TickData, bytes memory camelotPoolData, bytes memory balancerPoolData, bytes memory curvePoolData, bytes memory solidlyPoolData, bytes memory kyberElasticPoolData)
     ]"#,
 );
 
+/// How the `PriceService` syncs pool states
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceSyncMode {
+    /// Query pool states on request, retrying until the node has the block
+    #[default]
+    Poll,
+    /// Pre-fetch pool states as the node announces new heads ('eth_subscribe' newHeads),
+    /// requests for blocks not pre-fetched in time are polled
+    NewHeads,
+    /// Query pool states on request, keeping a query for the next block in flight
+    /// i.e. a lagging node delays the next block's query less
+    Pipelined,
+}
+
+/// The result of a price sync request
+#[derive(Clone, Debug, Default)]
+pub struct PriceUpdate {
+    /// The requested block number
+    pub block_number: u64,
+    /// The synced price graph (possibly of a later block than requested), `None` if prices could not be synced
+    pub graph: Option<PriceGraph>,
+}
+
+/// Provides price graphs on request
+#[async_trait]
+pub trait PriceSource {
+    /// Get the current block number of the price source
+    async fn block_number(&self) -> u64;
+    /// Starts the price source
+    ///
+    /// Returns a handle for issuing price sync requests (by block number) and the queue of resulting price updates,
+    /// one per request
+    async fn start(&self) -> (Sender<u64>, Receiver<PriceUpdate>);
+}
+
+#[async_trait]
+impl<T: PriceSource + Send + Sync> PriceSource for Arc<T> {
+    async fn block_number(&self) -> u64 {
+        self.as_ref().block_number().await
+    }
+    async fn start(&self) -> (Sender<u64>, Receiver<PriceUpdate>) {
+        self.as_ref().start().await
+    }
+}
+
 /// Provides queries and aggregations over multiple price sources
 pub struct PriceService<M: Middleware + 'static> {
     /// Provider handle
     client: Arc<M>,
+    /// Additional providers queried alongside `client`
+    secondary_clients: Vec<Arc<M>>,
     /// Uniswap v3 pools
-    uniswap_v3_pairs: Vec<Pair>,
+    uniswap_v3_pairs: Vec<(Pair, Address)>,
     /// Uniswap v2 (style) pools
-    uniswap_v2_pairs: Vec<Pair>,
+    uniswap_v2_pairs: Vec<(Pair, Address)>,
+    /// Camelot v2 pools
+    camelot_v2_pairs: Vec<(Pair, Address)>,
+    /// Balancer weighted pools (one entry per token pair)
+    balancer_pairs: Vec<(Pair, Address)>,
+    /// Curve stable pools (one entry per token pair)
+    curve_pairs: Vec<(Pair, Address)>,
+    /// Solidly (ramses) pairs
+    solidly_pairs: Vec<(Pair, Address)>,
+    /// KyberSwap Elastic pools
+    kyber_elastic_pairs: Vec<(Pair, Address)>,
+    /// Sync initialized Uniswap v3 ticks within this many tick bitmap words of the current tick
+    tick_words: Option<u8>,
+    /// How pool states are synced
+    sync_mode: PriceSyncMode,
+    /// Delays shutdown until the sync task ends, taken on start
+    shutdown: Mutex<Option<ShutdownSignal>>,
     // prebuilt contract call params to avoid re-serialization in hot loop
     pool_data_call: Value,
 }
 
 impl<M> PriceService<M>
 where
-    M: Middleware<Provider = FastWsClient> + 'static,
+    M: Middleware<Provider = FastClient> + 'static,
     // <M as Middleware>::Provider: JsonRpcClient<Error = WsClientError>,
 {
     /// Create a new `PriceService`
@@ -63,82 +152,564 @@ where
         uniswap_v3_pairs: &[(Pair, Address)],
     ) -> PriceService<M> {
         // Pre-build all the contract calls for re-use on the hot-path
-        let pool_data_call = build_call(uniswap_v2_pairs, uniswap_v3_pairs, client.clone());
+        let pool_data_call = build_call(
+            uniswap_v2_pairs,
+            uniswap_v3_pairs,
+            &[],
+            &[],
+            &[],
+            &[],
+            &[],
+            None,
+            client.clone(),
+        );
 
         Self {
             client,
+            secondary_clients: Default::default(),
             pool_data_call,
-            uniswap_v2_pairs: uniswap_v2_pairs.iter().map(|x| x.0).collect(),
-            uniswap_v3_pairs: uniswap_v3_pairs.iter().map(|x| x.0).collect(),
+            tick_words: None,
+            sync_mode: Default::default(),
+            shutdown: Default::default(),
+            uniswap_v2_pairs: uniswap_v2_pairs.to_vec(),
+            uniswap_v3_pairs: uniswap_v3_pairs.to_vec(),
+            camelot_v2_pairs: Default::default(),
+            balancer_pairs: Default::default(),
+            curve_pairs: Default::default(),
+            solidly_pairs: Default::default(),
+            kyber_elastic_pairs: Default::default(),
         }
     }
-    /// Get the current block number of the price source
-    pub async fn block_number(&self) -> u64 {
+    /// Also sync the initialized ticks of Uniswap v3 pools within `word_radius` tick bitmap words of the current tick
+    /// Allows simulating v3 trades which cross ticks
+    pub fn with_ticks(mut self, word_radius: u8) -> Self {
+        self.tick_words = Some(word_radius);
+        self.rebuild_call();
+        self
+    }
+    /// Also query `clients` for pool states, prices are synced from the first provider to respond
+    /// and diverging responses are warned of e.g. a lagging node
+    pub fn with_secondary_sources(mut self, clients: &[Arc<M>]) -> Self {
+        self.secondary_clients = clients.to_vec();
+        self
+    }
+    /// Sync pool states with `sync_mode`
+    pub fn with_sync_mode(mut self, sync_mode: PriceSyncMode) -> Self {
+        self.sync_mode = sync_mode;
+        self
+    }
+    /// Delay `shutdown` until price requests stop e.g. the engine syncs the prices to save on shutdown
+    pub fn with_shutdown(mut self, shutdown: ShutdownSignal) -> Self {
+        self.shutdown = Mutex::new(Some(shutdown));
+        self
+    }
+    /// Also sync the given Camelot v2 pools (reserves, directional fees, and curve)
+    pub fn with_camelot_v2(mut self, camelot_v2_pairs: &[(Pair, Address)]) -> Self {
+        self.camelot_v2_pairs = camelot_v2_pairs.to_vec();
+        self.rebuild_call();
+        self
+    }
+    /// Also sync the given Balancer weighted pools (pair balances, weights, and swap fee)
+    pub fn with_balancer(mut self, balancer_pairs: &[(Pair, Address)]) -> Self {
+        self.balancer_pairs = balancer_pairs.to_vec();
+        self.rebuild_call();
+        self
+    }
+    /// Also sync the given Curve stable pools (pair balances, amplification coefficient, and fee)
+    pub fn with_curve(mut self, curve_pairs: &[(Pair, Address)]) -> Self {
+        self.curve_pairs = curve_pairs.to_vec();
+        self.rebuild_call();
+        self
+    }
+    /// Also sync the given Solidly pairs (reserves and curve, fees are as configured)
+    pub fn with_solidly(mut self, solidly_pairs: &[(Pair, Address)]) -> Self {
+        self.solidly_pairs = solidly_pairs.to_vec();
+        self.rebuild_call();
+        self
+    }
+    /// Also sync the given KyberSwap Elastic pools (price, base and reinvestment liquidity)
+    pub fn with_kyber_elastic(mut self, kyber_elastic_pairs: &[(Pair, Address)]) -> Self {
+        self.kyber_elastic_pairs = kyber_elastic_pairs.to_vec();
+        self.rebuild_call();
+        self
+    }
+    /// Drop pools with less than `min_tvl` (wei) value locked, as of the latest block
+    ///
+    /// Uniswap v3 (and KyberSwap Elastic) pools are valued by their in-range liquidity. Pools which can't be valued i.e. with no
+    /// token/WETH edge, are kept
+    pub async fn with_min_tvl(mut self, min_tvl: u128) -> Self {
+        let block_number = self.block_number().await;
+        let mut buffers = Buffers::new();
+        if let Err(err) = sync_prices(
+            &self.client,
+            block_number,
+            &self.pool_data_call,
+            &mut buffers,
+        )
+        .await
+        {
+            warn!("pool tvl (#{block_number}): {:?}", err);
+            return self;
+        }
+        let v2_pairs: Vec<Pair> = self.uniswap_v2_pairs.iter().map(|x| x.0).collect();
+        let v3_pairs: Vec<Pair> = self.uniswap_v3_pairs.iter().map(|x| x.0).collect();
+        let camelot_pairs: Vec<Pair> = self.camelot_v2_pairs.iter().map(|x| x.0).collect();
+        let balancer_pairs: Vec<Pair> = self.balancer_pairs.iter().map(|x| x.0).collect();
+        let curve_pairs: Vec<Pair> = self.curve_pairs.iter().map(|x| x.0).collect();
+        let solidly_pairs: Vec<Pair> = self.solidly_pairs.iter().map(|x| x.0).collect();
+        let kyber_elastic_pairs: Vec<Pair> = self.kyber_elastic_pairs.iter().map(|x| x.0).collect();
+        let mut graph = PriceGraph::empty();
+        bootstrap_price_graph(
+            &mut graph,
+            v2_pairs.as_slice(),
+            v3_pairs.as_slice(),
+            camelot_pairs.as_slice(),
+            balancer_pairs.as_slice(),
+            curve_pairs.as_slice(),
+            solidly_pairs.as_slice(),
+            kyber_elastic_pairs.as_slice(),
+            &buffers.pools,
+        );
+
+        let pools = &buffers.pools;
+        let keep = |(pair, address): &(Pair, Address), reserves: (u128, u128)| match pool_tvl(
+            &graph, pair, reserves,
+        ) {
+            Some(tvl) if tvl < min_tvl => {
+                warn!("drop pool {:?} {:?}, tvl: {}wei", address, pair, tvl);
+                false
+            }
+            Some(_) => true,
+            None => {
+                warn!("unpriced pool {:?} {:?}", address, pair);
+                true
+            }
+        };
+        self.uniswap_v2_pairs = self
+            .uniswap_v2_pairs
+            .iter()
+            .zip(pools.v2_reserves.iter())
+            .filter(|(pool, r)| keep(pool, (r.reserve_0, r.reserve_1)))
+            .map(|(pool, _)| *pool)
+            .collect();
+        self.uniswap_v3_pairs = self
+            .uniswap_v3_pairs
+            .iter()
+            .zip(pools.v3_slot0s.iter())
+            .filter(|(pool, slot0)| keep(pool, v3_virtual_reserves(slot0)))
+            .map(|(pool, _)| *pool)
+            .collect();
+        self.camelot_v2_pairs = self
+            .camelot_v2_pairs
+            .iter()
+            .zip(pools.camelot_v2_reserves.iter())
+            .filter(|(pool, r)| keep(pool, (r.reserve_0, r.reserve_1)))
+            .map(|(pool, _)| *pool)
+            .collect();
+        self.balancer_pairs = self
+            .balancer_pairs
+            .iter()
+            .zip(pools.balancer_balances.iter())
+            .filter(|(pool, b)| keep(pool, (b.balance_0, b.balance_1)))
+            .map(|(pool, _)| *pool)
+            .collect();
+        self.curve_pairs = self
+            .curve_pairs
+            .iter()
+            .zip(pools.curve_balances.iter())
+            .filter(|(pool, b)| keep(pool, (b.balance_0, b.balance_1)))
+            .map(|(pool, _)| *pool)
+            .collect();
+        self.solidly_pairs = self
+            .solidly_pairs
+            .iter()
+            .zip(pools.solidly_reserves.iter())
+            .filter(|(pool, r)| keep(pool, (r.reserve_0, r.reserve_1)))
+            .map(|(pool, _)| *pool)
+            .collect();
+        self.kyber_elastic_pairs = self
+            .kyber_elastic_pairs
+            .iter()
+            .zip(pools.kyber_elastic_states.iter())
+            .filter(|(pool, state)| {
+                keep(
+                    pool,
+                    v3_virtual_reserves(&UniswapV3Slot0 {
+                        sqrt_p_x96: state.sqrt_p_x96,
+                        liquidity: saturating_u128(state.liquidity()),
+                    }),
+                )
+            })
+            .map(|(pool, _)| *pool)
+            .collect();
+        self.rebuild_call();
+        self
+    }
+    /// Uniswap v3 pools synced by the service
+    pub fn uniswap_v3_pairs(&self) -> &[(Pair, Address)] {
+        self.uniswap_v3_pairs.as_slice()
+    }
+    /// Uniswap v2 (style) pools synced by the service
+    pub fn uniswap_v2_pairs(&self) -> &[(Pair, Address)] {
+        self.uniswap_v2_pairs.as_slice()
+    }
+    /// Camelot v2 pools synced by the service
+    pub fn camelot_v2_pairs(&self) -> &[(Pair, Address)] {
+        self.camelot_v2_pairs.as_slice()
+    }
+    /// Balancer weighted pools synced by the service
+    pub fn balancer_pairs(&self) -> &[(Pair, Address)] {
+        self.balancer_pairs.as_slice()
+    }
+    /// Curve stable pools synced by the service
+    pub fn curve_pairs(&self) -> &[(Pair, Address)] {
+        self.curve_pairs.as_slice()
+    }
+    /// Solidly pairs synced by the service
+    pub fn solidly_pairs(&self) -> &[(Pair, Address)] {
+        self.solidly_pairs.as_slice()
+    }
+    /// KyberSwap Elastic pools synced by the service
+    pub fn kyber_elastic_pairs(&self) -> &[(Pair, Address)] {
+        self.kyber_elastic_pairs.as_slice()
+    }
+    fn rebuild_call(&mut self) {
+        self.pool_data_call = build_call(
+            self.uniswap_v2_pairs.as_slice(),
+            self.uniswap_v3_pairs.as_slice(),
+            self.camelot_v2_pairs.as_slice(),
+            self.balancer_pairs.as_slice(),
+            self.curve_pairs.as_slice(),
+            self.solidly_pairs.as_slice(),
+            self.kyber_elastic_pairs.as_slice(),
+            self.tick_words,
+            self.client.clone(),
+        );
+    }
+}
+
+#[async_trait]
+impl<M> PriceSource for PriceService<M>
+where
+    M: Middleware<Provider = FastClient> + 'static,
+{
+    async fn block_number(&self) -> u64 {
         self.client
             .get_block_number()
             .await
             .unwrap_or_default()
             .as_u64()
     }
-    /// Starts the price service
-    ///
-    /// Returns a handle for issuing price sync requests
-    pub async fn start(&self) -> (Sender<u64>, Receiver<Option<PriceGraph>>) {
+    async fn start(&self) -> (Sender<u64>, Receiver<PriceUpdate>) {
         let (price_sync_tx, price_sync_rx) = thingbuf::mpsc::channel(5);
         let (price_queue_tx, price_queue_rx) = thingbuf::mpsc::channel(5);
 
+        // the primary source first
+        let clients: Arc<[Arc<M>]> = std::iter::once(&self.client)
+            .chain(self.secondary_clients.iter())
+            .cloned()
+            .collect();
+        let heads = match self.sync_mode {
+            PriceSyncMode::Poll | PriceSyncMode::Pipelined => None,
+            PriceSyncMode::NewHeads => {
+                let (heads_tx, heads_rx) = watch::channel((0, PoolStates::default()));
+                tokio::spawn(prefetch_heads(
+                    Arc::clone(&self.client),
+                    self.pool_data_call.clone(),
+                    heads_tx,
+                ));
+                Some(heads_rx)
+            }
+        };
+        let sync = Arc::new(SyncLoop {
+            clients,
+            serialized_call_params: Arc::new(self.pool_data_call.clone()),
+            pipelined: self.sync_mode == PriceSyncMode::Pipelined,
+            v2_pairs: self.uniswap_v2_pairs.iter().map(|x| x.0).collect(),
+            v3_pairs: self.uniswap_v3_pairs.iter().map(|x| x.0).collect(),
+            camelot_pairs: self.camelot_v2_pairs.iter().map(|x| x.0).collect(),
+            balancer_pairs: self.balancer_pairs.iter().map(|x| x.0).collect(),
+            curve_pairs: self.curve_pairs.iter().map(|x| x.0).collect(),
+            solidly_pairs: self.solidly_pairs.iter().map(|x| x.0).collect(),
+            kyber_elastic_pairs: self.kyber_elastic_pairs.iter().map(|x| x.0).collect(),
+            requests: price_sync_rx,
+            updates: price_queue_tx,
+            syncing: AtomicU64::new(0),
+        });
+
+        let shutdown = self.shutdown.lock().expect("shutdown lock").take();
+        tokio::spawn(async move {
+            // held until price requests stop
+            let _shutdown = shutdown;
+            supervise(Subsystem::PriceSync, || {
+                Arc::clone(&sync).run(heads.clone())
+            })
+            .await;
+        });
+
+        (price_sync_tx, price_queue_rx)
+    }
+}
+
+/// The price sync task, restarted from here if it panics
+struct SyncLoop<M> {
+    /// Price sources, the primary first
+    clients: Arc<[Arc<M>]>,
+    serialized_call_params: Arc<Value>,
+    /// Sync with `PriceSyncMode::Pipelined`
+    pipelined: bool,
+    v2_pairs: Vec<Pair>,
+    v3_pairs: Vec<Pair>,
+    camelot_pairs: Vec<Pair>,
+    balancer_pairs: Vec<Pair>,
+    curve_pairs: Vec<Pair>,
+    solidly_pairs: Vec<Pair>,
+    kyber_elastic_pairs: Vec<Pair>,
+    /// Blocks to sync prices at
+    requests: Receiver<u64>,
+    /// Synced prices
+    updates: Sender<PriceUpdate>,
+    /// The block being synced, `0` if none
+    syncing: AtomicU64,
+}
+
+impl<M> SyncLoop<M>
+where
+    M: Middleware<Provider = FastClient> + 'static,
+{
+    /// Sync prices at the requested blocks until requests or updates stop
+    ///
+    /// `heads` are the states pre-fetched for `PriceSyncMode::NewHeads`
+    async fn run(self: Arc<Self>, mut heads: Option<watch::Receiver<(u64, PoolStates)>>) {
+        // restarted after a panic, the engine awaits an update for the block being synced
+        let interrupted = self.syncing.swap(0, Ordering::Relaxed);
+        if interrupted > 0 {
+            let Ok(mut price_update_ref) = self.updates.send_ref().await else {
+                return;
+            };
+            price_update_ref.block_number = interrupted;
+            price_update_ref.graph = None;
+        }
         let mut buffers = Buffers::new();
-        let client = Arc::clone(&self.client);
-        let serialized_call_params = self.pool_data_call.clone();
-        let v2_pairs = self.uniswap_v2_pairs.clone();
-        let v3_pairs = self.uniswap_v3_pairs.clone();
-
-        tokio::spawn({
-            async move {
-                while let Some(target_block) = price_sync_rx.recv().await {
-                    buffers.reset();
-                    if let Err(err) =
-                        sync_prices(&client, target_block, &serialized_call_params, &mut buffers)
-                            .await
-                    {
-                        warn!("price fetch (#{target_block}): {:?}", err);
-                        let mut price_graph_ref =
-                            price_queue_tx.send_ref().await.expect("capacity");
-                        *price_graph_ref = Option::<PriceGraph>::None;
-                    } else {
-                        let mut price_graph_opt_ref =
-                            price_queue_tx.send_ref().await.expect("capacity");
-                        let price_graph_opt = DerefMut::deref_mut(&mut price_graph_opt_ref);
-                        match price_graph_opt {
-                            Some(p) => {
-                                p.reset(target_block);
-                                bootstrap_price_graph(
-                                    p,
-                                    v2_pairs.as_slice(),
-                                    v3_pairs.as_slice(),
-                                    &buffers.v2_reserves,
-                                    &buffers.v3_slot0s,
-                                );
-                            }
-                            None => {
-                                let mut p = PriceGraph::empty();
-                                bootstrap_price_graph(
-                                    &mut p,
-                                    v2_pairs.as_slice(),
-                                    v3_pairs.as_slice(),
-                                    &buffers.v2_reserves,
-                                    &buffers.v3_slot0s,
-                                );
-                                *price_graph_opt_ref = Some(p);
-                            }
-                        }
+        let mut pipeline = self.pipelined.then(Pipeline::default);
+        while let Some(target_block) = self.requests.recv().await {
+            self.syncing.store(target_block, Ordering::Relaxed);
+            buffers.reset();
+            #[cfg(feature = "metrics")]
+            let t0 = std::time::Instant::now();
+            let prefetched = match heads.as_mut() {
+                Some(heads) => await_prefetched(heads, target_block).await,
+                None => false,
+            };
+            let result = if prefetched {
+                Ok(())
+            } else if let Some(pipeline) = pipeline.as_mut() {
+                pipeline
+                    .sync(
+                        &self.clients,
+                        target_block,
+                        &self.serialized_call_params,
+                        &mut buffers,
+                    )
+                    .await
+            } else {
+                sync_prices_from(
+                    &self.clients,
+                    target_block,
+                    &self.serialized_call_params,
+                    &mut buffers,
+                )
+                .await
+            };
+            #[cfg(feature = "metrics")]
+            metrics::PRICE_SYNC.observe(t0.elapsed());
+            // the engine stopped
+            let Ok(mut price_update_ref) = self.updates.send_ref().await else {
+                return;
+            };
+            if let Err(err) = result {
+                #[cfg(feature = "metrics")]
+                metrics::PRICE_SYNC_ERRORS.inc();
+                warn!("price fetch (#{target_block}): {:?}", err);
+                price_update_ref.block_number = target_block;
+                price_update_ref.graph = None;
+            } else {
+                // the update is sent even if building the graph panics, until built it reads as stale
+                price_update_ref.block_number = 0;
+                // pre-fetched states may be of a later block than requested
+                let head = heads.as_ref().filter(|_| prefetched).map(|h| h.borrow());
+                let (block_number, pools) = match head.as_ref() {
+                    Some(head) => (head.0, &head.1),
+                    None => (target_block, &buffers.pools),
+                };
+                match price_update_ref.graph.as_mut() {
+                    Some(p) => {
+                        p.reset(block_number);
+                        self.bootstrap(p, pools);
+                    }
+                    None => {
+                        let mut p = PriceGraph::empty();
+                        p.set_block_number(block_number);
+                        self.bootstrap(&mut p, pools);
+                        price_update_ref.graph = Some(p);
                     }
                 }
+                price_update_ref.block_number = target_block;
             }
-        });
+            self.syncing.store(0, Ordering::Relaxed);
+        }
+    }
+    /// Add the synced `pools` to `price_graph`
+    fn bootstrap(&self, price_graph: &mut PriceGraph, pools: &PoolStates) {
+        bootstrap_price_graph(
+            price_graph,
+            self.v2_pairs.as_slice(),
+            self.v3_pairs.as_slice(),
+            self.camelot_pairs.as_slice(),
+            self.balancer_pairs.as_slice(),
+            self.curve_pairs.as_slice(),
+            self.solidly_pairs.as_slice(),
+            self.kyber_elastic_pairs.as_slice(),
+            pools,
+        );
+    }
+}
 
-        (price_sync_tx, price_queue_rx)
+/// A pool states sync task, returning its buffers
+type SyncTask = JoinHandle<(Result<(), WsClientError>, Buffers)>;
+
+/// Pool state syncs in flight for `PriceSyncMode::Pipelined`
+#[derive(Default)]
+struct Pipeline {
+    /// The in-flight sync of the block after the last requested (block number, task)
+    next: Option<(u64, SyncTask)>,
+    /// Buffers of completed syncs for re-use
+    spare: Vec<Buffers>,
+}
+
+impl Pipeline {
+    /// Sync pool states at block `at` into `buffers`, starting a sync of block `at + 1`
+    ///
+    /// A sync of `at` already in flight is awaited rather than re-queried, if it failed e.g. it was started before the node
+    /// had the block, `at` is queried again
+    async fn sync<M>(
+        &mut self,
+        clients: &Arc<[Arc<M>]>,
+        at: u64,
+        serialized_call_params: &Arc<Value>,
+        buffers: &mut Buffers,
+    ) -> Result<(), WsClientError>
+    where
+        M: Middleware<Provider = FastClient> + 'static,
+    {
+        let current = match self.next.take() {
+            Some((block_number, task)) if block_number == at => task,
+            stale => {
+                // the requests skipped a block
+                if let Some((_, task)) = stale {
+                    task.abort();
+                }
+                self.spawn(clients, at, serialized_call_params)
+            }
+        };
+        self.next = Some((at + 1, self.spawn(clients, at + 1, serialized_call_params)));
+
+        match current.await {
+            Ok((Ok(()), mut synced)) => {
+                std::mem::swap(buffers, &mut synced);
+                self.spare.push(synced);
+                Ok(())
+            }
+            Ok((Err(err), synced)) => {
+                debug!("pipelined price fetch (#{at}): {:?}", err);
+                self.spare.push(synced);
+                sync_prices_from(clients, at, serialized_call_params, buffers).await
+            }
+            // the sync task panicked
+            Err(_) => sync_prices_from(clients, at, serialized_call_params, buffers).await,
+        }
+    }
+    /// Spawn a sync of pool states at block `at`
+    fn spawn<M>(
+        &mut self,
+        clients: &Arc<[Arc<M>]>,
+        at: u64,
+        serialized_call_params: &Arc<Value>,
+    ) -> SyncTask
+    where
+        M: Middleware<Provider = FastClient> + 'static,
+    {
+        let mut buffers = self.spare.pop().unwrap_or_else(Buffers::new);
+        buffers.reset();
+        let clients = Arc::clone(clients);
+        let serialized_call_params = Arc::clone(serialized_call_params);
+        tokio::spawn(async move {
+            let result =
+                sync_prices_from(&clients, at, &serialized_call_params, &mut buffers).await;
+            (result, buffers)
+        })
+    }
+}
+
+/// Pre-fetch pool states at every new head of the node, publishing them to `heads`
+///
+/// Returns if the subscription ends or `heads` has no receivers, requests are then polled
+async fn prefetch_heads<M>(
+    client: Arc<M>,
+    serialized_call_params: Value,
+    heads: watch::Sender<(u64, PoolStates)>,
+) where
+    M: Middleware<Provider = FastClient> + 'static,
+{
+    let mut blocks = match client.provider().subscribe_blocks().await {
+        Ok(blocks) => blocks,
+        Err(err) => {
+            warn!("newHeads subscription: {:?}", err);
+            return;
+        }
+    };
+    info!("price sync on new heads 🗣️");
+    let mut buffers = Buffers::new();
+    while let Some(block) = blocks.next().await {
+        let block_number = match block.number {
+            Some(block_number) => block_number.as_u64(),
+            None => continue,
+        };
+        buffers.reset();
+        match sync_prices(&client, block_number, &serialized_call_params, &mut buffers).await {
+            // swap the buffers, re-using the previous states' allocations
+            Ok(_) => heads.send_modify(|(head, pools)| {
+                *head = block_number;
+                std::mem::swap(pools, &mut buffers.pools);
+            }),
+            Err(err) => warn!("price prefetch (#{block_number}): {:?}", err),
+        }
+        if heads.is_closed() {
+            return;
+        }
     }
+    warn!("newHeads subscription ended");
+}
+
+/// Await pool states pre-fetched at `block_number` or later, for up to `QUERY_DEADLINE`
+///
+/// Returns false if none were pre-fetched in time
+async fn await_prefetched(
+    heads: &mut watch::Receiver<(u64, PoolStates)>,
+    block_number: u64,
+) -> bool {
+    let prefetched = async {
+        while heads.borrow_and_update().0 < block_number {
+            if heads.changed().await.is_err() {
+                // pre-fetching stopped
+                return false;
+            }
+        }
+        true
+    };
+    tokio::time::timeout(QUERY_DEADLINE, prefetched)
+        .await
+        .unwrap_or_default()
 }
 
 /// Fetch latest available prices/metadata from all sources
@@ -150,7 +721,112 @@ async fn sync_prices<M>(
     buffers: &mut Buffers,
 ) -> Result<(), WsClientError>
 where
-    M: Middleware<Provider = FastWsClient> + 'static,
+    M: Middleware<Provider = FastClient> + 'static,
+{
+    query_pools(client, at, serialized_call_params, &mut buffers.return_data).await?;
+    buffers.pools.decode(buffers.return_data.as_slice());
+
+    Ok(())
+}
+
+/// Sync prices at block `at` from `clients`, racing them if there are secondary sources
+async fn sync_prices_from<M>(
+    clients: &[Arc<M>],
+    at: u64,
+    serialized_call_params: &Arc<Value>,
+    buffers: &mut Buffers,
+) -> Result<(), WsClientError>
+where
+    M: Middleware<Provider = FastClient> + 'static,
+{
+    match clients {
+        [client] => sync_prices(client, at, serialized_call_params, buffers).await,
+        _ => sync_prices_any(clients, at, serialized_call_params, buffers).await,
+    }
+}
+
+/// Query pool states at block `at` from every source concurrently, syncing prices from the first to respond
+///
+/// The remaining responses are compared in the background, warning of any source diverging from the first e.g. a
+/// lagging or faulty node
+async fn sync_prices_any<M>(
+    clients: &[Arc<M>],
+    at: u64,
+    serialized_call_params: &Arc<Value>,
+    buffers: &mut Buffers,
+) -> Result<(), WsClientError>
+where
+    M: Middleware<Provider = FastClient> + 'static,
+{
+    let mut queries: FuturesUnordered<_> = clients
+        .iter()
+        .enumerate()
+        .map(|(source, client)| {
+            let client = Arc::clone(client);
+            let serialized_call_params = Arc::clone(serialized_call_params);
+            tokio::spawn(async move {
+                let mut return_data = Vec::with_capacity(2048);
+                let result = query_pools(&client, at, &serialized_call_params, &mut return_data)
+                    .await
+                    .map(|_| return_data);
+                (source, result)
+            })
+        })
+        .collect();
+
+    let mut last_err = None;
+    while let Some(joined) = queries.next().await {
+        let Ok((source, result)) = joined else {
+            continue;
+        };
+        match result {
+            Ok(return_data) => {
+                buffers
+                    .return_data
+                    .extend_from_slice(return_data.as_slice());
+                buffers.pools.decode(buffers.return_data.as_slice());
+                tokio::spawn(compare_sources(at, source, return_data, queries));
+                return Ok(());
+            }
+            Err(err) => {
+                warn!("price source {source} (#{at}): {:?}", err);
+                last_err = Some(err);
+            }
+        }
+    }
+
+    Err(last_err.unwrap_or(WsClientError::TooManyReconnects)) // TODO: proper error
+}
+
+/// Warn of pool states from the remaining `queries` which differ from `expected`, the response of source `source`
+async fn compare_sources(
+    at: u64,
+    source: usize,
+    expected: Vec<u8>,
+    mut queries: FuturesUnordered<JoinHandle<(usize, Result<Vec<u8>, WsClientError>)>>,
+) {
+    while let Some(joined) = queries.next().await {
+        match joined {
+            Ok((other, Ok(return_data))) if return_data != expected => {
+                #[cfg(feature = "metrics")]
+                metrics::PRICE_SOURCE_DIVERGENCES.inc();
+                warn!("price source {other} diverges from source {source} (#{at})");
+            }
+            Ok((other, Err(err))) => debug!("price source {other} (#{at}): {:?}", err),
+            _ => {}
+        }
+    }
+}
+
+/// Query the pool states at block `at`, writing the (de-hexed) viewer call result to `return_data`
+async fn query_pools<M>(
+    client: &Arc<M>,
+    at: u64,
+    serialized_call_params: &Value,
+    return_data: &mut Vec<u8>,
+) -> Result<(), WsClientError>
+where
+    M: Middleware<Provider = FastClient> + 'static,
 {
     let target_block = serialize(&BlockId::Number(at.into()));
     let serialized_call_params_with_block =
@@ -159,14 +835,12 @@ where
     // returns the Ethereum RLP encoded bytes (de-hexed)
     // allow 2 attempts
 
-    // TODO: this is racey and can fail
-    // - ideas: query multiple sources
-    // - use subscription/push approach (needs fast local node)
+    // TODO: this is racey and can fail, `PriceSyncMode::NewHeads` or secondary sources avoid it
     for _attempt in 1..=2_u32 {
         let result = client
             .provider()
             .as_ref()
-            .eth_call(&serialized_call_params_with_block, &mut buffers.return_data)
+            .eth_call(&serialized_call_params_with_block, return_data)
             .await;
         match result {
             Ok(_) => break,
@@ -182,25 +856,23 @@ where
             Err(err) => return Err(err),
         }
     }
-    if buffers.return_data.is_empty() {
+    if return_data.is_empty() {
         return Err(WsClientError::TooManyReconnects); // TODO: proper error
     }
 
-    decode_pools_data(
-        buffers.return_data.as_slice(),
-        &mut buffers.v3_slot0s,
-        &mut buffers.v2_reserves,
-    );
-
     Ok(())
 }
 /// bootstrap a price graph instance using the given price information
-fn bootstrap_price_graph(
+pub(crate) fn bootstrap_price_graph(
     price_graph: &mut PriceGraph,
     v2_pairs: &[Pair],
     v3_pairs: &[Pair],
-    v2_reserves: &[UniswapV2Reserves],
-    v3_slots: &[UniswapV3Slot0],
+    camelot_v2_pairs: &[Pair],
+    balancer_pairs: &[Pair],
+    curve_pairs: &[Pair],
+    solidly_pairs: &[Pair],
+    kyber_elastic_pairs: &[Pair],
+    pools: &PoolStates,
 ) {
     // calculate price heuristics for all v2 sources (query onchain reserves and calculate offline)
     for (
@@ -214,7 +886,7 @@ fn bootstrap_price_graph(
             reserve_0,
             reserve_1,
         },
-    ) in v2_pairs.iter().zip(v2_reserves.iter())
+    ) in v2_pairs.iter().zip(pools.v2_reserves.iter())
     {
         let edge = Edge::new_v2(*reserve_0, *reserve_1, *fee, *exchange_id);
         price_graph.add_edge(*token0, *token1, edge);
@@ -232,16 +904,221 @@ fn bootstrap_price_graph(
             sqrt_p_x96,
             liquidity,
         },
-    ) in v3_pairs.iter().zip(v3_slots.iter())
+    ) in v3_pairs.iter().zip(pools.v3_slot0s.iter())
     {
         let edge = Edge::new_v3(*sqrt_p_x96, (*liquidity).into(), *fee, true);
         price_graph.add_edge(*token0, *token1, edge);
     }
+
+    // initialized ticks are optional (ordered as `v3_pairs`)
+    for (pair, ticks) in v3_pairs.iter().zip(pools.v3_ticks.iter()) {
+        price_graph.set_ticks(pair.token0, pair.token1, pair.fee, ticks.clone());
+    }
+
+    // camelot v2 pairs have directional fees and possibly the stable swap curve
+    for (
+        Pair { token0, token1, .. },
+        CamelotV2Reserves {
+            reserve_0,
+            reserve_1,
+            token_0_fee,
+            token_1_fee,
+            stable,
+        },
+    ) in camelot_v2_pairs
+        .iter()
+        .zip(pools.camelot_v2_reserves.iter())
+    {
+        let edge = Edge::new_camelot_v2(
+            *reserve_0,
+            *reserve_1,
+            Fee::from_units(*token_0_fee as u32),
+            Fee::from_units(*token_1_fee as u32),
+            stable.then(|| (token0.decimals(), token1.decimals())),
+        );
+        price_graph.add_edge(*token0, *token1, edge);
+    }
+
+    // balancer weighted pools are synced per token pair
+    for (
+        Pair { token0, token1, .. },
+        BalancerWeightedBalances {
+            balance_0,
+            balance_1,
+            weight_0,
+            weight_1,
+            fee,
+        },
+    ) in balancer_pairs.iter().zip(pools.balancer_balances.iter())
+    {
+        let edge = Edge::new_balancer_weighted(
+            (*balance_0, *balance_1),
+            (*weight_0, *weight_1),
+            Fee::from_fraction(*fee as u128, balancer::ONE),
+        );
+        price_graph.add_edge(*token0, *token1, edge);
+    }
+
+    // curve stable pools are synced per token pair
+    for (
+        Pair { token0, token1, .. },
+        CurveStableBalances {
+            balance_0,
+            balance_1,
+            amp,
+            fee,
+        },
+    ) in curve_pairs.iter().zip(pools.curve_balances.iter())
+    {
+        let edge = Edge::new_curve_stable(
+            (*balance_0, *balance_1),
+            *amp,
+            Fee::from_fraction(*fee as u128, curve::FEE_DENOMINATOR),
+            (token0.decimals(), token1.decimals()),
+        );
+        price_graph.add_edge(*token0, *token1, edge);
+    }
+
+    // solidly pairs charge the configured fee on either token, possibly with the stable swap curve
+    for (
+        Pair {
+            token0,
+            token1,
+            fee,
+            ..
+        },
+        SolidlyReserves {
+            reserve_0,
+            reserve_1,
+            stable,
+        },
+    ) in solidly_pairs.iter().zip(pools.solidly_reserves.iter())
+    {
+        let edge = Edge::new_solidly(
+            *reserve_0,
+            *reserve_1,
+            *fee,
+            stable.then(|| (token0.decimals(), token1.decimals())),
+        );
+        price_graph.add_edge(*token0, *token1, edge);
+    }
+
+    // kyberswap elastic pools price with the base + reinvestment liquidity
+    for (
+        Pair {
+            token0,
+            token1,
+            fee,
+            ..
+        },
+        state,
+    ) in kyber_elastic_pairs
+        .iter()
+        .zip(pools.kyber_elastic_states.iter())
+    {
+        let edge = Edge::new_kyber_elastic(state.sqrt_p_x96, state.liquidity(), *fee, true);
+        price_graph.add_edge(*token0, *token1, edge);
+    }
+}
+
+/// The value (wei) of `pair`'s `reserves`, priced with the best token/WETH edges of `graph`
+/// Returns `None` if either token can't be priced in WETH
+fn pool_tvl(graph: &PriceGraph, pair: &Pair, (reserve_0, reserve_1): (u128, u128)) -> Option<u128> {
+    Some(
+        value_in_weth(graph, pair.token0, reserve_0)?.saturating_add(value_in_weth(
+            graph,
+            pair.token1,
+            reserve_1,
+        )?),
+    )
+}
+
+/// The value (wei) of `amount` of `token`, priced with the best token/WETH edge of `graph`
+pub(crate) fn value_in_weth(graph: &PriceGraph, token: Token, amount: u128) -> Option<u128> {
+    if token == Token::WETH {
+        return Some(amount);
+    }
+    // quote a small amount to limit price impact
+    let probe = (token.one() / 100).max(1);
+    let probe_out = graph.amount_out(token, Token::WETH, probe)?;
+    let value = U256::from(amount) * U256::from(probe_out) / U256::from(probe);
+    Some(value.min(U256::from(u128::MAX)).as_u128())
+}
+
+/// The virtual reserves of a Uniswap v3 pool i.e. its liquidity in range of the current price
+fn v3_virtual_reserves(slot0: &UniswapV3Slot0) -> (u128, u128) {
+    if slot0.sqrt_p_x96.is_zero() {
+        return (0, 0);
+    }
+    let liquidity = U256::from(slot0.liquidity);
+    // x = L / √P, y = L * √P
+    let reserve_0 = (liquidity << 96) / slot0.sqrt_p_x96;
+    let reserve_1 = (liquidity * (slot0.sqrt_p_x96 >> 32)) >> 64;
+    let saturate = |x: U256| x.min(U256::from(u128::MAX)).as_u128();
+    (saturate(reserve_0), saturate(reserve_1))
+}
+
+/// Decoded pool states of a price query
+#[derive(Default)]
+pub(crate) struct PoolStates {
+    pub v2_reserves: Vec<UniswapV2Reserves>,
+    pub v3_slot0s: Vec<UniswapV3Slot0>,
+    pub v3_ticks: Vec<TickLiquidity>,
+    pub camelot_v2_reserves: Vec<CamelotV2Reserves>,
+    pub balancer_balances: Vec<BalancerWeightedBalances>,
+    pub curve_balances: Vec<CurveStableBalances>,
+    pub solidly_reserves: Vec<SolidlyReserves>,
+    pub kyber_elastic_states: Vec<KyberElasticState>,
+}
+
+impl PoolStates {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            v2_reserves: Vec::with_capacity(capacity),
+            v3_slot0s: Vec::with_capacity(capacity),
+            v3_ticks: Vec::with_capacity(capacity),
+            camelot_v2_reserves: Vec::with_capacity(capacity),
+            balancer_balances: Vec::with_capacity(capacity),
+            curve_balances: Vec::with_capacity(capacity),
+            solidly_reserves: Vec::with_capacity(capacity),
+            kyber_elastic_states: Vec::with_capacity(capacity),
+        }
+    }
+    /// Decode the pool viewer `raw_pool_data`, appending to the current states
+    pub fn decode(&mut self, raw_pool_data: &[u8]) {
+        decode_pools_data(raw_pool_data, &mut self.v3_slot0s, &mut self.v2_reserves);
+        decode_pool_ticks(raw_pool_data, &mut self.v3_ticks);
+        decode_camelot_v2_reserves(raw_pool_data, &mut self.camelot_v2_reserves);
+        decode_balancer_balances(raw_pool_data, &mut self.balancer_balances);
+        decode_curve_balances(raw_pool_data, &mut self.curve_balances);
+        decode_solidly_reserves(raw_pool_data, &mut self.solidly_reserves);
+        decode_kyber_elastic_states(raw_pool_data, &mut self.kyber_elastic_states);
+    }
+    /// Clear all pool states
+    pub fn clear(&mut self) {
+        self.v2_reserves.clear();
+        self.v3_slot0s.clear();
+        self.v3_ticks.clear();
+        self.camelot_v2_reserves.clear();
+        self.balancer_balances.clear();
+        self.curve_balances.clear();
+        self.solidly_reserves.clear();
+        self.kyber_elastic_states.clear();
+    }
+}
+
+/// The number of fields in the returned bytes tuple i.e. which pool viewer query returned `raw_pool_data`
+fn field_count(raw_pool_data: &[u8]) -> usize {
+    // the first offset follows the head
+    raw_pool_data
+        .get(..32)
+        .map(|head| U256::from_big_endian(head).low_u64() as usize / 32)
+        .unwrap_or_default()
 }
 
 /// Deserialize packed pools data into the given buffers
 /// Uses a custom packed serialization not RLP/ethabi
-fn decode_pools_data<'a>(
+pub(crate) fn decode_pools_data<'a>(
     raw_pool_data: &'a [u8],
     v3_slots: &mut Vec<UniswapV3Slot0>,
     v2_reserves: &mut Vec<UniswapV2Reserves>,
@@ -288,10 +1165,226 @@ fn decode_pools_data<'a>(
     }
 }
 
+/// Deserialize packed Uniswap v3 tick data into the given buffer (one entry per v3 pool)
+/// Does nothing if `raw_pool_data` has no tick data i.e. it was returned by `getPoolData`
+/// Malformed tick data is dropped from the first truncated pool on, pools with out of range ticks have none
+pub(crate) fn decode_pool_ticks(raw_pool_data: &[u8], v3_ticks: &mut Vec<TickLiquidity>) {
+    #[derive(DecodeStatic)]
+    struct PoolDataWithTicks<'a> {
+        _v3_slots_data: BytesZcp<'a>,
+        _v2_reserves_data: BytesZcp<'a>,
+        v3_ticks_data: BytesZcp<'a>,
+    }
+    if field_count(raw_pool_data) < 3 {
+        return;
+    }
+    let pool_data = match PoolDataWithTicks::decode(raw_pool_data) {
+        Ok(pool_data) => pool_data,
+        Err(err) => {
+            warn!("v3 tick data: {:?}", err);
+            return;
+        }
+    };
+
+    // per pool: tick count (uint16) then (tick (int24), liquidity net (int128)) for each initialized tick
+    let v3_ticks_data = pool_data.v3_ticks_data.as_ref();
+    let mut offset = 0;
+    while let Some(&[hi, lo]) = v3_ticks_data.get(offset..offset + 2) {
+        let tick_count = u16::from_be_bytes([hi, lo]) as usize;
+        offset += 2;
+        // 19 bytes == the size of each packed tick datum (24bit + 128bit)
+        let Some(tick_data) = v3_ticks_data.get(offset..offset + tick_count * 19) else {
+            warn!("v3 tick data truncated: {tick_count} ticks at offset {offset}");
+            return;
+        };
+        // sign extend the int24
+        let tick_at = |tick_data: &[u8]| {
+            i32::from_be_bytes([tick_data[0], tick_data[1], tick_data[2], 0]) >> 8
+        };
+        offset += tick_data.len();
+        if let Some(tick) = tick_data
+            .chunks_exact(19)
+            .map(tick_at)
+            .find(|tick| !(MIN_TICK..=MAX_TICK).contains(tick))
+        {
+            warn!("v3 tick out of range: {tick}, pool ticks skipped");
+            v3_ticks.push(TickLiquidity::default());
+            continue;
+        }
+        let ticks = tick_data.chunks_exact(19).map(|tick_data| {
+            let liquidity_net = i128::from_be_bytes(tick_data[3..19].try_into().expect("16 bytes"));
+            (tick_at(tick_data), liquidity_net)
+        });
+        v3_ticks.push(TickLiquidity::new(ticks));
+    }
+}
+
+/// Deserialize packed Camelot v2 pool data into the given buffer
+/// Does nothing if `raw_pool_data` has no camelot data i.e. it was not returned by `getAllPoolData`
+pub(crate) fn decode_camelot_v2_reserves(
+    raw_pool_data: &[u8],
+    camelot_v2_reserves: &mut Vec<CamelotV2Reserves>,
+) {
+    #[derive(DecodeStatic)]
+    struct AllPoolData<'a> {
+        _v3_slots_data: BytesZcp<'a>,
+        _v2_reserves_data: BytesZcp<'a>,
+        _v3_ticks_data: BytesZcp<'a>,
+        camelot_data: BytesZcp<'a>,
+    }
+    if field_count(raw_pool_data) < 4 {
+        return;
+    }
+    let pool_data = AllPoolData::decode(raw_pool_data).expect("bytes 4-tuple");
+
+    // 37 bytes == the size of each packed pool datum (128bit + 128bit + 16bit + 16bit + 8bit)
+    for datum in pool_data.camelot_data.as_ref().chunks_exact(37) {
+        camelot_v2_reserves.push(CamelotV2Reserves {
+            reserve_0: u128::from_be_bytes(datum[..16].try_into().expect("16 bytes")),
+            reserve_1: u128::from_be_bytes(datum[16..32].try_into().expect("16 bytes")),
+            token_0_fee: u16::from_be_bytes([datum[32], datum[33]]),
+            token_1_fee: u16::from_be_bytes([datum[34], datum[35]]),
+            stable: datum[36] != 0,
+        });
+    }
+}
+
+/// Deserialize packed Balancer weighted pool data into the given buffer
+/// Does nothing if `raw_pool_data` has no balancer data i.e. it was not returned by `getAllPoolDataWithBalancer`
+pub(crate) fn decode_balancer_balances(
+    raw_pool_data: &[u8],
+    balancer_balances: &mut Vec<BalancerWeightedBalances>,
+) {
+    #[derive(DecodeStatic)]
+    struct AllPoolDataWithBalancer<'a> {
+        _v3_slots_data: BytesZcp<'a>,
+        _v2_reserves_data: BytesZcp<'a>,
+        _v3_ticks_data: BytesZcp<'a>,
+        _camelot_data: BytesZcp<'a>,
+        balancer_data: BytesZcp<'a>,
+    }
+    if field_count(raw_pool_data) < 5 {
+        return;
+    }
+    let pool_data = AllPoolDataWithBalancer::decode(raw_pool_data).expect("bytes 5-tuple");
+
+    // 56 bytes == the size of each packed pool datum (128bit + 128bit + 64bit + 64bit + 64bit)
+    for datum in pool_data.balancer_data.as_ref().chunks_exact(56) {
+        balancer_balances.push(BalancerWeightedBalances {
+            balance_0: u128::from_be_bytes(datum[..16].try_into().expect("16 bytes")),
+            balance_1: u128::from_be_bytes(datum[16..32].try_into().expect("16 bytes")),
+            weight_0: u64::from_be_bytes(datum[32..40].try_into().expect("8 bytes")),
+            weight_1: u64::from_be_bytes(datum[40..48].try_into().expect("8 bytes")),
+            fee: u64::from_be_bytes(datum[48..56].try_into().expect("8 bytes")),
+        });
+    }
+}
+
+/// Deserialize packed Curve stable pool data into the given buffer
+/// Does nothing if `raw_pool_data` has no curve data i.e. it was not returned by `getAllPoolDataWithCurve`
+pub(crate) fn decode_curve_balances(
+    raw_pool_data: &[u8],
+    curve_balances: &mut Vec<CurveStableBalances>,
+) {
+    #[derive(DecodeStatic)]
+    struct AllPoolDataWithCurve<'a> {
+        _v3_slots_data: BytesZcp<'a>,
+        _v2_reserves_data: BytesZcp<'a>,
+        _v3_ticks_data: BytesZcp<'a>,
+        _camelot_data: BytesZcp<'a>,
+        _balancer_data: BytesZcp<'a>,
+        curve_data: BytesZcp<'a>,
+    }
+    if field_count(raw_pool_data) < 6 {
+        return;
+    }
+    let pool_data = AllPoolDataWithCurve::decode(raw_pool_data).expect("bytes 6-tuple");
+
+    // 40 bytes == the size of each packed pool datum (128bit + 128bit + 32bit + 32bit)
+    for datum in pool_data.curve_data.as_ref().chunks_exact(40) {
+        curve_balances.push(CurveStableBalances {
+            balance_0: u128::from_be_bytes(datum[..16].try_into().expect("16 bytes")),
+            balance_1: u128::from_be_bytes(datum[16..32].try_into().expect("16 bytes")),
+            amp: u32::from_be_bytes(datum[32..36].try_into().expect("4 bytes")),
+            fee: u32::from_be_bytes(datum[36..40].try_into().expect("4 bytes")),
+        });
+    }
+}
+
+/// Deserialize packed Solidly pair data into the given buffer
+/// Does nothing if `raw_pool_data` has no solidly data i.e. it was not returned by `getAllPoolDataWithSolidly`
+pub(crate) fn decode_solidly_reserves(
+    raw_pool_data: &[u8],
+    solidly_reserves: &mut Vec<SolidlyReserves>,
+) {
+    #[derive(DecodeStatic)]
+    struct AllPoolDataWithSolidly<'a> {
+        _v3_slots_data: BytesZcp<'a>,
+        _v2_reserves_data: BytesZcp<'a>,
+        _v3_ticks_data: BytesZcp<'a>,
+        _camelot_data: BytesZcp<'a>,
+        _balancer_data: BytesZcp<'a>,
+        _curve_data: BytesZcp<'a>,
+        solidly_data: BytesZcp<'a>,
+    }
+    if field_count(raw_pool_data) < 7 {
+        return;
+    }
+    let pool_data = AllPoolDataWithSolidly::decode(raw_pool_data).expect("bytes 7-tuple");
+
+    // 33 bytes == the size of each packed pool datum (128bit + 128bit + 8bit)
+    for datum in pool_data.solidly_data.as_ref().chunks_exact(33) {
+        solidly_reserves.push(SolidlyReserves {
+            reserve_0: u128::from_be_bytes(datum[..16].try_into().expect("16 bytes")),
+            reserve_1: u128::from_be_bytes(datum[16..32].try_into().expect("16 bytes")),
+            stable: datum[32] != 0,
+        });
+    }
+}
+
+/// Deserialize packed KyberSwap Elastic pool data into the given buffer
+/// Does nothing if `raw_pool_data` has no elastic data i.e. it was not returned by `getAllPoolDataWithKyberElastic`
+pub(crate) fn decode_kyber_elastic_states(
+    raw_pool_data: &[u8],
+    kyber_elastic_states: &mut Vec<KyberElasticState>,
+) {
+    #[derive(DecodeStatic)]
+    struct AllPoolDataWithKyberElastic<'a> {
+        _v3_slots_data: BytesZcp<'a>,
+        _v2_reserves_data: BytesZcp<'a>,
+        _v3_ticks_data: BytesZcp<'a>,
+        _camelot_data: BytesZcp<'a>,
+        _balancer_data: BytesZcp<'a>,
+        _curve_data: BytesZcp<'a>,
+        _solidly_data: BytesZcp<'a>,
+        kyber_elastic_data: BytesZcp<'a>,
+    }
+    if field_count(raw_pool_data) < 8 {
+        return;
+    }
+    let pool_data = AllPoolDataWithKyberElastic::decode(raw_pool_data).expect("bytes 8-tuple");
+
+    // 52 bytes == the size of each packed pool datum (160bit + 128bit + 128bit)
+    for datum in pool_data.kyber_elastic_data.as_ref().chunks_exact(52) {
+        kyber_elastic_states.push(KyberElasticState {
+            sqrt_p_x96: U256::from_big_endian(&datum[..20]),
+            base_liquidity: u128::from_be_bytes(datum[20..36].try_into().expect("16 bytes")),
+            reinvest_liquidity: u128::from_be_bytes(datum[36..52].try_into().expect("16 bytes")),
+        });
+    }
+}
+
 /// Return the prebuilt contract call i.e for an Eth-JSON RPC eth_call request
+/// Queries initialized v3 ticks within `tick_words` tick bitmap words of the current tick, if given
 fn build_call<M: Middleware + 'static>(
     v2_pairs: &[(Pair, Address)],
     v3_pairs: &[(Pair, Address)],
+    camelot_v2_pairs: &[(Pair, Address)],
+    balancer_pairs: &[(Pair, Address)],
+    curve_pairs: &[(Pair, Address)],
+    solidly_pairs: &[(Pair, Address)],
+    kyber_elastic_pairs: &[(Pair, Address)],
+    tick_words: Option<u8>,
     client: Arc<M>,
 ) -> Value {
     #[derive(Serialize)]
@@ -310,13 +1403,115 @@ fn build_call<M: Middleware + 'static>(
         v2_addresses.extend_from_slice(&pool_address.0);
     }
 
-    let pools_call =
-        pool_viewer.get_pool_data(Bytes(v3_addresses.into()), Bytes(v2_addresses.into()));
+    let mut camelot_addresses = Vec::with_capacity(camelot_v2_pairs.len() * 20);
+    for (_, pool_address) in camelot_v2_pairs.iter() {
+        camelot_addresses.extend_from_slice(&pool_address.0);
+    }
+
+    // balancer pools are queried with the pair's tokens
+    let mut balancer_pools = Vec::with_capacity(balancer_pairs.len() * 60);
+    for (pair, pool_address) in balancer_pairs.iter() {
+        balancer_pools.extend_from_slice(&pool_address.0);
+        balancer_pools.extend_from_slice(&pair.token0.address().0);
+        balancer_pools.extend_from_slice(&pair.token1.address().0);
+    }
+
+    // curve pools are queried with the pair's tokens
+    let mut curve_pools = Vec::with_capacity(curve_pairs.len() * 60);
+    for (pair, pool_address) in curve_pairs.iter() {
+        curve_pools.extend_from_slice(&pool_address.0);
+        curve_pools.extend_from_slice(&pair.token0.address().0);
+        curve_pools.extend_from_slice(&pair.token1.address().0);
+    }
+
+    let mut solidly_addresses = Vec::with_capacity(solidly_pairs.len() * 20);
+    for (_, pool_address) in solidly_pairs.iter() {
+        solidly_addresses.extend_from_slice(&pool_address.0);
+    }
+
+    let mut kyber_elastic_addresses = Vec::with_capacity(kyber_elastic_pairs.len() * 20);
+    for (_, pool_address) in kyber_elastic_pairs.iter() {
+        kyber_elastic_addresses.extend_from_slice(&pool_address.0);
+    }
+
+    let pools_call_tx = if !kyber_elastic_addresses.is_empty() {
+        pool_viewer
+            .get_all_pool_data_with_kyber_elastic(
+                Bytes(v3_addresses.into()),
+                Bytes(v2_addresses.into()),
+                Bytes(camelot_addresses.into()),
+                Bytes(balancer_pools.into()),
+                Bytes(curve_pools.into()),
+                Bytes(solidly_addresses.into()),
+                Bytes(kyber_elastic_addresses.into()),
+                tick_words.is_some(),
+                tick_words.unwrap_or_default(),
+            )
+            .tx
+    } else if !solidly_addresses.is_empty() {
+        pool_viewer
+            .get_all_pool_data_with_solidly(
+                Bytes(v3_addresses.into()),
+                Bytes(v2_addresses.into()),
+                Bytes(camelot_addresses.into()),
+                Bytes(balancer_pools.into()),
+                Bytes(curve_pools.into()),
+                Bytes(solidly_addresses.into()),
+                tick_words.is_some(),
+                tick_words.unwrap_or_default(),
+            )
+            .tx
+    } else if !curve_pools.is_empty() {
+        pool_viewer
+            .get_all_pool_data_with_curve(
+                Bytes(v3_addresses.into()),
+                Bytes(v2_addresses.into()),
+                Bytes(camelot_addresses.into()),
+                Bytes(balancer_pools.into()),
+                Bytes(curve_pools.into()),
+                tick_words.is_some(),
+                tick_words.unwrap_or_default(),
+            )
+            .tx
+    } else if !balancer_pools.is_empty() {
+        pool_viewer
+            .get_all_pool_data_with_balancer(
+                Bytes(v3_addresses.into()),
+                Bytes(v2_addresses.into()),
+                Bytes(camelot_addresses.into()),
+                Bytes(balancer_pools.into()),
+                tick_words.is_some(),
+                tick_words.unwrap_or_default(),
+            )
+            .tx
+    } else if !camelot_addresses.is_empty() {
+        pool_viewer
+            .get_all_pool_data(
+                Bytes(v3_addresses.into()),
+                Bytes(v2_addresses.into()),
+                Bytes(camelot_addresses.into()),
+                tick_words.is_some(),
+                tick_words.unwrap_or_default(),
+            )
+            .tx
+    } else if let Some(word_radius) = tick_words {
+        pool_viewer
+            .get_pool_data_with_ticks(
+                Bytes(v3_addresses.into()),
+                Bytes(v2_addresses.into()),
+                word_radius,
+            )
+            .tx
+    } else {
+        pool_viewer
+            .get_pool_data(Bytes(v3_addresses.into()), Bytes(v2_addresses.into()))
+            .tx
+    };
 
     // removes extraneous fields
     let call_params = CallRequestParams {
-        data: pools_call.tx.data().unwrap().clone(),
-        to: *pools_call.tx.to().unwrap().as_address().unwrap(),
+        data: pools_call_tx.data().unwrap().clone(),
+        to: *pools_call_tx.to().unwrap().as_address().unwrap(),
     };
     // let latest_block = serde_json::Value::String("latest".to_string());
     // let serialized_call_params = to_raw_value(&[&serialize(&call_params), &latest_block]).unwrap();
@@ -326,30 +1521,28 @@ fn build_call<M: Middleware + 'static>(
 /// Re-usable buffer for price queries
 struct Buffers {
     return_data: Vec<u8>,
-    v2_reserves: Vec<UniswapV2Reserves>,
-    v3_slot0s: Vec<UniswapV3Slot0>,
+    pools: PoolStates,
 }
 
 impl Buffers {
     fn new() -> Self {
         Self {
             return_data: Vec::with_capacity(2048),
-            v2_reserves: Vec::with_capacity(18),
-            v3_slot0s: Vec::with_capacity(18),
+            pools: PoolStates::with_capacity(18),
         }
     }
     /// Reset the buffers
     fn reset(&mut self) {
         unsafe {
             self.return_data.set_len(0);
-            self.v3_slot0s.set_len(0);
-            self.v2_reserves.set_len(0);
         }
+        self.pools.clear();
     }
 }
 
 #[cfg(test)]
 mod test {
+    use ethers::abi::{encode, Token as ABIToken};
     use hex_literal::hex;
 
     use super::*;
@@ -429,4 +1622,305 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn decode_v3_pool_ticks() {
+        let mut v3_ticks = Vec::<TickLiquidity>::with_capacity(10);
+
+        // no tick data
+        let buf = encode(&[ABIToken::Bytes(vec![]), ABIToken::Bytes(vec![])]);
+        decode_pool_ticks(&buf, &mut v3_ticks);
+        assert!(v3_ticks.is_empty());
+
+        let tick_data = hex!(
+            "0002"
+            "ffffc4" "fffffffffffffffffffffffffffffffb" // -60, -5
+            "00003c" "00000000000000000000000000000005" // 60, 5
+            "0000"
+        );
+        let buf = encode(&[
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(tick_data.to_vec()),
+        ]);
+        decode_pool_ticks(&buf, &mut v3_ticks);
+        assert_eq!(
+            v3_ticks,
+            vec![
+                TickLiquidity::new([(-60, -5), (60, 5)]),
+                TickLiquidity::default(),
+            ]
+        );
+
+        // a truncated pool is dropped
+        v3_ticks.clear();
+        let buf = encode(&[
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(tick_data[..2 + 19 + 7].to_vec()),
+        ]);
+        decode_pool_ticks(&buf, &mut v3_ticks);
+        assert!(v3_ticks.is_empty());
+        let buf = encode(&[
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes([&tick_data[..], &hex!("ffff00")].concat()),
+        ]);
+        decode_pool_ticks(&buf, &mut v3_ticks);
+        assert_eq!(v3_ticks.len(), 2);
+
+        // a pool with an out of range tick has none, later pools are kept
+        v3_ticks.clear();
+        let tick_data = hex!(
+            "0002"
+            "ffffc4" "fffffffffffffffffffffffffffffffb" // -60, -5
+            "7fffff" "00000000000000000000000000000005" // 8388607, 5
+            "0001"
+            "00003c" "00000000000000000000000000000005" // 60, 5
+        );
+        let buf = encode(&[
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(tick_data.to_vec()),
+        ]);
+        decode_pool_ticks(&buf, &mut v3_ticks);
+        assert_eq!(
+            v3_ticks,
+            vec![TickLiquidity::default(), TickLiquidity::new([(60, 5)])]
+        );
+    }
+
+    #[test]
+    fn decode_camelot_pool_data() {
+        let mut pools = PoolStates::default();
+        let camelot_data = hex!(
+            "000000000000000000000000000003e8" "000000000000000000000000000007d0" "012c" "0064" "00"
+            "00000000000000000000000000000005" "00000000000000000000000000000006" "0004" "0004" "01"
+        );
+        let buf = encode(&[
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(camelot_data.to_vec()),
+        ]);
+        pools.decode(&buf);
+
+        assert!(pools.v3_ticks.is_empty());
+        assert_eq!(
+            pools.camelot_v2_reserves,
+            vec![
+                CamelotV2Reserves {
+                    reserve_0: 1_000,
+                    reserve_1: 2_000,
+                    token_0_fee: 300,
+                    token_1_fee: 100,
+                    stable: false,
+                },
+                CamelotV2Reserves {
+                    reserve_0: 5,
+                    reserve_1: 6,
+                    token_0_fee: 4,
+                    token_1_fee: 4,
+                    stable: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_balancer_pool_data() {
+        let mut pools = PoolStates::default();
+        let balancer_data = hex!(
+            "000000000000000000000000000003e8" "000000000000000000000000000007d0" "06f05b59d3b20000" "06f05b59d3b20000" "000aa87bee538000"
+            "00000000000000000000000000000005" "00000000000000000000000000000006" "0b1a2bc2ec500000" "02c68af0bb140000" "000aa87bee538000"
+        );
+        let buf = encode(&[
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(balancer_data.to_vec()),
+        ]);
+        pools.decode(&buf);
+
+        assert!(pools.camelot_v2_reserves.is_empty());
+        assert_eq!(
+            pools.balancer_balances,
+            vec![
+                BalancerWeightedBalances {
+                    balance_0: 1_000,
+                    balance_1: 2_000,
+                    weight_0: 500_000_000_000_000_000,
+                    weight_1: 500_000_000_000_000_000,
+                    fee: 3_000_000_000_000_000,
+                },
+                BalancerWeightedBalances {
+                    balance_0: 5,
+                    balance_1: 6,
+                    weight_0: 800_000_000_000_000_000,
+                    weight_1: 200_000_000_000_000_000,
+                    fee: 3_000_000_000_000_000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_curve_pool_data() {
+        let mut pools = PoolStates::default();
+        let curve_data = hex!(
+            "000000000000000000000000000f4240" "000000000000000000000000001e8480" "000007d0" "003d0900"
+        );
+        let buf = encode(&[
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(curve_data.to_vec()),
+        ]);
+        pools.decode(&buf);
+
+        assert!(pools.balancer_balances.is_empty());
+        assert_eq!(
+            pools.curve_balances,
+            vec![CurveStableBalances {
+                balance_0: 1_000_000,
+                balance_1: 2_000_000,
+                amp: 2_000,
+                fee: 4_000_000,
+            }]
+        );
+    }
+
+    #[test]
+    fn decode_solidly_pool_data() {
+        let mut pools = PoolStates::default();
+        let solidly_data = hex!(
+            "000000000000000000000000000003e8" "000000000000000000000000000007d0" "00"
+            "00000000000000000000000000000005" "00000000000000000000000000000006" "01"
+        );
+        let buf = encode(&[
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(vec![]),
+            ABIToken::Bytes(solidly_data.to_vec()),
+        ]);
+        pools.decode(&buf);
+
+        assert!(pools.curve_balances.is_empty());
+        assert_eq!(
+            pools.solidly_reserves,
+            vec![
+                SolidlyReserves {
+                    reserve_0: 1_000,
+                    reserve_1: 2_000,
+                    stable: false,
+                },
+                SolidlyReserves {
+                    reserve_0: 5,
+                    reserve_1: 6,
+                    stable: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_kyber_elastic_pool_data() {
+        let mut pools = PoolStates::default();
+        let kyber_elastic_data = hex!(
+            "0000000000000001000000000000000000000000" "000000000000000000000000000003e8" "00000000000000000000000000000064"
+        );
+        let mut fields = vec![ABIToken::Bytes(vec![]); 7];
+        fields.push(ABIToken::Bytes(kyber_elastic_data.to_vec()));
+        pools.decode(&encode(&fields));
+
+        assert!(pools.solidly_reserves.is_empty());
+        assert_eq!(
+            pools.kyber_elastic_states,
+            vec![KyberElasticState {
+                sqrt_p_x96: U256::from(2_u128.pow(96)),
+                base_liquidity: 1_000,
+                reinvest_liquidity: 100,
+            }]
+        );
+        assert_eq!(pools.kyber_elastic_states[0].liquidity(), U256::from(1_100));
+    }
+
+    #[test]
+    fn pool_tvl_in_weth() {
+        use crate::types::ExchangeId;
+
+        let mut graph = PriceGraph::empty();
+        // 2,000 usdc / weth
+        graph.add_edge(
+            Token::USDC,
+            Token::WETH,
+            Edge::new_v2(
+                2_000_000_000_000_000,
+                1_000_000 * 10_u128.pow(18),
+                Fee::ZERO,
+                ExchangeId::Sushi,
+            ),
+        );
+        let pair = |token0, token1| Pair {
+            token0,
+            token1,
+            fee: Fee::from_units(300),
+            exchange_id: ExchangeId::Sushi,
+        };
+        // 1 weth + 2,000 usdc
+        let tvl = pool_tvl(
+            &graph,
+            &pair(Token::USDC, Token::WETH),
+            (2_000_000_000, 10_u128.pow(18)),
+        )
+        .unwrap();
+        assert!(tvl.abs_diff(2 * 10_u128.pow(18)) < 10_u128.pow(15));
+        // no arb/weth edge
+        assert_eq!(
+            pool_tvl(&graph, &pair(Token::USDC, Token::ARB), (1, 1)),
+            None
+        );
+
+        let slot0 = |sqrt_p_x96: U256, liquidity| UniswapV3Slot0 {
+            sqrt_p_x96,
+            liquidity,
+        };
+        assert_eq!(
+            v3_virtual_reserves(&slot0(U256::one() << 96, 1_000)),
+            (1_000, 1_000)
+        );
+        // price 4
+        assert_eq!(
+            v3_virtual_reserves(&slot0(U256::from(2) << 96, 1_000)),
+            (500, 2_000)
+        );
+        assert_eq!(v3_virtual_reserves(&slot0(U256::zero(), 1_000)), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn await_prefetched_heads() {
+        let (heads_tx, mut heads) = watch::channel((0, PoolStates::default()));
+        // nothing pre-fetched in time
+        assert!(!await_prefetched(&mut heads, 5).await);
+        heads_tx.send_modify(|(head, _)| *head = 5);
+        assert!(await_prefetched(&mut heads, 5).await);
+        // later states are used
+        assert!(await_prefetched(&mut heads, 4).await);
+
+        // pre-fetched while awaiting
+        let prefetch = tokio::spawn(async move {
+            heads_tx.send_modify(|(head, _)| *head = 6);
+            heads_tx
+        });
+        assert!(await_prefetched(&mut heads, 6).await);
+        // pre-fetching stopped
+        drop(prefetch.await.unwrap());
+        assert!(!await_prefetched(&mut heads, 7).await);
+    }
 }
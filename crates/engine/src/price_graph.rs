@@ -4,48 +4,94 @@ use std::fmt::{self};
 use ethers::types::U256;
 use log::{debug, trace};
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    types::{ExchangeId, Pair, Position, Token},
-    uniswap_v2, uniswap_v3,
-    util::{NoopHasherU32, U32Map},
+    balancer, camelot_v2, config, curve, kyber_elastic,
+    registry::MAX_TOKENS,
+    solidly,
+    types::{ExchangeId, Fee, Pair, PoolKind, Position, Token},
+    uniswap_v2,
+    uniswap_v3::{self, TickLiquidity},
+    util::{saturating_u128, u256_to_f64, NoopHasherU32, U32Map},
 };
 
-/// Lookup table from token decimals to one whole token
+/// Lookup table from token to its search position size (or one whole token)
 /// Used to calculate edge scores
-static ONE_LOOKUP_TABLE: Lazy<[u128; N]> = Lazy::new(|| {
-    let mut lookup_table = <[u128; N]>::default();
-    lookup_table[Token::USDC as usize] = 5000 * 10_u128.pow(6_u32);
-    lookup_table[Token::USDT as usize] = 5000 * 10_u128.pow(6_u32);
-    lookup_table[Token::WBTC as usize] = 1 * 10_u128.pow(7_u32);
-    lookup_table[Token::WETH as usize] = 3 * 10_u128.pow(18_u32);
-    lookup_table[Token::ARB as usize] = 4_500 * 10_u128.pow(18_u32);
-
-    lookup_table
-});
-
-// TODO: `core::mem::variant_count` when stable
+static ONE_LOOKUP_TABLE: Lazy<[u128; N]> = Lazy::new(|| config::active().one_lookup_table());
+
+/// Arb search probes paths at the position amount >> `PROBE_SHIFT` and sizes them to this precision
+const PROBE_SHIFT: u32 = 12;
+
+/// Max trades in an arb path
+pub const MAX_HOPS: usize = 5;
+
 /// Max edges in the price graph
-const N: usize = Token::VARIANT_COUNT;
-const _: () = assert!(N <= 64, "update pair identity hash");
+const N: usize = MAX_TOKENS;
+const _: () = assert!(N <= 32, "update edge hash");
 
 /// Unique edge identifier
 type EdgeId = u32;
 
 /// A graph edge (weight, exchange)
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
 pub enum Edge {
     UniV2 {
         reserve_in: u128,
         reserve_out: u128,
-        fee: u16,
+        fee: Fee,
         exchange_id: ExchangeId,
     },
     UniV3 {
         // sqrt price ratio x 2**96
         sqrt_p_x96: U256,
         liquidity: U256,
-        fee: u16,
+        fee: Fee,
+        /// Is this edge a token0 => token1 trade
+        zero_for_one: bool,
+    },
+    CamelotV2 {
+        reserve_in: u128,
+        reserve_out: u128,
+        /// fee charged on the input token
+        fee_in: Fee,
+        /// fee charged on the output token i.e. the inverse edge fee
+        fee_out: Fee,
+        /// (in, out) token decimals of stable swap pairs
+        stable: Option<(u8, u8)>,
+    },
+    BalancerWeighted {
+        /// (in, out) token balances
+        balances: (u128, u128),
+        /// (in, out) normalized token weights (1e18 fixed point)
+        weights: (u64, u64),
+        /// swap fee charged on the input token
+        fee: Fee,
+    },
+    CurveStable {
+        /// (in, out) token balances
+        balances: (u128, u128),
+        /// amplification coefficient
+        amp: u32,
+        /// fee charged on the output token
+        fee: Fee,
+        /// (in, out) token decimals
+        decimals: (u8, u8),
+    },
+    Solidly {
+        reserve_in: u128,
+        reserve_out: u128,
+        /// fee charged on the input token
+        fee: Fee,
+        /// (in, out) token decimals of stable pairs
+        stable: Option<(u8, u8)>,
+    },
+    KyberElastic {
+        // sqrt price ratio x 2**96
+        sqrt_p_x96: U256,
+        /// base + reinvestment liquidity
+        liquidity: U256,
+        fee: Fee,
         /// Is this edge a token0 => token1 trade
         zero_for_one: bool,
     },
@@ -56,26 +102,79 @@ impl Edge {
     /// a - token in
     /// b - token out
     /// c - exchange id
-    /// d - pool fee (0 for v2 edges)
-    pub fn hash(a: u8, b: u8, c: u8, fee: u16) -> u32 {
-        // 8bit in | 8bit out | 8bit exchange | 16bit (fee)
+    /// d - pool fee (zero for dynamic fee edges)
+    pub fn hash(a: u8, b: u8, c: u8, fee: Fee) -> u32 {
+        // 8bit in | 8bit out | 8bit exchange | 16bit (fee pips, higher bits are dropped)
         ((a & 63_u8) as u32)
             | (((b & 63_u8) as u32) << 5)
             | (((c & 63_u8) as u32) << 10)
-            | ((fee as u32) << 16)
+            | (fee.pips() << 16)
+    }
+    /// The (token in, token out) of `edge_id` i.e. the inverse of `Edge::hash`
+    pub fn tokens(edge_id: EdgeId) -> (Token, Token) {
+        (
+            Token::new((edge_id & 31) as u8),
+            Token::new(((edge_id >> 5) & 31) as u8),
+        )
     }
     /// Get unique id of the edge
     pub fn id(&self, token_in: Token, token_out: Token) -> EdgeId {
         match self {
             Edge::UniV2 {
                 exchange_id, fee, ..
-            } => Edge::hash(token_in as u8, token_out as u8, *exchange_id as u8, *fee),
+            } => Edge::hash(token_in.id(), token_out.id(), *exchange_id as u8, *fee),
             Edge::UniV3 { fee, .. } => Edge::hash(
-                token_in as u8,
-                token_out as u8,
+                token_in.id(),
+                token_out.id(),
                 ExchangeId::Uniswap as u8,
                 *fee,
             ),
+            // camelot fees are dynamic, edges are keyed without fee
+            Edge::CamelotV2 { .. } => Edge::hash(
+                token_in.id(),
+                token_out.id(),
+                ExchangeId::Camelot as u8,
+                Fee::ZERO,
+            ),
+            // balancer fees don't fit the edge hash, edges are keyed without fee
+            Edge::BalancerWeighted { .. } => Edge::hash(
+                token_in.id(),
+                token_out.id(),
+                ExchangeId::Balancer as u8,
+                Fee::ZERO,
+            ),
+            Edge::CurveStable { .. } => Edge::hash(
+                token_in.id(),
+                token_out.id(),
+                ExchangeId::Curve as u8,
+                Fee::ZERO,
+            ),
+            Edge::Solidly { fee, .. } => Edge::hash(
+                token_in.id(),
+                token_out.id(),
+                ExchangeId::Ramses as u8,
+                *fee,
+            ),
+            Edge::KyberElastic { fee, .. } => Edge::hash(
+                token_in.id(),
+                token_out.id(),
+                ExchangeId::KyberElastic as u8,
+                *fee,
+            ),
+        }
+    }
+    /// Get the id of the edge's pool i.e. the id of its token0 => token1 edge
+    pub fn pool_id(&self, token_in: Token, token_out: Token) -> EdgeId {
+        match self {
+            Edge::UniV3 {
+                zero_for_one: false,
+                ..
+            }
+            | Edge::KyberElastic {
+                zero_for_one: false,
+                ..
+            } => self.id(token_out, token_in),
+            _ => self.id(token_in, token_out),
         }
     }
     /// Return the inverse edge
@@ -93,10 +192,121 @@ impl Edge {
                 fee,
                 zero_for_one,
             } => Edge::new_v3(sqrt_p_x96, liquidity, fee, !zero_for_one),
+            Edge::CamelotV2 {
+                reserve_in,
+                reserve_out,
+                fee_in,
+                fee_out,
+                stable,
+            } => Edge::new_camelot_v2(
+                reserve_out,
+                reserve_in,
+                fee_out,
+                fee_in,
+                stable.map(|(decimals_in, decimals_out)| (decimals_out, decimals_in)),
+            ),
+            Edge::BalancerWeighted {
+                balances: (balance_in, balance_out),
+                weights: (weight_in, weight_out),
+                fee,
+            } => {
+                Edge::new_balancer_weighted((balance_out, balance_in), (weight_out, weight_in), fee)
+            }
+            Edge::CurveStable {
+                balances: (balance_in, balance_out),
+                amp,
+                fee,
+                decimals: (decimals_in, decimals_out),
+            } => Edge::new_curve_stable(
+                (balance_out, balance_in),
+                amp,
+                fee,
+                (decimals_out, decimals_in),
+            ),
+            Edge::Solidly {
+                reserve_in,
+                reserve_out,
+                fee,
+                stable,
+            } => Edge::new_solidly(
+                reserve_out,
+                reserve_in,
+                fee,
+                stable.map(|(decimals_in, decimals_out)| (decimals_out, decimals_in)),
+            ),
+            Edge::KyberElastic {
+                sqrt_p_x96,
+                liquidity,
+                fee,
+                zero_for_one,
+            } => Edge::new_kyber_elastic(sqrt_p_x96, liquidity, fee, !zero_for_one),
+        }
+    }
+    /// The marginal price of the edge i.e. units out per unit in, before fees
+    /// Camelot and Solidly stable pairs are approximated by their reserve ratio
+    pub fn price(&self) -> f64 {
+        match self {
+            Self::UniV2 {
+                reserve_in,
+                reserve_out,
+                ..
+            }
+            | Self::CamelotV2 {
+                reserve_in,
+                reserve_out,
+                ..
+            }
+            | Self::Solidly {
+                reserve_in,
+                reserve_out,
+                ..
+            } => *reserve_out as f64 / *reserve_in as f64,
+            Self::UniV3 {
+                sqrt_p_x96,
+                zero_for_one,
+                ..
+            }
+            | Self::KyberElastic {
+                sqrt_p_x96,
+                zero_for_one,
+                ..
+            } => {
+                let p = (u256_to_f64(sqrt_p_x96) / 2_f64.powi(96)).powi(2);
+                if *zero_for_one {
+                    p
+                } else {
+                    1.0 / p
+                }
+            }
+            Self::BalancerWeighted {
+                balances: (balance_in, balance_out),
+                weights: (weight_in, weight_out),
+                ..
+            } => {
+                (*balance_out as f64 / *weight_out as f64)
+                    / (*balance_in as f64 / *weight_in as f64)
+            }
+            // no closed form, quote a small swap (1 unit or 0.1% of the balance) without fee
+            Self::CurveStable {
+                balances: (balance_in, balance_out),
+                amp,
+                decimals,
+                ..
+            } => {
+                let amount_in = (*balance_in / 1_000).clamp(1, 10_u128.pow(decimals.0 as u32));
+                curve::get_amount_out_f(
+                    *amp,
+                    Fee::ZERO,
+                    amount_in,
+                    *balance_in,
+                    *balance_out,
+                    *decimals,
+                ) / amount_in as f64
+            }
         }
     }
     /// Create a new Uniswap V2 style edge
-    pub fn new_v2(reserve_in: u128, reserve_out: u128, fee: u16, exchange_id: ExchangeId) -> Edge {
+    pub fn new_v2(reserve_in: u128, reserve_out: u128, fee: Fee, exchange_id: ExchangeId) -> Edge {
         Edge::UniV2 {
             reserve_in,
             reserve_out,
@@ -105,7 +315,7 @@ impl Edge {
         }
     }
     /// Create a new Uniswap V3 style edge
-    pub fn new_v3(sqrt_p_x96: U256, liquidity: U256, fee: u16, zero_for_one: bool) -> Edge {
+    pub fn new_v3(sqrt_p_x96: U256, liquidity: U256, fee: Fee, zero_for_one: bool) -> Edge {
         Edge::UniV3 {
             sqrt_p_x96,
             liquidity,
@@ -113,16 +323,106 @@ impl Edge {
             zero_for_one,
         }
     }
-    pub fn fee(&self) -> u16 {
+    /// Create a new Camelot V2 edge
+    pub fn new_camelot_v2(
+        reserve_in: u128,
+        reserve_out: u128,
+        fee_in: Fee,
+        fee_out: Fee,
+        stable: Option<(u8, u8)>,
+    ) -> Edge {
+        Edge::CamelotV2 {
+            reserve_in,
+            reserve_out,
+            fee_in,
+            fee_out,
+            stable,
+        }
+    }
+    /// Create a new Balancer V2 weighted pool edge
+    pub fn new_balancer_weighted(balances: (u128, u128), weights: (u64, u64), fee: Fee) -> Edge {
+        Edge::BalancerWeighted {
+            balances,
+            weights,
+            fee,
+        }
+    }
+    /// Create a new Curve StableSwap pool edge
+    pub fn new_curve_stable(
+        balances: (u128, u128),
+        amp: u32,
+        fee: Fee,
+        decimals: (u8, u8),
+    ) -> Edge {
+        Edge::CurveStable {
+            balances,
+            amp,
+            fee,
+            decimals,
+        }
+    }
+    /// Create a new Solidly pair edge
+    pub fn new_solidly(
+        reserve_in: u128,
+        reserve_out: u128,
+        fee: Fee,
+        stable: Option<(u8, u8)>,
+    ) -> Edge {
+        Edge::Solidly {
+            reserve_in,
+            reserve_out,
+            fee,
+            stable,
+        }
+    }
+    /// Create a new KyberSwap Elastic pool edge
+    pub fn new_kyber_elastic(
+        sqrt_p_x96: U256,
+        liquidity: U256,
+        fee: Fee,
+        zero_for_one: bool,
+    ) -> Edge {
+        Edge::KyberElastic {
+            sqrt_p_x96,
+            liquidity,
+            fee,
+            zero_for_one,
+        }
+    }
+    /// The pool fee, zero for dynamic fee edges (camelot, balancer, and curve)
+    pub fn fee(&self) -> Fee {
         match self {
             Self::UniV2 { fee, .. } => *fee,
             Self::UniV3 { fee, .. } => *fee,
+            Self::Solidly { fee, .. } => *fee,
+            Self::KyberElastic { fee, .. } => *fee,
+            Self::CamelotV2 { .. } | Self::BalancerWeighted { .. } | Self::CurveStable { .. } => {
+                Fee::ZERO
+            }
+        }
+    }
+    /// The pricing curve of the edge's pool
+    pub fn pool_kind(&self) -> PoolKind {
+        match self {
+            Self::UniV3 { .. } | Self::KyberElastic { .. } => PoolKind::ConcentratedLiquidity,
+            Self::CamelotV2 {
+                stable: Some(_), ..
+            }
+            | Self::Solidly {
+                stable: Some(_), ..
+            } => PoolKind::SolidlyStable,
+            _ => PoolKind::ConstantProduct,
         }
     }
     pub fn exchange_id(&self) -> ExchangeId {
         match self {
             Self::UniV2 { exchange_id, .. } => *exchange_id,
             Self::UniV3 { .. } => ExchangeId::Uniswap,
+            Self::CamelotV2 { .. } => ExchangeId::Camelot,
+            Self::BalancerWeighted { .. } => ExchangeId::Balancer,
+            Self::CurveStable { .. } => ExchangeId::Curve,
+            Self::Solidly { .. } => ExchangeId::Ramses,
+            Self::KyberElastic { .. } => ExchangeId::KyberElastic,
         }
     }
     /// calculate the amount out given `amount_in` for the edge (fast, less precise)
@@ -134,21 +434,66 @@ impl Edge {
                 reserve_out,
                 ..
             } => uniswap_v2::get_amount_out_f(*fee, amount_in, *reserve_in, *reserve_out),
+            Self::CamelotV2 {
+                reserve_in,
+                reserve_out,
+                fee_in,
+                stable,
+                ..
+            } => {
+                camelot_v2::get_amount_out_f(*fee_in, amount_in, *reserve_in, *reserve_out, *stable)
+            }
+            Self::Solidly {
+                reserve_in,
+                reserve_out,
+                fee,
+                stable,
+            } => solidly::get_amount_out_f(*fee, amount_in, *reserve_in, *reserve_out, *stable),
+            Self::BalancerWeighted {
+                balances: (balance_in, balance_out),
+                weights: (weight_in, weight_out),
+                fee,
+            } => balancer::get_amount_out_f(
+                *fee,
+                amount_in,
+                *balance_in,
+                *weight_in,
+                *balance_out,
+                *weight_out,
+            ),
+            Self::CurveStable {
+                balances: (balance_in, balance_out),
+                amp,
+                fee,
+                decimals,
+            } => {
+                curve::get_amount_out_f(*amp, *fee, amount_in, *balance_in, *balance_out, *decimals)
+            }
             Self::UniV3 {
                 sqrt_p_x96,
                 liquidity,
                 zero_for_one,
                 fee,
                 ..
-            } => {
-                uniswap_v3::get_amount_out_f(
-                    amount_in,
-                    sqrt_p_x96.as_u128() as f64, // maybe this blows up
-                    liquidity.as_u128() as f64,
-                    *fee as u32,
-                    *zero_for_one,
-                )
-            }
+            } => uniswap_v3::get_amount_out_f(
+                amount_in,
+                u256_to_f64(sqrt_p_x96),
+                u256_to_f64(liquidity),
+                fee.pips(),
+                *zero_for_one,
+            ),
+            Self::KyberElastic {
+                sqrt_p_x96,
+                liquidity,
+                fee,
+                zero_for_one,
+            } => kyber_elastic::get_amount_out_f(
+                amount_in,
+                u256_to_f64(sqrt_p_x96),
+                u256_to_f64(liquidity),
+                *fee,
+                *zero_for_one,
+            ),
         }
     }
     /// calculate the amount out given `amount_in` for the edge
@@ -160,6 +505,37 @@ impl Edge {
                 reserve_out,
                 ..
             } => uniswap_v2::get_amount_out(*fee, amount_in, *reserve_in, *reserve_out),
+            Self::CamelotV2 {
+                reserve_in,
+                reserve_out,
+                fee_in,
+                stable,
+                ..
+            } => camelot_v2::get_amount_out(*fee_in, amount_in, *reserve_in, *reserve_out, *stable),
+            Self::Solidly {
+                reserve_in,
+                reserve_out,
+                fee,
+                stable,
+            } => solidly::get_amount_out(*fee, amount_in, *reserve_in, *reserve_out, *stable),
+            Self::BalancerWeighted {
+                balances: (balance_in, balance_out),
+                weights: (weight_in, weight_out),
+                fee,
+            } => balancer::get_amount_out(
+                *fee,
+                amount_in,
+                *balance_in,
+                *weight_in,
+                *balance_out,
+                *weight_out,
+            ),
+            Self::CurveStable {
+                balances: (balance_in, balance_out),
+                amp,
+                fee,
+                decimals,
+            } => curve::get_amount_out(*amp, *fee, amount_in, *balance_in, *balance_out, *decimals),
             Self::UniV3 {
                 sqrt_p_x96,
                 liquidity,
@@ -171,16 +547,31 @@ impl Edge {
                     amount_in,
                     sqrt_p_x96,
                     liquidity,
-                    *fee as u32,
+                    fee.pips(),
                     *zero_for_one,
                 )
                 .1
             }
+            Self::KyberElastic {
+                sqrt_p_x96,
+                liquidity,
+                fee,
+                zero_for_one,
+            } => {
+                kyber_elastic::get_amount_out(amount_in, sqrt_p_x96, liquidity, *fee, *zero_for_one)
+                    .2
+            }
         }
     }
     /// Calculate output amount and shifts the price (as if applying the trade)
+    /// Uniswap V3 trades cross the pool's initialized `ticks`, if known
+    /// KyberSwap Elastic trades fill within the current tick range, reinvesting the fee as liquidity
     /// Returns amount out given `amount_in`
-    pub fn calculate_amount_out_updating(&mut self, amount_in: u128) -> u128 {
+    pub fn calculate_amount_out_updating(
+        &mut self,
+        amount_in: u128,
+        ticks: Option<&TickLiquidity>,
+    ) -> u128 {
         match self {
             Self::UniV2 {
                 fee,
@@ -190,8 +581,76 @@ impl Edge {
             } => {
                 let amount_out =
                     uniswap_v2::get_amount_out(*fee, amount_in, *reserve_in, *reserve_out);
-                *reserve_in += amount_in;
-                *reserve_out -= amount_out;
+                *reserve_in = reserve_in.saturating_add(amount_in);
+                *reserve_out = reserve_out.saturating_sub(amount_out);
+                amount_out
+            }
+            Self::CamelotV2 {
+                reserve_in,
+                reserve_out,
+                fee_in,
+                stable,
+                ..
+            } => {
+                let amount_out = camelot_v2::get_amount_out(
+                    *fee_in,
+                    amount_in,
+                    *reserve_in,
+                    *reserve_out,
+                    *stable,
+                );
+                *reserve_in = reserve_in.saturating_add(amount_in);
+                *reserve_out = reserve_out.saturating_sub(amount_out);
+                amount_out
+            }
+            Self::Solidly {
+                reserve_in,
+                reserve_out,
+                fee,
+                stable,
+            } => {
+                let amount_out =
+                    solidly::get_amount_out(*fee, amount_in, *reserve_in, *reserve_out, *stable);
+                // the fee is sent to the pair's fee contract, it doesn't stay in reserves
+                *reserve_in = reserve_in.saturating_add(less_fee(amount_in, *fee));
+                *reserve_out = reserve_out.saturating_sub(amount_out);
+                amount_out
+            }
+            Self::BalancerWeighted {
+                balances: (balance_in, balance_out),
+                weights: (weight_in, weight_out),
+                fee,
+            } => {
+                let amount_out = balancer::get_amount_out(
+                    *fee,
+                    amount_in,
+                    *balance_in,
+                    *weight_in,
+                    *balance_out,
+                    *weight_out,
+                );
+                // the swap fee stays in the pool
+                *balance_in = balance_in.saturating_add(amount_in);
+                *balance_out = balance_out.saturating_sub(amount_out);
+                amount_out
+            }
+            Self::CurveStable {
+                balances: (balance_in, balance_out),
+                amp,
+                fee,
+                decimals,
+            } => {
+                let amount_out = curve::get_amount_out(
+                    *amp,
+                    *fee,
+                    amount_in,
+                    *balance_in,
+                    *balance_out,
+                    *decimals,
+                );
+                // half the fee goes to the admin, ignored
+                *balance_in = balance_in.saturating_add(amount_in);
+                *balance_out = balance_out.saturating_sub(amount_out);
                 amount_out
             }
             Self::UniV3 {
@@ -201,21 +660,59 @@ impl Edge {
                 fee,
                 ..
             } => {
+                if let Some(ticks) = ticks {
+                    let (new_sqrt_p_x96, new_liquidity, amount_out) =
+                        uniswap_v3::get_amount_out_crossing(
+                            amount_in,
+                            sqrt_p_x96,
+                            liquidity,
+                            fee.pips(),
+                            *zero_for_one,
+                            ticks,
+                        );
+                    *sqrt_p_x96 = new_sqrt_p_x96;
+                    *liquidity = new_liquidity;
+                    return amount_out;
+                }
                 let (new_sqrt_p_x96, amount_out) = uniswap_v3::get_amount_out(
                     amount_in,
                     sqrt_p_x96,
                     liquidity,
-                    *fee as u32,
+                    fee.pips(),
+                    *zero_for_one,
+                );
+                *sqrt_p_x96 = new_sqrt_p_x96;
+                amount_out
+            }
+            Self::KyberElastic {
+                sqrt_p_x96,
+                liquidity,
+                fee,
+                zero_for_one,
+            } => {
+                let (new_sqrt_p_x96, delta_l, amount_out) = kyber_elastic::get_amount_out(
+                    amount_in,
+                    sqrt_p_x96,
+                    liquidity,
+                    *fee,
                     *zero_for_one,
                 );
+                // the fee is reinvested as liquidity in range of the price
                 *sqrt_p_x96 = new_sqrt_p_x96;
+                *liquidity = liquidity.saturating_add(delta_l);
                 amount_out
             }
         }
     }
     /// Calculate the input amount required to take `amount_out` of the edge and shifts the price (as if applying the trade)
+    /// Uniswap V3 trades cross the pool's initialized `ticks`, if known
+    /// KyberSwap Elastic trades fill within the current tick range, reinvesting the fee as liquidity
     /// Returns `amount_in` owed
-    pub fn calculate_amount_in_updating(&mut self, amount_out: u128) -> u128 {
+    pub fn calculate_amount_in_updating(
+        &mut self,
+        amount_out: u128,
+        ticks: Option<&TickLiquidity>,
+    ) -> u128 {
         match self {
             Self::UniV2 {
                 fee,
@@ -224,10 +721,83 @@ impl Edge {
                 ..
             } => {
                 let amount_in =
-                    uniswap_v2::get_amount_out(*fee, amount_out, *reserve_in, *reserve_out);
-                *reserve_in += amount_in;
-                *reserve_out -= amount_out;
-                amount_out
+                    uniswap_v2::get_amount_in(*fee, amount_out, *reserve_in, *reserve_out);
+                // unfillable, leave the reserves as is
+                if amount_in == u128::MAX {
+                    return amount_in;
+                }
+                *reserve_in = reserve_in.saturating_add(amount_in);
+                *reserve_out = reserve_out.saturating_sub(amount_out);
+                amount_in
+            }
+            Self::CamelotV2 {
+                reserve_in,
+                reserve_out,
+                fee_in,
+                stable,
+                ..
+            } => {
+                let amount_in = camelot_v2::get_amount_in(
+                    *fee_in,
+                    amount_out,
+                    *reserve_in,
+                    *reserve_out,
+                    *stable,
+                );
+                // unfillable, leave the reserves as is
+                if amount_in == u128::MAX {
+                    return amount_in;
+                }
+                *reserve_in = reserve_in.saturating_add(amount_in);
+                *reserve_out = reserve_out.saturating_sub(amount_out);
+                amount_in
+            }
+            Self::Solidly {
+                reserve_in,
+                reserve_out,
+                fee,
+                stable,
+            } => {
+                let amount_in =
+                    solidly::get_amount_in(*fee, amount_out, *reserve_in, *reserve_out, *stable);
+                *reserve_in = reserve_in.saturating_add(less_fee(amount_in, *fee));
+                *reserve_out = reserve_out.saturating_sub(amount_out);
+                amount_in
+            }
+            Self::BalancerWeighted {
+                balances: (balance_in, balance_out),
+                weights: (weight_in, weight_out),
+                fee,
+            } => {
+                let amount_in = balancer::get_amount_in(
+                    *fee,
+                    amount_out,
+                    *balance_in,
+                    *weight_in,
+                    *balance_out,
+                    *weight_out,
+                );
+                *balance_in = balance_in.saturating_add(amount_in);
+                *balance_out = balance_out.saturating_sub(amount_out);
+                amount_in
+            }
+            Self::CurveStable {
+                balances: (balance_in, balance_out),
+                amp,
+                fee,
+                decimals,
+            } => {
+                let amount_in = curve::get_amount_in(
+                    *amp,
+                    *fee,
+                    amount_out,
+                    *balance_in,
+                    *balance_out,
+                    *decimals,
+                );
+                *balance_in = balance_in.saturating_add(amount_in);
+                *balance_out = balance_out.saturating_sub(amount_out);
+                amount_in
             }
             Self::UniV3 {
                 sqrt_p_x96,
@@ -236,20 +806,57 @@ impl Edge {
                 fee,
                 ..
             } => {
+                if let Some(ticks) = ticks {
+                    let (new_sqrt_p_x96, new_liquidity, amount_in) =
+                        uniswap_v3::get_amount_in_crossing(
+                            amount_out,
+                            sqrt_p_x96,
+                            liquidity,
+                            fee.pips(),
+                            *zero_for_one,
+                            ticks,
+                        );
+                    *sqrt_p_x96 = new_sqrt_p_x96;
+                    *liquidity = new_liquidity;
+                    return amount_in;
+                }
                 let (new_sqrt_p_x96, amount_in) = uniswap_v3::get_amount_in(
                     amount_out,
                     sqrt_p_x96,
                     liquidity,
-                    *fee as u32,
+                    fee.pips(),
                     *zero_for_one,
                 );
                 *sqrt_p_x96 = new_sqrt_p_x96;
                 amount_in
             }
+            Self::KyberElastic {
+                sqrt_p_x96,
+                liquidity,
+                fee,
+                zero_for_one,
+            } => {
+                let (new_sqrt_p_x96, delta_l, amount_in) = kyber_elastic::get_amount_in(
+                    amount_out,
+                    sqrt_p_x96,
+                    liquidity,
+                    *fee,
+                    *zero_for_one,
+                );
+                *sqrt_p_x96 = new_sqrt_p_x96;
+                *liquidity = liquidity.saturating_add(delta_l);
+                amount_in
+            }
         }
     }
 }
 
+/// `amount` less the `fee` charged on it, the product is computed in U256 so any amount is safe
+fn less_fee(amount: u128, fee: Fee) -> u128 {
+    let fee_amount = U256::from(amount) * U256::from(fee.pips()) / U256::from(Fee::PIPS);
+    amount.saturating_sub(saturating_u128(fee_amount))
+}
+
 /// Part of a `CompositeTrade`
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Trade {
@@ -258,35 +865,49 @@ pub struct Trade {
     /// Fulcrum Id of the token to receive
     pub token_out: u8,
     /// The pool fee tier (generally 0 for uniswap v2 pairs)
-    pub fee_tier: u16,
+    pub fee: Fee,
     /// Fulcrum Id of the exchange to execute the trade
     pub exchange_id: u8,
+    /// The pricing curve of the pool
+    pub pool_kind: PoolKind,
 }
 impl Trade {
-    pub fn new(token_in: u8, token_out: u8, fee_tier: u16, exchange_id: u8) -> Self {
+    /// A trade on a pool of the exchange's usual kind, see `PoolKind::of`
+    pub fn new(token_in: u8, token_out: u8, fee: Fee, exchange_id: u8) -> Self {
         Self {
             token_in,
             token_out,
-            fee_tier,
+            fee,
             exchange_id,
+            pool_kind: PoolKind::of(exchange_id),
         }
     }
+    /// Set the pool kind e.g. a solidly stable pair
+    pub fn with_pool_kind(mut self, pool_kind: PoolKind) -> Self {
+        self.pool_kind = pool_kind;
+        self
+    }
 }
-/// A trade path consisting of 2 or 3 `Trades`
-/// The 3rd trade may be a semantic noop
+/// A trade path consisting of 2 to `MAX_HOPS` `Trades`
+/// Trades beyond `len` are semantic noops
 #[derive(Copy, Clone, Default, Debug, PartialEq)]
 pub struct CompositeTrade {
-    pub path: [Trade; 3],
+    pub path: [Trade; MAX_HOPS],
+    /// Number of trades in the path
+    pub len: u8,
 }
 
 impl fmt::Display for CompositeTrade {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let _ = write!(f, "Trade: ");
-        for trade in self.path {
+        for trade in self.hops() {
             write!(
                 f,
                 "{}/{}/{}/{} ->",
-                trade.token_in, trade.token_out, trade.fee_tier, trade.exchange_id
+                trade.token_in,
+                trade.token_out,
+                trade.fee.pips(),
+                trade.exchange_id
             )?;
         }
         Ok(())
@@ -294,65 +915,93 @@ impl fmt::Display for CompositeTrade {
 }
 
 impl CompositeTrade {
-    pub fn new(path: [Trade; 3]) -> Self {
-        Self { path }
+    /// Create a trade path from `hops` (at most `MAX_HOPS`)
+    pub fn new(hops: &[Trade]) -> Self {
+        assert!(hops.len() <= MAX_HOPS, "unsupported path length");
+        let mut path = <[Trade; MAX_HOPS]>::default();
+        path[..hops.len()].copy_from_slice(hops);
+        Self {
+            path,
+            len: hops.len() as u8,
+        }
+    }
+    /// The trades of the path
+    pub fn hops(&self) -> &[Trade] {
+        &self.path[..self.len as usize]
     }
     /// Return whether the trade paths intersect at any point
     pub fn intersects(self, other: Self) -> bool {
-        // compiler should infer the slice indexes are in bounds
-        let own: u32 = 1_u32 << self.path[0].token_in
-            | 1_u32 << self.path[0].token_out
-            | 1_u32 << self.path[1].token_out;
-
-        let other: u32 = 1_u32 << other.path[0].token_in
-            | 1_u32 << other.path[0].token_out
-            | 1_u32 << other.path[1].token_out;
+        // trades form a cycle, every token is some trade's input
+        let tokens = |trade: Self| {
+            trade
+                .hops()
+                .iter()
+                .fold(0_u32, |tokens, hop| tokens | 1_u32 << hop.token_in)
+        };
+        tokens(self) & tokens(other) > 0
+    }
+}
+/// A sized arbitrage opportunity
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Arb {
+    /// The optimal amount of the start token to trade
+    pub amount_in: u128,
+    /// The expected amount of the start token returned
+    pub amount_out: u128,
+    pub trade: CompositeTrade,
+}
 
-        own & other > 0
+impl Arb {
+    /// The expected profit in units of the start token
+    pub fn profit(&self) -> u128 {
+        self.amount_out - self.amount_in
     }
 }
-/// A reflexive path type
-pub type ReflexivePath = [(usize, usize); 2]; // storing twice is technically redundant as its always a/b, b/a
-/// A triangle path type
-pub type TrianglePath = [(usize, usize); 3];
-/// An abstract, prebuilt price graph path e.g 'weth/usdc <> usdc/weth'
+
+/// A quote for trading an amount of one token for another over the best route
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quote {
+    pub amount_in: u128,
+    /// The expected amount out
+    pub amount_out: u128,
+    /// The route, 1 or 2 trades
+    pub trade: CompositeTrade,
+}
+
+/// An abstract, prebuilt price graph path e.g 'weth/usdc <> usdc/weth', a cycle of 2 to `MAX_HOPS` edges
 /// The exact edges are determined at runtime by the price graph
 #[derive(Clone, Debug, PartialEq)]
-pub enum Path {
-    /// Path with immediate neighbor from start
-    /// `base_id` uniquely identifies the base (1st) edge
-    Reflexive { path: ReflexivePath, base_id: u16 },
-    /// Path with 2nd degree neighbor from start
-    /// `base_id` uniquely identifies the base (1st) edge
-    Triangle { path: TrianglePath, base_id: u16 },
+pub struct Path {
+    /// (token in, token out) index of each edge, valid up to `len`
+    hops: [(usize, usize); MAX_HOPS],
+    len: u8,
+    /// uniquely identifies the base (1st) edge
+    base_id: u16,
 }
 
 impl Path {
-    fn reflexive(path: [(usize, usize); 2]) -> Path {
-        Path::Reflexive {
-            path,
-            base_id: Self::pair_identity(path[0].0 as u8, path[0].1 as u8),
-        }
-    }
-    fn triangular(path: [(usize, usize); 3]) -> Path {
-        Path::Triangle {
-            path,
-            base_id: Self::pair_identity(path[0].0 as u8, path[0].1 as u8),
-        }
+    fn new(hops: &[(usize, usize)]) -> Path {
+        let mut path = Path {
+            hops: Default::default(),
+            len: hops.len() as u8,
+            base_id: Self::pair_identity(hops[0].0 as u8, hops[0].1 as u8),
+        };
+        path.hops[..hops.len()].copy_from_slice(hops);
+        path
     }
     // Convert the path to a slice
     fn as_slice(&self) -> &[(usize, usize)] {
-        match self {
-            Self::Reflexive { path, .. } => path,
-            Self::Triangle { path, .. } => path,
-        }
+        &self.hops[..self.len as usize]
+    }
+    /// The tokens of the path in trade order, from the start token back to it
+    pub fn tokens(&self) -> impl Iterator<Item = Token> + '_ {
+        std::iter::once(self.hops[0].0)
+            .chain(self.as_slice().iter().map(|(_, b)| *b))
+            .map(Token::from_usize)
     }
     /// Return the Path's base pair Id
     fn base_id(&self) -> u16 {
-        match self {
-            Self::Reflexive { base_id, .. } => *base_id,
-            Self::Triangle { base_id, .. } => *base_id,
-        }
+        self.base_id
     }
     /// simple pair 'hash' for two positive integers
     fn pair_identity(a: u8, b: u8) -> u16 {
@@ -360,6 +1009,41 @@ impl Path {
     }
 }
 
+/// Price graph cells (token in, token out) changed since the last reset
+/// A bitset of token out indices per token in, rows are as wide as the max. `N` (32)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DirtyEdges([u32; N]);
+
+impl DirtyEdges {
+    /// All cells dirty e.g. to search every path
+    pub fn all() -> Self {
+        Self([u32::MAX; N])
+    }
+    /// Mark the (a, b) and (b, a) cells dirty
+    pub fn mark(&mut self, a: Token, b: Token) {
+        let (idx_a, idx_b) = (a.index(), b.index());
+        if idx_a < N && idx_b < N {
+            self.0[idx_a] |= 1 << idx_b;
+            self.0[idx_b] |= 1 << idx_a;
+        }
+    }
+    /// Returns true if the (a, b) cell is dirty
+    pub fn contains(&self, a: Token, b: Token) -> bool {
+        let (idx_a, idx_b) = (a.index(), b.index());
+        idx_a < N && idx_b < N && self.0[idx_a] & (1 << idx_b) != 0
+    }
+    /// Returns true if no cells are dirty
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|row| *row == 0)
+    }
+    /// Returns true if any edge of `path` is dirty
+    pub fn touches(&self, path: &Path) -> bool {
+        path.as_slice()
+            .iter()
+            .any(|(a, b)| self.0[*a] & (1 << *b) != 0)
+    }
+}
+
 /// Maintains a sorted list of scores for the `S` best candidate edges
 #[derive(Clone, Debug, PartialEq)]
 pub struct ScoreArray<const S: usize> {
@@ -453,8 +1137,10 @@ pub struct PriceGraph {
     scores: [[ScoreArray<5>; N]; N],
     // All known edges
     all: U32Map<Edge>,
+    /// Initialized ticks of Uniswap V3 pools (by pool id)
+    ticks: U32Map<TickLiquidity>,
     /// Edges touched during a round of price updates.
-    touched: bool,
+    dirty: DirtyEdges,
     /// Block number for which the graph was built
     block_number: u64,
 }
@@ -495,9 +1181,10 @@ impl Default for PriceGraph {
     fn default() -> Self {
         Self {
             all: U32Map::<Edge>::with_capacity_and_hasher(50, NoopHasherU32::default()),
+            ticks: U32Map::<TickLiquidity>::with_hasher(NoopHasherU32::default()),
             hyper_loop: Default::default(),
             scores: Default::default(),
-            touched: false,
+            dirty: DirtyEdges::default(),
             block_number: 0,
         }
     }
@@ -506,13 +1193,18 @@ impl Default for PriceGraph {
 impl PriceGraph {
     /// Returns true if the price graph has been updated
     pub fn touched(&self) -> bool {
-        self.touched
+        !self.dirty.is_empty()
+    }
+    /// The edges updated since the last reset
+    pub fn dirty(&self) -> &DirtyEdges {
+        &self.dirty
     }
     /// Reset price graph (calculated features only) for re-use at `block_number`
     pub fn reset(&mut self, block_number: u64) {
         self.hyper_loop = Default::default();
         self.scores = Default::default();
-        self.touched = false;
+        self.ticks.clear();
+        self.dirty = DirtyEdges::default();
         self.block_number = block_number;
     }
     /// Set the block number of the price graph
@@ -532,6 +1224,30 @@ impl PriceGraph {
     pub fn add_edge(&mut self, a: Token, b: Token, edge_a_b: Edge) {
         self.score_edge_bidirectional(a, b, edge_a_b);
     }
+    /// Set the initialized ticks of the Uniswap V3 pool (a, b, fee)
+    /// It is expected that a is token0 and b is token1 as in the uniswap token ordering
+    pub fn set_ticks(&mut self, a: Token, b: Token, fee: Fee, ticks: TickLiquidity) {
+        let pool_id = Edge::hash(a.id(), b.id(), ExchangeId::Uniswap as u8, fee);
+        self.ticks.insert(pool_id, ticks);
+    }
+    /// The edge with `edge_id`, if any
+    pub fn edge_by_id(&self, edge_id: u32) -> Option<&Edge> {
+        self.all.get(&edge_id)
+    }
+    /// All known edges (token in, token out, edge), both directions of each pool
+    pub fn edges(&self) -> impl Iterator<Item = (Token, Token, &Edge)> {
+        self.all.iter().map(|(edge_id, edge)| {
+            let (token_in, token_out) = Edge::tokens(*edge_id);
+            (token_in, token_out, edge)
+        })
+    }
+    /// The initialized ticks of Uniswap V3 pools (token0, token1, fee, ticks)
+    pub fn pool_ticks(&self) -> impl Iterator<Item = (Token, Token, Fee, &TickLiquidity)> {
+        self.ticks.iter().map(|(pool_id, ticks)| {
+            let (token0, token1) = Edge::tokens(*pool_id);
+            (token0, token1, Fee::from_pips(pool_id >> 16), ticks)
+        })
+    }
     /// Update an edge in the graph with a trade adding `amount_in`
     pub fn update_edge_in(
         &mut self,
@@ -542,8 +1258,9 @@ impl PriceGraph {
     ) -> Result<u128, ()> {
         let (amount_out, edge) = if let Some(edge) = self.all.get_mut(&edge_id) {
             debug!("before: {:?}", edge);
-            self.touched = true;
-            (edge.calculate_amount_out_updating(amount_in), *edge)
+            self.dirty.mark(token_in, token_out);
+            let ticks = self.ticks.get(&edge.pool_id(token_in, token_out));
+            (edge.calculate_amount_out_updating(amount_in, ticks), *edge)
         } else {
             return Err(());
         };
@@ -562,8 +1279,9 @@ impl PriceGraph {
     ) -> Result<u128, ()> {
         let (amount_in, edge) = if let Some(edge) = self.all.get_mut(&edge_id) {
             debug!("before: {:?}", edge);
-            self.touched = true;
-            (edge.calculate_amount_in_updating(amount_out), *edge)
+            self.dirty.mark(token_in, token_out);
+            let ticks = self.ticks.get(&edge.pool_id(token_in, token_out));
+            (edge.calculate_amount_in_updating(amount_out, ticks), *edge)
         } else {
             return Err(());
         };
@@ -572,11 +1290,25 @@ impl PriceGraph {
         self.score_edge_bidirectional(token_in, token_out, edge);
         Ok(amount_in)
     }
+    /// Apply `trade` selling `amount_in` along its path e.g. an order of ours yet to be included
+    /// Returns the amount out or `None` if an edge of the path is missing
+    pub fn apply_trade(&mut self, trade: &CompositeTrade, amount_in: u128) -> Option<u128> {
+        trade.hops().iter().try_fold(amount_in, |amount_in, hop| {
+            let edge_id = Edge::hash(hop.token_in, hop.token_out, hop.exchange_id, hop.fee);
+            self.update_edge_in(
+                Token::new(hop.token_in),
+                Token::new(hop.token_out),
+                edge_id,
+                amount_in,
+            )
+            .ok()
+        })
+    }
     /// Score the bi-directional edge from a/b and b/a possibly noting it as the best edge
     /// i.e. call after the edge price has changed
     pub fn score_edge_bidirectional(&mut self, a: Token, b: Token, edge_ab: Edge) {
-        let heuristic_amount_in_a = unsafe { *ONE_LOOKUP_TABLE.get_unchecked(a as usize) };
-        let heuristic_amount_in_b = unsafe { *ONE_LOOKUP_TABLE.get_unchecked(b as usize) };
+        let heuristic_amount_in_a = unsafe { *ONE_LOOKUP_TABLE.get_unchecked(a.index()) };
+        let heuristic_amount_in_b = unsafe { *ONE_LOOKUP_TABLE.get_unchecked(b.index()) };
         let edge_ba = edge_ab.inverse();
         // could use sqrt(P)x96 as the heuristic
         // however very uniswap specific and requires tracking the token0/token1 ordering
@@ -587,8 +1319,8 @@ impl PriceGraph {
         self.all.insert(edge_ab_id, edge_ab); // always reinsert the edge as it may've updated
         self.all.insert(edge_ba_id, edge_ba);
 
-        let idx_a = a as usize;
-        let idx_b = b as usize;
+        let idx_a = a.index();
+        let idx_b = b.index();
         if idx_a < N && idx_b < N {
             let scores = &mut self.scores[idx_a][idx_b];
             let (best_score, best_edge_id) = scores.best();
@@ -649,113 +1381,202 @@ impl PriceGraph {
         }
     }
     /// Find supported arbitrage paths for token `start` through the provided pairs list
+    /// Paths are cycles of 2 up to `max_hops` (at most `MAX_HOPS`) trades, through distinct tokens
     /// This is intended to be run once to produce searchable paths for `find_arb`
-    pub fn find_paths(start: Token, pairs: &[Pair]) -> Vec<Path> {
-        // reflex and triangles are always together and can be processed together for improved efficiency
+    pub fn find_paths(start: Token, pairs: &[Pair], max_hops: usize) -> Vec<Path> {
+        assert!(
+            (2..=MAX_HOPS).contains(&max_hops),
+            "unsupported max. path length"
+        );
         let mut paths = Vec::<Path>::with_capacity(2 * pairs.len());
-        let start_idx = start as usize;
         // N possible edges from start node
         let mut edges = <[[Option<usize>; N]; N]>::default();
         for pair in pairs {
             let (a, b) = pair.tokens();
-            edges[a as usize][b as usize] = Some(b as usize);
-            edges[b as usize][a as usize] = Some(a as usize);
+            edges[a.index()][b.index()] = Some(b.index());
+            edges[b.index()][a.index()] = Some(a.index());
         }
 
-        // find _supported_ paths
+        // find _supported_ paths, depth first so paths sharing a base edge are adjacent
+        let start_idx = start.index();
+        let mut hops = Vec::<(usize, usize)>::with_capacity(max_hops);
         for first_neighbor in edges[start_idx].into_iter().flatten() {
-            for second_neighbor in edges[first_neighbor].into_iter().flatten() {
-                if second_neighbor == start_idx {
-                    paths.push(Path::reflexive([
-                        (start_idx, first_neighbor),
-                        (first_neighbor, start_idx),
-                    ]));
-                } else if edges[second_neighbor][start_idx].is_some() {
-                    paths.push(Path::triangular([
-                        (start_idx, first_neighbor),
-                        (first_neighbor, second_neighbor),
-                        (second_neighbor, start_idx),
-                    ]));
-                }
-            }
+            hops.push((start_idx, first_neighbor));
+            Self::extend_paths(&edges, &mut hops, max_hops, &mut paths);
+            hops.pop();
         }
 
         paths
     }
+    /// Extend the path `hops` back to its start token, collecting the cycles in `paths`
+    fn extend_paths(
+        edges: &[[Option<usize>; N]; N],
+        hops: &mut Vec<(usize, usize)>,
+        max_hops: usize,
+        paths: &mut Vec<Path>,
+    ) {
+        let start_idx = hops[0].0;
+        let current = hops[hops.len() - 1].1;
+        for next in edges[current].into_iter().flatten() {
+            if next == start_idx {
+                hops.push((current, next));
+                paths.push(Path::new(hops));
+                hops.pop();
+            } else if hops.len() + 1 < max_hops && hops.iter().all(|(a, _)| *a != next) {
+                hops.push((current, next));
+                Self::extend_paths(edges, hops, max_hops, paths);
+                hops.pop();
+            }
+        }
+    }
     /// Find an arbitrage opportunity in the price graph
     ///
-    /// Only prebuilt paths are checked i.e. from `PriceGraph::find_paths(start, pairs)`
-    /// search paths are also filtered by edges given in `filter`
-    pub fn find_arb(&self, start: &Position, paths: &[Path]) -> Option<(u128, CompositeTrade)> {
-        let start_amount = start.amount;
-        let mut best_output = start_amount;
-        let mut best_trade: Option<usize> = None;
+    /// Only prebuilt paths are checked i.e. from `PriceGraph::find_paths(start, pairs, max_hops)`
+    /// Paths are screened at a small probe size then the profitable ones sized to maximize profit, up to the `start` position amount
+    /// Paths with no `dirty` edges are skipped, their prices are unchanged since the last search
+    /// Returns the most profitable arb
+    pub fn find_arb(&self, start: &Position, paths: &[Path], dirty: &DirtyEdges) -> Option<Arb> {
+        let max_amount_in = start.amount;
+        let probe_amount = (max_amount_in >> PROBE_SHIFT).max(1);
+        let mut best: Option<(usize, u128, u128)> = None;
         let mut cache_amount_out = 0u128;
-        let mut cache_base_id: u16 = 0;
-        let mut edge: Edge;
-        'outer: for (path_idx, path) in paths.iter().enumerate() {
-            let mut current_output = start_amount;
+        let mut cache_base_id: Option<u16> = None;
+        for (path_idx, path) in paths.iter().enumerate() {
+            if !dirty.touches(path) {
+                continue;
+            }
             // is the previous path's base the same
-            let set_cache = path.base_id() != cache_base_id;
-            for (edge_idx, (a_idx, b_idx)) in path.as_slice().iter().enumerate() {
-                debug!("trade output: {:?}", current_output);
-                unsafe {
-                    // TODO: jumps randomly around memory space
-                    debug!("{a_idx},{b_idx}");
-                    edge = (self.hyper_loop.get_unchecked(*a_idx).get_unchecked(*b_idx))
-                        .expect("edge exists");
-                }
-                //  NB: could optimize with float calcs here, trade 100% exactness for speed is ok for flash swaps
-                if edge_idx == 0 {
-                    if set_cache {
-                        cache_amount_out = edge.calculate_amount_out(current_output);
-                        cache_base_id = path.base_id();
-                    }
-                    current_output = cache_amount_out;
-                    continue;
-                } else {
-                    current_output = edge.calculate_amount_out(current_output);
-                }
+            if cache_base_id != Some(path.base_id()) {
+                cache_amount_out = self
+                    .edge(path.as_slice()[0])
+                    .calculate_amount_out(probe_amount);
+                cache_base_id = Some(path.base_id());
+            }
+            let probe_output = self.path_amount_out_from(path, 1, cache_amount_out);
+            debug!("probe output: {:?}", probe_output);
+            // profits are concave in the amount in, a path unprofitable at the margin is unprofitable at any size
+            if probe_output <= probe_amount {
+                continue;
             }
-            debug!("trade output: {:?}\nend trade\n", current_output);
-            // check if we found a better path
-            if current_output > best_output {
-                best_trade = Some(path_idx);
-                best_output = current_output;
+            let (amount_in, amount_out) = self.optimal_amount_in(path, max_amount_in);
+            let profit = amount_out.saturating_sub(amount_in);
+            if profit > best.map_or(0, |(_, amount_in, amount_out)| amount_out - amount_in) {
+                best = Some((path_idx, amount_in, amount_out));
             }
         }
 
-        if let Some(best_trade) = best_trade {
-            // make the trade path pretty for consumer
-            let best_path = unsafe { paths.get_unchecked(best_trade) };
-            let mut trade = <[Trade; 3]>::default();
-            for (idx, (a, b)) in best_path.as_slice().iter().enumerate() {
-                // TODO: size hints to remove the unsafe
-                unsafe {
-                    let edge = self
-                        .hyper_loop
-                        .get_unchecked(*a)
-                        .get_unchecked(*b)
-                        .expect("edge exists");
-                    *trade.get_unchecked_mut(idx) =
-                        Trade::new(*a as u8, *b as u8, edge.fee(), edge.exchange_id() as u8);
-                };
+        let (best_trade, amount_in, amount_out) = best?;
+        // make the trade path pretty for consumer
+        let best_path = unsafe { paths.get_unchecked(best_trade) };
+        let mut trade = CompositeTrade {
+            len: best_path.len,
+            ..Default::default()
+        };
+        for (hop, (a, b)) in trade.path.iter_mut().zip(best_path.as_slice()) {
+            let edge = self.edge((*a, *b));
+            *hop = Trade::new(*a as u8, *b as u8, edge.fee(), edge.exchange_id() as u8)
+                .with_pool_kind(edge.pool_kind());
+        }
+        Some(Arb {
+            amount_in,
+            amount_out,
+            trade,
+        })
+    }
+    /// Find the amount in (up to `max_amount_in`) maximizing the profit of trading `path`
+    ///
+    /// Ternary search over the amount in, the profit of a v2/v3 cycle is concave (v3 within the current tick range)
+    /// Returns (amount in, amount out)
+    pub fn optimal_amount_in(&self, path: &Path, max_amount_in: u128) -> (u128, u128) {
+        let profit = |amount_in: u128| {
+            self.path_amount_out_from(path, 0, amount_in) as i128 - amount_in as i128
+        };
+        let tolerance = (max_amount_in >> PROBE_SHIFT).max(2);
+        let (mut lo, mut hi) = (0_u128, max_amount_in);
+        while hi - lo > tolerance {
+            let third = (hi - lo) / 3;
+            let (m1, m2) = (lo + third, hi - third);
+            if profit(m1) < profit(m2) {
+                lo = m1;
+            } else {
+                hi = m2;
             }
-            Some((best_output, CompositeTrade::new(trade)))
-        } else {
-            None
         }
+        let amount_in = lo + (hi - lo) / 2;
+        (amount_in, self.path_amount_out_from(path, 0, amount_in))
+    }
+    /// Trade `amount_in` over the best `token_in`/`token_out` edge
+    /// Returns `None` if there is no such edge
+    pub fn amount_out(&self, token_in: Token, token_out: Token, amount_in: u128) -> Option<u128> {
+        self.hyper_loop
+            .get(token_in.index())?
+            .get(token_out.index())?
+            .map(|edge| edge.calculate_amount_out(amount_in))
+    }
+    /// Quote trading `amount_in` of `token_in` for `token_out` over the best edges, directly or via one other token
+    /// Returns `None` if no route connects the tokens
+    pub fn quote(&self, token_in: Token, token_out: Token, amount_in: u128) -> Option<Quote> {
+        let (a, b) = (token_in.index(), token_out.index());
+        if a >= N || b >= N || a == b {
+            return None;
+        }
+        let direct = self.hyper_loop[a][b].map(|edge| (edge.calculate_amount_out(amount_in), None));
+        let via = (0..N)
+            .filter(|via| *via != a && *via != b)
+            .filter_map(|via| {
+                let (edge_in, edge_out) = (self.hyper_loop[a][via]?, self.hyper_loop[via][b]?);
+                let amount_out =
+                    edge_out.calculate_amount_out(edge_in.calculate_amount_out(amount_in));
+                Some((amount_out, Some(via)))
+            });
+        let (amount_out, via) = direct
+            .into_iter()
+            .chain(via)
+            .max_by_key(|(amount_out, _)| *amount_out)?;
+
+        let hop = |a: usize, b: usize| {
+            let edge = self.edge((a, b));
+            Trade::new(a as u8, b as u8, edge.fee(), edge.exchange_id() as u8)
+                .with_pool_kind(edge.pool_kind())
+        };
+        let trade = match via {
+            Some(via) => CompositeTrade::new(&[hop(a, via), hop(via, b)]),
+            None => CompositeTrade::new(&[hop(a, b)]),
+        };
+        Some(Quote {
+            amount_in,
+            amount_out,
+            trade,
+        })
+    }
+    /// Trade `amount_in` along the edges of `path` from index `from`
+    fn path_amount_out_from(&self, path: &Path, from: usize, amount_in: u128) -> u128 {
+        path.as_slice()[from..]
+            .iter()
+            .fold(amount_in, |amount, pair| {
+                self.edge(*pair).calculate_amount_out(amount)
+            })
+    }
+    /// The edge trading token `a` for token `b`
+    #[inline(always)]
+    fn edge(&self, (a, b): (usize, usize)) -> Edge {
+        // TODO: jumps randomly around memory space
+        unsafe { *self.hyper_loop.get_unchecked(a).get_unchecked(b) }.expect("edge exists")
     }
 }
 
 #[cfg(test)]
 mod test {
+    use ethers::types::U256;
+
     use crate::{
-        price_graph::Trade,
-        types::{ExchangeId, Pair, Position, Token},
+        price_graph::{CompositeTrade, DirtyEdges, Trade, PROBE_SHIFT},
+        registry::MAX_TOKENS,
+        types::{ExchangeId, Fee, Pair, Position, Token},
+        uniswap_v3::{self, TickLiquidity},
     };
 
-    use super::{Edge, Path, PriceGraph, ScoreArray};
+    use super::{less_fee, Edge, Path, PriceGraph, ScoreArray};
 
     pub fn eth(wei: u32) -> u128 {
         wei as u128 * 10_u128.pow(18_u32)
@@ -764,32 +1585,37 @@ mod test {
     #[test]
     pub fn find_paths_triangular() {
         let pairs = &[
-            Pair::new(Token::USDC, Token::WETH, 0, ExchangeId::Camelot),
-            Pair::new(Token::USDC, Token::ARB, 0, ExchangeId::Sushi),
-            Pair::new(Token::WETH, Token::ARB, 500, ExchangeId::Uniswap),
+            Pair::new(Token::USDC, Token::WETH, Fee::ZERO, ExchangeId::Camelot),
+            Pair::new(Token::USDC, Token::ARB, Fee::ZERO, ExchangeId::Sushi),
+            Pair::new(
+                Token::WETH,
+                Token::ARB,
+                Fee::from_pips(500),
+                ExchangeId::Uniswap,
+            ),
         ];
 
-        let paths = PriceGraph::find_paths(Token::USDC, pairs);
+        let paths = PriceGraph::find_paths(Token::USDC, pairs, 3);
         assert_eq!(
             paths,
             vec![
-                Path::reflexive([
-                    (Token::USDC as usize, Token::WETH as usize),
-                    (Token::WETH as usize, Token::USDC as usize)
+                Path::new(&[
+                    (Token::USDC.index(), Token::WETH.index()),
+                    (Token::WETH.index(), Token::USDC.index())
                 ]),
-                Path::triangular([
-                    (Token::USDC as usize, Token::WETH as usize),
-                    (Token::WETH as usize, Token::ARB as usize),
-                    (Token::ARB as usize, Token::USDC as usize)
+                Path::new(&[
+                    (Token::USDC.index(), Token::WETH.index()),
+                    (Token::WETH.index(), Token::ARB.index()),
+                    (Token::ARB.index(), Token::USDC.index())
                 ]),
-                Path::reflexive([
-                    (Token::USDC as usize, Token::ARB as usize),
-                    (Token::ARB as usize, Token::USDC as usize)
+                Path::new(&[
+                    (Token::USDC.index(), Token::ARB.index()),
+                    (Token::ARB.index(), Token::USDC.index())
                 ]),
-                Path::triangular([
-                    (Token::USDC as usize, Token::ARB as usize),
-                    (Token::ARB as usize, Token::WETH as usize),
-                    (Token::WETH as usize, Token::USDC as usize)
+                Path::new(&[
+                    (Token::USDC.index(), Token::ARB.index()),
+                    (Token::ARB.index(), Token::WETH.index()),
+                    (Token::WETH.index(), Token::USDC.index())
                 ]),
             ]
         );
@@ -798,34 +1624,111 @@ mod test {
     #[test]
     pub fn find_paths_no_triangle() {
         let pairs = &[
-            Pair::new(Token::USDC, Token::WETH, 100, ExchangeId::Uniswap),
-            Pair::new(Token::USDC, Token::WETH, 0, ExchangeId::Chronos),
-            Pair::new(Token::WBTC, Token::WETH, 0, ExchangeId::Sushi),
+            Pair::new(
+                Token::USDC,
+                Token::WETH,
+                Fee::from_pips(100),
+                ExchangeId::Uniswap,
+            ),
+            Pair::new(Token::USDC, Token::WETH, Fee::ZERO, ExchangeId::Chronos),
+            Pair::new(Token::WBTC, Token::WETH, Fee::ZERO, ExchangeId::Sushi),
         ];
 
-        let paths = PriceGraph::find_paths(Token::USDC, pairs);
+        let paths = PriceGraph::find_paths(Token::USDC, pairs, 3);
         assert_eq!(
             paths,
-            vec![Path::reflexive([
-                (Token::USDC as usize, Token::WETH as usize),
-                (Token::WETH as usize, Token::USDC as usize)
+            vec![Path::new(&[
+                (Token::USDC.index(), Token::WETH.index()),
+                (Token::WETH.index(), Token::USDC.index())
             ]),]
         );
     }
 
+    #[test]
+    pub fn find_paths_long_cycles() {
+        // a 'square' USDC > WETH > ARB > USDT > USDC
+        let pairs = &[
+            Pair::new(
+                Token::USDC,
+                Token::WETH,
+                Fee::from_pips(500),
+                ExchangeId::Uniswap,
+            ),
+            Pair::new(Token::WETH, Token::ARB, Fee::ZERO, ExchangeId::Sushi),
+            Pair::new(Token::ARB, Token::USDT, Fee::ZERO, ExchangeId::Camelot),
+            Pair::new(
+                Token::USDT,
+                Token::USDC,
+                Fee::from_pips(100),
+                ExchangeId::Uniswap,
+            ),
+        ];
+        let (usdc, weth, arb, usdt) = (
+            Token::USDC.index(),
+            Token::WETH.index(),
+            Token::ARB.index(),
+            Token::USDT.index(),
+        );
+        let reflexive = vec![
+            Path::new(&[(usdc, weth), (weth, usdc)]),
+            Path::new(&[(usdc, usdt), (usdt, usdc)]),
+        ];
+        assert_eq!(PriceGraph::find_paths(Token::USDC, pairs, 3), reflexive);
+        assert_eq!(
+            PriceGraph::find_paths(Token::USDC, pairs, 4),
+            vec![
+                reflexive[0].clone(),
+                Path::new(&[(usdc, weth), (weth, arb), (arb, usdt), (usdt, usdc)]),
+                reflexive[1].clone(),
+                Path::new(&[(usdc, usdt), (usdt, arb), (arb, weth), (weth, usdc)]),
+            ]
+        );
+        // no new cycles without revisiting a token
+        assert_eq!(
+            PriceGraph::find_paths(Token::USDC, pairs, 5),
+            PriceGraph::find_paths(Token::USDC, pairs, 4)
+        );
+    }
+
+    #[test]
+    fn composite_trade_hops() {
+        let square = CompositeTrade::new(&[
+            Trade::new(0, 1, Fee::from_pips(500), 0),
+            Trade::new(1, 3, Fee::ZERO, 2),
+            Trade::new(3, 4, Fee::ZERO, 1),
+            Trade::new(4, 0, Fee::from_pips(100), 0),
+        ]);
+        assert_eq!(square.hops().len(), 4);
+        assert_eq!(square.path[4], Trade::default());
+        assert_eq!(
+            square.to_string(),
+            "Trade: 0/1/500/0 ->1/3/0/2 ->3/4/0/1 ->4/0/100/0 ->"
+        );
+        let reflexive = CompositeTrade::new(&[
+            Trade::new(5, 3, Fee::ZERO, 2),
+            Trade::new(3, 5, Fee::ZERO, 1),
+        ]);
+        assert!(square.intersects(reflexive));
+        let reflexive = CompositeTrade::new(&[
+            Trade::new(5, 2, Fee::ZERO, 2),
+            Trade::new(2, 5, Fee::ZERO, 1),
+        ]);
+        assert!(!square.intersects(reflexive));
+    }
+
     #[test]
     pub fn add_edges() {
         let mut graph: PriceGraph = PriceGraph::empty();
 
         // 3,000 usdc / 2 weth
         let p = (eth(2) - 15_000_000_u128) / 2999_999988_u128;
-        let edge0 = Edge::new_v3(p.into(), 1_000_000.into(), 500, true);
+        let edge0 = Edge::new_v3(p.into(), 1_000_000.into(), Fee::from_pips(500), true);
         graph.add_edge(Token::USDC, Token::WETH, edge0);
 
         let edge1 = Edge::UniV2 {
             reserve_in: (eth(2) - 1_000_000_u128),
             reserve_out: 2999_000000_u128,
-            fee: 9997_u16,
+            fee: Fee::from_units(9997),
             exchange_id: ExchangeId::Sushi,
         };
         graph.add_edge(Token::USDC, Token::WETH, edge1);
@@ -834,63 +1737,44 @@ mod test {
         let edge2 = Edge::UniV2 {
             reserve_in: (eth(2) - 1_000_000_000_u128),
             reserve_out: 2_400000_u128,
-            fee: 9997_u16,
+            fee: Fee::from_units(9997),
             exchange_id: ExchangeId::Chronos,
         };
         graph.add_edge(Token::USDC, Token::ARB, edge2);
 
         let p = (eth(2) - 1_110_000_000_u128) / 2_410000_u128;
-        let edge3 = Edge::new_v3(p.into(), 1_000_000.into(), 3000, true);
+        let edge3 = Edge::new_v3(p.into(), 1_000_000.into(), Fee::from_pips(3000), true);
         graph.add_edge(Token::USDC, Token::ARB, edge3);
 
         let edge4 = Edge::UniV2 {
             reserve_in: (5_011 + 100_u128),
             reserve_out: 40_000_u128,
-            fee: 9997_u16,
+            fee: Fee::from_units(9997),
             exchange_id: ExchangeId::Camelot,
         };
         graph.add_edge(Token::ARB, Token::WETH, edge4);
 
-        // could pretty this up with some to/from string type e.g.
-        // "[][x][][][x][]"
-        // "[][][x][][][]"
-        // "[][][][][x][]"
-        assert_eq!(
-            graph.hyper_loop,
-            [
-                [None, Some(edge1), None, Some(edge2), None, None, None,],
-                [
-                    Some(edge0.inverse()),
-                    None,
-                    None,
-                    Some(edge4.inverse()),
-                    None,
-                    None,
-                    None,
-                ],
-                [None, None, None, None, None, None, None],
-                [
-                    Some(edge3.inverse()),
-                    Some(edge4),
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                ],
-                [None, None, None, None, None, None, None],
-                [None, None, None, None, None, None, None],
-                [None, None, None, None, None, None, None],
-            ]
-        );
+        let mut expected = <[[Option<Edge>; MAX_TOKENS]; MAX_TOKENS]>::default();
+        expected[Token::USDC.index()][Token::WETH.index()] = Some(edge1);
+        expected[Token::USDC.index()][Token::ARB.index()] = Some(edge2);
+        expected[Token::WETH.index()][Token::USDC.index()] = Some(edge0.inverse());
+        expected[Token::WETH.index()][Token::ARB.index()] = Some(edge4.inverse());
+        expected[Token::ARB.index()][Token::USDC.index()] = Some(edge3.inverse());
+        expected[Token::ARB.index()][Token::WETH.index()] = Some(edge4);
+        assert_eq!(graph.hyper_loop, expected);
     }
 
     #[test]
     pub fn find_arb_works() {
         let pairs = &[
-            Pair::new(Token::USDC, Token::WETH, 500, ExchangeId::Uniswap),
-            Pair::new(Token::USDC, Token::ARB, 0, ExchangeId::Chronos),
-            Pair::new(Token::WETH, Token::ARB, 0, ExchangeId::Sushi),
+            Pair::new(
+                Token::USDC,
+                Token::WETH,
+                Fee::from_pips(500),
+                ExchangeId::Uniswap,
+            ),
+            Pair::new(Token::USDC, Token::ARB, Fee::ZERO, ExchangeId::Chronos),
+            Pair::new(Token::WETH, Token::ARB, Fee::ZERO, ExchangeId::Sushi),
         ];
 
         let edges = vec![
@@ -900,20 +1784,20 @@ mod test {
                     as u128)
                     .into(),
                 liquidity: 1000_0000.into(),
-                fee: 500_u16,
+                fee: Fee::from_pips(500),
                 zero_for_one: true,
             },
             // 2.4 usdc / 2 ARB
             Edge::UniV2 {
                 reserve_in: (eth(2) - 1_000_000_000_u128),
                 reserve_out: 2_400000_u128,
-                fee: 9997_u16,
+                fee: Fee::from_units(9997),
                 exchange_id: ExchangeId::Chronos,
             },
             Edge::UniV2 {
                 reserve_in: 5_011_u128 + 100_u128,
                 reserve_out: 40_000_u128,
-                fee: 9997_u16,
+                fee: Fee::from_units(9997),
                 exchange_id: ExchangeId::Camelot,
             },
         ];
@@ -924,42 +1808,159 @@ mod test {
             graph.add_edge(a, b, *edge);
         }
 
-        let search_paths = PriceGraph::find_paths(Token::USDC, pairs);
-        let (_value, found) = graph
+        let search_paths = PriceGraph::find_paths(Token::USDC, pairs, 3);
+        let arb = graph
             .find_arb(
                 &Position {
                     amount: 1_000000_u128,
                     token: Token::USDC,
                 },
                 search_paths.as_slice(),
+                &DirtyEdges::all(),
             )
             .unwrap();
+        assert!(arb.amount_in <= 1_000000_u128);
+        assert!(arb.profit() > 0);
 
         assert_eq!(
-            found.path,
+            arb.trade.hops(),
             [
                 Trade {
                     token_in: 0,
                     token_out: 3,
-                    fee_tier: 9997,
+                    fee: Fee::from_units(9997),
                     exchange_id: 3
                 },
                 Trade {
                     token_in: 3,
                     token_out: 1,
-                    fee_tier: 9997,
+                    fee: Fee::from_units(9997),
                     exchange_id: 1
                 },
                 Trade {
                     token_in: 1,
                     token_out: 0,
-                    fee_tier: 500,
+                    fee: Fee::from_pips(500),
                     exchange_id: 0
                 }
             ]
         );
     }
 
+    #[test]
+    fn optimal_amount_in_v2_cycle() {
+        const USDC: u128 = 1_000_000;
+        let pairs = &[
+            Pair::new(Token::USDC, Token::WETH, Fee::ZERO, ExchangeId::Sushi),
+            Pair::new(Token::USDC, Token::WETH, Fee::ZERO, ExchangeId::Camelot),
+        ];
+        let mut graph = PriceGraph::empty();
+        // WETH is ~1% cheaper on sushi
+        graph.add_edge(
+            Token::USDC,
+            Token::WETH,
+            Edge::new_v2(
+                2_000_000 * USDC,
+                eth(1_010),
+                Fee::from_units(300),
+                ExchangeId::Sushi,
+            ),
+        );
+        graph.add_edge(
+            Token::WETH,
+            Token::USDC,
+            Edge::new_v2(
+                eth(1_000),
+                2_000_000 * USDC,
+                Fee::from_units(300),
+                ExchangeId::Camelot,
+            ),
+        );
+        let path = &PriceGraph::find_paths(Token::USDC, pairs, 3)[0];
+        let max_amount_in = 100_000 * USDC;
+        let (amount_in, amount_out) = graph.optimal_amount_in(path, max_amount_in);
+        let profit = |amount_in: u128| {
+            graph.path_amount_out_from(path, 0, amount_in) as i128 - amount_in as i128
+        };
+        assert!(amount_out > amount_in);
+        // interior optimum, better than its neighbours (within the search precision) and the position bounds
+        let step = max_amount_in >> (PROBE_SHIFT - 1);
+        assert!(amount_in < max_amount_in);
+        assert!(profit(amount_in) >= profit(amount_in - step));
+        assert!(profit(amount_in) >= profit(amount_in + step));
+        assert!(profit(amount_in) > profit(max_amount_in));
+
+        let arb = graph
+            .find_arb(
+                &Position::new(max_amount_in, Token::USDC),
+                std::slice::from_ref(path),
+                &DirtyEdges::all(),
+            )
+            .unwrap();
+        assert_eq!((arb.amount_in, arb.amount_out), (amount_in, amount_out));
+        // the optimum is out of range of a small position
+        let arb = graph
+            .find_arb(
+                &Position::new(1_000 * USDC, Token::USDC),
+                std::slice::from_ref(path),
+                &DirtyEdges::all(),
+            )
+            .unwrap();
+        assert!(arb.amount_in > 999 * USDC);
+    }
+
+    #[test]
+    fn find_arb_skips_clean_paths() {
+        let pairs = &[
+            Pair::new(Token::USDC, Token::WETH, Fee::ZERO, ExchangeId::Sushi),
+            Pair::new(Token::USDC, Token::WETH, Fee::ZERO, ExchangeId::Camelot),
+        ];
+        let mut graph = PriceGraph::empty();
+        graph.add_edge(
+            Token::USDC,
+            Token::WETH,
+            Edge::new_v2(
+                2_000_000_000_000,
+                eth(1_010),
+                Fee::from_units(300),
+                ExchangeId::Sushi,
+            ),
+        );
+        graph.add_edge(
+            Token::WETH,
+            Token::USDC,
+            Edge::new_v2(
+                eth(1_000),
+                2_000_000_000_000,
+                Fee::from_units(300),
+                ExchangeId::Camelot,
+            ),
+        );
+        let paths = PriceGraph::find_paths(Token::USDC, pairs, 3);
+        let position = Position::new(1_000_000_000, Token::USDC);
+        // price sync alone touches nothing
+        assert!(!graph.touched());
+        assert!(graph.find_arb(&position, &paths, graph.dirty()).is_none());
+
+        let mut dirty = DirtyEdges::default();
+        dirty.mark(Token::USDC, Token::ARB);
+        assert!(graph.find_arb(&position, &paths, &dirty).is_none());
+        dirty.mark(Token::WETH, Token::USDC);
+        assert!(graph.find_arb(&position, &paths, &dirty).is_some());
+
+        // a simulated trade dirties its edge
+        let edge_id = graph
+            .edge((Token::USDC.index(), Token::WETH.index()))
+            .id(Token::USDC, Token::WETH);
+        graph
+            .update_edge_in(Token::USDC, Token::WETH, edge_id, 1_000_000)
+            .unwrap();
+        assert!(graph.touched());
+        assert!(graph.find_arb(&position, &paths, graph.dirty()).is_some());
+        graph.reset(1);
+        assert_eq!(graph.dirty(), &DirtyEdges::default());
+    }
+
     #[test]
     fn score_array() {
         let mut scores = ScoreArray::<5>::default();
@@ -1035,4 +2036,442 @@ mod test {
             ScoreArray::new([(8.0, 2), (7.0, 7), (6.0, 3), (5.0, 5), (4.0, 4)])
         );
     }
+
+    #[test]
+    fn update_edge_out_v2() {
+        // sushi WETH/USDC
+        let (reserve_weth, reserve_usdc) = (2757113099049556297952_u128, 5176991819833_u128);
+        let mut price_graph = PriceGraph::empty();
+        price_graph.add_edge(
+            Token::WETH,
+            Token::USDC,
+            Edge::new_v2(
+                reserve_weth,
+                reserve_usdc,
+                Fee::from_units(300),
+                ExchangeId::Sushi,
+            ),
+        );
+
+        // sell WETH for exactly 9343.369893 USDC
+        let edge_id = Edge::hash(
+            Token::WETH.id(),
+            Token::USDC.id(),
+            ExchangeId::Sushi as u8,
+            Fee::from_units(300),
+        );
+        let amount_in = price_graph
+            .update_edge_out(Token::USDC, Token::WETH, edge_id, 9343369893)
+            .unwrap();
+        assert_eq!(amount_in, 4999999999670943150);
+        assert_eq!(
+            price_graph.all.get(&edge_id),
+            Some(&Edge::new_v2(
+                reserve_weth + amount_in,
+                reserve_usdc - 9343369893,
+                Fee::from_units(300),
+                ExchangeId::Sushi
+            ))
+        );
+    }
+
+    #[test]
+    fn update_edge_v2_extreme_amounts() {
+        let (reserve_in, reserve_out) = (eth(1_000), 2_000_000_000000_u128);
+        for edge in [
+            Edge::new_v2(
+                reserve_in,
+                reserve_out,
+                Fee::from_units(300),
+                ExchangeId::Sushi,
+            ),
+            Edge::new_camelot_v2(
+                reserve_in,
+                reserve_out,
+                Fee::from_units(300),
+                Fee::from_units(300),
+                None,
+            ),
+        ] {
+            // taking the whole reserve is unfillable, the edge is left as is
+            for amount_out in [reserve_out, reserve_out + 1, u128::MAX] {
+                let mut updated = edge;
+                assert_eq!(
+                    updated.calculate_amount_in_updating(amount_out, None),
+                    u128::MAX
+                );
+                assert_eq!(updated, edge);
+            }
+            // an amount in near u128::MAX saturates the input reserve
+            let mut updated = edge;
+            let amount_out = updated.calculate_amount_out_updating(u128::MAX - 1, None);
+            assert!(amount_out < reserve_out);
+            assert_eq!(
+                updated,
+                match edge {
+                    Edge::UniV2 { .. } => Edge::new_v2(
+                        u128::MAX,
+                        reserve_out - amount_out,
+                        Fee::from_units(300),
+                        ExchangeId::Sushi
+                    ),
+                    _ => Edge::new_camelot_v2(
+                        u128::MAX,
+                        reserve_out - amount_out,
+                        Fee::from_units(300),
+                        Fee::from_units(300),
+                        None
+                    ),
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn camelot_edge_directional_fees() {
+        let edge = Edge::new_camelot_v2(
+            eth(1_000),
+            2_000_000_000,
+            Fee::from_units(300),
+            Fee::from_units(100),
+            Some((18, 6)),
+        );
+        let inverse = edge.inverse();
+        assert_eq!(
+            inverse,
+            Edge::new_camelot_v2(
+                2_000_000_000,
+                eth(1_000),
+                Fee::from_units(100),
+                Fee::from_units(300),
+                Some((6, 18))
+            )
+        );
+        assert_eq!(inverse.inverse(), edge);
+        // keyed without fee
+        assert_eq!(
+            edge.id(Token::WETH, Token::USDC),
+            Edge::hash(
+                Token::WETH.id(),
+                Token::USDC.id(),
+                ExchangeId::Camelot as u8,
+                Fee::ZERO
+            )
+        );
+
+        let mut updated = edge;
+        let amount_out = updated.calculate_amount_out_updating(eth(1), None);
+        assert_eq!(amount_out, edge.calculate_amount_out(eth(1)));
+        assert_eq!(
+            updated,
+            Edge::new_camelot_v2(
+                eth(1_001),
+                2_000_000_000 - amount_out,
+                Fee::from_units(300),
+                Fee::from_units(100),
+                Some((18, 6))
+            )
+        );
+    }
+
+    #[test]
+    fn balancer_weighted_edge() {
+        // 80/20 WETH/USDC, ~2000 USDC/WETH
+        let (weight_weth, weight_usdc) = (800_000_000_000_000_000, 200_000_000_000_000_000);
+        let fee = Fee::from_units(300);
+        let edge = Edge::new_balancer_weighted(
+            (eth(4_000), 2_000_000_000_000),
+            (weight_weth, weight_usdc),
+            fee,
+        );
+        let inverse = edge.inverse();
+        assert_eq!(
+            inverse,
+            Edge::new_balancer_weighted(
+                (2_000_000_000_000, eth(4_000)),
+                (weight_usdc, weight_weth),
+                fee
+            )
+        );
+        assert_eq!(inverse.inverse(), edge);
+        assert!((edge.price() * 1e12 - 2_000.0).abs() < 1e-6);
+        // keyed without fee
+        assert_eq!(edge.fee(), Fee::ZERO);
+        assert_eq!(
+            edge.id(Token::WETH, Token::USDC),
+            Edge::hash(
+                Token::WETH.id(),
+                Token::USDC.id(),
+                ExchangeId::Balancer as u8,
+                Fee::ZERO
+            )
+        );
+
+        let mut updated = edge;
+        let amount_out = updated.calculate_amount_out_updating(eth(1), None);
+        assert_eq!(amount_out, edge.calculate_amount_out(eth(1)));
+        assert!(amount_out < 2_000_000000 && amount_out > 1_990_000000);
+        assert_eq!(
+            updated,
+            Edge::new_balancer_weighted(
+                (eth(4_001), 2_000_000_000_000 - amount_out),
+                (weight_weth, weight_usdc),
+                fee
+            )
+        );
+        let amount_out_f = edge.calculate_amount_out_f(eth(1));
+        assert!((amount_out as f64 - amount_out_f).abs() / amount_out_f < 1e-6);
+    }
+
+    #[test]
+    fn curve_stable_edge() {
+        // USDC/DAI 2m/1m, A = 1000, 0.04% fee
+        let fee = Fee::from_units(40);
+        let edge = Edge::new_curve_stable(
+            (2_000_000_000000, 1_000_000 * 10_u128.pow(18)),
+            1_000,
+            fee,
+            (6, 18),
+        );
+        let inverse = edge.inverse();
+        assert_eq!(
+            inverse,
+            Edge::new_curve_stable(
+                (1_000_000 * 10_u128.pow(18), 2_000_000_000000),
+                1_000,
+                fee,
+                (18, 6)
+            )
+        );
+        assert_eq!(inverse.inverse(), edge);
+        // imbalanced but near the peg, excess USDC is cheaper
+        let price = edge.price() / 1e12;
+        assert!(price < 1.0 && price > 0.99);
+        assert_eq!(edge.fee(), Fee::ZERO);
+        assert_eq!(
+            edge.id(Token::USDC, Token::DAI),
+            Edge::hash(
+                Token::USDC.id(),
+                Token::DAI.id(),
+                ExchangeId::Curve as u8,
+                Fee::ZERO
+            )
+        );
+
+        let mut updated = edge;
+        let amount_out = updated.calculate_amount_out_updating(1_000_000000, None);
+        assert_eq!(amount_out, edge.calculate_amount_out(1_000_000000));
+        assert_eq!(
+            updated,
+            Edge::new_curve_stable(
+                (2_001_000_000000, 1_000_000 * 10_u128.pow(18) - amount_out),
+                1_000,
+                fee,
+                (6, 18)
+            )
+        );
+    }
+
+    #[test]
+    fn solidly_edge() {
+        // 0.05% fee stable USDC/USDT pair
+        let (reserve_usdc, reserve_usdt) = (1_000_000_000000, 2_000_000_000000);
+        let edge = Edge::new_solidly(
+            reserve_usdc,
+            reserve_usdt,
+            Fee::from_units(50),
+            Some((6, 6)),
+        );
+        let inverse = edge.inverse();
+        assert_eq!(
+            inverse,
+            Edge::new_solidly(
+                reserve_usdt,
+                reserve_usdc,
+                Fee::from_units(50),
+                Some((6, 6))
+            )
+        );
+        assert_eq!(inverse.inverse(), edge);
+        assert_eq!(edge.exchange_id(), ExchangeId::Ramses);
+        assert_eq!(
+            edge.id(Token::USDC, Token::USDT),
+            Edge::hash(
+                Token::USDC.id(),
+                Token::USDT.id(),
+                ExchangeId::Ramses as u8,
+                Fee::from_units(50)
+            )
+        );
+
+        let mut updated = edge;
+        let amount_out = updated.calculate_amount_out_updating(1_000_000000, None);
+        assert_eq!(amount_out, edge.calculate_amount_out(1_000_000000));
+        // excess USDT trades above the peg
+        assert!(amount_out > 1_000_000000);
+        // the fee leaves the pair
+        assert_eq!(
+            updated,
+            Edge::new_solidly(
+                reserve_usdc + 999_500000,
+                reserve_usdt - amount_out,
+                Fee::from_units(50),
+                Some((6, 6))
+            )
+        );
+
+        // an amount in near u128::MAX overflows the stable math, as the pair would revert
+        let mut updated = edge;
+        assert_eq!(updated.calculate_amount_out_updating(u128::MAX, None), 0);
+        assert_eq!(
+            less_fee(u128::MAX, Fee::from_units(50)),
+            u128::MAX - u128::MAX / 2_000
+        );
+    }
+
+    #[test]
+    fn kyber_elastic_edge() {
+        // 1:1 pool, 0.04% fee
+        let (sqrt_p_x96, liquidity) = (U256::from(2_u128.pow(96)), U256::exp10(24));
+        let edge = Edge::new_kyber_elastic(sqrt_p_x96, liquidity, Fee::from_units(40), true);
+        let inverse = edge.inverse();
+        assert_eq!(
+            inverse,
+            Edge::new_kyber_elastic(sqrt_p_x96, liquidity, Fee::from_units(40), false)
+        );
+        assert_eq!(edge.exchange_id(), ExchangeId::KyberElastic);
+        assert_eq!(edge.price(), 1.0);
+        // both directions are keyed by the pool's token0 => token1 edge
+        assert_eq!(
+            inverse.pool_id(Token::USDC, Token::WETH),
+            edge.id(Token::WETH, Token::USDC)
+        );
+
+        let mut updated = edge;
+        let amount_out = updated.calculate_amount_out_updating(10_u128.pow(20), None);
+        assert_eq!(amount_out, edge.calculate_amount_out(10_u128.pow(20)));
+        assert!(amount_out < 10_u128.pow(20));
+        // the fee is reinvested
+        let Edge::KyberElastic {
+            sqrt_p_x96: new_sqrt_p_x96,
+            liquidity: new_liquidity,
+            ..
+        } = updated
+        else {
+            unreachable!()
+        };
+        assert!(new_sqrt_p_x96 < sqrt_p_x96);
+        assert!(new_liquidity > liquidity);
+    }
+
+    #[test]
+    fn quote_best_route() {
+        let mut graph = PriceGraph::empty();
+        // a thin direct pool
+        graph.add_edge(
+            Token::USDC,
+            Token::WETH,
+            Edge::new_v2(
+                2_000_000000,
+                eth(1),
+                Fee::from_units(300),
+                ExchangeId::Sushi,
+            ),
+        );
+        // deep pools via ARB
+        graph.add_edge(
+            Token::USDC,
+            Token::ARB,
+            Edge::new_v2(
+                2_000_000_000000,
+                eth(2_000_000),
+                Fee::from_units(300),
+                ExchangeId::Sushi,
+            ),
+        );
+        graph.add_edge(
+            Token::WETH,
+            Token::ARB,
+            Edge::new_v2(
+                eth(1_000),
+                eth(2_000_000),
+                Fee::from_units(300),
+                ExchangeId::Sushi,
+            ),
+        );
+
+        let amount_in = 1_000_000000;
+        let quote = graph.quote(Token::USDC, Token::WETH, amount_in).unwrap();
+        let via_arb = graph
+            .amount_out(Token::USDC, Token::ARB, amount_in)
+            .and_then(|arb| graph.amount_out(Token::ARB, Token::WETH, arb))
+            .unwrap();
+        assert_eq!(quote.amount_out, via_arb);
+        assert!(
+            via_arb
+                > graph
+                    .amount_out(Token::USDC, Token::WETH, amount_in)
+                    .unwrap()
+        );
+        assert_eq!(quote.trade.len, 2);
+        assert_eq!(quote.trade.path[0].token_out, Token::ARB.id());
+
+        // a small trade is best direct
+        let quote = graph
+            .quote(Token::WETH, Token::USDC, eth(1) / 1_000)
+            .unwrap();
+        assert_eq!(quote.trade.len, 1);
+        assert_eq!(
+            Some(quote.amount_out),
+            graph.amount_out(Token::WETH, Token::USDC, eth(1) / 1_000)
+        );
+
+        assert_eq!(graph.quote(Token::USDC, Token::GMX, amount_in), None);
+        assert_eq!(graph.quote(Token::USDC, Token::USDC, amount_in), None);
+    }
+
+    #[test]
+    fn update_edge_crosses_ticks() {
+        let Pair { token0, token1, .. } = Pair::new(
+            Token::USDC,
+            Token::WETH,
+            Fee::from_pips(500),
+            ExchangeId::Uniswap,
+        );
+        let liquidity = U256::from(eth(1));
+        let ticks = TickLiquidity::new([(-60, -(eth(1) as i128)), (60, eth(1) as i128)]);
+
+        let mut price_graph = PriceGraph::empty();
+        price_graph.add_edge(
+            token0,
+            token1,
+            Edge::new_v3(*uniswap_v3::X96, liquidity, Fee::from_pips(500), true),
+        );
+        price_graph.set_ticks(token0, token1, Fee::from_pips(500), ticks.clone());
+
+        // token1 => token0 trade uses the pool's ticks
+        let edge_id =
+            Edge::new_v3(U256::zero(), U256::zero(), Fee::from_pips(500), false).id(token1, token0);
+        let amount_out = price_graph
+            .update_edge_in(token1, token0, edge_id, eth(1) / 10)
+            .unwrap();
+        let (sqrt_p_x96, next_liquidity, expected) = uniswap_v3::get_amount_out_crossing(
+            eth(1) / 10,
+            &uniswap_v3::X96,
+            &liquidity,
+            500,
+            false,
+            &ticks,
+        );
+        assert_eq!(amount_out, expected);
+        assert_eq!(
+            price_graph.all.get(&edge_id),
+            Some(&Edge::new_v3(
+                sqrt_p_x96,
+                next_liquidity,
+                Fee::from_pips(500),
+                false
+            ))
+        );
+    }
 }
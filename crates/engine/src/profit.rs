@@ -0,0 +1,123 @@
+//! Arb profitability model
+//!
+//! Nets the order tx gas cost of the arb's path, priced in the arb's start token, from its expected output before
+//! checking the min. profit threshold
+use std::sync::Arc;
+
+use log::debug;
+
+use crate::{
+    gas::GasModel,
+    price_graph::{Arb, CompositeTrade, PriceGraph},
+    types::Token,
+};
+
+/// Decides whether arbs are profitable enough to execute
+#[derive(Clone, Debug)]
+pub struct ProfitModel {
+    /// Min. ratio of net amount out to amount in
+    min_profit_threshold: f64,
+    /// Max. fee per gas (wei) paid by order txs
+    max_fee_per_gas: u128,
+    /// Order tx gas by path
+    gas: Arc<GasModel>,
+}
+
+impl ProfitModel {
+    /// `min_profit` the minimum profit required for trade execution, expressed as a percent e.g 0.007f64 = 0.007%
+    /// `max_fee_per_gas` the max. fee per gas (wei) paid by order txs
+    /// `gas` the order tx gas model
+    pub fn new(min_profit: f64, max_fee_per_gas: u64, gas: Arc<GasModel>) -> Self {
+        Self {
+            min_profit_threshold: 1.0_f64 + min_profit,
+            max_fee_per_gas: max_fee_per_gas as u128,
+            gas,
+        }
+    }
+    /// Change the min. profit required for trade execution
+    pub fn set_min_profit(&mut self, min_profit: f64) {
+        self.min_profit_threshold = 1.0_f64 + min_profit;
+    }
+    /// The gas cost of an order tx for `trade` in units of `token`, priced with the best WETH/`token` edge of `graph`
+    /// Returns `None` if gas can't be priced in `token`
+    pub fn gas_cost(
+        &self,
+        graph: &PriceGraph,
+        trade: &CompositeTrade,
+        token: Token,
+    ) -> Option<u128> {
+        let gas_cost_wei = self.gas.estimate(trade) as u128 * self.max_fee_per_gas;
+        if gas_cost_wei == 0 || token == Token::WETH {
+            return Some(gas_cost_wei);
+        }
+        graph.amount_out(Token::WETH, token, gas_cost_wei)
+    }
+    /// The ratio of `arb`'s expected amount out net of gas to its amount in
+    /// Returns `None` if the arb is not profitable after gas, or gas can't be priced in the start `token`
+    pub fn net_profit_ratio(&self, graph: &PriceGraph, arb: &Arb, token: Token) -> Option<f64> {
+        let gas_cost = match self.gas_cost(graph, &arb.trade, token) {
+            Some(gas_cost) => gas_cost,
+            None => {
+                debug!("no gas price for token: {:?}", token);
+                return None;
+            }
+        };
+        let ratio = arb.amount_out.saturating_sub(gas_cost) as f64 / arb.amount_in as f64;
+        (ratio > self.min_profit_threshold).then_some(ratio)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        price_graph::Edge,
+        types::{ExchangeId, Fee},
+    };
+
+    #[test]
+    fn net_profit_after_gas() {
+        let mut graph = PriceGraph::empty();
+        // 2,000 usdc / weth
+        graph.add_edge(
+            Token::USDC,
+            Token::WETH,
+            Edge::new_v2(
+                2_000_000_000_000_000,
+                1_000_000 * 10_u128.pow(18),
+                Fee::ZERO,
+                ExchangeId::Sushi,
+            ),
+        );
+        let arb = Arb {
+            amount_in: 1_000_000_000,
+            amount_out: 1_001_000_000,
+            trade: Default::default(),
+        };
+        let gas = Arc::new(GasModel::default());
+        // 0.1 gwei
+        let model = ProfitModel::new(0.0, 100_000_000, Arc::clone(&gas));
+        let gas_cost = model.gas_cost(&graph, &arb.trade, Token::USDC).unwrap();
+        let expected_gas_cost =
+            gas.estimate(&arb.trade) as u128 * 100_000_000 * 2_000 / 10_u128.pow(12);
+        assert!(gas_cost.abs_diff(expected_gas_cost) <= 1);
+        assert_eq!(
+            model.net_profit_ratio(&graph, &arb, Token::USDC),
+            Some((arb.amount_out - gas_cost) as f64 / arb.amount_in as f64)
+        );
+
+        // gas eats the profit
+        let model = ProfitModel::new(0.0, 10_000_000_000, Arc::clone(&gas));
+        assert!(model.gas_cost(&graph, &arb.trade, Token::USDC).unwrap() > arb.profit());
+        assert_eq!(model.net_profit_ratio(&graph, &arb, Token::USDC), None);
+
+        // gas free, profit below threshold
+        let model = ProfitModel::new(0.01, 0, Arc::clone(&gas));
+        assert_eq!(model.gas_cost(&graph, &arb.trade, Token::USDC), Some(0));
+        assert_eq!(model.net_profit_ratio(&graph, &arb, Token::USDC), None);
+
+        // no WETH edge to price gas
+        let model = ProfitModel::new(0.0, 100_000_000, gas);
+        assert_eq!(model.net_profit_ratio(&graph, &arb, Token::ARB), None);
+    }
+}
@@ -0,0 +1,224 @@
+//! Price queries
+//!
+//! The engine publishes its live price graph (synced prices with the feed's txs applied) to a `PriceView` after each
+//! simulated batch. External tools query it over a minimal HTTP endpoint e.g.
+//! `GET /quote?token_in=USDC&token_out=WETH&amount_in=1000000000` returns the best route and amount out as JSON
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{Arc, PoisonError, RwLock, TryLockError},
+};
+
+use log::{debug, info};
+use serde::Serialize;
+use tokio::net::TcpListener;
+
+use crate::{
+    config, http_server,
+    price_graph::{PriceGraph, Quote},
+    types::{Fee, Token},
+};
+
+/// The engine's latest price graph, read-only to queries
+#[derive(Debug, Default)]
+pub struct PriceView {
+    graph: RwLock<Option<PriceGraph>>,
+}
+
+impl PriceView {
+    /// Publish `price_graph` as the latest view, reusing the previous view's memory
+    ///
+    /// The engine never waits on queries, the publish is skipped while a query holds the view
+    pub fn publish(&self, price_graph: &PriceGraph) {
+        let mut graph = match self.graph.try_write() {
+            Ok(graph) => graph,
+            Err(TryLockError::WouldBlock) => {
+                debug!("price view busy, skip publish");
+                return;
+            }
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+        };
+        match graph.as_mut() {
+            Some(graph) => graph.clone_from(price_graph),
+            None => *graph = Some(price_graph.clone()),
+        }
+    }
+    /// Quote trading `amount_in` of `token_in` for `token_out` at the latest view, with the view's block number
+    ///
+    /// Returns `None` before the first view is published
+    pub fn quote(
+        &self,
+        token_in: Token,
+        token_out: Token,
+        amount_in: u128,
+    ) -> Option<(u64, Option<Quote>)> {
+        let graph = self.graph.read().unwrap_or_else(PoisonError::into_inner);
+        let graph = graph.as_ref()?;
+        Some((
+            graph.block_number(),
+            graph.quote(token_in, token_out, amount_in),
+        ))
+    }
+}
+
+/// A quote response
+#[derive(Debug, Serialize)]
+struct QuoteResponse {
+    /// Block of the prices quoted
+    block_number: u64,
+    token_in: Token,
+    token_out: Token,
+    amount_in: u128,
+    amount_out: u128,
+    route: Vec<Hop>,
+}
+
+/// A trade of a quoted route
+#[derive(Debug, Serialize)]
+struct Hop {
+    token_in: Token,
+    token_out: Token,
+    fee: Fee,
+    exchange_id: u8,
+}
+
+/// Answer the HTTP request line `request` from `view`, returns (status, JSON body)
+fn respond(view: &PriceView, request: &str) -> (u16, String) {
+    let error = |status: u16, message: &str| (status, format!("{{\"error\":\"{message}\"}}"));
+    let Some(query) = request
+        .strip_prefix("GET /quote?")
+        .and_then(|rest| rest.split_whitespace().next())
+    else {
+        return error(404, "not found");
+    };
+    let (mut token_in, mut token_out, mut amount_in) = (None, None, None);
+    for (key, value) in query.split('&').filter_map(|param| param.split_once('=')) {
+        match key {
+            "token_in" => token_in = config::active().registry().by_symbol(value),
+            "token_out" => token_out = config::active().registry().by_symbol(value),
+            "amount_in" => amount_in = value.parse::<u128>().ok(),
+            _ => {}
+        }
+    }
+    let (Some(token_in), Some(token_out), Some(amount_in)) = (token_in, token_out, amount_in)
+    else {
+        return error(
+            400,
+            "expected token_in, token_out (symbols), and amount_in (units)",
+        );
+    };
+    let Some((block_number, quote)) = view.quote(token_in, token_out, amount_in) else {
+        return error(503, "no prices yet");
+    };
+    let Some(quote) = quote else {
+        return error(404, "no route");
+    };
+    let response = QuoteResponse {
+        block_number,
+        token_in,
+        token_out,
+        amount_in,
+        amount_out: quote.amount_out,
+        route: quote
+            .trade
+            .hops()
+            .iter()
+            .map(|hop| Hop {
+                token_in: Token::new(hop.token_in),
+                token_out: Token::new(hop.token_out),
+                fee: hop.fee,
+                exchange_id: hop.exchange_id,
+            })
+            .collect(),
+    };
+    match serde_json::to_string(&response) {
+        Ok(body) => (200, body),
+        Err(_) => error(500, "quote encoding"),
+    }
+}
+
+/// Serve price queries from `view` over HTTP at `addr`, `GET /quote`
+pub async fn serve(view: Arc<PriceView>, addr: SocketAddr) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("price queries 💱: http://{addr}/quote");
+    http_server::serve("quote", listener, move |request| {
+        let (status, body) = respond(&view, request);
+        (status, "application/json", body)
+    })
+    .await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{price_graph::Edge, types::ExchangeId};
+
+    use super::*;
+
+    #[test]
+    fn responds_to_quotes() {
+        let view = PriceView::default();
+        let request =
+            "GET /quote?token_in=WETH&token_out=USDC&amount_in=1000000000000000000 HTTP/1.1";
+        assert_eq!(respond(&view, request).0, 503);
+
+        let mut graph = PriceGraph::empty();
+        graph.add_edge(
+            Token::USDC,
+            Token::WETH,
+            Edge::new_v2(
+                2_000_000_000000,
+                1_000_000000000000000000,
+                Fee::from_units(300),
+                ExchangeId::Sushi,
+            ),
+        );
+        graph.set_block_number(91_146_328);
+        view.publish(&graph);
+
+        let (status, body) = respond(&view, request);
+        assert_eq!(status, 200);
+        let amount_out = graph
+            .amount_out(Token::WETH, Token::USDC, 1_000000000000000000)
+            .unwrap();
+        assert_eq!(
+            body,
+            format!(
+                "{{\"block_number\":91146328,\"token_in\":\"WETH\",\"token_out\":\"USDC\",\"amount_in\":1000000000000000000,\"amount_out\":{amount_out},\"route\":[{{\"token_in\":\"WETH\",\"token_out\":\"USDC\",\"fee\":3000,\"exchange_id\":{}}}]}}",
+                ExchangeId::Sushi as u8
+            )
+        );
+
+        assert_eq!(
+            respond(
+                &view,
+                "GET /quote?token_in=WETH&token_out=GMX&amount_in=1 HTTP/1.1"
+            )
+            .0,
+            404
+        );
+        assert_eq!(
+            respond(&view, "GET /quote?token_in=WETH&amount_in=1 HTTP/1.1").0,
+            400
+        );
+        assert_eq!(respond(&view, "GET /metrics HTTP/1.1").0, 404);
+    }
+
+    #[test]
+    fn publish_skips_while_queried() {
+        let view = PriceView::default();
+        let mut graph = PriceGraph::empty();
+        graph.set_block_number(1);
+        view.publish(&graph);
+
+        // a query holds the view, the engine doesn't wait
+        let reader = view.graph.read().unwrap();
+        graph.set_block_number(2);
+        view.publish(&graph);
+        drop(reader);
+        assert_eq!(view.quote(Token::WETH, Token::USDC, 1), Some((1, None)));
+
+        view.publish(&graph);
+        assert_eq!(view.quote(Token::WETH, Token::USDC, 1), Some((2, None)));
+    }
+}
@@ -0,0 +1,239 @@
+//! Order tx request encoding
+//!
+//! Signed order txs are RLP encoded and written as 'eth_sendRawTransaction' requests into reused buffers,
+//! so submission does not allocate once the buffers have grown to fit an order tx
+use ethers::types::{
+    transaction::eip2718::TypedTransaction, Eip1559TransactionRequest, NameOrAddress, Signature,
+    U256,
+};
+
+/// Request JSON up to the hex encoded tx
+const REQUEST_PREFIX: &str =
+    r#"{"id":1337,"jsonrpc":"2.0","method":"eth_sendRawTransaction","params":["0x"#;
+/// Request JSON following the hex encoded tx
+const REQUEST_SUFFIX: &str = r#""]}"#;
+/// EIP-2718 type of EIP-1559 txs
+const EIP1559_TX_TYPE: u8 = 0x02;
+/// Bytes reserved ahead of the tx fields for the tx type and (max. 9 byte) list header
+const HEADER_RESERVED: usize = 10;
+/// Initial buffer capacity (bytes), order txs encode to ~300 bytes
+const DEFAULT_CAPACITY: usize = 1_024;
+
+const HEX_CHARS: &[u8; 16] = b"0123456789abcdef";
+
+/// Reusable buffers for encoding order tx requests
+pub(crate) struct RawTxEncoder {
+    /// RLP encoded signed tx
+    rlp: Vec<u8>,
+    /// The JSON-RPC request
+    request: String,
+}
+
+impl Default for RawTxEncoder {
+    fn default() -> Self {
+        Self {
+            rlp: Vec::with_capacity(DEFAULT_CAPACITY),
+            request: String::with_capacity(REQUEST_PREFIX.len() + 2 * DEFAULT_CAPACITY),
+        }
+    }
+}
+
+impl RawTxEncoder {
+    /// Encode `tx` signed with `signature` as an 'eth_sendRawTransaction' request
+    ///
+    /// EIP-1559 txs without an access list (i.e. order txs) are hand encoded, others fall back to ethers (allocating)
+    pub fn signed_tx_request(&mut self, tx: &TypedTransaction, signature: &Signature) -> &str {
+        self.rlp.clear();
+        match tx {
+            TypedTransaction::Eip1559(tx) if tx.access_list.0.is_empty() => {
+                if let Some(start) = encode_eip1559(tx, signature, &mut self.rlp) {
+                    write_request(&self.rlp[start..], &mut self.request);
+                    return self.request.as_str();
+                }
+            }
+            _ => {}
+        }
+        let signed_tx = tx.rlp_signed(signature);
+        write_request(&signed_tx, &mut self.request);
+        self.request.as_str()
+    }
+    /// Encode the RLP encoded signed tx `raw_tx` as an 'eth_sendRawTransaction' request
+    pub fn raw_tx_request(&mut self, raw_tx: &[u8]) -> &str {
+        write_request(raw_tx, &mut self.request);
+        self.request.as_str()
+    }
+}
+
+/// Write the 'eth_sendRawTransaction' request of `raw_tx` into `request`
+fn write_request(raw_tx: &[u8], request: &mut String) {
+    request.clear();
+    request.push_str(REQUEST_PREFIX);
+    for byte in raw_tx {
+        request.push(HEX_CHARS[(byte >> 4) as usize] as char);
+        request.push(HEX_CHARS[(byte & 0x0f) as usize] as char);
+    }
+    request.push_str(REQUEST_SUFFIX);
+}
+
+/// Encode the signed EIP-1559 `tx` (with an empty access list) into `buf`
+///
+/// Returns the start of the encoded tx in `buf` or `None` if `tx` can't be encoded e.g. `to` is an ENS name
+fn encode_eip1559(
+    tx: &Eip1559TransactionRequest,
+    signature: &Signature,
+    buf: &mut Vec<u8>,
+) -> Option<usize> {
+    let chain_id = tx.chain_id?.as_u64();
+    // the list header precedes the fields but depends on their length, reserve space for it
+    buf.resize(HEADER_RESERVED, 0);
+    encode_u64(buf, chain_id);
+    encode_opt_u256(buf, tx.nonce);
+    encode_opt_u256(buf, tx.max_priority_fee_per_gas);
+    encode_opt_u256(buf, tx.max_fee_per_gas);
+    encode_opt_u256(buf, tx.gas);
+    match tx.to.as_ref() {
+        Some(NameOrAddress::Address(to)) => encode_bytes(buf, to.as_bytes()),
+        Some(NameOrAddress::Name(_)) => return None,
+        None => encode_bytes(buf, &[]),
+    }
+    encode_opt_u256(buf, tx.value);
+    encode_bytes(buf, tx.data.as_deref().unwrap_or_default());
+    // empty access list
+    buf.push(0xc0);
+    // y parity, from an EIP-155 or legacy `v`
+    let y_parity = match signature.v {
+        0 | 1 => signature.v,
+        27 | 28 => signature.v - 27,
+        v => v - chain_id * 2 - 35,
+    };
+    encode_u64(buf, y_parity);
+    encode_u256(buf, signature.r);
+    encode_u256(buf, signature.s);
+
+    let mut header = [0_u8; HEADER_RESERVED];
+    header[0] = EIP1559_TX_TYPE;
+    let header_len = 1 + encode_header(&mut header[1..], 0xc0, buf.len() - HEADER_RESERVED);
+    let start = HEADER_RESERVED - header_len;
+    buf[start..HEADER_RESERVED].copy_from_slice(&header[..header_len]);
+
+    Some(start)
+}
+
+/// Write the RLP header of a string (`offset` 0x80) or list (`offset` 0xc0) of `len` bytes into `out`
+///
+/// Returns the header length
+fn encode_header(out: &mut [u8], offset: u8, len: usize) -> usize {
+    if len < 56 {
+        out[0] = offset + len as u8;
+        return 1;
+    }
+    let len_be = len.to_be_bytes();
+    let skip = len.leading_zeros() as usize / 8;
+    out[0] = offset + 55 + (len_be.len() - skip) as u8;
+    out[1..=len_be.len() - skip].copy_from_slice(&len_be[skip..]);
+    1 + len_be.len() - skip
+}
+
+/// Append the RLP encoding of the string `bytes`
+fn encode_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        buf.push(bytes[0]);
+        return;
+    }
+    let mut header = [0_u8; 9];
+    let header_len = encode_header(&mut header, 0x80, bytes.len());
+    buf.extend_from_slice(&header[..header_len]);
+    buf.extend_from_slice(bytes);
+}
+
+/// Append the RLP encoding of the integer `value`
+fn encode_u64(buf: &mut Vec<u8>, value: u64) {
+    let skip = value.leading_zeros() as usize / 8;
+    encode_bytes(buf, &value.to_be_bytes()[skip..]);
+}
+
+/// Append the RLP encoding of the integer `value`
+fn encode_u256(buf: &mut Vec<u8>, value: U256) {
+    let mut value_be = [0_u8; 32];
+    value.to_big_endian(&mut value_be);
+    let skip = value.leading_zeros() as usize / 8;
+    encode_bytes(buf, &value_be[skip..]);
+}
+
+/// Append the RLP encoding of the integer `value`, the empty string if `None`
+fn encode_opt_u256(buf: &mut Vec<u8>, value: Option<U256>) {
+    match value {
+        Some(value) => encode_u256(buf, value),
+        None => encode_bytes(buf, &[]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::types::{
+        transaction::eip2930::{AccessList, AccessListItem},
+        Address, Bytes, H256,
+    };
+    use ethers_signers::{LocalWallet, Signer};
+
+    use super::*;
+
+    fn wallet() -> LocalWallet {
+        "0000000000000000000000000000000000000000000000000000000000000001"
+            .parse::<LocalWallet>()
+            .unwrap()
+            .with_chain_id(42_161_u64)
+    }
+
+    fn expected_request(raw_tx: &[u8]) -> String {
+        format!(
+            r#"{{"id":1337,"jsonrpc":"2.0","method":"eth_sendRawTransaction","params":["0x{}"]}}"#,
+            hex::encode(raw_tx)
+        )
+    }
+
+    #[test]
+    fn encode_send_raw_tx_json() {
+        assert_eq!(
+            RawTxEncoder::default().raw_tx_request(b"10334551124512451245012343241234"),
+            r#"{"id":1337,"jsonrpc":"2.0","method":"eth_sendRawTransaction","params":["0x3130333334353531313234353132343531323435303132333433323431323334"]}"#,
+        );
+    }
+
+    #[test]
+    fn encode_signed_tx_matches_ethers() {
+        let wallet = wallet();
+        let mut encoder = RawTxEncoder::default();
+        let order_tx = Eip1559TransactionRequest::new()
+            .chain_id(42_161_u64)
+            .nonce(5_u64)
+            .to(Address::repeat_byte(0x11))
+            .gas(1_327_902_u64)
+            .max_fee_per_gas(200_000_000_u64)
+            .max_priority_fee_per_gas(200_000_000_u64)
+            .data(Bytes::from(vec![0x5a_u8; 100]));
+        let txs: [TypedTransaction; 4] = [
+            order_tx.clone().into(),
+            // long calldata i.e. multi-byte length headers
+            order_tx.clone().data(vec![0xff_u8; 1_000]).into(),
+            // zero and unset fields
+            Eip1559TransactionRequest::new()
+                .chain_id(42_161_u64)
+                .nonce(0_u64)
+                .into(),
+            // access lists fall back to ethers
+            order_tx
+                .access_list(AccessList(vec![AccessListItem {
+                    address: Address::repeat_byte(0x22),
+                    storage_keys: vec![H256::repeat_byte(0x33)],
+                }]))
+                .into(),
+        ];
+
+        for tx in txs.iter() {
+            let signature = wallet.sign_transaction_sync(tx).unwrap();
+            let expected = expected_request(&tx.rlp_signed(&signature));
+            assert_eq!(encoder.signed_tx_request(tx, &signature), expected);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Reverted order tx analysis
+//!
+//! Receipts carry no revert data so reverted order txs are replayed with `eth_call` at the parent of their block,
+//! the executor contract's custom errors and common pool errors are decoded to classify the failure.
+//! Txs earlier in the block are not replayed, an order which succeeds at the parent was taken by one of them
+use ethers::types::{
+    transaction::eip2718::TypedTransaction, BlockId, Bytes, Eip1559TransactionRequest,
+    TransactionReceipt, TxHash, U256,
+};
+use ethers_providers::{Middleware, MiddlewareError};
+use hex_literal::hex;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+/// `Slippage(uint256)` the trade returned less than the min. amount out
+const SLIPPAGE_SELECTOR: [u8; 4] = hex!("5188f80d");
+/// `Loss(uint256)` the trade returned less than the loan
+const LOSS_SELECTOR: [u8; 4] = hex!("b65b2e08");
+/// `Error(string)` i.e. `require`/`revert` with a message
+const ERROR_SELECTOR: [u8; 4] = hex!("08c379a0");
+/// Uniswap V3 pool errors due to a lack of liquidity for the trade
+const LIQUIDITY_ERRORS: [&str; 3] = ["SPL", "IIA", "AS"];
+/// Prefix of Uniswap V2 style pool errors due to a lack of liquidity e.g. `INSUFFICIENT_OUTPUT_AMOUNT`
+const LIQUIDITY_ERROR_PREFIX: &str = "INSUFFICIENT_";
+
+/// Why an order tx reverted
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevertReason {
+    /// The trade returned less than the min. amount out by the given amount
+    Slippage(u128),
+    /// The trade returned less than the loan by the given amount
+    Loss(u128),
+    /// The order succeeds at the parent block, a tx earlier in the block took the opportunity
+    AlreadyArbed,
+    /// A pool could not fill the trade
+    InsufficientLiquidity,
+    /// The tx ran out of gas
+    OutOfGas,
+    /// Unrecognized revert, with its message if any
+    Unknown(Option<String>),
+}
+
+impl RevertReason {
+    /// The reason as a metric label
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Slippage(_) => "slippage",
+            Self::Loss(_) => "loss",
+            Self::AlreadyArbed => "already_arbed",
+            Self::InsufficientLiquidity => "insufficient_liquidity",
+            Self::OutOfGas => "out_of_gas",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+    /// Classify the revert `data` of an order tx
+    pub fn from_revert_data(data: &[u8]) -> Self {
+        if data.len() < 4 {
+            return Self::Unknown(None);
+        }
+        let (selector, args) = data.split_at(4);
+        let amount = || U256::from_big_endian(args.get(..32).unwrap_or_default()).low_u128();
+        match selector {
+            s if s == SLIPPAGE_SELECTOR => Self::Slippage(amount()),
+            s if s == LOSS_SELECTOR => Self::Loss(amount()),
+            s if s == ERROR_SELECTOR => match decode_error_message(args) {
+                Some(message)
+                    if LIQUIDITY_ERRORS.contains(&message.as_str())
+                        || message.starts_with(LIQUIDITY_ERROR_PREFIX) =>
+                {
+                    Self::InsufficientLiquidity
+                }
+                message => Self::Unknown(message),
+            },
+            _ => Self::Unknown(None),
+        }
+    }
+}
+
+/// Decode the message of an `Error(string)` revert from its ABI encoded `args`
+fn decode_error_message(args: &[u8]) -> Option<String> {
+    let len = U256::from_big_endian(args.get(32..64)?);
+    if len > U256::from(args.len()) {
+        return None;
+    }
+    let message = args.get(64..64 + len.as_usize())?;
+    // Uniswap V2 style messages are prefixed e.g. 'UniswapV2: INSUFFICIENT_LIQUIDITY'
+    let message = String::from_utf8_lossy(message);
+    Some(
+        message
+            .rsplit_once(": ")
+            .map_or(message.as_ref(), |(_, m)| m)
+            .to_string(),
+    )
+}
+
+/// Classify why the order tx `tx_hash` reverted given its `receipt`
+///
+/// Returns `None` if the tx could not be replayed e.g. the node has pruned the parent block state
+pub async fn analyze_revert<M: Middleware>(
+    client: &M,
+    tx_hash: TxHash,
+    receipt: &TransactionReceipt,
+) -> Option<RevertReason> {
+    let tx = match client.get_transaction(tx_hash).await {
+        Ok(Some(tx)) => tx,
+        result => {
+            debug!("revert analysis tx {:?}: {:?}", tx_hash, result.err());
+            return None;
+        }
+    };
+    if receipt.gas_used == Some(tx.gas) {
+        return Some(RevertReason::OutOfGas);
+    }
+    let block_number = receipt.block_number?.as_u64();
+    let call: TypedTransaction = Eip1559TransactionRequest::new()
+        .from(tx.from)
+        .to(tx.to?)
+        .data(tx.input)
+        .gas(tx.gas)
+        .into();
+    match client
+        .call(&call, Some(BlockId::from(block_number.saturating_sub(1))))
+        .await
+    {
+        Ok(_) => Some(RevertReason::AlreadyArbed),
+        Err(err) => match revert_data(&err) {
+            Some(data) => Some(RevertReason::from_revert_data(&data)),
+            None => {
+                debug!("revert analysis call {:?}: {:?}", tx_hash, err);
+                None
+            }
+        },
+    }
+}
+
+/// The revert data of a failed `eth_call` or `eth_estimateGas`, if the node returned any
+pub(crate) fn revert_data<E: MiddlewareError>(err: &E) -> Option<Bytes> {
+    err.as_error_response()
+        .and_then(|response| response.data.as_ref())
+        .and_then(|data| data.as_str())
+        .and_then(|data| data.parse::<Bytes>().ok())
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::{
+        abi::{encode, Token},
+        types::H256,
+    };
+
+    use super::*;
+
+    fn error_data(message: &str) -> Vec<u8> {
+        let mut data = ERROR_SELECTOR.to_vec();
+        data.extend(encode(&[Token::String(message.to_string())]));
+        data
+    }
+
+    #[test]
+    fn revert_reason_from_data() {
+        let mut slippage = SLIPPAGE_SELECTOR.to_vec();
+        slippage.extend_from_slice(H256::from_low_u64_be(1_234).as_bytes());
+        assert_eq!(
+            RevertReason::from_revert_data(&slippage),
+            RevertReason::Slippage(1_234)
+        );
+        let mut loss = LOSS_SELECTOR.to_vec();
+        loss.extend_from_slice(H256::from_low_u64_be(5).as_bytes());
+        assert_eq!(RevertReason::from_revert_data(&loss), RevertReason::Loss(5));
+
+        assert_eq!(
+            RevertReason::from_revert_data(&error_data("SPL")),
+            RevertReason::InsufficientLiquidity
+        );
+        assert_eq!(
+            RevertReason::from_revert_data(&error_data("UniswapV2: INSUFFICIENT_LIQUIDITY")),
+            RevertReason::InsufficientLiquidity
+        );
+        assert_eq!(
+            RevertReason::from_revert_data(&error_data("LOK")),
+            RevertReason::Unknown(Some("LOK".to_string()))
+        );
+        assert_eq!(
+            RevertReason::from_revert_data(&[]),
+            RevertReason::Unknown(None)
+        );
+        assert_eq!(
+            RevertReason::from_revert_data(&hex!("4e487b710000")),
+            RevertReason::Unknown(None)
+        );
+    }
+}
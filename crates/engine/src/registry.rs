@@ -0,0 +1,162 @@
+//! Token registry
+//!
+//! Maps runtime token Ids to their address, decimals, and symbol.
+//! Ids index the price graph and must match the executor contract's token lookup (see contract/TradeExecutor.sol)
+use ethers::types::Address;
+
+use crate::{
+    types::{ExchangeId, Fee, Pair, Token, MAX_DECIMALS},
+    util::AddressMap,
+};
+
+/// Max. tokens in the registry i.e. the price graph dimension
+pub const MAX_TOKENS: usize = 16;
+
+/// Registered token info
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenInfo {
+    pub symbol: String,
+    pub address: Address,
+    pub decimals: u8,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RegistryError {
+    /// The token Id exceeds `MAX_TOKENS`
+    IdOutOfRange(u8),
+    /// The token Id, address, or symbol is already registered
+    Duplicate(String),
+    /// The token decimals exceed `MAX_DECIMALS`
+    Decimals(String),
+}
+
+/// Lookup between token Ids, addresses, and symbols
+#[derive(Clone, Debug, Default)]
+pub struct TokenRegistry {
+    /// Token info by Id
+    tokens: Vec<Option<TokenInfo>>,
+    /// Map from token address to Id
+    by_address: AddressMap<Token>,
+}
+
+impl TokenRegistry {
+    /// Register a token with `id`
+    pub fn register(&mut self, id: u8, info: TokenInfo) -> Result<Token, RegistryError> {
+        if id as usize >= MAX_TOKENS {
+            return Err(RegistryError::IdOutOfRange(id));
+        }
+        if info.decimals > MAX_DECIMALS {
+            return Err(RegistryError::Decimals(info.symbol));
+        }
+        if self.get(Token::new(id)).is_some()
+            || self.by_address.contains_key(&info.address.0)
+            || self.by_symbol(&info.symbol).is_some()
+        {
+            return Err(RegistryError::Duplicate(info.symbol));
+        }
+        let idx = id as usize;
+        if self.tokens.len() <= idx {
+            self.tokens.resize(idx + 1, None);
+        }
+        let token = Token::new(id);
+        self.by_address.insert(info.address.0, token);
+        self.tokens[idx] = Some(info);
+
+        Ok(token)
+    }
+    /// Info of `token`, if registered
+    pub fn get(&self, token: Token) -> Option<&TokenInfo> {
+        self.tokens.get(token.index()).and_then(Option::as_ref)
+    }
+    /// The token registered at `address`
+    pub fn by_address(&self, address: &[u8; 20]) -> Option<Token> {
+        self.by_address.get(address).copied()
+    }
+    /// The token registered with `symbol`
+    pub fn by_symbol(&self, symbol: &str) -> Option<Token> {
+        self.tokens
+            .iter()
+            .position(|t| t.as_ref().is_some_and(|t| t.symbol == symbol))
+            .map(|idx| Token::new(idx as u8))
+    }
+    /// All registered tokens
+    pub fn tokens(&self) -> impl Iterator<Item = (Token, &TokenInfo)> {
+        self.tokens
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, t)| t.as_ref().map(|t| (Token::new(idx as u8), t)))
+    }
+    /// Create a new pair (orders a/b based on their registered address as per Uniswap v2)
+    pub fn pair(&self, a: Token, b: Token, fee: Fee, exchange_id: ExchangeId) -> Pair {
+        if self.address(a) < self.address(b) {
+            Pair::new_raw(a, b, fee, exchange_id)
+        } else {
+            Pair::new_raw(b, a, fee, exchange_id)
+        }
+    }
+    /// Address of `token`
+    ///
+    /// Panics if `token` is not registered
+    pub fn address(&self, token: Token) -> Address {
+        self.get(token).expect("token registered").address
+    }
+    /// Decimals of `token`
+    ///
+    /// Panics if `token` is not registered
+    pub fn decimals(&self, token: Token) -> u8 {
+        self.get(token).expect("token registered").decimals
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(symbol: &str, address: u8, decimals: u8) -> TokenInfo {
+        TokenInfo {
+            symbol: symbol.to_string(),
+            address: Address::repeat_byte(address),
+            decimals,
+        }
+    }
+
+    #[test]
+    fn register_tokens() {
+        let mut registry = TokenRegistry::default();
+        let b = registry.register(9, info("B", 0xbb, 6)).unwrap();
+        let a = registry.register(2, info("A", 0xaa, 18)).unwrap();
+
+        assert_eq!(registry.by_symbol("A"), Some(a));
+        assert_eq!(registry.by_address(&[0xbb; 20]), Some(b));
+        assert_eq!(registry.decimals(b), 6);
+        assert!(registry.get(Token::new(3)).is_none());
+        assert_eq!(
+            registry.tokens().map(|(t, _)| t).collect::<Vec<_>>(),
+            vec![a, b]
+        );
+        // ordered by address
+        assert_eq!(
+            registry
+                .pair(b, a, Fee::from_pips(500), ExchangeId::Uniswap)
+                .tokens(),
+            (a, b)
+        );
+
+        assert_eq!(
+            registry.register(MAX_TOKENS as u8, info("C", 0xcc, 18)),
+            Err(RegistryError::IdOutOfRange(MAX_TOKENS as u8))
+        );
+        assert_eq!(
+            registry.register(3, info("C", 0xaa, 18)),
+            Err(RegistryError::Duplicate("C".to_string()))
+        );
+        assert_eq!(
+            registry.register(2, info("C", 0xcc, 18)),
+            Err(RegistryError::Duplicate("C".to_string()))
+        );
+        assert_eq!(
+            registry.register(3, info("D", 0xdd, MAX_DECIMALS + 1)),
+            Err(RegistryError::Decimals("D".to_string()))
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! Order risk controls
+//!
+//! The circuit breaker pauses order submission (orders are built but not sent, as in dry-run) once realized results
+//! go bad i.e. too many reverts in a row or net losses (gas paid beyond profit) over budget, it stays tripped until
+//! resumed manually e.g. `kill -USR1 <pid>`
+//!
+//! Risk limits bound the size and rate of orders before they are built, orders exceeding one are rejected
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{info, warn};
+
+use crate::{order::Order, registry::MAX_TOKENS, types::Token};
+
+/// Seconds per (UTC) day, daily notional resets at midnight
+const DAY_S: u64 = 86_400;
+
+/// Pauses order submission after consecutive reverts or net losses beyond a budget
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    /// Reverted order txs in a row which trip the breaker
+    max_consecutive_reverts: u32,
+    /// Net loss (wei) of order txs which trips the breaker
+    loss_budget: u128,
+    consecutive_reverts: u32,
+    /// Net loss (wei) of order txs since resuming, each tx losing money after gas adds its loss
+    loss: u128,
+    tripped: bool,
+}
+
+impl Default for CircuitBreaker {
+    /// A breaker which never trips
+    fn default() -> Self {
+        Self::new(u32::MAX, u128::MAX)
+    }
+}
+
+impl CircuitBreaker {
+    /// Trip after `max_consecutive_reverts` reverted order txs in a row or order txs losing more than `loss_budget` (wei)
+    /// after gas
+    pub fn new(max_consecutive_reverts: u32, loss_budget: u128) -> Self {
+        Self {
+            max_consecutive_reverts,
+            loss_budget,
+            consecutive_reverts: 0,
+            loss: 0,
+            tripped: false,
+        }
+    }
+    /// Record the result of an included order tx paying `gas_cost` (wei) and realizing `profit` (valued in wei)
+    ///
+    /// A tx losing money after gas counts toward the loss budget whether it reverted or not, profits don't offset
+    /// earlier losses
+    ///
+    /// Returns true if this trips the breaker
+    pub fn record(&mut self, success: bool, gas_cost: u128, profit: u128) -> bool {
+        self.loss = self.loss.saturating_add(gas_cost.saturating_sub(profit));
+        if success {
+            self.consecutive_reverts = 0;
+        } else {
+            self.consecutive_reverts += 1;
+        }
+        if self.tripped {
+            return false;
+        }
+        if !success && self.consecutive_reverts >= self.max_consecutive_reverts {
+            warn!(
+                "circuit breaker tripped 🛑: {} reverts in a row, order submission paused",
+                self.consecutive_reverts
+            );
+            self.tripped = true;
+        } else if self.loss > self.loss_budget {
+            warn!(
+                "circuit breaker tripped 🛑: order txs lost {}wei after gas, order submission paused",
+                self.loss
+            );
+            self.tripped = true;
+        }
+        self.tripped
+    }
+    /// True if order submission is paused
+    pub fn is_tripped(&self) -> bool {
+        self.tripped
+    }
+    /// Reverted order txs in a row
+    pub fn consecutive_reverts(&self) -> u32 {
+        self.consecutive_reverts
+    }
+    /// Net loss (wei) of order txs since resuming
+    pub fn loss(&self) -> u128 {
+        self.loss
+    }
+    /// Resume order submission, resetting the revert count and loss
+    pub fn resume(&mut self) {
+        info!("circuit breaker reset, order submission resumed");
+        self.consecutive_reverts = 0;
+        self.loss = 0;
+        self.tripped = false;
+    }
+}
+
+/// Resume `breaker` whenever the process receives `SIGUSR1`
+#[cfg(unix)]
+pub fn resume_on_signal(breaker: Arc<Mutex<CircuitBreaker>>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async move {
+        let mut signals = match signal(SignalKind::user_defined1()) {
+            Ok(signals) => signals,
+            Err(err) => {
+                warn!("circuit breaker signal: {:?}", err);
+                return;
+            }
+        };
+        while signals.recv().await.is_some() {
+            breaker.lock().expect("breaker lock").resume();
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn resume_on_signal(_breaker: Arc<Mutex<CircuitBreaker>>) {
+    warn!("circuit breaker can't be resumed by signal on this platform");
+}
+
+/// Limits on the orders submitted, amounts are in units of the order's input token
+#[derive(Clone, Debug, PartialEq)]
+pub struct RiskLimits {
+    /// Max. amount in of a single order, by token
+    max_amount_in: [u128; MAX_TOKENS],
+    /// Max. total amount in of orders per day, by token
+    max_daily_notional: [u128; MAX_TOKENS],
+    /// Max. orders submitted at the prices of a block
+    max_trades_per_block: u32,
+}
+
+impl Default for RiskLimits {
+    /// Limits which never reject an order
+    fn default() -> Self {
+        Self {
+            max_amount_in: [u128::MAX; MAX_TOKENS],
+            max_daily_notional: [u128::MAX; MAX_TOKENS],
+            max_trades_per_block: u32::MAX,
+        }
+    }
+}
+
+impl RiskLimits {
+    /// Limit the amount in of a single `token` order
+    pub fn with_max_amount_in(mut self, token: Token, amount: u128) -> Self {
+        self.max_amount_in[token.index()] = amount;
+        self
+    }
+    /// Limit the total amount in of `token` orders per (UTC) day
+    pub fn with_max_daily_notional(mut self, token: Token, amount: u128) -> Self {
+        self.max_daily_notional[token.index()] = amount;
+        self
+    }
+    /// Limit the orders submitted at the prices of a block
+    pub fn with_max_trades_per_block(mut self, trades: u32) -> Self {
+        self.max_trades_per_block = trades;
+        self
+    }
+}
+
+/// An order limit which was exceeded
+#[derive(Clone, Debug, PartialEq)]
+pub enum RiskViolation {
+    /// The order amount in exceeds the token's max.
+    AmountIn {
+        token: Token,
+        amount_in: u128,
+        limit: u128,
+    },
+    /// The max. orders were already submitted for the block
+    TradesPerBlock { block_number: u64, limit: u32 },
+    /// The order would take the token's notional for the day over its max.
+    DailyNotional {
+        token: Token,
+        notional: u128,
+        limit: u128,
+    },
+}
+
+impl fmt::Display for RiskViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AmountIn {
+                token,
+                amount_in,
+                limit,
+            } => write!(f, "{token:?} amount in {amount_in} exceeds max. {limit}"),
+            Self::TradesPerBlock {
+                block_number,
+                limit,
+            } => write!(f, "block #{block_number} reached max. {limit} trades"),
+            Self::DailyNotional {
+                token,
+                notional,
+                limit,
+            } => write!(
+                f,
+                "{token:?} daily notional {notional} exceeds max. {limit}"
+            ),
+        }
+    }
+}
+
+/// Enforces `RiskLimits` on orders, tracking the orders submitted per block and per day
+#[derive(Debug)]
+pub struct RiskGuard {
+    limits: RiskLimits,
+    /// Latest block with submitted orders
+    block_number: u64,
+    /// Orders submitted at the latest block
+    block_trades: u32,
+    /// Current day since the unix epoch
+    day: u64,
+    /// Amount in of orders submitted today, by token
+    daily_notional: [u128; MAX_TOKENS],
+}
+
+impl RiskGuard {
+    /// Enforce `limits`, no orders submitted yet
+    pub fn new(limits: RiskLimits) -> Self {
+        Self {
+            limits,
+            block_number: 0,
+            block_trades: 0,
+            day: 0,
+            daily_notional: [0; MAX_TOKENS],
+        }
+    }
+    /// Check `order` against the limits
+    pub fn check(&mut self, order: &Order) -> Result<(), RiskViolation> {
+        self.check_at(order, today())
+    }
+    /// Count `order` as submitted toward the limits
+    pub fn record(&mut self, order: &Order) {
+        self.record_at(order, today())
+    }
+    fn check_at(&mut self, order: &Order, day: u64) -> Result<(), RiskViolation> {
+        self.roll_day(day);
+        let token = input_token(order);
+        let limit = self.limits.max_amount_in[token.index()];
+        if order.amount_in > limit {
+            return Err(RiskViolation::AmountIn {
+                token,
+                amount_in: order.amount_in,
+                limit,
+            });
+        }
+        if order.block_number == self.block_number
+            && self.block_trades >= self.limits.max_trades_per_block
+        {
+            return Err(RiskViolation::TradesPerBlock {
+                block_number: order.block_number,
+                limit: self.limits.max_trades_per_block,
+            });
+        }
+        let notional = self.daily_notional[token.index()].saturating_add(order.amount_in);
+        let limit = self.limits.max_daily_notional[token.index()];
+        if notional > limit {
+            return Err(RiskViolation::DailyNotional {
+                token,
+                notional,
+                limit,
+            });
+        }
+        Ok(())
+    }
+    fn record_at(&mut self, order: &Order, day: u64) {
+        self.roll_day(day);
+        // orders queued from older blocks count toward the latest block
+        if order.block_number > self.block_number {
+            self.block_number = order.block_number;
+            self.block_trades = 0;
+        }
+        self.block_trades += 1;
+        let notional = &mut self.daily_notional[input_token(order).index()];
+        *notional = notional.saturating_add(order.amount_in);
+    }
+    /// Reset the daily notional on a new day
+    fn roll_day(&mut self, day: u64) {
+        if day != self.day {
+            self.day = day;
+            self.daily_notional = [0; MAX_TOKENS];
+        }
+    }
+}
+
+/// The token sold by the first trade of `order`
+fn input_token(order: &Order) -> Token {
+    Token::new(order.trade.path[0].token_in)
+}
+
+/// Current (UTC) day since the unix epoch
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() / DAY_S)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        price_graph::{CompositeTrade, Trade},
+        types::Fee,
+    };
+
+    #[test]
+    fn trips_on_consecutive_reverts() {
+        let mut breaker = CircuitBreaker::new(3, u128::MAX);
+        assert!(!breaker.record(false, 1, 0));
+        assert!(!breaker.record(false, 1, 0));
+        // a success resets the count
+        assert!(!breaker.record(true, 1, 2));
+        assert!(!breaker.record(false, 1, 0));
+        assert!(!breaker.record(false, 1, 0));
+        assert!(!breaker.is_tripped());
+        assert!(breaker.record(false, 1, 0));
+        assert!(breaker.is_tripped());
+        // stays tripped
+        assert!(!breaker.record(true, 1, 2));
+        assert!(breaker.is_tripped());
+
+        breaker.resume();
+        assert!(!breaker.is_tripped());
+        assert!(!breaker.record(false, 1, 0));
+    }
+
+    #[test]
+    fn trips_on_loss_budget() {
+        let mut breaker = CircuitBreaker::new(u32::MAX, 100);
+        assert!(!breaker.record(false, 60, 0));
+        // profitable after gas, earlier losses aren't offset
+        assert!(!breaker.record(true, 1_000, 5_000));
+        assert_eq!(breaker.loss(), 60);
+        // included but losing money after gas
+        assert!(!breaker.record(true, 50, 10));
+        assert_eq!(breaker.loss(), 100);
+        assert!(breaker.record(true, 1, 0));
+        assert!(breaker.is_tripped());
+
+        assert!(!CircuitBreaker::default().record(false, u128::MAX, 0));
+    }
+
+    fn order(token_in: Token, amount_in: u128, block_number: u64) -> Order {
+        Order {
+            amount_in,
+            trade: CompositeTrade::new(&[Trade::new(
+                token_in.id(),
+                Token::WETH.id(),
+                Fee::from_pips(500),
+                0,
+            )]),
+            block_number,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn risk_limits() {
+        let mut guard = RiskGuard::new(
+            RiskLimits::default()
+                .with_max_amount_in(Token::USDC, 1_000)
+                .with_max_daily_notional(Token::USDC, 2_500)
+                .with_max_trades_per_block(2),
+        );
+        assert_eq!(
+            guard.check_at(&order(Token::USDC, 1_001, 1), 0),
+            Err(RiskViolation::AmountIn {
+                token: Token::USDC,
+                amount_in: 1_001,
+                limit: 1_000
+            })
+        );
+        // other tokens are unlimited
+        assert!(guard.check_at(&order(Token::ARB, u128::MAX, 1), 0).is_ok());
+
+        for _ in 0..2 {
+            let o = order(Token::USDC, 1_000, 1);
+            assert!(guard.check_at(&o, 0).is_ok());
+            guard.record_at(&o, 0);
+        }
+        assert_eq!(
+            guard.check_at(&order(Token::USDC, 1, 1), 0),
+            Err(RiskViolation::TradesPerBlock {
+                block_number: 1,
+                limit: 2
+            })
+        );
+        // a new block, but the daily notional is nearly spent
+        assert_eq!(
+            guard.check_at(&order(Token::USDC, 501, 2), 0),
+            Err(RiskViolation::DailyNotional {
+                token: Token::USDC,
+                notional: 2_501,
+                limit: 2_500
+            })
+        );
+        assert!(guard.check_at(&order(Token::USDC, 500, 2), 0).is_ok());
+        // resets the next day
+        assert!(guard.check_at(&order(Token::USDC, 1_000, 2), 1).is_ok());
+    }
+}
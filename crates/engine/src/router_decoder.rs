@@ -0,0 +1,3128 @@
+//! Router calldata decoders
+//!
+//! Each known router has a `RouterDecoder` extracting the trades of a call, registered by router address in `DECODERS`.
+//! Decoders don't touch the price graph, trades which depend on the output of prior trades (e.g. multi-hop or split routes)
+//! draw from balance slots credited as the `TradeSimulator` applies them
+use ethabi_static::{AddressZcp, Bytes32, BytesZcp, DecodeStatic, Tuple};
+use ethers::types::U256;
+use fulcrum_sequencer_feed::TransactionInfo;
+use log::{debug, info, warn};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::{
+    abi_check::{AbiLayout, OffsetError},
+    config,
+    constant::arbitrum::{CAMELOT_ROUTER, SUSHI_ROUTER, WETH},
+    paraswap,
+    trade_router::*,
+    types::{ExchangeId, Fee, OpaqueRouterPolicy, RouterId, Token},
+    uniswap_v3::{Path, PathDirection},
+    util::AddressMap,
+    zero_ex,
+};
+
+/// Pair fee of uniswap v2 and sushi i.e. 0.3%
+const V2_FEE: Fee = Fee::from_units(300);
+
+/// Map from router contract address to its calldata decoder
+///
+/// Curve pools are called directly, each configured pool is registered with the curve pool decoder
+pub static DECODERS: Lazy<AddressMap<&'static dyn RouterDecoder>> = Lazy::new(|| {
+    ROUTERS
+        .iter()
+        .map(|(address, router_id)| (*address, decoder(*router_id)))
+        .chain(
+            CURVE_COINS
+                .keys()
+                .map(|address| (*address, &CurvePoolDecoder as &'static dyn RouterDecoder)),
+        )
+        .collect()
+});
+
+/// Extracts the trades of calls to a router contract
+pub trait RouterDecoder: Sync {
+    /// Decode the trades of `tx`, a call to the router
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError>;
+}
+
+/// Errors decoding router calldata
+///
+/// Anyone can call a router with garbage, these are never fatal
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    /// The calldata (or some nested payload) doesn't match the method's ABI
+    Abi,
+    /// Params are inconsistent e.g. more commands than inputs
+    Malformed,
+    /// An amount exceeds the simulator's u128 amounts
+    Overflow,
+    /// A dynamic field's offset can't point at its encoding (`strict-decode` builds)
+    Offset(OffsetError),
+}
+
+/// The calldata decoder of `router_id`
+pub fn decoder(router_id: RouterId) -> &'static dyn RouterDecoder {
+    match router_id {
+        RouterId::UniswapV3RouterV1 => &UniswapV3RouterV1Decoder,
+        RouterId::UniswapV3RouterV2 => &UniswapV3RouterV2Decoder,
+        RouterId::UniswapV3UniversalRouter => &UniversalRouterDecoder,
+        RouterId::SushiRouterV2 => &SushiRouterV2Decoder,
+        RouterId::CamelotRouterV2 => &CamelotRouterV2Decoder,
+        RouterId::Gmx => &GmxDecoder,
+        RouterId::ParaswapAugustus => &ParaswapDecoder,
+        RouterId::OneInch => &OneInchDecoder,
+        RouterId::ZeroEx => &ZeroExDecoder,
+        RouterId::Odos => &OdosDecoder,
+        RouterId::SushiRouteProcessor3 => &SushiRouteProcessorDecoder { v2_fee: false },
+        RouterId::SushiRouteProcessor4 => &SushiRouteProcessorDecoder { v2_fee: true },
+        RouterId::BalancerVault => &BalancerVaultDecoder,
+        RouterId::RamsesRouter => &RamsesRouterDecoder,
+        RouterId::KyberSwap => &KyberSwapDecoder,
+        RouterId::OdosV2 => &OdosV2Decoder,
+    }
+}
+
+/// Decode the trades of `tx` if it calls a known router
+///
+/// Malformed calls are flagged as skipped, we can't tell what they would do onchain.
+/// Calls to other contracts are handled by their opaque router policy
+pub fn decode_call(tx: &TransactionInfo) -> DecodedTrades {
+    match try_decode_call(tx) {
+        Ok(trades) => trades,
+        Err(err) => {
+            warn!("malformed call to {:02x?}: {:?}", tx.to.0, err);
+            DecodedTrades {
+                skip: true,
+                ..Default::default()
+            }
+        }
+    }
+}
+
+/// Decode the trades of `tx` if it calls a known router, checking trade amounts are in range
+fn try_decode_call(tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+    // need atleast 4 bytes of input to call a contract method
+    if tx.input.len() < 5 {
+        return Ok(DecodedTrades::default());
+    }
+    let trades = match DECODERS.get(&tx.to.0) {
+        Some(decoder) => decoder.decode(tx)?,
+        None => {
+            return Ok(match OPAQUE_ROUTERS.get(&tx.to.0) {
+                // a known router without a decoder, none of its methods are handled
+                Some(policy) => DecodedTrades {
+                    unhandled: Some(split_selector(tx.input)?.0),
+                    ..opaque_trades(*policy, tx)
+                },
+                None => opaque_trades(config::active().unknown_router_policy(), tx),
+            });
+        }
+    };
+    for step in trades.steps.iter() {
+        if let TradeStep::Trade {
+            info, take: None, ..
+        } = step
+        {
+            to_u128(info.amount)?;
+        }
+    }
+    Ok(trades)
+}
+
+/// The trades of a call we can't decode, skipped if `policy` expects it to move monitored pools
+fn opaque_trades(policy: OpaqueRouterPolicy, tx: &TransactionInfo) -> DecodedTrades {
+    let skip = match policy {
+        OpaqueRouterPolicy::Ignore => false,
+        OpaqueRouterPolicy::SkipRound => true,
+        OpaqueRouterPolicy::Tokens => touches_monitored(tx.input),
+    };
+    if skip {
+        info!("opaque call to {:02x?}: {:?}", tx.to.0, policy);
+    }
+    DecodedTrades {
+        skip,
+        ..Default::default()
+    }
+}
+
+/// Cheap heuristic for whether opaque calldata trades through monitored pools
+/// i.e. it mentions a known pool or atleast two known tokens
+fn touches_monitored(data: &[u8]) -> bool {
+    !scan_known_pools(data).is_empty() || scan_known_tokens(data).len() > 1
+}
+
+/// Take `numerator / denominator` of the balance remaining in `slot`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Take {
+    pub slot: usize,
+    pub numerator: u128,
+    pub denominator: u128,
+}
+
+impl Take {
+    /// Take the whole balance of `slot`
+    pub fn all(slot: usize) -> Self {
+        Take {
+            slot,
+            numerator: 1,
+            denominator: 1,
+        }
+    }
+    /// Deduct the amount taken from `balances`, returning it
+    pub fn apply(&self, balances: &mut [u128]) -> u128 {
+        let balance = &mut balances[self.slot];
+        let amount = if self.numerator >= self.denominator {
+            *balance
+        } else {
+            (U256::from(*balance) * U256::from(self.numerator) / U256::from(self.denominator))
+                .as_u128()
+        };
+        *balance -= amount;
+        amount
+    }
+}
+
+/// A step of the trades decoded from a router call
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeStep {
+    /// Run a trade, exact input or exact output
+    Trade {
+        info: TradeInfo,
+        exact_in: bool,
+        /// Take the amount from a balance slot rather than `info.amount`
+        take: Option<Take>,
+        /// Slot credited with the amount out (exact input) or amount requested (exact output)
+        credit: Option<usize>,
+        /// Subsequent steps depend on this trade, stop if it can't be simulated
+        required: bool,
+    },
+    /// Add `amount` to a balance slot e.g. the router's input
+    Credit { slot: usize, amount: u128 },
+    /// Move an amount between balance slots
+    Move { take: Take, to: usize },
+}
+
+/// The trades of a router call, applied in order
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct DecodedTrades {
+    pub steps: Vec<TradeStep>,
+    /// Number of balance slots used by `steps`
+    pub slots: usize,
+    /// The call touches monitored pools in a way that can't be simulated
+    pub skip: bool,
+    /// Selector of a router method without a decoder, if the call (or a nested call) invoked one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unhandled: Option<[u8; 4]>,
+}
+
+impl DecodedTrades {
+    /// Allocate an empty balance slot
+    pub fn slot(&mut self) -> usize {
+        self.slots += 1;
+        self.slots - 1
+    }
+    /// Allocate a balance slot holding `amount`
+    pub fn credit(&mut self, amount: u128) -> usize {
+        let slot = self.slot();
+        self.steps.push(TradeStep::Credit { slot, amount });
+        slot
+    }
+    /// Push an independent trade of `info.amount`
+    pub fn push(&mut self, info: TradeInfo, exact_in: bool) {
+        self.steps.push(TradeStep::Trade {
+            info,
+            exact_in,
+            take: None,
+            credit: None,
+            required: false,
+        });
+    }
+    /// Push single pool swap `hops` run in order, the first drawing from `take` and the last crediting `credit`
+    pub fn push_hops(&mut self, hops: &[([u8; 20], bool)], mut take: Take, credit: Option<usize>) {
+        let last = hops.len().saturating_sub(1);
+        for (idx, (pool, zero_for_one)) in hops.iter().enumerate() {
+            let output = if idx == last {
+                credit
+            } else {
+                Some(self.slot())
+            };
+            self.steps.push(TradeStep::Trade {
+                info: pool_trade_info(pool, *zero_for_one, 0),
+                exact_in: true,
+                take: Some(take),
+                credit: output,
+                required: true,
+            });
+            if let Some(slot) = output {
+                take = Take::all(slot);
+            }
+        }
+    }
+    /// Wrap `amount` of native ETH into the router's WETH balance `slot`
+    ///
+    /// Pools only see the WETH, routers wrap `msg.value` before trading it
+    pub fn wrap_eth(&mut self, slot: usize, amount: u128) {
+        self.steps.push(TradeStep::Credit { slot, amount });
+    }
+    /// Unwrap the router's WETH balance `slot` e.g. to pay out ETH, later trades can't draw from it
+    pub fn unwrap_weth(&mut self, slot: usize) {
+        self.pay_out(Take::all(slot));
+    }
+    /// Pay `take` out of the router e.g. a fee or sweep, later trades can't draw from it
+    pub fn pay_out(&mut self, take: Take) {
+        let to = self.slot();
+        self.steps.push(TradeStep::Move { take, to });
+    }
+    /// Append the trades of another call, keeping their balance slots separate
+    pub fn append(&mut self, other: DecodedTrades) {
+        let offset = self.slots;
+        self.slots += other.slots;
+        self.skip |= other.skip;
+        self.unhandled = self.unhandled.or(other.unhandled);
+        self.steps
+            .extend(other.steps.into_iter().map(|step| match step {
+                TradeStep::Trade {
+                    info,
+                    exact_in,
+                    take,
+                    credit,
+                    required,
+                } => TradeStep::Trade {
+                    info,
+                    exact_in,
+                    take: take.map(|take| Take {
+                        slot: take.slot + offset,
+                        ..take
+                    }),
+                    credit: credit.map(|slot| slot + offset),
+                    required,
+                },
+                TradeStep::Credit { slot, amount } => TradeStep::Credit {
+                    slot: slot + offset,
+                    amount,
+                },
+                TradeStep::Move { take, to } => TradeStep::Move {
+                    take: Take {
+                        slot: take.slot + offset,
+                        ..take
+                    },
+                    to: to + offset,
+                },
+            }));
+    }
+}
+
+/// Uniswap V3 `SwapRouter`
+pub struct UniswapV3RouterV1Decoder;
+
+impl RouterDecoder for UniswapV3RouterV1Decoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        // malformed calldata fails to decode with a `DecodeError`, the tx is skipped
+        if selector == UNISWAP_V3_V1_EXACT_INPUT {
+            debug!("🦄1 exact input");
+            let Tuple(swap) = abi::<Tuple<UniswapV3ExactInputParamsV1>>(buf)?;
+            trades.push(
+                v3_path_trade_info(
+                    swap.path.as_ref(),
+                    PathDirection::Forward,
+                    swap.amount_in,
+                    ExchangeId::Uniswap,
+                ),
+                true,
+            );
+        } else if selector == UNISWAP_V3_V1_EXACT_OUTPUT {
+            debug!("🦄1 exact output");
+            let Tuple(swap) = abi::<Tuple<UniswapV3ExactOutputParamsV1>>(buf)?;
+            trades.push(
+                v3_path_trade_info(
+                    swap.path.as_ref(),
+                    PathDirection::Reverse,
+                    swap.amount_out,
+                    ExchangeId::Uniswap,
+                ),
+                false,
+            );
+        } else if selector == UNISWAP_V3_V1_EXACT_INPUT_SINGLE {
+            debug!("🦄1 exact input single");
+            let UniswapV3ExactInputSingleParamsV1 {
+                amount_in,
+                token_in,
+                token_out,
+                fee,
+                ..
+            } = abi::<UniswapV3ExactInputSingleParamsV1>(buf)?;
+            trades.push(
+                exact_single_to_trade_info(token_in.as_ref(), token_out.as_ref(), amount_in, fee),
+                true,
+            );
+        } else if selector == UNISWAP_V3_V1_EXACT_OUTPUT_SINGLE {
+            debug!("🦄1 exact output single");
+            let UniswapV3ExactOutputSingleParamsV1 {
+                token_in,
+                token_out,
+                amount_out,
+                fee,
+                ..
+            } = abi::<UniswapV3ExactOutputSingleParamsV1>(buf)?;
+            trades.push(
+                exact_single_to_trade_info(token_out.as_ref(), token_in.as_ref(), amount_out, fee),
+                false,
+            );
+        } else if selector == UNISWAP_V3_MULTI_CALL {
+            debug!("🦄1 multicall");
+            let multi_call = abi::<UniswapV3MultiCall>(buf)?;
+            for call in multi_call.data.iter() {
+                trades.append(try_decode_call(&TransactionInfo {
+                    input: call.as_ref(),
+                    ..*tx
+                })?);
+            }
+        } else if selector == UNISWAP_V3_MULTI_CALL_DEADLINE {
+            debug!("🦄1 multicall deadline");
+            let multi_call = abi::<UniswapV3MultiCallDeadline>(buf)?;
+            for call in multi_call.data.iter() {
+                trades.append(try_decode_call(&TransactionInfo {
+                    input: call.as_ref(),
+                    ..*tx
+                })?);
+            }
+        } else {
+            debug!("unhandled 🦄1: {:02x?}", selector);
+            trades.unhandled = Some(selector);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Uniswap V3 `SwapRouter02`
+pub struct UniswapV3RouterV2Decoder;
+
+impl RouterDecoder for UniswapV3RouterV2Decoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let mut trades = DecodedTrades::default();
+        // the router's WETH balance carried between multicall calls i.e. wrapped ETH
+        let balance = trades.slot();
+        self.decode_into(tx.input, &mut trades, balance)?;
+
+        Ok(trades)
+    }
+}
+
+impl UniswapV3RouterV2Decoder {
+    /// Decode the trades of the router call `input` into `trades`
+    ///
+    /// Exact input swaps of the router's contract balance draw from its WETH `balance` slot
+    fn decode_into(
+        &self,
+        input: &[u8],
+        trades: &mut DecodedTrades,
+        balance: usize,
+    ) -> Result<(), DecodeError> {
+        let (selector, buf) = split_selector(input)?;
+
+        if selector == UNISWAP_V3_V2_EXACT_INPUT {
+            debug!("🦄2 exact input");
+            let Tuple(swap) = abi::<Tuple<UniswapV3ExactInputParamsV2>>(buf)?;
+            trades.steps.push(TradeStep::Trade {
+                info: v3_path_trade_info(
+                    swap.path.as_ref(),
+                    PathDirection::Forward,
+                    swap.amount_in,
+                    ExchangeId::Uniswap,
+                ),
+                exact_in: true,
+                take: (swap.amount_in == SWAP_ROUTER_02_CONTRACT_BALANCE)
+                    .then(|| Take::all(balance)),
+                credit: None,
+                required: false,
+            });
+        } else if selector == UNISWAP_V3_V2_EXACT_OUTPUT {
+            debug!("🦄2 exact output");
+            let Tuple(swap) = abi::<Tuple<UniswapV3ExactOutputParamsV2>>(buf)?;
+            trades.push(
+                v3_path_trade_info(
+                    swap.path.as_ref(),
+                    PathDirection::Reverse,
+                    swap.amount_out,
+                    ExchangeId::Uniswap,
+                ),
+                false,
+            );
+        } else if selector == UNISWAP_V3_V2_EXACT_INPUT_SINGLE {
+            debug!("🦄2 exact input single");
+            let UniswapV3ExactInputSingleParamsV2 {
+                token_in,
+                token_out,
+                amount_in,
+                fee,
+                ..
+            } = abi::<UniswapV3ExactInputSingleParamsV2>(buf)?;
+            trades.steps.push(TradeStep::Trade {
+                info: exact_single_to_trade_info(
+                    token_in.as_ref(),
+                    token_out.as_ref(),
+                    amount_in,
+                    fee,
+                ),
+                exact_in: true,
+                take: (amount_in == SWAP_ROUTER_02_CONTRACT_BALANCE).then(|| Take::all(balance)),
+                credit: None,
+                required: false,
+            });
+        } else if selector == UNISWAP_V3_V2_EXACT_OUTPUT_SINGLE {
+            debug!("🦄2 exact output single");
+            let UniswapV3ExactOutputSingleParamsV2 {
+                token_in,
+                token_out,
+                amount_out,
+                fee,
+                ..
+            } = abi::<UniswapV3ExactOutputSingleParamsV2>(buf)?;
+            trades.push(
+                exact_single_to_trade_info(token_out.as_ref(), token_in.as_ref(), amount_out, fee),
+                false,
+            );
+        } else if selector == UNISWAP_V3_V2_WRAP_ETH {
+            debug!("🦄2 wrap eth");
+            let wrap = abi::<UniswapV3WrapEth>(buf)?;
+            trades.wrap_eth(balance, to_u128(wrap.value)?);
+        } else if selector == UNISWAP_V3_V2_UNWRAP_WETH9
+            || selector == UNISWAP_V3_V2_UNWRAP_WETH9_RECIPIENT
+        {
+            debug!("🦄2 unwrap weth");
+            trades.unwrap_weth(balance);
+        } else if selector == UNISWAP_V3_MULTI_CALL {
+            debug!("🦄2 multicall");
+            let multi_call = abi::<UniswapV3MultiCall>(buf)?;
+            for call in multi_call.data.iter() {
+                self.decode_into(call.as_ref(), trades, balance)?;
+            }
+        } else if selector == UNISWAP_V3_MULTI_CALL_DEADLINE {
+            debug!("🦄2 multicall deadline");
+            let multi_call = abi::<UniswapV3MultiCallDeadline>(buf)?;
+            for call in multi_call.data.iter() {
+                self.decode_into(call.as_ref(), trades, balance)?;
+            }
+        } else {
+            debug!("unhandled 🦄2: {:02x?}", selector);
+            trades.unhandled = Some(selector);
+        }
+
+        Ok(())
+    }
+}
+
+/// Uniswap `UniversalRouter`
+pub struct UniversalRouterDecoder;
+
+impl RouterDecoder for UniversalRouterDecoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        if selector != UNISWAP_UNIVERSAL_ROUTER_EXECUTE
+            && selector != UNISWAP_UNIVERSAL_ROUTER_EXECUTE_DEADLINE
+        {
+            debug!("unhandled 🦄🌐: {:02x?}", selector);
+            trades.unhandled = Some(selector);
+            return Ok(trades);
+        }
+        let params = abi::<UniswapV3UniversalExecuteParams>(buf)?;
+        // the router's balance carried between commands e.g. wrapped ETH or a prior swap's output
+        let balance = trades.slot();
+        decode_universal_commands(
+            &mut trades,
+            tx,
+            params.commands.as_ref(),
+            &params.inputs,
+            balance,
+            false,
+        )?;
+
+        Ok(trades)
+    }
+}
+
+/// Decode universal router `commands`, each with its input at the same index
+///
+/// Commands without trades (e.g. permits and balance checks) are skipped, `sub_plan` is true within an `EXECUTE_SUB_PLAN`
+fn decode_universal_commands(
+    trades: &mut DecodedTrades,
+    tx: &TransactionInfo,
+    commands: &[u8],
+    inputs: &[BytesZcp],
+    balance: usize,
+    sub_plan: bool,
+) -> Result<(), DecodeError> {
+    use universal_router_command::*;
+    // the router reverts otherwise
+    if commands.len() != inputs.len() {
+        return Err(DecodeError::Malformed);
+    }
+    for (command, input) in commands.iter().zip(inputs) {
+        let input = input.as_ref();
+        let command = command & MASK;
+        match command {
+            V3_SWAP_EXACT_IN => {
+                debug!("🦄🌐 exact input {command}");
+                let swap = abi::<UniswapV3UniversalRouterSwapExactIn>(input)?;
+                trades.steps.push(TradeStep::Trade {
+                    info: v3_path_trade_info(
+                        swap.path.as_ref(),
+                        PathDirection::Forward,
+                        swap.amount_in,
+                        ExchangeId::Uniswap,
+                    ),
+                    exact_in: true,
+                    take: contract_balance_or(swap.amount_in, balance),
+                    credit: Some(balance),
+                    required: false,
+                });
+            }
+            V3_SWAP_EXACT_OUT => {
+                debug!("🦄🌐 exact output {command}");
+                let swap = abi::<UniswapV3UniversalRouterSwapExactOut>(input)?;
+                trades.steps.push(TradeStep::Trade {
+                    info: v3_path_trade_info(
+                        swap.path.as_ref(),
+                        PathDirection::Reverse,
+                        swap.amount_out,
+                        ExchangeId::Uniswap,
+                    ),
+                    exact_in: false,
+                    take: None,
+                    credit: Some(balance),
+                    required: false,
+                });
+            }
+            V2_SWAP_EXACT_IN => {
+                debug!("🦄🌐 v2 exact input {command}");
+                let swap = abi::<UniswapV2UniversalRouterSwapExactIn>(input)?;
+                // tokens already paid to the pair e.g. wrapped ETH were carried by the router's balance
+                let take = if swap.amount_in == UNIVERSAL_ROUTER_ALREADY_PAID {
+                    Some(Take::all(balance))
+                } else {
+                    contract_balance_or(swap.amount_in, balance)
+                };
+                trades.steps.push(TradeStep::Trade {
+                    info: v2_path_trade_info::<true>(
+                        swap.path.as_slice(),
+                        swap.amount_in,
+                        V2_FEE,
+                        ExchangeId::UniswapV2,
+                    )?,
+                    exact_in: true,
+                    take,
+                    credit: Some(balance),
+                    required: false,
+                });
+            }
+            V2_SWAP_EXACT_OUT => {
+                debug!("🦄🌐 v2 exact output {command}");
+                let swap = abi::<UniswapV2UniversalRouterSwapExactOut>(input)?;
+                trades.steps.push(TradeStep::Trade {
+                    info: v2_path_trade_info::<false>(
+                        swap.path.as_slice(),
+                        swap.amount_out,
+                        V2_FEE,
+                        ExchangeId::UniswapV2,
+                    )?,
+                    exact_in: false,
+                    take: None,
+                    credit: Some(balance),
+                    required: false,
+                });
+            }
+            WRAP_ETH => {
+                debug!("🦄🌐 wrap eth {command}");
+                let wrap = abi::<UniversalRouterWrapEth>(input)?;
+                // the router's ETH balance is the call's value
+                let amount = if wrap.amount == UNIVERSAL_ROUTER_CONTRACT_BALANCE {
+                    tx.value
+                } else {
+                    wrap.amount
+                };
+                trades.wrap_eth(balance, to_u128(amount)?);
+            }
+            UNWRAP_WETH => {
+                debug!("🦄🌐 unwrap weth {command}");
+                trades.unwrap_weth(balance);
+            }
+            V4_SWAP => {
+                debug!("🦄🌐 v4 swap {command}");
+                let swap = abi::<UniswapV4Swap>(input)?;
+                push_v4_swap(&mut trades, &swap, balance)?;
+            }
+            PERMIT2_TRANSFER_FROM => {
+                debug!("🦄🌐 permit2 transfer {command}");
+                // pulled to the router or a pair, later swaps draw from the router's balance
+                let transfer = abi::<UniversalRouterPermit2TransferFrom>(input)?;
+                trades.steps.push(TradeStep::Credit {
+                    slot: balance,
+                    amount: to_u128(transfer.amount)?,
+                });
+            }
+            PAY_PORTION => {
+                debug!("🦄🌐 pay portion {command}");
+                let pay = abi::<UniversalRouterPayPortion>(input)?;
+                trades.pay_out(Take {
+                    slot: balance,
+                    numerator: to_u128(pay.bips)?,
+                    denominator: 10_000,
+                });
+            }
+            SWEEP => {
+                debug!("🦄🌐 sweep {command}");
+                trades.pay_out(Take::all(balance));
+            }
+            EXECUTE_SUB_PLAN if !sub_plan => {
+                debug!("🦄🌐 sub plan {command}");
+                let plan = abi::<UniswapV3UniversalExecuteParams>(input)?;
+                decode_universal_commands(
+                    trades,
+                    tx,
+                    plan.commands.as_ref(),
+                    &plan.inputs,
+                    balance,
+                    true,
+                )?;
+            }
+            EXECUTE_SUB_PLAN => {
+                warn!("🦄🌐 nested sub plan");
+                trades.skip = true;
+            }
+            PERMIT2_PERMIT
+            | PERMIT2_PERMIT_BATCH
+            | PERMIT2_TRANSFER_FROM_BATCH
+            | TRANSFER
+            | BALANCE_CHECK_ERC20 => {
+                debug!("🦄🌐 no trade {command}");
+            }
+            _ => {
+                // command doing something we don't monitor
+                debug!("unhandled 🦄🌐: {:?}", command);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 1inch aggregation router
+///
+/// NB: we map v4 and V5 aggregator to same router Id
+pub struct OneInchDecoder;
+
+impl RouterDecoder for OneInchDecoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        debug!("🐴");
+        if selector == ONE_INCH_UNISWAP_V3_SWAP {
+            let params = abi::<OneInchUniswapV3Swap>(buf)?;
+            trades.push(
+                one_inch_pools_trade_info(&params.pools, params.amount_in),
+                true,
+            );
+        } else if selector == ONE_INCH_UNISWAP_V3_SWAP_TWP {
+            let params = abi::<OneInchUniswapV3SwapTWP>(buf)?;
+            trades.push(
+                one_inch_pools_trade_info(&params.pools, params.amount_in),
+                true,
+            );
+        } else if selector == ONE_INCH_UNISWAP_SWAP {
+            let swap = abi::<OneInchSwap>(buf)?;
+            debug!(
+                "🐴 swap: {:02x?}/{:02x?} via {:02x?}",
+                swap.token_in.0, swap.token_out.0, swap.executor.0
+            );
+            push_executor_swap(
+                &mut trades,
+                swap.data.as_ref(),
+                swap.token_in.0,
+                swap.token_out.0,
+                swap.amount,
+            )?;
+        } else {
+            debug!("unhandled 🐴: {:02x?}", selector);
+            trades.unhandled = Some(selector);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// KyberSwap meta aggregation router
+pub struct KyberSwapDecoder;
+
+impl RouterDecoder for KyberSwapDecoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        debug!("🐉");
+        let (desc, executor_data) = if selector == KYBER_SWAP || selector == KYBER_SWAP_GENERIC {
+            let Tuple(execution) = abi::<Tuple<KyberSwapExecution>>(buf)?;
+            (execution.desc.0, execution.target_data)
+        } else if selector == KYBER_SWAP_SIMPLE_MODE {
+            let swap = abi::<KyberSwapSimpleMode>(buf)?;
+            (swap.desc.0, swap.executor_data)
+        } else {
+            debug!("unhandled 🐉: {:02x?}", selector);
+            trades.unhandled = Some(selector);
+            return Ok(trades);
+        };
+        debug!(
+            "🐉 swap: {:02x?}/{:02x?}",
+            desc.src_token.0, desc.dst_token.0
+        );
+        push_executor_swap(
+            &mut trades,
+            executor_data.as_ref(),
+            desc.src_token.0,
+            desc.dst_token.0,
+            desc.amount,
+        )?;
+
+        Ok(trades)
+    }
+}
+
+/// 0x exchange proxy
+pub struct ZeroExDecoder;
+
+impl RouterDecoder for ZeroExDecoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        use zero_ex::*;
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        debug!("👌🙅‍♀️");
+        if selector != ZERO_EX_TRANSFORM_ERC20 {
+            debug!("unhandled 👌🙅‍♀️: {:02x?}", selector);
+            trades.unhandled = Some(selector);
+            return Ok(trades);
+        }
+        let outer_transform = abi::<TransformErc20>(buf)?;
+        for t in outer_transform.transformations.0.as_slice() {
+            match t.deployment_nonce {
+                FILL_QUOTE_TRANSFORMER_19 | FILL_QUOTE_TRANSFORMER_21 => {
+                    let Tuple(data) = abi::<Tuple<FillQuoteTransformData>>(t.data.as_ref())?;
+                    // orders we can't simulate are skipped if they may touch monitored pools
+                    let monitored = matches!(
+                        address_to_token(
+                            &route_token(*data.sell_token.0),
+                            &route_token(*data.buy_token.0)
+                        ),
+                        (Some(_), Some(_))
+                    );
+                    let orders = data.bridge_orders.0.as_slice();
+                    for order in orders {
+                        let protocol_id = order.source.0[15];
+                        info!(
+                            "👌🙅‍♀️ trade via: {}",
+                            core::str::from_utf8(&order.source.0[16..32])
+                                .unwrap_or_default()
+                                .trim_end()
+                        );
+                        if (protocol_id == bridge_id::UNISWAPV3
+                            || protocol_id == bridge_id::KYBERELASTIC)
+                            && !(data.fill_amount & *HIGH_BIT).is_zero()
+                        {
+                            // 0x features allows specifying a ratio of user balance as fill amount
+                            // we cant' simulate without pulling it from chain...
+                            info!("0x can't simulate");
+                            trades.skip |= monitored;
+                            return Ok(trades);
+                        }
+                        if protocol_id == bridge_id::UNISWAPV3 {
+                            let v3_trade = abi::<UniswapV3Mixin>(order.data.0)?;
+                            trades.push(
+                                v3_path_trade_info(
+                                    v3_trade.path.as_ref(),
+                                    PathDirection::Forward,
+                                    data.fill_amount,
+                                    ExchangeId::Uniswap,
+                                ),
+                                true,
+                            );
+                        } else if protocol_id == bridge_id::KYBERELASTIC {
+                            // elastic paths are uniswap v3 encoded, with fees in 1/100_000ths
+                            let elastic_trade = abi::<KyberElasticMixin>(order.data.0)?;
+                            trades.push(
+                                v3_path_trade_info(
+                                    elastic_trade.path.as_ref(),
+                                    PathDirection::Forward,
+                                    data.fill_amount,
+                                    ExchangeId::KyberElastic,
+                                ),
+                                true,
+                            );
+                        } else if protocol_id == bridge_id::UNISWAPV2 {
+                            let v2_trade = abi::<UniswapV2Mixin>(order.data.0)?;
+                            match v2_trade.router.0 {
+                                &SUSHI_ROUTER => {
+                                    debug!("sushi via 1inch: {:?}", v2_trade);
+                                    // TODO: lookup fees from some constant
+                                    trades.push(
+                                        v2_path_trade_info::<true>(
+                                            v2_trade.path.as_slice(),
+                                            data.fill_amount,
+                                            V2_FEE,
+                                            ExchangeId::Sushi,
+                                        )?,
+                                        true,
+                                    );
+                                }
+                                &CAMELOT_ROUTER => {
+                                    debug!("camelot via 1inch: {:?}", v2_trade);
+                                    trades.push(
+                                        v2_path_trade_info::<true>(
+                                            v2_trade.path.as_slice(),
+                                            data.fill_amount,
+                                            Fee::ZERO, // camelot edges are keyed without fee
+                                            ExchangeId::Camelot,
+                                        )?,
+                                        true,
+                                    );
+                                }
+                                _ => {
+                                    info!("uniswapV2 via 1inch: {:?}", v2_trade)
+                                }
+                            }
+                        } else {
+                            info!("unhandled protocol Id: {:?}", protocol_id);
+                            trades.skip |= monitored;
+                            return Ok(trades);
+                        }
+                    }
+                }
+                POSITIVE_SLIPPAGE_FEE_TRANSFORMER => (),
+                PAY_TAKER_TRANSFORMER => (),
+                AFFILIATE_FEE_TRANSFORMER => (),
+                WETH_TRANSFORMER => (),
+                _ => println!("unknown transformer: {:?}", t.deployment_nonce),
+            }
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Odos router
+pub struct OdosDecoder;
+
+impl RouterDecoder for OdosDecoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        // https://arbiscan.io/address/0xa0b07f9a11dfb01388149abbdbc5b4f2196600ab#code
+        // ODOS swap: simpler interface available non-opaque
+        // used by Chronos DeFi
+        // the bytecode is opaque and not publicly documented (ODOS wants to protect users from MEV)
+        // skip if the swap tokens or path mention monitored pools
+        let mut trades = DecodedTrades::default();
+        if selector == ODOS_SWAP {
+            debug!("⏰ swap: {:?}", abi::<OdosSwap>(buf)?);
+            trades.skip = touches_monitored(buf);
+        } else {
+            debug!("⏰: {:02x?}", selector);
+            trades.unhandled = Some(selector);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Odos V2 router
+pub struct OdosV2Decoder;
+
+impl RouterDecoder for OdosV2Decoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        let (token_in, token_out, amount, path) = if selector == ODOS_V2_SWAP_COMPACT {
+            let swap = decode_odos_compact(buf).map_err(|_| DecodeError::Malformed)?;
+            (
+                swap.input_token,
+                swap.output_token,
+                swap.input_amount,
+                swap.path_definition,
+            )
+        } else if selector == ODOS_V2_SWAP {
+            let swap = abi::<OdosV2Swap>(buf)?;
+            (
+                Some(*swap.input_token.0),
+                Some(*swap.output_token.0),
+                Some(swap.input_amount).filter(|amount| !amount.is_zero()),
+                swap.path_definition.as_ref(),
+            )
+        } else {
+            debug!("unhandled ⏰: {:02x?}", selector);
+            trades.unhandled = Some(selector);
+            return Ok(trades);
+        };
+        debug!("⏰ swap: {:02x?}/{:02x?}", token_in, token_out);
+
+        // the path definition is opaque, a path through a single known pool connecting the tokens is a direct swap
+        let pools = scan_known_pools(path);
+        if let (Some(token_in), Some(token_out), Some(amount), [pool]) =
+            (token_in, token_out, amount, pools.as_slice())
+        {
+            if let Some(hops) = pools_to_hops(&pools, &odos_token(token_in), &odos_token(token_out))
+            {
+                trades.push(pool_trade_info(pool, hops[0].1, to_u128(amount)?), true);
+                return Ok(trades);
+            }
+        }
+        // token addresses are in the params, pools in the path
+        trades.skip = touches_monitored(buf);
+        if trades.skip {
+            warn!("⏰ unfollowed path: {:02x?}", pools);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Odos denotes native ETH by the zero address, pools trade it as WETH
+fn odos_token(token: [u8; 20]) -> [u8; 20] {
+    if token == [0_u8; 20] {
+        WETH
+    } else {
+        token
+    }
+}
+
+/// Sushi V2 router
+pub struct SushiRouterV2Decoder;
+
+impl RouterDecoder for SushiRouterV2Decoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        if selector == SUSHI_SWAP_EXACT_ETH_FOR_TOKENS
+            || selector == SUSHI_SWAP_EXACT_ETH_FOR_TOKENS_SFOTT
+        {
+            let swap = abi::<SwapExactETHForTokens>(buf)?;
+            trades.push(
+                v2_path_trade_info::<true>(
+                    swap.path.as_slice(),
+                    tx.value,
+                    V2_FEE,
+                    ExchangeId::Sushi,
+                )?,
+                true,
+            );
+        } else if selector == SUSHI_SWAP_EXACT_TOKENS_FOR_ETH
+            || selector == SUSHI_SWAP_EXACT_TOKENS_FOR_ETH_SFOTT
+        {
+            let swap = abi::<SwapExactTokensForETH>(buf)?;
+            trades.push(
+                v2_path_trade_info::<true>(
+                    swap.path.as_slice(),
+                    swap.amount_in,
+                    V2_FEE,
+                    ExchangeId::Sushi,
+                )?,
+                true,
+            );
+        } else {
+            debug!("🍣: {:02x?} unhandled", selector);
+            trades.unhandled = Some(selector);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Sushi `RouteProcessor3` (`v2_fee = false`) and `RouteProcessor4`
+pub struct SushiRouteProcessorDecoder {
+    /// uniswap V2 pool swaps encode their fee
+    v2_fee: bool,
+}
+
+impl RouterDecoder for SushiRouteProcessorDecoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        if selector == SUSHI_ROUTE_PROCESSOR_PROCESS_ROUTE {
+            let swap = abi::<ProcessRoute>(buf)?;
+            debug!(
+                "🍣🛣️ process route: {:02x?}/{:02x?}",
+                swap.token_in.0, swap.token_out.0
+            );
+            push_route(
+                &mut trades,
+                swap.route.as_ref(),
+                to_u128(swap.amount_in)?,
+                self.v2_fee,
+            );
+        } else if selector == SUSHI_ROUTE_PROCESSOR_TRANSFER_VALUE_AND_PROCESS_ROUTE {
+            let swap = abi::<TransferValueAndProcessRoute>(buf)?;
+            debug!(
+                "🍣🛣️ transfer value and process route: {:02x?}/{:02x?}",
+                swap.token_in.0, swap.token_out.0
+            );
+            push_route(
+                &mut trades,
+                swap.route.as_ref(),
+                to_u128(swap.amount_in)?,
+                self.v2_fee,
+            );
+        } else {
+            debug!("🍣🛣️: {:02x?} unhandled", selector);
+            trades.unhandled = Some(selector);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Camelot V2 router
+pub struct CamelotRouterV2Decoder;
+
+impl RouterDecoder for CamelotRouterV2Decoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        if selector == CAMELOT_V2_SWAP_EXACT_ETH_FOR_TOKENS_SFOTT {
+            let swap = abi::<SwapExactETHForTokensSFOTT>(buf)?;
+            trades.push(
+                v2_path_trade_info::<true>(
+                    swap.path.as_slice(),
+                    tx.value,
+                    Fee::ZERO, // camelot edges are keyed without fee
+                    ExchangeId::Camelot,
+                )?,
+                true,
+            );
+        } else if selector == CAMELOT_V2_SWAP_EXACT_TOKENS_FOR_ETH_SFOTT {
+            let swap = abi::<SwapExactTokensForEthSFOTT>(buf)?;
+            trades.push(
+                v2_path_trade_info::<true>(
+                    swap.path.as_slice(),
+                    swap.amount_in,
+                    Fee::ZERO, // camelot edges are keyed without fee
+                    ExchangeId::Camelot,
+                )?,
+                true,
+            );
+        } else {
+            debug!("🛡️: {:02x?} unhandled", selector);
+            trades.unhandled = Some(selector);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Balancer V2 vault
+pub struct BalancerVaultDecoder;
+
+impl RouterDecoder for BalancerVaultDecoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        if selector == BALANCER_VAULT_SWAP {
+            let Tuple(swap) = abi::<Tuple<BalancerSingleSwap>>(buf)?;
+            debug!("⚖️ swap: {:?}", swap.amount);
+            trades.push(
+                balancer_trade_info(
+                    swap.pool_id.0,
+                    swap.asset_in.0,
+                    swap.asset_out.0,
+                    swap.amount,
+                ),
+                swap.kind == BALANCER_GIVEN_IN,
+            );
+        } else if selector == BALANCER_VAULT_BATCH_SWAP {
+            let swap = abi::<BalancerBatchSwap>(buf)?;
+            debug!("⚖️ batch swap: {} steps", swap.swaps.0.len());
+            push_balancer_batch(&mut trades, &swap)?;
+        } else {
+            debug!("⚖️: {:02x?} unhandled", selector);
+            trades.unhandled = Some(selector);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Ramses (Solidly) router
+pub struct RamsesRouterDecoder;
+
+impl RouterDecoder for RamsesRouterDecoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        if selector == SOLIDLY_SWAP_EXACT_TOKENS_FOR_TOKENS
+            || selector == SOLIDLY_SWAP_EXACT_TOKENS_FOR_TOKENS_SFOTT
+            || selector == SOLIDLY_SWAP_EXACT_TOKENS_FOR_ETH
+        {
+            let swap = abi::<SolidlySwapExactTokensFor>(buf)?;
+            debug!("🐏 swap: {:?}", swap.amount_in);
+            trades.push(
+                solidly_routes_trade_info(swap.routes.as_slice(), swap.amount_in)?,
+                true,
+            );
+        } else if selector == SOLIDLY_SWAP_EXACT_ETH_FOR_TOKENS {
+            let swap = abi::<SolidlySwapExactETHForTokens>(buf)?;
+            debug!("🐏 swap: {:?}", tx.value);
+            trades.push(
+                solidly_routes_trade_info(swap.routes.as_slice(), tx.value)?,
+                true,
+            );
+        } else {
+            debug!("🐏: {:02x?} unhandled", selector);
+            trades.unhandled = Some(selector);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Curve stable pool, swaps are called on the pool itself
+pub struct CurvePoolDecoder;
+
+impl RouterDecoder for CurvePoolDecoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        if selector == CURVE_EXCHANGE || selector == CURVE_EXCHANGE_RECEIVER {
+            let swap = abi::<CurveExchange>(buf)?;
+            debug!("🌀 exchange: {:?}", swap.dx);
+            let (coin_0, coin_1) = CURVE_COINS.get(&tx.to.0).ok_or(DecodeError::Malformed)?;
+            let (token_in, token_out) = match (swap.i, swap.j) {
+                (0, 1) => (*coin_0, *coin_1),
+                (1, 0) => (*coin_1, *coin_0),
+                _ => return Err(DecodeError::Malformed),
+            };
+            trades.push(
+                TradeInfo {
+                    amount: swap.dx,
+                    exchange_id: ExchangeId::Curve,
+                    path: vec![(token_in, token_out, Fee::ZERO)],
+                    unknown: vec![],
+                },
+                true,
+            );
+        } else {
+            debug!("🌀: {:02x?} unhandled", selector);
+            trades.unhandled = Some(selector);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// GMX router
+pub struct GmxDecoder;
+
+impl RouterDecoder for GmxDecoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        let path = if selector == GMX_SWAP || selector == GMX_SWAP_TOKENS_TO_ETH {
+            let swap = abi::<GmxSwap>(buf)?;
+            debug!("🫐 swap: {:?}", swap.amount_in);
+            swap.path
+        } else if selector == GMX_SWAP_ETH_TO_TOKENS {
+            debug!("🫐 swap: {:?}", tx.value);
+            abi::<GmxSwapETHToTokens>(buf)?.path
+        } else {
+            debug!("unhandled 🫐: {:02x?}", selector);
+            trades.unhandled = Some(selector);
+            vec![]
+        };
+        // GMX swaps against its vault at oracle prices, there's no pool state to simulate
+        // TODO: model vault swaps as an oracle priced edge
+        if path
+            .iter()
+            .any(|token| Token::from_address(*token.0).is_some())
+        {
+            warn!("🫐 swap on monitored tokens: {:02x?}", path);
+            trades.skip = true;
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Paraswap `AugustusSwapper`
+pub struct ParaswapDecoder;
+
+impl RouterDecoder for ParaswapDecoder {
+    fn decode(&self, tx: &TransactionInfo) -> Result<DecodedTrades, DecodeError> {
+        let (selector, buf) = split_selector(tx.input)?;
+        let mut trades = DecodedTrades::default();
+
+        if selector == PARASWAP_SIMPLE_SWAP {
+            debug!("🦜 simple swap");
+            let Tuple(swap) = abi::<Tuple<paraswap::SimpleData>>(buf)?;
+            // calls to known routers are decoded as is
+            // calls to any other contract touching monitored pools flag the call as skipped
+            let exchange_data = swap.exchange_data.as_ref();
+            for (idx, callee) in swap.callees.iter().enumerate() {
+                let input = swap
+                    .start_indexes
+                    .get(idx..idx + 2)
+                    .and_then(|range| exchange_data.get(range[0] as usize..range[1] as usize))
+                    .ok_or(DecodeError::Malformed)?;
+                if DECODERS.contains_key(callee.0) {
+                    trades.append(try_decode_call(&TransactionInfo {
+                        to: (*callee.0).into(),
+                        input,
+                        ..*tx
+                    })?);
+                } else if POOL_LOOKUP.contains_key(callee.0) || !scan_known_pools(input).is_empty()
+                {
+                    warn!("🦜 simple swap via unsupported exchange: {:02x?}", callee.0);
+                    trades.skip = true;
+                    break;
+                }
+            }
+        } else if selector == PARASWAP_MULTI_SWAP {
+            debug!("🦜 multi swap");
+            let Tuple(swap) = abi::<Tuple<paraswap::SellData>>(buf)?;
+            let slot = trades.credit(to_u128(swap.from_amount)?);
+            push_paraswap_paths(&mut trades, swap.path.0.as_slice(), swap.from_token.0, slot);
+        } else if selector == PARASWAP_MEGA_SWAP {
+            debug!("🦜 mega swap");
+            let Tuple(swap) = abi::<Tuple<paraswap::MegaSwapSellData>>(buf)?;
+            for mega_path in swap.path.0.iter() {
+                let slot = trades.credit(paraswap::share(
+                    to_u128(swap.from_amount)?,
+                    mega_path.from_amount_percent,
+                ));
+                push_paraswap_paths(
+                    &mut trades,
+                    mega_path.path.0.as_slice(),
+                    swap.from_token.0,
+                    slot,
+                );
+            }
+        } else {
+            debug!("unhandled 🦜: {:02x?}", selector);
+            trades.unhandled = Some(selector);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Push the steps of a Balancer vault `batchSwap` in order
+///
+/// Steps with a zero amount chain the previous step's output. Chained exact output steps can't be simulated
+/// (the amount owed isn't credited), these flag the call as skipped if they touch monitored tokens
+fn push_balancer_batch(
+    trades: &mut DecodedTrades,
+    swap: &BalancerBatchSwap,
+) -> Result<(), DecodeError> {
+    let exact_in = swap.kind == BALANCER_GIVEN_IN;
+    let mut previous: Option<usize> = None;
+    for step in swap.swaps.0.iter() {
+        let asset = |idx: u32| {
+            swap.assets
+                .get(idx as usize)
+                .map(|a| a.0)
+                .ok_or(DecodeError::Malformed)
+        };
+        let (asset_in, asset_out) = (asset(step.asset_in_index)?, asset(step.asset_out_index)?);
+        let info = balancer_trade_info(step.pool_id.0, asset_in, asset_out, step.amount);
+        let take = if step.amount.is_zero() {
+            match previous {
+                Some(slot) if exact_in => Some(Take::all(slot)),
+                Some(_) => {
+                    if !info.path.is_empty() {
+                        warn!("⚖️ chained exact output on monitored tokens");
+                        trades.skip = true;
+                    }
+                    continue;
+                }
+                None => return Err(DecodeError::Malformed),
+            }
+        } else {
+            None
+        };
+        let credit = exact_in.then(|| trades.slot());
+        trades.steps.push(TradeStep::Trade {
+            info,
+            exact_in,
+            take,
+            credit,
+            required: exact_in,
+        });
+        previous = credit.or(previous);
+    }
+    Ok(())
+}
+
+/// Split a call `input` into its selector and parameters
+fn split_selector(input: &[u8]) -> Result<([u8; 4], &[u8]), DecodeError> {
+    match input.get(0..4) {
+        Some(selector) => Ok((selector.try_into().unwrap(), &input[4..])),
+        None => Err(DecodeError::Malformed),
+    }
+}
+
+/// Decode ABI encoded `buf`
+///
+/// With the `strict-decode` feature the offsets of `T`'s dynamic fields are checked first
+fn abi<'a, T: DecodeStatic<'a> + AbiLayout>(buf: &'a [u8]) -> Result<T, DecodeError> {
+    if cfg!(feature = "strict-decode") {
+        T::check_offsets(buf).map_err(DecodeError::Offset)?;
+    }
+    T::decode(buf).map_err(|_| DecodeError::Abi)
+}
+
+/// Narrow a calldata `amount` to the simulator's u128 amounts
+fn to_u128(amount: U256) -> Result<u128, DecodeError> {
+    u128::try_from(amount).map_err(|_| DecodeError::Overflow)
+}
+
+/// Push the swaps of a Sushi RouteProcessor `route` in order
+///
+/// Each pool swap is run as a single hop trade, its output is credited to the routed token's balance
+/// which later commands draw from
+fn push_route(trades: &mut DecodedTrades, route: &[u8], amount_in: u128, v2_fee: bool) {
+    let commands = match decode_route(route, v2_fee) {
+        Ok(commands) => commands,
+        Err(err) => {
+            warn!("🍣🛣️ unsupported route: {:?}", err);
+            trades.skip = true;
+            return;
+        }
+    };
+    // balance slots of tokens held by the router or transferred to pools mid route
+    let mut token_slots = Vec::<([u8; 20], usize)>::with_capacity(commands.len());
+
+    for RouteCommand {
+        source,
+        token,
+        swaps,
+    } in commands
+    {
+        let token = route_token(token);
+        let amount_slot = match source {
+            RouteSource::Sender => trades.credit(amount_in),
+            RouteSource::Router | RouteSource::Pool => {
+                let slot = trades.slot();
+                let take = Take::all(token_slot(trades, &mut token_slots, token));
+                trades.steps.push(TradeStep::Move { take, to: slot });
+                slot
+            }
+        };
+        for (share, pool) in swaps {
+            let take = Take {
+                slot: amount_slot,
+                numerator: share as u128,
+                denominator: u16::MAX as u128,
+            };
+            let (pool, zero_for_one) = match pool {
+                RoutePool::UniswapV2 { pool, zero_for_one }
+                | RoutePool::UniswapV3 { pool, zero_for_one } => (pool, zero_for_one),
+                RoutePool::WrapNative => {
+                    // native and wrapped balances are tracked as one
+                    let to = token_slot(trades, &mut token_slots, token);
+                    trades.steps.push(TradeStep::Move { take, to });
+                    continue;
+                }
+            };
+            let info = pool_trade_info(&pool, zero_for_one, 0);
+            let credit = info.path.first().map(|(_, token_out, _)| {
+                token_slot(trades, &mut token_slots, token_out.address().0)
+            });
+            trades.steps.push(TradeStep::Trade {
+                info,
+                exact_in: true,
+                take: Some(take),
+                credit,
+                required: true,
+            });
+        }
+    }
+}
+
+/// The balance slot of a routed `token`, allocated on first use
+fn token_slot(
+    trades: &mut DecodedTrades,
+    token_slots: &mut Vec<([u8; 20], usize)>,
+    token: [u8; 20],
+) -> usize {
+    match token_slots.iter().find(|(t, _)| *t == token) {
+        Some((_, slot)) => *slot,
+        None => {
+            let slot = trades.slot();
+            token_slots.push((token, slot));
+            slot
+        }
+    }
+}
+
+/// Push Paraswap multi/mega swap `paths` selling the balance of `slot` in `token_in`
+///
+/// Routes on any other exchange than uniswap V2/V3 (and forks) flag the call as skipped
+fn push_paraswap_paths(
+    trades: &mut DecodedTrades,
+    paths: &[paraswap::Path],
+    token_in: &[u8; 20],
+    slot: usize,
+) {
+    let mut token_in = *token_in;
+    let mut slot_in = slot;
+    for path in paths {
+        let slot_out = trades.slot();
+        // each route takes its share of the path step's input, tracked as a share of what remains
+        let mut remaining = paraswap::PERCENT_DENOMINATOR * paraswap::PERCENT_DENOMINATOR;
+        for adapter in path.adapters.0.iter() {
+            for route in adapter.route.0.iter() {
+                let percent = adapter.percent as u128 * route.percent as u128;
+                let take = Take {
+                    slot: slot_in,
+                    numerator: percent,
+                    denominator: remaining,
+                };
+                remaining = remaining.saturating_sub(percent);
+                if !push_paraswap_route(trades, route, &token_in, path.to.0, take, slot_out) {
+                    warn!(
+                        "🦜 route via unsupported exchange: {:02x?}",
+                        route.target_exchange.0
+                    );
+                    trades.skip = true;
+                    return;
+                }
+            }
+        }
+        token_in = *path.to.0;
+        slot_in = slot_out;
+    }
+}
+
+/// Push a Paraswap uniswap V2/V3 `route` crediting its output to `credit`, false if unsupported
+fn push_paraswap_route(
+    trades: &mut DecodedTrades,
+    route: &paraswap::Route,
+    token_in: &[u8; 20],
+    token_out: &[u8; 20],
+    take: Take,
+    credit: usize,
+) -> bool {
+    let payload = route.payload.as_ref();
+    if let Some(RouterId::UniswapV3RouterV1 | RouterId::UniswapV3RouterV2) =
+        ROUTERS.get(route.target_exchange.0)
+    {
+        if let Ok(Tuple(data)) = Tuple::<paraswap::UniswapV3Data>::decode(payload) {
+            trades.steps.push(TradeStep::Trade {
+                info: v3_path_trade_info(
+                    data.path.as_ref(),
+                    PathDirection::Forward,
+                    U256::zero(),
+                    ExchangeId::Uniswap,
+                ),
+                exact_in: true,
+                take: Some(take),
+                credit: Some(credit),
+                required: true,
+            });
+            return true;
+        }
+    } else if let Ok(Tuple(data)) = Tuple::<paraswap::UniswapV2Data>::decode(payload) {
+        let pools: Vec<[u8; 20]> = data
+            .pools
+            .iter()
+            .map(|pool| pool.0[12..32].try_into().unwrap())
+            .collect();
+        if let Some(hops) = pools_to_hops(&pools, token_in, token_out) {
+            trades.push_hops(&hops, take, Some(credit));
+            return true;
+        }
+    }
+    false
+}
+
+/// Push the swap actions of a universal router `V4_SWAP` command
+///
+/// Swaps draw from (exact input of the contract balance) and credit the router's `balance` slot.
+/// Paths through unregistered currencies (e.g. native ETH) can't touch monitored pools and are ignored
+fn push_v4_swap(
+    trades: &mut DecodedTrades,
+    swap: &UniswapV4Swap,
+    balance: usize,
+) -> Result<(), DecodeError> {
+    use v4_action::*;
+    for (idx, action) in swap.actions.as_ref().iter().enumerate() {
+        let params = swap.params.get(idx).ok_or(DecodeError::Malformed)?.as_ref();
+        let step = match *action {
+            SWAP_EXACT_IN_SINGLE | SWAP_EXACT_OUT_SINGLE => {
+                let Tuple(params) = abi::<Tuple<UniswapV4SwapSingleParams>>(params)?;
+                let (token_in, token_out) = if params.zero_for_one {
+                    (params.currency_0.0, params.currency_1.0)
+                } else {
+                    (params.currency_1.0, params.currency_0.0)
+                };
+                let amount = params.amount.into();
+                let exact_in = *action == SWAP_EXACT_IN_SINGLE;
+                TradeStep::Trade {
+                    info: v4_trade_info(&[(token_in, token_out, params.fee)], amount),
+                    exact_in,
+                    take: if exact_in {
+                        contract_balance_or(amount, balance)
+                    } else {
+                        None
+                    },
+                    credit: Some(balance),
+                    required: false,
+                }
+            }
+            SWAP_EXACT_IN => {
+                let Tuple(params) = abi::<Tuple<UniswapV4SwapParams>>(params)?;
+                let mut currency = params.currency.0;
+                let hops: Vec<_> = params
+                    .path
+                    .0
+                    .iter()
+                    .map(|key| {
+                        let hop = (currency, key.intermediate_currency.0, key.fee);
+                        currency = key.intermediate_currency.0;
+                        hop
+                    })
+                    .collect();
+                let amount = params.amount.into();
+                TradeStep::Trade {
+                    info: v4_trade_info(&hops, amount),
+                    exact_in: true,
+                    take: contract_balance_or(amount, balance),
+                    credit: Some(balance),
+                    required: false,
+                }
+            }
+            SWAP_EXACT_OUT => {
+                let Tuple(params) = abi::<Tuple<UniswapV4SwapParams>>(params)?;
+                // path keys are ordered from the input, apply from the output
+                let mut currency = params.currency.0;
+                let hops: Vec<_> = params
+                    .path
+                    .0
+                    .iter()
+                    .rev()
+                    .map(|key| {
+                        let hop = (currency, key.intermediate_currency.0, key.fee);
+                        currency = key.intermediate_currency.0;
+                        hop
+                    })
+                    .collect();
+                TradeStep::Trade {
+                    info: v4_trade_info(&hops, params.amount.into()),
+                    exact_in: false,
+                    take: None,
+                    credit: Some(balance),
+                    required: false,
+                }
+            }
+            // settle, take, etc.
+            _ => continue,
+        };
+        trades.steps.push(step);
+    }
+    Ok(())
+}
+
+/// Build trade info from uniswap compliant `path` bytes of `exchange_id` pools, encoded in `direction`
+///
+/// Trades are in path order, as the simulator applies them: exact input (forward) paths from the first swap as
+/// (token in, token out), exact output (reverse) paths from the last swap as (token out, token in).
+/// Path fees are as quoted by `exchange_id` pools e.g. kyber elastic fees are 1/100_000ths.
+/// The path is empty if malformed
+fn v3_path_trade_info(
+    path: &[u8],
+    direction: PathDirection,
+    amount: U256,
+    exchange_id: ExchangeId,
+) -> TradeInfo {
+    let path = Path::parse(path, direction);
+    let mut trade_info = TradeInfo {
+        amount,
+        exchange_id,
+        path: Vec::with_capacity(path.map_or(0, |path| path.pool_count())),
+        unknown: vec![],
+    };
+
+    for (token_a, fee, token_b) in path.into_iter().flat_map(Path::pools) {
+        match address_to_token(token_a, token_b) {
+            (Some(a), Some(b)) => trade_info
+                .path
+                .push((a, b, Fee::from_quoted(exchange_id, fee))),
+            _ => {
+                // trade is through a path we aren't monitoring locally
+                trade_info
+                    .unknown
+                    .push(((*token_a).into(), (*token_b).into(), fee));
+                debug!("{:02x?}/{:02x?}/{fee}", token_a, token_b);
+            }
+        }
+    }
+
+    trade_info
+}
+
+/// Build trade info from uniswap V2 compliant `path` addresses
+///
+/// exact output trades (`!D`) are applied from the end of the `path`
+fn v2_path_trade_info<const D: bool>(
+    path: &[AddressZcp],
+    amount: U256,
+    fee: Fee,
+    exchange_id: ExchangeId,
+) -> Result<TradeInfo, DecodeError> {
+    if path.len() < 2 {
+        return Err(DecodeError::Malformed);
+    }
+    let trade_count = path.len() - 1;
+    let mut trade_info = TradeInfo {
+        amount,
+        exchange_id,
+        path: Vec::with_capacity(trade_count),
+        unknown: vec![],
+    };
+
+    (0..trade_count).for_each(|idx| {
+        let (token_in, token_out) = if D {
+            (path[idx].0, path[idx + 1].0)
+        } else {
+            // (token out, token in) of the hops in reverse
+            (path[trade_count - idx].0, path[trade_count - idx - 1].0)
+        };
+        let (a, b) = address_to_token(token_in, token_out);
+        match (a, b) {
+            (Some(a), Some(b)) => trade_info.path.push((a, b, fee)),
+            _ => {
+                // trade is through a path we aren't monitoring locally
+                trade_info
+                    .unknown
+                    .push(((*token_in).into(), (*token_out).into(), 0));
+                debug!("{:02x?}/{:02x?}/0", token_in, token_out);
+            }
+        }
+    });
+
+    Ok(trade_info)
+}
+
+/// Build trade info from Solidly router `routes`
+///
+/// Hops are matched to configured pairs by deriving the pair address (tokens + stable), hops through other pairs are unknown
+fn solidly_routes_trade_info(
+    routes: &[SolidlyRoute],
+    amount: U256,
+) -> Result<TradeInfo, DecodeError> {
+    if routes.is_empty() {
+        return Err(DecodeError::Malformed);
+    }
+    let mut trade_info = TradeInfo {
+        amount,
+        exchange_id: ExchangeId::Ramses,
+        path: Vec::with_capacity(routes.len()),
+        unknown: vec![],
+    };
+    for route in routes {
+        let pair = RAMSES_FACTORY
+            .map(|factory| {
+                factory.pair_address((*route.from.0).into(), (*route.to.0).into(), route.stable)
+            })
+            .and_then(|pool| POOL_LOOKUP.get(&pool.0))
+            .filter(|pair| pair.exchange_id == ExchangeId::Ramses);
+        match (pair, address_to_token(route.from.0, route.to.0)) {
+            (Some(pair), (Some(a), Some(b))) => trade_info.path.push((a, b, pair.fee)),
+            _ => {
+                trade_info
+                    .unknown
+                    .push(((*route.from.0).into(), (*route.to.0).into(), 0));
+                debug!("{:02x?}/{:02x?}/0", route.from.0, route.to.0);
+            }
+        }
+    }
+
+    Ok(trade_info)
+}
+
+/// Build trade info from exact|output single
+fn exact_single_to_trade_info(
+    token_in: &[u8; 20],
+    token_out: &[u8; 20],
+    amount: U256,
+    fee: u32,
+) -> TradeInfo {
+    let (a, b) = address_to_token(token_in, token_out);
+    match (a, b) {
+        (Some(a), Some(b)) => TradeInfo {
+            path: vec![(a, b, Fee::from_pips(fee))],
+            unknown: vec![],
+            amount,
+            exchange_id: ExchangeId::Uniswap,
+        },
+        _ => TradeInfo {
+            path: vec![],
+            unknown: vec![(token_in.into(), token_out.into(), fee)],
+            amount,
+            exchange_id: ExchangeId::Uniswap,
+        },
+    }
+}
+
+/// Build trade info from 1inch packed uniswap V3 `pools`
+fn one_inch_pools_trade_info(pools: &[Bytes32], amount: U256) -> TradeInfo {
+    let mut trade_info = TradeInfo {
+        amount,
+        exchange_id: ExchangeId::Uniswap,
+        path: vec![],
+        unknown: vec![],
+    };
+    for pool in pools {
+        let pool_bytes = pool.0;
+        let zero_for_one = pool_bytes[0] & 0x01 == 0;
+        let pool_address: [u8; 20] =
+            unsafe { *(&pool_bytes[12..32] as *const [u8] as *const [u8; 20]) };
+        if let Some(pool) = POOL_LOOKUP.get(&pool_address) {
+            if zero_for_one {
+                trade_info.path.push((pool.token0, pool.token1, pool.fee));
+            } else {
+                trade_info.path.push((pool.token1, pool.token0, pool.fee));
+            }
+        } else {
+            trade_info
+                .unknown
+                .push((pool_address.into(), pool_address.into(), 0_u32));
+        }
+    }
+    trade_info
+}
+
+/// Normalize a routed token, the native token is tracked as WETH
+fn route_token(token: [u8; 20]) -> [u8; 20] {
+    if token == NATIVE_TOKEN {
+        WETH
+    } else {
+        token
+    }
+}
+
+/// Take the router's `balance` slot if `amount` is the universal router's contract balance flag
+fn contract_balance_or(amount: U256, balance: usize) -> Option<Take> {
+    (amount == UNIVERSAL_ROUTER_CONTRACT_BALANCE).then(|| Take::all(balance))
+}
+
+/// Build trade info from uniswap v4 `hops` (currency in|out, currency out|in, fee)
+///
+/// The path is dropped if any currency is unregistered
+fn v4_trade_info(hops: &[(&[u8; 20], &[u8; 20], u32)], amount: U256) -> TradeInfo {
+    let path = hops
+        .iter()
+        .map(|(a, b, fee)| match address_to_token(a, b) {
+            (Some(a), Some(b)) => Some((a, b, Fee::from_pips(*fee))),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default();
+    TradeInfo {
+        amount,
+        exchange_id: ExchangeId::UniswapV4,
+        path,
+        unknown: vec![],
+    }
+}
+
+/// Build a single hop trade info for a swap on `pool`
+fn pool_trade_info(pool: &[u8; 20], zero_for_one: bool, amount: u128) -> TradeInfo {
+    match POOL_LOOKUP.get(pool) {
+        Some(pair) => {
+            let (token_in, token_out) = if zero_for_one {
+                (pair.token0, pair.token1)
+            } else {
+                (pair.token1, pair.token0)
+            };
+            TradeInfo {
+                amount: amount.into(),
+                exchange_id: pair.exchange_id,
+                path: vec![(token_in, token_out, pair.fee)],
+                unknown: vec![],
+            }
+        }
+        // unmonitored, the exchange is irrelevant
+        None => TradeInfo {
+            amount: amount.into(),
+            exchange_id: ExchangeId::Sushi,
+            path: vec![],
+            unknown: vec![((*pool).into(), (*pool).into(), 0_u32)],
+        },
+    }
+}
+
+/// Build a single hop trade info for a Balancer vault swap on pool `pool_id`
+///
+/// Balancer edges are keyed by token pair (fee 0), swaps on pools we don't sync are unknown
+fn balancer_trade_info(
+    pool_id: &[u8; 32],
+    asset_in: &[u8; 20],
+    asset_out: &[u8; 20],
+    amount: U256,
+) -> TradeInfo {
+    // the vault's native ETH placeholder is the zero address
+    let asset = |a: &[u8; 20]| if *a == [0_u8; 20] { WETH } else { *a };
+    let (asset_in, asset_out) = (asset(asset_in), asset(asset_out));
+    let pool: &[u8; 20] = pool_id[..20].try_into().unwrap();
+    let synced = POOL_LOOKUP
+        .get(pool)
+        .is_some_and(|pair| pair.exchange_id == ExchangeId::Balancer);
+    match address_to_token(&asset_in, &asset_out) {
+        (Some(a), Some(b)) if synced => TradeInfo {
+            amount,
+            exchange_id: ExchangeId::Balancer,
+            path: vec![(a, b, Fee::ZERO)],
+            unknown: vec![],
+        },
+        _ => TradeInfo {
+            amount,
+            exchange_id: ExchangeId::Balancer,
+            path: vec![],
+            unknown: vec![(asset_in.into(), asset_out.into(), 0)],
+        },
+    }
+}
+
+/// Push an aggregator swap of `amount` from `token_in` to `token_out` through opaque `executor_data`
+///
+/// The pools are recovered from the executor calldata, known pools are simulated in order if they form a path from
+/// `token_in` to `token_out`. Otherwise the swap touched monitored pools in a way we can't follow (e.g. split routes)
+/// and is skipped
+fn push_executor_swap(
+    trades: &mut DecodedTrades,
+    executor_data: &[u8],
+    token_in: &[u8; 20],
+    token_out: &[u8; 20],
+    amount: U256,
+) -> Result<(), DecodeError> {
+    let pools = scan_known_pools(executor_data);
+    if pools.is_empty() {
+        debug!("swap on unmonitored pools");
+    } else if let Some(hops) = pools_to_hops(&pools, token_in, token_out) {
+        let slot = trades.credit(to_u128(amount)?);
+        trades.push_hops(&hops, Take::all(slot), None);
+    } else {
+        warn!("swap unfollowed path: {:02x?}", pools);
+        trades.skip = true;
+    }
+    Ok(())
+}
+
+/// Order single pool swaps along `pools` starting from `token_in`
+///
+/// `None` if any pool is unknown or they don't form a path to `token_out`
+fn pools_to_hops(
+    pools: &[[u8; 20]],
+    token_in: &[u8; 20],
+    token_out: &[u8; 20],
+) -> Option<Vec<([u8; 20], bool)>> {
+    let mut token = Token::from_address(route_token(*token_in))?;
+    let mut hops = Vec::with_capacity(pools.len());
+    for pool in pools {
+        let pair = POOL_LOOKUP.get(pool)?;
+        let zero_for_one = if token == pair.token0 {
+            true
+        } else if token == pair.token1 {
+            false
+        } else {
+            return None;
+        };
+        token = if zero_for_one {
+            pair.token1
+        } else {
+            pair.token0
+        };
+        hops.push((*pool, zero_for_one));
+    }
+    (Token::from_address(route_token(*token_out)) == Some(token)).then_some(hops)
+}
+
+/// Lookup token addresses returning corresponding `Token`s, if matched
+fn address_to_token<'a>(
+    token_in: &'a [u8; 20],
+    token_out: &'a [u8; 20],
+) -> (Option<Token>, Option<Token>) {
+    (
+        Token::from_address(*token_in),
+        Token::from_address(*token_out),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constant::arbitrum::CURVE_2POOL;
+    use ethers::types::Address;
+    use hex_literal::hex;
+
+    fn call(to: [u8; 20], input: &[u8]) -> TransactionInfo<'_> {
+        TransactionInfo {
+            to: to.into(),
+            value: U256::zero(),
+            input,
+            nonce: 0,
+            gas_limit: 0,
+            max_fee_per_gas: 0,
+        }
+    }
+
+    /// xorshift, deterministic junk for calldata
+    struct Junk(u64);
+
+    impl Junk {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+        /// An ABI word, biased toward values which pass as offsets, lengths and addresses
+        fn word(&mut self) -> [u8; 32] {
+            let mut word = [0_u8; 32];
+            match self.next() % 4 {
+                0 => word[31] = (self.next() % 8) as u8 * 32,
+                1 => word[24..].copy_from_slice(&self.next().to_be_bytes()),
+                2 => word[12..32].copy_from_slice(&Token::USDC.address().0),
+                _ => word
+                    .chunks_mut(8)
+                    .for_each(|c| c.copy_from_slice(&self.next().to_be_bytes())),
+            }
+            word
+        }
+    }
+
+    /// Decoding truncated and corrupted copies of `input` must not panic
+    fn assert_survives_mutations(decoder: &dyn RouterDecoder, input: &[u8]) {
+        let mut junk = Junk(0x5eed);
+        for len in 0..input.len() {
+            let _ = decoder.decode(&call([0_u8; 20], &input[..len]));
+        }
+        let mut mutant = input.to_vec();
+        for _ in 0..2_000 {
+            mutant.copy_from_slice(input);
+            for _ in 0..1 + junk.next() % 4 {
+                let idx = junk.next() as usize % mutant.len();
+                mutant[idx] = junk.next() as u8;
+            }
+            let _ = decoder.decode(&call([0_u8; 20], &mutant));
+        }
+    }
+
+    #[test]
+    fn opaque_router_policies() {
+        let usdc = Token::USDC.address().0;
+        let pair = [
+            hex!("deadbeef").as_slice(),
+            &usdc,
+            &[0_u8; 7],
+            &NATIVE_TOKEN,
+        ]
+        .concat();
+        let single = [hex!("deadbeef").as_slice(), &usdc, &[0_u8; 12]].concat();
+        let pool = [
+            hex!("deadbeef").as_slice(),
+            &hex!("c31e54c7a869b9fcbecc14363cf510d1c41fa443"),
+        ]
+        .concat();
+        let tx = |input| call([0x11; 20], input);
+
+        assert!(!opaque_trades(OpaqueRouterPolicy::Ignore, &tx(&pair)).skip);
+        assert!(opaque_trades(OpaqueRouterPolicy::SkipRound, &tx(&single)).skip);
+        assert!(opaque_trades(OpaqueRouterPolicy::Tokens, &tx(&pair)).skip);
+        assert!(opaque_trades(OpaqueRouterPolicy::Tokens, &tx(&pool)).skip);
+        assert!(!opaque_trades(OpaqueRouterPolicy::Tokens, &tx(&single)).skip);
+        // unknown contracts are ignored by the bundled config
+        assert_eq!(decode_call(&tx(&pair)), DecodedTrades::default());
+    }
+
+    #[test]
+    fn malformed_calldata() {
+        let selectors = [
+            UNISWAP_V3_V1_EXACT_INPUT,
+            UNISWAP_V3_V1_EXACT_INPUT_SINGLE,
+            UNISWAP_V3_V1_EXACT_OUTPUT,
+            UNISWAP_V3_V1_EXACT_OUTPUT_SINGLE,
+            UNISWAP_V3_V2_EXACT_INPUT,
+            UNISWAP_V3_V2_EXACT_INPUT_SINGLE,
+            UNISWAP_V3_V2_EXACT_OUTPUT,
+            UNISWAP_V3_V2_EXACT_OUTPUT_SINGLE,
+            UNISWAP_V3_MULTI_CALL,
+            UNISWAP_V3_MULTI_CALL_DEADLINE,
+            UNISWAP_UNIVERSAL_ROUTER_EXECUTE,
+            UNISWAP_UNIVERSAL_ROUTER_EXECUTE_DEADLINE,
+            ONE_INCH_UNISWAP_V3_SWAP,
+            ONE_INCH_UNISWAP_V3_SWAP_TWP,
+            ONE_INCH_UNISWAP_SWAP,
+            GMX_SWAP,
+            GMX_SWAP_TOKENS_TO_ETH,
+            GMX_SWAP_ETH_TO_TOKENS,
+            PARASWAP_SIMPLE_SWAP,
+            PARASWAP_MULTI_SWAP,
+            PARASWAP_MEGA_SWAP,
+            ZERO_EX_TRANSFORM_ERC20,
+            ODOS_SWAP,
+            SUSHI_SWAP_EXACT_TOKENS_FOR_ETH,
+            SUSHI_SWAP_EXACT_TOKENS_FOR_ETH_SFOTT,
+            SUSHI_SWAP_EXACT_ETH_FOR_TOKENS,
+            SUSHI_SWAP_EXACT_ETH_FOR_TOKENS_SFOTT,
+            CAMELOT_V2_SWAP_EXACT_TOKENS_FOR_ETH_SFOTT,
+            CAMELOT_V2_SWAP_EXACT_ETH_FOR_TOKENS_SFOTT,
+            SUSHI_ROUTE_PROCESSOR_PROCESS_ROUTE,
+            SUSHI_ROUTE_PROCESSOR_TRANSFER_VALUE_AND_PROCESS_ROUTE,
+            BALANCER_VAULT_SWAP,
+            BALANCER_VAULT_BATCH_SWAP,
+            CURVE_EXCHANGE,
+            CURVE_EXCHANGE_RECEIVER,
+            SOLIDLY_SWAP_EXACT_TOKENS_FOR_TOKENS,
+            SOLIDLY_SWAP_EXACT_TOKENS_FOR_TOKENS_SFOTT,
+            SOLIDLY_SWAP_EXACT_TOKENS_FOR_ETH,
+            SOLIDLY_SWAP_EXACT_ETH_FOR_TOKENS,
+            KYBER_SWAP,
+            KYBER_SWAP_GENERIC,
+            KYBER_SWAP_SIMPLE_MODE,
+            ODOS_V2_SWAP,
+            ODOS_V2_SWAP_COMPACT,
+        ];
+        let decoders = [
+            RouterId::UniswapV3RouterV1,
+            RouterId::UniswapV3RouterV2,
+            RouterId::UniswapV3UniversalRouter,
+            RouterId::SushiRouterV2,
+            RouterId::CamelotRouterV2,
+            RouterId::Gmx,
+            RouterId::ParaswapAugustus,
+            RouterId::OneInch,
+            RouterId::ZeroEx,
+            RouterId::Odos,
+            RouterId::SushiRouteProcessor3,
+            RouterId::SushiRouteProcessor4,
+            RouterId::BalancerVault,
+            RouterId::RamsesRouter,
+            RouterId::KyberSwap,
+            RouterId::OdosV2,
+        ]
+        .map(decoder);
+
+        let mut junk = Junk(0xf00d);
+        for selector in selectors {
+            for _ in 0..200 {
+                let words = junk.next() % 16;
+                let mut input = selector.to_vec();
+                (0..words).for_each(|_| input.extend_from_slice(&junk.word()));
+                // sometimes unaligned
+                if words > 0 && junk.next() & 1 == 0 {
+                    input.truncate(input.len() - (junk.next() % 32) as usize);
+                }
+                for decoder in decoders {
+                    let _ = decoder.decode(&call([0_u8; 20], &input));
+                }
+                let _ = CurvePoolDecoder.decode(&call(CURVE_2POOL, &input));
+            }
+        }
+        // too short for a selector
+        for decoder in decoders {
+            assert!(decoder.decode(&call([0_u8; 20], &[0x12, 0x34])).is_err());
+        }
+    }
+
+    #[test]
+    fn take_balance_share() {
+        let mut balances = [100_u128, 0];
+        let share = Take {
+            slot: 0,
+            numerator: 1,
+            denominator: 4,
+        };
+        assert_eq!(share.apply(&mut balances), 25);
+        assert_eq!(Take::all(0).apply(&mut balances), 75);
+        assert_eq!(balances, [0, 0]);
+
+        // appended trades keep their own balance slots
+        let mut trades = DecodedTrades::default();
+        trades.credit(1);
+        let mut other = DecodedTrades::default();
+        let (slot, to) = (other.credit(2), other.slot());
+        other.steps.push(TradeStep::Move {
+            take: Take::all(slot),
+            to,
+        });
+        trades.append(other);
+        assert_eq!(trades.slots, 3);
+        assert_eq!(
+            trades.steps[2],
+            TradeStep::Move {
+                take: Take::all(1),
+                to: 2
+            }
+        );
+    }
+
+    #[test]
+    fn universal_router_decoder() {
+        // two USDC -> ARB exact input commands, direct and via WETH
+        let input = [
+            UNISWAP_UNIVERSAL_ROUTER_EXECUTE.as_slice(),
+            &hex!("000000000000000000000000000000000000000000000000000000000000006000000000000000000000000000000000000000000000000000000000000000a000000000000000000000000000000000000000000000000000000000646ed6d700000000000000000000000000000000000000000000000000000000000000020000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000200000000000000000000000000000000000000000000000000000000000000400000000000000000000000000000000000000000000000000000000000000160000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000ba43b740000000000000000000000000000000000000000000000098a1b3fd24f4d168ea200000000000000000000000000000000000000000000000000000000000000a00000000000000000000000000000000000000000000000000000000000000001000000000000000000000000000000000000000000000000000000000000002bff970a61a04b1ca14834a43f5de4533ebddb5cc80001f4912ce59144191c1204e64559fe8253a0e49e6548000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000012000000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000ba43b740000000000000000000000000000000000000000000000098b057a68577b20cfaa00000000000000000000000000000000000000000000000000000000000000a000000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000042ff970a61a04b1ca14834a43f5de4533ebddb5cc80001f482af49447d8a07e3bd95bd0d56f35241523fbab10001f4912ce59144191c1204e64559fe8253a0e49e6548000000000000000000000000000000000000000000000000000000000000"),
+        ]
+        .concat();
+        let trades = UniversalRouterDecoder
+            .decode(&call([0_u8; 20], &input))
+            .unwrap();
+        assert!(!trades.skip);
+        assert_eq!(trades.slots, 1);
+        assert_eq!(trades.steps.len(), 2);
+        assert_eq!(
+            trades.steps[0],
+            TradeStep::Trade {
+                info: TradeInfo {
+                    amount: 50_000_000_000_u64.into(),
+                    path: vec![(Token::USDC, Token::ARB, Fee::from_pips(500))],
+                    exchange_id: ExchangeId::Uniswap,
+                    unknown: vec![],
+                },
+                exact_in: true,
+                take: None,
+                credit: Some(0),
+                required: false,
+            }
+        );
+        // not a swap
+        assert_eq!(
+            UniversalRouterDecoder.decode(&call([0_u8; 20], &hex!("deadbeef00"))),
+            Ok(DecodedTrades {
+                unhandled: Some(hex!("deadbeef")),
+                ..Default::default()
+            })
+        );
+        assert_survives_mutations(&UniversalRouterDecoder, &input);
+    }
+
+    #[test]
+    fn universal_router_permits() {
+        use ethers::abi::{encode, Token as ABIToken};
+        use universal_router_command::*;
+
+        let execute = |commands: &[u8], inputs: Vec<Vec<u8>>| {
+            [
+                UNISWAP_UNIVERSAL_ROUTER_EXECUTE.as_slice(),
+                &encode(&[
+                    ABIToken::Bytes(commands.to_vec()),
+                    ABIToken::Array(inputs.into_iter().map(ABIToken::Bytes).collect()),
+                    ABIToken::Uint(0.into()),
+                ]),
+            ]
+            .concat()
+        };
+        let recipient = ABIToken::Address(Address::repeat_byte(0x11));
+        let permit = encode(&[
+            ABIToken::Tuple(vec![
+                ABIToken::Tuple(vec![
+                    ABIToken::Address(Token::USDC.address()),
+                    ABIToken::Uint(U256::MAX >> 96),
+                    ABIToken::Uint(1_700_000_000.into()),
+                    ABIToken::Uint(0.into()),
+                ]),
+                recipient.clone(),
+                ABIToken::Uint(1_700_000_000.into()),
+            ]),
+            ABIToken::Bytes(vec![0x1b; 65]),
+        ]);
+        let v3_swap = encode(&[
+            recipient.clone(),
+            ABIToken::Uint(100_000_000.into()),
+            ABIToken::Uint(0.into()),
+            ABIToken::Bytes(
+                [
+                    Token::USDC.address().0.as_slice(),
+                    &hex!("0001f4"),
+                    &Token::WETH.address().0,
+                ]
+                .concat(),
+            ),
+            ABIToken::Bool(true),
+        ]);
+        let v3_trade = || TradeStep::Trade {
+            info: TradeInfo {
+                amount: 100_000_000_u128.into(),
+                path: vec![(Token::USDC, Token::WETH, Fee::from_pips(500))],
+                exchange_id: ExchangeId::Uniswap,
+                unknown: vec![],
+            },
+            exact_in: true,
+            take: None,
+            credit: Some(0),
+            required: false,
+        };
+
+        // permit, swap USDC -> WETH, pay a 0.25% fee, unwrap the rest
+        let input = execute(
+            &[PERMIT2_PERMIT, V3_SWAP_EXACT_IN, PAY_PORTION, UNWRAP_WETH],
+            vec![
+                permit.clone(),
+                v3_swap.clone(),
+                encode(&[
+                    ABIToken::Address(Token::WETH.address()),
+                    recipient.clone(),
+                    ABIToken::Uint(25.into()),
+                ]),
+                encode(&[recipient.clone(), ABIToken::Uint(0.into())]),
+            ],
+        );
+        let trades = UniversalRouterDecoder
+            .decode(&call([0_u8; 20], &input))
+            .unwrap();
+        assert!(!trades.skip);
+        assert_eq!(trades.slots, 3);
+        assert_eq!(
+            trades.steps,
+            vec![
+                v3_trade(),
+                TradeStep::Move {
+                    take: Take {
+                        slot: 0,
+                        numerator: 25,
+                        denominator: 10_000
+                    },
+                    to: 1
+                },
+                TradeStep::Move {
+                    take: Take::all(0),
+                    to: 2
+                },
+            ]
+        );
+        assert_survives_mutations(&UniversalRouterDecoder, &input);
+
+        // permit2 transfer to the pair, v2 swap of the paid amount
+        let input = execute(
+            &[PERMIT2_TRANSFER_FROM, V2_SWAP_EXACT_IN],
+            vec![
+                encode(&[
+                    ABIToken::Address(Token::WETH.address()),
+                    recipient.clone(),
+                    ABIToken::Uint(10_u128.pow(18).into()),
+                ]),
+                encode(&[
+                    recipient.clone(),
+                    ABIToken::Uint(UNIVERSAL_ROUTER_ALREADY_PAID),
+                    ABIToken::Uint(0.into()),
+                    ABIToken::Array(vec![
+                        ABIToken::Address(Token::WETH.address()),
+                        ABIToken::Address(Token::USDC.address()),
+                    ]),
+                    ABIToken::Bool(false),
+                ]),
+            ],
+        );
+        let trades = UniversalRouterDecoder
+            .decode(&call([0_u8; 20], &input))
+            .unwrap();
+        assert_eq!(
+            trades.steps[0],
+            TradeStep::Credit {
+                slot: 0,
+                amount: 10_u128.pow(18)
+            }
+        );
+        assert!(matches!(
+            &trades.steps[1],
+            TradeStep::Trade { info, take: Some(take), .. }
+                if *take == Take::all(0) && info.path == vec![(Token::WETH, Token::USDC, Fee::from_units(300))]
+        ));
+        assert_survives_mutations(&UniversalRouterDecoder, &input);
+
+        // permit then a sub plan (allowed to revert) with the swap
+        let sub_plan = encode(&[
+            ABIToken::Bytes(vec![V3_SWAP_EXACT_IN]),
+            ABIToken::Array(vec![ABIToken::Bytes(v3_swap.clone())]),
+        ]);
+        let input = execute(
+            &[PERMIT2_PERMIT, 0x80 | EXECUTE_SUB_PLAN],
+            vec![permit.clone(), sub_plan.clone()],
+        );
+        let trades = UniversalRouterDecoder
+            .decode(&call([0_u8; 20], &input))
+            .unwrap();
+        assert_eq!(trades.steps, vec![v3_trade()]);
+        // nested sub plans are skipped
+        let nested = encode(&[
+            ABIToken::Bytes(vec![EXECUTE_SUB_PLAN]),
+            ABIToken::Array(vec![ABIToken::Bytes(sub_plan)]),
+        ]);
+        let input = execute(&[EXECUTE_SUB_PLAN], vec![nested]);
+        assert!(
+            UniversalRouterDecoder
+                .decode(&call([0_u8; 20], &input))
+                .unwrap()
+                .skip
+        );
+        // every command has an input
+        let input = execute(&[PERMIT2_PERMIT, V3_SWAP_EXACT_IN], vec![v3_swap]);
+        assert_eq!(
+            UniversalRouterDecoder.decode(&call([0_u8; 20], &input)),
+            Err(DecodeError::Malformed)
+        );
+    }
+
+    #[test]
+    fn swap_router_02_wraps_eth() {
+        fn word(value: u128) -> [u8; 32] {
+            let mut word = [0_u8; 32];
+            U256::from(value).to_big_endian(&mut word);
+            word
+        }
+        fn address(token: Token) -> [u8; 32] {
+            let mut word = [0_u8; 32];
+            word[12..].copy_from_slice(&token.address().0);
+            word
+        }
+        // multicall(wrapETH(1 ETH), exactInputSingle(WETH -> USDC, contract balance), unwrapWETH9(0))
+        let wrap = [UNISWAP_V3_V2_WRAP_ETH.as_slice(), &word(10_u128.pow(18))].concat();
+        let swap = [
+            UNISWAP_V3_V2_EXACT_INPUT_SINGLE.as_slice(),
+            &address(Token::WETH),
+            &address(Token::USDC),
+            &word(500),
+            &word(1),
+            &word(0),
+            &word(0),
+            &word(0),
+        ]
+        .concat();
+        let unwrap = [UNISWAP_V3_V2_UNWRAP_WETH9.as_slice(), &word(0)].concat();
+        let calls = [wrap, swap, unwrap];
+        let mut head = vec![word(0x20), word(calls.len() as u128)];
+        let mut tail = Vec::<u8>::new();
+        for call in calls.iter() {
+            head.push(word((calls.len() * 32 + tail.len()) as u128));
+            tail.extend_from_slice(&word(call.len() as u128));
+            tail.extend_from_slice(call);
+            tail.resize(tail.len() + (32 - call.len() % 32) % 32, 0);
+        }
+        let input = [UNISWAP_V3_MULTI_CALL.as_slice(), &head.concat(), &tail].concat();
+
+        let trades = UniswapV3RouterV2Decoder
+            .decode(&call([0_u8; 20], &input))
+            .unwrap();
+        assert_eq!(trades.slots, 2);
+        assert_eq!(
+            trades.steps[0],
+            TradeStep::Credit {
+                slot: 0,
+                amount: 10_u128.pow(18)
+            }
+        );
+        assert!(matches!(
+            &trades.steps[1],
+            TradeStep::Trade { info, take: Some(take), .. }
+                if *take == Take::all(0) && info.path == vec![(Token::WETH, Token::USDC, Fee::from_pips(500))]
+        ));
+        assert_eq!(
+            trades.steps[2],
+            TradeStep::Move {
+                take: Take::all(0),
+                to: 1
+            }
+        );
+        assert_survives_mutations(&UniswapV3RouterV2Decoder, &input);
+    }
+
+    #[test]
+    fn v3_exact_output_paths_reverse() {
+        use ethers::abi::{encode, Token as ABIToken};
+        // ARB -> WETH (0.3%) -> USDC (0.05%)
+        let tokens = [Token::ARB, Token::WETH, Token::USDC].map(|token| token.address().0);
+        let params = |path: Vec<u8>| {
+            encode(&[ABIToken::Tuple(vec![
+                ABIToken::Bytes(path),
+                ABIToken::Address(Address::zero()),
+                ABIToken::Uint(1_000_000_u64.into()),
+                ABIToken::Uint(2_000_000_u64.into()),
+            ])])
+        };
+        let exact_input = [
+            UNISWAP_V3_V2_EXACT_INPUT.as_slice(),
+            &params(Path::encode(&tokens, &[3_000, 500])),
+        ]
+        .concat();
+        // exact output paths are encoded from the output token
+        let [arb, weth, usdc] = tokens;
+        let exact_output = [
+            UNISWAP_V3_V2_EXACT_OUTPUT.as_slice(),
+            &params(Path::encode(&[usdc, weth, arb], &[500, 3_000])),
+        ]
+        .concat();
+
+        let trades = UniswapV3RouterV2Decoder
+            .decode(&call([0_u8; 20], &exact_input))
+            .unwrap();
+        assert!(matches!(
+            &trades.steps[0],
+            TradeStep::Trade { info, exact_in: true, .. } if info.path == vec![
+                (Token::ARB, Token::WETH, Fee::from_pips(3_000)),
+                (Token::WETH, Token::USDC, Fee::from_pips(500)),
+            ]
+        ));
+        // applied from the last swap, (token out, token in)
+        let trades = UniswapV3RouterV2Decoder
+            .decode(&call([0_u8; 20], &exact_output))
+            .unwrap();
+        assert!(matches!(
+            &trades.steps[0],
+            TradeStep::Trade { info, exact_in: false, .. } if info.amount == 1_000_000_u64.into() && info.path == vec![
+                (Token::USDC, Token::WETH, Fee::from_pips(500)),
+                (Token::WETH, Token::ARB, Fee::from_pips(3_000)),
+            ]
+        ));
+        // a partial pool is malformed
+        let truncated = Path::encode(&tokens, &[3_000, 500]);
+        assert!(v3_path_trade_info(
+            &truncated[..truncated.len() - 1],
+            PathDirection::Forward,
+            U256::one(),
+            ExchangeId::Uniswap
+        )
+        .path
+        .is_empty());
+        assert_survives_mutations(&UniswapV3RouterV2Decoder, &exact_output);
+    }
+
+    #[test]
+    fn route_processor_decoder() {
+        // processRoute 100 USDC, USDC -> WETH (uniV3) then WETH -> USDC (uniV2)
+        let input = hex!("2646478b000000000000000000000000ff970a61a04b1ca14834a43f5de4533ebddb5cc80000000000000000000000000000000000000000000000000000000005f5e10000000000000000000000000082af49447d8a07e3bd95bd0d56f35241523fbab10000000000000000000000000000000000000000000000000000000000000000000000000000000000000000e754841b77c874135caca3386676e886459c2d6100000000000000000000000000000000000000000000000000000000000000c0000000000000000000000000000000000000000000000000000000000000008102ff970a61a04b1ca14834a43f5de4533ebddb5cc801ffff01c31e54c7a869b9fcbecc14363cf510d1c41fa44301e754841b77c874135caca3386676e886459c2d610482af49447d8a07e3bd95bd0d56f35241523fbab100e754841b77c874135caca3386676e886459c2d6100c31e54c7a869b9fcbecc14363cf510d1c41fa44300000000000000000000000000000000000000000000000000000000000000");
+        let trades = decoder(RouterId::SushiRouteProcessor3)
+            .decode(&call([0_u8; 20], &input))
+            .unwrap();
+        assert!(!trades.skip);
+        assert_eq!(trades.steps.len(), 4);
+        assert_eq!(
+            trades.steps[0],
+            TradeStep::Credit {
+                slot: 0,
+                amount: 100_000_000
+            }
+        );
+        assert!(matches!(
+            trades.steps[1],
+            TradeStep::Trade {
+                take: Some(Take { slot: 0, .. }),
+                credit: Some(_),
+                required: true,
+                ..
+            }
+        ));
+        // the second command swaps the pool's balance
+        assert!(matches!(trades.steps[2], TradeStep::Move { .. }));
+        assert!(matches!(
+            trades.steps[3],
+            TradeStep::Trade {
+                take: Some(Take {
+                    numerator: 65_535,
+                    denominator: 65_535,
+                    ..
+                }),
+                required: true,
+                ..
+            }
+        ));
+
+        // RouteProcessor4 encodes the uniV2 fee
+        let trades = decoder(RouterId::SushiRouteProcessor4)
+            .decode(&call([0_u8; 20], &input))
+            .unwrap();
+        assert!(trades.skip);
+        // not a router
+        assert_eq!(
+            decode_call(&call(Address::zero().0, &input)),
+            DecodedTrades::default()
+        );
+        assert_survives_mutations(decoder(RouterId::SushiRouteProcessor3), &input);
+    }
+
+    #[test]
+    fn paraswap_multi_swap_routes() {
+        // USDC -> WETH, 60% uniswap v3 (0.05%), 40% sushi
+        let buf = hex!("0000000000000000000000000000000000000000000000000000000000000020000000000000000000000000ff970a61a04b1ca14834a43f5de4533ebddb5cc80000000000000000000000000000000000000000000000000000000005f5e10000000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000001000000000000000000000000369a2fdb910d432f0a07381a5e3d27572c8767130000000000000000000000000000000000000000000000000000000000000160000000000000000000000000369a2fdb910d432f0a07381a5e3d27572c87671300000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000600000000000000000000000000000000000000000000000000000000006553f10000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000001000000000000000000000000000000000000000000000000000000000000002000000000000000000000000082af49447d8a07e3bd95bd0d56f35241523fbab10000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000006000000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000020000000000000000000000000369a2fdb910d432f0a07381a5e3d27572c8767130000000000000000000000000000000000000000000000000000000000002710000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000800000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000001c00000000000000000000000000000000000000000000000000000000000000003000000000000000000000000e592427a0aece92de3edee1f18e0157c05861564000000000000000000000000000000000000000000000000000000000000177000000000000000000000000000000000000000000000000000000000000000a0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000c000000000000000000000000000000000000000000000000000000000000000200000000000000000000000000000000000000000000000000000000000000040000000000000000000000000000000000000000000000000000000006553f100000000000000000000000000000000000000000000000000000000000000002bff970a61a04b1ca14834a43f5de4533ebddb5cc80001f482af49447d8a07e3bd95bd0d56f35241523fbab10000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000003000000000000000000000000905dfcd5649217c42684f23958568e533c711aa30000000000000000000000000000000000000000000000000000000000000fa000000000000000000000000000000000000000000000000000000000000000a0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000a0000000000000000000000000000000000000000000000000000000000000002000000000000000000000000082af49447d8a07e3bd95bd0d56f35241523fbab100000000000000000000000000000000000000000000000000000000000000400000000000000000000000000000000000000000000000000000000000000001000000000000000000000001905dfcd5649217c42684f23958568e533c711aa30000000000000000000000000000000000000000000000000000000000000000");
+        let Tuple(swap) = Tuple::<paraswap::SellData>::decode(&buf).unwrap();
+        assert_eq!(
+            swap.from_token.0,
+            &hex!("ff970a61a04b1ca14834a43f5de4533ebddb5cc8")
+        );
+        assert_eq!(swap.from_amount, 100_000_000_u64.into());
+
+        let path = &swap.path.0[0];
+        assert_eq!(path.to.0, &hex!("82af49447d8a07e3bd95bd0d56f35241523fbab1"));
+        let adapter = &path.adapters.0[0];
+        assert_eq!(adapter.percent, 10_000);
+        let routes = adapter.route.0.as_slice();
+        assert_eq!(
+            paraswap::share(
+                paraswap::share(swap.from_amount.as_u128(), adapter.percent),
+                routes[0].percent
+            ),
+            60_000_000
+        );
+
+        let Tuple(v3) =
+            Tuple::<paraswap::UniswapV3Data>::decode(routes[0].payload.as_ref()).unwrap();
+        assert_eq!(
+            v3.path.as_ref(),
+            hex!("ff970a61a04b1ca14834a43f5de4533ebddb5cc80001f482af49447d8a07e3bd95bd0d56f35241523fbab1")
+        );
+
+        let Tuple(v2) =
+            Tuple::<paraswap::UniswapV2Data>::decode(routes[1].payload.as_ref()).unwrap();
+        let pools: Vec<[u8; 20]> = v2
+            .pools
+            .iter()
+            .map(|pool| pool.0[12..32].try_into().unwrap())
+            .collect();
+        assert_eq!(
+            pools_to_hops(&pools, swap.from_token.0, path.to.0),
+            Some(vec![(
+                hex!("905dfcd5649217c42684f23958568e533c711aa3"),
+                false
+            )])
+        );
+        // not a path to USDC
+        assert_eq!(
+            pools_to_hops(&pools, swap.from_token.0, swap.from_token.0),
+            None
+        );
+    }
+
+    #[test]
+    fn decode_universal_router_v2_swap() {
+        let buf = hex!("00000000000000000000000000000000000000000000000000000000000000028000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000100000000000000000000000000000000000000000000000000000000000000a00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000200000000000000000000000082af49447d8a07e3bd95bd0d56f35241523fbab1000000000000000000000000ff970a61a04b1ca14834a43f5de4533ebddb5cc8");
+        let swap = UniswapV2UniversalRouterSwapExactIn::decode(&buf).unwrap();
+        assert_eq!(swap.amount_in, UNIVERSAL_ROUTER_CONTRACT_BALANCE);
+        assert_eq!(contract_balance_or(swap.amount_in, 0), Some(Take::all(0)));
+        assert_eq!(contract_balance_or(1_u64.into(), 0), None);
+        assert_eq!(
+            swap.path[0].0,
+            &hex!("82af49447d8a07e3bd95bd0d56f35241523fbab1")
+        );
+        assert_eq!(
+            swap.path[1].0,
+            &hex!("ff970a61a04b1ca14834a43f5de4533ebddb5cc8")
+        );
+    }
+
+    #[test]
+    fn decode_universal_router_v4_swap() {
+        // exact in single WETH/USDC, exact in ETH -> WETH -> USDC, settle all
+        let buf = hex!("00000000000000000000000000000000000000000000000000000000000000400000000000000000000000000000000000000000000000000000000000000080000000000000000000000000000000000000000000000000000000000000000306070c00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000003000000000000000000000000000000000000000000000000000000000000006000000000000000000000000000000000000000000000000000000000000001e000000000000000000000000000000000000000000000000000000000000004800000000000000000000000000000000000000000000000000000000000000160000000000000000000000000000000000000000000000000000000000000002000000000000000000000000082af49447d8a07e3bd95bd0d56f35241523fbab1000000000000000000000000ff970a61a04b1ca14834a43f5de4533ebddb5cc800000000000000000000000000000000000000000000000000000000000001f4000000000000000000000000000000000000000000000000000000000000000a000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000de0b6b3a764000000000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000120000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000002800000000000000000000000000000000000000000000000000000000000000020000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000800000000000000000000000000000000000000000000000000de0b6b3a7640000000000000000000000000000000000000000000000000000000000000000000100000000000000000000000000000000000000000000000000000000000000020000000000000000000000000000000000000000000000000000000000000040000000000000000000000000000000000000000000000000000000000000010000000000000000000000000082af49447d8a07e3bd95bd0d56f35241523fbab100000000000000000000000000000000000000000000000000000000000001f4000000000000000000000000000000000000000000000000000000000000000a000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000a00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000ff970a61a04b1ca14834a43f5de4533ebddb5cc800000000000000000000000000000000000000000000000000000000000000640000000000000000000000000000000000000000000000000000000000000001000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000a00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000004000000000000000000000000082af49447d8a07e3bd95bd0d56f35241523fbab10000000000000000000000000000000000000000000000000de0b6b3a7640000");
+        let swap = UniswapV4Swap::decode(&buf).unwrap();
+        assert_eq!(
+            swap.actions.as_ref(),
+            &[
+                v4_action::SWAP_EXACT_IN_SINGLE,
+                v4_action::SWAP_EXACT_IN,
+                0x0c
+            ]
+        );
+
+        let Tuple(single) =
+            Tuple::<UniswapV4SwapSingleParams>::decode(swap.params[0].as_ref()).unwrap();
+        assert!(single.zero_for_one);
+        assert_eq!(single.fee, 500);
+        assert_eq!(single.amount, 1_000_000_000_000_000_000);
+        let trade_info = v4_trade_info(
+            &[(single.currency_0.0, single.currency_1.0, single.fee)],
+            single.amount.into(),
+        );
+        assert_eq!(
+            trade_info.path,
+            vec![(Token::WETH, Token::USDC, Fee::from_pips(500))]
+        );
+        assert_eq!(trade_info.exchange_id, ExchangeId::UniswapV4);
+
+        let Tuple(multi) = Tuple::<UniswapV4SwapParams>::decode(swap.params[1].as_ref()).unwrap();
+        assert_eq!(multi.currency.0, &[0_u8; 20]);
+        assert_eq!(multi.path.0.len(), 2);
+        assert_eq!(
+            multi.path.0[1].intermediate_currency.0,
+            &hex!("ff970a61a04b1ca14834a43f5de4533ebddb5cc8")
+        );
+        assert_eq!(multi.path.0[1].fee, 100);
+        // native ETH is not registered
+        let trade_info = v4_trade_info(
+            &[(
+                multi.currency.0,
+                multi.path.0[0].intermediate_currency.0,
+                multi.path.0[0].fee,
+            )],
+            multi.amount.into(),
+        );
+        assert!(trade_info.path.is_empty());
+    }
+
+    #[test]
+    fn balancer_vault_decoder() {
+        use ethers::abi::{encode, Token as ABIToken};
+
+        // WETH/USDC/WBTC weighted pool
+        let pool_id = hex!("64541216bafffeec8ea535bb71fbc927831d0595000100000000000000000002");
+        let unknown_pool_id = [0x11_u8; 32];
+        let funds = ABIToken::Tuple(vec![
+            ABIToken::Address(Address::zero()),
+            ABIToken::Bool(false),
+            ABIToken::Address(Address::zero()),
+            ABIToken::Bool(false),
+        ]);
+        let step = |pool_id: &[u8; 32], asset_in: u64, asset_out: u64, amount: u128| {
+            ABIToken::Tuple(vec![
+                ABIToken::FixedBytes(pool_id.to_vec()),
+                ABIToken::Uint(asset_in.into()),
+                ABIToken::Uint(asset_out.into()),
+                ABIToken::Uint(amount.into()),
+                ABIToken::Bytes(vec![]),
+            ])
+        };
+
+        // swap 1 ETH (native) for USDC
+        let input = [
+            BALANCER_VAULT_SWAP.as_slice(),
+            &encode(&[
+                ABIToken::Tuple(vec![
+                    ABIToken::FixedBytes(pool_id.to_vec()),
+                    ABIToken::Uint(BALANCER_GIVEN_IN.into()),
+                    ABIToken::Address(Address::zero()),
+                    ABIToken::Address(Token::USDC.address()),
+                    ABIToken::Uint(10_u128.pow(18).into()),
+                    ABIToken::Bytes(vec![]),
+                ]),
+                funds.clone(),
+                ABIToken::Uint(0.into()),
+                ABIToken::Uint(U256::MAX),
+            ]),
+        ]
+        .concat();
+        let trades = BalancerVaultDecoder
+            .decode(&call([0_u8; 20], &input))
+            .unwrap();
+        assert_eq!(trades.steps.len(), 1);
+        assert!(matches!(
+            &trades.steps[0],
+            TradeStep::Trade { info, exact_in: true, take: None, .. }
+                if info.path == vec![(Token::WETH, Token::USDC, Fee::ZERO)]
+                    && info.exchange_id == ExchangeId::Balancer
+        ));
+        assert_survives_mutations(&BalancerVaultDecoder, &input);
+
+        // batch swap WETH -> USDC -> WETH, the second step chains the first's output on an unknown pool
+        let assets = ABIToken::Array(vec![
+            ABIToken::Address(Token::WETH.address()),
+            ABIToken::Address(Token::USDC.address()),
+        ]);
+        let batch_swap = |kind: u32, steps: Vec<ABIToken>| {
+            [
+                BALANCER_VAULT_BATCH_SWAP.as_slice(),
+                &encode(&[
+                    ABIToken::Uint(kind.into()),
+                    ABIToken::Array(steps),
+                    assets.clone(),
+                    funds.clone(),
+                    ABIToken::Array(vec![]),
+                    ABIToken::Uint(U256::MAX),
+                ]),
+            ]
+            .concat()
+        };
+        let input = batch_swap(
+            BALANCER_GIVEN_IN,
+            vec![
+                step(&pool_id, 0, 1, 10_u128.pow(18)),
+                step(&unknown_pool_id, 1, 0, 0),
+            ],
+        );
+        let trades = BalancerVaultDecoder
+            .decode(&call([0_u8; 20], &input))
+            .unwrap();
+        assert!(!trades.skip);
+        assert_eq!(trades.slots, 2);
+        assert!(matches!(
+            &trades.steps[0],
+            TradeStep::Trade { info, take: None, credit: Some(0), required: true, .. }
+                if info.path == vec![(Token::WETH, Token::USDC, Fee::ZERO)]
+        ));
+        assert!(matches!(
+            &trades.steps[1],
+            TradeStep::Trade { info, take: Some(take), credit: Some(1), .. }
+                if *take == Take::all(0) && info.path.is_empty() && info.unknown.len() == 1
+        ));
+        assert_survives_mutations(&BalancerVaultDecoder, &input);
+
+        // chained exact output on a synced pool can't be simulated
+        let input = batch_swap(
+            1,
+            vec![
+                step(&unknown_pool_id, 1, 0, 10_u128.pow(18)),
+                step(&pool_id, 0, 1, 0),
+            ],
+        );
+        let trades = BalancerVaultDecoder
+            .decode(&call([0_u8; 20], &input))
+            .unwrap();
+        assert!(trades.skip);
+
+        // asset index out of range
+        let input = batch_swap(BALANCER_GIVEN_IN, vec![step(&pool_id, 0, 2, 1)]);
+        assert_eq!(
+            BalancerVaultDecoder
+                .decode(&call([0_u8; 20], &input))
+                .unwrap_err(),
+            DecodeError::Malformed
+        );
+    }
+
+    #[test]
+    fn curve_pool_decoder() {
+        use ethers::abi::{encode, Token as ABIToken};
+
+        assert!(DECODERS.contains_key(&CURVE_2POOL));
+        let exchange = |selector: [u8; 4], i: u64, j: u64, dx: u128| {
+            let mut params = vec![
+                ABIToken::Int(i.into()),
+                ABIToken::Int(j.into()),
+                ABIToken::Uint(dx.into()),
+                ABIToken::Uint(0.into()),
+            ];
+            if selector == CURVE_EXCHANGE_RECEIVER {
+                params.push(ABIToken::Address(Address::zero()));
+            }
+            [selector.as_slice(), &encode(&params)].concat()
+        };
+
+        // 2pool coins are (USDC, USDT)
+        let input = exchange(CURVE_EXCHANGE, 0, 1, 1_000_000000);
+        let trades = CurvePoolDecoder.decode(&call(CURVE_2POOL, &input)).unwrap();
+        assert_eq!(trades.steps.len(), 1);
+        assert!(matches!(
+            &trades.steps[0],
+            TradeStep::Trade { info, exact_in: true, take: None, .. }
+                if info.path == vec![(Token::USDC, Token::USDT, Fee::ZERO)]
+                    && info.exchange_id == ExchangeId::Curve
+                    && info.amount == U256::from(1_000_000000_u128)
+        ));
+        assert_survives_mutations(&CurvePoolDecoder, &input);
+
+        let input = exchange(CURVE_EXCHANGE_RECEIVER, 1, 0, 5_000000);
+        let trades = CurvePoolDecoder.decode(&call(CURVE_2POOL, &input)).unwrap();
+        assert!(matches!(
+            &trades.steps[0],
+            TradeStep::Trade { info, .. } if info.path == vec![(Token::USDT, Token::USDC, Fee::ZERO)]
+        ));
+
+        // coin index out of range
+        let input = exchange(CURVE_EXCHANGE, 0, 2, 1);
+        assert_eq!(
+            CurvePoolDecoder
+                .decode(&call(CURVE_2POOL, &input))
+                .unwrap_err(),
+            DecodeError::Malformed
+        );
+        // not a configured pool
+        let input = exchange(CURVE_EXCHANGE, 0, 1, 1);
+        assert_eq!(
+            CurvePoolDecoder
+                .decode(&call([0_u8; 20], &input))
+                .unwrap_err(),
+            DecodeError::Malformed
+        );
+    }
+
+    #[test]
+    fn kyber_swap_decoder() {
+        use ethers::abi::{encode, Token as ABIToken};
+
+        // uniswap v3 WETH/USDC 0.05%
+        const POOL: [u8; 20] = hex!("c31e54c7a869b9fcbecc14363cf510d1c41fa443");
+        let desc = |src: [u8; 20], dst: [u8; 20], amount: u128| {
+            ABIToken::Tuple(vec![
+                ABIToken::Address(src.into()),
+                ABIToken::Address(dst.into()),
+                ABIToken::Array(vec![]),
+                ABIToken::Array(vec![]),
+                ABIToken::Array(vec![]),
+                ABIToken::Array(vec![]),
+                ABIToken::Address(Address::zero()),
+                ABIToken::Uint(amount.into()),
+                ABIToken::Uint(0.into()),
+                ABIToken::Uint(0.into()),
+                ABIToken::Bytes(vec![]),
+            ])
+        };
+        // the executor calldata is opaque, pools are recovered from it
+        let executor_data = [hex!("deadbeef").as_slice(), &POOL, &[0_u8; 12]].concat();
+
+        // native ETH -> USDC via a monitored pool
+        let input = [
+            KYBER_SWAP_SIMPLE_MODE.as_slice(),
+            &encode(&[
+                ABIToken::Address(Address::zero()),
+                desc(NATIVE_TOKEN, Token::USDC.address().0, 10_u128.pow(18)),
+                ABIToken::Bytes(executor_data.clone()),
+                ABIToken::Bytes(vec![]),
+            ]),
+        ]
+        .concat();
+        let trades = KyberSwapDecoder.decode(&call([0_u8; 20], &input)).unwrap();
+        assert!(!trades.skip);
+        assert_eq!(trades.steps.len(), 2);
+        assert_eq!(
+            trades.steps[0],
+            TradeStep::Credit {
+                slot: 0,
+                amount: 10_u128.pow(18)
+            }
+        );
+        assert!(matches!(
+            &trades.steps[1],
+            TradeStep::Trade { info, take: Some(take), .. }
+                if info.path == vec![(Token::WETH, Token::USDC, Fee::from_pips(500))] && *take == Take::all(0)
+        ));
+        assert_survives_mutations(&KyberSwapDecoder, &input);
+
+        // USDC -> ARB, the pool doesn't form a path
+        let input = [
+            KYBER_SWAP.as_slice(),
+            &encode(&[ABIToken::Tuple(vec![
+                ABIToken::Address(Address::zero()),
+                ABIToken::Address(Address::zero()),
+                ABIToken::Bytes(executor_data),
+                desc(
+                    Token::USDC.address().0,
+                    Token::ARB.address().0,
+                    1_000_000000,
+                ),
+                ABIToken::Bytes(vec![]),
+            ])]),
+        ]
+        .concat();
+        let trades = KyberSwapDecoder.decode(&call([0_u8; 20], &input)).unwrap();
+        assert!(trades.skip);
+        assert!(trades.steps.is_empty());
+        assert_survives_mutations(&KyberSwapDecoder, &input);
+    }
+
+    #[test]
+    fn odos_v2_decoder() {
+        use ethers::abi::{encode, Token as ABIToken};
+
+        // uniswap v3 WETH/USDC 0.05%
+        const POOL: [u8; 20] = hex!("c31e54c7a869b9fcbecc14363cf510d1c41fa443");
+        // the executor path is opaque, pools are recovered from it
+        let path = |pools: &[[u8; 20]]| {
+            let mut path = hex!("010203").to_vec();
+            for pool in pools {
+                path.extend_from_slice(pool);
+                path.extend_from_slice(&[0_u8; 12]);
+            }
+            path.resize(path.len() + (32 - path.len() % 32) % 32, 0);
+            path
+        };
+        // swapCompact 100 USDC -> WETH, `token_code` is the packed output token
+        let compact = |token_code: &[u8], path: &[u8]| {
+            [
+                ODOS_V2_SWAP_COMPACT.as_slice(),
+                &hex!("0001"),
+                &Token::USDC.address().0,
+                token_code,
+                &hex!("04 05f5e100"),
+                &hex!("08 0000000000000001"),
+                &hex!("000064"),
+                &hex!("0001"),
+                &[0x11; 20],
+                &hex!("0000 0000"),
+                &hex!("00000000"),
+                &[(path.len() / 32) as u8],
+                path,
+            ]
+            .concat()
+        };
+        let weth = [hex!("0001").as_slice(), &Token::WETH.address().0].concat();
+
+        let input = compact(&weth, &path(&[POOL]));
+        let trades = OdosV2Decoder.decode(&call([0_u8; 20], &input)).unwrap();
+        assert!(!trades.skip);
+        assert_eq!(
+            trades.steps,
+            vec![TradeStep::Trade {
+                info: TradeInfo {
+                    amount: 100_000_000_u128.into(),
+                    path: vec![(Token::USDC, Token::WETH, Fee::from_pips(500))],
+                    exchange_id: ExchangeId::Uniswap,
+                    unknown: vec![],
+                },
+                exact_in: true,
+                take: None,
+                credit: None,
+                required: false,
+            }]
+        );
+        assert_survives_mutations(&OdosV2Decoder, &input);
+
+        // the output token is cached in router storage, the path touches a known pool
+        let input = compact(&hex!("0005"), &path(&[POOL]));
+        let trades = OdosV2Decoder.decode(&call([0_u8; 20], &input)).unwrap();
+        assert!(trades.skip);
+        assert!(trades.steps.is_empty());
+        // split/multi-hop paths are skipped
+        let input = compact(
+            &weth,
+            &path(&[POOL, hex!("e754841b77c874135caca3386676e886459c2d61")]),
+        );
+        assert!(
+            OdosV2Decoder
+                .decode(&call([0_u8; 20], &input))
+                .unwrap()
+                .skip
+        );
+        // unknown pools and tokens
+        let input = compact(&[hex!("0001").as_slice(), &[0x22; 20]].concat(), &path(&[]));
+        assert_eq!(
+            OdosV2Decoder.decode(&call([0_u8; 20], &input)).unwrap(),
+            DecodedTrades::default()
+        );
+        // truncated path
+        let input = compact(&weth, &path(&[POOL]));
+        assert_eq!(
+            OdosV2Decoder
+                .decode(&call([0_u8; 20], &input[..input.len() - 1]))
+                .unwrap_err(),
+            DecodeError::Malformed
+        );
+
+        // swap native ETH -> USDC
+        let input = [
+            ODOS_V2_SWAP.as_slice(),
+            &encode(&[
+                ABIToken::Tuple(vec![
+                    ABIToken::Address(Address::zero()),
+                    ABIToken::Uint(10_u128.pow(18).into()),
+                    ABIToken::Address(Address::zero()),
+                    ABIToken::Address(Token::USDC.address()),
+                    ABIToken::Uint(1.into()),
+                    ABIToken::Uint(1.into()),
+                    ABIToken::Address(Address::zero()),
+                ]),
+                ABIToken::Bytes(path(&[POOL])),
+                ABIToken::Address(Address::repeat_byte(0x11)),
+                ABIToken::Uint(0.into()),
+            ]),
+        ]
+        .concat();
+        let trades = OdosV2Decoder.decode(&call([0_u8; 20], &input)).unwrap();
+        assert!(matches!(
+            &trades.steps[..],
+            [TradeStep::Trade { info, exact_in: true, .. }]
+                if info.path == vec![(Token::WETH, Token::USDC, Fee::from_pips(500))] && info.amount == U256::exp10(18)
+        ));
+        assert_survives_mutations(&OdosV2Decoder, &input);
+    }
+
+    #[test]
+    fn ramses_router_decoder() {
+        use ethers::abi::{encode, Token as ABIToken};
+
+        let route = |from: [u8; 20], to: [u8; 20], stable: bool| {
+            ABIToken::Tuple(vec![
+                ABIToken::Address(from.into()),
+                ABIToken::Address(to.into()),
+                ABIToken::Bool(stable),
+            ])
+        };
+        let input = [
+            SOLIDLY_SWAP_EXACT_TOKENS_FOR_TOKENS.as_slice(),
+            &encode(&[
+                ABIToken::Uint(1_000_000000_u128.into()),
+                ABIToken::Uint(0.into()),
+                ABIToken::Array(vec![
+                    route(Token::USDC.address().0, Token::USDT.address().0, true),
+                    route(Token::USDT.address().0, Token::WETH.address().0, false),
+                ]),
+                ABIToken::Address(Address::zero()),
+                ABIToken::Uint(U256::MAX),
+            ]),
+        ]
+        .concat();
+        let trades = RamsesRouterDecoder
+            .decode(&call([0_u8; 20], &input))
+            .unwrap();
+        assert_eq!(trades.steps.len(), 1);
+        // the bundled config has no ramses pairs, hops are unknown
+        assert!(matches!(
+            &trades.steps[0],
+            TradeStep::Trade { info, exact_in: true, .. }
+                if info.path.is_empty()
+                    && info.unknown.len() == 2
+                    && info.exchange_id == ExchangeId::Ramses
+                    && info.amount == U256::from(1_000_000000_u128)
+        ));
+        assert_survives_mutations(&RamsesRouterDecoder, &input);
+
+        // native ETH in
+        let input = [
+            SOLIDLY_SWAP_EXACT_ETH_FOR_TOKENS.as_slice(),
+            &encode(&[
+                ABIToken::Uint(0.into()),
+                ABIToken::Array(vec![route(
+                    Token::WETH.address().0,
+                    Token::USDC.address().0,
+                    false,
+                )]),
+                ABIToken::Address(Address::zero()),
+                ABIToken::Uint(U256::MAX),
+            ]),
+        ]
+        .concat();
+        let mut tx = call([0_u8; 20], &input);
+        tx.value = U256::exp10(18);
+        let trades = RamsesRouterDecoder.decode(&tx).unwrap();
+        assert!(matches!(
+            &trades.steps[0],
+            TradeStep::Trade { info, .. } if info.amount == U256::exp10(18)
+        ));
+
+        // no routes
+        let input = [
+            SOLIDLY_SWAP_EXACT_ETH_FOR_TOKENS.as_slice(),
+            &encode(&[
+                ABIToken::Uint(0.into()),
+                ABIToken::Array(vec![]),
+                ABIToken::Address(Address::zero()),
+                ABIToken::Uint(U256::MAX),
+            ]),
+        ]
+        .concat();
+        assert_eq!(
+            RamsesRouterDecoder
+                .decode(&call([0_u8; 20], &input))
+                .unwrap_err(),
+            DecodeError::Malformed
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Unhandled selector statistics
+//!
+//! Calls of router methods without a decoder are simulated as no-ops (or skip the batch, per the opaque router policy).
+//! The collector counts them per router and selector over the engine's run, periodically logging the top selectors
+//! with hashes of sample calldata to find the txs again e.g. in a feed capture. Real flow, rather than guesswork, picks
+//! the next decoder to build
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use ethers::{
+    types::{Address, H256},
+    utils::{hex, keccak256},
+};
+use log::info;
+
+use crate::trade_router::ROUTERS;
+
+/// Default number of selectors reported
+pub const DEFAULT_TOP_N: usize = 10;
+/// Default interval between reports
+pub const DEFAULT_REPORT_INTERVAL: Duration = Duration::from_secs(600);
+/// Calldata samples kept per selector
+const SAMPLES: usize = 3;
+
+/// Calls of an unhandled router method
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SelectorCount {
+    pub calls: u64,
+    /// Keccak hashes of the first calls' calldata
+    pub samples: Vec<H256>,
+}
+
+/// Counts calls of unhandled router methods
+#[derive(Debug)]
+pub struct SelectorStats {
+    counts: HashMap<(Address, [u8; 4]), SelectorCount>,
+    /// Number of selectors reported
+    top_n: usize,
+    report_interval: Duration,
+    started: Instant,
+    last_report: Instant,
+}
+
+impl Default for SelectorStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SelectorStats {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            counts: Default::default(),
+            top_n: DEFAULT_TOP_N,
+            report_interval: DEFAULT_REPORT_INTERVAL,
+            started: now,
+            last_report: now,
+        }
+    }
+    /// Report the `n` most called selectors (default: 10)
+    pub fn with_top_n(mut self, n: usize) -> Self {
+        self.top_n = n;
+        self
+    }
+    /// Report every `interval` (default: 10 mins)
+    pub fn with_report_interval(mut self, interval: Duration) -> Self {
+        self.report_interval = interval;
+        self
+    }
+    /// Count a call of the unhandled `selector` of `router`, `input` is the call's calldata
+    pub fn record(&mut self, router: Address, selector: [u8; 4], input: &[u8]) {
+        let count = self.counts.entry((router, selector)).or_default();
+        count.calls += 1;
+        if count.samples.len() < SAMPLES {
+            count.samples.push(H256(keccak256(input)));
+        }
+    }
+    /// The `n` most called (router, selector)s, most calls first
+    pub fn top(&self, n: usize) -> Vec<(Address, [u8; 4], &SelectorCount)> {
+        let mut top: Vec<_> = self
+            .counts
+            .iter()
+            .map(|((router, selector), count)| (*router, *selector, count))
+            .collect();
+        top.sort_unstable_by_key(|(router, selector, count)| {
+            (Reverse(count.calls), *router, *selector)
+        });
+        top.truncate(n);
+        top
+    }
+    /// Log the top selectors if the report interval has passed
+    pub fn report_if_due(&mut self) {
+        if self.last_report.elapsed() >= self.report_interval {
+            self.report();
+        }
+    }
+    /// Log the top selectors
+    pub fn report(&mut self) {
+        self.last_report = Instant::now();
+        if self.counts.is_empty() {
+            return;
+        }
+        info!(
+            "unhandled selectors 📊: {} calls of {} methods in {}m",
+            self.counts.values().map(|count| count.calls).sum::<u64>(),
+            self.counts.len(),
+            self.started.elapsed().as_secs() / 60,
+        );
+        for (rank, (router, selector, count)) in self.top(self.top_n).into_iter().enumerate() {
+            let router = match ROUTERS.get(&router.0) {
+                Some(router_id) => format!("{router_id:?}"),
+                None => format!("{router:?}"),
+            };
+            info!(
+                "#{} {router} 0x{}: {} calls, samples: {:?}",
+                rank + 1,
+                hex::encode(selector),
+                count.calls,
+                count.samples,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_top_selectors() {
+        let router = Address::from_low_u64_be(1);
+        let other = Address::from_low_u64_be(2);
+        let mut stats = SelectorStats::new();
+        for i in 0..5_u8 {
+            stats.record(
+                router,
+                [0xde, 0xad, 0xbe, 0xef],
+                &[0xde, 0xad, 0xbe, 0xef, i],
+            );
+        }
+        stats.record(router, [0xca, 0xfe, 0xba, 0xbe], &[0xca, 0xfe, 0xba, 0xbe]);
+        stats.record(other, [0xde, 0xad, 0xbe, 0xef], &[0xde, 0xad, 0xbe, 0xef]);
+        stats.record(other, [0xde, 0xad, 0xbe, 0xef], &[0xde, 0xad, 0xbe, 0xef]);
+
+        let top = stats.top(2);
+        assert_eq!(top.len(), 2);
+        assert_eq!(
+            (top[0].0, top[0].1, top[0].2.calls),
+            (router, [0xde, 0xad, 0xbe, 0xef], 5)
+        );
+        // samples are capped and hash the calldata
+        assert_eq!(top[0].2.samples.len(), SAMPLES);
+        assert_eq!(
+            top[0].2.samples[0],
+            H256(keccak256([0xde, 0xad, 0xbe, 0xef, 0]))
+        );
+        assert_eq!((top[1].0, top[1].2.calls), (other, 2));
+        assert_eq!(top[1].2.samples.len(), 2);
+        assert_eq!(stats.top(10).len(), 3);
+    }
+}
@@ -0,0 +1,145 @@
+//! Graceful shutdown
+//!
+//! The `Shutdown` coordinator broadcasts a shutdown (e.g. on ctrl-c) to the long-running tasks, each holds a
+//! `ShutdownSignal` until it has drained e.g. in-flight orders resolved. Once the tasks have drained, or a deadline
+//! passes, the registered JSON-RPC clients are shut down after answering their in-flight requests
+use std::time::Duration;
+
+use log::{info, warn};
+use tokio::sync::{broadcast, mpsc};
+
+use fulcrum_ws_cli::FastClient;
+
+/// Coordinates the shutdown of long-running tasks
+pub struct Shutdown {
+    /// Broadcasts the shutdown
+    notify: broadcast::Sender<()>,
+    /// Cloned into each signal, closed once all signals are dropped
+    drained_tx: mpsc::Sender<()>,
+    drained_rx: mpsc::Receiver<()>,
+    /// JSON-RPC clients shut down once the tasks have drained
+    clients: Vec<FastClient>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        let (notify, _) = broadcast::channel(1);
+        let (drained_tx, drained_rx) = mpsc::channel(1);
+        Self {
+            notify,
+            drained_tx,
+            drained_rx,
+            clients: Vec::new(),
+        }
+    }
+}
+
+impl Shutdown {
+    /// A signal for a task which must drain before shutdown completes
+    pub fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal {
+            notify: self.notify.subscribe(),
+            _drained: self.drained_tx.clone(),
+        }
+    }
+    /// Shut down `client` once the tasks have drained e.g. the provider they share
+    pub fn with_client(mut self, client: FastClient) -> Self {
+        self.clients.push(client);
+        self
+    }
+    /// Broadcast the shutdown
+    pub fn trigger(&self) {
+        let _ = self.notify.send(());
+    }
+    /// Broadcast the shutdown when the process receives ctrl-c
+    pub fn trigger_on_ctrl_c(&self) {
+        let notify = self.notify.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                info!("shutting down 👋");
+                let _ = notify.send(());
+            }
+        });
+    }
+    /// Broadcast the shutdown (if not already) and wait up to `deadline` for the tasks to drain, then shut down the
+    /// clients
+    ///
+    /// Returns false if the deadline passed
+    pub async fn complete(self, deadline: Duration) -> bool {
+        let Self {
+            notify,
+            drained_tx,
+            mut drained_rx,
+            clients,
+        } = self;
+        let _ = notify.send(());
+        drop(drained_tx);
+        // closed once every signal is dropped
+        let drained = tokio::time::timeout(deadline, drained_rx.recv())
+            .await
+            .is_ok();
+        if !drained {
+            warn!("shutdown: tasks not drained after {:?}", deadline);
+        }
+        for client in clients {
+            client.shutdown().await;
+        }
+        drained
+    }
+}
+
+/// Notifies a task of the shutdown, the task drains then drops it
+#[derive(Debug)]
+pub struct ShutdownSignal {
+    notify: broadcast::Receiver<()>,
+    /// Held until the task has drained
+    _drained: mpsc::Sender<()>,
+}
+
+impl ShutdownSignal {
+    /// Await the shutdown
+    pub async fn recv(&mut self) {
+        // a dropped coordinator also shuts down
+        let _ = self.notify.recv().await;
+    }
+}
+
+/// Await the shutdown of `signal`, never if there is none
+pub(crate) async fn shutdown_of(signal: &mut Option<ShutdownSignal>) {
+    match signal.as_mut() {
+        Some(signal) => signal.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn shutdown_awaits_drained_tasks() {
+        let shutdown = Shutdown::default();
+        let (drained_tx, mut drained_rx) = mpsc::channel::<()>(3);
+        for _ in 0..3 {
+            let mut signal = shutdown.signal();
+            let drained_tx = drained_tx.clone();
+            tokio::spawn(async move {
+                signal.recv().await;
+                // drain, then drop the signal
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                let _ = drained_tx.send(()).await;
+            });
+        }
+        drop(drained_tx);
+        assert!(shutdown.complete(Duration::from_secs(5)).await);
+        for _ in 0..3 {
+            assert!(drained_rx.recv().await.is_some());
+        }
+
+        // a task which never drains
+        let shutdown = Shutdown::default();
+        let signal = shutdown.signal();
+        assert!(!shutdown.complete(Duration::from_millis(10)).await);
+        drop(signal);
+    }
+}
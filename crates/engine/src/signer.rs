@@ -0,0 +1,235 @@
+//! Order tx signing
+//!
+//! A `LocalWallet` signs on the hot path synchronously, other signers (e.g. hardware, remote, or threshold signers)
+//! are plugged in through any ethers `Signer` with `AsyncSigner` at the cost of an await per order tx
+//!
+//! Build with the `secp256k1` feature for `Secp256k1Signer`, signing with libsecp256k1 (bitcoin core) rather than k256
+use async_trait::async_trait;
+#[cfg(feature = "secp256k1")]
+use ethers::types::U256;
+use ethers::types::{transaction::eip2718::TypedTransaction, Address, Signature};
+#[cfg(feature = "secp256k1")]
+use ethers_signers::to_eip155_v;
+use ethers_signers::{LocalWallet, Signer};
+use log::error;
+
+use crate::order::OrderError;
+
+/// Signs order txs
+#[async_trait]
+pub trait TxSigner: Send + Sync {
+    /// Address of the order account
+    fn address(&self) -> Address;
+    /// Chain Id order txs are signed for
+    fn chain_id(&self) -> u64;
+    /// Sign `tx` without awaiting, `None` if the signer can't e.g. it is remote
+    /// (default: `None`)
+    fn sign_sync(&self, _tx: &TypedTransaction) -> Option<Result<Signature, OrderError>> {
+        None
+    }
+    /// Sign `tx`
+    async fn sign(&self, tx: &TypedTransaction) -> Result<Signature, OrderError>;
+}
+
+#[async_trait]
+impl TxSigner for LocalWallet {
+    fn address(&self) -> Address {
+        Signer::address(self)
+    }
+    fn chain_id(&self) -> u64 {
+        Signer::chain_id(self)
+    }
+    fn sign_sync(&self, tx: &TypedTransaction) -> Option<Result<Signature, OrderError>> {
+        Some(
+            // TODO(optimization):
+            // EC math causing most of slowness need special hardware
+            // some unnecessary copy and mem-move in here
+            self.sign_transaction_sync(tx)
+                .map_err(|_| OrderError::TxSigning),
+        )
+    }
+    async fn sign(&self, tx: &TypedTransaction) -> Result<Signature, OrderError> {
+        self.sign_transaction_sync(tx)
+            .map_err(|_| OrderError::TxSigning)
+    }
+}
+
+/// Signs order txs with any ethers `Signer` e.g. `Ledger`, `AwsSigner`
+pub struct AsyncSigner<S>(pub S);
+
+#[async_trait]
+impl<S> TxSigner for AsyncSigner<S>
+where
+    S: Signer + 'static,
+{
+    fn address(&self) -> Address {
+        self.0.address()
+    }
+    fn chain_id(&self) -> u64 {
+        self.0.chain_id()
+    }
+    async fn sign(&self, tx: &TypedTransaction) -> Result<Signature, OrderError> {
+        self.0.sign_transaction(tx).await.map_err(|err| {
+            error!("tx signing: {:?}", err);
+            OrderError::TxSigning
+        })
+    }
+}
+
+/// Signs order txs with libsecp256k1 using a precomputed signing context
+#[cfg(feature = "secp256k1")]
+pub struct Secp256k1Signer {
+    /// Signing context, its precomputed tables are built once
+    secp: secp256k1::Secp256k1<secp256k1::SignOnly>,
+    key: secp256k1::SecretKey,
+    address: Address,
+    chain_id: u64,
+}
+
+#[cfg(feature = "secp256k1")]
+impl Secp256k1Signer {
+    /// Sign with the key of `wallet`, for its chain
+    pub fn new(wallet: &LocalWallet) -> Self {
+        Self {
+            secp: secp256k1::Secp256k1::signing_only(),
+            key: secp256k1::SecretKey::from_slice(&wallet.signer().to_bytes())
+                .expect("valid secret key"),
+            address: Signer::address(wallet),
+            chain_id: Signer::chain_id(wallet),
+        }
+    }
+    /// Sign `tx` matching `LocalWallet::sign_transaction_sync` i.e. `v` is EIP-155 encoded
+    fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, OrderError> {
+        // the sighash must commit to the same chain Id as `v`
+        let chain_id = tx.chain_id().map(|id| id.as_u64()).unwrap_or(self.chain_id);
+        let sighash = if tx.chain_id().is_some() {
+            tx.sighash()
+        } else {
+            let mut tx = tx.clone();
+            tx.set_chain_id(chain_id);
+            tx.sighash()
+        };
+        let message = secp256k1::Message::from_slice(sighash.as_bytes())
+            .map_err(|_| OrderError::TxSigning)?;
+        let (recovery_id, rs) = self
+            .secp
+            .sign_ecdsa_recoverable(&message, &self.key)
+            .serialize_compact();
+
+        Ok(Signature {
+            r: U256::from_big_endian(&rs[..32]),
+            s: U256::from_big_endian(&rs[32..]),
+            v: to_eip155_v(recovery_id.to_i32() as u8, chain_id),
+        })
+    }
+}
+
+#[cfg(feature = "secp256k1")]
+#[async_trait]
+impl TxSigner for Secp256k1Signer {
+    fn address(&self) -> Address {
+        self.address
+    }
+    fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+    fn sign_sync(&self, tx: &TypedTransaction) -> Option<Result<Signature, OrderError>> {
+        Some(self.sign_transaction(tx))
+    }
+    async fn sign(&self, tx: &TypedTransaction) -> Result<Signature, OrderError> {
+        self.sign_transaction(tx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::types::{Eip1559TransactionRequest, U256};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn async_signer_matches_local() {
+        let wallet = "0000000000000000000000000000000000000000000000000000000000000001"
+            .parse::<LocalWallet>()
+            .unwrap()
+            .with_chain_id(42_161_u64);
+        let tx: TypedTransaction = Eip1559TransactionRequest::new()
+            .chain_id(42_161_u64)
+            .nonce(7)
+            .to(Address::repeat_byte(0x11))
+            .value(U256::from(1_000_u64))
+            .into();
+
+        let signature = TxSigner::sign_sync(&wallet, &tx).unwrap().unwrap();
+        assert_eq!(TxSigner::sign(&wallet, &tx).await, Ok(signature));
+
+        let signer = AsyncSigner(wallet.clone());
+        assert!(signer.sign_sync(&tx).is_none());
+        assert_eq!(signer.sign(&tx).await, Ok(signature));
+        assert_eq!(TxSigner::address(&signer), Signer::address(&wallet));
+        assert_eq!(TxSigner::chain_id(&signer), 42_161);
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn secp256k1_signer_matches_local() {
+        let wallet = "0000000000000000000000000000000000000000000000000000000000000001"
+            .parse::<LocalWallet>()
+            .unwrap()
+            .with_chain_id(42_161_u64);
+        let signer = Secp256k1Signer::new(&wallet);
+        assert_eq!(TxSigner::address(&signer), Signer::address(&wallet));
+
+        // both sign deterministically (RFC6979)
+        for nonce in 0..8_u64 {
+            let tx: TypedTransaction = Eip1559TransactionRequest::new()
+                .chain_id(42_161_u64)
+                .nonce(nonce)
+                .to(Address::repeat_byte(0x11))
+                .value(U256::from(1_000_u64))
+                .into();
+            assert_eq!(signer.sign_sync(&tx), TxSigner::sign_sync(&wallet, &tx));
+        }
+    }
+}
+
+#[cfg(all(feature = "bench", feature = "secp256k1"))]
+mod bench {
+    extern crate test;
+    use ethers::types::{Bytes, Eip1559TransactionRequest};
+    use test::{black_box, Bencher};
+
+    use super::*;
+
+    fn order_tx() -> TypedTransaction {
+        Eip1559TransactionRequest::new()
+            .chain_id(42_161_u64)
+            .nonce(5_u64)
+            .to(Address::repeat_byte(0x11))
+            .gas(1_327_902_u64)
+            .max_fee_per_gas(200_000_000_u64)
+            .data(Bytes::from_static(&[0x5a; 100]))
+            .into()
+    }
+
+    fn wallet() -> LocalWallet {
+        "0000000000000000000000000000000000000000000000000000000000000001"
+            .parse::<LocalWallet>()
+            .unwrap()
+            .with_chain_id(42_161_u64)
+    }
+
+    #[bench]
+    fn sign_k256(b: &mut Bencher) {
+        let wallet = wallet();
+        let tx = order_tx();
+        b.iter(|| black_box(TxSigner::sign_sync(&wallet, &tx)));
+    }
+
+    #[bench]
+    fn sign_secp256k1(b: &mut Bencher) {
+        let signer = Secp256k1Signer::new(&wallet());
+        let tx = order_tx();
+        b.iter(|| black_box(signer.sign_sync(&tx)));
+    }
+}
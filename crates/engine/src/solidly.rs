@@ -0,0 +1,169 @@
+//! Solidly (ve(3,3)) pair price source e.g. Ramses
+//!
+//! Solidly pairs charge a fee on the input token and price with either the constant product or the stable swap (x³y + y³x = k)
+//! invariant. Camelot forked the same pair, the math is shared with `camelot_v2`
+use ethers::{
+    abi::{encode_packed, Token as ABIToken},
+    types::{Address, H256},
+    utils::keccak256,
+};
+use serde::Deserialize;
+
+use crate::{camelot_v2, types::Fee};
+
+/// Mirror pair 'getAmountOut' calculation
+///
+/// - `fee` the fee charged on the input token
+/// - `stable` the (input, output) token decimals of stable pairs, `None` for volatile pairs
+pub fn get_amount_out(
+    fee: Fee,
+    amount_in: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    stable: Option<(u8, u8)>,
+) -> u128 {
+    camelot_v2::get_amount_out(fee, amount_in, reserve_in, reserve_out, stable)
+}
+
+/// Calculate the input amount required to receive `amount_out`
+///
+/// - `fee` the fee charged on the input token
+/// - `stable` the (input, output) token decimals of stable pairs, `None` for volatile pairs
+pub fn get_amount_in(
+    fee: Fee,
+    amount_out: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    stable: Option<(u8, u8)>,
+) -> u128 {
+    camelot_v2::get_amount_in(fee, amount_out, reserve_in, reserve_out, stable)
+}
+
+/// `get_amount_out` with float (speed > precision)
+pub fn get_amount_out_f(
+    fee: Fee,
+    amount_in: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    stable: Option<(u8, u8)>,
+) -> f64 {
+    camelot_v2::get_amount_out_f(fee, amount_in, reserve_in, reserve_out, stable)
+}
+
+/// A Solidly fork's pair factory, pair addresses are derived from it
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub struct SolidlyFactory {
+    pub factory: Address,
+    /// keccak256 of the pair creation code, as `PairFactory.pairCodeHash()`
+    pub init_code_hash: H256,
+}
+
+impl SolidlyFactory {
+    /// Calculate the pair address of `token_a`/`token_b` (any order) and `stable`
+    ///
+    /// Stable and volatile pairs of the same tokens are distinct pools
+    pub fn pair_address(&self, token_a: Address, token_b: Address, stable: bool) -> Address {
+        let (token_0, token_1) = if token_a < token_b {
+            (token_a, token_b)
+        } else {
+            (token_b, token_a)
+        };
+        let salt = keccak256(
+            encode_packed(&[
+                ABIToken::Address(token_0),
+                ABIToken::Address(token_1),
+                ABIToken::Bool(stable),
+            ])
+            .expect("it encodes"),
+        );
+        let encoded = encode_packed(&[
+            ABIToken::Bytes(vec![0xff]),
+            ABIToken::Address(self.factory),
+            ABIToken::FixedBytes(salt.to_vec()),
+            ABIToken::FixedBytes(self.init_code_hash.0.to_vec()),
+        ])
+        .expect("it encodes");
+
+        let address_raw: [u8; 20] = keccak256(encoded)[12..].try_into().expect("32 byte value");
+        address_raw.into()
+    }
+}
+
+/// Solidly pair state as returned by the viewer contract
+#[derive(Debug, PartialEq)]
+pub struct SolidlyReserves {
+    pub reserve_0: u128,
+    pub reserve_1: u128,
+    /// The pair prices with the stable swap invariant
+    pub stable: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{types::Token, uniswap_v2};
+
+    #[test]
+    fn get_amount_out_volatile_and_stable() {
+        // 0.05% fee, 1m USDC / 1m USDT
+        let reserve = 1_000_000 * 10_u128.pow(6);
+        let amount_in = 10_000 * 10_u128.pow(6);
+        let volatile = get_amount_out(Fee::from_units(50), amount_in, reserve, reserve, None);
+        assert_eq!(
+            volatile,
+            uniswap_v2::get_amount_out(Fee::from_units(50), amount_in, reserve, reserve)
+        );
+        // the stable curve is flat near the peg
+        let stable = get_amount_out(
+            Fee::from_units(50),
+            amount_in,
+            reserve,
+            reserve,
+            Some((6, 6)),
+        );
+        assert!(stable > volatile);
+        assert!(stable < amount_in);
+
+        let amount_in_required =
+            get_amount_in(Fee::from_units(50), stable, reserve, reserve, Some((6, 6)));
+        assert!(amount_in_required >= amount_in);
+        assert!(amount_in_required - amount_in < amount_in / 1_000_000);
+    }
+
+    #[test]
+    fn stable_extreme_values_do_not_panic() {
+        let reserve = 1_000_000 * 10_u128.pow(6);
+        let fee = Fee::from_units(50);
+        assert_eq!(
+            get_amount_out(fee, u128::MAX, reserve, reserve, Some((6, 6))),
+            0
+        );
+        assert_eq!(
+            get_amount_in(fee, u128::MAX, reserve, reserve, Some((6, 6))),
+            u128::MAX
+        );
+    }
+
+    #[test]
+    fn pair_address() {
+        let factory = SolidlyFactory {
+            factory: Address::repeat_byte(0x11),
+            init_code_hash: H256::repeat_byte(0x22),
+        };
+        let (usdc, weth) = (Token::USDC.address(), Token::WETH.address());
+        let volatile = factory.pair_address(usdc, weth, false);
+        // token order doesn't matter
+        assert_eq!(volatile, factory.pair_address(weth, usdc, false));
+        assert_ne!(volatile, factory.pair_address(usdc, weth, true));
+        // the salt includes the stable flag, unlike uniswap v2
+        assert_ne!(
+            volatile,
+            uniswap_v2::pair_address_for_tokens(
+                weth,
+                usdc,
+                factory.factory,
+                &factory.init_code_hash.0
+            )
+        );
+    }
+}
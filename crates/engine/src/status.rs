@@ -0,0 +1,566 @@
+//! Status page
+//!
+//! A live summary of the engine folded from its events (see `EventSink::with_status`): feed and price sync state,
+//! recent arbs found and submitted, and the PnL of the trade ledger. Served over HTTP as a page refreshing itself
+//! `GET /`, or as JSON `GET /status`
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write as _,
+    io,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::info;
+use serde::Serialize;
+use tokio::net::TcpListener;
+
+use crate::{
+    events::EngineEvent,
+    fallback::DEFAULT_FEED_LAG,
+    http_server,
+    ledger::{Ledger, Pnl},
+    price_graph::CompositeTrade,
+    types::{Position, Token},
+};
+
+/// Arbs kept for the status page, latest first
+const RECENT_ARBS: usize = 20;
+/// Seconds between refreshes of the status page
+const PAGE_REFRESH: u32 = 2;
+
+/// State of the sequencer feed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedState {
+    /// No feed messages yet
+    #[default]
+    Connecting,
+    /// Feed messages are simulated
+    Streaming,
+    /// No feed messages for a while, prices may be advanced from receipts
+    Lagging,
+}
+
+/// State of the price source
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceState {
+    /// Prices are (re)synced to the feed's block
+    #[default]
+    Syncing,
+    /// Prices are synced with the feed
+    Synced,
+}
+
+/// An arb found by the engine
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RecentArb {
+    /// Unix timestamp (s)
+    pub ts: u64,
+    pub block_number: u64,
+    pub token: Token,
+    pub amount_in: u128,
+    pub amount_out: u128,
+    pub path: String,
+    /// True if the arb was sent for execution
+    pub submitted: bool,
+    #[serde(skip)]
+    trade: CompositeTrade,
+}
+
+/// Cumulative PnL of the trade ledger
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PnlSummary {
+    /// Included order txs
+    pub trades: usize,
+    pub reverted: usize,
+    /// Submitted orders not yet reconciled
+    pub pending: usize,
+    /// Cumulative profit by start token symbol
+    pub profit: BTreeMap<String, u128>,
+    /// Cumulative gas paid (wei)
+    pub gas_cost: u128,
+    /// Simulated fills of dry-run orders
+    pub simulated_fills: usize,
+    /// Cumulative simulated profit by start token symbol
+    pub simulated_profit: BTreeMap<String, u128>,
+}
+
+impl From<&Pnl> for PnlSummary {
+    fn from(pnl: &Pnl) -> Self {
+        Self {
+            trades: pnl.trades.len(),
+            reverted: pnl.reverted(),
+            pending: pnl.pending,
+            profit: pnl.profit.clone(),
+            gas_cost: pnl.gas_cost,
+            simulated_fills: pnl.simulated.fills,
+            simulated_profit: pnl.simulated.profit.clone(),
+        }
+    }
+}
+
+/// The engine's status at a point in time
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StatusSnapshot {
+    /// Time since the engine started (s)
+    pub uptime: u64,
+    pub feed: FeedState,
+    pub prices: PriceState,
+    /// Latest block delivered by the feed
+    pub feed_block: u64,
+    /// Block of the latest prices
+    pub price_block: u64,
+    /// Time since the latest feed message (ms)
+    pub feed_age_ms: Option<u64>,
+    /// Time the latest price resync took (ms), from the feed moving ahead to prices catching up
+    pub sync_latency_ms: Option<u64>,
+    /// Feed batches simulated
+    pub batches: u64,
+    pub arbs_found: u64,
+    pub orders_submitted: u64,
+    /// Subsystem restarts after panics
+    pub task_restarts: u64,
+    /// The latest event forcing a price resync e.g. a reorg
+    pub last_resync: Option<String>,
+    /// Recent arbs, latest first
+    pub recent_arbs: Vec<RecentArb>,
+    /// PnL of the trade ledger, if any
+    pub pnl: Option<PnlSummary>,
+}
+
+/// The engine's state, as told by its events
+#[derive(Debug)]
+struct State {
+    started_at: Instant,
+    prices: PriceState,
+    feed_block: u64,
+    price_block: u64,
+    /// Time of the latest feed message
+    feed_at: Option<Instant>,
+    /// True if prices were advanced from receipts since the latest feed message
+    fallback: bool,
+    /// Time prices started resyncing
+    syncing_since: Option<Instant>,
+    sync_latency: Option<Duration>,
+    batches: u64,
+    arbs_found: u64,
+    orders_submitted: u64,
+    task_restarts: u64,
+    last_resync: Option<String>,
+    recent_arbs: VecDeque<RecentArb>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            prices: PriceState::default(),
+            feed_block: 0,
+            price_block: 0,
+            feed_at: None,
+            fallback: false,
+            syncing_since: None,
+            sync_latency: None,
+            batches: 0,
+            arbs_found: 0,
+            orders_submitted: 0,
+            task_restarts: 0,
+            last_resync: None,
+            recent_arbs: VecDeque::with_capacity(RECENT_ARBS),
+        }
+    }
+}
+
+impl State {
+    fn record(&mut self, event: &EngineEvent, now: Instant) {
+        match event {
+            EngineEvent::AwaitingSync {
+                feed_block,
+                price_block,
+            } => {
+                self.feed_message(*feed_block, now);
+                self.price_block = *price_block;
+                self.resync(now);
+            }
+            EngineEvent::Synced { block_number } => {
+                self.feed_message(*block_number, now);
+                self.price_block = *block_number;
+                self.prices = PriceState::Synced;
+                if let Some(since) = self.syncing_since.take() {
+                    self.sync_latency = Some(now.duration_since(since));
+                }
+            }
+            EngineEvent::FeedGap { .. }
+            | EngineEvent::Reorg { .. }
+            | EngineEvent::Resequenced { .. }
+            | EngineEvent::BlockNumberDrift { .. } => {
+                self.last_resync = Some(event.to_string());
+                self.resync(now);
+            }
+            EngineEvent::FeedFallback { block_number, .. } => {
+                self.fallback = true;
+                self.price_block = *block_number;
+            }
+            EngineEvent::TaskRestarted { .. } => self.task_restarts += 1,
+            EngineEvent::BlockEnded { block_number }
+            | EngineEvent::BatchSkipped { block_number, .. } => {
+                self.feed_message(*block_number, now)
+            }
+            EngineEvent::BatchSimulated {
+                block_number,
+                price_block,
+                ..
+            } => {
+                self.feed_message(*block_number, now);
+                self.price_block = *price_block;
+                self.batches += 1;
+            }
+            EngineEvent::ArbFound {
+                block_number,
+                token,
+                amount_in,
+                amount_out,
+                path,
+            } => {
+                self.arbs_found += 1;
+                if self.recent_arbs.len() == RECENT_ARBS {
+                    self.recent_arbs.pop_back();
+                }
+                self.recent_arbs.push_front(RecentArb {
+                    ts: unix_now(),
+                    block_number: *block_number,
+                    token: *token,
+                    amount_in: *amount_in,
+                    amount_out: *amount_out,
+                    path: path.to_string(),
+                    submitted: false,
+                    trade: *path,
+                });
+            }
+            EngineEvent::OrderSubmitted {
+                block_number, path, ..
+            } => {
+                self.orders_submitted += 1;
+                if let Some(arb) = self
+                    .recent_arbs
+                    .iter_mut()
+                    .find(|arb| arb.block_number == *block_number && arb.trade == *path)
+                {
+                    arb.submitted = true;
+                }
+            }
+            EngineEvent::DelayedMessage { .. }
+            | EngineEvent::ArenaNearLimit { .. }
+            | EngineEvent::ArbSearch { .. } => {}
+        }
+    }
+    /// The feed delivered `block_number` at `now`
+    fn feed_message(&mut self, block_number: u64, now: Instant) {
+        self.feed_block = block_number;
+        self.feed_at = Some(now);
+        self.fallback = false;
+    }
+    /// Prices are resynced from `now`
+    fn resync(&mut self, now: Instant) {
+        self.prices = PriceState::Syncing;
+        self.syncing_since.get_or_insert(now);
+    }
+    fn snapshot(&self, now: Instant) -> StatusSnapshot {
+        let feed_age = self.feed_at.map(|feed_at| now.duration_since(feed_at));
+        let feed = match feed_age {
+            None => FeedState::Connecting,
+            Some(age) if self.fallback || age > DEFAULT_FEED_LAG => FeedState::Lagging,
+            Some(_) => FeedState::Streaming,
+        };
+        StatusSnapshot {
+            uptime: now.duration_since(self.started_at).as_secs(),
+            feed,
+            prices: self.prices,
+            feed_block: self.feed_block,
+            price_block: self.price_block,
+            feed_age_ms: feed_age.map(|age| age.as_millis() as u64),
+            sync_latency_ms: self.sync_latency.map(|latency| latency.as_millis() as u64),
+            batches: self.batches,
+            arbs_found: self.arbs_found,
+            orders_submitted: self.orders_submitted,
+            task_restarts: self.task_restarts,
+            last_resync: self.last_resync.clone(),
+            recent_arbs: self.recent_arbs.iter().cloned().collect(),
+            pnl: None,
+        }
+    }
+}
+
+/// The engine's status, updated from its events
+#[derive(Debug, Default)]
+pub struct EngineStatus {
+    state: Mutex<State>,
+    /// Trade ledger to report PnL from
+    ledger: Option<PathBuf>,
+}
+
+impl EngineStatus {
+    /// Report the PnL of the trade ledger at `path` (as written by `run --ledger`)
+    pub fn with_ledger<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.ledger = Some(path.into());
+        self
+    }
+    /// Update the status from `event`
+    pub fn record(&self, event: &EngineEvent) {
+        self.state
+            .lock()
+            .expect("status lock")
+            .record(event, Instant::now());
+    }
+    /// The current status, PnL is read from the ledger
+    pub fn snapshot(&self) -> StatusSnapshot {
+        let mut snapshot = self
+            .state
+            .lock()
+            .expect("status lock")
+            .snapshot(Instant::now());
+        snapshot.pnl = self
+            .ledger
+            .as_ref()
+            .and_then(|path| Ledger::load(path).ok())
+            .map(|records| PnlSummary::from(&Pnl::from_records(&records)));
+        snapshot
+    }
+}
+
+/// The current unix timestamp (s)
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Escape `text` for HTML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Render `snapshot` as a HTML page, refreshing itself
+fn render(snapshot: &StatusSnapshot) -> String {
+    let mut page = format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\"><meta http-equiv=\"refresh\" content=\"{PAGE_REFRESH}\">\
+<title>fulcrum</title><style>body{{font-family:monospace}}td{{padding:0 1em 0 0}}</style></head><body><h3>fulcrum ⚡</h3><table>"
+    );
+    let mut row = |label: &str, value: String| {
+        let _ = write!(page, "<tr><td>{label}</td><td>{}</td></tr>", escape(&value));
+    };
+    row("uptime", format!("{}s", snapshot.uptime));
+    row(
+        "feed",
+        match snapshot.feed_age_ms {
+            Some(age) => format!("{:?} #{} ({age}ms ago)", snapshot.feed, snapshot.feed_block),
+            None => format!("{:?}", snapshot.feed),
+        },
+    );
+    row(
+        "prices",
+        match snapshot.sync_latency_ms {
+            Some(latency) => format!(
+                "{:?} #{} (last sync: {latency}ms)",
+                snapshot.prices, snapshot.price_block
+            ),
+            None => format!("{:?} #{}", snapshot.prices, snapshot.price_block),
+        },
+    );
+    row("batches", snapshot.batches.to_string());
+    row(
+        "arbs",
+        format!(
+            "{} found, {} submitted",
+            snapshot.arbs_found, snapshot.orders_submitted
+        ),
+    );
+    row("task restarts", snapshot.task_restarts.to_string());
+    if let Some(resync) = snapshot.last_resync.as_ref() {
+        row("last resync", resync.clone());
+    }
+    if let Some(pnl) = snapshot.pnl.as_ref() {
+        row(
+            "trades",
+            format!(
+                "{} (reverted: {}, pending: {})",
+                pnl.trades, pnl.reverted, pnl.pending
+            ),
+        );
+        for (token, profit) in pnl.profit.iter() {
+            row(&format!("profit {}", escape(token)), profit.to_string());
+        }
+        row("gas", format!("{}wei", pnl.gas_cost));
+        if pnl.simulated_fills > 0 {
+            row("simulated fills", pnl.simulated_fills.to_string());
+        }
+    }
+    page.push_str("</table><h4>recent arbs</h4><table>");
+    for arb in snapshot.recent_arbs.iter() {
+        let _ = write!(
+            page,
+            "<tr><td>#{}</td><td>{}</td><td>{} -&gt; {}</td><td>{}</td></tr>",
+            arb.block_number,
+            if arb.submitted { "📤" } else { "" },
+            Position::new(arb.amount_in, arb.token),
+            Position::new(arb.amount_out, arb.token),
+            escape(&arb.path),
+        );
+    }
+    page.push_str("</table></body></html>");
+    page
+}
+
+/// Answer the HTTP request line `request` from `status`, returns (status code, content type, body)
+fn respond(status: &EngineStatus, request: &str) -> http_server::Response {
+    let path = request
+        .strip_prefix("GET ")
+        .and_then(|rest| rest.split_whitespace().next());
+    match path {
+        Some("/") => (200, "text/html; charset=utf-8", render(&status.snapshot())),
+        Some("/status") => match serde_json::to_string(&status.snapshot()) {
+            Ok(body) => (200, "application/json", body),
+            Err(_) => (
+                500,
+                "application/json",
+                "{\"error\":\"status encoding\"}".to_string(),
+            ),
+        },
+        _ => (
+            404,
+            "application/json",
+            "{\"error\":\"not found\"}".to_string(),
+        ),
+    }
+}
+
+/// Serve the status page of `status` over HTTP at `addr`, `GET /` (page) and `GET /status` (JSON)
+pub async fn serve(status: Arc<EngineStatus>, addr: SocketAddr) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("status page 📋: http://{addr}/");
+    http_server::serve("status", listener, move |request| respond(&status, request)).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{events::SkipReason, price_graph::Trade, types::Fee};
+
+    use super::*;
+
+    #[test]
+    fn status_from_events() {
+        let t0 = Instant::now();
+        let mut state = State {
+            started_at: t0,
+            ..Default::default()
+        };
+        assert_eq!(state.snapshot(t0).feed, FeedState::Connecting);
+
+        state.record(
+            &EngineEvent::AwaitingSync {
+                feed_block: 91_146_330,
+                price_block: 91_146_328,
+            },
+            t0,
+        );
+        state.record(
+            &EngineEvent::Synced {
+                block_number: 91_146_330,
+            },
+            t0 + Duration::from_millis(350),
+        );
+        let path = CompositeTrade::new(&[
+            Trade::new(Token::WETH.id(), Token::ARB.id(), Fee::from_pips(500), 0),
+            Trade::new(Token::ARB.id(), Token::WETH.id(), Fee::from_pips(3000), 0),
+        ]);
+        for event in [
+            EngineEvent::BatchSimulated {
+                block_number: 91_146_331,
+                price_block: 91_146_330,
+                txs: 3,
+                touched: true,
+                elapsed: Duration::from_micros(40),
+            },
+            EngineEvent::ArbFound {
+                block_number: 91_146_330,
+                token: Token::WETH,
+                amount_in: 1_000,
+                amount_out: 1_010,
+                path,
+            },
+            EngineEvent::OrderSubmitted {
+                block_number: 91_146_330,
+                token: Token::WETH,
+                amount_in: 1_000,
+                amount_out: 1_010,
+                path,
+            },
+            EngineEvent::BatchSkipped {
+                block_number: 91_146_332,
+                reason: SkipReason::PriceImpact,
+            },
+        ] {
+            state.record(&event, t0 + Duration::from_millis(400));
+        }
+
+        let snapshot = state.snapshot(t0 + Duration::from_millis(500));
+        assert_eq!(snapshot.feed, FeedState::Streaming);
+        assert_eq!(snapshot.prices, PriceState::Synced);
+        assert_eq!(snapshot.feed_block, 91_146_332);
+        assert_eq!(snapshot.price_block, 91_146_330);
+        assert_eq!(snapshot.feed_age_ms, Some(100));
+        assert_eq!(snapshot.sync_latency_ms, Some(350));
+        assert_eq!(snapshot.batches, 1);
+        assert_eq!(snapshot.arbs_found, 1);
+        assert_eq!(snapshot.orders_submitted, 1);
+        assert_eq!(snapshot.recent_arbs.len(), 1);
+        assert!(snapshot.recent_arbs[0].submitted);
+
+        // prices advanced from receipts while the feed lags
+        state.record(
+            &EngineEvent::FeedFallback {
+                block_number: 91_146_340,
+                pool_updates: 4,
+            },
+            t0 + Duration::from_secs(3),
+        );
+        state.record(
+            &EngineEvent::Reorg {
+                block_number: 91_146_339,
+                depth: 1,
+                feed_block: 91_146_332,
+            },
+            t0 + Duration::from_secs(3),
+        );
+        let snapshot = state.snapshot(t0 + Duration::from_secs(3));
+        assert_eq!(snapshot.feed, FeedState::Lagging);
+        assert_eq!(snapshot.prices, PriceState::Syncing);
+        assert_eq!(snapshot.price_block, 91_146_340);
+        assert!(snapshot.last_resync.is_some());
+
+        let status = EngineStatus::default();
+        status.record(&EngineEvent::Synced {
+            block_number: 91_146_330,
+        });
+        let (code, _, body) = respond(&status, "GET /status HTTP/1.1");
+        assert_eq!(code, 200);
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["feed"], "streaming");
+        assert_eq!(json["prices"], "synced");
+        assert_eq!(json["price_block"], 91_146_330);
+        assert!(json["pnl"].is_null());
+        let (code, content_type, body) = respond(&status, "GET / HTTP/1.1");
+        assert_eq!(code, 200);
+        assert!(content_type.starts_with("text/html"));
+        assert!(body.contains("#91146330"));
+        assert_eq!(respond(&status, "GET /metrics HTTP/1.1").0, 404);
+    }
+}
@@ -0,0 +1,252 @@
+//! Order tx submission
+//!
+//! Signed order txs are sent to a configurable list of RPC endpoints with a submission strategy,
+//! endpoint latencies are tracked so the fastest are preferred over time
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::future::{join_all, select_ok};
+use log::{debug, error};
+use serde::Deserialize;
+
+use fulcrum_ws_cli::{HttpClient, Response};
+
+/// Official sequencer rpc endpoint
+const ARB_SEQUENCER_HTTPS: &str = "https://arb1-sequencer.arbitrum.io/rpc";
+/// Arbitrum public rpc endpoint
+const ARB_FULL_HTTPS: &str = "https://arb1.arbitrum.io/rpc";
+/// Latency sample recorded for a failed request, demotes unreliable endpoints
+const FAILURE_PENALTY: Duration = Duration::from_secs(1);
+/// Weight of a new latency sample in the moving average (1/2^n)
+const LATENCY_SMOOTHING_SHIFT: u32 = 3;
+
+/// How signed order txs are sent to the submission endpoints
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmitStrategy {
+    /// Send to all endpoints, the first to accept the tx wins
+    #[default]
+    Race,
+    /// Send to all endpoints and await every response, ensuring each endpoint receives the tx
+    Spray,
+    /// Send to the fastest endpoint, falling back to the next fastest on error
+    Fallback,
+}
+
+/// Order tx submission endpoints and strategy
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct SubmissionConfig {
+    /// JSON-RPC endpoints accepting 'eth_sendRawTransaction'
+    pub endpoints: Vec<String>,
+    #[serde(default)]
+    pub strategy: SubmitStrategy,
+}
+
+impl Default for SubmissionConfig {
+    /// Race the official sequencer and public Arbitrum One endpoints
+    fn default() -> Self {
+        Self {
+            endpoints: vec![ARB_SEQUENCER_HTTPS.to_string(), ARB_FULL_HTTPS.to_string()],
+            strategy: SubmitStrategy::Race,
+        }
+    }
+}
+
+/// A submission endpoint and its request stats
+#[derive(Debug)]
+pub struct Endpoint {
+    url: String,
+    /// Moving average request latency (ns), 0 if unmeasured
+    latency_ns: AtomicU64,
+    successes: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Endpoint {
+    fn new(url: String) -> Self {
+        Self {
+            url,
+            latency_ns: AtomicU64::new(0),
+            successes: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+        }
+    }
+    /// The endpoint URL
+    pub fn url(&self) -> &str {
+        self.url.as_str()
+    }
+    /// Moving average request latency, failed requests count as `FAILURE_PENALTY`
+    /// Returns `None` if the endpoint is unmeasured
+    pub fn latency(&self) -> Option<Duration> {
+        match self.latency_ns.load(Ordering::Relaxed) {
+            0 => None,
+            latency_ns => Some(Duration::from_nanos(latency_ns)),
+        }
+    }
+    /// Number of successful requests
+    pub fn successes(&self) -> u64 {
+        self.successes.load(Ordering::Relaxed)
+    }
+    /// Number of failed requests
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+    /// Record a request taking `latency`
+    fn record(&self, latency: Duration, ok: bool) {
+        let sample = if ok {
+            self.successes.fetch_add(1, Ordering::Relaxed);
+            latency
+        } else {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+            latency.max(FAILURE_PENALTY)
+        };
+        let sample = (sample.as_nanos() as u64).max(1);
+        let latency_ns = match self.latency_ns.load(Ordering::Relaxed) {
+            0 => sample,
+            average => {
+                average - (average >> LATENCY_SMOOTHING_SHIFT) + (sample >> LATENCY_SMOOTHING_SHIFT)
+            }
+        };
+        self.latency_ns.store(latency_ns, Ordering::Relaxed);
+    }
+    /// POST `body` to the endpoint, recording the request stats
+    async fn post(&self, client: &HttpClient, body: &str) -> Result<Response, ()> {
+        let t0 = Instant::now();
+        let result = client.post_async(self.url.as_str(), body).await;
+        self.record(Instant::now() - t0, result.is_ok());
+        result.map_err(|err| error!("post {}: {:?}", self.url, err))
+    }
+}
+
+/// Sends requests to the submission endpoints per the configured strategy
+pub struct Submitter {
+    client: HttpClient,
+    strategy: SubmitStrategy,
+    endpoints: Arc<[Endpoint]>,
+}
+
+impl Submitter {
+    /// Create a new submitter for `config` sending with `client`
+    pub fn new(config: SubmissionConfig, client: HttpClient) -> Self {
+        assert!(!config.endpoints.is_empty(), "no submission endpoints");
+        Self {
+            client,
+            strategy: config.strategy,
+            endpoints: config.endpoints.into_iter().map(Endpoint::new).collect(),
+        }
+    }
+    /// The submission endpoints and their request stats
+    pub fn endpoints(&self) -> Arc<[Endpoint]> {
+        Arc::clone(&self.endpoints)
+    }
+    /// Endpoint indices, fastest first
+    /// Unmeasured endpoints are ranked first so they get measured
+    fn by_latency(&self) -> Vec<usize> {
+        let mut ranked: Vec<usize> = (0..self.endpoints.len()).collect();
+        ranked.sort_by_key(|idx| self.endpoints[*idx].latency_ns.load(Ordering::Relaxed));
+        ranked
+    }
+    /// Send `body` to the endpoints per the submission strategy
+    ///
+    /// Returns the first successful response or `None` if every endpoint failed
+    pub async fn send(&self, body: &str) -> Option<Response> {
+        match self.strategy {
+            SubmitStrategy::Race => select_ok(
+                self.endpoints
+                    .iter()
+                    .map(|endpoint| Box::pin(endpoint.post(&self.client, body))),
+            )
+            .await
+            .ok()
+            .map(|(response, _)| response),
+            SubmitStrategy::Spray => join_all(
+                self.endpoints
+                    .iter()
+                    .map(|endpoint| endpoint.post(&self.client, body)),
+            )
+            .await
+            .into_iter()
+            .find_map(Result::ok),
+            SubmitStrategy::Fallback => {
+                for idx in self.by_latency() {
+                    if let Ok(response) = self.endpoints[idx].post(&self.client, body).await {
+                        return Some(response);
+                    }
+                }
+                None
+            }
+        }
+    }
+    /// Keep the endpoint connections warm, measuring their latency
+    pub fn warm(&self) {
+        tokio::spawn({
+            let client = self.client.clone();
+            let endpoints = Arc::clone(&self.endpoints);
+            async move {
+                let t0 = Instant::now();
+                join_all(endpoints.iter().map(|endpoint| {
+                    endpoint.post(&client, r#"{"method":"eth_chainId","params":[]}"#)
+                }))
+                .await;
+                debug!("warm conns 🔥: {:?}", Instant::now() - t0);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{
+        Endpoint, HttpClient, SubmissionConfig, SubmitStrategy, Submitter, FAILURE_PENALTY,
+    };
+
+    #[test]
+    fn endpoint_latency_average() {
+        let endpoint = Endpoint::new("http://localhost".to_string());
+        assert_eq!(endpoint.latency(), None);
+        endpoint.record(Duration::from_millis(80), true);
+        assert_eq!(endpoint.latency(), Some(Duration::from_millis(80)));
+        endpoint.record(Duration::from_millis(160), true);
+        assert_eq!(endpoint.latency(), Some(Duration::from_millis(90)));
+        // failures are penalized
+        endpoint.record(Duration::from_millis(1), false);
+        assert!(endpoint.latency().unwrap() > Duration::from_millis(90) + FAILURE_PENALTY / 10);
+        assert_eq!((endpoint.successes(), endpoint.failures()), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn submit_strategies() {
+        // nothing listens on the discard port
+        let config = |strategy| SubmissionConfig {
+            endpoints: vec![
+                "http://127.0.0.1:9/a".to_string(),
+                "http://127.0.0.1:9/b".to_string(),
+            ],
+            strategy,
+        };
+        for strategy in [
+            SubmitStrategy::Race,
+            SubmitStrategy::Spray,
+            SubmitStrategy::Fallback,
+        ] {
+            let submitter = Submitter::new(config(strategy), HttpClient::new().unwrap());
+            assert!(submitter.send("{}").await.is_none());
+            for endpoint in submitter.endpoints().iter() {
+                assert_eq!(endpoint.failures(), 1);
+            }
+        }
+
+        let submitter =
+            Submitter::new(config(SubmitStrategy::Fallback), HttpClient::new().unwrap());
+        submitter.endpoints()[0].record(Duration::from_millis(20), true);
+        submitter.endpoints()[1].record(Duration::from_millis(10), true);
+        assert_eq!(submitter.by_latency(), [1, 0]);
+    }
+}
@@ -0,0 +1,226 @@
+//! Task supervision
+//!
+//! A panic in a long-running task (e.g. an `expect` on an unexpected response) would end it, leaving the engine
+//! waiting on it forever. Supervised tasks are restarted after a backoff instead, the feed loop resyncs prices.
+//! Restarts are logged, counted in `fulcrum_task_restarts_total`, and emitted as `EngineEvent::TaskRestarted`
+use std::{
+    any::Any,
+    fmt,
+    future::Future,
+    panic::AssertUnwindSafe,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use futures::FutureExt;
+use log::error;
+use serde::Serialize;
+use thingbuf::mpsc::{channel, Receiver, Sender};
+
+#[cfg(feature = "metrics")]
+use crate::metrics;
+
+/// Delay before the first restart, doubled on each restart
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+/// Max. delay before a restart
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A task running this long since its last restart has recovered, the backoff starts over
+const HEALTHY_AFTER: Duration = Duration::from_secs(60);
+/// Max. restarts queued for the engine, later ones are dropped until it catches up
+const RESTART_QUEUE_SIZE: usize = 16;
+
+/// Reports restarts to the engine, if it is running
+static RESTARTS: Mutex<Option<Sender<TaskRestart>>> = Mutex::new(None);
+
+/// A supervised part of the engine
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    /// Feed message decoding and simulation in the engine loop
+    #[default]
+    Feed,
+    /// The price service's sync task
+    PriceSync,
+    /// The order service's loop
+    OrderLoop,
+}
+
+impl Subsystem {
+    /// Metric label of the subsystem
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Feed => "feed",
+            Self::PriceSync => "price_sync",
+            Self::OrderLoop => "order_loop",
+        }
+    }
+}
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// A subsystem panicked and is restarted
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TaskRestart {
+    pub subsystem: Subsystem,
+    /// Restarts of the subsystem so far
+    pub restarts: u32,
+    /// Delay before the restart
+    pub backoff: Duration,
+    /// The panic message
+    pub panic: String,
+}
+
+/// Exponential backoff between restarts, starting over once the task stays up for `HEALTHY_AFTER`
+#[derive(Debug)]
+pub struct Backoff {
+    /// Delay before the next restart
+    next: Duration,
+    /// Time of the latest restart
+    restarted_at: Option<Instant>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            next: INITIAL_BACKOFF,
+            restarted_at: None,
+        }
+    }
+}
+
+impl Backoff {
+    /// Delay before restarting a task which failed at `now`
+    pub fn next(&mut self, now: Instant) -> Duration {
+        if self
+            .restarted_at
+            .is_some_and(|restarted_at| now.duration_since(restarted_at) >= HEALTHY_AFTER)
+        {
+            self.next = INITIAL_BACKOFF;
+        }
+        let delay = self.next;
+        self.next = (delay * 2).min(MAX_BACKOFF);
+        self.restarted_at = Some(now + delay);
+        delay
+    }
+}
+
+/// Restarts of a subsystem
+#[derive(Debug, Default)]
+pub(crate) struct Restarts {
+    subsystem: Subsystem,
+    backoff: Backoff,
+    restarts: u32,
+}
+
+impl Restarts {
+    pub fn new(subsystem: Subsystem) -> Self {
+        Self {
+            subsystem,
+            ..Default::default()
+        }
+    }
+    /// Report the subsystem panicked with `panic`, then await the backoff before it restarts
+    pub async fn restart(&mut self, panic: &(dyn Any + Send)) {
+        self.restarts += 1;
+        let delay = self.backoff.next(Instant::now());
+        report(self.subsystem, self.restarts, delay, panic);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Queue of restarts of supervised tasks, replacing any earlier queue
+pub fn restarts() -> Receiver<TaskRestart> {
+    let (tx, rx) = channel(RESTART_QUEUE_SIZE);
+    *RESTARTS.lock().expect("restarts lock") = Some(tx);
+    rx
+}
+
+/// Report a restart of `subsystem` after it panicked with `panic`
+fn report(subsystem: Subsystem, restarts: u32, backoff: Duration, panic: &(dyn Any + Send)) {
+    let panic = panic_message(panic);
+    error!("{subsystem} panicked: {panic}, restart #{restarts} in {backoff:?}");
+    #[cfg(feature = "metrics")]
+    metrics::TASK_RESTARTS.inc(subsystem.label());
+    if let Some(tx) = RESTARTS.lock().expect("restarts lock").as_ref() {
+        let _ = tx.try_send(TaskRestart {
+            subsystem,
+            restarts,
+            backoff,
+            panic,
+        });
+    }
+}
+
+/// The message of a panic payload e.g. the `expect` message
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown".to_string()
+    }
+}
+
+/// Run the task built by `task` until it returns, restarting it with a backoff whenever it panics
+pub async fn supervise<F, Fut>(subsystem: Subsystem, mut task: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut restarts = Restarts::new(subsystem);
+    while let Err(panic) = AssertUnwindSafe(task()).catch_unwind().await {
+        restarts.restart(panic.as_ref()).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_and_resets() {
+        let mut backoff = Backoff::default();
+        let t0 = Instant::now();
+        assert_eq!(backoff.next(t0), INITIAL_BACKOFF);
+        assert_eq!(backoff.next(t0), INITIAL_BACKOFF * 2);
+        assert_eq!(backoff.next(t0), INITIAL_BACKOFF * 4);
+        for _ in 0..16 {
+            backoff.next(t0);
+        }
+        assert_eq!(backoff.next(t0), MAX_BACKOFF);
+        // up long enough after the latest restart
+        assert_eq!(
+            backoff.next(t0 + MAX_BACKOFF + HEALTHY_AFTER),
+            INITIAL_BACKOFF
+        );
+    }
+
+    #[tokio::test]
+    async fn supervise_restarts_panicked_tasks() {
+        let runs = Arc::new(AtomicU32::new(0));
+        supervise(Subsystem::PriceSync, || {
+            let runs = Arc::clone(&runs);
+            async move {
+                if runs.fetch_add(1, Ordering::Relaxed) < 2 {
+                    panic!("capacity");
+                }
+            }
+        })
+        .await;
+        // panicked twice, then returned
+        assert_eq!(runs.load(Ordering::Relaxed), 3);
+
+        assert_eq!(panic_message(&"pong ok"), "pong ok");
+        assert_eq!(panic_message(&format!("ws error: {}", 1)), "ws error: 1");
+    }
+}
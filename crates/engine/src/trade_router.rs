@@ -1,17 +1,17 @@
 //! Trade routing utilities
 
-use ethabi_static::{AddressZcp, Bytes32, BytesZcp, DecodeStatic};
+use ethabi_static::{AddressZcp, Bytes32, BytesZcp, DecodeStatic, Tuple, Tuples};
 use ethers::types::{Address, U256};
 use hex_literal::hex;
 use once_cell::sync::Lazy;
+use serde::Serialize;
 
 use crate::{
-    constant::arbitrum::{
-        CAMELOT_ROUTER, ODOS_ROUTER, ONE_INCH_ROUTER_V4, ONE_INCH_ROUTER_V5, PARASWAP_AUGUSTUS,
-        SUSHI_ROUTER, UNISWAP_V3_ROUTER_V1, UNISWAP_V3_ROUTER_V2, UNISWAP_V3_UNIVERSAL_ROUTER,
-        ZERO_EX_ROUTER,
-    },
-    types::{ExchangeId, Pair, RouterId, Token},
+    abi_check::abi_layout,
+    config,
+    constant::arbitrum::WETH,
+    solidly::SolidlyFactory,
+    types::{ExchangeId, Fee, OpaqueRouterPolicy, Pair, RouterId, Token},
     util::AddressMap,
 };
 
@@ -26,15 +26,62 @@ pub const UNISWAP_V3_V2_EXACT_OUTPUT: [u8; 4] = hex!("09b81346");
 pub const UNISWAP_V3_V2_EXACT_OUTPUT_SINGLE: [u8; 4] = hex!("5023b4df");
 pub const UNISWAP_V3_MULTI_CALL: [u8; 4] = hex!("ac9650d8");
 pub const UNISWAP_V3_MULTI_CALL_DEADLINE: [u8; 4] = hex!("5ae401dc");
+pub const UNISWAP_V3_V2_WRAP_ETH: [u8; 4] = hex!("1c58db4f");
+pub const UNISWAP_V3_V2_UNWRAP_WETH9: [u8; 4] = hex!("49616997");
+pub const UNISWAP_V3_V2_UNWRAP_WETH9_RECIPIENT: [u8; 4] = hex!("49404b7c");
+/// `SwapRouter02` amount in flag to use its entire balance of a token
+pub const SWAP_ROUTER_02_CONTRACT_BALANCE: U256 = U256([0, 0, 0, 0]);
 
 pub const UNISWAP_UNIVERSAL_ROUTER_EXECUTE_DEADLINE: [u8; 4] = hex!("24856bc3");
 pub const UNISWAP_UNIVERSAL_ROUTER_EXECUTE: [u8; 4] = hex!("3593564c");
 
+/// Universal router commands https://docs.uniswap.org/contracts/universal-router/technical-reference
+pub mod universal_router_command {
+    pub const MASK: u8 = 0x3f;
+    pub const V3_SWAP_EXACT_IN: u8 = 0x00;
+    pub const V3_SWAP_EXACT_OUT: u8 = 0x01;
+    pub const PERMIT2_TRANSFER_FROM: u8 = 0x02;
+    pub const PERMIT2_PERMIT_BATCH: u8 = 0x03;
+    pub const SWEEP: u8 = 0x04;
+    pub const TRANSFER: u8 = 0x05;
+    pub const PAY_PORTION: u8 = 0x06;
+    pub const V2_SWAP_EXACT_IN: u8 = 0x08;
+    pub const V2_SWAP_EXACT_OUT: u8 = 0x09;
+    pub const PERMIT2_PERMIT: u8 = 0x0a;
+    pub const WRAP_ETH: u8 = 0x0b;
+    pub const UNWRAP_WETH: u8 = 0x0c;
+    pub const PERMIT2_TRANSFER_FROM_BATCH: u8 = 0x0d;
+    pub const BALANCE_CHECK_ERC20: u8 = 0x0e;
+    pub const V4_SWAP: u8 = 0x10;
+    pub const EXECUTE_SUB_PLAN: u8 = 0x21;
+}
+
+/// Uniswap V4 router actions https://github.com/Uniswap/v4-periphery/blob/main/src/libraries/Actions.sol
+pub mod v4_action {
+    pub const SWAP_EXACT_IN_SINGLE: u8 = 0x06;
+    pub const SWAP_EXACT_IN: u8 = 0x07;
+    pub const SWAP_EXACT_OUT_SINGLE: u8 = 0x08;
+    pub const SWAP_EXACT_OUT: u8 = 0x09;
+}
+
+/// Universal router amount flag to use its entire balance of a token
+pub const UNIVERSAL_ROUTER_CONTRACT_BALANCE: U256 = U256([0, 0, 0, 1 << 63]);
+/// Universal router v2 amount in flag, the input was already transferred to the pair e.g. wrapped ETH
+pub const UNIVERSAL_ROUTER_ALREADY_PAID: U256 = U256([0, 0, 0, 0]);
+
 pub const ONE_INCH_UNISWAP_V3_SWAP: [u8; 4] = hex!("e449022e");
 pub const ONE_INCH_UNISWAP_V3_SWAP_TWP: [u8; 4] = hex!("e449022e"); // with permit
 /// 1inch V2 swap
 pub const ONE_INCH_UNISWAP_SWAP: [u8; 4] = hex!("12aa3caf");
 
+pub const GMX_SWAP: [u8; 4] = hex!("6023e966");
+pub const GMX_SWAP_TOKENS_TO_ETH: [u8; 4] = hex!("2d4ba6a7");
+pub const GMX_SWAP_ETH_TO_TOKENS: [u8; 4] = hex!("abe68eaa");
+
+pub const PARASWAP_SIMPLE_SWAP: [u8; 4] = hex!("54e3f31b");
+pub const PARASWAP_MULTI_SWAP: [u8; 4] = hex!("a94e78ef");
+pub const PARASWAP_MEGA_SWAP: [u8; 4] = hex!("46c67b6d");
+
 pub const ZERO_EX_TRANSFORM_ERC20: [u8; 4] = hex!("415565b0");
 
 // pub const IT_BUY_1: [u8; 4] = hex!("a6f2ae3a");
@@ -97,6 +144,333 @@ pub struct SwapExactTokensForEthSFOTT<'a> {
     // uint deadline
 }
 
+/// GMX router `swap` and `swapTokensToETH`
+#[derive(Debug, DecodeStatic)]
+pub struct GmxSwap<'a> {
+    pub path: Vec<AddressZcp<'a>>,
+    pub amount_in: U256,
+    // uint256 minOut,
+    // address receiver
+}
+#[derive(Debug, DecodeStatic)]
+pub struct GmxSwapETHToTokens<'a> {
+    pub path: Vec<AddressZcp<'a>>,
+    // uint256 minOut,
+    // address receiver
+}
+
+pub const BALANCER_VAULT_SWAP: [u8; 4] = hex!("52bbbe29");
+pub const BALANCER_VAULT_BATCH_SWAP: [u8; 4] = hex!("945bcec9");
+/// Balancer vault swap `kind`, the given amount is the exact amount in
+pub const BALANCER_GIVEN_IN: u32 = 0;
+/// Balancer vault `swap` `SingleSwap`
+#[derive(Debug, DecodeStatic)]
+pub struct BalancerSingleSwap<'a> {
+    /// pool address in the high 160 bits
+    pub pool_id: Bytes32<'a>,
+    pub kind: u32,
+    pub asset_in: AddressZcp<'a>,
+    pub asset_out: AddressZcp<'a>,
+    /// exact amount in | out
+    pub amount: U256,
+    #[ethabi(skip)]
+    _user_data: U256,
+}
+/// Balancer vault `batchSwap`
+#[derive(Debug, DecodeStatic)]
+pub struct BalancerBatchSwap<'a> {
+    pub kind: u32,
+    pub swaps: Tuples<BalancerBatchSwapStep<'a>>,
+    pub assets: Vec<AddressZcp<'a>>,
+    // FundManagement funds,
+    // int256[] limits,
+    // uint256 deadline
+}
+/// A step of a Balancer vault `batchSwap`
+#[derive(Debug, DecodeStatic)]
+pub struct BalancerBatchSwapStep<'a> {
+    /// pool address in the high 160 bits
+    pub pool_id: Bytes32<'a>,
+    pub asset_in_index: u32,
+    pub asset_out_index: u32,
+    /// exact amount in | out, 0 to chain the previous step's amount out | in
+    pub amount: U256,
+    #[ethabi(skip)]
+    _user_data: U256,
+}
+
+pub const CURVE_EXCHANGE: [u8; 4] = hex!("3df02124");
+pub const CURVE_EXCHANGE_RECEIVER: [u8; 4] = hex!("ddc1f59d");
+/// Curve pool `exchange`
+#[derive(Debug, DecodeStatic)]
+pub struct CurveExchange {
+    /// coin index in
+    pub i: u32,
+    /// coin index out
+    pub j: u32,
+    pub dx: U256,
+    // uint256 min_dy
+    // address receiver (optional)
+}
+
+pub const SOLIDLY_SWAP_EXACT_TOKENS_FOR_TOKENS: [u8; 4] = hex!("f41766d8");
+pub const SOLIDLY_SWAP_EXACT_TOKENS_FOR_TOKENS_SFOTT: [u8; 4] = hex!("6cc1ae13");
+pub const SOLIDLY_SWAP_EXACT_TOKENS_FOR_ETH: [u8; 4] = hex!("18a13086");
+pub const SOLIDLY_SWAP_EXACT_ETH_FOR_TOKENS: [u8; 4] = hex!("67ffb66a");
+/// A hop of a Solidly router swap
+#[derive(Debug, DecodeStatic)]
+pub struct SolidlyRoute<'a> {
+    pub from: AddressZcp<'a>,
+    pub to: AddressZcp<'a>,
+    pub stable: bool,
+}
+/// Solidly router `swapExactTokensForTokens` and `swapExactTokensForETH`
+#[derive(Debug, DecodeStatic)]
+pub struct SolidlySwapExactTokensFor<'a> {
+    pub amount_in: U256,
+    pub amount_out_min: U256,
+    pub routes: Vec<SolidlyRoute<'a>>,
+    // address to,
+    // uint deadline
+}
+/// Solidly router `swapExactETHForTokens`
+#[derive(Debug, DecodeStatic)]
+pub struct SolidlySwapExactETHForTokens<'a> {
+    pub amount_out_min: U256,
+    pub routes: Vec<SolidlyRoute<'a>>,
+    // address to,
+    // uint deadline
+}
+
+pub const KYBER_SWAP: [u8; 4] = hex!("e21fd0e9");
+pub const KYBER_SWAP_GENERIC: [u8; 4] = hex!("59e50fed");
+pub const KYBER_SWAP_SIMPLE_MODE: [u8; 4] = hex!("8af033fb");
+/// https://arbiscan.io/address/0x6131B5fae19EA4f9D964eAc0408E4408b66337b5#code
+/// KyberSwap meta aggregation router `SwapDescriptionV2`
+#[derive(Debug, DecodeStatic)]
+pub struct KyberSwapDescription<'a> {
+    pub src_token: AddressZcp<'a>,
+    pub dst_token: AddressZcp<'a>,
+    #[ethabi(skip)]
+    _src_receivers: U256,
+    #[ethabi(skip)]
+    _src_amounts: U256,
+    #[ethabi(skip)]
+    _fee_receivers: U256,
+    #[ethabi(skip)]
+    _fee_amounts: U256,
+    #[ethabi(skip)]
+    _dst_receiver: U256,
+    pub amount: U256,
+    // uint256 minReturnAmount,
+    // uint256 flags,
+    // bytes permit
+}
+/// KyberSwap `swap` and `swapGeneric` `SwapExecutionParams`
+#[derive(Debug, DecodeStatic)]
+pub struct KyberSwapExecution<'a> {
+    #[ethabi(skip)]
+    _call_target: U256,
+    #[ethabi(skip)]
+    _approve_target: U256,
+    /// Opaque executor calldata
+    pub target_data: BytesZcp<'a>,
+    pub desc: Tuple<KyberSwapDescription<'a>>,
+    // bytes clientData
+}
+/// KyberSwap `swapSimpleMode(caller, desc, executorData, clientData)`
+#[derive(Debug, DecodeStatic)]
+pub struct KyberSwapSimpleMode<'a> {
+    #[ethabi(skip)]
+    _caller: U256,
+    pub desc: Tuple<KyberSwapDescription<'a>>,
+    /// Opaque executor calldata
+    pub executor_data: BytesZcp<'a>,
+    // bytes clientData
+}
+
+pub const SUSHI_ROUTE_PROCESSOR_PROCESS_ROUTE: [u8; 4] = hex!("2646478b");
+pub const SUSHI_ROUTE_PROCESSOR_TRANSFER_VALUE_AND_PROCESS_ROUTE: [u8; 4] = hex!("93b3774c");
+#[derive(Debug, DecodeStatic)]
+pub struct ProcessRoute<'a> {
+    pub token_in: AddressZcp<'a>,
+    pub amount_in: U256,
+    pub token_out: AddressZcp<'a>,
+    #[ethabi(skip)]
+    _amount_out_min: U256,
+    #[ethabi(skip)]
+    _to: U256,
+    pub route: BytesZcp<'a>,
+}
+#[derive(Debug, DecodeStatic)]
+pub struct TransferValueAndProcessRoute<'a> {
+    #[ethabi(skip)]
+    _transfer_value_to: U256,
+    #[ethabi(skip)]
+    _amount_value_transfer: U256,
+    pub token_in: AddressZcp<'a>,
+    pub amount_in: U256,
+    pub token_out: AddressZcp<'a>,
+    #[ethabi(skip)]
+    _amount_out_min: U256,
+    #[ethabi(skip)]
+    _to: U256,
+    pub route: BytesZcp<'a>,
+}
+
+/// Placeholder address of the native token in aggregator routes
+pub const NATIVE_TOKEN: [u8; 20] = hex!("EeeeeEeeeEeEeeEeEeEeeEEEeeeeEeeeeeeeEEeE");
+
+/// Where a RouteProcessor command takes its input amount from
+#[derive(Debug, PartialEq)]
+pub enum RouteSource {
+    /// The `amount_in` of the call (from the sender or msg.value)
+    Sender,
+    /// The router's balance of the token i.e. output of earlier swaps
+    Router,
+    /// Tokens already transferred to the pool by an earlier swap
+    Pool,
+}
+
+/// A pool swap within a RouteProcessor route
+#[derive(Debug, PartialEq)]
+pub enum RoutePool {
+    UniswapV2 {
+        pool: [u8; 20],
+        zero_for_one: bool,
+    },
+    UniswapV3 {
+        pool: [u8; 20],
+        zero_for_one: bool,
+    },
+    /// (un)wrap the native token
+    WrapNative,
+}
+
+/// A RouteProcessor command, the `source` amount of `token` is distributed over `swaps` by share (out of `u16::MAX`)
+#[derive(Debug, PartialEq)]
+pub struct RouteCommand {
+    pub source: RouteSource,
+    pub token: [u8; 20],
+    pub swaps: Vec<(u16, RoutePool)>,
+}
+
+/// Errors decoding a RouteProcessor route
+#[derive(Debug, PartialEq)]
+pub enum RouteError {
+    /// The route ended mid command
+    Truncated,
+    /// The route uses a command we don't simulate e.g. bentobox
+    UnsupportedCommand(u8),
+    /// The route swaps on a pool type we don't simulate e.g. trident
+    UnsupportedPool(u8),
+}
+
+/// Cursor over the packed RouteProcessor route bytes
+struct RouteStream<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> RouteStream<'a> {
+    fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+    fn read(&mut self, len: usize) -> Result<&'a [u8], RouteError> {
+        if self.buf.len() < len {
+            return Err(RouteError::Truncated);
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Ok(head)
+    }
+    fn read_u8(&mut self) -> Result<u8, RouteError> {
+        self.read(1).map(|b| b[0])
+    }
+    fn read_u16(&mut self) -> Result<u16, RouteError> {
+        self.read(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+    fn read_address(&mut self) -> Result<[u8; 20], RouteError> {
+        self.read(20).map(|b| b.try_into().unwrap())
+    }
+}
+
+/// Decode a Sushi RouteProcessor `route`
+///
+/// - `v2_fee` true if uniswap V2 swaps encode the pool fee (RouteProcessor4)
+pub fn decode_route(route: &[u8], v2_fee: bool) -> Result<Vec<RouteCommand>, RouteError> {
+    let mut stream = RouteStream { buf: route };
+    let mut commands = Vec::with_capacity(2);
+    while !stream.is_empty() {
+        let (source, token, swap_count) = match stream.read_u8()? {
+            // processMyERC20
+            1 => (
+                RouteSource::Router,
+                stream.read_address()?,
+                stream.read_u8()?,
+            ),
+            // processUserERC20
+            2 => (
+                RouteSource::Sender,
+                stream.read_address()?,
+                stream.read_u8()?,
+            ),
+            // processNative
+            3 => (RouteSource::Sender, NATIVE_TOKEN, stream.read_u8()?),
+            // processOnePool
+            4 => (RouteSource::Pool, stream.read_address()?, 0),
+            // applyPermit (value, deadline, v, r, s)
+            6 => {
+                stream.read(32 + 32 + 1 + 32 + 32)?;
+                continue;
+            }
+            code => return Err(RouteError::UnsupportedCommand(code)),
+        };
+        let mut swaps = Vec::with_capacity(swap_count.max(1) as usize);
+        if source == RouteSource::Pool {
+            swaps.push((u16::MAX, decode_route_swap(&mut stream, v2_fee)?));
+        } else {
+            for _ in 0..swap_count {
+                let share = stream.read_u16()?;
+                swaps.push((share, decode_route_swap(&mut stream, v2_fee)?));
+            }
+        }
+        commands.push(RouteCommand {
+            source,
+            token,
+            swaps,
+        });
+    }
+
+    Ok(commands)
+}
+
+/// Decode the next pool swap from a RouteProcessor route
+fn decode_route_swap(stream: &mut RouteStream, v2_fee: bool) -> Result<RoutePool, RouteError> {
+    match stream.read_u8()? {
+        0 => {
+            // pool, direction, recipient, (fee)
+            let pool = stream.read_address()?;
+            let zero_for_one = stream.read_u8()? == 1;
+            stream.read(if v2_fee { 20 + 3 } else { 20 })?;
+            Ok(RoutePool::UniswapV2 { pool, zero_for_one })
+        }
+        1 => {
+            // pool, zeroForOne, recipient
+            let pool = stream.read_address()?;
+            let zero_for_one = stream.read_u8()? > 0;
+            stream.read(20)?;
+            Ok(RoutePool::UniswapV3 { pool, zero_for_one })
+        }
+        2 => {
+            // directionAndFake, recipient, (wrap token)
+            let direction = stream.read_u8()?;
+            stream.read(if direction & 1 == 1 { 20 + 20 } else { 20 })?;
+            Ok(RoutePool::WrapNative)
+        }
+        pool_type => Err(RouteError::UnsupportedPool(pool_type)),
+    }
+}
+
 /// https://github.com/odos-xyz/router_v1/blob/581d4400f29aed9538ab94a860afae0c1dbd97c7/OdosRouter.sol#LL22C1-L22C89
 /// @dev Contains all information needed to describe an input token being swapped from
 #[derive(Debug, DecodeStatic)]
@@ -123,26 +497,104 @@ pub struct OdosSwap<'a> {
     pub path: BytesZcp<'a>,
 }
 
+pub const ODOS_V2_SWAP: [u8; 4] = hex!("3b635ce4");
+pub const ODOS_V2_SWAP_COMPACT: [u8; 4] = hex!("83bd37f9");
+/// https://arbiscan.io/address/0xa669e7a0d4b3e4fa48af2de86bd4cd7126be4e13#code
+/// Odos V2 `swap(tokenInfo, pathDefinition, executor, referralCode)`, the static `swapTokenInfo` tuple is encoded inline
 #[derive(Debug, DecodeStatic)]
-pub struct SwapDescription<'a> {
-    pub token_in: AddressZcp<'a>,
-    pub token_out: AddressZcp<'a>,
+pub struct OdosV2Swap<'a> {
+    pub input_token: AddressZcp<'a>,
+    /// Zero uses the router's balance of the input token
+    pub input_amount: U256,
     #[ethabi(skip)]
-    _source_receiver: U256,
+    _input_receiver: U256,
+    pub output_token: AddressZcp<'a>,
     #[ethabi(skip)]
-    _dst_receiver: U256,
-    pub amount: U256,
-    // min_return_amount: U256,
-    // flags: U256
+    _output_quote: U256,
+    #[ethabi(skip)]
+    _output_min: U256,
+    #[ethabi(skip)]
+    _output_receiver: U256,
+    /// Opaque executor path
+    pub path_definition: BytesZcp<'a>,
+    // address executor
+    // uint32 referralCode
+}
+
+/// Odos V2 `swapCompact()` params, packed after the selector
+#[derive(Debug, PartialEq)]
+pub struct OdosCompactSwap<'a> {
+    /// The input token (zero for native ETH), `None` if loaded from the router's cached address list
+    pub input_token: Option<[u8; 20]>,
+    /// The output token (zero for native ETH), `None` if loaded from the router's cached address list
+    pub output_token: Option<[u8; 20]>,
+    /// `None` uses the router's balance of the input token
+    pub input_amount: Option<U256>,
+    /// Opaque executor path
+    pub path_definition: &'a [u8],
+}
+
+/// Decode Odos V2 `swapCompact()` params
+///
+/// see `OdosRouterV2.swapCompact`, addresses are 2 byte codes: `0` the zero address, `1` followed by the address,
+/// or an index into the router's cached address list
+pub fn decode_odos_compact(buf: &[u8]) -> Result<OdosCompactSwap<'_>, RouteError> {
+    fn read_address(stream: &mut RouteStream) -> Result<Option<[u8; 20]>, RouteError> {
+        match stream.read_u16()? {
+            0 => Ok(Some([0_u8; 20])),
+            1 => stream.read_address().map(Some),
+            _cached => Ok(None),
+        }
+    }
+    fn read_amount(stream: &mut RouteStream) -> Result<U256, RouteError> {
+        let len = stream.read_u8()? as usize;
+        if len > 32 {
+            return Err(RouteError::Truncated);
+        }
+        stream.read(len).map(U256::from_big_endian)
+    }
+    let mut stream = RouteStream { buf };
+    let input_token = read_address(&mut stream)?;
+    let output_token = read_address(&mut stream)?;
+    let input_amount = Some(read_amount(&mut stream)?).filter(|amount| !amount.is_zero());
+    // output quote, slippage tolerance
+    read_amount(&mut stream)?;
+    stream.read(3)?;
+    // executor, input receiver, output receiver
+    for _ in 0..3 {
+        read_address(&mut stream)?;
+    }
+    // referral code
+    stream.read(4)?;
+    let path_len = stream.read_u8()? as usize * 32;
+
+    Ok(OdosCompactSwap {
+        input_token,
+        output_token,
+        input_amount,
+        path_definition: stream.read(path_len)?,
+    })
 }
 
 /// https://arbiscan.io/address/0x0A9f824C05A74F577A536A8A0c673183a872Dff4#writeContract
+/// `swap(executor, desc, permit, data)`, the static `SwapDescription` tuple is encoded inline
 #[derive(Debug, DecodeStatic)]
 pub struct OneInchSwap<'a> {
     pub executor: AddressZcp<'a>,
-    pub swap: SwapDescription<'a>,
+    pub token_in: AddressZcp<'a>,
+    pub token_out: AddressZcp<'a>,
+    #[ethabi(skip)]
+    _source_receiver: U256,
+    #[ethabi(skip)]
+    _dst_receiver: U256,
+    pub amount: U256,
     #[ethabi(skip)]
-    pub permit: BytesZcp<'a>,
+    _min_return_amount: U256,
+    #[ethabi(skip)]
+    _flags: U256,
+    #[ethabi(skip)]
+    _permit: U256,
+    /// Opaque executor calldata
     pub data: BytesZcp<'a>,
 }
 
@@ -192,6 +644,7 @@ pub struct UniswapV3ExactOutputSingleParamsV2<'a> {
     pub sqrtPriceLimitX96: U256,
 }
 
+/// `SwapRouter02` `exactOutput` `ExactOutputParams`, a dynamic tuple (decode as `Tuple`), the path is reversed
 #[derive(Debug, Default, DecodeStatic)]
 pub struct UniswapV3ExactOutputParamsV2<'a> {
     pub path: BytesZcp<'a>,
@@ -201,6 +654,7 @@ pub struct UniswapV3ExactOutputParamsV2<'a> {
     pub amount_in_max: U256,
 }
 
+/// `SwapRouter` `exactOutput` `ExactOutputParams`, a dynamic tuple (decode as `Tuple`), the path is reversed
 #[derive(Debug, Default, DecodeStatic)]
 pub struct UniswapV3ExactOutputParamsV1<'a> {
     pub path: BytesZcp<'a>,
@@ -212,6 +666,7 @@ pub struct UniswapV3ExactOutputParamsV1<'a> {
     pub amount_in_max: U256,
 }
 
+/// `SwapRouter02` `exactInput` `ExactInputParams`, a dynamic tuple (decode as `Tuple`)
 #[derive(Debug, Default, DecodeStatic)]
 pub struct UniswapV3ExactInputParamsV2<'a> {
     pub path: BytesZcp<'a>,
@@ -234,6 +689,7 @@ pub struct UniswapV3ExactInputSingleParamsV2<'a> {
     pub sqrtPriceLimitX96: U256,
 }
 
+/// `SwapRouter` `exactInput` `ExactInputParams`, a dynamic tuple (decode as `Tuple`)
 #[derive(Debug, DecodeStatic)]
 pub struct UniswapV3ExactInputParamsV1<'a> {
     pub path: BytesZcp<'a>,
@@ -296,6 +752,111 @@ pub struct UniswapV3UniversalRouterSwapExactOut<'a> {
     pub sender_or_router: bool,
 }
 
+// https://docs.uniswap.org/contracts/universal-router/technical-reference#v2_swap_exact_in
+#[derive(Debug, DecodeStatic)]
+pub struct UniswapV2UniversalRouterSwapExactIn<'a> {
+    #[ethabi(skip)]
+    _recipient: U256,
+    pub amount_in: U256,
+    #[ethabi(skip)]
+    _amount_out_min: U256,
+    pub path: Vec<AddressZcp<'a>>,
+    // bool payerIsUser
+}
+
+#[derive(Debug, DecodeStatic)]
+pub struct UniswapV2UniversalRouterSwapExactOut<'a> {
+    #[ethabi(skip)]
+    _recipient: U256,
+    pub amount_out: U256,
+    #[ethabi(skip)]
+    _amount_in_max: U256,
+    pub path: Vec<AddressZcp<'a>>,
+    // bool payerIsUser
+}
+
+/// Universal router `WRAP_ETH` input
+#[derive(Debug, DecodeStatic)]
+pub struct UniversalRouterWrapEth {
+    #[ethabi(skip)]
+    _recipient: U256,
+    pub amount: U256,
+}
+
+/// Universal router `PERMIT2_TRANSFER_FROM` input
+#[derive(Debug, DecodeStatic)]
+pub struct UniversalRouterPermit2TransferFrom {
+    #[ethabi(skip)]
+    _token: U256,
+    #[ethabi(skip)]
+    _recipient: U256,
+    pub amount: U256,
+}
+
+/// Universal router `PAY_PORTION` input
+#[derive(Debug, DecodeStatic)]
+pub struct UniversalRouterPayPortion {
+    #[ethabi(skip)]
+    _token: U256,
+    #[ethabi(skip)]
+    _recipient: U256,
+    pub bips: U256,
+}
+
+/// `SwapRouter02` `wrapETH`
+#[derive(Debug, DecodeStatic)]
+pub struct UniswapV3WrapEth {
+    pub value: U256,
+}
+
+/// Universal router `V4_SWAP` input
+#[derive(Debug, DecodeStatic)]
+pub struct UniswapV4Swap<'a> {
+    pub actions: BytesZcp<'a>,
+    pub params: Vec<BytesZcp<'a>>,
+}
+
+/// V4 router `ExactInputSingleParams` | `ExactOutputSingleParams`, the static `PoolKey` tuple is encoded inline
+#[derive(Debug, DecodeStatic)]
+pub struct UniswapV4SwapSingleParams<'a> {
+    pub currency_0: AddressZcp<'a>,
+    pub currency_1: AddressZcp<'a>,
+    pub fee: u32,
+    #[ethabi(skip)]
+    _tick_spacing: U256,
+    #[ethabi(skip)]
+    _hooks: U256,
+    pub zero_for_one: bool,
+    /// exact amount in | out
+    pub amount: u128,
+    // uint128 amountOutMinimum | amountInMaximum
+    // bytes hookData
+}
+
+/// V4 router `ExactInputParams` | `ExactOutputParams`
+#[derive(Debug, DecodeStatic)]
+pub struct UniswapV4SwapParams<'a> {
+    /// currency in | out
+    pub currency: AddressZcp<'a>,
+    pub path: Tuples<UniswapV4PathKey<'a>>,
+    /// exact amount in | out
+    pub amount: u128,
+    // uint128 amountOutMinimum | amountInMaximum
+}
+
+/// A hop of a V4 router path
+#[derive(Debug, DecodeStatic)]
+pub struct UniswapV4PathKey<'a> {
+    pub intermediate_currency: AddressZcp<'a>,
+    pub fee: u32,
+    #[ethabi(skip)]
+    _tick_spacing: U256,
+    #[ethabi(skip)]
+    _hooks: U256,
+    #[ethabi(skip)]
+    _hook_data: U256,
+}
+
 #[derive(Debug, DecodeStatic)]
 pub struct UniswapV3MultiCall<'a> {
     pub data: Vec<BytesZcp<'a>>,
@@ -308,111 +869,106 @@ pub struct UniswapV3MultiCallDeadline<'a> {
     pub data: Vec<BytesZcp<'a>>,
 }
 
+abi_layout! {
+    SwapExactTokensForETH<'_>: 3, [2: Array];
+    SwapExactETHForTokens<'_>: 2, [1: Array];
+    SwapExactETHForTokensSFOTT<'_>: 2, [1: Array];
+    SwapExactTokensForEthSFOTT<'_>: 3, [2: Array];
+    GmxSwap<'_>: 2, [0: Array];
+    GmxSwapETHToTokens<'_>: 1, [0: Array];
+    BalancerSingleSwap<'_>: 6, [];
+    BalancerBatchSwap<'_>: 3, [1: Array, 2: Array];
+    CurveExchange: 3, [];
+    SolidlySwapExactTokensFor<'_>: 3, [2: Array];
+    SolidlySwapExactETHForTokens<'_>: 2, [1: Array];
+    KyberSwapExecution<'_>: 4, [2: Bytes, 3: Tuple];
+    KyberSwapSimpleMode<'_>: 3, [1: Tuple, 2: Bytes];
+    ProcessRoute<'_>: 6, [5: Bytes];
+    TransferValueAndProcessRoute<'_>: 8, [7: Bytes];
+    OdosSwap<'_>: 6, [0: Array, 1: Array, 5: Bytes];
+    OdosV2Swap<'_>: 8, [7: Bytes];
+    OneInchSwap<'_>: 10, [9: Bytes];
+    OneInchUniswapV3Swap<'_>: 3, [2: Array];
+    OneInchUniswapV3SwapTWP<'_>: 5, [4: Array];
+    UniswapV3ExactOutputSingleParamsV1<'_>: 8, [];
+    UniswapV3ExactOutputSingleParamsV2<'_>: 7, [];
+    UniswapV3ExactOutputParamsV2<'_>: 4, [0: Bytes];
+    UniswapV3ExactOutputParamsV1<'_>: 5, [0: Bytes];
+    UniswapV3ExactInputParamsV2<'_>: 4, [0: Bytes];
+    UniswapV3ExactInputSingleParamsV2<'_>: 7, [];
+    UniswapV3ExactInputParamsV1<'_>: 5, [0: Bytes];
+    UniswapV3ExactInputSingleParamsV1<'_>: 8, [];
+    UniswapV3UniversalExecuteParams<'_>: 2, [0: Bytes, 1: Array];
+    UniswapV3UniversalRouterSwapExactIn<'_>: 5, [3: Bytes];
+    UniswapV3UniversalRouterSwapExactOut<'_>: 5, [3: Bytes];
+    UniswapV2UniversalRouterSwapExactIn<'_>: 4, [3: Array];
+    UniswapV2UniversalRouterSwapExactOut<'_>: 4, [3: Array];
+    UniversalRouterWrapEth: 2, [];
+    UniversalRouterPermit2TransferFrom: 3, [];
+    UniversalRouterPayPortion: 3, [];
+    UniswapV3WrapEth: 1, [];
+    UniswapV4Swap<'_>: 2, [0: Bytes, 1: Array];
+    UniswapV4SwapSingleParams<'_>: 7, [];
+    UniswapV4SwapParams<'_>: 3, [1: Array];
+    UniswapV3MultiCall<'_>: 1, [0: Array];
+    UniswapV3MultiCallDeadline<'_>: 2, [1: Array];
+}
+
 /// Info extracted from an external trade
 /// we only care about 'sells'
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct TradeInfo {
     pub amount: U256,
-    pub path: Vec<(Token, Token, u32)>,
+    /// (token in, token out, pool fee) per hop
+    pub path: Vec<(Token, Token, Fee)>,
     pub exchange_id: ExchangeId,
+    /// (token in, token out, fee as quoted) per hop through unmonitored tokens
     pub unknown: Vec<(Address, Address, u32)>,
 }
 
 /// Map from contract address to known router Ids
-pub static ROUTERS: Lazy<AddressMap<RouterId>> = Lazy::new(|| {
-    let mut routers = AddressMap::<RouterId>::default();
-    routers.insert(UNISWAP_V3_ROUTER_V1, RouterId::UniswapV3RouterV1);
-    routers.insert(UNISWAP_V3_ROUTER_V2, RouterId::UniswapV3RouterV2);
-    routers.insert(
-        UNISWAP_V3_UNIVERSAL_ROUTER,
-        RouterId::UniswapV3UniversalRouter,
-    );
-    routers.insert(CAMELOT_ROUTER, RouterId::CamelotRouterV2);
-    routers.insert(SUSHI_ROUTER, RouterId::SushiRouterV2);
-    routers.insert(PARASWAP_AUGUSTUS, RouterId::ParaswapAugustus);
-    routers.insert(ONE_INCH_ROUTER_V5, RouterId::OneInch);
-    routers.insert(ONE_INCH_ROUTER_V4, RouterId::OneInch);
-    routers.insert(ZERO_EX_ROUTER, RouterId::ZeroEx);
-    routers.insert(ODOS_ROUTER, RouterId::Odos);
-
-    routers
-});
-
-/// Map from token address to know token Ids
-pub static TOKEN_LOOKUP: Lazy<AddressMap<Token>> = Lazy::new(|| {
-    let mut tokens = AddressMap::<Token>::default();
-    tokens.insert(Token::USDC.address().into(), Token::USDC);
-    tokens.insert(Token::WETH.address().into(), Token::WETH);
-    tokens.insert(Token::USDT.address().into(), Token::USDT);
-    tokens.insert(Token::ARB.address().into(), Token::ARB);
+pub static ROUTERS: Lazy<AddressMap<RouterId>> = Lazy::new(|| config::active().router_lookup());
 
+// Map from pool/pair contract address to its two tokens
+pub static POOL_LOOKUP: Lazy<AddressMap<Pair>> = Lazy::new(|| config::active().pool_lookup());
+
+/// Map from contract address to the handling of its calls, for routers without a calldata decoder
+pub static OPAQUE_ROUTERS: Lazy<AddressMap<OpaqueRouterPolicy>> =
+    Lazy::new(|| config::active().opaque_router_lookup());
+
+// The ramses pair factory, if configured
+pub static RAMSES_FACTORY: Lazy<Option<SolidlyFactory>> = Lazy::new(|| config::active().ramses());
+
+// Map from curve pool address to its coins (coin 0, coin 1)
+pub static CURVE_COINS: Lazy<AddressMap<(Token, Token)>> =
+    Lazy::new(|| config::active().curve_coins());
+
+/// Scan opaque calldata for the addresses of known tokens (the native token as WETH), in order of first appearance
+pub fn scan_known_tokens(data: &[u8]) -> Vec<Token> {
+    let registry = config::active().registry();
+    let mut tokens = Vec::<Token>::new();
+    for window in data.windows(20) {
+        let mut address: [u8; 20] = window.try_into().unwrap();
+        if address == NATIVE_TOKEN {
+            address = WETH;
+        }
+        if let Some(token) = registry.by_address(&address) {
+            if !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+    }
     tokens
-});
+}
 
-// Map from pool/pair contract address to its two tokens
-pub static POOL_LOOKUP: Lazy<AddressMap<Pair>> = Lazy::new(|| {
-    // TODO: get from config 🤦‍♀️
-    let mut pool_lookup = AddressMap::<Pair>::with_capacity(20);
-    pool_lookup.insert(
-        hex!("e754841b77c874135caca3386676e886459c2d61"),
-        Pair::new(Token::WETH, Token::USDC, 100_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("c31e54c7a869b9fcbecc14363cf510d1c41fa443"),
-        Pair::new(Token::WETH, Token::USDC, 500_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("17c14d2c404d167802b16c450d3c99f88f2c4f4d"),
-        Pair::new(Token::WETH, Token::USDC, 3000_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("cda53b1f66614552f834ceef361a8d12a0b8dad8"),
-        Pair::new(Token::ARB, Token::USDC, 500_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("81c48d31365e6b526f6bbadc5c9aafd822134863"),
-        Pair::new(Token::ARB, Token::USDC, 3000_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("89a4026e9ade251c67b7fb38054931a39936d9c5"),
-        Pair::new(Token::WETH, Token::ARB, 100_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("c6f780497a95e246eb9449f5e4770916dcd6396a"),
-        Pair::new(Token::WETH, Token::ARB, 500_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("92c63d0e701caae670c9415d91c474f686298f00"),
-        Pair::new(Token::WETH, Token::ARB, 3000_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("42161084d0672e1d3f26a9b53e653be2084ff19c"),
-        Pair::new(Token::WETH, Token::USDT, 100_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("641c00a822e8b671738d32a431a4fb6074e5c79d"),
-        Pair::new(Token::WETH, Token::USDT, 500_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("c82819f72a9e77e2c0c3a69b3196478f44303cf4"),
-        Pair::new(Token::WETH, Token::USDT, 3000_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("8c9d230d45d6cfee39a6680fb7cb7e8de7ea8e71"),
-        Pair::new(Token::USDT, Token::USDC, 100_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("b791ad21ba45c76629003b4a2f04c0d544406e37"),
-        Pair::new(Token::ARB, Token::USDT, 500_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("97bca422ec0ee4851f2110ea743c1cd0a14835a1"),
-        Pair::new(Token::ARB, Token::USDT, 3000_u16, ExchangeId::Uniswap),
-    );
-    pool_lookup.insert(
-        hex!("80151aae63b24a7e1837fe578fb6be026ae8abba"),
-        Pair::new(Token::ARB, Token::USDT, 10000_u16, ExchangeId::Uniswap),
-    );
-
-    pool_lookup
-});
+/// Scan opaque calldata for the addresses of known pools, in order of first appearance
+pub fn scan_known_pools(data: &[u8]) -> Vec<[u8; 20]> {
+    let mut pools = Vec::<[u8; 20]>::new();
+    for window in data.windows(20) {
+        let address: &[u8; 20] = window.try_into().unwrap();
+        if POOL_LOOKUP.contains_key(address) && !pools.contains(address) {
+            pools.push(*address);
+        }
+    }
+    pools
+}
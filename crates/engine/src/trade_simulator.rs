@@ -1,45 +1,73 @@
 //! Trade simulator
 
-use ethabi_static::{AddressZcp, DecodeStatic, Tuple};
-use ethers::types::U256;
 use fulcrum_sequencer_feed::TransactionInfo;
 use log::{debug, info, warn};
 
+#[cfg(feature = "metrics")]
+use crate::metrics;
 use crate::{
-    constant::arbitrum::{CAMELOT_ROUTER, SUSHI_ROUTER},
     price_graph::Edge,
-    trade_router::*,
-    types::{ExchangeId, RouterId, Token},
-    uniswap_v3::fee_from_path_bytes,
-    zero_ex, PriceGraph,
+    router_decoder::{decode_call, DecodedTrades, TradeStep},
+    selector_stats::SelectorStats,
+    trade_router::TradeInfo,
+    PriceGraph,
 };
 
+/// Default max. price impact of a trade on any edge (bps), beyond it the trade is likely a fat finger or decoding bug
+pub const DEFAULT_MAX_PRICE_IMPACT_BPS: u32 = 2_000;
+
 /// Simulates trades locally against a price graph
 pub struct TradeSimulator<'a> {
     /// The price graph to simulate trades onto
     graph: &'a mut PriceGraph,
     /// True if any essential trades were unable to be simulated
     skip: bool,
+    /// Max. fraction a trade may move an edge's price
+    max_price_impact: f64,
+    /// True if a trade moved an edge's price beyond `max_price_impact`
+    price_impact_exceeded: bool,
+    /// Counts calls of unhandled router methods
+    selector_stats: Option<&'a mut SelectorStats>,
 }
 
 impl<'a> TradeSimulator<'a> {
     pub fn new(graph: &'a mut PriceGraph) -> Self {
-        TradeSimulator { graph, skip: false }
+        TradeSimulator {
+            graph,
+            skip: false,
+            max_price_impact: DEFAULT_MAX_PRICE_IMPACT_BPS as f64 / 10_000_f64,
+            price_impact_exceeded: false,
+            selector_stats: None,
+        }
+    }
+    /// Skip the simulation if a trade moves any edge's price by more than `bps`
+    pub fn with_max_price_impact_bps(mut self, bps: u32) -> Self {
+        self.max_price_impact = bps as f64 / 10_000_f64;
+        self
+    }
+    /// Count calls of unhandled router methods in `stats`
+    pub fn with_selector_stats(mut self, stats: &'a mut SelectorStats) -> Self {
+        self.selector_stats = Some(stats);
+        self
     }
     /// True if any trades were skipped
     /// i.e this round of trading does not have accurate local prices
     pub fn skipped(&self) -> bool {
         self.skip
     }
-    /// Apply the trade if possible
+    /// True if trades were skipped for exceeding the max. price impact
+    pub fn price_impact_exceeded(&self) -> bool {
+        self.price_impact_exceeded
+    }
+    /// Apply the trade if possible, returning the amount out (`D`) or amount in (`!D`) at the end of the path
     /// - `exact_in` true if `trade` is adding exact amount of tokens to the pool
-    fn try_run_trade<const D: bool>(&mut self, trade: &TradeInfo) {
+    fn try_run_trade<const D: bool>(&mut self, trade: &TradeInfo) -> Option<u128> {
         // TODO: could be clever here and simulate only trades that are dependent on prices we care about
         // its not clear how useful this would be, effort required for the dependency graph implementation, or performance gain/loss
         if trade.path.is_empty() {
             // not a trade we're monitoring
             debug!("trade on unknown paths");
-            return;
+            return None;
         }
         // trade had a component we aren't monitoring
         if !trade.unknown.is_empty() {
@@ -47,9 +75,13 @@ impl<'a> TradeSimulator<'a> {
                 // TODO: the 1inch output here is garbage
                 warn!("needed 🏊‍♂️: {:x}/{:x} ({fee})", token_in, token_out);
             }
+            #[cfg(feature = "metrics")]
+            metrics::TRADES_SKIPPED.inc();
             self.skip = true;
-            return;
+            return None;
         }
+        #[cfg(feature = "metrics")]
+        metrics::TRADES_SIMULATED.inc();
 
         // TODO: monomorphic
         if D {
@@ -59,18 +91,16 @@ impl<'a> TradeSimulator<'a> {
                 // if we fail here there is a pool we aren't monitoring explicitly e.g different fee tier or token combination
                 debug!("update edge: {:?}/{:?}/{fee}", token_in, token_out);
                 // all v3 edges are stored with zero for one value
-                let edge_id = Edge::hash(
-                    *token_in as u8,
-                    *token_out as u8,
-                    trade.exchange_id as u8,
-                    (*fee) as u16,
-                );
+                let edge_id =
+                    Edge::hash(token_in.id(), token_out.id(), trade.exchange_id as u8, *fee);
                 // outputs the next amount in for the subsequent trade
                 debug!("selling: {:?}{:?}", amount_in, token_in);
+                let price_before = self.graph.edge_by_id(edge_id).map(Edge::price);
                 if let Ok(amount_out) = self
                     .graph
                     .update_edge_in(*token_in, *token_out, edge_id, amount_in)
                 {
+                    self.check_price_impact(edge_id, price_before);
                     amount_in = amount_out;
                     debug!("received: {:?}{:?}", amount_in, token_out);
                 } else {
@@ -79,9 +109,10 @@ impl<'a> TradeSimulator<'a> {
                         "missing pool: {:?}/{:?}/{fee} {:?}",
                         token_in, token_out, trade.exchange_id
                     );
-                    return;
+                    return None;
                 }
             }
+            Some(amount_in)
         } else {
             // apply the trade
             let mut amount_out = trade.amount.as_u128();
@@ -89,18 +120,16 @@ impl<'a> TradeSimulator<'a> {
                 // if we fail here there is a pool we aren't monitoring explicitly e.g different fee tier or token combination
                 debug!("update edge: {:?}/{:?}/{fee}", token_in, token_out);
                 // all v3 edges are stored with zero for one value
-                let edge_id = Edge::hash(
-                    *token_in as u8,
-                    *token_out as u8,
-                    trade.exchange_id as u8,
-                    (*fee) as u16,
-                );
+                let edge_id =
+                    Edge::hash(token_in.id(), token_out.id(), trade.exchange_id as u8, *fee);
                 // outputs the next amount out for the subsequent trade
                 debug!("requesting: {:?}{:?}", amount_out, token_out);
+                let price_before = self.graph.edge_by_id(edge_id).map(Edge::price);
                 if let Ok(amount_in) = self
                     .graph
                     .update_edge_out(*token_out, *token_in, edge_id, amount_out)
                 {
+                    self.check_price_impact(edge_id, price_before);
                     amount_out = amount_in;
                     debug!("owed: {:?}{:?}", amount_out, token_in);
                 } else {
@@ -109,550 +138,143 @@ impl<'a> TradeSimulator<'a> {
                         "missing pool: {:?}/{:?}/{fee} {:?}",
                         token_in, token_out, trade.exchange_id
                     );
-                    return;
+                    return None;
                 }
             }
+            Some(amount_out)
         }
     }
     /// Extract trade information from raw transactions and apply locally if possible
     ///
     /// Note: there will always be some transactions with trades we cannot simulate e.g. routed through some custom contract
     /// this is a best effort, accuracy for speed tradeoff
-    /// this could be refactored but we are interested in performance (less branching)
     pub fn wrangle_transaction(&mut self, tx: &TransactionInfo) {
-        // need atleast 4 bytes of input to call a contract method
-        if tx.input.len() < 5 {
+        let trades = decode_call(tx);
+        if let (Some(stats), Some(selector)) =
+            (self.selector_stats.as_deref_mut(), trades.unhandled)
+        {
+            stats.record(tx.to, selector, tx.input);
+        }
+        if trades.skip {
+            self.mark_skipped();
             return;
         }
-
-        // TODO: this needs some clean up e.g. visitor pattern
-        if let Some(router_id) = ROUTERS.get(&tx.to.0) {
-            let selector: [u8; 4] = unsafe { tx.input.get_unchecked(0..4) }.try_into().unwrap(); // length asserted prior
-            let buf = &tx.input[4..];
-
-            // we expect inputs to be well-formed, this is brittle but most inputs should be well formed anyway
-            // i.e. we're  willing to tolerate the occasional panic and restart for improved normal case
-            match router_id {
-                RouterId::UniswapV3RouterV1 => {
-                    if selector == UNISWAP_V3_V1_EXACT_INPUT {
-                        debug!("🦄1 exact input");
-                        let swap = UniswapV3ExactInputParamsV1::decode(buf).unwrap();
-                        self.v3_path_to_trade_info::<true>(swap.path.as_ref(), swap.amount_in);
-                    } else if selector == UNISWAP_V3_V1_EXACT_OUTPUT {
-                        debug!("🦄1 exact output");
-                        let swap = UniswapV3ExactOutputParamsV1::decode(buf).unwrap();
-                        self.v3_path_to_trade_info::<false>(swap.path.as_ref(), swap.amount_out);
-                    } else if selector == UNISWAP_V3_V1_EXACT_INPUT_SINGLE {
-                        debug!("🦄1 exact input single");
-                        let UniswapV3ExactInputSingleParamsV1 {
-                            amount_in,
-                            token_in,
-                            token_out,
-                            fee,
-                            ..
-                        } = UniswapV3ExactInputSingleParamsV1::decode(buf).unwrap();
-                        self.try_run_trade::<true>(&exact_single_to_trade_info(
-                            token_in.as_ref(),
-                            token_out.as_ref(),
-                            amount_in,
-                            fee,
-                        ));
-                    } else if selector == UNISWAP_V3_V1_EXACT_OUTPUT_SINGLE {
-                        debug!("🦄1 exact output single");
-                        let UniswapV3ExactOutputSingleParamsV1 {
-                            token_in,
-                            token_out,
-                            amount_out,
-                            fee,
-                            ..
-                        } = UniswapV3ExactOutputSingleParamsV1::decode(buf).unwrap();
-                        self.try_run_trade::<false>(&exact_single_to_trade_info(
-                            token_out.as_ref(),
-                            token_in.as_ref(),
-                            amount_out,
-                            fee,
-                        ));
-                    } else if selector == UNISWAP_V3_MULTI_CALL {
-                        debug!("🦄1 multicall");
-                        let multi_call = UniswapV3MultiCall::decode(buf).unwrap();
-                        for call in multi_call.data.iter() {
-                            self.wrangle_transaction(&TransactionInfo {
-                                to: tx.to,
-                                value: tx.value,
-                                input: call.as_ref(),
-                            });
-                        }
-                    } else if selector == UNISWAP_V3_MULTI_CALL_DEADLINE {
-                        debug!("🦄1 multicall deadline");
-                        let multi_call = UniswapV3MultiCallDeadline::decode(buf)
-                            .map_err(|err| {
-                                warn!("{:02x?}", buf);
-                                err
-                            })
-                            .unwrap();
-                        for call in multi_call.data.iter() {
-                            self.wrangle_transaction(&TransactionInfo {
-                                to: tx.to,
-                                value: tx.value,
-                                input: call.as_ref(),
-                            });
-                        }
-                    } else {
-                        debug!("unhandled 🦄1: {:02x?}", selector);
-                    }
-                }
-                RouterId::UniswapV3RouterV2 => {
-                    if selector == UNISWAP_V3_V2_EXACT_INPUT {
-                        debug!("🦄2 exact input");
-                        let swap = UniswapV3ExactInputParamsV2::decode(buf).unwrap();
-                        self.v3_path_to_trade_info::<true>(swap.path.as_ref(), swap.amount_in);
-                    } else if selector == UNISWAP_V3_V2_EXACT_OUTPUT {
-                        debug!("🦄2 exact output");
-                        let swap = UniswapV3ExactOutputParamsV2::decode(buf).unwrap();
-                        self.v3_path_to_trade_info::<false>(swap.path.as_ref(), swap.amount_out);
-                    } else if selector == UNISWAP_V3_V2_EXACT_INPUT_SINGLE {
-                        debug!("🦄2 exact input single");
-                        let UniswapV3ExactInputSingleParamsV2 {
-                            token_in,
-                            token_out,
-                            amount_in,
-                            fee,
-                            ..
-                        } = UniswapV3ExactInputSingleParamsV2::decode(buf).unwrap();
-                        self.try_run_trade::<true>(&exact_single_to_trade_info(
-                            token_in.as_ref(),
-                            token_out.as_ref(),
-                            amount_in,
-                            fee,
-                        ));
-                    } else if selector == UNISWAP_V3_V2_EXACT_OUTPUT_SINGLE {
-                        debug!("🦄1 exact output single");
-                        let UniswapV3ExactOutputSingleParamsV2 {
-                            token_in,
-                            token_out,
-                            amount_out,
-                            fee,
-                            ..
-                        } = UniswapV3ExactOutputSingleParamsV2::decode(buf).unwrap();
-                        self.try_run_trade::<false>(&exact_single_to_trade_info(
-                            token_out.as_ref(),
-                            token_in.as_ref(),
-                            amount_out,
-                            fee,
-                        ));
-                    } else if selector == UNISWAP_V3_MULTI_CALL {
-                        debug!("🦄2 multicall");
-                        let multi_call = UniswapV3MultiCall::decode(buf).unwrap();
-                        for call in multi_call.data.iter() {
-                            self.wrangle_transaction(&TransactionInfo {
-                                to: tx.to,
-                                value: tx.value,
-                                input: call.as_ref(),
-                            });
-                        }
-                    } else if selector == UNISWAP_V3_MULTI_CALL_DEADLINE {
-                        debug!("🦄2 multicall deadline");
-                        let multi_call = UniswapV3MultiCallDeadline::decode(buf)
-                            .map_err(|err| {
-                                warn!("{:02x?}", buf);
-                                err
-                            })
-                            .unwrap();
-                        for call in multi_call.data.iter() {
-                            self.wrangle_transaction(&TransactionInfo {
-                                to: tx.to,
-                                value: tx.value,
-                                input: call.as_ref(),
-                            });
-                        }
-                    } else {
-                        debug!("unhandled 🦄2: {:02x?}", selector);
-                    }
-                }
-                RouterId::UniswapV3UniversalRouter => {
-                    if selector == UNISWAP_UNIVERSAL_ROUTER_EXECUTE
-                        || selector == UNISWAP_UNIVERSAL_ROUTER_EXECUTE_DEADLINE
-                    {
-                        let params = UniswapV3UniversalExecuteParams::decode(buf).unwrap();
-                        for (idx, command) in params.commands.as_ref().iter().enumerate() {
-                            // V3_SWAP_EXACT_IN  0x00 https://docs.uniswap.org/contracts/universal-router/technical-reference
-                            // V3_SWAP_EXACT_OUT 0x01 / 0b0000_0001
-                            let command = command & 0x1f;
-                            if command == 0x00_u8 {
-                                debug!("🦄🌐 exact input {command}");
-                                if let Ok(swap) = UniswapV3UniversalRouterSwapExactIn::decode(
-                                    params.inputs[idx].as_ref(),
-                                ) {
-                                    self.v3_path_to_trade_info::<true>(
-                                        swap.path.as_ref(),
-                                        swap.amount_in,
-                                    );
-                                } else {
-                                    warn!("{:02x?}", buf);
-                                }
-                            } else if command == 0x01_u8 {
-                                debug!("🦄🌐 exact output {command}");
-                                if let Ok(swap) = UniswapV3UniversalRouterSwapExactOut::decode(
-                                    params.inputs[idx].as_ref(),
-                                ) {
-                                    self.v3_path_to_trade_info::<false>(
-                                        swap.path.as_ref(),
-                                        swap.amount_out,
-                                    );
-                                } else {
-                                    warn!("{:02x?}", buf);
-                                }
-                            } else {
-                                // command doing something we don't monitor
-                                debug!("unhandled 🦄🌐: {:?}", command);
-                            }
-                        }
-                    } else {
-                        debug!("unhandled 🦄🌐: {:02x?}", selector);
+        self.apply(trades);
+    }
+    /// Apply decoded trades in order, tracking the balances passed between them
+    fn apply(&mut self, trades: DecodedTrades) {
+        let mut balances = vec![0_u128; trades.slots];
+        for step in trades.steps {
+            match step {
+                TradeStep::Trade {
+                    mut info,
+                    exact_in,
+                    take,
+                    credit,
+                    required,
+                } => {
+                    if let Some(take) = take {
+                        info.amount = take.apply(&mut balances).into();
                     }
-                }
-                // NB: we map v4 and V5 aggregator to same router Id
-                RouterId::OneInch => {
-                    debug!("🐴");
-                    if selector == ONE_INCH_UNISWAP_V3_SWAP {
-                        let params = OneInchUniswapV3Swap::decode(buf).unwrap();
-                        let mut trade_info = TradeInfo {
-                            amount: params.amount_in,
-                            exchange_id: ExchangeId::Uniswap,
-                            path: vec![],
-                            unknown: vec![],
-                        };
-                        for pool in &params.pools {
-                            let pool_bytes = pool.0;
-                            let zero_for_one = pool_bytes[0] & 0x01 == 0;
-                            let pool_address: [u8; 20] =
-                                unsafe { *(&pool_bytes[12..32] as *const [u8] as *const [u8; 20]) };
-                            if let Some(pool) = POOL_LOOKUP.get(&pool_address) {
-                                if zero_for_one {
-                                    trade_info.path.push((
-                                        pool.token0,
-                                        pool.token1,
-                                        pool.fee as u32,
-                                    ));
-                                } else {
-                                    trade_info.path.push((
-                                        pool.token1,
-                                        pool.token0,
-                                        pool.fee as u32,
-                                    ));
-                                }
-                            } else {
-                                trade_info.unknown.push((
-                                    pool_address.into(),
-                                    pool_address.into(),
-                                    0_u32,
-                                ));
-                            }
-                        }
-                        self.try_run_trade::<true>(&trade_info);
-                    } else if selector == ONE_INCH_UNISWAP_V3_SWAP_TWP {
-                        let params = OneInchUniswapV3SwapTWP::decode(buf).unwrap();
-                        let mut trade_info = TradeInfo {
-                            amount: params.amount_in,
-                            exchange_id: ExchangeId::Uniswap,
-                            path: vec![],
-                            unknown: vec![],
-                        };
-                        for pool in &params.pools {
-                            let pool_bytes = pool.0;
-                            let zero_for_one = pool_bytes[0] & 0x01 == 0;
-                            let pool_address: [u8; 20] =
-                                unsafe { *(&pool_bytes[12..32] as *const [u8] as *const [u8; 20]) };
-                            if let Some(pool) = POOL_LOOKUP.get(&pool_address) {
-                                if zero_for_one {
-                                    trade_info.path.push((
-                                        pool.token0,
-                                        pool.token1,
-                                        pool.fee as u32,
-                                    ));
-                                } else {
-                                    trade_info.path.push((
-                                        pool.token1,
-                                        pool.token0,
-                                        pool.fee as u32,
-                                    ));
-                                }
-                            } else {
-                                trade_info.unknown.push((
-                                    pool_address.into(),
-                                    pool_address.into(),
-                                    0_u32,
-                                ));
-                            }
-                        }
-                        self.try_run_trade::<true>(&trade_info);
-                    } else if selector == ONE_INCH_UNISWAP_SWAP {
-                        debug!("v2 swap 🐴 unhandled");
+                    let received = if exact_in {
+                        self.try_run_trade::<true>(&info)
                     } else {
-                        debug!("unhandled 🐴: {:02x?}", selector);
-                    }
-                }
-                RouterId::ZeroEx => {
-                    debug!("👌🙅‍♀️");
-                    match selector {
-                        ZERO_EX_TRANSFORM_ERC20 => {
-                            use zero_ex::*;
-                            let outer_transform: TransformErc20 =
-                                <TransformErc20>::decode(buf).unwrap();
-                            for t in outer_transform.transformations.0.as_slice() {
-                                match t.deployment_nonce {
-                                    FILL_QUOTE_TRANSFORMER_19 | FILL_QUOTE_TRANSFORMER_21 => {
-                                        let data = Tuple::<FillQuoteTransformData>::decode(
-                                            t.data.as_ref(),
-                                        )
-                                        .unwrap()
-                                        .0;
-                                        let orders = data.bridge_orders.0.as_slice();
-                                        for order in orders {
-                                            let protocol_id = order.source.0[15];
-                                            info!(
-                                                "👌🙅‍♀️ trade via: {}",
-                                                core::str::from_utf8(&order.source.0[16..32])
-                                                    .unwrap()
-                                                    .trim_end()
-                                            );
-                                            if protocol_id == bridge_id::UNISWAPV3 {
-                                                if !(data.fill_amount & *HIGH_BIT).is_zero() {
-                                                    // 0x features allows specifying a ratio of user balance as fill amount
-                                                    // we cant' simulate without pulling it from chain...
-                                                    info!("0x can't simulate");
-                                                    // TODO: signal skip via TradeInfo
-                                                    return;
-                                                }
-                                                let v3_trade =
-                                                    UniswapV3Mixin::decode(order.data.0).unwrap();
-                                                self.v3_path_to_trade_info::<true>(
-                                                    v3_trade.path.as_ref(),
-                                                    data.fill_amount,
-                                                )
-                                            } else if protocol_id == bridge_id::UNISWAPV2 {
-                                                let v2_trade =
-                                                    UniswapV2Mixin::decode(order.data.0).unwrap();
-                                                match v2_trade.router.0 {
-                                                    &SUSHI_ROUTER => {
-                                                        debug!("sushi via 1inch: {:?}", v2_trade);
-                                                        // TODO: lookup fees from some constant
-                                                        self.v2_path_to_trade_info::<true>(
-                                                            v2_trade.path.as_slice(),
-                                                            data.fill_amount,
-                                                            300_u16,
-                                                            ExchangeId::Sushi,
-                                                        );
-                                                    }
-                                                    &CAMELOT_ROUTER => {
-                                                        debug!("camelot via 1inch: {:?}", v2_trade);
-                                                        self.v2_path_to_trade_info::<true>(
-                                                            v2_trade.path.as_slice(),
-                                                            data.fill_amount,
-                                                            300_u16,
-                                                            ExchangeId::Camelot,
-                                                        );
-                                                    }
-                                                    _ => {
-                                                        info!("uniswapV2 via 1inch: {:?}", v2_trade)
-                                                    }
-                                                }
-                                            } else {
-                                                // TODO: signal skip via TradeInfo
-                                                info!("unhandled protocol Id: {:?}", protocol_id);
-                                                return;
-                                            }
-                                        }
-                                    }
-                                    POSITIVE_SLIPPAGE_FEE_TRANSFORMER => (),
-                                    PAY_TAKER_TRANSFORMER => (),
-                                    AFFILIATE_FEE_TRANSFORMER => (),
-                                    WETH_TRANSFORMER => (),
-                                    _ => println!("unknown transformer: {:?}", t.deployment_nonce),
-                                }
-                            }
+                        self.try_run_trade::<false>(&info)
+                            .map(|_| info.amount.as_u128())
+                    };
+                    match (received, credit) {
+                        (Some(amount), Some(slot)) => {
+                            balances[slot] = balances[slot].saturating_add(amount)
                         }
-                        _ => debug!("unhandled 👌🙅‍♀️: {:02x?}", selector),
+                        // the rest of the trades depend on this one
+                        (None, _) if required => return,
+                        _ => (),
                     }
                 }
-                RouterId::Odos => {
-                    // https://arbiscan.io/address/0xa0b07f9a11dfb01388149abbdbc5b4f2196600ab#code
-                    // ODOS swap: simpler interface available non-opaque
-                    // used by Chronos DeFi
-                    // the bytecode is opaque and not publicly documented (ODOS wants to protect users from MEV)
-                    // TODO: can atleast check which tokens are included and signal skip or not
-                    if selector == ODOS_SWAP {
-                        debug!("⏰ swap: {:?}", OdosSwap::decode(buf).unwrap());
-                    } else {
-                        debug!("⏰: {:02x?}", selector);
-                    }
+                TradeStep::Credit { slot, amount } => {
+                    balances[slot] = balances[slot].saturating_add(amount)
                 }
-                RouterId::SushiRouterV2 => {
-                    // TODO: sushi 'RouteProcessor' needs scan also
-                    if selector == SUSHI_SWAP_EXACT_ETH_FOR_TOKENS
-                        || selector == SUSHI_SWAP_EXACT_ETH_FOR_TOKENS_SFOTT
-                    {
-                        let swap = SwapExactETHForTokens::decode(buf).unwrap();
-                        self.v2_path_to_trade_info::<true>(
-                            swap.path.as_slice(),
-                            tx.value,
-                            300_u16,
-                            ExchangeId::Sushi,
-                        );
-                    } else if selector == SUSHI_SWAP_EXACT_TOKENS_FOR_ETH
-                        || selector == SUSHI_SWAP_EXACT_TOKENS_FOR_ETH_SFOTT
-                    {
-                        let swap = SwapExactTokensForETH::decode(buf).unwrap();
-                        self.v2_path_to_trade_info::<true>(
-                            swap.path.as_slice(),
-                            swap.amount_in,
-                            300_u16,
-                            ExchangeId::Sushi,
-                        );
-                    } else {
-                        debug!("🍣: {:02x?} unhandled", selector);
-                    }
+                TradeStep::Move { take, to } => {
+                    let amount = take.apply(&mut balances);
+                    balances[to] = balances[to].saturating_add(amount);
                 }
-                RouterId::CamelotRouterV2 => {
-                    if selector == CAMELOT_V2_SWAP_EXACT_ETH_FOR_TOKENS_SFOTT {
-                        let swap = SwapExactETHForTokensSFOTT::decode(buf).unwrap();
-                        self.v2_path_to_trade_info::<true>(
-                            swap.path.as_slice(),
-                            tx.value,
-                            300_u16,
-                            ExchangeId::Camelot,
-                        );
-                    } else if selector == CAMELOT_V2_SWAP_EXACT_TOKENS_FOR_ETH_SFOTT {
-                        let swap = SwapExactTokensForEthSFOTT::decode(buf).unwrap();
-                        self.v2_path_to_trade_info::<true>(
-                            swap.path.as_slice(),
-                            swap.amount_in,
-                            300_u16,
-                            ExchangeId::Camelot,
-                        );
-                    } else {
-                        debug!("🛡️: {:02x?} unhandled", selector);
-                    }
-                }
-                RouterId::Gmx => {}
-                RouterId::ParaswapAugustus => {}
             }
         }
     }
-    /// Build trade info from uniswap compliant `path` bytes
-    fn v3_path_to_trade_info<const D: bool>(&mut self, path: &[u8], amount: U256) {
-        if path.len() % 43 != 0 {
+    /// Flag the simulation skipped if a trade moved edge `edge_id` from `price_before` by more than the max. price impact
+    /// i.e. the prices are likely garbage, resync from chain rather than trade on them
+    fn check_price_impact(&mut self, edge_id: u32, price_before: Option<f64>) {
+        let (Some(price_before), Some(edge)) = (price_before, self.graph.edge_by_id(edge_id))
+        else {
             return;
-        }
-        let trade_count = path.len() / 43; // 20 + 3 + 20 (uint160, uint24, uint160)
-        let mut trade_info = TradeInfo {
-            amount,
-            exchange_id: ExchangeId::Uniswap,
-            path: Vec::with_capacity(trade_count),
-            unknown: vec![],
-        };
-
-        (0..trade_count).for_each(|idx| {
-            let offset = idx * 43;
-            let token_in: &[u8; 20] =
-                &unsafe { *(&path[offset..offset + 20] as *const [u8] as *const [u8; 20]) };
-            let fee = fee_from_path_bytes(&path[offset + 20..offset + 23]);
-            let token_out: &[u8; 20] =
-                &unsafe { *(&path[offset + 23..offset + 43] as *const [u8] as *const [u8; 20]) };
-
-            let (a, b) = address_to_token(token_in, token_out);
-
-            match (a, b) {
-                (Some(a), Some(b)) => trade_info.path.push((a, b, fee)),
-                _ => {
-                    // trade is through a path we aren't monitoring locally
-                    trade_info
-                        .unknown
-                        .push(((*token_in).into(), (*token_out).into(), fee));
-                    debug!("{:02x?}/{:02x?}/{fee}", token_in, token_out);
-                }
-            }
-        });
-
-        self.try_run_trade::<D>(&trade_info);
-    }
-    /// Build trade info from uniswap compliant `path` bytes
-    fn v2_path_to_trade_info<const D: bool>(
-        &mut self,
-        path: &[AddressZcp],
-        amount: U256,
-        fee: u16,
-        exchange_id: ExchangeId,
-    ) {
-        let trade_count = path.len() - 1;
-        let mut trade_info = TradeInfo {
-            amount,
-            exchange_id,
-            path: Vec::with_capacity(trade_count),
-            unknown: vec![],
         };
-
-        (0..trade_count).for_each(|idx| {
-            let token_in = path[idx].0;
-            let token_out = path[idx + 1].0;
-            let (a, b) = address_to_token(token_in, token_out);
-            match (a, b) {
-                (Some(a), Some(b)) => trade_info.path.push((a, b, fee as u32)),
-                _ => {
-                    // trade is through a path we aren't monitoring locally
-                    trade_info
-                        .unknown
-                        .push(((*token_in).into(), (*token_out).into(), 0));
-                    debug!("{:02x?}/{:02x?}/0", token_in, token_out);
-                }
-            }
-        });
-
-        self.try_run_trade::<D>(&trade_info);
+        let price_impact = 1.0 - edge.price() / price_before;
+        if price_impact > self.max_price_impact {
+            warn!(
+                "price impact {:.2}% on edge: {:?}",
+                price_impact * 100.0,
+                edge
+            );
+            self.price_impact_exceeded = true;
+            self.mark_skipped();
+        }
     }
-}
-
-/// Build trade info from exact|output single
-fn exact_single_to_trade_info(
-    token_in: &[u8; 20],
-    token_out: &[u8; 20],
-    amount: U256,
-    fee: u32,
-) -> TradeInfo {
-    let (a, b) = address_to_token(token_in, token_out);
-    match (a, b) {
-        (Some(a), Some(b)) => TradeInfo {
-            path: vec![(a, b, fee)],
-            unknown: vec![],
-            amount,
-            exchange_id: ExchangeId::Uniswap,
-        },
-        _ => TradeInfo {
-            path: vec![],
-            unknown: vec![(token_in.into(), token_out.into(), fee)],
-            amount,
-            exchange_id: ExchangeId::Uniswap,
-        },
+    /// Flag that a trade could not be simulated
+    fn mark_skipped(&mut self) {
+        #[cfg(feature = "metrics")]
+        metrics::TRADES_SKIPPED.inc();
+        self.skip = true;
     }
 }
 
-/// Lookup token addresses returning corresponding `Token`s, if matched
-fn address_to_token<'a>(
-    token_in: &'a [u8; 20],
-    token_out: &'a [u8; 20],
-) -> (Option<Token>, Option<Token>) {
-    (
-        TOKEN_LOOKUP.get(token_in).copied(),
-        TOKEN_LOOKUP.get(token_out).copied(),
-    )
-}
-
 #[cfg(test)]
 mod test {
     use crate::trade_router::*;
     use ethabi_static::DecodeStatic;
     use hex_literal::hex;
 
+    #[test]
+    fn price_impact_guard() {
+        use super::TradeSimulator;
+        use crate::{
+            price_graph::Edge,
+            types::{ExchangeId, Fee, Token},
+            PriceGraph,
+        };
+
+        let mut graph = PriceGraph::empty();
+        graph.add_edge(
+            Token::USDC,
+            Token::WETH,
+            Edge::new_v2(
+                2_000_000_000000,
+                1_000 * 10_u128.pow(18),
+                Fee::from_units(30),
+                ExchangeId::Sushi,
+            ),
+        );
+        let trade = |amount: u128| TradeInfo {
+            amount: amount.into(),
+            path: vec![(Token::USDC, Token::WETH, Fee::from_units(30))],
+            exchange_id: ExchangeId::Sushi,
+            unknown: vec![],
+        };
+
+        // ~1% impact
+        let mut simulator = TradeSimulator::new(&mut graph).with_max_price_impact_bps(500);
+        assert!(simulator
+            .try_run_trade::<true>(&trade(10_000_000000))
+            .is_some());
+        assert!(!simulator.skipped());
+
+        // ~50% impact, the pool holds ~ $4m
+        assert!(simulator
+            .try_run_trade::<true>(&trade(1_000_000_000000))
+            .is_some());
+        assert!(simulator.skipped());
+        assert!(simulator.price_impact_exceeded());
+    }
+
     #[test]
     fn test_execute_deadline() {
         let buf = hex!("000000000000000000000000000000000000000000000000000000000000006000000000000000000000000000000000000000000000000000000000000000a000000000000000000000000000000000000000000000000000000000646ed6d700000000000000000000000000000000000000000000000000000000000000020000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000200000000000000000000000000000000000000000000000000000000000000400000000000000000000000000000000000000000000000000000000000000160000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000ba43b740000000000000000000000000000000000000000000000098a1b3fd24f4d168ea200000000000000000000000000000000000000000000000000000000000000a00000000000000000000000000000000000000000000000000000000000000001000000000000000000000000000000000000000000000000000000000000002bff970a61a04b1ca14834a43f5de4533ebddb5cc80001f4912ce59144191c1204e64559fe8253a0e49e6548000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000012000000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000ba43b740000000000000000000000000000000000000000000000098b057a68577b20cfaa00000000000000000000000000000000000000000000000000000000000000a000000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000042ff970a61a04b1ca14834a43f5de4533ebddb5cc80001f482af49447d8a07e3bd95bd0d56f35241523fbab10001f4912ce59144191c1204e64559fe8253a0e49e6548000000000000000000000000000000000000000000000000000000000000");
@@ -704,6 +326,87 @@ mod test {
         assert!(res.is_ok());
     }
 
+    #[test]
+    fn decode_route_processor_route() {
+        let route = hex!(
+            // processUserERC20 USDC, 1 swap, full share, uniV3 pool, zeroForOne, recipient
+            "02ff970a61a04b1ca14834a43f5de4533ebddb5cc801ffff01c31e54c7a869b9fcbecc14363cf510d1c41fa44301e754841b77c874135caca3386676e886459c2d61"
+            // processOnePool WETH, uniV2 pool, direction, recipient
+            "0482af49447d8a07e3bd95bd0d56f35241523fbab100e754841b77c874135caca3386676e886459c2d6100c31e54c7a869b9fcbecc14363cf510d1c41fa443"
+        );
+        let commands = decode_route(&route, false).unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                RouteCommand {
+                    source: RouteSource::Sender,
+                    token: hex!("ff970a61a04b1ca14834a43f5de4533ebddb5cc8"),
+                    swaps: vec![(
+                        u16::MAX,
+                        RoutePool::UniswapV3 {
+                            pool: hex!("c31e54c7a869b9fcbecc14363cf510d1c41fa443"),
+                            zero_for_one: true,
+                        }
+                    )],
+                },
+                RouteCommand {
+                    source: RouteSource::Pool,
+                    token: hex!("82af49447d8a07e3bd95bd0d56f35241523fbab1"),
+                    swaps: vec![(
+                        u16::MAX,
+                        RoutePool::UniswapV2 {
+                            pool: hex!("e754841b77c874135caca3386676e886459c2d61"),
+                            zero_for_one: false,
+                        }
+                    )],
+                },
+            ]
+        );
+        // RouteProcessor4 encodes the uniV2 fee
+        assert_eq!(decode_route(&route, true), Err(RouteError::Truncated));
+        // bentobox
+        assert_eq!(
+            decode_route(&hex!("05"), false),
+            Err(RouteError::UnsupportedCommand(5))
+        );
+    }
+
+    #[test]
+    fn one_inch_swap_executor_pools() {
+        let buf = hex!("000000000000000000000000f0694acc9e941b176e17b9ef923e71e7b8b2477a000000000000000000000000ff970a61a04b1ca14834a43f5de4533ebddb5cc800000000000000000000000082af49447d8a07e3bd95bd0d56f35241523fbab1000000000000000000000000f0694acc9e941b176e17b9ef923e71e7b8b2477a00000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000005f5e100000000000000000000000000000000000000000000000000000000000000000100000000000000000000000000000000000000000000000000000000000000040000000000000000000000000000000000000000000000000000000000000140000000000000000000000000000000000000000000000000000000000000016000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000044fa461e33800000000000000000000000c31e54c7a869b9fcbecc14363cf510d1c41fa4430000000000000000000000000000000000000000000000000000000005f5e10000000000000000000000000000000000000000000000000000000000");
+        let swap = OneInchSwap::decode(&buf).unwrap();
+        assert_eq!(
+            swap.token_in.0,
+            &hex!("ff970a61a04b1ca14834a43f5de4533ebddb5cc8")
+        );
+        assert_eq!(
+            swap.token_out.0,
+            &hex!("82af49447d8a07e3bd95bd0d56f35241523fbab1")
+        );
+        assert_eq!(swap.amount, 100_000_000_u64.into());
+        assert_eq!(
+            scan_known_pools(swap.data.as_ref()),
+            vec![hex!("c31e54c7a869b9fcbecc14363cf510d1c41fa443")]
+        );
+    }
+
+    #[test]
+    fn decode_gmx_swap() {
+        let buf = hex!("00000000000000000000000000000000000000000000000000000000000000800000000000000000000000000000000000000000000000000000000005f5e1000000000000000000000000000000000000000000000000000000000000000001000000000000000000000000369a2fdb910d432f0a07381a5e3d27572c8767130000000000000000000000000000000000000000000000000000000000000002000000000000000000000000ff970a61a04b1ca14834a43f5de4533ebddb5cc800000000000000000000000082af49447d8a07e3bd95bd0d56f35241523fbab1");
+        let swap = GmxSwap::decode(&buf).unwrap();
+        assert_eq!(swap.amount_in, 100_000_000_u64.into());
+        assert_eq!(
+            swap.path
+                .iter()
+                .map(|token| *token.0)
+                .collect::<Vec<[u8; 20]>>(),
+            vec![
+                hex!("ff970a61a04b1ca14834a43f5de4533ebddb5cc8"),
+                hex!("82af49447d8a07e3bd95bd0d56f35241523fbab1")
+            ]
+        );
+    }
+
     #[test]
     fn one_inch_v3_swap() {
         let buf = hex!("0000000000000000000000000000000000000000000000000000000000c2cab70000000000000000000000000000000000000000000000000018be73ce4ce1ea00000000000000000000000000000000000000000000000000000000000000600000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000e754841b77c874135caca3386676e886459c2d61cfee7c08");
@@ -724,4 +427,118 @@ mod test {
 
         assert!(false);
     }
+
+    /// Reserves of each pool in the corpus graphs, deep enough trades don't hit the price impact guard
+    const CORPUS_RESERVE: u128 = 10_u128.pow(30);
+
+    /// A recorded router call and the trades it should decode to, a `res/calldata/` corpus file
+    #[derive(serde::Deserialize)]
+    struct CorpusCall {
+        router: ethers::types::Address,
+        #[serde(default)]
+        value: ethers::types::U256,
+        input: ethers::types::Bytes,
+        /// The call is expected to skip the simulation round
+        #[serde(default)]
+        skip: bool,
+        /// `TradeInfo` of each trade step in order, as printed by `fulcrum decode`
+        trades: Vec<serde_json::Value>,
+    }
+
+    /// Check the corpus call `json` from `file` decodes to its trades and they simulate on a graph of their pools
+    fn check_corpus_call(file: &str, json: &str) {
+        use super::TradeSimulator;
+        use crate::{
+            price_graph::Edge,
+            router_decoder::{decode_call, TradeStep},
+            PriceGraph,
+        };
+        use fulcrum_sequencer_feed::TransactionInfo;
+
+        let call: CorpusCall =
+            serde_json::from_str(json).unwrap_or_else(|err| panic!("{file}: {err}"));
+        let tx = TransactionInfo {
+            to: call.router,
+            value: call.value,
+            input: call.input.as_ref(),
+            nonce: 0,
+            gas_limit: 0,
+            max_fee_per_gas: 0,
+        };
+        let trades = decode_call(&tx);
+        assert_eq!(trades.skip, call.skip, "{file}: skip");
+        let infos: Vec<&TradeInfo> = trades
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                TradeStep::Trade { info, .. } => Some(info),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            serde_json::to_value(&infos).unwrap(),
+            serde_json::Value::Array(call.trades),
+            "{file}: trades"
+        );
+        if call.skip {
+            return;
+        }
+
+        let mut graph = PriceGraph::empty();
+        for info in infos.iter() {
+            for (a, b, fee) in info.path.iter() {
+                graph.add_edge(
+                    *a,
+                    *b,
+                    Edge::new_v2(CORPUS_RESERVE, CORPUS_RESERVE, *fee, info.exchange_id),
+                );
+            }
+        }
+        let mut simulator = TradeSimulator::new(&mut graph);
+        simulator.wrangle_transaction(&tx);
+        // trades through unmonitored pools can't be simulated
+        let unknown = infos.iter().any(|info| !info.unknown.is_empty());
+        assert_eq!(simulator.skipped(), unknown, "{file}: simulation skipped");
+        for info in infos.iter().filter(|info| info.unknown.is_empty()) {
+            for (a, b, _fee) in info.path.iter() {
+                assert!(
+                    graph.dirty().contains(*a, *b),
+                    "{file}: {a:?}/{b:?} not traded"
+                );
+            }
+        }
+    }
+
+    /// Generate a test per corpus file, `test_name: "file.json"`
+    macro_rules! calldata_corpus {
+        ($($name:ident: $file:literal,)*) => {
+            /// The corpus files with a test
+            const CORPUS: &[&str] = &[$($file),*];
+            $(
+                #[test]
+                fn $name() {
+                    check_corpus_call($file, include_str!(concat!("../res/calldata/", $file)));
+                }
+            )*
+        };
+    }
+
+    calldata_corpus! {
+        corpus_sushi_router_v2_exact_tokens_for_eth: "sushi-router-v2-exact-tokens-for-eth.json",
+        corpus_uniswap_universal_router_v3_exact_in: "uniswap-universal-router-v3-exact-in.json",
+        corpus_uniswap_v3_router_v2_exact_output: "uniswap-v3-router-v2-exact-output.json",
+    }
+
+    #[test]
+    fn corpus_files_have_tests() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/res/calldata");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let file = entry.unwrap().file_name();
+            let file = file.to_string_lossy();
+            assert!(
+                !file.ends_with(".json") || CORPUS.contains(&file.as_ref()),
+                "add {file} to `calldata_corpus!`"
+            );
+        }
+    }
 }
@@ -1,77 +1,99 @@
 //! Common data types and traits
 
+use std::fmt;
+
 pub use ethers::types::{Address, U256};
-use variant_count::VariantCount;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{config, registry::MAX_TOKENS};
 
-use crate::constant::arbitrum::{ARB, DAI, GMX, USDC, USDT, WBTC, WETH};
+/// Max. token decimals, one whole token must fit in a `u128` amount
+pub const MAX_DECIMALS: u8 = 38;
 
-/// Represents an asset type
-#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, VariantCount)]
-pub enum Token {
-    // THIS ORDER MUST NOT CHANGE arbitrarily see contract/TradeExecutor.sol
-    USDC = 0,
-    WETH = 1,
-    WBTC = 2,
-    ARB = 3,
-    USDT = 4,
-    DAI = 5,
-    GMX = 6,
+/// Units of one whole token with `decimals` e.g. 1e6 for USDC
+pub const fn one_token(decimals: u8) -> u128 {
+    10_u128.pow(decimals as u32)
 }
 
+/// A token Id, assigned at runtime by the `TokenRegistry`
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Token(u8);
+
 impl Token {
+    // Ids of the bundled Arbitrum One tokens, see contract/TradeExecutor.sol
+    pub const USDC: Self = Self(0);
+    pub const WETH: Self = Self(1);
+    pub const WBTC: Self = Self(2);
+    pub const ARB: Self = Self(3);
+    pub const USDT: Self = Self(4);
+    pub const DAI: Self = Self(5);
+    pub const GMX: Self = Self(6);
+
+    /// Create a token with Id `id`
+    pub const fn new(id: u8) -> Self {
+        assert!((id as usize) < MAX_TOKENS, "unsupported token index");
+        Self(id)
+    }
     /// Cast usize into `Token`
     pub fn from_usize(x: usize) -> Self {
-        match x {
-            0 => Self::USDC,
-            1 => Self::WETH,
-            2 => Self::WBTC,
-            3 => Self::ARB,
-            4 => Self::USDT,
-            5 => Self::DAI,
-            6 => Self::GMX,
-            _ => panic!("unsupported token index"),
-        }
+        Self::new(u8::try_from(x).expect("unsupported token index"))
+    }
+    /// The token Id
+    pub fn id(&self) -> u8 {
+        self.0
+    }
+    /// The token Id as an index
+    pub fn index(&self) -> usize {
+        self.0 as usize
     }
     /// The onchain address of the token contract
     pub fn address(&self) -> Address {
-        match self {
-            Self::WETH => WETH.into(),
-            Self::USDC => USDC.into(),
-            Self::WBTC => WBTC.into(),
-            Self::ARB => ARB.into(),
-            Self::USDT => USDT.into(),
-            Self::DAI => DAI.into(),
-            Self::GMX => GMX.into(),
-        }
+        config::active().registry().address(*self)
     }
-    pub fn from_address(a: [u8; 20]) -> Self {
-        match a {
-            WETH => Self::WETH,
-            USDC => Self::USDC,
-            WBTC => Self::WBTC,
-            ARB => Self::ARB,
-            USDT => Self::USDT,
-            DAI => Self::DAI,
-            GMX => Self::GMX,
-            _ => unimplemented!(),
-        }
+    /// The registered token at address `a`, if any
+    pub fn from_address(a: [u8; 20]) -> Option<Self> {
+        config::active().registry().by_address(&a)
     }
     /// The decimals of the token
     pub fn decimals(&self) -> u8 {
-        match self {
-            Self::USDC | Self::USDT => 6,
-            Self::WBTC => 8,
-            _ => 18,
+        config::active().registry().decimals(*self)
+    }
+    /// Units of one whole token
+    pub fn one(&self) -> u128 {
+        one_token(self.decimals())
+    }
+    /// `amount` units as whole tokens, lossy
+    pub fn to_whole(&self, amount: u128) -> f64 {
+        amount as f64 / self.one() as f64
+    }
+    /// `amount` whole tokens as units, saturating
+    pub fn from_whole(&self, amount: f64) -> u128 {
+        (amount * self.one() as f64) as u128
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match config::active().registry().get(*self) {
+            Some(info) => f.write_str(&info.symbol),
+            None => write!(f, "Token({})", self.0),
         }
     }
 }
 
+/// Serialized as the token symbol
+impl Serialize for Token {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{self:?}"))
+    }
+}
+
 /// A trading pair/pool
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Pair {
     pub token0: Token,
     pub token1: Token,
-    pub fee: u16,
+    pub fee: Fee,
     pub exchange_id: ExchangeId,
 }
 
@@ -80,12 +102,12 @@ impl Pair {
     pub fn tokens(&self) -> (Token, Token) {
         (self.token0, self.token1)
     }
-    /// Return the pair's fee (as in uniswap v3 fee tier or uniswapV2 protocol wide fee)
-    pub fn fee(&self) -> u16 {
+    /// Return the pair's pool fee
+    pub fn fee(&self) -> Fee {
         self.fee
     }
     /// Create a new pair (a, b) as given
-    pub fn new_raw(a: Token, b: Token, fee: u16, exchange_id: ExchangeId) -> Self {
+    pub fn new_raw(a: Token, b: Token, fee: Fee, exchange_id: ExchangeId) -> Self {
         Self {
             token0: a,
             token1: b,
@@ -94,8 +116,8 @@ impl Pair {
         }
     }
     /// Create a new pair (orders a/b based on their address as per Uniswap v2)
-    /// `fee` denotes the pair's pool fee as in uniswap v3
-    pub fn new(a: Token, b: Token, fee: u16, exchange_id: ExchangeId) -> Self {
+    /// `fee` denotes the pair's pool fee
+    pub fn new(a: Token, b: Token, fee: Fee, exchange_id: ExchangeId) -> Self {
         // optimization for univ2, always organize pair by address
         if a.address() < b.address() {
             Self {
@@ -117,7 +139,8 @@ impl Pair {
 
 /// Unique ID for a router contract
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
 pub enum RouterId {
     UniswapV3RouterV1 = 0,
     UniswapV3RouterV2 = 1,
@@ -130,10 +153,30 @@ pub enum RouterId {
     ZeroEx = 8,
     // Value([u8; 20]) = 9,
     Odos = 10,
+    SushiRouteProcessor3 = 11,
+    SushiRouteProcessor4 = 12,
+    BalancerVault = 13,
+    RamsesRouter = 14,
+    KyberSwap = 15,
+    OdosV2 = 16,
+}
+
+/// How calls to a contract without a calldata decoder are handled e.g. intent settlement or RFQ flow
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OpaqueRouterPolicy {
+    /// Assume the call doesn't move monitored pools
+    #[default]
+    Ignore,
+    /// Skip the simulation round, the call is certain to move monitored pools
+    SkipRound,
+    /// Skip the simulation round if the calldata mentions a known pool or two known tokens
+    Tokens,
 }
 
 /// Unique ID for an exchange
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
 pub enum ExchangeId {
     /// UniswapV3
     Uniswap = 0,
@@ -141,10 +184,133 @@ pub enum ExchangeId {
     Sushi = 2,
     Chronos = 3,
     Zyber = 4,
+    /// Uniswap V2 pairs (not supported by the executor contract)
+    UniswapV2 = 5,
+    /// Uniswap V4 PoolManager pools (not price synced)
+    UniswapV4 = 6,
+    /// Balancer V2 weighted pools (not supported by the executor contract)
+    Balancer = 7,
+    /// Curve StableSwap pools (not supported by the executor contract)
+    Curve = 8,
+    /// Ramses (Solidly fork) pairs (not supported by the executor contract)
+    Ramses = 9,
+    /// KyberSwap Elastic pools (not supported by the executor contract)
+    KyberElastic = 10,
     /// Non-production price source
     Test = 255,
 }
 
+/// A pool fee in pips (hundredths of a basis point, 1/1_000_000ths) as uniswap v3 fee tiers e.g. 3_000 is 0.3%
+///
+/// Exchanges quote fees in different units, convert at the edges of the engine (config, pool state, calldata) with
+/// the constructor for the unit rather than passing raw integers around
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(transparent)]
+pub struct Fee(u32);
+
+impl Fee {
+    /// Pips per whole i.e. a 100% fee
+    pub const PIPS: u32 = 1_000_000;
+    /// Pips per fee unit (1/100_000ths)
+    const PIPS_PER_UNIT: u32 = 10;
+    /// No fee
+    pub const ZERO: Self = Self(0);
+
+    /// A fee of `pips` 1/1_000_000ths e.g. a uniswap v3 fee tier
+    pub const fn from_pips(pips: u32) -> Self {
+        Self(pips)
+    }
+    /// A fee of `units` 1/100_000ths e.g. uniswap v2 style, camelot, solidly, and kyber elastic fees
+    pub const fn from_units(units: u32) -> Self {
+        Self(units.saturating_mul(Self::PIPS_PER_UNIT))
+    }
+    /// A fee of `bps` basis points (1/10_000ths)
+    pub const fn from_bps(bps: u32) -> Self {
+        Self(bps.saturating_mul(100))
+    }
+    /// A fee of `fee` / `denominator` e.g. balancer (1e18 fixed point) and curve (1/10_000_000_000ths) fees
+    ///
+    /// Rounded up to whole pips, a fee finer than a pip is overestimated rather than missed
+    pub fn from_fraction(fee: u128, denominator: u128) -> Self {
+        let pips = fee
+            .saturating_mul(Self::PIPS as u128)
+            .saturating_add(denominator.saturating_sub(1))
+            .checked_div(denominator)
+            .unwrap_or_default();
+        Self(pips.min(u32::MAX as u128) as u32)
+    }
+    /// A fee as quoted by `exchange_id` pools and configured, uniswap v3 fee tiers in pips and others in 1/100_000ths
+    pub fn from_quoted(exchange_id: ExchangeId, fee: u32) -> Self {
+        match exchange_id {
+            ExchangeId::Uniswap | ExchangeId::UniswapV4 => Self::from_pips(fee),
+            _ => Self::from_units(fee),
+        }
+    }
+    /// The fee in pips (1/1_000_000ths)
+    pub const fn pips(self) -> u32 {
+        self.0
+    }
+    /// The fee in 1/100_000ths, rounded down
+    pub const fn units(self) -> u32 {
+        self.0 / Self::PIPS_PER_UNIT
+    }
+    /// The fee as quoted by `exchange_id` pools, the inverse of `from_quoted`
+    pub fn quoted(self, exchange_id: ExchangeId) -> u32 {
+        match exchange_id {
+            ExchangeId::Uniswap | ExchangeId::UniswapV4 => self.pips(),
+            _ => self.units(),
+        }
+    }
+    /// The fee as a fraction of 1
+    pub fn as_f64(self) -> f64 {
+        self.0 as f64 / Self::PIPS as f64
+    }
+}
+
+impl fmt::Display for Fee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}bps", self.0 as f64 / 100.0)
+    }
+}
+
+/// The pricing curve of a pool, tells the executor contract how to trade it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PoolKind {
+    /// Uniswap V2 style constant product pair (also balancer and curve pools, not supported by the executor contract)
+    #[default]
+    ConstantProduct = 0,
+    /// Solidly stable pair (x³y + xy³ = k)
+    SolidlyStable = 1,
+    /// Uniswap V3 style concentrated liquidity pool
+    ConcentratedLiquidity = 2,
+}
+
+impl PoolKind {
+    /// The pool kind of exchange `exchange_id`'s pools, solidly stable pairs must be set explicitly
+    pub fn of(exchange_id: u8) -> Self {
+        if exchange_id == ExchangeId::Uniswap as u8
+            || exchange_id == ExchangeId::UniswapV4 as u8
+            || exchange_id == ExchangeId::KyberElastic as u8
+        {
+            Self::ConcentratedLiquidity
+        } else {
+            Self::ConstantProduct
+        }
+    }
+    /// The pool kind with Id `id`, `None` if unknown
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::ConstantProduct),
+            1 => Some(Self::SolidlyStable),
+            2 => Some(Self::ConcentratedLiquidity),
+            _ => None,
+        }
+    }
+}
+
 /// Represents a token position
 #[derive(Debug)]
 pub struct Position {
@@ -162,10 +328,29 @@ impl Position {
     }
     /// Create a position of `size` whole `token`s
     pub fn of(size: u32, token: Token) -> Self {
-        Self::new(size as u128 * 10_u128.pow(token.decimals() as u32), token)
+        Self::new(size as u128 * token.one(), token)
+    }
+}
+
+/// Formatted in whole tokens e.g. `1.5 WETH`
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_units(f, self.amount, self.token.decimals())?;
+        write!(f, " {:?}", self.token)
     }
 }
 
+/// Write `amount` units of a token with `decimals` as whole tokens, exact
+fn fmt_units(f: &mut fmt::Formatter<'_>, amount: u128, decimals: u8) -> fmt::Result {
+    let one = one_token(decimals);
+    let (whole, fraction) = (amount / one, amount % one);
+    if fraction == 0 {
+        return write!(f, "{whole}");
+    }
+    let fraction = format!("{fraction:0>width$}", width = decimals as usize);
+    write!(f, "{whole}.{}", fraction.trim_end_matches('0'))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -181,4 +366,51 @@ mod test {
         assert_eq!(Token::from_usize(5), Token::DAI);
         assert_eq!(Token::from_usize(6), Token::GMX);
     }
+
+    #[test]
+    fn whole_token_units() {
+        struct Units(u128, u8);
+        impl fmt::Display for Units {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt_units(f, self.0, self.1)
+            }
+        }
+        assert_eq!(one_token(6), 1_000_000);
+        assert_eq!(one_token(MAX_DECIMALS), 10_u128.pow(38));
+        assert_eq!(Units(1_500_000, 6).to_string(), "1.5");
+        assert_eq!(Units(2 * 10_u128.pow(18), 18).to_string(), "2");
+        assert_eq!(Units(1, 18).to_string(), "0.000000000000000001");
+        assert_eq!(Units(42, 0).to_string(), "42");
+        assert_eq!(
+            Units(u128::MAX, MAX_DECIMALS).to_string(),
+            "3.40282366920938463463374607431768211455"
+        );
+    }
+
+    #[test]
+    fn fee_units() {
+        // 0.3% in each unit
+        let fee = Fee::from_pips(3_000);
+        assert_eq!(Fee::from_units(300), fee);
+        assert_eq!(Fee::from_bps(30), fee);
+        assert_eq!(fee.units(), 300);
+        assert_eq!(fee.as_f64(), 0.003);
+        assert_eq!(fee.to_string(), "30bps");
+        // uniswap v3 tiers are quoted in pips, others in 1/100_000ths
+        assert_eq!(Fee::from_quoted(ExchangeId::Uniswap, 3_000), fee);
+        assert_eq!(Fee::from_quoted(ExchangeId::Sushi, 300), fee);
+        assert_eq!(Fee::from_quoted(ExchangeId::KyberElastic, 40).pips(), 400);
+        assert_eq!(fee.quoted(ExchangeId::Uniswap), 3_000);
+        assert_eq!(fee.quoted(ExchangeId::Ramses), 300);
+        assert_eq!(Fee::from_units(u32::MAX).pips(), u32::MAX);
+        // balancer (1e18 fixed point) and curve (1/10_000_000_000ths) fees, rounded up to whole pips
+        assert_eq!(
+            Fee::from_fraction(3_000_000_000_000_000, 1_000_000_000_000_000_000),
+            fee
+        );
+        assert_eq!(Fee::from_fraction(30_000_000, 10_000_000_000), fee);
+        assert_eq!(Fee::from_fraction(1, 10_000_000_000).pips(), 1);
+        assert_eq!(Fee::from_fraction(u128::MAX, 1).pips(), u32::MAX);
+        assert_eq!(Fee::from_fraction(1, 0), Fee::ZERO);
+    }
 }
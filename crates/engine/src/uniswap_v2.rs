@@ -2,33 +2,53 @@
 use ethabi_static::DecodeStatic;
 use ethers::{
     abi::{encode_packed, Token as ABIToken},
+    types::U512,
     utils::keccak256,
 };
 
-use crate::types::{Address, Pair, U256};
+use crate::{
+    types::{Address, Fee, Pair, U256},
+    util::saturating_u128,
+};
 
-pub const FEE_DENOMINATOR: u128 = 100_000;
+/// Fees are applied in pips
+const FEE_DENOMINATOR: u128 = Fee::PIPS as u128;
 
 /// Mirror router 'getAmountOut' calculation
-pub fn get_amount_out(fee: u16, amount_in: u128, reserve_in: u128, reserve_out: u128) -> u128 {
-    let amount_in_with_fee = U256::from(amount_in * (FEE_DENOMINATOR - fee as u128));
+///
+/// Products of amounts and reserves are computed in U512 so any input is safe, the amount out is always < `reserve_out`
+pub fn get_amount_out(fee: Fee, amount_in: u128, reserve_in: u128, reserve_out: u128) -> u128 {
+    let amount_in_with_fee =
+        U512::from(amount_in) * U512::from(FEE_DENOMINATOR - fee.pips() as u128);
     // y0 = (y.x0)  / (x + x0)
-    let amount_out = (U256::from(reserve_out) * amount_in_with_fee)
-        / ((U256::from(reserve_in) * U256::from(FEE_DENOMINATOR)) + amount_in_with_fee);
+    let denominator = (U512::from(reserve_in) * U512::from(FEE_DENOMINATOR)) + amount_in_with_fee;
+    if denominator.is_zero() {
+        return 0;
+    }
+    let amount_out = (U512::from(reserve_out) * amount_in_with_fee) / denominator;
 
-    amount_out.as_u128()
+    amount_out.low_u128()
 }
 
-/// Mirror router 'getAmountOut' calculation
-pub fn get_amount_in(fee: u16, amount_out: u128, reserve_in: u128, reserve_out: u128) -> u128 {
-    let numerator = reserve_in * amount_out * FEE_DENOMINATOR;
-    let denominator = reserve_out - (amount_out * (FEE_DENOMINATOR - fee as u128));
-    (numerator / denominator) + 1
+/// Mirror router 'getAmountIn' calculation
+///
+/// Returns `u128::MAX` i.e. an unfillable amount, if `amount_out` would drain the reserves
+pub fn get_amount_in(fee: Fee, amount_out: u128, reserve_in: u128, reserve_out: u128) -> u128 {
+    if amount_out >= reserve_out {
+        return u128::MAX;
+    }
+    // x0 = (x.y0) / (y - y0), rounded up
+    let numerator = U512::from(reserve_in) * U512::from(amount_out) * U512::from(FEE_DENOMINATOR);
+    let denominator =
+        U512::from(reserve_out - amount_out) * U512::from(FEE_DENOMINATOR - fee.pips() as u128);
+    let amount_in = U256::try_from(numerator / denominator).unwrap_or(U256::MAX);
+
+    saturating_u128(amount_in).saturating_add(1)
 }
 
 /// `get_amount_out` with float (speed > precision)
-pub fn get_amount_out_f(fee: u16, amount_in: u128, reserve_in: u128, reserve_out: u128) -> f64 {
-    let amount_in_with_fee = (amount_in * (FEE_DENOMINATOR - fee as u128)) as f64;
+pub fn get_amount_out_f(fee: Fee, amount_in: u128, reserve_in: u128, reserve_out: u128) -> f64 {
+    let amount_in_with_fee = amount_in as f64 * (FEE_DENOMINATOR - fee.pips() as u128) as f64;
     // y0 = (y.x0)  / (x + x0)
     let amount_out = ((reserve_out as f64) * amount_in_with_fee)
         / ((reserve_in as f64 * FEE_DENOMINATOR as f64) + amount_in_with_fee);
@@ -51,9 +71,16 @@ pub fn get_amount_out_f(fee: u16, amount_in: u128, reserve_in: u128, reserve_out
 /// ```
 pub fn pair_address_for(pair: &Pair, factory: Address, init_code_hash: &[u8; 32]) -> Address {
     let (a, b) = pair.tokens();
-    let token_0 = a.address();
-    let token_1 = b.address();
+    pair_address_for_tokens(a.address(), b.address(), factory, init_code_hash)
+}
 
+/// Calculate the canonical UniswapV2 pair address for the sorted tokens `token_0`/`token_1` and `factory`
+pub fn pair_address_for_tokens(
+    token_0: Address,
+    token_1: Address,
+    factory: Address,
+    init_code_hash: &[u8; 32],
+) -> Address {
     let encoded = encode_packed(&[
         ABIToken::Bytes(vec![0xff]),
         ABIToken::Address(factory),
@@ -96,7 +123,7 @@ mod test {
         let expected = Address::from(hex!("84652bb2539513BAf36e225c930Fdd8eaa63CE27"));
         assert_eq!(
             pair_address_for(
-                &Pair::new(Token::WETH, Token::USDC, 0, ExchangeId::Camelot),
+                &Pair::new(Token::WETH, Token::USDC, Fee::ZERO, ExchangeId::Camelot),
                 CAMELOT_FACTORY.into(),
                 &CAMELOT_INIT_CODE_HASH
             ),
@@ -104,7 +131,12 @@ mod test {
         );
         assert_eq!(
             pair_address_for(
-                &Pair::new(Token::WETH, Token::USDC, 500, ExchangeId::Camelot),
+                &Pair::new(
+                    Token::WETH,
+                    Token::USDC,
+                    Fee::from_units(500),
+                    ExchangeId::Camelot
+                ),
                 CAMELOT_FACTORY.into(),
                 &CAMELOT_INIT_CODE_HASH
             ),
@@ -116,7 +148,7 @@ mod test {
     fn get_amount_out_contract() {
         assert_eq!(
             get_amount_out(
-                9970,
+                Fee::from_units(300),
                 5000000000000000000,
                 2757113099049556297952,
                 5176991819833
@@ -124,4 +156,68 @@ mod test {
             9343369893
         );
     }
+
+    #[test]
+    fn get_amount_in_contract() {
+        // sushi WETH/USDC, sell WETH for exactly 9343.369893 USDC
+        assert_eq!(
+            get_amount_in(
+                Fee::from_units(300),
+                9343369893,
+                2757113099049556297952,
+                5176991819833
+            ),
+            4999999999670943150
+        );
+        // sell USDC for exactly 1 WETH
+        assert_eq!(
+            get_amount_in(
+                Fee::from_units(300),
+                1000000000000000000,
+                5176991819833,
+                2757113099049556297952
+            ),
+            1884019031
+        );
+        // the amount in covers the amount out
+        assert_eq!(
+            get_amount_out(
+                Fee::from_units(300),
+                4999999999670943150,
+                2757113099049556297952,
+                5176991819833
+            ),
+            9343369893
+        );
+    }
+
+    #[test]
+    fn get_amount_extremes() {
+        // amount in * fee overflows u128
+        assert_eq!(
+            get_amount_out(Fee::from_units(300), u128::MAX, u128::MAX, u128::MAX),
+            169885588292526613957428384381308416034
+        );
+        assert_eq!(
+            get_amount_out(Fee::from_units(300), u128::MAX, 1, 1_000),
+            999
+        );
+        assert_eq!(get_amount_out(Fee::from_units(300), 0, 0, 1_000), 0);
+        assert!(
+            get_amount_out_f(Fee::from_units(300), u128::MAX, u128::MAX, u128::MAX).is_finite()
+        );
+        // draining the pool is unfillable
+        assert_eq!(
+            get_amount_in(Fee::from_units(300), 1_000, 1_000, 1_000),
+            u128::MAX
+        );
+        assert_eq!(
+            get_amount_in(Fee::from_units(300), 2_000, 1_000, 1_000),
+            u128::MAX
+        );
+        assert_eq!(
+            get_amount_in(Fee::from_units(300), u128::MAX - 1, u128::MAX, u128::MAX),
+            u128::MAX
+        );
+    }
 }
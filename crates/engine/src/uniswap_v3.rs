@@ -6,24 +6,54 @@ use ethers::{
     utils::keccak256,
 };
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 
-use crate::types::{Address, Pair, U256};
+use crate::{
+    types::{Address, Pair, U256},
+    util::saturating_u128,
+};
 
 /// 2 ** 96
 pub static X96: Lazy<U256> = Lazy::new(|| U256::from(2_u128.pow(96_u32)));
-pub static Q96: Lazy<U256> = Lazy::new(|| U256::from(96));
 static X96_F: Lazy<f64> = Lazy::new(|| 2_f64.powi(96));
 
+/// Narrow `x` to U256, saturating at `U256::MAX`
+fn saturating_u256(x: U512) -> U256 {
+    U256::try_from(x).unwrap_or(U256::MAX)
+}
+
+/// `a / b` rounding up as `UnsafeMath.divRoundingUp`
+fn div_rounding_up(a: U512, b: U512) -> U512 {
+    let (quotient, remainder) = a.div_mod(b);
+    if remainder.is_zero() {
+        quotient
+    } else {
+        quotient + U512::one()
+    }
+}
+
+/// `a * b / denominator` rounding up as `FullMath.mulDivRoundingUp`
+fn mul_div_rounding_up(a: U512, b: U512, denominator: U512) -> U512 {
+    div_rounding_up(a * b, denominator)
+}
+
+/// The next √P.96 selling `amount_0_in`, rounding up as `SqrtPriceMath.getNextSqrtPriceFromAmount0RoundingUp`
 pub fn get_next_sqrt_price_amount_0(
     liquidity: &U256,
     current_sqrt_p_x96: &U256,
     amount_0_in: &U256,
 ) -> U256 {
-    let numerator_1 = liquidity << *Q96;
-    let product = amount_0_in * current_sqrt_p_x96;
-    let denominator = U512::from(numerator_1 + product);
-    U256::try_from((U512::from(numerator_1) * U512::from(current_sqrt_p_x96)) / denominator)
-        .expect("no overflow")
+    let numerator_1 = U512::from(liquidity) << 96;
+    let denominator = numerator_1 + U512::from(amount_0_in) * U512::from(current_sqrt_p_x96);
+    if denominator.is_zero() {
+        return *current_sqrt_p_x96;
+    }
+    // the price only moves down, always fits
+    saturating_u256(mul_div_rounding_up(
+        numerator_1,
+        U512::from(current_sqrt_p_x96),
+        denominator,
+    ))
 }
 
 pub fn get_next_sqrt_price_amount_0_f(
@@ -37,13 +67,19 @@ pub fn get_next_sqrt_price_amount_0_f(
     (numerator_1 * current_sqrt_p_x96) / denominator
 }
 
+/// The next √P.96 selling `amount_1_in`, rounding down as `SqrtPriceMath.getNextSqrtPriceFromAmount1RoundingDown`
+///
+/// Saturates at `U256::MAX` if `liquidity` can't absorb it
 pub fn get_next_sqrt_price_amount_1(
     liquidity: &U256,
     current_sqrt_p_x96: &U256,
     amount_1_in: &U256,
 ) -> U256 {
-    let quotient = (amount_1_in << *Q96) / liquidity;
-    current_sqrt_p_x96 + quotient
+    if liquidity.is_zero() {
+        return U256::MAX;
+    }
+    let quotient = saturating_u256((U512::from(amount_1_in) << 96) / U512::from(liquidity));
+    current_sqrt_p_x96.saturating_add(quotient)
 }
 
 pub fn get_next_sqrt_price_amount_1_f(
@@ -55,30 +91,43 @@ pub fn get_next_sqrt_price_amount_1_f(
     current_sqrt_p_x96 + quotient
 }
 
+/// The next √P.96 buying `amount_out` of token 0, rounding up
+///
+/// Returns `U256::MAX` if `amount_out` exceeds the virtual reserves i.e. the swap is unfillable
 pub fn get_next_sqrt_price_amount_0_output(
     liquidity: &U256,
     current_sqrt_p_x96: &U256,
     amount_out: &U256,
 ) -> U256 {
-    let numerator_1 = liquidity << *Q96;
-    let product = amount_out * current_sqrt_p_x96;
-    let denominator = numerator_1 - product;
+    let numerator_1 = U512::from(liquidity) << 96;
+    let product = U512::from(amount_out) * U512::from(current_sqrt_p_x96);
+    if product >= numerator_1 {
+        return U256::MAX;
+    }
 
-    ((U512::from(numerator_1) * U512::from(current_sqrt_p_x96)) / denominator)
-        .try_into()
-        .expect("fits 256")
+    saturating_u256(mul_div_rounding_up(
+        numerator_1,
+        U512::from(current_sqrt_p_x96),
+        numerator_1 - product,
+    ))
 }
 
+/// The next √P.96 buying `amount_out` of token 1, rounding down
+///
+/// Returns zero if `amount_out` exceeds the virtual reserves i.e. the swap is unfillable
 pub fn get_next_sqrt_price_amount_1_output(
     liquidity: &U256,
     current_sqrt_p_x96: &U256,
     amount_out: &U256,
 ) -> U256 {
-    // assume fits 160bits
-    let quotient: U256 = ((U512::from(amount_out) << *Q96) / liquidity)
-        .try_into()
-        .expect("fits 256");
-    current_sqrt_p_x96 - quotient
+    if liquidity.is_zero() {
+        return U256::zero();
+    }
+    let quotient = saturating_u256(div_rounding_up(
+        U512::from(amount_out) << 96,
+        U512::from(liquidity),
+    ));
+    current_sqrt_p_x96.saturating_sub(quotient)
 }
 
 /// Get the amount0 delta between two prices
@@ -96,35 +145,58 @@ pub fn get_amount_0_delta_f(liquidity: f64, sqrt_ratio_aX96: f64, sqrt_ratio_bX9
 }
 
 /// Get the amount0 delta between two prices
+///
+/// Amounts in are `round_up` and amounts out are rounded down as in the contracts.
+/// Saturates at `U256::MAX` e.g. moving the price to zero
 pub fn get_amount_0_delta(
     liquidity: &U256,
     sqrt_ratio_aX96: &U256,
     sqrt_ratio_bX96: &U256,
+    round_up: bool,
 ) -> U256 {
-    let numerator_1 = liquidity << *Q96;
     let (sqrt_ratio_aX96, sqrt_ratio_bX96) = if sqrt_ratio_aX96 > sqrt_ratio_bX96 {
         (sqrt_ratio_bX96, sqrt_ratio_aX96)
     } else {
         (sqrt_ratio_aX96, sqrt_ratio_bX96)
     };
-    let numerator_2 = sqrt_ratio_bX96 - sqrt_ratio_aX96;
+    if sqrt_ratio_aX96 == sqrt_ratio_bX96 || liquidity.is_zero() {
+        return U256::zero();
+    }
+    if sqrt_ratio_aX96.is_zero() {
+        return U256::MAX;
+    }
+    let numerator_1 = U512::from(liquidity) << 96;
+    let numerator_2 = U512::from(sqrt_ratio_bX96 - sqrt_ratio_aX96);
+    let (sqrt_ratio_aX96, sqrt_ratio_bX96) =
+        (U512::from(sqrt_ratio_aX96), U512::from(sqrt_ratio_bX96));
 
-    ((U512::from(numerator_1) * U512::from(numerator_2) / sqrt_ratio_bX96) / sqrt_ratio_aX96)
-        .try_into()
-        .expect("fits u256")
+    saturating_u256(if round_up {
+        div_rounding_up(
+            mul_div_rounding_up(numerator_1, numerator_2, sqrt_ratio_bX96),
+            sqrt_ratio_aX96,
+        )
+    } else {
+        (numerator_1 * numerator_2 / sqrt_ratio_bX96) / sqrt_ratio_aX96
+    })
 }
 
 /// Get the amount1 delta between two prices
+///
+/// Amounts in are `round_up` and amounts out are rounded down as in the contracts
 /// https://github.com/Uniswap/v3-core/blob/fc2107bd5709cdee6742d5164c1eb998566bcb75/contracts/libraries/SqrtPriceMath.sol#L182
 pub fn get_amount_1_delta(
     liquidity: &U256,
     sqrt_ratio_aX96: &U256,
     sqrt_ratio_bX96: &U256,
+    round_up: bool,
 ) -> U256 {
-    let delta_sqrt_p = sqrt_ratio_aX96.abs_diff(*sqrt_ratio_bX96);
+    let delta_sqrt_p = U512::from(sqrt_ratio_aX96.abs_diff(*sqrt_ratio_bX96));
 
-    U256::try_from((U512::from(liquidity) * U512::from(delta_sqrt_p)) / U512::from(*X96))
-        .expect("fits u256")
+    saturating_u256(if round_up {
+        mul_div_rounding_up(U512::from(liquidity), delta_sqrt_p, U512::one() << 96)
+    } else {
+        (U512::from(liquidity) * delta_sqrt_p) >> 96
+    })
 }
 
 /// Get the amount1 delta between two prices
@@ -133,12 +205,19 @@ pub fn get_amount_1_delta_f(liquidity: f64, sqrt_ratio_aX96: f64, sqrt_ratio_bX9
     (liquidity * (sqrt_ratio_bX96 - sqrt_ratio_aX96).abs()) / *X96_F
 }
 
+/// `amount` less the pool fee of `fee_pips`
+fn sub_fee(amount: U256, fee_pips: u32) -> U256 {
+    saturating_u256(U512::from(amount) * U512::from(1_000_000 - fee_pips) / U512::from(1_000_000))
+}
+
 /// Get the amount out given some amount in
 ///
 /// - `current_sqrt_p_x96` The √P.96
 /// - `liquidity` The liquidity value
 /// - `amount_in` the amount of tokens to input
 ///
+/// Intermediates are computed in U512 so extreme prices and liquidity don't overflow, the amount out saturates at `u128::MAX`
+///
 /// Returns the amount of tokens output
 pub fn get_amount_out(
     amount_in: u128,
@@ -148,21 +227,30 @@ pub fn get_amount_out(
     zero_for_one: bool,
 ) -> (U256, u128) {
     // calculate the expected price shift then return the amount out (i.e. price target is set exactly to required price shift)
-    let amount_in_less_fee =
-        U256::from(amount_in * (1_000_000_u32 - fee_pips) as u128) / U256::from(1_000_000_u128);
+    let amount_in_less_fee = sub_fee(U256::from(amount_in), fee_pips);
     if zero_for_one {
         let next_sqrt_p_x96 =
             get_next_sqrt_price_amount_0(liquidity, current_sqrt_p_x96, &amount_in_less_fee);
         (
             next_sqrt_p_x96,
-            get_amount_1_delta(liquidity, &next_sqrt_p_x96, current_sqrt_p_x96).as_u128(), // TODO needs round up
+            saturating_u128(get_amount_1_delta(
+                liquidity,
+                &next_sqrt_p_x96,
+                current_sqrt_p_x96,
+                false,
+            )),
         )
     } else {
         let next_sqrt_p_x96 =
             get_next_sqrt_price_amount_1(liquidity, current_sqrt_p_x96, &amount_in_less_fee);
         (
             next_sqrt_p_x96,
-            get_amount_0_delta(liquidity, current_sqrt_p_x96, &next_sqrt_p_x96).as_u128(), // TODO: needs round up
+            saturating_u128(get_amount_0_delta(
+                liquidity,
+                current_sqrt_p_x96,
+                &next_sqrt_p_x96,
+                false,
+            )),
         )
     }
 }
@@ -195,6 +283,8 @@ pub fn get_amount_out_f(
 /// - `liquidity` The liquidity value
 /// - `amount_out` the amount of tokens to output
 ///
+/// The amount in saturates at `u128::MAX` if `amount_out` exceeds the pool's virtual reserves
+///
 /// Returns the amount of tokens to input and the new price
 pub fn get_amount_in(
     amount_out: u128,
@@ -212,10 +302,10 @@ pub fn get_amount_in(
             get_next_sqrt_price_amount_1_output(liquidity, current_sqrt_p_x96, amount_out);
         (
             next_sqrt_p_x96,
-            ((get_amount_0_delta(liquidity, &next_sqrt_p_x96, current_sqrt_p_x96)
-                * U256::from(1_000_000 - fee_pips))
-                / U256::from(1_000_000))
-            .as_u128(),
+            saturating_u128(add_fee(
+                get_amount_0_delta(liquidity, &next_sqrt_p_x96, current_sqrt_p_x96, true),
+                fee_pips,
+            )),
         )
     } else {
         // expect the order filled within one tick
@@ -223,19 +313,274 @@ pub fn get_amount_in(
             get_next_sqrt_price_amount_0_output(liquidity, current_sqrt_p_x96, amount_out);
         (
             next_sqrt_p_x96,
-            ((get_amount_1_delta(liquidity, current_sqrt_p_x96, &next_sqrt_p_x96)
-                * U256::from(1_000_000 - fee_pips))
-                / U256::from(1_000_000))
-            .as_u128(),
+            saturating_u128(add_fee(
+                get_amount_1_delta(liquidity, current_sqrt_p_x96, &next_sqrt_p_x96, true),
+                fee_pips,
+            )),
         )
     }
 }
 
+/// Min. tick supported by UniswapV3
+pub const MIN_TICK: i32 = -887272;
+/// Max. tick supported by UniswapV3
+pub const MAX_TICK: i32 = 887272;
+
+/// Calculate √P.96 at `tick` i.e. √(1.0001 ** tick) * 2 ** 96
+/// https://github.com/Uniswap/v3-core/blob/fc2107bd5709cdee6742d5164c1eb998566bcb75/contracts/libraries/TickMath.sol#L23
+pub fn get_sqrt_ratio_at_tick(tick: i32) -> U256 {
+    assert!((MIN_TICK..=MAX_TICK).contains(&tick), "tick out of range");
+    let abs_tick = tick.unsigned_abs();
+
+    let mut ratio = if abs_tick & 0x1 != 0 {
+        U256::from(0xfffcb933bd6fad37aa2d162d1a594001_u128)
+    } else {
+        U256::one() << 128
+    };
+    for (bit, magic) in [
+        (0x2, 0xfff97272373d413259a46990580e213a_u128),
+        (0x4, 0xfff2e50f5f656932ef12357cf3c7fdcc),
+        (0x8, 0xffe5caca7e10e4e61c3624eaa0941cd0),
+        (0x10, 0xffcb9843d60f6159c9db58835c926644),
+        (0x20, 0xff973b41fa98c081472e6896dfb254c0),
+        (0x40, 0xff2ea16466c96a3843ec78b326b52861),
+        (0x80, 0xfe5dee046a99a2a811c461f1969c3053),
+        (0x100, 0xfcbe86c7900a88aedcffc83b479aa3a4),
+        (0x200, 0xf987a7253ac413176f2b074cf7815e54),
+        (0x400, 0xf3392b0822b70005940c7a398e4b70f3),
+        (0x800, 0xe7159475a2c29b7443b29c7fa6e889d9),
+        (0x1000, 0xd097f3bdfd2022b8845ad8f792aa5825),
+        (0x2000, 0xa9f746462d870fdf8a65dc1f90e061e5),
+        (0x4000, 0x70d869a156d2a1b890bb3df62baf32f7),
+        (0x8000, 0x31be135f97d08fd981231505542fcfa6),
+        (0x10000, 0x9aa508b5b7a84e1c677de54f3e99bc9),
+        (0x20000, 0x5d6af8dedb81196699c329225ee604),
+        (0x40000, 0x2216e584f5fa1ea926041bedfe98),
+        (0x80000, 0x48a170391f7dc42444e8fa2),
+    ] {
+        if abs_tick & bit != 0 {
+            ratio = (ratio * U256::from(magic)) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+
+    // Q128.128 => Q64.96, rounding up
+    let round_up = !(ratio & U256::from(u32::MAX)).is_zero();
+    (ratio >> 32) + U256::from(round_up as u8)
+}
+
+/// Initialized ticks of a UniswapV3 pool
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct TickLiquidity {
+    /// (√P.96, liquidity net) of each initialized tick, ordered by price
+    ticks: Vec<(U256, i128)>,
+}
+
+impl TickLiquidity {
+    /// Build from a list of initialized (tick, liquidity net)
+    pub fn new(ticks: impl IntoIterator<Item = (i32, i128)>) -> Self {
+        let mut ticks: Vec<(U256, i128)> = ticks
+            .into_iter()
+            .map(|(tick, liquidity_net)| (get_sqrt_ratio_at_tick(tick), liquidity_net))
+            .collect();
+        ticks.sort_unstable_by_key(|(sqrt_p_x96, _)| *sqrt_p_x96);
+        Self { ticks }
+    }
+    /// True if no ticks are known
+    pub fn is_empty(&self) -> bool {
+        self.ticks.is_empty()
+    }
+    /// The next initialized tick crossed when moving the price from `sqrt_p_x96`
+    /// i.e. the closest tick below when `zero_for_one` and the closest tick above otherwise
+    fn next(&self, sqrt_p_x96: &U256, zero_for_one: bool) -> Option<(U256, i128)> {
+        if zero_for_one {
+            let idx = self.ticks.partition_point(|(p, _)| p < sqrt_p_x96);
+            idx.checked_sub(1).map(|idx| self.ticks[idx])
+        } else {
+            let idx = self.ticks.partition_point(|(p, _)| p <= sqrt_p_x96);
+            self.ticks.get(idx).copied()
+        }
+    }
+}
+
+/// Apply a tick's `liquidity_net` to `liquidity` when crossing it in the `zero_for_one` direction
+fn cross_tick(liquidity: U256, liquidity_net: i128, zero_for_one: bool) -> U256 {
+    // liquidity net is added moving up and subtracted moving down
+    let liquidity_net = if zero_for_one {
+        liquidity_net.saturating_neg()
+    } else {
+        liquidity_net
+    };
+    if liquidity_net < 0 {
+        liquidity.saturating_sub(U256::from(liquidity_net.unsigned_abs()))
+    } else {
+        liquidity.saturating_add(U256::from(liquidity_net as u128))
+    }
+}
+
+/// Gross up `amount` (less fees) to include the fee, rounding up
+fn add_fee(amount: U256, fee_pips: u32) -> U256 {
+    let fee_complement = U512::from(1_000_000 - fee_pips);
+    saturating_u256(
+        (U512::from(amount) * U512::from(1_000_000_u32) + fee_complement - U512::one())
+            / fee_complement,
+    )
+}
+
+/// Get the amount out given some amount in, crossing any initialized `ticks` on the way
+///
+/// - `current_sqrt_p_x96` The √P.96
+/// - `liquidity` The in range liquidity value
+/// - `amount_in` the amount of tokens to input
+/// - `ticks` the pool's known initialized ticks
+///
+/// Fees are charged per step as in `SwapMath.computeSwapStep`.
+/// Beyond the known ticks the swap continues with the last in range liquidity.
+///
+/// Returns the new √P.96, the new in range liquidity, and the amount of tokens output
+pub fn get_amount_out_crossing(
+    amount_in: u128,
+    current_sqrt_p_x96: &U256,
+    liquidity: &U256,
+    fee_pips: u32,
+    zero_for_one: bool,
+    ticks: &TickLiquidity,
+) -> (U256, U256, u128) {
+    let mut sqrt_p_x96 = *current_sqrt_p_x96;
+    let mut liquidity = *liquidity;
+    let mut remaining = U256::from(amount_in);
+    let mut amount_out = U256::zero();
+
+    while !remaining.is_zero() {
+        let remaining_less_fee = sub_fee(remaining, fee_pips);
+        match ticks.next(&sqrt_p_x96, zero_for_one) {
+            Some((target_sqrt_p_x96, liquidity_net)) => {
+                // amount required to move the price to the next tick
+                let step_in = if liquidity.is_zero() {
+                    U256::zero()
+                } else if zero_for_one {
+                    get_amount_0_delta(&liquidity, &target_sqrt_p_x96, &sqrt_p_x96, true)
+                } else {
+                    get_amount_1_delta(&liquidity, &sqrt_p_x96, &target_sqrt_p_x96, true)
+                };
+                if step_in <= remaining_less_fee {
+                    if !liquidity.is_zero() {
+                        amount_out = amount_out.saturating_add(if zero_for_one {
+                            get_amount_1_delta(&liquidity, &target_sqrt_p_x96, &sqrt_p_x96, false)
+                        } else {
+                            get_amount_0_delta(&liquidity, &sqrt_p_x96, &target_sqrt_p_x96, false)
+                        });
+                    }
+                    remaining = remaining.saturating_sub(add_fee(step_in, fee_pips));
+                    sqrt_p_x96 = target_sqrt_p_x96;
+                    liquidity = cross_tick(liquidity, liquidity_net, zero_for_one);
+                    continue;
+                }
+            }
+            // no liquidity left to fill the order
+            None if liquidity.is_zero() => break,
+            None => (),
+        }
+
+        // the remaining amount is filled within the current tick range
+        let next_sqrt_p_x96 = if zero_for_one {
+            get_next_sqrt_price_amount_0(&liquidity, &sqrt_p_x96, &remaining_less_fee)
+        } else {
+            get_next_sqrt_price_amount_1(&liquidity, &sqrt_p_x96, &remaining_less_fee)
+        };
+        amount_out = amount_out.saturating_add(if zero_for_one {
+            get_amount_1_delta(&liquidity, &next_sqrt_p_x96, &sqrt_p_x96, false)
+        } else {
+            get_amount_0_delta(&liquidity, &sqrt_p_x96, &next_sqrt_p_x96, false)
+        });
+        sqrt_p_x96 = next_sqrt_p_x96;
+        break;
+    }
+
+    (sqrt_p_x96, liquidity, saturating_u128(amount_out))
+}
+
+/// Get the amount in given some amount out, crossing any initialized `ticks` on the way
+///
+/// - `current_sqrt_p_x96` The √P.96
+/// - `liquidity` The in range liquidity value
+/// - `amount_out` the amount of tokens to output
+/// - `ticks` the pool's known initialized ticks
+///
+/// Fees are charged per step as in `SwapMath.computeSwapStep`.
+/// Beyond the known ticks the swap continues with the last in range liquidity.
+///
+/// Returns the new √P.96, the new in range liquidity, and the amount of tokens to input
+pub fn get_amount_in_crossing(
+    amount_out: u128,
+    current_sqrt_p_x96: &U256,
+    liquidity: &U256,
+    fee_pips: u32,
+    zero_for_one: bool,
+    ticks: &TickLiquidity,
+) -> (U256, U256, u128) {
+    let mut sqrt_p_x96 = *current_sqrt_p_x96;
+    let mut liquidity = *liquidity;
+    let mut remaining = U256::from(amount_out);
+    let mut amount_in = U256::zero();
+
+    while !remaining.is_zero() {
+        match ticks.next(&sqrt_p_x96, zero_for_one) {
+            Some((target_sqrt_p_x96, liquidity_net)) => {
+                // amount output moving the price to the next tick
+                let step_out = if liquidity.is_zero() {
+                    U256::zero()
+                } else if zero_for_one {
+                    get_amount_1_delta(&liquidity, &target_sqrt_p_x96, &sqrt_p_x96, false)
+                } else {
+                    get_amount_0_delta(&liquidity, &sqrt_p_x96, &target_sqrt_p_x96, false)
+                };
+                if step_out <= remaining {
+                    if !liquidity.is_zero() {
+                        let step_in = if zero_for_one {
+                            get_amount_0_delta(&liquidity, &target_sqrt_p_x96, &sqrt_p_x96, true)
+                        } else {
+                            get_amount_1_delta(&liquidity, &sqrt_p_x96, &target_sqrt_p_x96, true)
+                        };
+                        amount_in = amount_in.saturating_add(add_fee(step_in, fee_pips));
+                    }
+                    remaining -= step_out;
+                    sqrt_p_x96 = target_sqrt_p_x96;
+                    liquidity = cross_tick(liquidity, liquidity_net, zero_for_one);
+                    continue;
+                }
+            }
+            // no liquidity left to fill the order
+            None if liquidity.is_zero() => break,
+            None => (),
+        }
+
+        // the remaining amount is filled within the current tick range
+        let next_sqrt_p_x96 = if zero_for_one {
+            get_next_sqrt_price_amount_1_output(&liquidity, &sqrt_p_x96, &remaining)
+        } else {
+            get_next_sqrt_price_amount_0_output(&liquidity, &sqrt_p_x96, &remaining)
+        };
+        let step_in = if zero_for_one {
+            get_amount_0_delta(&liquidity, &next_sqrt_p_x96, &sqrt_p_x96, true)
+        } else {
+            get_amount_1_delta(&liquidity, &sqrt_p_x96, &next_sqrt_p_x96, true)
+        };
+        amount_in = amount_in.saturating_add(add_fee(step_in, fee_pips));
+        sqrt_p_x96 = next_sqrt_p_x96;
+        break;
+    }
+
+    (sqrt_p_x96, liquidity, saturating_u128(amount_in))
+}
+
 /// Calculate the canonical UniswapV2 pair address for the given `Pair` and `factory`
 pub fn pool_address_from_pair(pair: Pair, factory: Address, init_code_hash: &[u8; 32]) -> Address {
     let token_0 = pair.token0.address();
     let token_1 = pair.token1.address();
-    pool_address_for(token_0, token_1, pair.fee as u32, factory, init_code_hash)
+    pool_address_for(token_0, token_1, pair.fee.pips(), factory, init_code_hash)
 }
 
 /// Calculate the canonical UniswapV3 pair address for the given tokens,fee and `factory`
@@ -286,11 +631,110 @@ pub struct UniswapV3Slot0 {
     pub liquidity: u128,
 }
 
-#[inline(always)]
-pub fn fee_from_path_bytes(buf: &[u8]) -> u32 {
-    // OPTIMIZATION: nothing sensible should ever be longer than 2 ** 16 so we ignore the other bytes
-    // ((unsafe { *buf.get_unchecked(0) } as u32) << 16) +
-    ((unsafe { *buf.get_unchecked(1) } as u32) << 8) + (unsafe { *buf.get_unchecked(2) } as u32)
+/// Bytes of a token address in an encoded path
+const PATH_ADDRESS_SIZE: usize = 20;
+/// Bytes of a pool's fee (uint24) in an encoded path
+const PATH_FEE_SIZE: usize = 3;
+/// Bytes each pool adds to an encoded path, its fee and next token
+const PATH_HOP_SIZE: usize = PATH_FEE_SIZE + PATH_ADDRESS_SIZE;
+
+/// The order of an encoded path's tokens
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathDirection {
+    /// `tokenIn, fee, ..., tokenOut` i.e. exact input swaps
+    Forward,
+    /// `tokenOut, fee, ..., tokenIn` i.e. exact output swaps, the router swaps from the end of the path
+    Reverse,
+}
+
+/// A Uniswap V3 encoded path, `token (20 bytes), fee (3 bytes), token, ..., fee, token`
+///
+/// Each pool's fee sits between its tokens, in either direction. Kyber elastic paths share the encoding
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Path<'a> {
+    buf: &'a [u8],
+    direction: PathDirection,
+}
+
+impl<'a> Path<'a> {
+    /// Parse the path `buf` encoded in `direction`, `None` if it's not one or more whole pools
+    pub fn parse(buf: &'a [u8], direction: PathDirection) -> Option<Self> {
+        let hops_len = buf.len().checked_sub(PATH_ADDRESS_SIZE)?;
+        if hops_len == 0 || hops_len % PATH_HOP_SIZE != 0 {
+            return None;
+        }
+        Some(Self { buf, direction })
+    }
+    /// Encode the path through `tokens` in order, `fees[i]` is the fee of the pool between `tokens[i]` and `tokens[i + 1]`
+    pub fn encode(tokens: &[[u8; 20]], fees: &[u32]) -> Vec<u8> {
+        assert_eq!(tokens.len(), fees.len() + 1, "a fee per pool");
+        let mut buf = Vec::with_capacity(PATH_ADDRESS_SIZE + fees.len() * PATH_HOP_SIZE);
+        buf.extend_from_slice(&tokens[0]);
+        for (fee, token) in fees.iter().zip(&tokens[1..]) {
+            buf.extend_from_slice(&fee.to_be_bytes()[1..]);
+            buf.extend_from_slice(token);
+        }
+        buf
+    }
+    pub fn direction(&self) -> PathDirection {
+        self.direction
+    }
+    /// Number of pools on the path
+    pub fn pool_count(&self) -> usize {
+        (self.buf.len() - PATH_ADDRESS_SIZE) / PATH_HOP_SIZE
+    }
+    /// The `idx`th token in encoding order
+    fn token(&self, idx: usize) -> &'a [u8; 20] {
+        let offset = idx * PATH_HOP_SIZE;
+        self.buf[offset..offset + PATH_ADDRESS_SIZE]
+            .try_into()
+            .expect("20 bytes")
+    }
+    /// The `idx`th pool in encoding order, (token, fee, next token)
+    fn pool(&self, idx: usize) -> (&'a [u8; 20], u32, &'a [u8; 20]) {
+        let offset = idx * PATH_HOP_SIZE + PATH_ADDRESS_SIZE;
+        let fee = &self.buf[offset..offset + PATH_FEE_SIZE];
+        (
+            self.token(idx),
+            u32::from_be_bytes([0, fee[0], fee[1], fee[2]]),
+            self.token(idx + 1),
+        )
+    }
+    /// The pools in encoding order, (token, fee, next token)
+    ///
+    /// i.e. (token in, fee, token out) from the first swap of forward paths, (token out, fee, token in) from the last
+    /// swap of reverse paths
+    pub fn pools(self) -> impl DoubleEndedIterator<Item = (&'a [u8; 20], u32, &'a [u8; 20])> {
+        (0..self.pool_count()).map(move |idx| self.pool(idx))
+    }
+    /// The swaps in execution order, (token in, token out, fee)
+    pub fn swaps(self) -> impl Iterator<Item = (&'a [u8; 20], &'a [u8; 20], u32)> {
+        let pool_count = self.pool_count();
+        (0..pool_count).map(move |idx| match self.direction {
+            PathDirection::Forward => {
+                let (token_in, fee, token_out) = self.pool(idx);
+                (token_in, token_out, fee)
+            }
+            PathDirection::Reverse => {
+                let (token_out, fee, token_in) = self.pool(pool_count - 1 - idx);
+                (token_in, token_out, fee)
+            }
+        })
+    }
+    /// The token paid in to the first swap
+    pub fn token_in(&self) -> &'a [u8; 20] {
+        match self.direction {
+            PathDirection::Forward => self.token(0),
+            PathDirection::Reverse => self.token(self.pool_count()),
+        }
+    }
+    /// The token paid out by the last swap
+    pub fn token_out(&self) -> &'a [u8; 20] {
+        match self.direction {
+            PathDirection::Forward => self.token(self.pool_count()),
+            PathDirection::Reverse => self.token(0),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -301,13 +745,78 @@ mod test {
     use super::*;
     use crate::{
         constant::arbitrum::{UNISWAP_V3_FACTORY, UNISWAP_V3_INIT_CODE_HASH},
-        types::{ExchangeId, Pair, Token},
+        types::{ExchangeId, Fee, Pair, Token},
     };
 
+    #[test]
+    fn path_round_trips() {
+        // xorshift, deterministic random paths
+        let mut seed = 0x5eed_u64;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        for _ in 0..1_000 {
+            let pools = 1 + next() as usize % 4;
+            let tokens: Vec<[u8; 20]> = (0..=pools)
+                .map(|_| Address::from_low_u64_be(next()).0)
+                .collect();
+            let fees: Vec<u32> = (0..pools).map(|_| next() as u32 & 0xff_ffff).collect();
+            let swaps: Vec<_> = (0..pools)
+                .map(|idx| (&tokens[idx], &tokens[idx + 1], fees[idx]))
+                .collect();
+
+            let encoded = Path::encode(&tokens, &fees);
+            let path = Path::parse(&encoded, PathDirection::Forward).unwrap();
+            assert_eq!(path.pool_count(), pools);
+            assert_eq!(path.swaps().collect::<Vec<_>>(), swaps);
+            assert_eq!(
+                path.pools()
+                    .map(|(token_in, fee, token_out)| (token_in, token_out, fee))
+                    .collect::<Vec<_>>(),
+                swaps
+            );
+            assert_eq!(path.token_in(), &tokens[0]);
+            assert_eq!(path.token_out(), &tokens[pools]);
+
+            // the same swaps encoded from the output token
+            let reversed_tokens: Vec<_> = tokens.iter().rev().copied().collect();
+            let reversed_fees: Vec<_> = fees.iter().rev().copied().collect();
+            let encoded = Path::encode(&reversed_tokens, &reversed_fees);
+            let path = Path::parse(&encoded, PathDirection::Reverse).unwrap();
+            assert_eq!(path.swaps().collect::<Vec<_>>(), swaps);
+            assert_eq!(
+                path.pools()
+                    .map(|(token_out, fee, token_in)| (token_in, token_out, fee))
+                    .rev()
+                    .collect::<Vec<_>>(),
+                swaps
+            );
+            assert_eq!(path.token_in(), &tokens[0]);
+            assert_eq!(path.token_out(), &tokens[pools]);
+
+            // anything but whole pools is malformed
+            let cut = 1 + next() as usize % (PATH_HOP_SIZE - 1);
+            assert_eq!(
+                Path::parse(&encoded[..encoded.len() - cut], PathDirection::Forward),
+                None
+            );
+        }
+        assert_eq!(Path::parse(&[0_u8; 20], PathDirection::Forward), None);
+        assert_eq!(Path::parse(&[], PathDirection::Reverse), None);
+    }
+
     #[test]
     fn pool_address_for_works() {
         let actual = pool_address_from_pair(
-            Pair::new(Token::WETH, Token::USDC, 100_u16, ExchangeId::Uniswap),
+            Pair::new(
+                Token::WETH,
+                Token::USDC,
+                Fee::from_pips(100),
+                ExchangeId::Uniswap,
+            ),
             Address::from(UNISWAP_V3_FACTORY),
             &UNISWAP_V3_INIT_CODE_HASH,
         );
@@ -317,7 +826,12 @@ mod test {
         );
 
         let actual = pool_address_from_pair(
-            Pair::new(Token::USDC, Token::WETH, 500, ExchangeId::Uniswap),
+            Pair::new(
+                Token::USDC,
+                Token::WETH,
+                Fee::from_pips(500),
+                ExchangeId::Uniswap,
+            ),
             Address::from(UNISWAP_V3_FACTORY),
             &UNISWAP_V3_INIT_CODE_HASH,
         );
@@ -350,6 +864,51 @@ mod test {
         );
     }
 
+    #[test]
+    fn rounding_matches_contracts() {
+        // amounts in round up, amounts out round down
+        let liquidity = U256::from(10_u128.pow(18));
+        let sqrt_p_x96 = get_sqrt_ratio_at_tick(60);
+        assert_eq!(
+            get_amount_0_delta(&liquidity, &X96, &sqrt_p_x96, true),
+            U256::from(2995354955910781_u128)
+        );
+        assert_eq!(
+            get_amount_0_delta(&liquidity, &X96, &sqrt_p_x96, false),
+            U256::from(2995354955910780_u128)
+        );
+        assert_eq!(
+            get_amount_1_delta(&liquidity, &X96, &sqrt_p_x96, true),
+            U256::from(3004354062741926_u128)
+        );
+        assert_eq!(
+            get_amount_1_delta(&liquidity, &X96, &sqrt_p_x96, false),
+            U256::from(3004354062741925_u128)
+        );
+
+        // exact input and exact output agree, the exact output amount in is the least which fills it
+        let sqrt_p_x96 = U256::from(2910392625228200618462908431436_u128);
+        let liquidity = U256::from(3055895843484221589591460_u128);
+        let amount_0_in = 2 * 10_u128.pow(18);
+        let amount_1_out = get_amount_out(amount_0_in, &sqrt_p_x96, &liquidity, 500, true).1;
+        assert_eq!(
+            get_amount_in(amount_1_out, &sqrt_p_x96, &liquidity, 500, true).1,
+            amount_0_in
+        );
+        let amount_0_out = get_amount_out(10_u128.pow(15), &sqrt_p_x96, &liquidity, 500, false).1;
+        assert_eq!(amount_0_out, 740693960415);
+        let amount_1_in = get_amount_in(amount_0_out, &sqrt_p_x96, &liquidity, 500, false).1;
+        assert_eq!(amount_1_in, 999999999999293);
+        assert_eq!(
+            get_amount_out(amount_1_in, &sqrt_p_x96, &liquidity, 500, false).1,
+            amount_0_out
+        );
+        assert_eq!(
+            get_amount_out(amount_1_in - 1, &sqrt_p_x96, &liquidity, 500, false).1,
+            amount_0_out - 1
+        );
+    }
+
     #[test]
     fn get_amount_1_delta_overflow() {
         let current_sqrt_p_x96 = U256::from(3379669370077374717864357_u128);
@@ -366,4 +925,105 @@ mod test {
             zero_for_one,
         );
     }
+
+    #[test]
+    fn get_amount_extremes() {
+        for sqrt_p_x96 in [
+            get_sqrt_ratio_at_tick(MIN_TICK),
+            *X96,
+            get_sqrt_ratio_at_tick(MAX_TICK),
+        ] {
+            for zero_for_one in [true, false] {
+                // amount in * fee overflows u128, amount * √P.96 overflows U256
+                let liquidity = U256::from(u128::MAX);
+                let (next_sqrt_p_x96, _) =
+                    get_amount_out(u128::MAX, &sqrt_p_x96, &liquidity, 3_000, zero_for_one);
+                assert_eq!(next_sqrt_p_x96 < sqrt_p_x96, zero_for_one);
+                get_amount_in(u128::MAX, &sqrt_p_x96, &liquidity, 3_000, zero_for_one);
+                get_amount_out(u128::MAX, &sqrt_p_x96, &U256::one(), 3_000, zero_for_one);
+                get_amount_out_crossing(
+                    u128::MAX,
+                    &sqrt_p_x96,
+                    &liquidity,
+                    3_000,
+                    zero_for_one,
+                    &TickLiquidity::new([(-60, i128::MAX), (60, i128::MIN)]),
+                );
+            }
+        }
+
+        // buying more than the virtual reserves is unfillable
+        let liquidity = U256::from(10_u128.pow(18));
+        assert_eq!(
+            get_amount_in(10_u128.pow(19), &X96, &liquidity, 500, true).1,
+            u128::MAX
+        );
+        assert_eq!(
+            get_amount_in(10_u128.pow(19), &X96, &liquidity, 500, false).1,
+            u128::MAX
+        );
+        // no liquidity
+        assert_eq!(get_amount_out(1_000, &X96, &U256::zero(), 500, false).1, 0);
+        assert_eq!(get_amount_out(1_000, &X96, &U256::zero(), 500, true).1, 0);
+    }
+
+    #[test]
+    fn get_sqrt_ratio_at_tick_works() {
+        assert_eq!(get_sqrt_ratio_at_tick(0), *X96);
+        assert_eq!(get_sqrt_ratio_at_tick(MIN_TICK), U256::from(4295128739_u64));
+        assert_eq!(
+            get_sqrt_ratio_at_tick(MAX_TICK),
+            U256::from_dec_str("1461446703485210103287273052203988822378723970342").unwrap()
+        );
+        assert!(get_sqrt_ratio_at_tick(-1) < *X96);
+        assert!(get_sqrt_ratio_at_tick(1) > *X96);
+    }
+
+    #[test]
+    fn get_amount_crossing_ticks() {
+        let sqrt_p_x96 = *X96;
+        let liquidity = U256::from(10_u128.pow(18));
+        let amount_in = 10_u128.pow(17);
+
+        // no known ticks, matches the single range math
+        let (next_sqrt_p_x96, next_liquidity, amount_out) = get_amount_out_crossing(
+            amount_in,
+            &sqrt_p_x96,
+            &liquidity,
+            500,
+            true,
+            &TickLiquidity::default(),
+        );
+        assert_eq!(
+            (next_sqrt_p_x96, amount_out),
+            get_amount_out(amount_in, &sqrt_p_x96, &liquidity, 500, true)
+        );
+        assert_eq!(next_liquidity, liquidity);
+
+        // liquidity doubles below tick -60
+        let ticks = TickLiquidity::new([(-60, -(10_i128.pow(18))), (60, 10_i128.pow(18))]);
+        let (next_sqrt_p_x96, next_liquidity, crossing_amount_out) =
+            get_amount_out_crossing(amount_in, &sqrt_p_x96, &liquidity, 500, true, &ticks);
+        assert!(next_sqrt_p_x96 < get_sqrt_ratio_at_tick(-60));
+        assert_eq!(next_liquidity, liquidity * 2);
+        assert!(crossing_amount_out > amount_out);
+
+        // exact output crosses the same tick back
+        let (in_sqrt_p_x96, in_liquidity, crossing_amount_in) = get_amount_in_crossing(
+            crossing_amount_out,
+            &sqrt_p_x96,
+            &liquidity,
+            500,
+            true,
+            &ticks,
+        );
+        assert_eq!(in_liquidity, liquidity * 2);
+        assert!(in_sqrt_p_x96 < get_sqrt_ratio_at_tick(-60));
+        assert!(crossing_amount_in.abs_diff(amount_in) <= 2);
+
+        // swapping up crosses out of range liquidity
+        let (_, next_liquidity, _) =
+            get_amount_out_crossing(amount_in, &sqrt_p_x96, &liquidity, 500, false, &ticks);
+        assert_eq!(next_liquidity, liquidity * 2);
+    }
 }
@@ -4,7 +4,23 @@ use std::{
     mem::transmute,
 };
 
-use ethers::types::H160;
+use ethers::types::{H160, U256};
+
+/// Narrow `x` to a u128, saturating at `u128::MAX`
+pub fn saturating_u128(x: U256) -> u128 {
+    if x.bits() > 128 {
+        u128::MAX
+    } else {
+        x.as_u128()
+    }
+}
+
+/// Convert `x` to the nearest f64, precision is lost beyond 53 bits
+pub fn u256_to_f64(x: &U256) -> f64 {
+    x.0.iter().rev().fold(0_f64, |acc, limb| {
+        acc * 18_446_744_073_709_551_616_f64 + *limb as f64
+    })
+}
 
 #[derive(Eq, PartialEq)]
 pub struct AddressForHash([u8; 20]);
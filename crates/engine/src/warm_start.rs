@@ -0,0 +1,266 @@
+//! Price graph warm start
+//!
+//! The latest price graph is saved on shutdown and reloaded on restart, if it is fresh enough it prices the
+//! first batch rather than waiting on a full price sync
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use ethers::types::Address;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    price_graph::Edge,
+    registry::MAX_TOKENS,
+    types::{Fee, Token},
+    uniswap_v3::TickLiquidity,
+    PriceGraph,
+};
+
+/// Default max. blocks a saved price graph may be behind the feed to be used
+pub const DEFAULT_MAX_STALE_BLOCKS: u64 = 20;
+
+/// A saved price graph, edges are keyed by token Id
+#[derive(Debug, Deserialize, Serialize)]
+struct Snapshot {
+    /// Block number the graph was built for
+    block_number: u64,
+    /// Addresses of the registered tokens (by Id) when saved
+    tokens: Vec<Address>,
+    /// (token in, token out, edge) of every known edge
+    edges: Vec<(u8, u8, Edge)>,
+    /// (token0, token1, fee, ticks) of Uniswap V3 pools
+    ticks: Vec<(u8, u8, Fee, TickLiquidity)>,
+}
+
+/// Registered token addresses ordered by Id
+fn registry_tokens() -> Vec<Address> {
+    let mut tokens: Vec<(Token, Address)> = config::active()
+        .registry()
+        .tokens()
+        .map(|(token, info)| (token, info.address))
+        .collect();
+    tokens.sort_unstable_by_key(|(token, _)| *token);
+    tokens.into_iter().map(|(_, address)| address).collect()
+}
+
+impl Snapshot {
+    fn of(graph: &PriceGraph) -> Self {
+        Self {
+            block_number: graph.block_number(),
+            tokens: registry_tokens(),
+            edges: graph
+                .edges()
+                .map(|(token_in, token_out, edge)| (token_in.id(), token_out.id(), *edge))
+                .collect(),
+            ticks: graph
+                .pool_ticks()
+                .map(|(token0, token1, fee, ticks)| (token0.id(), token1.id(), fee, ticks.clone()))
+                .collect(),
+        }
+    }
+    /// Rebuild the price graph, the token registry must be unchanged since it was saved
+    fn into_graph(self) -> io::Result<PriceGraph> {
+        if self.tokens != registry_tokens() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "token registry changed",
+            ));
+        }
+        // token Ids may be sparse, check each is registered
+        let registry = config::active().registry();
+        let token = |id: u8| {
+            if (id as usize) < MAX_TOKENS && registry.get(Token::new(id)).is_some() {
+                Ok(Token::new(id))
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown token: {id}"),
+                ))
+            }
+        };
+        let mut graph = PriceGraph::empty();
+        graph.set_block_number(self.block_number);
+        for (token_in, token_out, edge) in self.edges {
+            graph.add_edge(token(token_in)?, token(token_out)?, edge);
+        }
+        for (token0, token1, fee, ticks) in self.ticks {
+            graph.set_ticks(token(token0)?, token(token1)?, fee, ticks);
+        }
+        Ok(graph)
+    }
+}
+
+/// Saves and reloads the price graph across restarts
+#[derive(Clone, Debug)]
+pub struct WarmStart {
+    /// The saved price graph file
+    path: PathBuf,
+    /// Max. blocks the saved graph may be behind the feed to be used
+    max_stale_blocks: u64,
+}
+
+impl WarmStart {
+    /// Warm start from the price graph saved at `path`
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            max_stale_blocks: DEFAULT_MAX_STALE_BLOCKS,
+        }
+    }
+    /// Use a saved graph at most `max_stale_blocks` behind the feed (default: 20)
+    pub fn with_max_stale_blocks(mut self, max_stale_blocks: u64) -> Self {
+        self.max_stale_blocks = max_stale_blocks;
+        self
+    }
+    /// Save `graph`, replacing any previously saved graph
+    pub fn save(&self, graph: &PriceGraph) -> io::Result<()> {
+        // write aside and rename so a crash mid-write leaves the previous graph intact
+        let tmp_path = self.path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer(&mut writer, &Snapshot::of(graph))?;
+        writer.flush()?;
+        fs::rename(tmp_path, &self.path)?;
+        info!(
+            "saved price graph #{} to {:?}",
+            graph.block_number(),
+            self.path
+        );
+        Ok(())
+    }
+    /// Load the saved graph, `None` if there is none or it is unusable
+    pub fn load(&self) -> Option<PriceGraph> {
+        let graph = File::open(&self.path)
+            .and_then(|file| {
+                let snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))?;
+                snapshot.into_graph()
+            })
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::NotFound => info!("no saved price graph at {:?}", self.path),
+                _ => warn!("warm start {:?}: {:?}", self.path, err),
+            })
+            .ok()?;
+        info!("loaded price graph #{}", graph.block_number());
+        Some(graph)
+    }
+    /// True if `graph` is recent enough to price the batch at `block_number`
+    pub fn is_fresh(&self, graph: &PriceGraph, block_number: u64) -> bool {
+        block_number
+            .checked_sub(graph.block_number())
+            .is_some_and(|stale_blocks| stale_blocks <= self.max_stale_blocks)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::types::U256;
+
+    use super::{Snapshot, WarmStart};
+    use crate::{
+        price_graph::Edge,
+        types::{ExchangeId, Fee, Token},
+        uniswap_v3::TickLiquidity,
+        PriceGraph,
+    };
+
+    fn graph() -> PriceGraph {
+        let mut graph = PriceGraph::empty();
+        graph.set_block_number(100);
+        graph.add_edge(
+            Token::USDC,
+            Token::WETH,
+            Edge::new_v2(
+                2_000_000_000000,
+                1_000 * 10_u128.pow(18),
+                Fee::from_units(30),
+                ExchangeId::Sushi,
+            ),
+        );
+        // ~2000 USDC/WETH
+        graph.add_edge(
+            Token::WETH,
+            Token::USDC,
+            Edge::new_v3(
+                U256::from(3_543_191_142_285_914_205_922_034_u128),
+                U256::from(10_u128.pow(18)),
+                Fee::from_pips(500),
+                true,
+            ),
+        );
+        graph.set_ticks(
+            Token::WETH,
+            Token::USDC,
+            Fee::from_pips(500),
+            TickLiquidity::new([(-200_000, 10_i128.pow(18)), (-190_000, -(10_i128.pow(18)))]),
+        );
+        graph
+    }
+
+    #[test]
+    fn save_and_reload() {
+        let path = std::env::temp_dir().join("fulcrum-warm-start-save-and-reload.json");
+        let warm_start = WarmStart::new(&path).with_max_stale_blocks(5);
+        let graph = graph();
+        warm_start.save(&graph).expect("saved");
+
+        let loaded = warm_start.load().expect("loaded");
+        assert_eq!(loaded.block_number(), 100);
+        let mut expected = graph
+            .edges()
+            .map(|(a, b, e)| (a, b, *e))
+            .collect::<Vec<_>>();
+        let mut edges = loaded
+            .edges()
+            .map(|(a, b, e)| (a, b, *e))
+            .collect::<Vec<_>>();
+        expected.sort_unstable_by_key(|(a, b, e)| (*a, *b, e.fee()));
+        edges.sort_unstable_by_key(|(a, b, e)| (*a, *b, e.fee()));
+        assert_eq!(edges, expected);
+        assert_eq!(
+            loaded.pool_ticks().collect::<Vec<_>>(),
+            graph.pool_ticks().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            loaded.amount_out(Token::USDC, Token::WETH, 1_000_000000),
+            graph.amount_out(Token::USDC, Token::WETH, 1_000_000000)
+        );
+
+        assert!(warm_start.is_fresh(&loaded, 100));
+        assert!(warm_start.is_fresh(&loaded, 105));
+        assert!(!warm_start.is_fresh(&loaded, 106));
+        assert!(!warm_start.is_fresh(&loaded, 99));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn rejects_changed_registry() {
+        let mut snapshot = Snapshot::of(&graph());
+        snapshot.tokens.swap(0, 1);
+        assert!(snapshot.into_graph().is_err());
+
+        let mut snapshot = Snapshot::of(&graph());
+        snapshot.edges.push((u8::MAX, 0, snapshot.edges[0].2));
+        assert!(snapshot.into_graph().is_err());
+
+        // a supported Id that isn't registered
+        let unregistered = MAX_TOKENS as u8 - 1;
+        assert!(config::active()
+            .registry()
+            .get(Token::new(unregistered))
+            .is_none());
+        let mut snapshot = Snapshot::of(&graph());
+        snapshot.edges.push((0, unregistered, snapshot.edges[0].2));
+        assert!(snapshot.into_graph().is_err());
+    }
+
+    #[test]
+    fn missing_graph() {
+        let warm_start =
+            WarmStart::new(std::env::temp_dir().join("fulcrum-warm-start-missing.json"));
+        assert!(warm_start.load().is_none());
+    }
+}
@@ -5,6 +5,8 @@ use ethers::types::U256;
 use log::debug;
 use once_cell::sync::Lazy;
 
+use crate::abi_check::abi_layout;
+
 pub static HIGH_BIT: Lazy<U256> = Lazy::new(|| U256::from(2).pow(U256::from(255)));
 
 pub mod bridge_id {
@@ -207,12 +209,27 @@ pub struct UniswapV3Mixin<'a> {
     pub path: BytesZcp<'a>,
 }
 
+/// `MixinKyberElastic` bridge data, the path is encoded as uniswap v3 (token, fee, token, ...)
+#[derive(Debug, DecodeStatic, PartialEq)]
+pub struct KyberElasticMixin<'a> {
+    pub router: AddressZcp<'a>,
+    pub path: BytesZcp<'a>,
+}
+
 #[derive(Debug, DecodeStatic, PartialEq)]
 pub struct UniswapV2Mixin<'a> {
     pub router: AddressZcp<'a>,
     pub path: Vec<AddressZcp<'a>>,
 }
 
+abi_layout! {
+    TransformErc20<'_>: 5, [4: Array];
+    FillQuoteTransformData<'_>: 10, [3: Array, 4: Array, 5: Array, 6: Array, 9: Array];
+    UniswapV3Mixin<'_>: 2, [1: Bytes];
+    KyberElasticMixin<'_>: 2, [1: Bytes];
+    UniswapV2Mixin<'_>: 2, [1: Array];
+}
+
 /// Decode a 0x ERC20 transform and its inner typed transforms for processing
 pub fn decode_erc20_transform<'a>(buf: &'a [u8]) {
     let outer_transform: TransformErc20 = <TransformErc20>::decode(buf).unwrap();
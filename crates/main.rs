@@ -1,4 +1,4 @@
-use std::{str::FromStr, sync::Arc, time::Duration};
+use std::{sync::Arc, time::Duration};
 
 mod cli;
 use cli::*;
@@ -7,19 +7,24 @@ use ethers_providers::{Middleware, Provider};
 use ethers_signers::{LocalWallet, Signer};
 
 use fulcrum_engine::{
-    constant::arbitrum::{UNISWAP_V3_FACTORY, UNISWAP_V3_INIT_CODE_HASH},
-    prices_at,
-    types::{Address, ExchangeId, Pair, Position, Token},
-    uniswap_v3::{self},
-    Engine, FulcrumExecutor, OrderService, PriceGraph, PriceService,
+    config::{self, Config},
+    control, discovery, inspect, prices_at, quote, status,
+    types::Pair,
+    BlockNumberCheck, ChainWatcher, CircuitBreaker, Controls, Engine, EngineStatus, EventSink,
+    FulcrumExecutor, L1InboxWatcher, Ledger, OrderEncoders, OrderService, Pnl, PriceGraph,
+    PriceService, PriceView, ReceiptFallback, SelectorStats, Shutdown, SimulationAuditor,
+    WarmStart,
 };
-use fulcrum_sequencer_feed::SequencerFeed;
-use fulcrum_ws_cli::FastWsClient;
+use fulcrum_sequencer_feed::{SequencerFeed, TxExporter};
+use fulcrum_ws_cli::{FastClient, Keepalive};
 
 use mimalloc::MiMalloc;
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
+/// Max. duration to await tasks draining on shutdown e.g. in-flight orders
+const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(10);
+
 #[tokio::main]
 async fn main() {
     println!(
@@ -38,18 +43,59 @@ async fn main() {
     // Load cli args
     let FulcrumCli {
         ws,
+        ipc,
+        ws_keepalive,
         chain,
+        config,
+        discover_pools,
         sub_command,
     } = argh::from_env();
 
-    let ws_endpoint = ws;
+    // Load the trading universe
+    let mut config = config
+        .map(|path| Config::from_file(path).expect("valid config"))
+        .unwrap_or_else(Config::arbitrum_one);
+
+    // Offline tools, the feed tap, and capture don't need a provider
+    if !matches!(sub_command, SubCommand::Run(_) | SubCommand::Prices(_)) {
+        config::install(config).expect("config installed once");
+        run_tool(sub_command).await;
+        return;
+    }
+
     let provider = Provider::new(
-        FastWsClient::connect(ws_endpoint)
-            .await
-            .expect("provider connects"),
+        match ipc {
+            Some(path) => FastClient::connect_ipc(path).await,
+            None => {
+                let keepalive = (ws_keepalive > 0).then(|| Keepalive {
+                    interval: Duration::from_secs(ws_keepalive),
+                    ..Default::default()
+                });
+                FastClient::connect_with_keepalive(
+                    ws.expect("--ws or --ipc endpoint").as_str(),
+                    keepalive,
+                )
+                .await
+            }
+        }
+        .expect("provider connects"),
     );
 
-    let (uniswap_v2_pairs, uniswap_v3_pairs) = load_pairs();
+    if discover_pools {
+        let pools = discovery::discover_pools(Arc::new(provider.clone()), config.registry()).await;
+        println!("discovered pools: {}", config.add_pools(pools));
+    }
+    let config = config::install(config).expect("config installed once");
+    // the provider is shut down once the tasks using it have drained
+    let shutdown = Shutdown::default().with_client(provider.provider().clone());
+
+    let uniswap_v2_pairs = config.uniswap_v2_pairs();
+    let uniswap_v3_pairs = config.uniswap_v3_pairs();
+    let camelot_v2_pairs = config.camelot_v2_pairs();
+    let balancer_pairs = config.balancer_pairs();
+    let curve_pairs = config.curve_pairs();
+    let solidly_pairs = config.solidly_pairs();
+    let kyber_elastic_pairs = config.kyber_elastic_pairs();
 
     // Price fetch
     if let SubCommand::Prices(PricesCommand { at }) = sub_command {
@@ -58,9 +104,15 @@ async fn main() {
             Arc::new(provider),
             uniswap_v2_pairs.as_slice(),
             uniswap_v3_pairs.as_slice(),
-        );
+        )
+        .with_camelot_v2(camelot_v2_pairs.as_slice())
+        .with_balancer(balancer_pairs.as_slice())
+        .with_curve(curve_pairs.as_slice())
+        .with_solidly(solidly_pairs.as_slice())
+        .with_kyber_elastic(kyber_elastic_pairs.as_slice())
+        .with_shutdown(shutdown.signal());
         prices_at(price_service, at).await;
-        // TODO: graceful shutdown
+        shutdown.complete(SHUTDOWN_DEADLINE).await;
         return;
     }
 
@@ -70,8 +122,41 @@ async fn main() {
         min_profit,
         executor,
         dry_run,
+        events,
+        ledger,
+        slippage_bps,
+        max_price_impact_bps,
+        order_max_age_ms,
+        presign,
+        preflight_ms,
+        shadow,
+        max_reverts,
+        loss_budget,
+        price_ws,
+        warm_start,
+        tick_words,
+        min_pool_tvl,
+        l1_ws,
+        watch_reorgs,
+        reorg_pause,
+        block_check,
+        calibrate_blocks,
+        receipt_fallback,
+        audit_bps,
+        selector_stats,
+        quote_addr,
+        status_addr,
+        control_socket,
+        control_file,
+        nonce_state,
+        #[cfg(feature = "metrics")]
+        metrics,
     }) = sub_command
     {
+        #[cfg(feature = "metrics")]
+        if let Some(addr) = metrics {
+            tokio::spawn(fulcrum_engine::metrics::serve(addr));
+        }
         let wallet = key
             .expect("--key given")
             .parse::<LocalWallet>()
@@ -86,19 +171,100 @@ async fn main() {
         );
 
         let executor_contract = FulcrumExecutor::new(executor, Arc::clone(&provider));
-        let order_service = OrderService::new(
+        // signing backend is selected at build time
+        #[cfg(feature = "secp256k1")]
+        let order_signer = fulcrum_engine::Secp256k1Signer::new(&wallet);
+        #[cfg(not(feature = "secp256k1"))]
+        let order_signer = wallet.clone();
+        let mut order_service = OrderService::new(
             Arc::clone(&provider),
             chain,
             executor_contract,
-            wallet.clone(),
+            order_signer,
         )
-        .await;
+        .await
+        .with_submission(config.submission().clone())
+        .with_order_encoder(
+            OrderEncoders::default()
+                .build(config.order_encoder())
+                .expect("order encoder registered"),
+        );
+        if let Some(slippage_bps) = slippage_bps {
+            order_service = order_service.with_slippage_bps(slippage_bps);
+        }
+        if let Some(order_max_age_ms) = order_max_age_ms {
+            order_service =
+                order_service.with_order_max_age(Duration::from_millis(order_max_age_ms));
+        }
+        if max_reverts.is_some() || loss_budget.is_some() {
+            order_service = order_service.with_circuit_breaker(CircuitBreaker::new(
+                max_reverts.unwrap_or(u32::MAX),
+                loss_budget.unwrap_or(u128::MAX),
+            ));
+        }
+        if let Some(limits) = config.risk_limits() {
+            order_service = order_service.with_risk_limits(limits.clone());
+        }
+        if let Some(templates) = presign {
+            order_service = order_service.with_presigning(templates);
+        }
+        if let Some(preflight_ms) = preflight_ms {
+            order_service = order_service.with_preflight(Duration::from_millis(preflight_ms));
+        }
+        if let Some(mode) = shadow {
+            assert!(ledger.is_some(), "--shadow requires --ledger");
+            order_service = order_service.with_shadow_execution(mode);
+        }
+        if let Some(path) = ledger.as_ref() {
+            order_service = order_service.with_ledger(Ledger::open(path).expect("ledger opens"));
+        }
+        if let Some(path) = nonce_state {
+            order_service = order_service.with_nonce_state(path);
+        }
+        #[cfg(feature = "notify")]
+        let alerts = config
+            .notify()
+            .map(|notify| fulcrum_engine::Notifier::new(notify).start());
+        #[cfg(feature = "notify")]
+        if let Some(alerts) = alerts.as_ref() {
+            order_service = order_service.with_alerts(alerts.clone());
+        }
+        order_service = order_service.with_shutdown(shutdown.signal());
+        // min. profit, dry-run, and searched paths are changed at runtime by the control socket or file
+        let controls = (control_socket.is_some() || control_file.is_some())
+            .then(|| Arc::new(Controls::new(min_profit, dry_run)));
+        if let Some(controls) = controls.as_ref() {
+            order_service = order_service.with_controls(Arc::clone(controls));
+        }
         let sequencer_feed = SequencerFeed::arbitrum_one().await;
-        let price_service = PriceService::new(
+        let mut price_service = PriceService::new(
             Arc::clone(&provider),
             uniswap_v2_pairs.as_slice(),
             uniswap_v3_pairs.as_slice(),
-        );
+        )
+        .with_camelot_v2(camelot_v2_pairs.as_slice())
+        .with_balancer(balancer_pairs.as_slice())
+        .with_curve(curve_pairs.as_slice())
+        .with_solidly(solidly_pairs.as_slice())
+        .with_kyber_elastic(kyber_elastic_pairs.as_slice())
+        .with_sync_mode(config.price_sync())
+        .with_shutdown(shutdown.signal());
+        if let Some(word_radius) = tick_words {
+            price_service = price_service.with_ticks(word_radius);
+        }
+        if !price_ws.is_empty() {
+            let mut secondary_providers = Vec::with_capacity(price_ws.len());
+            for endpoint in price_ws {
+                let client = FastClient::connect(endpoint.as_str())
+                    .await
+                    .expect("price provider connects");
+                secondary_providers.push(Arc::new(Provider::new(client)));
+            }
+            price_service = price_service.with_secondary_sources(secondary_providers.as_slice());
+        }
+        if let Some(min_tvl) = min_pool_tvl {
+            price_service = price_service.with_min_tvl(min_tvl).await;
+        }
 
         println!(
             "monitoring chain: {:?}\nsigning with: {:?}\nexecutor: {:?}\npassive: {dry_run}",
@@ -109,85 +275,171 @@ async fn main() {
         let ws_latency = provider.provider().as_ref().report_latency().await;
         println!("~ws latency: ~{:?}ms", ws_latency);
         println!(
-            "min. profit margin: {:?}%\npairs: {:#?}{:#?}\n",
-            min_profit, uniswap_v3_pairs, uniswap_v2_pairs,
+            "min. profit margin: {:?}%\npairs: {:#?}{:#?}{:#?}{:#?}{:#?}{:#?}{:#?}\n",
+            min_profit,
+            price_service.uniswap_v3_pairs(),
+            price_service.uniswap_v2_pairs(),
+            price_service.camelot_v2_pairs(),
+            price_service.balancer_pairs(),
+            price_service.curve_pairs(),
+            price_service.solidly_pairs(),
+            price_service.kyber_elastic_pairs(),
         );
 
         // build trade search paths
-        let pairs: Vec<Pair> = uniswap_v3_pairs.iter().map(|(p, _)| *p).collect(); // TODO: include v2 pairs
-        let weth_paths = PriceGraph::find_paths(Token::WETH, pairs.as_slice());
-        let arb_paths = PriceGraph::find_paths(Token::ARB, pairs.as_slice());
-        let usdt_paths = PriceGraph::find_paths(Token::USDT, pairs.as_slice());
-        let usdc_paths = PriceGraph::find_paths(Token::USDC, pairs.as_slice());
+        let pairs: Vec<Pair> = price_service
+            .uniswap_v3_pairs()
+            .iter()
+            .map(|(p, _)| *p)
+            .collect(); // TODO: include v2 pairs
+
         // via flash loans position can be anything
-        // positions should be big enough to make profits, small enough to not cross v3 liquidity ticks
-        let all_paths = [
-            (Position::of(5_000, Token::USDC), usdc_paths.as_ref()),
-            (Position::of(3, Token::WETH), weth_paths.as_ref()),
-            (Position::of(5_000, Token::USDT), usdt_paths.as_ref()),
-            (Position::of(4_500, Token::ARB), arb_paths.as_ref()),
-        ];
-
-        let engine = Engine::new(price_service, order_service, sequencer_feed);
+        let positions = config.positions();
+        let paths: Vec<_> = positions
+            .iter()
+            .map(|p| PriceGraph::find_paths(p.token, pairs.as_slice(), config.max_hops()))
+            .collect();
+        let all_paths: Vec<_> = positions
+            .into_iter()
+            .zip(paths.iter().map(Vec::as_slice))
+            .collect();
+
+        let receipt_fallback = receipt_fallback.map(|lag_ms| {
+            ReceiptFallback::new(
+                provider.as_ref().clone(),
+                price_service.uniswap_v3_pairs(),
+                price_service.uniswap_v2_pairs(),
+            )
+            .with_lag(Duration::from_millis(lag_ms))
+        });
+        let auditor = audit_bps.map(|bps| {
+            SimulationAuditor::new(
+                provider.as_ref().clone(),
+                price_service.uniswap_v3_pairs(),
+                price_service.uniswap_v2_pairs(),
+            )
+            .with_threshold_bps(bps)
+        });
+        let mut event_sink = events
+            .map(|target| EventSink::open(&target).expect("event sink opens"))
+            .unwrap_or_default();
+        if let Some(addr) = status_addr {
+            let mut engine_status = EngineStatus::default();
+            if let Some(path) = ledger {
+                engine_status = engine_status.with_ledger(path);
+            }
+            let engine_status = Arc::new(engine_status);
+            tokio::spawn(status::serve(Arc::clone(&engine_status), addr));
+            event_sink = event_sink.with_status(engine_status);
+        }
+        let mut engine = Engine::new(price_service, order_service, sequencer_feed)
+            .with_event_sink(event_sink)
+            .with_arena(config.arena())
+            .with_shutdown(shutdown.signal());
+        if let Some(max_price_impact_bps) = max_price_impact_bps {
+            engine = engine.with_max_price_impact_bps(max_price_impact_bps);
+        }
+        if let Some(path) = warm_start {
+            engine = engine.with_warm_start(WarmStart::new(path));
+        }
+        if let Some(endpoint) = l1_ws {
+            let l1_client = FastClient::connect(endpoint.as_str())
+                .await
+                .expect("L1 provider connects");
+            engine =
+                engine.with_delayed_messages(L1InboxWatcher::new(Provider::new(l1_client)).start());
+        }
+        if watch_reorgs {
+            engine = engine.with_chain_watch(ChainWatcher::new(provider.as_ref().clone()).start());
+        }
+        if let Some(blocks) = reorg_pause {
+            engine = engine.with_reorg_pause(blocks);
+        }
+        if let Some(secs) = block_check {
+            engine = engine.with_block_check(
+                Duration::from_secs(secs),
+                BlockNumberCheck::default(),
+                calibrate_blocks,
+            );
+        }
+        if let Some(fallback) = receipt_fallback {
+            engine = engine.with_receipt_fallback(fallback);
+        }
+        if let Some(auditor) = auditor {
+            engine = engine.with_auditor(auditor);
+        }
+        if let Some(secs) = selector_stats {
+            engine = engine.with_selector_stats(
+                SelectorStats::new().with_report_interval(Duration::from_secs(secs)),
+            );
+        }
+        if let Some(addr) = quote_addr {
+            let view = Arc::new(PriceView::default());
+            tokio::spawn(quote::serve(Arc::clone(&view), addr));
+            engine = engine.with_price_view(view);
+        }
+        #[cfg(feature = "notify")]
+        if let Some(alerts) = alerts {
+            engine = engine.with_alerts(alerts);
+        }
+        if let Some(controls) = controls {
+            if let Some(path) = control_file {
+                control::reload_on_signal(Arc::clone(&controls), path.into());
+            }
+            if let Some(path) = control_socket {
+                control::serve(Arc::clone(&controls), path.into());
+            }
+            engine = engine.with_controls(controls);
+        }
+        shutdown.trigger_on_ctrl_c();
         engine.run(&all_paths, min_profit, dry_run).await;
+        shutdown.complete(SHUTDOWN_DEADLINE).await;
     }
 }
 
-/// Load the active trading pairs (uniswapv2, uniswapv3)
-fn load_pairs() -> (Vec<(Pair, Address)>, Vec<(Pair, Address)>) {
-    // only these v3 pairs have sufficient liquidity
-    let pairs: &[Pair] = &[
-        Pair::new(Token::USDC, Token::WETH, 100, ExchangeId::Uniswap),
-        Pair::new(Token::USDC, Token::WETH, 500, ExchangeId::Uniswap),
-        Pair::new(Token::USDC, Token::WETH, 3_000, ExchangeId::Uniswap),
-        Pair::new(Token::USDC, Token::ARB, 500, ExchangeId::Uniswap),
-        Pair::new(Token::WETH, Token::ARB, 100, ExchangeId::Uniswap),
-        Pair::new(Token::WETH, Token::ARB, 500, ExchangeId::Uniswap),
-        Pair::new(Token::WETH, Token::ARB, 3_000, ExchangeId::Uniswap),
-        Pair::new(Token::WETH, Token::USDT, 500, ExchangeId::Uniswap),
-        Pair::new(Token::WETH, Token::USDT, 100, ExchangeId::Uniswap),
-        Pair::new(Token::USDT, Token::USDC, 100, ExchangeId::Uniswap),
-    ];
-    let uniswap_v3_pairs: Vec<(Pair, Address)> = pairs
-        .iter()
-        .map(|p| {
-            let pool_address = uniswap_v3::pool_address_from_pair(
-                *p,
-                UNISWAP_V3_FACTORY.into(),
-                &UNISWAP_V3_INIT_CODE_HASH,
-            );
-            (*p, pool_address)
-        })
-        .collect();
-
-    let chronos_pairs: &[(Pair, Address)] = &[
-        (
-            Pair::new(Token::WETH, Token::ARB, 200, ExchangeId::Chronos),
-            Address::from_str("afe909b1a5ed90d36f9ee1490fcb855645c00eb3").unwrap(),
-        ),
-        (
-            Pair::new(Token::WETH, Token::USDC, 200, ExchangeId::Chronos),
-            Address::from_str("A2F1C1B52E1b7223825552343297Dc68a29ABecC").unwrap(),
-        ),
-        (
-            Pair::new(Token::WETH, Token::USDT, 200, ExchangeId::Chronos),
-            Address::from_str("8a263cc1dfdce6c64e2a1cf6133c22eed5d4e29d").unwrap(),
-        ),
-    ];
-    let sushi_pairs: &[(Pair, Address)] = &[(
-        Pair::new(Token::WETH, Token::USDC, 300, ExchangeId::Sushi),
-        Address::from_str("905dfcd5649217c42684f23958568e533c711aa3").unwrap(),
-    )];
-    let camelot_pairs: &[(Pair, Address)] = &[
-        (
-            Pair::new(Token::WETH, Token::ARB, 300, ExchangeId::Sushi),
-            Address::from_str("a6c5c7d189fa4eb5af8ba34e63dcdd3a635d433f").unwrap(),
-        ),
-        (
-            Pair::new(Token::WETH, Token::USDC, 300, ExchangeId::Sushi),
-            Address::from_str("84652bb2539513baf36e225c930fdd8eaa63ce27").unwrap(),
-        ),
-    ];
-    let uniswap_v2_pairs = [chronos_pairs, sushi_pairs, camelot_pairs].concat();
-    (uniswap_v2_pairs, uniswap_v3_pairs)
+/// Run an offline tool, the feed tap, or capture
+async fn run_tool(sub_command: SubCommand) {
+    // Ledger query, offline
+    if let SubCommand::Ledger(LedgerCommand { path }) = sub_command {
+        let records = Ledger::load(&path).expect("ledger readable");
+        println!("{}", Pnl::from_records(&records));
+        return;
+    }
+
+    // Payload decode, offline
+    if let SubCommand::Decode(DecodeCommand { file, hex }) = sub_command {
+        if let Some(path) = file {
+            let messages = std::fs::read(path).expect("feed messages readable");
+            for message in messages.split(|b| *b == b'\n').filter(|m| !m.is_empty()) {
+                println!(
+                    "{}",
+                    inspect::decode_feed_payload(message.to_vec().as_mut_slice())
+                );
+            }
+        }
+        if let Some(raw_tx) = hex {
+            println!("{}", inspect::decode_raw_tx(raw_tx.as_slice()));
+        }
+        return;
+    }
+
+    // Live feed tap
+    if let SubCommand::Tap(TapCommand { to, selector }) = sub_command {
+        let filter = inspect::TxFilter {
+            to,
+            selectors: selector,
+        };
+        inspect::tap(SequencerFeed::arbitrum_one().await, &filter).await;
+        return;
+    }
+
+    // Live feed capture
+    if let SubCommand::Capture(CaptureCommand { path, to, selector }) = sub_command {
+        let filter = inspect::TxFilter {
+            to,
+            selectors: selector,
+        };
+        let exporter = TxExporter::create(path).expect("export file created");
+        inspect::capture(SequencerFeed::arbitrum_one().await, &filter, exporter).await;
+    }
 }
@@ -1,102 +1,339 @@
+#[cfg(feature = "tolerant-decode")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ethers::utils::hex;
 use log::info;
+#[cfg(feature = "tolerant-decode")]
+use log::warn;
+
+#[cfg(feature = "tolerant-decode")]
+use crate::types::BroadcastMessage;
+use crate::types::{FeedError, Header};
+
+/// Count of messages decoded by the serde fallback
+#[cfg(feature = "tolerant-decode")]
+static SERDE_FALLBACKS: AtomicU64 = AtomicU64::new(0);
+
+/// Fields of a sequencer feed JSON message
+#[derive(Debug, Default, PartialEq)]
+pub struct FeedJson<'a> {
+    pub sequence_number: u64,
+    pub header: Header,
+    /// Count of L1 delayed inbox messages sequenced as of this message
+    pub delayed_messages_read: u64,
+    /// base64 encoded 'L2' message, `None` if the message has none e.g. a confirmation
+    pub l2_msg: Option<&'a mut [u8]>,
+}
 
-/// Deserialize a sequencer feed JSON message into its base64 encoded 'L2' message
+/// Length of a feed frame's prefix `{"version":1,"messages":[`
+const MESSAGES_OFFSET: usize = 25;
+
+/// Deserialize the first message of a sequencer feed JSON frame into its sequence number, L1 header,
+/// delayed messages read, and base64 encoded 'L2' message
 ///
-/// serde is reasonably efficient but degrades as it must scan the lengthy base64 'l2msg' >10kb
-/// we can do better by searching from the msg tail for the end of the l2msg
-pub fn feed_json_from_input(buf: &mut [u8]) -> (u64, Option<&mut [u8]>) {
-    // {"version":1,"confirmedSequenceNumberMessage":{"sequenceNumber":69287376}}
-    let mut index = 42_usize;
-    // let version_key = &buf[1..10];
-    // print_bytes(version_key);
-    // let sequencer_number_0_key = &buf[26..42];
-    // print_bytes(sequencer_number_0_key);
-
-    /*
-    arbitrum one nitro genesis block: 22207817
-    // https://github.com/OffchainLabs/arbitrum-subgraphs/blob/fa8e55b7aec8609b6c8a6cad704d44a0b2fde3b9/packages/subgraph-common/config/nitro-mainnet.json#L14
-    func MessageCountToBlockNumber(messageCount MessageIndex, genesisBlockNumber uint64) int64 {
-        return int64(uint64(messageCount)+genesisBlockNumber) - 1
-    }
-     */
-    if buf.len() <= 75 {
-        // {"version":1,"confirmedSequenceNumberMessage":{"sequenceNumber":72346029}}
-        // print_bytes(&buf);
-        return (0, None);
+/// Errors if the message is truncated or malformed e.g. an invalid sequence number
+pub fn feed_json_from_input(buf: &mut [u8]) -> Result<FeedJson<'_>, FeedError> {
+    FeedMessages::new(buf)
+        .next()
+        .unwrap_or_else(|| Ok(FeedJson::default()))
+}
+
+/// Iterates the messages of a sequencer feed JSON frame, deserializing each in turn
+///
+/// Frames usually carry one message but may carry many e.g. when catching up after a reconnect
+pub struct FeedMessages<'a> {
+    /// The frame from the start of the next message
+    rest: &'a mut [u8],
+}
+
+impl<'a> FeedMessages<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        /*
+        arbitrum one nitro genesis block: 22207817
+        // https://github.com/OffchainLabs/arbitrum-subgraphs/blob/fa8e55b7aec8609b6c8a6cad704d44a0b2fde3b9/packages/subgraph-common/config/nitro-mainnet.json#L14
+        func MessageCountToBlockNumber(messageCount MessageIndex, genesisBlockNumber uint64) int64 {
+            return int64(uint64(messageCount)+genesisBlockNumber) - 1
+        }
+         */
+        if buf.len() <= 75 {
+            // {"version":1,"confirmedSequenceNumberMessage":{"sequenceNumber":72346029}}
+            // print_bytes(&buf);
+            return Self {
+                rest: Default::default(),
+            };
+        }
+        Self {
+            rest: &mut buf[MESSAGES_OFFSET..],
+        }
+    }
+}
+
+impl<'a> Iterator for FeedMessages<'a> {
+    type Item = Result<FeedJson<'a>, FeedError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        // the next message or the end of the frame `]}`
+        if self.rest.first() != Some(&b'{') {
+            return None;
+        }
+        let buf = std::mem::take(&mut self.rest);
+        Some(message_json(buf).map(|(feed_json, rest)| {
+            self.rest = rest;
+            feed_json
+        }))
     }
-    index += 6;
-    while buf[index] as char != ',' {
+}
+
+/// Deserialize a feed message, returning it and the remainder of the frame after it
+///
+/// serde is reasonably efficient but degrades as it must scan the lengthy base64 'l2msg' >10kb
+/// we can do better skipping the fixed layout keys and searching for the end of the l2msg with memchr
+///
+/// Errors on truncated or malformed messages
+fn message_json(buf: &mut [u8]) -> Result<(FeedJson<'_>, &mut [u8]), FeedError> {
+    let byte = |index: usize| buf.get(index).copied().ok_or(FeedError::InvalidJson);
+    // {"sequenceNumber":69287376,"message":{"message":{"header":{"kind":3,...
+    // sequence numbers are at least 5 digits
+    let mut index = 23_usize;
+    while byte(index)? != b',' {
         index += 1;
     }
+    #[cfg(feature = "safe-decode")]
     let sequence_number =
-        str::parse::<u64>(unsafe { core::str::from_utf8_unchecked(&buf[43..index]) })
-            .expect("sequencer number");
-    if buf.len() < 80 {
-        return (sequence_number, None);
+        core::str::from_utf8(&buf[18..index]).map_err(|_| FeedError::InvalidJson)?;
+    #[cfg(not(feature = "safe-decode"))]
+    let sequence_number = unsafe { core::str::from_utf8_unchecked(&buf[18..index]) };
+    let sequence_number = str::parse::<u64>(sequence_number).map_err(|_| FeedError::InvalidJson)?;
+    if buf.len() < 55 {
+        return Ok((
+            FeedJson {
+                sequence_number,
+                ..Default::default()
+            },
+            Default::default(),
+        ));
     }
 
-    // index = 42;
-    // length of the sequencer number can grow so we must search
-    while buf[index] as char != '"' {
+    // skip `,"message":{"message":{"header":{"kind":`
+    index += 40;
+    // kind may be 1 or 2 ascii digits
+    let mut header = Header::default();
+    loop {
+        let digit = byte(index)?;
+        if digit == b',' {
+            break;
+        }
+        if !digit.is_ascii_digit() {
+            return Err(FeedError::InvalidJson);
+        }
+        header.kind = header
+            .kind
+            .checked_mul(10)
+            .and_then(|kind| kind.checked_add(digit - b'0'))
+            .ok_or(FeedError::InvalidJson)?;
         index += 1;
     }
-    /*
-    let message_inner_key = &buf[index..index + 9];
-    print_bytes(message_inner_key);
-    index += 11;
-    let message_inner_key = &buf[index..index + 9];
-    print_bytes(message_inner_key);
-    index += 11;
-    let header_key = &buf[index..index + 8];
-    print_bytes(header_key);
-    index += 10;
-    let kind_key = &buf[index..index + 6];
-    print_bytes(kind_key);
-    index+=7;
-    */
-    index += 39;
-    let _kind_value = buf[index] - 0x30; // convert ascii digit to u8
-                                         // println!("kind:{kind_value}");
-                                         // skip this: `,"sender":"0xa4b000000000000000000073657175656e636572","blockNumber":`
-                                         /*
-                                         let block_number_start = index + 70;
-                                         index += 70 + 7; // +7 hint since block # is atleast this length
-                                         while buf[index] as char != ',' {
-                                             index += 1;
-                                         }
-                                         print_bytes(&buf[block_number_start..index]);
-                                         if let Ok(block_number) = str::parse::<u64>(unsafe {
-                                             core::str::from_utf8_unchecked(&buf[block_number_start..index])
-                                         }) {
-                                             println!("block: {:?}", block_number);
-                                         }
-                                         */
+    // skip `,"sender":"0x`
+    let sender_start = index + 13;
+    if let Some(sender) = buf.get(sender_start..sender_start + 40) {
+        let _ = hex::decode_to_slice(sender, header.sender.as_bytes_mut());
+    }
+    // skip this: `,"sender":"0xa4b000000000000000000073657175656e636572","blockNumber":`
 
     // skip to end of 'header' object
     // some of the fields are variable length so search to be safe
-    while buf[index] as char != '}' {
+    while byte(index)? != b'}' {
         index += 1;
     }
-    // index += 2;
-    // let l2msg_key = &buf[index..index + 7];
-    // print_bytes(l2msg_key);
-    // index += 9; //+ :"
+    // skip `},"l2Msg":"`
     index += 11;
+    let l2msg_start = index;
+    // base64 has no quotes, the first is the end of the l2msg
+    let l2msg_end = buf
+        .get(l2msg_start..)
+        .and_then(|l2msg| memchr::memchr(b'"', l2msg))
+        .ok_or(FeedError::InvalidJson)?
+        + l2msg_start;
 
-    // for extremely long l2msgs its more efficient to
-    // search from the end of the payload in reverse
-    let mut tail_index = buf.len() - 1;
-    let mut count = 4;
-    while count > 0 {
-        if buf[tail_index] as char == '}' {
-            count -= 1;
+    // skip `"},"delayedMessagesRead":`
+    index = l2msg_end + 25;
+    let mut delayed_messages_read = 0_u64;
+    loop {
+        let digit = byte(index)?;
+        if !digit.is_ascii_digit() {
+            break;
         }
-        tail_index -= 1;
+        delayed_messages_read = delayed_messages_read
+            .checked_mul(10)
+            .and_then(|count| count.checked_add((digit - b'0') as u64))
+            .ok_or(FeedError::InvalidJson)?;
+        index += 1;
+    }
+    // skip `},"signature":null}`, the signature is null or a hex string
+    index += 1;
+    while byte(index)? != b'}' {
+        index += 1;
     }
-    let l2msg_value = buf[index..tail_index].as_mut();
+
+    let (message, rest) = buf.split_at_mut(index + 1);
+    // the next message follows a ','
+    let rest = match rest.split_first_mut() {
+        Some((b',', rest)) => rest,
+        _ => Default::default(),
+    };
+    let l2msg_value = message[l2msg_start..l2msg_end].as_mut();
     // print_bytes(l2msg_value);
 
-    (sequence_number, Some(l2msg_value))
+    Ok((
+        FeedJson {
+            sequence_number,
+            header,
+            delayed_messages_read,
+            l2_msg: Some(l2msg_value),
+        },
+        rest,
+    ))
+}
+
+/// Deserialize a sequencer feed JSON frame with serde, the slow path for frames not matching the fast layout
+///
+/// e.g. fields were reordered or added upstream, a warning is logged
+#[cfg(feature = "tolerant-decode")]
+pub fn feed_jsons_tolerant(buf: &mut [u8]) -> Result<Vec<FeedJson<'_>>, FeedError> {
+    // warn at 1, 2, 4, 8.. fallbacks, a schema change affects every message
+    let fallbacks = SERDE_FALLBACKS.fetch_add(1, Ordering::Relaxed) + 1;
+    if fallbacks.is_power_of_two() {
+        warn!(
+            "feed json layout changed, serde fallback: fallbacks={fallbacks} len={} prefix={:?}",
+            buf.len(),
+            String::from_utf8_lossy(&buf[..buf.len().min(96)]),
+        );
+    }
+    feed_jsons_from_serde(buf)
+}
+
+/// Check `buf` has the field layout `feed_json_from_input` expects, without panicking on unexpected input
+///
+/// Only the first message's header and the last message's tail are checked
+#[cfg(feature = "tolerant-decode")]
+pub(crate) fn matches_fast_layout(buf: &[u8]) -> bool {
+    const PREFIX: &[u8] = br#"{"version":1,"messages":[{"sequenceNumber":"#;
+    const HEADER: &[u8] = br#","message":{"message":{"header":{"kind":"#;
+    const SENDER: &[u8] = br#","sender":"0x"#;
+    const L2_MSG: &[u8] = br#"},"l2Msg":""#;
+    const TAIL: &[u8] = br#""},"delayedMessagesRead":"#;
+    // confirmations aren't decoded
+    if buf.len() <= 75 {
+        return true;
+    }
+    let skip_digits = |mut index: usize| {
+        while index < buf.len() && buf[index].is_ascii_digit() {
+            index += 1;
+        }
+        index
+    };
+    if !buf.starts_with(PREFIX) {
+        return false;
+    }
+    let mut index = skip_digits(PREFIX.len());
+    if buf.len() < 80 {
+        return buf.get(index) == Some(&b',');
+    }
+    if !buf[index..].starts_with(HEADER) {
+        return false;
+    }
+    let kind_start = index + HEADER.len();
+    index = skip_digits(kind_start);
+    // `kind` must fit a u8
+    if !(kind_start + 1..=kind_start + 3).contains(&index)
+        || buf[kind_start..index]
+            .iter()
+            .fold(0_u32, |acc, digit| acc * 10 + (digit - 0x30) as u32)
+            > u8::MAX as u32
+    {
+        return false;
+    }
+    if !buf[index..].starts_with(SENDER) || buf.len() < index + SENDER.len() + 40 {
+        return false;
+    }
+    index += SENDER.len() + 40;
+    while index < buf.len() && buf[index] != b'}' {
+        index += 1;
+    }
+    if !buf[index..].starts_with(L2_MSG) {
+        return false;
+    }
+    // the last l2msg ends at the 4th last '}' e.g. `"},"delayedMessagesRead":852410},"signature":null}]}`
+    match buf
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, b)| **b == b'}')
+        .nth(3)
+    {
+        Some((tail, _)) => tail > index + L2_MSG.len() && buf[tail - 1..].starts_with(TAIL),
+        None => false,
+    }
+}
+
+/// Deserialize the messages of a sequencer feed JSON frame with serde
+#[cfg(feature = "tolerant-decode")]
+fn feed_jsons_from_serde(buf: &mut [u8]) -> Result<Vec<FeedJson<'_>>, FeedError> {
+    // l2msgs are borrowed from `buf`, by offset, so they can be decoded in place
+    let messages: Vec<_> = {
+        let message: BroadcastMessage =
+            serde_json::from_slice(buf).map_err(|_| FeedError::InvalidJson)?;
+        message
+            .messages
+            .unwrap_or_default()
+            .into_iter()
+            .map(|feed_message| {
+                let l2_msg = feed_message.message.message.l2msg;
+                (
+                    feed_message.sequence_number,
+                    feed_message.message.message.header,
+                    feed_message.message.delayed_messages_read,
+                    l2_msg.as_ptr() as usize - buf.as_ptr() as usize,
+                    l2_msg.len(),
+                )
+            })
+            .collect()
+    };
+
+    // split the l2msgs from `buf` in order
+    let mut rest = buf;
+    let mut consumed = 0;
+    let mut feed_jsons = Vec::with_capacity(messages.len());
+    for (sequence_number, header, delayed_messages_read, offset, len) in messages {
+        let (_, tail) = std::mem::take(&mut rest).split_at_mut(offset - consumed);
+        let (l2_msg, tail) = tail.split_at_mut(len);
+        rest = tail;
+        consumed = offset + len;
+        feed_jsons.push(FeedJson {
+            sequence_number,
+            header,
+            delayed_messages_read,
+            l2_msg: Some(l2_msg),
+        });
+    }
+
+    Ok(feed_jsons)
+}
+
+/// Read the sequence number of a sequencer feed JSON message without decoding the rest of it
+///
+/// Returns `None` for messages which carry no sequence number e.g. confirmations
+pub fn sequence_number(buf: &[u8]) -> Option<u64> {
+    // {"version":1,"messages":[{"sequenceNumber":69287376,"message":...
+    if buf.len() <= 75 {
+        return None;
+    }
+    let mut index = 48_usize;
+    while index < buf.len() && buf[index] as char != ',' {
+        index += 1;
+    }
+    core::str::from_utf8(buf.get(43..index)?)
+        .ok()
+        .and_then(|s| str::parse::<u64>(s).ok())
 }
 
 pub fn print_bytes(b: &[u8]) {
@@ -0,0 +1,360 @@
+//! Decoded tx export
+//!
+//! Captures decoded txs to CSV or Parquet (`parquet` feature) files for research e.g. to replay order flow.
+//! Txs are copied off the decoding arena and queued per feed message, a background thread batches them to the file so
+//! decoding isn't held up by formatting, compression, or disk writes. A full queue drops messages rather than block
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ethers::{
+    types::{Address, U256},
+    utils::hex,
+};
+use log::debug;
+
+use crate::{DecodedBatch, TransactionInfo};
+
+/// Max. feed messages queued for the writer, later ones are dropped until it catches up
+const EXPORT_QUEUE_SIZE: usize = 1_024;
+/// Rows buffered before they're written out
+const WRITE_BATCH_ROWS: usize = 8_192;
+/// Max. time rows are buffered before they're written out
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// CSV column names, in order
+const CSV_HEADER: &str =
+    "block_number,timestamp_us,index,to,value,nonce,gas_limit,max_fee_per_gas,input";
+
+/// Export file format
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// Comma separated values with a header row, addresses and calldata as 0x-hex
+    Csv,
+    /// Apache Parquet, snappy compressed
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl ExportFormat {
+    /// The format of `path` by its extension, `.parquet` or otherwise CSV
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            #[cfg(feature = "parquet")]
+            Some("parquet") => Self::Parquet,
+            _ => Self::Csv,
+        }
+    }
+}
+
+/// An exported tx, owning its calldata
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxRecord {
+    /// L2 block number of the tx's feed message
+    pub block_number: u64,
+    /// Unix timestamp (µs) the tx's feed message was exported i.e. received
+    pub timestamp_us: u64,
+    /// Position of the tx in its feed message
+    pub index: u32,
+    pub to: Address,
+    pub value: U256,
+    pub nonce: u64,
+    pub gas_limit: u64,
+    /// The max. fee per gas (EIP-1559) or gas price (legacy)
+    pub max_fee_per_gas: u128,
+    pub input: Vec<u8>,
+}
+
+impl TxRecord {
+    /// Write the record as a CSV row
+    fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{},{},{},{:?},{},{},{},{},0x{}",
+            self.block_number,
+            self.timestamp_us,
+            self.index,
+            self.to,
+            self.value,
+            self.nonce,
+            self.gas_limit,
+            self.max_fee_per_gas,
+            hex::encode(&self.input),
+        )
+    }
+}
+
+/// Writes records in an export format
+enum Sink<W: Write + Send> {
+    Csv(BufWriter<W>),
+    #[cfg(feature = "parquet")]
+    Parquet(parquet::arrow::ArrowWriter<W>),
+}
+
+impl<W: Write + Send> Sink<W> {
+    fn new(writer: W, format: ExportFormat) -> io::Result<Self> {
+        match format {
+            ExportFormat::Csv => {
+                let mut writer = BufWriter::new(writer);
+                writeln!(writer, "{CSV_HEADER}")?;
+                Ok(Self::Csv(writer))
+            }
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => {
+                let props = parquet::file::properties::WriterProperties::builder()
+                    .set_compression(parquet::basic::Compression::SNAPPY)
+                    .build();
+                parquet::arrow::ArrowWriter::try_new(writer, columnar::schema(), Some(props))
+                    .map(Self::Parquet)
+                    .map_err(io_error)
+            }
+        }
+    }
+    fn write(&mut self, records: &[TxRecord]) -> io::Result<()> {
+        match self {
+            Self::Csv(writer) => {
+                for record in records {
+                    record.write_csv(writer)?;
+                }
+                writer.flush()
+            }
+            #[cfg(feature = "parquet")]
+            Self::Parquet(writer) => {
+                let batch = columnar::record_batch(records).map_err(io_error)?;
+                writer.write(&batch).map_err(io_error)
+            }
+        }
+    }
+    /// Complete the file e.g. the Parquet footer
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Csv(mut writer) => writer.flush(),
+            #[cfg(feature = "parquet")]
+            Self::Parquet(writer) => writer.close().map(|_| ()).map_err(io_error),
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn io_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+/// Parquet layout of records
+#[cfg(feature = "parquet")]
+mod columnar {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, BinaryArray, RecordBatch, StringArray, UInt32Array, UInt64Array};
+    use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+
+    use super::TxRecord;
+
+    /// Columns as the CSV header, `value` and `max_fee_per_gas` are decimal strings (> 64 bits)
+    pub fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("block_number", DataType::UInt64, false),
+            Field::new("timestamp_us", DataType::UInt64, false),
+            Field::new("index", DataType::UInt32, false),
+            Field::new("to", DataType::Utf8, false),
+            Field::new("value", DataType::Utf8, false),
+            Field::new("nonce", DataType::UInt64, false),
+            Field::new("gas_limit", DataType::UInt64, false),
+            Field::new("max_fee_per_gas", DataType::Utf8, false),
+            Field::new("input", DataType::Binary, false),
+        ]))
+    }
+
+    pub fn record_batch(records: &[TxRecord]) -> Result<RecordBatch, ArrowError> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(
+                records.iter().map(|r| r.block_number),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                records.iter().map(|r| r.timestamp_us),
+            )),
+            Arc::new(UInt32Array::from_iter_values(
+                records.iter().map(|r| r.index),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                records.iter().map(|r| format!("{:?}", r.to)),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                records.iter().map(|r| r.value.to_string()),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                records.iter().map(|r| r.nonce),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                records.iter().map(|r| r.gas_limit),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                records.iter().map(|r| r.max_fee_per_gas.to_string()),
+            )),
+            Arc::new(BinaryArray::from_iter_values(
+                records.iter().map(|r| r.input.as_slice()),
+            )),
+        ];
+        RecordBatch::try_new(schema(), columns)
+    }
+}
+
+/// Exports decoded txs to a file from a background thread
+pub struct TxExporter {
+    tx: SyncSender<Vec<TxRecord>>,
+    writer: JoinHandle<io::Result<u64>>,
+    /// Feed messages dropped while the queue was full
+    dropped: AtomicU64,
+}
+
+impl TxExporter {
+    /// Export to a new file at `path`, Parquet if it ends `.parquet` (`parquet` feature) otherwise CSV
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let format = ExportFormat::from_path(&path);
+        Self::new(File::create(path)?, format)
+    }
+    /// Export to `writer` in `format`
+    pub fn new<W: Write + Send + 'static>(writer: W, format: ExportFormat) -> io::Result<Self> {
+        let sink = Sink::new(writer, format)?;
+        let (tx, rx) = sync_channel(EXPORT_QUEUE_SIZE);
+        let writer = thread::Builder::new()
+            .name("tx-export".to_string())
+            .spawn(move || write_loop(rx, sink))?;
+
+        Ok(Self {
+            tx,
+            writer,
+            dropped: AtomicU64::new(0),
+        })
+    }
+    /// Queue `txs` of the feed message at `block_number` for export, timestamped now
+    pub fn record<'t, 'a: 't>(
+        &self,
+        block_number: u64,
+        txs: impl IntoIterator<Item = &'t TransactionInfo<'a>>,
+    ) {
+        let timestamp_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let records: Vec<TxRecord> = txs
+            .into_iter()
+            .enumerate()
+            .map(|(index, tx)| TxRecord {
+                block_number,
+                timestamp_us,
+                index: index as u32,
+                to: tx.to,
+                value: tx.value,
+                nonce: tx.nonce,
+                gas_limit: tx.gas_limit,
+                max_fee_per_gas: tx.max_fee_per_gas,
+                input: tx.input.to_vec(),
+            })
+            .collect();
+        if records.is_empty() {
+            return;
+        }
+        if self.tx.try_send(records).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            debug!("export queue full, drop: #{block_number}");
+        }
+    }
+    /// Queue the txs of `batch` for export
+    pub fn record_batch(&self, batch: &DecodedBatch) {
+        self.record(batch.block_number(), batch.txs());
+    }
+    /// Feed messages dropped so far while the writer was behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+    /// Write out the queued txs and complete the file, returning the txs exported
+    pub fn finish(self) -> io::Result<u64> {
+        drop(self.tx);
+        self.writer.join().unwrap_or_else(|_| {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "export writer panicked",
+            ))
+        })
+    }
+}
+
+/// Write queued records to `sink` in batches until the queue closes, returning the records written
+fn write_loop<W: Write + Send>(rx: Receiver<Vec<TxRecord>>, mut sink: Sink<W>) -> io::Result<u64> {
+    let mut rows = Vec::<TxRecord>::with_capacity(WRITE_BATCH_ROWS);
+    let mut written = 0_u64;
+    loop {
+        match rx.recv_timeout(FLUSH_INTERVAL) {
+            Ok(records) => {
+                rows.extend(records);
+                if rows.len() < WRITE_BATCH_ROWS {
+                    continue;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) if rows.is_empty() => continue,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        sink.write(&rows)?;
+        written += rows.len() as u64;
+        rows.clear();
+    }
+    if !rows.is_empty() {
+        sink.write(&rows)?;
+        written += rows.len() as u64;
+    }
+    sink.finish()?;
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test {
+    use bumpalo::Bump;
+    use bytes::BytesMut;
+
+    use crate::{decode_feed_message, TxBuffer};
+
+    use super::*;
+
+    #[test]
+    fn exports_csv() {
+        let path = std::env::temp_dir().join("fulcrum-exports-csv.csv");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(ExportFormat::from_path(&path), ExportFormat::Csv);
+
+        let mut payload = BytesMut::from(&include_bytes!("../res/batch.json")[..]);
+        let bump = Bump::new();
+        let mut tx_buffer = TxBuffer::new(&bump);
+        let event = decode_feed_message(payload.as_mut(), &mut tx_buffer).unwrap();
+        let txs = tx_buffer.as_slice();
+        assert!(!txs.is_empty());
+
+        let exporter = TxExporter::create(&path).unwrap();
+        exporter.record(event.block_number(), txs);
+        // nothing to export
+        exporter.record(event.block_number(), &[]);
+        assert_eq!(exporter.finish().unwrap(), txs.len() as u64);
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), txs.len() + 1);
+        assert_eq!(lines[0], CSV_HEADER);
+        let columns: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(columns.len(), CSV_HEADER.split(',').count());
+        assert_eq!(columns[0], event.block_number().to_string());
+        assert!(columns[1].parse::<u64>().unwrap() > 0);
+        assert_eq!(columns[2], "0");
+        assert_eq!(columns[3], format!("{:?}", txs[0].to));
+        assert_eq!(columns[8], format!("0x{}", hex::encode(txs[0].input)));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,121 +1,556 @@
 //! low latency Arbitrum sequencer feed decoder
 #![cfg_attr(feature = "bench", feature(test))]
 #![allow(dead_code)]
-use std::time::Instant;
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
+use async_trait::async_trait;
+use bumpalo::collections;
+use bytes::BytesMut;
 use http::Uri;
-use log::{debug, error};
+use log::{debug, error, info, warn};
 use ws_tool::{
-    codec::{AsyncFrameCodec, PMDConfig},
+    codec::{AsyncFrameCodec, FrameConfig, PMDConfig},
     connector::{async_tcp_connect, async_wrap_tls, get_host, TlsStream},
     frame::{Header, OpCode, OwnedFrame},
+    protocol::standard_handshake_resp_check,
     ClientBuilder,
 };
 
 mod deser;
+pub mod export;
+mod mux;
+mod stream;
 mod types;
-use types::{decode_arbitrum_tx, FeedError};
-pub use types::{TransactionInfo, TxBuffer};
+#[cfg(feature = "verify-signature")]
+mod verify;
+#[cfg(feature = "verify-signature")]
+use ethers::types::Address;
+pub use export::{ExportFormat, TxExporter};
+use mux::FeedMux;
+pub use stream::DecodedBatch;
+use types::{
+    decode_arbitrum_tx, decode_batch_posting_report, decode_eth_deposit, decode_submit_retryable,
+    L1MsgType,
+};
+pub use types::{
+    decode_typed_tx, BatchLimits, BatchPostingReport, EthDeposit, FeedError, FeedEvent,
+    SignatureStatus, SubmitRetryable, Timestamps, TransactionInfo, TxArena, TxBuffer,
+    TypedTransaction, DEFAULT_TX_ARENA_CAPACITY, DEFAULT_TX_BUFFER_CAPACITY,
+};
+#[cfg(feature = "verify-signature")]
+pub use verify::verify_feed_message;
+pub use ws_tool::codec::WindowBit;
+
+/// Fuzzing entry points (see /fuzz)
+#[cfg(feature = "fuzz")]
+pub mod fuzz {
+    pub use crate::types::decode_arbitrum_tx;
+}
 
 /// Arbitrum one sequencer feed
 const SEQUENCER_WSS: &str = "wss://arb1.arbitrum.io/feed";
+/// Max. payload of a ws control frame (RFC 6455 5.5)
+const MAX_CONTROL_PAYLOAD: usize = 125;
+/// Delay before reconnecting a dropped feed endpoint
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+/// Default read buffer size of a feed connection (bytes)
+const DEFAULT_READ_BUFFER_SIZE: usize = 64 * 1024;
+/// Default max. payload of a feed frame (bytes)
+const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
 /// Arbitrum One nitro genesis block number
 /// https://github.com/OffchainLabs/arbitrum-subgraphs/blob/fa8e55b7aec8609b6c8a6cad704d44a0b2fde3b9/packages/subgraph-common/config/nitro-mainnet.json#L14
 const NITRO_GENESIS_BLOCK_NUMBER: u64 = 22_207_817_u64;
 
+/// A source of sequencer feed messages
+///
+/// Messages are owned by the caller so decoded txs may borrow from them i.e. zero copy
+#[async_trait(?Send)]
+pub trait FeedSource {
+    /// An owned feed message
+    type Message;
+    /// Await the next message from the feed
+    async fn next_message(&mut self) -> Result<Self::Message, FeedError>;
+    /// Decode txs from `message` into `tx_buffer`
+    ///
+    /// Returns the decoded event, L1 messages e.g. deposits are returned rather than buffered. Malformed messages
+    /// return their decode error
+    async fn handle_message<'bump: 'a, 'a>(
+        &mut self,
+        message: &'a mut Self::Message,
+        tx_buffer: &mut TxBuffer<'bump, 'a>,
+    ) -> Result<FeedEvent<'a>, FeedError>;
+    /// Close the feed on shutdown, no messages are received after
+    async fn close(&mut self) {}
+    /// Shift the block numbers of later messages by `blocks`, sources with fixed numbering ignore it
+    fn shift_block_numbers(&mut self, _blocks: i64) {}
+}
+
 /// Sequencer feed
 ///
 /// The caller should drive the feed by `await`ing on `next_message` and then
 /// passing the result to `handle_frame`
 /// This allows deserialization of feed messages as zero copy
 pub struct SequencerFeed {
-    pub client: AsyncFrameCodec<TlsStream>,
+    connection: FeedConnection,
+    /// Receive time of the latest message
+    received_at: Option<Instant>,
+    /// Detects messages missed by the connection(s)
+    sequence: SequenceTracker,
+    /// Block number of sequence number 1
+    genesis_block: u64,
+    /// Expected signer of feed messages, `None` skips verification
+    #[cfg(feature = "verify-signature")]
+    signer: Option<Address>,
+}
+
+/// Tracks the last sequence number handled, detecting dropped messages
+#[derive(Debug, Default)]
+struct SequenceTracker {
+    last: Option<u64>,
+}
+
+impl SequenceTracker {
+    /// Record `sequence_number` as handled
+    ///
+    /// Returns `FeedError::Gap` if messages were skipped since the last one, stale messages are ignored
+    fn observe(&mut self, sequence_number: u64) -> Result<(), FeedError> {
+        match self.last {
+            Some(last) if sequence_number <= last => Ok(()),
+            Some(last) if sequence_number > last + 1 => {
+                self.last = Some(sequence_number);
+                Err(FeedError::Gap {
+                    from: last + 1,
+                    to: sequence_number - 1,
+                })
+            }
+            _ => {
+                self.last = Some(sequence_number);
+                Ok(())
+            }
+        }
+    }
+    /// Record messages up to `sequence_number` as handled e.g. the rest of a frame of many messages
+    fn advance(&mut self, sequence_number: u64) {
+        self.last = self.last.max(Some(sequence_number));
+    }
+}
+
+/// Underlying feed connection(s)
+enum FeedConnection {
+    /// A single feed endpoint
+    Single(Box<FeedEndpoint>),
+    /// Many feed endpoints, deduplicated by sequence number
+    Multi(FeedMux),
+}
+
+/// A single feed endpoint connection, re-established whenever it drops
+struct FeedEndpoint {
+    uri: Uri,
+    config: FeedConfig,
+    client: AsyncFrameCodec<TlsStream>,
+    /// Preallocated pong payload
+    pong: [u8; MAX_CONTROL_PAYLOAD],
+}
+
+impl FeedEndpoint {
+    async fn connect(uri: Uri, config: FeedConfig) -> Self {
+        Self {
+            client: sequencer_feed_with_uri(&uri, &config).await,
+            uri,
+            config,
+            pong: [0_u8; MAX_CONTROL_PAYLOAD],
+        }
+    }
+    /// Await the next text frame and its receive time, control frames are answered here
+    ///
+    /// Connection failures are not surfaced, the endpoint is reconnected instead
+    async fn next_message(&mut self) -> Result<(Instant, OwnedFrame), FeedError> {
+        loop {
+            let result = match self.client.receive().await {
+                Ok(frame) => match frame.header().opcode() {
+                    OpCode::Text => return Ok((Instant::now(), frame)),
+                    OpCode::Ping => answer_ping(&mut self.client, &mut self.pong, frame).await,
+                    OpCode::Close => Err(FeedError::Closed),
+                    opcode => {
+                        debug!("unhandled frame: {:?}", opcode);
+                        Ok(())
+                    }
+                },
+                Err(err) => {
+                    error!("feed ws frame: {:?}", err);
+                    Err(FeedError::Internal)
+                }
+            };
+            if result.is_err() {
+                self.reconnect().await;
+            }
+        }
+    }
+    /// Close the connection with a close frame
+    async fn close(&mut self) {
+        if let Err(err) = self.client.send(OpCode::Close, &[]).await {
+            debug!("feed close: {:?}", err);
+            return;
+        }
+        let _ = self.client.flush().await;
+        info!("feed closed: {}", self.uri);
+    }
+    /// Re-establish the connection, retrying until it succeeds
+    async fn reconnect(&mut self) {
+        loop {
+            warn!("feed reconnecting: {}", self.uri);
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            if let Ok(client) = try_sequencer_feed_with_uri(&self.uri, &self.config).await {
+                info!("feed connected: {}", self.uri);
+                self.client = client;
+                // the first message is a huuge un-parasable JSON dump, drop it
+                let _ = self.client.receive().await;
+                return;
+            }
+        }
+    }
+}
+
+/// Answer the ping `frame` with a pong echoing its payload from the preallocated `pong` buffer
+///
+/// The frame is released before sending, returning its memory to the codec's receive buffer for the next message
+async fn answer_ping(
+    client: &mut AsyncFrameCodec<TlsStream>,
+    pong: &mut [u8; MAX_CONTROL_PAYLOAD],
+    frame: OwnedFrame,
+) -> Result<(), FeedError> {
+    let (_header, payload) = frame.parts();
+    let len = payload.len().min(MAX_CONTROL_PAYLOAD);
+    pong[..len].copy_from_slice(&payload[..len]);
+    drop(payload);
+    if let Err(err) = client.send(OpCode::Pong, &pong[..len]).await {
+        error!("feed pong: {:?}", err);
+        return Err(FeedError::Internal);
+    }
+    client.flush().await.map_err(|err| {
+        error!("feed pong flush: {:?}", err);
+        FeedError::Internal
+    })
+}
+
+/// Connection options for sequencer feed endpoints
+#[derive(Clone, Debug)]
+pub(crate) struct FeedConfig {
+    /// Read buffer size (bytes)
+    read_buffer_size: usize,
+    /// Max. payload of a received frame (bytes)
+    max_frame_size: usize,
+    /// permessage-deflate parameters, `None` disables compression
+    deflate: Option<PMDConfig>,
+    /// Additional TLS root certificates (PEM files)
+    root_certs: Vec<PathBuf>,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            deflate: Some(PMDConfig::default()),
+            root_certs: Default::default(),
+        }
+    }
+}
+
+/// Configures the frame and TLS options of a `SequencerFeed` connection
+///
+/// ```ignore
+/// let feed = SequencerFeed::builder()
+///     .read_buffer_size(256 * 1024)
+///     .deflate(false)
+///     .build()
+///     .await;
+/// ```
+#[derive(Clone, Debug)]
+pub struct SequencerFeedBuilder {
+    endpoints: Vec<Uri>,
+    config: FeedConfig,
+    genesis_block: u64,
+    #[cfg(feature = "verify-signature")]
+    signer: Option<Address>,
+}
+
+impl Default for SequencerFeedBuilder {
+    fn default() -> Self {
+        Self {
+            endpoints: vec![SEQUENCER_WSS.parse().unwrap()],
+            config: Default::default(),
+            genesis_block: NITRO_GENESIS_BLOCK_NUMBER,
+            #[cfg(feature = "verify-signature")]
+            signer: None,
+        }
+    }
+}
+
+impl SequencerFeedBuilder {
+    /// Connect to the given relay `endpoints` rather than the official Arbitrum One feed
+    pub fn endpoints(mut self, endpoints: &[Uri]) -> Self {
+        self.endpoints = endpoints.to_vec();
+        self
+    }
+    /// Set the connection read buffer size (bytes)
+    pub fn read_buffer_size(mut self, size: usize) -> Self {
+        self.config.read_buffer_size = size;
+        self
+    }
+    /// Set the max. payload of a received frame (bytes), larger frames drop the connection
+    pub fn max_frame_size(mut self, size: usize) -> Self {
+        self.config.max_frame_size = size;
+        self
+    }
+    /// Enable or disable permessage-deflate compression (default: enabled)
+    pub fn deflate(mut self, enabled: bool) -> Self {
+        self.config.deflate = enabled.then(|| self.config.deflate.take().unwrap_or_default());
+        self
+    }
+    /// Set the permessage-deflate window bits requested for the server and client
+    ///
+    /// Enables compression if it was disabled
+    pub fn deflate_window_bits(mut self, server: WindowBit, client: WindowBit) -> Self {
+        let deflate = self.config.deflate.get_or_insert_with(Default::default);
+        deflate.server_max_window_bits = server;
+        deflate.client_max_window_bits = client;
+        self
+    }
+    /// Number blocks from `genesis_block` i.e. the block of sequence number 1 (default: Arbitrum One nitro genesis)
+    pub fn genesis_block(mut self, genesis_block: u64) -> Self {
+        self.genesis_block = genesis_block;
+        self
+    }
+    /// Trust the root certificate(s) in the PEM file at `path` in addition to the default root store
+    pub fn root_cert(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.root_certs.push(path.into());
+        self
+    }
+    /// Verify feed message signatures against the sequencer address `signer`
+    ///
+    /// The status is exposed per message via `TxBuffer::signature`, verification adds latency so it is
+    /// intended for feeds relayed through untrusted third-party mirrors
+    #[cfg(feature = "verify-signature")]
+    pub fn verify_signer(mut self, signer: Address) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+    /// Connect the feed
+    ///
+    /// A single endpoint is connected before returning, many endpoints are connected in the background
+    pub async fn build(self) -> SequencerFeed {
+        match self.endpoints.as_slice() {
+            [uri] => {
+                let mut feed = SequencerFeed {
+                    connection: FeedConnection::Single(Box::new(
+                        FeedEndpoint::connect(uri.clone(), self.config).await,
+                    )),
+                    received_at: None,
+                    sequence: SequenceTracker::default(),
+                    genesis_block: self.genesis_block,
+                    #[cfg(feature = "verify-signature")]
+                    signer: self.signer,
+                };
+                // the first message is a huuge un-parasable JSON dump, drop it
+                feed.first_message().await;
+                feed
+            }
+            endpoints => SequencerFeed {
+                connection: FeedConnection::Multi(FeedMux::new(endpoints, self.config)),
+                received_at: None,
+                sequence: SequenceTracker::default(),
+                genesis_block: self.genesis_block,
+                #[cfg(feature = "verify-signature")]
+                signer: self.signer,
+            },
+        }
+    }
 }
 
 impl SequencerFeed {
+    /// Configure a sequencer feed connection
+    pub fn builder() -> SequencerFeedBuilder {
+        SequencerFeedBuilder::default()
+    }
     pub async fn arbitrum_one() -> Self {
-        // Arbitrum one sequencer feed
-        let uri = SEQUENCER_WSS.parse().unwrap();
-        let mut feed = Self {
-            client: sequencer_feed_with_uri(&uri).await,
-        };
-        // the first message is a huuge un-parasable JSON dump, drop it
-        feed.first_message().await;
-
-        feed
+        Self::builder().build().await
+    }
+    /// Sequencer feed over several relay `endpoints` e.g. the official feed plus third-party relays
+    ///
+    /// Messages are deduplicated by sequence number, delivering whichever copy arrives first.
+    /// Dropped endpoints are reconnected in the background, must be called from within a tokio runtime
+    pub fn with_endpoints(endpoints: &[Uri]) -> Self {
+        Self {
+            connection: FeedConnection::Multi(FeedMux::new(endpoints, FeedConfig::default())),
+            received_at: None,
+            sequence: SequenceTracker::default(),
+            genesis_block: NITRO_GENESIS_BLOCK_NUMBER,
+            #[cfg(feature = "verify-signature")]
+            signer: None,
+        }
     }
     /// await first message and drop it
     pub async fn first_message(&mut self) {
         let _ = self.next_message().await;
     }
     /// Await the next message from the feed
+    ///
+    /// Only text frames are returned, control frames are answered internally and dropped connections re-established
     pub async fn next_message(&mut self) -> Result<OwnedFrame, FeedError> {
-        match self.client.receive().await {
-            Ok(frame) => Ok(frame),
-            Err(err) => {
-                error!("feed ws frame: {:?}", err);
-                Err(FeedError::Internal)
-            }
+        let (received_at, frame) = match &mut self.connection {
+            FeedConnection::Single(endpoint) => endpoint.next_message().await,
+            FeedConnection::Multi(mux) => mux.next_message().await,
+        }?;
+        self.received_at = Some(received_at);
+        Ok(frame)
+    }
+    /// Close the feed connection(s) e.g. on shutdown, no messages are received after
+    pub async fn close(&mut self) {
+        match &mut self.connection {
+            FeedConnection::Single(endpoint) => endpoint.close().await,
+            FeedConnection::Multi(mux) => mux.close(),
         }
     }
+    /// Shift the block numbers derived from sequence numbers by `blocks` e.g. to match a node after a numbering change
+    pub fn shift_block_numbers(&mut self, blocks: i64) {
+        self.genesis_block = self.genesis_block.saturating_add_signed(blocks);
+        info!("feed blocks numbered from #{}", self.genesis_block);
+    }
+    /// Receive time of the latest message
+    pub fn received_at(&self) -> Option<Instant> {
+        self.received_at
+    }
+    /// Signature status of the feed message `payload`, it must be checked before decoding in place
+    #[cfg_attr(not(feature = "verify-signature"), allow(unused_variables))]
+    pub(crate) fn verify_signature(&self, payload: &[u8]) -> SignatureStatus {
+        #[cfg(feature = "verify-signature")]
+        if let Some(signer) = self.signer {
+            return verify_feed_message(payload, verify::ARBITRUM_ONE_CHAIN_ID, signer);
+        }
+        SignatureStatus::Unverified
+    }
     /// Handle next ws frame from the sequencer feed
+    ///
+    /// Returns `FeedError::Gap` (without decoding the message) if earlier messages were missed,
+    /// the feed continues from the message after it. Malformed messages return their decode error, their txs are
+    /// missed as with a gap
     pub async fn handle_frame<'bump: 'a, 'a>(
         &mut self,
         header: &Header,
         payload: &'a mut [u8],
         tx_buffer: &mut TxBuffer<'bump, 'a>,
-    ) -> Result<(), FeedError> {
+    ) -> Result<FeedEvent<'a>, FeedError> {
         match header.opcode() {
             OpCode::Text => {
                 let t0: Instant = Instant::now();
-                if let Ok(block_number) = decode_feed_message(payload, tx_buffer) {
-                    tx_buffer.set_block_number(block_number);
-                    debug!(
-                        "process feed tx: {:?} for ⛓{block_number}",
-                        Instant::now() - t0
-                    );
+                if let Some(sequence_number) = deser::sequence_number(payload) {
+                    self.sequence.observe(sequence_number)?;
                 }
+                let signature = self.verify_signature(payload);
+                let event = decode_feed_message_from(payload, tx_buffer, self.genesis_block)?;
+                let block_number = event.block_number();
+                if let FeedEvent::Messages(_) = event {
+                    self.sequence.advance(block_number + 1 - self.genesis_block);
+                }
+                tx_buffer.set_block_number(block_number);
+                tx_buffer.set_timestamps(self.received_at, Instant::now());
+                tx_buffer.set_signature(signature);
+                debug!(
+                    "process feed tx: {:?} for ⛓{block_number}",
+                    Instant::now() - t0
+                );
+                Ok(event)
             }
-            OpCode::Ping => {
-                self.client
-                    .send(OpCode::Pong, payload)
-                    .await
-                    .expect("pong ok");
-                self.client.flush().await.expect("flush ok");
-            }
-            OpCode::Pong => return Ok(()),
+            // pings are answered on receipt by `next_message`
+            OpCode::Ping | OpCode::Pong => Ok(FeedEvent::Empty),
             OpCode::Binary => {
                 debug!("unhandled binary frame: {:?}", header.opcode());
                 debug!("{:02x?}", payload);
-                return Ok(());
+                Ok(FeedEvent::Empty)
             }
-            OpCode::Close => return Err(FeedError::Closed),
-            OpCode::Continue => panic!("unhandled continuation frame"),
+            OpCode::Close => Err(FeedError::Closed),
             _ => {
                 debug!("unhandled frame: {:?}", header.opcode());
-                return Err(FeedError::Internal);
+                Err(FeedError::Internal)
             }
         }
+    }
+}
 
-        Ok(())
+#[async_trait(?Send)]
+impl FeedSource for SequencerFeed {
+    type Message = (Header, BytesMut);
+    async fn next_message(&mut self) -> Result<Self::Message, FeedError> {
+        SequencerFeed::next_message(self)
+            .await
+            .map(OwnedFrame::parts)
+    }
+    async fn handle_message<'bump: 'a, 'a>(
+        &mut self,
+        message: &'a mut Self::Message,
+        tx_buffer: &mut TxBuffer<'bump, 'a>,
+    ) -> Result<FeedEvent<'a>, FeedError> {
+        let (header, payload) = message;
+        self.handle_frame(header, payload.as_mut(), tx_buffer).await
+    }
+    async fn close(&mut self) {
+        SequencerFeed::close(self).await
+    }
+    fn shift_block_numbers(&mut self, blocks: i64) {
+        SequencerFeed::shift_block_numbers(self, blocks)
     }
 }
 
 /// Arbitrum sequencer feed from the given `uri`
-async fn sequencer_feed_with_uri(uri: &Uri) -> AsyncFrameCodec<TlsStream> {
-    let stream = async_tcp_connect(uri).await.expect("tcp connect ok");
-    let stream = async_wrap_tls(stream, get_host(uri).unwrap(), vec![])
+async fn sequencer_feed_with_uri(uri: &Uri, config: &FeedConfig) -> AsyncFrameCodec<TlsStream> {
+    try_sequencer_feed_with_uri(uri, config)
         .await
-        .expect("TLS support");
+        .expect("start client")
+}
 
-    // TODO: modify this to allow setting frame config
-    let client = ClientBuilder::new()
-        .extension(PMDConfig::default().ext_string())
-        .async_with_stream(uri.clone(), stream, AsyncFrameCodec::check_fn)
+/// Arbitrum sequencer feed from the given `uri`, returning an error if the connection fails
+async fn try_sequencer_feed_with_uri(
+    uri: &Uri,
+    config: &FeedConfig,
+) -> Result<AsyncFrameCodec<TlsStream>, FeedError> {
+    let host = get_host(uri).map_err(|err| {
+        error!("feed host ({uri}): {:?}", err);
+        FeedError::Internal
+    })?;
+    let stream = async_tcp_connect(uri).await.map_err(|err| {
+        error!("feed tcp connect ({uri}): {:?}", err);
+        FeedError::Internal
+    })?;
+    let stream = async_wrap_tls(stream, host, config.root_certs.clone())
         .await
-        .expect("start client");
+        .map_err(|err| {
+            error!("feed TLS ({uri}): {:?}", err);
+            FeedError::Internal
+        })?;
 
-    client
+    let frame_config = FrameConfig {
+        max_frame_payload_size: config.max_frame_size,
+        resize_size: config.read_buffer_size,
+        ..Default::default()
+    };
+    let mut builder = ClientBuilder::new();
+    if let Some(deflate) = config.deflate.as_ref() {
+        builder = builder.extension(deflate.ext_string());
+    }
+    builder
+        .async_with_stream(uri.clone(), stream, move |key, resp, stream| {
+            standard_handshake_resp_check(key.as_bytes(), &resp)?;
+            Ok(AsyncFrameCodec::new_with(stream, frame_config))
+        })
+        .await
+        .map_err(|err| {
+            error!("feed ws handshake ({uri}): {:?}", err);
+            FeedError::Internal
+        })
 }
 
 /// Decode a sequencer feed message
@@ -123,40 +558,136 @@ async fn sequencer_feed_with_uri(uri: &Uri) -> AsyncFrameCodec<TlsStream> {
 /// - `payload` of base64 encoded json bytes, the buffer will be used to decode in place
 /// - `tx_buffer` storage buffer to fill with decoded transaction info
 ///
-/// Returns the block number of the message, `0` indicates no txs
+/// Returns the decoded event, user txs are decoded into `tx_buffer` while other L1 message kinds are returned in the event.
+/// Frames of many messages return `FeedEvent::Messages`, with the event of each
 #[inline(always)]
-fn decode_feed_message<'bump: 'a, 'a>(
+pub fn decode_feed_message<'bump: 'a, 'a>(
     payload: &'a mut [u8],
     tx_buffer: &mut TxBuffer<'bump, 'a>,
-) -> Result<u64, FeedError> {
-    let (sequence_number, l2_msg) = deser::feed_json_from_input(payload);
-    if let Some(l2_msg) = l2_msg {
-        match base64_simd::forgiving_decode_inplace(l2_msg) {
-            Ok(l2_msg) => {
-                decode_arbitrum_tx(l2_msg, tx_buffer);
-            }
-            Err(_) => return Err(FeedError::InvalidBase64),
-        }
+) -> Result<FeedEvent<'a>, FeedError> {
+    decode_feed_message_from(payload, tx_buffer, NITRO_GENESIS_BLOCK_NUMBER)
+}
+
+/// Decode a sequencer feed message as `decode_feed_message`, numbering blocks from `genesis_block`
+/// i.e. the block of sequence number 1
+#[inline(always)]
+pub fn decode_feed_message_from<'bump: 'a, 'a>(
+    payload: &'a mut [u8],
+    tx_buffer: &mut TxBuffer<'bump, 'a>,
+    genesis_block: u64,
+) -> Result<FeedEvent<'a>, FeedError> {
+    #[cfg(feature = "tolerant-decode")]
+    if !deser::matches_fast_layout(payload) {
+        return decode_messages(
+            deser::feed_jsons_tolerant(payload)?.into_iter().map(Ok),
+            tx_buffer,
+            genesis_block,
+        );
+    }
+    decode_messages(deser::FeedMessages::new(payload), tx_buffer, genesis_block)
+}
+
+/// Decode the messages of a feed frame, see `decode_feed_message`
+#[inline(always)]
+fn decode_messages<'bump: 'a, 'a>(
+    mut messages: impl Iterator<Item = Result<deser::FeedJson<'a>, FeedError>>,
+    tx_buffer: &mut TxBuffer<'bump, 'a>,
+    genesis_block: u64,
+) -> Result<FeedEvent<'a>, FeedError> {
+    let first = match messages.next() {
+        Some(feed_json) => decode_message(feed_json?, tx_buffer, genesis_block)?,
+        None => return Ok(FeedEvent::Empty),
+    };
+    let second = match messages.next() {
+        Some(feed_json) => decode_message(feed_json?, tx_buffer, genesis_block)?,
+        None => return Ok(first),
+    };
+    // many messages, rare so their events are allocated in the tx buffer's arena
+    let mut events = collections::Vec::with_capacity_in(4, tx_buffer.bump());
+    events.push(first);
+    events.push(second);
+    for feed_json in messages {
+        events.push(decode_message(feed_json?, tx_buffer, genesis_block)?);
     }
 
+    Ok(FeedEvent::Messages(events.into_bump_slice()))
+}
+
+/// Decode a single feed message
+#[inline(always)]
+fn decode_message<'bump: 'a, 'a>(
+    feed_json: deser::FeedJson<'a>,
+    tx_buffer: &mut TxBuffer<'bump, 'a>,
+    genesis_block: u64,
+) -> Result<FeedEvent<'a>, FeedError> {
+    let deser::FeedJson {
+        sequence_number,
+        header,
+        delayed_messages_read,
+        l2_msg,
+    } = feed_json;
     if sequence_number == 0 {
-        Ok(0)
-    } else {
-        Ok(sequence_number + NITRO_GENESIS_BLOCK_NUMBER - 1)
+        return Ok(FeedEvent::Empty);
+    }
+    tx_buffer.set_delayed_messages_read(delayed_messages_read);
+    let block_number = sequence_number
+        .checked_add(genesis_block)
+        .and_then(|block_number| block_number.checked_sub(1))
+        .ok_or(FeedError::InvalidMessage)?;
+    let l2_msg = match l2_msg {
+        Some(l2_msg) => match base64_simd::forgiving_decode_inplace(l2_msg) {
+            Ok(l2_msg) => &*l2_msg,
+            Err(_) => return Err(FeedError::InvalidBase64),
+        },
+        None => {
+            return Ok(FeedEvent::Other {
+                block_number,
+                kind: header.kind,
+            })
+        }
+    };
+
+    match L1MsgType::quick_from(header.kind) {
+        L1MsgType::L2Message => {
+            decode_arbitrum_tx(l2_msg, tx_buffer)?;
+            Ok(FeedEvent::L2Message { block_number })
+        }
+        L1MsgType::EthDeposit => Ok(FeedEvent::EthDeposit {
+            block_number,
+            deposit: decode_eth_deposit(&header, l2_msg)?,
+        }),
+        L1MsgType::SubmitRetryable => Ok(FeedEvent::SubmitRetryable {
+            block_number,
+            retryable: decode_submit_retryable(&header, l2_msg)?,
+        }),
+        L1MsgType::BatchPostingReport => Ok(FeedEvent::BatchPostingReport {
+            block_number,
+            report: decode_batch_posting_report(l2_msg)?,
+        }),
+        L1MsgType::EndOfBlock => Ok(FeedEvent::EndOfBlock { block_number }),
+        _ => Ok(FeedEvent::Other {
+            block_number,
+            kind: header.kind,
+        }),
     }
 }
 
 #[cfg(test)]
 mod test {
     use bumpalo::Bump;
-    use ethers::types::{Address, U256};
+    use ethers::types::{Address, H256, U256};
     use hex_literal::hex;
-    use std::str::FromStr;
+    use std::{
+        str::FromStr,
+        time::{Duration, Instant},
+    };
 
     use crate::{
-        decode_feed_message, deser,
-        types::{decode_tx_info_legacy, TxBuffer},
-        TransactionInfo, NITRO_GENESIS_BLOCK_NUMBER,
+        decode_feed_message, decode_feed_message_from, decode_typed_tx, deser,
+        mux::SequenceFilter,
+        types::{decode_arbitrum_tx, decode_tx_info_legacy, FeedError, TxBuffer},
+        BatchPostingReport, EthDeposit, FeedEvent, SubmitRetryable, Timestamps, TransactionInfo,
+        TypedTransaction, NITRO_GENESIS_BLOCK_NUMBER,
     };
 
     #[test]
@@ -180,7 +711,10 @@ mod test {
                         255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
                         255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
                         255
-                    ]
+                    ],
+                    nonce: 1,
+                    gas_limit: 2_718_882,
+                    max_fee_per_gas: 105_000_000,
                 },
                 TransactionInfo {
                     to: Address::from_str("10acb149fac9867045ed6af86bb2e61f2602fa51").unwrap(),
@@ -188,7 +722,10 @@ mod test {
                     input: &[
                         130, 126, 57, 118, 0, 0, 0, 0, 0, 15, 3, 0, 4, 3, 128, 81, 2, 208, 91, 4,
                         64, 91, 0, 0, 0, 0, 0, 0, 18, 38, 20, 3, 214, 9, 210, 114
-                    ]
+                    ],
+                    nonce: 101_878,
+                    gas_limit: 15_680_000,
+                    max_fee_per_gas: 125_000_000,
                 },
                 TransactionInfo {
                     to: Address::from_str("bf22f0f184bccbea268df387a49ff5238dd23e40").unwrap(),
@@ -205,7 +742,10 @@ mod test {
                         0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 20, 7, 237, 127, 141,
                         220, 201, 8, 207, 251, 157, 162, 236, 244, 61, 240, 216, 249, 236, 138,
                         111, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
-                    ]
+                    ],
+                    nonce: 3,
+                    gas_limit: 3_525_568,
+                    max_fee_per_gas: 135_000_000,
                 },
                 TransactionInfo {
                     to: Address::from_str("7879e4523907bdaaf94416442d6a63a841181c91").unwrap(),
@@ -213,7 +753,10 @@ mod test {
                     input: &[
                         84, 54, 62, 125, 32, 4, 42, 127, 132, 64, 5, 192, 11, 2, 0, 10, 15, 66, 64,
                         0, 1, 244, 6, 18, 8, 4, 11, 2, 0, 50, 15, 66, 64, 0, 9, 196, 6, 18
-                    ]
+                    ],
+                    nonce: 2158,
+                    gas_limit: 8_000_000,
+                    max_fee_per_gas: 400_000_000,
                 },
                 TransactionInfo {
                     to: Address::from_str("e592427a0aece92de3edee1f18e0157c05861564").unwrap(),
@@ -232,7 +775,10 @@ mod test {
                         0, 0, 0, 0, 0, 0, 1, 234, 52, 4, 241, 195, 194, 192, 0, 0, 0, 0, 0, 0, 0,
                         0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
                         0, 0
-                    ]
+                    ],
+                    nonce: 23_406,
+                    gas_limit: 10_000_000,
+                    max_fee_per_gas: 200_000_000,
                 },
                 TransactionInfo {
                     to: Address::from_str("0x68b3465833fb72a70ecdf485e0e4c7bd8665fc45").unwrap(),
@@ -257,7 +803,10 @@ mod test {
                         172, 222, 182, 236, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
                         0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
                         0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
-                    ]
+                    ],
+                    nonce: 7683,
+                    gas_limit: 15_000_000,
+                    max_fee_per_gas: 425_000_000,
                 },
                 TransactionInfo {
                     to: Address::from_str("0x0000000001e4ef00d069e71d6ba041b0a16f7ea0").unwrap(),
@@ -292,7 +841,10 @@ mod test {
                         0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
                         0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
                         0, 0, 0, 0
-                    ]
+                    ],
+                    nonce: 155,
+                    gas_limit: 5_689_132,
+                    max_fee_per_gas: 135_000_000,
                 },
             ]
         );
@@ -311,34 +863,563 @@ mod test {
     #[test]
     fn bespoke_decode_feed_msg() {
         let mut batch_json = include_bytes!("../res/small.json").to_owned();
-        let (block_number, l2_msg) = deser::feed_json_from_input(batch_json.as_mut_slice());
-        assert_eq!(l2_msg.unwrap(), b"myawsomemessageyaysocool");
-        assert_eq!(block_number, 68938512 + NITRO_GENESIS_BLOCK_NUMBER - 1);
+        let feed_json = deser::feed_json_from_input(batch_json.as_mut_slice()).unwrap();
+        assert_eq!(feed_json.l2_msg.unwrap(), b"myawsomemessageyaysocool");
+        assert_eq!(feed_json.header.kind, 3);
+        assert_eq!(
+            feed_json.header.sender,
+            Address::from(hex!("a4b000000000000000000073657175656e636572"))
+        );
+        assert_eq!(feed_json.sequence_number, 68938512);
+        assert_eq!(feed_json.delayed_messages_read, 852409);
     }
 
     #[test]
     fn bespoke_decode_feed_msg_huuge() {
         let mut batch_json = include_bytes!("../res/huuge.json").to_owned();
-        let _l2_msg = deser::feed_json_from_input(batch_json.as_mut_slice());
+        let feed_json = deser::feed_json_from_input(batch_json.as_mut_slice()).unwrap();
+        assert_eq!(feed_json.delayed_messages_read, 841960);
     }
 
     #[test]
-    fn failing_tx() {
-        let buf = hex!("047862412af18da4c549549630887dba1af6c0f20000000000000000000000000000000000000000000000004563918244f40000");
+    fn bespoke_decode_feed_msg_malformed() {
+        let frame = include_bytes!("../res/small.json");
+        // truncated anywhere before the end of the message
+        for len in 80..frame.len() - 2 {
+            let mut truncated = frame[..len].to_owned();
+            assert_eq!(
+                deser::feed_json_from_input(truncated.as_mut_slice()),
+                Err(FeedError::InvalidJson),
+                "truncated at {len}"
+            );
+        }
+        // `kind` not a u8
+        for kind in [r#""kind":x3,"#, r#""kind":256,"#, r#""kind":99999,"#] {
+            let mut malformed = String::from_utf8_lossy(frame)
+                .replace(r#""kind":3,"#, kind)
+                .into_bytes();
+            assert_eq!(
+                deser::feed_json_from_input(malformed.as_mut_slice()),
+                Err(FeedError::InvalidJson)
+            );
+        }
+    }
+
+    #[test]
+    fn decode_brotli_batch() {
+        use std::io::Write;
+
+        let mut batch_json = include_bytes!("../res/batch.json").to_owned();
+        let feed_json = deser::feed_json_from_input(batch_json.as_mut_slice()).unwrap();
+        let l2_msg = base64_simd::forgiving_decode_inplace(feed_json.l2_msg.unwrap()).unwrap();
+        let bump = Bump::new();
+        let mut expected = TxBuffer::new(&bump);
+        decode_arbitrum_tx(l2_msg, &mut expected).unwrap();
+
+        // batch containing a single brotli compressed copy of the original message
+        let mut compressed = Vec::<u8>::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 11, 22);
+            writer.write_all(l2_msg).unwrap();
+        }
+        let mut batch = vec![3_u8];
+        batch.extend_from_slice(&(compressed.len() as u64 + 1).to_be_bytes());
+        batch.push(0);
+        batch.extend_from_slice(&compressed);
+        // truncated compressed payload
+        let mut corrupt = batch[..batch.len() - 10].to_vec();
+        corrupt[1..9].copy_from_slice(&(compressed.len() as u64 - 9).to_be_bytes());
+
+        let mut tx_info = TxBuffer::new(&bump);
+        decode_arbitrum_tx(&batch, &mut tx_info).unwrap();
+        assert!(!tx_info.as_slice().is_empty());
+        assert_eq!(tx_info.as_slice(), expected.as_slice());
+
+        // corrupt payloads are skipped
+        let mut tx_info = TxBuffer::new(&bump);
+        decode_arbitrum_tx(&corrupt, &mut tx_info).unwrap();
+        assert!(tx_info.as_slice().is_empty());
+    }
+
+    #[test]
+    fn decode_nested_batch() {
+        use std::io::Write;
+
+        // a batch of one brotli compressed `l2_msg`
+        let wrap = |l2_msg: &[u8]| {
+            let mut compressed = Vec::<u8>::new();
+            {
+                let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+                writer.write_all(l2_msg).unwrap();
+            }
+            let mut batch = vec![3_u8];
+            batch.extend_from_slice(&(compressed.len() as u64 + 1).to_be_bytes());
+            batch.push(0);
+            batch.extend_from_slice(&compressed);
+            batch
+        };
+        let mut batch_json = include_bytes!("../res/batch.json").to_owned();
+        let feed_json = deser::feed_json_from_input(batch_json.as_mut_slice()).unwrap();
+        let l2_msg = base64_simd::forgiving_decode_inplace(feed_json.l2_msg.unwrap()).unwrap();
+        let bump = Bump::new();
+        let mut expected = TxBuffer::new(&bump);
+        decode_arbitrum_tx(l2_msg, &mut expected).unwrap();
+
+        // the original batch nested 3 deep
+        let nested = wrap(&wrap(l2_msg));
+        let mut tx_info = TxBuffer::new(&bump);
+        tx_info.set_batch_limits(BatchLimits {
+            max_depth: 3,
+            ..Default::default()
+        });
+        decode_arbitrum_tx(&nested, &mut tx_info).unwrap();
+        assert_eq!(tx_info.as_slice(), expected.as_slice());
+
+        let mut tx_info = TxBuffer::new(&bump);
+        tx_info.set_batch_limits(BatchLimits {
+            max_depth: 2,
+            ..Default::default()
+        });
+        assert_eq!(
+            decode_arbitrum_tx(&nested, &mut tx_info),
+            Err(FeedError::BatchLimit)
+        );
+
+        // deeply nested batches error rather than overflow the stack
+        let mut nested = l2_msg.to_vec();
+        for _ in 0..64 {
+            nested = wrap(&nested);
+        }
+        let mut tx_info = TxBuffer::new(&bump);
+        assert_eq!(
+            decode_arbitrum_tx(&nested, &mut tx_info),
+            Err(FeedError::BatchLimit)
+        );
+    }
+
+    #[test]
+    fn decode_batch_limits() {
+        let mut batch_json = include_bytes!("../res/batch.json").to_owned();
+        let feed_json = deser::feed_json_from_input(batch_json.as_mut_slice()).unwrap();
+        let l2_msg = base64_simd::forgiving_decode_inplace(feed_json.l2_msg.unwrap()).unwrap();
+        let bump = Bump::new();
+
+        let mut tx_info = TxBuffer::new(&bump);
+        tx_info.set_batch_limits(BatchLimits {
+            max_tx_size: 16,
+            ..Default::default()
+        });
+        assert_eq!(
+            decode_arbitrum_tx(l2_msg, &mut tx_info),
+            Err(FeedError::BatchLimit)
+        );
+        let mut tx_info = TxBuffer::new(&bump);
+        tx_info.set_batch_limits(BatchLimits {
+            max_txs: 0,
+            ..Default::default()
+        });
+        assert_eq!(
+            decode_arbitrum_tx(l2_msg, &mut tx_info),
+            Err(FeedError::BatchLimit)
+        );
+
+        // entry length walks off the end of the batch
+        let mut batch = vec![3_u8];
+        batch.extend_from_slice(&64_u64.to_be_bytes());
+        batch.extend_from_slice(&[4, 0xf8, 0x6b]);
+        let mut tx_info = TxBuffer::new(&bump);
+        assert_eq!(
+            decode_arbitrum_tx(&batch, &mut tx_info),
+            Err(FeedError::InvalidMessage)
+        );
+        // entry length beyond any sensible message
+        batch[1..9].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert_eq!(
+            decode_arbitrum_tx(&batch, &mut tx_info),
+            Err(FeedError::BatchLimit)
+        );
+        assert!(tx_info.as_slice().is_empty());
+    }
+
+    #[cfg(feature = "safe-decode")]
+    #[test]
+    fn safe_decode_truncated_msg() {
         let bump = Bump::new();
         let mut tx_info = TxBuffer::new(&bump);
-        println!("{:?}", decode_tx_info_legacy(&buf));
-        assert!(false);
+        assert_eq!(
+            decode_arbitrum_tx(&[], &mut tx_info),
+            Err(FeedError::InvalidMessage)
+        );
+        // batch too short for its first message length
+        assert_eq!(
+            decode_arbitrum_tx(&[3, 0, 0, 0], &mut tx_info),
+            Err(FeedError::InvalidMessage)
+        );
+        assert!(tx_info.as_slice().is_empty());
+    }
+
+    #[test]
+    fn decode_feed_eth_deposit() {
+        let mut feed_json = include_bytes!("../res/deposit.json").to_owned();
+        let bump = Bump::new();
+        let mut tx_info = TxBuffer::new(&bump);
+
+        assert_eq!(
+            decode_feed_message(feed_json.as_mut_slice(), &mut tx_info),
+            Ok(FeedEvent::EthDeposit {
+                block_number: 68938513 + NITRO_GENESIS_BLOCK_NUMBER - 1,
+                deposit: EthDeposit {
+                    from: Address::from(hex!("1d3e2ab9b4ad0e1bd2ec1dd1b2e0cf9cb8b0e1b2")),
+                    to: Address::from(hex!("6c4ff37a1b1b0e6bfbd1e6a0c4bb0a3d5e8b2c47")),
+                    value: U256::from(100_000_000_000_000_000_u128),
+                },
+            })
+        );
+        // deposits are not user txs
+        assert!(tx_info.as_slice().is_empty());
+        assert_eq!(tx_info.delayed_messages_read(), 852410);
+    }
+
+    #[test]
+    fn decode_feed_from_genesis_block() {
+        let mut feed_json = include_bytes!("../res/deposit.json").to_owned();
+        let bump = Bump::new();
+        let mut tx_info = TxBuffer::new(&bump);
+
+        // sequence number 1 is block #1_000
+        assert_eq!(
+            decode_feed_message_from(feed_json.as_mut_slice(), &mut tx_info, 1_000)
+                .map(|event| event.block_number()),
+            Ok(68938513 + 1_000 - 1)
+        );
+    }
+
+    #[test]
+    fn decode_feed_submit_retryable() {
+        let mut feed_json = include_bytes!("../res/retryable.json").to_owned();
+        let bump = Bump::new();
+        let mut tx_info = TxBuffer::new(&bump);
+
+        assert_eq!(
+            decode_feed_message(feed_json.as_mut_slice(), &mut tx_info),
+            Ok(FeedEvent::SubmitRetryable {
+                block_number: 68938514 + NITRO_GENESIS_BLOCK_NUMBER - 1,
+                retryable: SubmitRetryable {
+                    from: Address::from(hex!("3f1eae7d46d88f08fc2f8ed27fcb2ab183eb3d0e")),
+                    to: Address::from(hex!("e592427a0aece92de3edee1f18e0157c05861564")),
+                    call_value: U256::from(50_000_000_000_000_000_u128),
+                    deposit: U256::from(60_000_000_000_000_000_u128),
+                    max_submission_fee: U256::from(200_000_000_000_000_u128),
+                    fee_refund_address: Address::from([0x11; 20]),
+                    beneficiary: Address::from([0x22; 20]),
+                    gas_limit: 300_000,
+                    max_fee_per_gas: U256::from(100_000_000_u64),
+                    data: &hex!("deadbeef"),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn decode_feed_batch_posting_report() {
+        let mut feed_json = include_bytes!("../res/batch-posting-report.json").to_owned();
+        let bump = Bump::new();
+        let mut tx_info = TxBuffer::new(&bump);
+
+        assert_eq!(
+            decode_feed_message(feed_json.as_mut_slice(), &mut tx_info),
+            Ok(FeedEvent::BatchPostingReport {
+                block_number: 68938515 + NITRO_GENESIS_BLOCK_NUMBER - 1,
+                report: BatchPostingReport {
+                    batch_timestamp: U256::from(1684207085_u64),
+                    batch_poster: Address::from(hex!("c1b634853cb333d3ad8663715b08f41a3aec47cc")),
+                    data_hash: H256::from([0xab; 32]),
+                    batch_number: 412345,
+                    l1_base_fee: U256::from(25_000_000_000_u64),
+                    extra_gas: 1234,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn decode_feed_end_of_block() {
+        let mut feed_json = include_bytes!("../res/end-of-block.json").to_owned();
+        let bump = Bump::new();
+        let mut tx_info = TxBuffer::new(&bump);
+
+        assert_eq!(
+            decode_feed_message(feed_json.as_mut_slice(), &mut tx_info),
+            Ok(FeedEvent::EndOfBlock {
+                block_number: 68938516 + NITRO_GENESIS_BLOCK_NUMBER - 1,
+            })
+        );
+        assert!(tx_info.as_slice().is_empty());
+    }
+
+    #[cfg(feature = "tolerant-decode")]
+    #[test]
+    fn tolerant_decode_schema_drift() {
+        for feed_json in [
+            &include_bytes!("../res/batch.json")[..],
+            include_bytes!("../res/deposit.json"),
+            include_bytes!("../res/end-of-block.json"),
+            include_bytes!("../res/huuge.json"),
+            include_bytes!("../res/small.json"),
+        ] {
+            assert!(deser::matches_fast_layout(feed_json));
+        }
+
+        // reordered and new fields
+        let mut drifted = br#"{"version":1,"messages":[{"message":{"delayedMessagesRead":852410,"message":{"l2Msg":"bE/zehsbDmv70eagxLsKPV6LLEcAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABY0V4XYoAAA==","header":{"sender":"0x1d3e2ab9b4ad0e1bd2ec1dd1b2e0cf9cb8b0e1b2","kind":12,"blockNumber":17269600,"timestamp":1684207200,"requestId":"0x000000000000000000000000000000000000000000000000000000000000000a","baseFeeL1":null,"extra":[1,2]}}},"sequenceNumber":68938513,"signature":null}]}"#.to_vec();
+        assert!(!deser::matches_fast_layout(&drifted));
+        let mut feed_json = include_bytes!("../res/deposit.json").to_owned();
+        let bump = Bump::new();
+        let mut expected = TxBuffer::new(&bump);
+        let mut tx_info = TxBuffer::new(&bump);
+
+        assert_eq!(
+            decode_feed_message(drifted.as_mut_slice(), &mut tx_info),
+            decode_feed_message(feed_json.as_mut_slice(), &mut expected),
+        );
+        assert_eq!(tx_info.delayed_messages_read(), 852410);
+
+        // truncated messages error rather than panic
+        let mut truncated = include_bytes!("../res/deposit.json")[..200].to_vec();
+        assert_eq!(
+            decode_feed_message(truncated.as_mut_slice(), &mut tx_info),
+            Err(FeedError::InvalidJson)
+        );
+    }
+
+    #[test]
+    fn decode_feed_many_messages() {
+        // a frame of the batch and deposit messages
+        let message = |feed_json: &[u8]| {
+            let feed_json = std::str::from_utf8(feed_json).unwrap().trim_end();
+            feed_json[25..feed_json.len() - 2].to_owned()
+        };
+        let mut frame = format!(
+            r#"{{"version":1,"messages":[{},{}]}}"#,
+            message(include_bytes!("../res/batch.json")),
+            message(include_bytes!("../res/deposit.json")),
+        )
+        .into_bytes();
+        assert_eq!(deser::FeedMessages::new(frame.as_mut_slice()).count(), 2);
+
+        let mut batch_json = include_bytes!("../res/batch.json").to_owned();
+        let mut deposit_json = include_bytes!("../res/deposit.json").to_owned();
+        let bump = Bump::new();
+        let mut expected = TxBuffer::new(&bump);
+        let batch = decode_feed_message(batch_json.as_mut_slice(), &mut expected).unwrap();
+        let deposit = decode_feed_message(deposit_json.as_mut_slice(), &mut expected).unwrap();
+        let mut tx_info = TxBuffer::new(&bump);
+
+        let event = decode_feed_message(frame.as_mut_slice(), &mut tx_info).unwrap();
+        assert_eq!(event, FeedEvent::Messages(&[batch, deposit]));
+        assert_eq!(event.block_number(), deposit.block_number());
+        assert_eq!(tx_info.as_slice(), expected.as_slice());
+        assert_eq!(tx_info.delayed_messages_read(), 852410);
+    }
+
+    #[test]
+    fn decode_feed_sequence_number_overflow() {
+        let mut frame = String::from_utf8_lossy(include_bytes!("../res/small.json"))
+            .replace("68938512", &u64::MAX.to_string())
+            .into_bytes();
+        let bump = Bump::new();
+        let mut tx_info = TxBuffer::new(&bump);
+
+        assert_eq!(
+            decode_feed_message(frame.as_mut_slice(), &mut tx_info),
+            Err(FeedError::InvalidMessage)
+        );
+    }
+
+    #[test]
+    fn decode_feed_confirmation() {
+        let mut confirmation =
+            br#"{"version":1,"confirmedSequenceNumberMessage":{"sequenceNumber":72346029}}"#
+                .to_owned();
+        let bump = Bump::new();
+        let mut tx_info = TxBuffer::new(&bump);
+
+        assert_eq!(
+            decode_feed_message(confirmation.as_mut_slice(), &mut tx_info),
+            Ok(FeedEvent::Empty)
+        );
+    }
+
+    #[test]
+    fn sequence_gaps() {
+        let mut sequence = SequenceTracker::default();
+        assert_eq!(sequence.observe(100), Ok(()));
+        assert_eq!(sequence.observe(101), Ok(()));
+        assert_eq!(
+            sequence.observe(105),
+            Err(FeedError::Gap { from: 102, to: 104 })
+        );
+        // continues from the latest message
+        assert_eq!(sequence.observe(106), Ok(()));
+        // stale or duplicate messages are not gaps
+        assert_eq!(sequence.observe(103), Ok(()));
+        assert_eq!(sequence.observe(106), Ok(()));
+        assert_eq!(sequence.observe(107), Ok(()));
+        // the rest of a frame of many messages
+        sequence.advance(110);
+        sequence.advance(108);
+        assert_eq!(sequence.observe(111), Ok(()));
+    }
+
+    #[test]
+    fn feed_sequence_number() {
+        let batch_json = include_bytes!("../res/small.json");
+        assert_eq!(deser::sequence_number(batch_json), Some(68938512));
+        // confirmations have no sequence number
+        let confirmation =
+            br#"{"version":1,"confirmedSequenceNumberMessage":{"sequenceNumber":72346029}}"#;
+        assert_eq!(deser::sequence_number(confirmation), None);
+    }
+
+    #[test]
+    fn tx_arena_reuses_memory() {
+        let mut arena = TxArena::with_capacity(1024);
+        let mut allocated = 0;
+        for round in 0..10 {
+            arena.reset();
+            let mut batch_json = include_bytes!("../res/batch.json").to_owned();
+            let mut tx_buffer = arena.tx_buffer();
+            assert!(decode_feed_message(batch_json.as_mut_slice(), &mut tx_buffer).is_ok());
+            assert!(!tx_buffer.as_slice().is_empty());
+            // memory is reused once the arena has grown to fit a message
+            if round == 1 {
+                allocated = arena.allocated_bytes();
+            }
+        }
+        assert_eq!(arena.allocated_bytes(), allocated);
+    }
+
+    #[test]
+    fn tx_arena_usage() {
+        let mut arena = TxArena::with_capacity(64 * 1024).with_tx_capacity(2);
+        assert_eq!((arena.capacity(), arena.tx_capacity()), (64 * 1024, 2));
+        assert_eq!(arena.used_bytes(), 0);
+        let mut batch_json = include_bytes!("../res/batch.json").to_owned();
+        let mut tx_buffer = arena.tx_buffer();
+        decode_feed_message(batch_json.as_mut_slice(), &mut tx_buffer).unwrap();
+        assert!(tx_buffer.as_slice().len() > 2);
+        assert!(arena.used_bytes() > 0);
+        drop(tx_buffer);
+        arena.reset();
+        assert_eq!(arena.used_bytes(), 0);
+    }
+
+    #[test]
+    fn tx_buffer_timestamps() {
+        let bump = Bump::new();
+        let mut tx_buffer = TxBuffer::new(&bump);
+        assert_eq!(tx_buffer.timestamps(), Timestamps::default());
+
+        let received = Instant::now();
+        let decoded = received + Duration::from_micros(50);
+        tx_buffer.set_timestamps(Some(received), decoded);
+        let timestamps = tx_buffer.timestamps();
+        assert_eq!(
+            timestamps.since_received(timestamps.decoded),
+            Some(Duration::from_micros(50))
+        );
+        // unstamped stages aren't measured
+        assert_eq!(timestamps.since_received(timestamps.submitted), None);
+        tx_buffer.set_timestamps(None, decoded);
+        let timestamps = tx_buffer.timestamps();
+        assert_eq!(timestamps.since_received(timestamps.decoded), None);
+    }
+
+    #[test]
+    fn sequence_filter_dedups() {
+        let mut filter = SequenceFilter::default();
+        assert!(filter.accept(100));
+        // duplicate from a slower endpoint
+        assert!(!filter.accept(100));
+        assert!(filter.accept(101));
+        // stale message
+        assert!(!filter.accept(99));
+        assert!(filter.accept(103));
+    }
+
+    #[test]
+    fn failing_tx() {
+        // not a tx envelope (deposit payload), must not panic
+        let buf = hex!("047862412af18da4c549549630887dba1af6c0f20000000000000000000000000000000000000000000000004563918244f40000");
+        assert_eq!(
+            decode_typed_tx(&buf),
+            Ok(TypedTransaction::Unsupported(0x04))
+        );
+        assert_eq!(decode_tx_info_legacy(&buf), None);
     }
 
     #[test]
     fn failing_tx2() {
         let buf = include_bytes!("../res/test.base64");
         let l2msg = base64_simd::forgiving_decode_to_vec(buf).unwrap();
-        println!("{:?}", l2msg);
+        // batch message given as tx, must not panic
+        assert!(decode_tx_info_legacy(l2msg.as_slice()).is_none());
+
         let bump = Bump::new();
         let mut tx_info = TxBuffer::new(&bump);
-        println!("{:?}", decode_tx_info_legacy(&l2msg.as_slice()));
+        decode_arbitrum_tx(l2msg.as_slice(), &mut tx_info).unwrap();
+        assert_eq!(
+            tx_info.as_slice()[0].to,
+            Address::from_str("def1c0ded9bec7f1a1670819833240f027b25eff").unwrap()
+        );
+    }
+
+    #[test]
+    fn decode_typed_tx_eip4844() {
+        let buf = hex!("03f89282a4b107830f42408405f5e1008252089482af49447d8a07e3bd95bd0d56f35241523fbab10584d0e30db0c001e1a0010000000000000000000000000000000000000000000000000000000000000080a01111111111111111111111111111111111111111111111111111111111111111a02222222222222222222222222222222222222222222222222222222222222222");
+        assert_eq!(
+            decode_typed_tx(&buf),
+            Ok(TypedTransaction::Eip4844(TransactionInfo {
+                to: Address::from_str("82af49447d8a07e3bd95bd0d56f35241523fbab1").unwrap(),
+                value: U256::from(5_u64),
+                input: &hex!("d0e30db0"),
+                nonce: 7,
+                gas_limit: 21_000,
+                max_fee_per_gas: 100_000_000,
+            }))
+        );
+    }
+
+    #[test]
+    fn decode_typed_tx_eip2930() {
+        let buf = hex!("01f8a482a4b1038405f5e10082ea609482af49447d8a07e3bd95bd0d56f35241523fbab180842e1a7d4df838f79482af49447d8a07e3bd95bd0d56f35241523fbab1e1a0000000000000000000000000000000000000000000000000000000000000000001a01111111111111111111111111111111111111111111111111111111111111111a02222222222222222222222222222222222222222222222222222222222222222");
+        assert_eq!(
+            decode_typed_tx(&buf),
+            Ok(TypedTransaction::Eip2930(TransactionInfo {
+                to: Address::from_str("82af49447d8a07e3bd95bd0d56f35241523fbab1").unwrap(),
+                value: U256::zero(),
+                input: &hex!("2e1a7d4d"),
+                nonce: 3,
+                gas_limit: 60_000,
+                max_fee_per_gas: 100_000_000,
+            }))
+        );
+    }
+
+    #[test]
+    fn decode_typed_tx_legacy_create() {
+        let buf = hex!("f858018405f5e1008307a120808085608060405283014985a01111111111111111111111111111111111111111111111111111111111111111a02222222222222222222222222222222222222222222222222222222222222222");
+        assert_eq!(decode_typed_tx(&buf), Ok(TypedTransaction::Create(0x00)));
+    }
+
+    #[test]
+    fn decode_typed_tx_malformed() {
+        assert_eq!(decode_typed_tx(&[]), Err(FeedError::InvalidRlp));
+        // truncated eip1559 tx
+        assert_eq!(
+            decode_typed_tx(&hex!("02f89282a4b107")),
+            Err(FeedError::InvalidRlp)
+        );
     }
 }
 
@@ -0,0 +1,138 @@
+//! Multi-endpoint sequencer feed connection manager
+//!
+//! Maintains a connection per relay endpoint and merges their messages into one stream.
+//! Messages are deduplicated by sequence number, whichever endpoint delivers a message first wins.
+use std::time::Instant;
+
+use http::Uri;
+use log::{debug, info, warn};
+use tokio::{sync::mpsc, task::JoinHandle};
+use ws_tool::frame::{OpCode, OwnedFrame};
+
+use crate::{
+    answer_ping, deser, try_sequencer_feed_with_uri, FeedConfig, FeedError, MAX_CONTROL_PAYLOAD,
+    RECONNECT_DELAY,
+};
+
+/// Max. frames buffered between the endpoint tasks and the consumer
+const FRAME_QUEUE_SIZE: usize = 256;
+
+/// Tracks the highest sequence number delivered so far
+#[derive(Debug, Default)]
+pub(crate) struct SequenceFilter {
+    last: u64,
+}
+
+impl SequenceFilter {
+    /// Returns true if `sequence_number` is new i.e. it has not been delivered by any endpoint
+    pub fn accept(&mut self, sequence_number: u64) -> bool {
+        if sequence_number > self.last {
+            self.last = sequence_number;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Merges frames from multiple sequencer feed endpoints
+pub(crate) struct FeedMux {
+    /// Text frames received from all endpoints, with their receive time
+    frames: mpsc::Receiver<(Instant, OwnedFrame)>,
+    /// Drops messages already delivered by another endpoint
+    filter: SequenceFilter,
+    /// Endpoint connection tasks
+    endpoints: Vec<JoinHandle<()>>,
+}
+
+impl FeedMux {
+    /// Start connections to all `uris` with the given connection `config`
+    ///
+    /// Must be called from within a tokio runtime
+    pub fn new(uris: &[Uri], config: FeedConfig) -> Self {
+        let (tx, frames) = mpsc::channel(FRAME_QUEUE_SIZE);
+        let endpoints = uris
+            .iter()
+            .map(|uri| tokio::spawn(endpoint_loop(uri.clone(), config.clone(), tx.clone())))
+            .collect();
+
+        Self {
+            frames,
+            filter: SequenceFilter::default(),
+            endpoints,
+        }
+    }
+    /// Await the next unseen message from any endpoint
+    ///
+    /// Messages without a sequence number (i.e. confirmations) carry no txs and are dropped
+    pub async fn next_message(&mut self) -> Result<(Instant, OwnedFrame), FeedError> {
+        loop {
+            let (received_at, frame) = self.frames.recv().await.ok_or(FeedError::Closed)?;
+            match deser::sequence_number(frame.payload()) {
+                Some(sequence_number) if self.filter.accept(sequence_number) => {
+                    return Ok((received_at, frame))
+                }
+                _ => continue,
+            }
+        }
+    }
+    /// Stop all endpoint connections, queued frames are dropped
+    pub fn close(&mut self) {
+        info!("feed closed: {} endpoints", self.endpoints.len());
+        for endpoint in self.endpoints.drain(..) {
+            endpoint.abort();
+        }
+        self.frames.close();
+    }
+}
+
+impl Drop for FeedMux {
+    fn drop(&mut self) {
+        for endpoint in &self.endpoints {
+            endpoint.abort();
+        }
+    }
+}
+
+/// Drive a single endpoint connection forwarding text frames to `frames`
+///
+/// Pings are answered here and the connection is re-established whenever it drops
+async fn endpoint_loop(uri: Uri, config: FeedConfig, frames: mpsc::Sender<(Instant, OwnedFrame)>) {
+    let mut pong = [0_u8; MAX_CONTROL_PAYLOAD];
+    loop {
+        if let Ok(mut client) = try_sequencer_feed_with_uri(&uri, &config).await {
+            info!("feed connected: {uri}");
+            // the first message is a huuge un-parasable JSON dump, drop it
+            let _ = client.receive().await;
+            loop {
+                let frame = match client.receive().await {
+                    Ok(frame) => frame,
+                    Err(err) => {
+                        warn!("feed ws frame ({uri}): {:?}", err);
+                        break;
+                    }
+                };
+                match frame.header().opcode() {
+                    OpCode::Text => {
+                        if frames.send((Instant::now(), frame)).await.is_err() {
+                            // consumer is gone
+                            return;
+                        }
+                    }
+                    OpCode::Ping => {
+                        if answer_ping(&mut client, &mut pong, frame).await.is_err() {
+                            break;
+                        }
+                    }
+                    OpCode::Close => break,
+                    opcode => debug!("unhandled frame ({uri}): {:?}", opcode),
+                }
+            }
+        }
+        if frames.is_closed() {
+            return;
+        }
+        warn!("feed reconnecting: {uri}");
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
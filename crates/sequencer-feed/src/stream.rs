@@ -0,0 +1,167 @@
+//! Stream interface over the sequencer feed
+//!
+//! Each item owns its raw feed message and decoding arena so txs can borrow from them i.e. zero copy.
+//! Arenas are recycled once an item is dropped
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+};
+
+use bumpalo::Bump;
+use bytes::BytesMut;
+use futures::{stream, Stream};
+use log::debug;
+use ws_tool::frame::OwnedFrame;
+
+use crate::{
+    decode_feed_message, FeedEvent, SequencerFeed, SignatureStatus, TransactionInfo, TxBuffer,
+};
+
+/// Initial capacity of a decoding arena
+const BUMP_CAPACITY: usize = 1024 * 1_000;
+/// Max. idle arenas kept for reuse
+const BUMP_POOL_SIZE: usize = 4;
+
+/// Idle decoding arenas
+type BumpPool = Arc<Mutex<Vec<Bump>>>;
+
+/// A decoded sequencer feed message
+pub struct DecodedBatch {
+    /// The decoded txs, borrowing from `payload` and `bump`
+    txs: &'static [TransactionInfo<'static>],
+    /// The decoded event, borrowing from `payload`
+    event: FeedEvent<'static>,
+    /// Signature status of the message
+    signature: SignatureStatus,
+    /// The raw feed message, decoded in place
+    payload: BytesMut,
+    /// Arena holding `txs` (and any decompressed data), taken on drop
+    bump: Option<Bump>,
+    /// Receives `bump` on drop
+    pool: BumpPool,
+}
+
+impl DecodedBatch {
+    /// Decode the feed message `payload` using `bump` for allocations
+    fn decode(
+        mut payload: BytesMut,
+        signature: SignatureStatus,
+        bump: Bump,
+        pool: BumpPool,
+    ) -> Self {
+        let mut tx_buffer = TxBuffer::new(&bump);
+        let event = match decode_feed_message(payload.as_mut(), &mut tx_buffer) {
+            Ok(event) => event,
+            Err(err) => {
+                debug!("feed message: {:?}", err);
+                FeedEvent::Empty
+            }
+        };
+        let txs = tx_buffer.into_slice();
+        // SAFETY: `payload` and `bump` are heap allocations which are neither moved nor mutated while `Self` lives,
+        // borrows are only handed out bound to `&self`
+        let (txs, event) = unsafe {
+            (
+                mem::transmute::<&[TransactionInfo], &'static [TransactionInfo<'static>]>(txs),
+                mem::transmute::<FeedEvent, FeedEvent<'static>>(event),
+            )
+        };
+
+        Self {
+            txs,
+            event,
+            signature,
+            payload,
+            bump: Some(bump),
+            pool,
+        }
+    }
+    /// The L2 block number of the message, `0` if there is none
+    pub fn block_number(&self) -> u64 {
+        self.event.block_number()
+    }
+    /// The decoded txs
+    pub fn txs(&self) -> &[TransactionInfo<'_>] {
+        self.txs
+    }
+    /// The decoded event
+    pub fn event(&self) -> &FeedEvent<'_> {
+        &self.event
+    }
+    /// The signature status of the message
+    pub fn signature(&self) -> SignatureStatus {
+        self.signature
+    }
+}
+
+impl Drop for DecodedBatch {
+    fn drop(&mut self) {
+        self.txs = &[];
+        self.event = FeedEvent::Empty;
+        if let Some(mut bump) = self.bump.take() {
+            bump.reset();
+            if let Ok(mut pool) = self.pool.lock() {
+                if pool.len() < BUMP_POOL_SIZE {
+                    pool.push(bump);
+                }
+            }
+        }
+    }
+}
+
+impl SequencerFeed {
+    /// Consume the feed as a stream of decoded messages
+    ///
+    /// Control frames are handled internally and messages without a block (e.g. confirmations) are skipped.
+    /// The stream ends when the connection closes
+    pub fn stream(self) -> impl Stream<Item = DecodedBatch> {
+        let pool = BumpPool::default();
+        stream::unfold((self, pool), |(mut feed, pool)| async move {
+            loop {
+                // only text frames are received
+                let (_header, payload) = feed.next_message().await.ok().map(OwnedFrame::parts)?;
+                let bump = pool
+                    .lock()
+                    .ok()
+                    .and_then(|mut pool| pool.pop())
+                    .unwrap_or_else(|| Bump::with_capacity(BUMP_CAPACITY));
+                let signature = feed.verify_signature(&payload);
+                let batch = DecodedBatch::decode(payload, signature, bump, Arc::clone(&pool));
+                if batch.block_number() != 0 {
+                    return Some((batch, (feed, pool)));
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::NITRO_GENESIS_BLOCK_NUMBER;
+
+    use super::*;
+
+    #[test]
+    fn decoded_batch_recycles_bump() {
+        let pool = BumpPool::default();
+        let payload = BytesMut::from(&include_bytes!("../res/batch.json")[..]);
+        let batch = DecodedBatch::decode(
+            payload,
+            SignatureStatus::Unverified,
+            Bump::new(),
+            Arc::clone(&pool),
+        );
+
+        assert_eq!(
+            batch.block_number(),
+            66208255 + NITRO_GENESIS_BLOCK_NUMBER - 1
+        );
+        assert!(!batch.txs().is_empty());
+        assert!(matches!(batch.event(), FeedEvent::L2Message { .. }));
+        assert_eq!(batch.signature(), SignatureStatus::Unverified);
+        assert!(pool.lock().unwrap().is_empty());
+
+        drop(batch);
+        assert_eq!(pool.lock().unwrap().len(), 1);
+    }
+}
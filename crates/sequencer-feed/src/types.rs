@@ -1,27 +1,197 @@
 //! Sequencer feed types
 #![allow(dead_code)]
+use std::{
+    io::Read,
+    time::{Duration, Instant},
+};
+
+use brotli_decompressor::Decompressor;
 use bumpalo::{collections, Bump};
-use ethers::types::{Address, U256};
-use log::{debug, info, warn};
+use ethers::types::{Address, H256, U256};
+use log::debug;
 use rlp::Rlp;
 use serde::Deserialize;
 
+/// Prefix of a brotli compressed message in a batch
+const BROTLI_MESSAGE_HEADER_BYTE: u8 = 0;
+/// Max. size of an (uncompressed) L2 message
+const MAX_L2_MESSAGE_SIZE: usize = 256 * 1024;
+
+/// Limits on the batches of a feed message, messages exceeding them fail to decode with `FeedError::BatchLimit`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BatchLimits {
+    /// Max. txs decoded from a single feed message, also bounds the entries of any one batch
+    pub max_txs: usize,
+    /// Max. size of a batch entry (bytes)
+    pub max_tx_size: usize,
+    /// Max. nesting of batches i.e. batches of compressed batches, as nitro allows
+    pub max_depth: usize,
+}
+
+impl Default for BatchLimits {
+    fn default() -> Self {
+        Self {
+            max_txs: 1_024,
+            max_tx_size: MAX_L2_MESSAGE_SIZE,
+            max_depth: 16,
+        }
+    }
+}
+
+/// Latency timestamps of a feed message as it moves through the engine pipeline
+///
+/// NB: `received` is stamped in userspace as the ws codec yields the frame, kernel socket timestamps
+/// (`SO_TIMESTAMPING`) can't be read through the TLS stream
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Timestamps {
+    /// The feed message was received
+    pub received: Option<Instant>,
+    /// The message txs were decoded
+    pub decoded: Option<Instant>,
+    /// The txs were simulated against the price graph
+    pub simulated: Option<Instant>,
+    /// An order tx was submitted (or built, in dry run mode)
+    pub submitted: Option<Instant>,
+}
+
+impl Timestamps {
+    /// Duration from the message being received until `at`, `None` if either is unstamped
+    pub fn since_received(&self, at: Option<Instant>) -> Option<Duration> {
+        Some(at?.saturating_duration_since(self.received?))
+    }
+}
+
+/// Signature verification status of a feed message (see the `verify-signature` feature)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// Verification is disabled
+    #[default]
+    Unverified,
+    /// The message carried no signature
+    Unsigned,
+    /// Signed by the expected sequencer
+    Valid,
+    /// Bad signature or signed by someone else, the message may have been tampered with
+    Invalid,
+}
+
+/// Default capacity of a `TxArena` (bytes)
+pub const DEFAULT_TX_ARENA_CAPACITY: usize = 1024 * 1_000;
+/// Default initial tx capacity of a `TxBuffer`
+pub const DEFAULT_TX_BUFFER_CAPACITY: usize = 100;
+
+/// Owned arena backing `TxBuffer`s
+///
+/// Reset between feed messages to reuse its memory, the borrow checker ensures no tx buffers are alive
+/// ```ignore
+/// let mut arena = TxArena::default();
+/// loop {
+///     arena.reset();
+///     let mut tx_buffer = arena.tx_buffer();
+///     decode_feed_message(payload, &mut tx_buffer)?;
+/// }
+/// ```
+pub struct TxArena {
+    bump: Bump,
+    /// Bytes preallocated
+    capacity: usize,
+    /// Initial tx capacity of tx buffers handed out
+    tx_capacity: usize,
+    /// Limits applied to tx buffers handed out
+    batch_limits: BatchLimits,
+}
+
+impl Default for TxArena {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_TX_ARENA_CAPACITY)
+    }
+}
+
+impl TxArena {
+    /// An arena with `capacity` bytes preallocated
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bump: Bump::with_capacity(capacity),
+            capacity,
+            tx_capacity: DEFAULT_TX_BUFFER_CAPACITY,
+            batch_limits: BatchLimits::default(),
+        }
+    }
+    /// Preallocate room for `txs` txs in each tx buffer (default: 100)
+    pub fn with_tx_capacity(mut self, txs: usize) -> Self {
+        self.tx_capacity = txs;
+        self
+    }
+    /// Decode feed messages with `batch_limits` rather than the defaults
+    pub fn with_batch_limits(mut self, batch_limits: BatchLimits) -> Self {
+        self.batch_limits = batch_limits;
+        self
+    }
+    /// A new tx buffer allocating from the arena
+    pub fn tx_buffer<'bump, 'a>(&'bump self) -> TxBuffer<'bump, 'a>
+    where
+        'bump: 'a,
+    {
+        let mut tx_buffer = TxBuffer::with_capacity(&self.bump, self.tx_capacity);
+        tx_buffer.set_batch_limits(self.batch_limits);
+        tx_buffer
+    }
+    /// Free all tx buffers allocated from the arena, retaining its largest chunk of memory for reuse
+    pub fn reset(&mut self) {
+        self.bump.reset();
+    }
+    /// Bytes of memory held by the arena
+    pub fn allocated_bytes(&self) -> usize {
+        self.bump.allocated_bytes()
+    }
+    /// Bytes allocated from the arena since the last reset, over `capacity` the arena grew a chunk
+    pub fn used_bytes(&self) -> usize {
+        // Safety: only chunk lengths are read, the bump isn't allocated from meanwhile (it's !Sync)
+        unsafe { self.bump.iter_allocated_chunks_raw() }
+            .map(|(_, len)| len)
+            .sum()
+    }
+    /// Bytes preallocated by the arena
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// Initial tx capacity of its tx buffers
+    pub fn tx_capacity(&self) -> usize {
+        self.tx_capacity
+    }
+}
+
 /// Optimized buffer for deserialized transaction info
 pub struct TxBuffer<'bump, 'a> {
     /// The transaction info
     txs: collections::Vec<'bump, TransactionInfo<'a>>,
     /// The associated block number of the stored txs
     block_number: u64,
+    /// Pipeline timestamps of the message the txs were decoded from
+    timestamps: Timestamps,
+    /// Signature status of the message the txs were decoded from
+    signature: SignatureStatus,
+    /// Count of L1 delayed inbox messages sequenced as of the stored txs
+    delayed_messages_read: u64,
+    /// Limits on decoded batches
+    batch_limits: BatchLimits,
 }
 impl<'bump, 'a> TxBuffer<'bump, 'a>
 where
     'bump: 'a,
 {
     pub fn new(bump: &'bump Bump) -> Self {
-        // let bump = Bump::with_capacity((52 + 1024) * 1024); // 100kib buffer;
+        Self::with_capacity(bump, DEFAULT_TX_BUFFER_CAPACITY)
+    }
+    /// A buffer with room for `txs` txs before it reallocates
+    pub fn with_capacity(bump: &'bump Bump, txs: usize) -> Self {
         Self {
-            txs: collections::Vec::<'bump, TransactionInfo>::with_capacity_in(100, bump),
+            txs: collections::Vec::<'bump, TransactionInfo>::with_capacity_in(txs, bump),
             block_number: 0,
+            timestamps: Timestamps::default(),
+            signature: SignatureStatus::default(),
+            delayed_messages_read: 0,
+            batch_limits: BatchLimits::default(),
         }
     }
     /// Add a tx to the buffer
@@ -40,6 +210,49 @@ where
     pub fn block_number(&self) -> u64 {
         self.block_number
     }
+    /// Set the receive and decode timestamps of the message the txs were decoded from
+    pub fn set_timestamps(&mut self, received: Option<Instant>, decoded: Instant) {
+        self.timestamps.received = received;
+        self.timestamps.decoded = Some(decoded);
+    }
+    /// Get the pipeline timestamps of the stored txs
+    pub fn timestamps(&self) -> Timestamps {
+        self.timestamps
+    }
+    /// Set the signature status of the message the txs were decoded from
+    pub fn set_signature(&mut self, signature: SignatureStatus) {
+        self.signature = signature;
+    }
+    /// Get the signature status of the stored txs
+    pub fn signature(&self) -> SignatureStatus {
+        self.signature
+    }
+    /// Set the count of L1 delayed inbox messages sequenced as of the stored txs
+    pub fn set_delayed_messages_read(&mut self, delayed_messages_read: u64) {
+        self.delayed_messages_read = delayed_messages_read;
+    }
+    /// Get the count of L1 delayed inbox messages sequenced as of the stored txs
+    ///
+    /// Delayed messages (deposits, retryables, force-included txs) with an index below this are in L2 state
+    pub fn delayed_messages_read(&self) -> u64 {
+        self.delayed_messages_read
+    }
+    /// Set the limits on batches decoded into the buffer
+    pub fn set_batch_limits(&mut self, batch_limits: BatchLimits) {
+        self.batch_limits = batch_limits;
+    }
+    /// Get the limits on batches decoded into the buffer
+    pub fn batch_limits(&self) -> BatchLimits {
+        self.batch_limits
+    }
+    /// Consume the buffer returning the stored txs
+    pub(crate) fn into_slice(self) -> &'bump [TransactionInfo<'a>] {
+        self.txs.into_bump_slice()
+    }
+    /// The arena backing the buffer, for allocating decoded data which txs borrow from
+    pub(crate) fn bump(&self) -> &'bump Bump {
+        self.txs.bump()
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -50,6 +263,12 @@ pub enum FeedError {
     InvalidRlp,
     /// Invalid JSON during decoding
     InvalidJson,
+    /// Invalid L1 message fields during decoding
+    InvalidMessage,
+    /// A batch exceeded the configured `BatchLimits`
+    BatchLimit,
+    /// Messages `from..=to` (sequence numbers) were never received e.g. dropped during a reconnect
+    Gap { from: u64, to: u64 },
     /// Connection closed
     Closed,
     /// Some internal ws error
@@ -63,7 +282,7 @@ pub struct BroadcastMessage<'a> {
     // #[serde(skip)]
     // pub version: u64,
     #[serde(borrow = "'a")]
-    pub messages: Option<[BroadcastFeedMessage<'a>; 1]>,
+    pub messages: Option<Vec<BroadcastFeedMessage<'a>>>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Deserialize)]
@@ -81,8 +300,7 @@ pub struct BroadcastFeedMessage<'a> {
 pub struct MessageWithMetadata<'a> {
     #[serde(borrow = "'a")]
     pub message: L1IncomingMessageHeader<'a>,
-    // #[serde(skip)]
-    // pub delayed_messages_read: u64,
+    pub delayed_messages_read: u64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Deserialize)]
@@ -92,12 +310,11 @@ pub struct L1IncomingMessageHeader<'a> {
     pub l2msg: &'a [u8],
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Header {
     pub kind: u8,
-    // #[serde(skip)]
-    // pub sender: String,
+    pub sender: Address,
     // #[serde(skip)]
     // pub block_number: u64,
     // #[serde(skip)]
@@ -120,6 +337,189 @@ pub(crate) enum L1MsgType {
     BatchPostingReport = 13,
     Invalid = 0xFF,
 }
+impl L1MsgType {
+    pub(crate) fn quick_from(val: u8) -> Self {
+        match val {
+            3 => Self::L2Message,
+            6 => Self::EndOfBlock,
+            7 => Self::L2FundedByL1,
+            8 => Self::RollupEvent,
+            9 => Self::SubmitRetryable,
+            10 => Self::BatchForGasEstimation,
+            11 => Self::Initialize,
+            12 => Self::EthDeposit,
+            13 => Self::BatchPostingReport,
+            _ => Self::Invalid,
+        }
+    }
+}
+
+/// A decoded sequencer feed message
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FeedEvent<'a> {
+    /// The message carried nothing to decode e.g. a sequence number confirmation or control frame
+    Empty,
+    /// A batch of user txs, decoded into the tx buffer
+    L2Message { block_number: u64 },
+    /// ETH deposited from L1
+    EthDeposit {
+        block_number: u64,
+        deposit: EthDeposit,
+    },
+    /// A retryable ticket submitted from L1
+    SubmitRetryable {
+        block_number: u64,
+        retryable: SubmitRetryable<'a>,
+    },
+    /// A batch poster's report of L1 costs
+    BatchPostingReport {
+        block_number: u64,
+        report: BatchPostingReport,
+    },
+    /// The sequencer ended a block without user txs, the feed is at a block boundary
+    EndOfBlock { block_number: u64 },
+    /// Some other L1 message kind, not decoded
+    Other { block_number: u64, kind: u8 },
+    /// A frame of many messages e.g. a catch-up burst, the event of each in order
+    ///
+    /// User txs of all messages are decoded into the tx buffer, in order
+    Messages(&'a [FeedEvent<'a>]),
+}
+
+impl<'a> FeedEvent<'a> {
+    /// The L2 block number of the message, `0` if there is none
+    ///
+    /// The latest block number of a frame of many messages
+    pub fn block_number(&self) -> u64 {
+        match self {
+            Self::Empty => 0,
+            Self::Messages(events) => events.last().map_or(0, Self::block_number),
+            Self::L2Message { block_number }
+            | Self::EthDeposit { block_number, .. }
+            | Self::SubmitRetryable { block_number, .. }
+            | Self::BatchPostingReport { block_number, .. }
+            | Self::EndOfBlock { block_number }
+            | Self::Other { block_number, .. } => *block_number,
+        }
+    }
+}
+
+/// An ETH deposit from L1
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EthDeposit {
+    /// The L1 sender
+    pub from: Address,
+    /// The L2 recipient
+    pub to: Address,
+    /// Amount deposited (wei)
+    pub value: U256,
+}
+
+/// A retryable ticket submitted from L1
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SubmitRetryable<'a> {
+    /// The (aliased) L1 sender
+    pub from: Address,
+    /// The L2 call target, zero for contract deployments
+    pub to: Address,
+    /// Value of the L2 call
+    pub call_value: U256,
+    /// ETH deposited from L1 to fund the ticket
+    pub deposit: U256,
+    /// The max. fee for ticket submission
+    pub max_submission_fee: U256,
+    /// Receives any excess fees
+    pub fee_refund_address: Address,
+    /// Receives the call value if the ticket is never redeemed
+    pub beneficiary: Address,
+    pub gas_limit: u64,
+    pub max_fee_per_gas: U256,
+    /// L2 call data
+    pub data: &'a [u8],
+}
+
+/// Report of a batch posted to L1
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BatchPostingReport {
+    pub batch_timestamp: U256,
+    pub batch_poster: Address,
+    pub data_hash: H256,
+    pub batch_number: u64,
+    pub l1_base_fee: U256,
+    /// Extra gas charged to the batch poster, absent on older reports
+    pub extra_gas: u64,
+}
+
+/// Read the 32 byte word at `offset` of `buf`
+fn read_word(buf: &[u8], offset: usize) -> Result<&[u8], FeedError> {
+    buf.get(offset..offset + 32)
+        .ok_or(FeedError::InvalidMessage)
+}
+
+/// Read the left padded address at `offset` of `buf`
+fn read_address_256(buf: &[u8], offset: usize) -> Result<Address, FeedError> {
+    read_word(buf, offset).map(|word| Address::from_slice(&word[12..]))
+}
+
+/// Read the word at `offset` of `buf` as a u64, failing if it overflows
+fn read_u64_256(buf: &[u8], offset: usize) -> Result<u64, FeedError> {
+    let word = read_word(buf, offset)?;
+    if word[..24].iter().any(|b| *b != 0) {
+        return Err(FeedError::InvalidMessage);
+    }
+    Ok(u64::from_be_bytes(word[24..].try_into().unwrap()))
+}
+
+/// Decode an ETH deposit L1 message from `buf` (`to || value`)
+pub(crate) fn decode_eth_deposit(header: &Header, buf: &[u8]) -> Result<EthDeposit, FeedError> {
+    let to = buf.get(..20).ok_or(FeedError::InvalidMessage)?;
+    Ok(EthDeposit {
+        from: header.sender,
+        to: Address::from_slice(to),
+        value: U256::from_big_endian(read_word(buf, 20)?),
+    })
+}
+
+/// Decode a submit retryable L1 message from `buf`
+pub(crate) fn decode_submit_retryable<'a>(
+    header: &Header,
+    buf: &'a [u8],
+) -> Result<SubmitRetryable<'a>, FeedError> {
+    let data_length = read_u64_256(buf, 256)? as usize;
+    let data = buf
+        .get(288..288_usize.saturating_add(data_length))
+        .ok_or(FeedError::InvalidMessage)?;
+
+    Ok(SubmitRetryable {
+        from: header.sender,
+        to: read_address_256(buf, 0)?,
+        call_value: U256::from_big_endian(read_word(buf, 32)?),
+        deposit: U256::from_big_endian(read_word(buf, 64)?),
+        max_submission_fee: U256::from_big_endian(read_word(buf, 96)?),
+        fee_refund_address: read_address_256(buf, 128)?,
+        beneficiary: read_address_256(buf, 160)?,
+        gas_limit: read_u64_256(buf, 192)?,
+        max_fee_per_gas: U256::from_big_endian(read_word(buf, 224)?),
+        data,
+    })
+}
+
+/// Decode a batch posting report L1 message from `buf`
+pub(crate) fn decode_batch_posting_report(buf: &[u8]) -> Result<BatchPostingReport, FeedError> {
+    // the batch poster address is packed (20 bytes), the remaining fields are words
+    let batch_poster = buf.get(32..52).ok_or(FeedError::InvalidMessage)?;
+    Ok(BatchPostingReport {
+        batch_timestamp: U256::from_big_endian(read_word(buf, 0)?),
+        batch_poster: Address::from_slice(batch_poster),
+        data_hash: H256::from_slice(read_word(buf, 52)?),
+        batch_number: read_u64_256(buf, 84)?,
+        l1_base_fee: U256::from_big_endian(read_word(buf, 116)?),
+        extra_gas: buf
+            .get(148..156)
+            .map(|b| u64::from_be_bytes(b.try_into().unwrap()))
+            .unwrap_or_default(),
+    })
+}
 
 #[derive(Debug)]
 pub(crate) enum L2MsgKind {
@@ -152,24 +552,76 @@ impl L2MsgKind {
 }
 
 /// Subset of transaction fields useful for the trading engine
-#[derive(Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct TransactionInfo<'a> {
     pub to: Address,
     pub value: U256,
     pub input: &'a [u8],
+    pub nonce: u64,
+    pub gas_limit: u64,
+    /// The max. fee per gas (EIP-1559) or gas price (legacy)
+    pub max_fee_per_gas: u128,
 }
 
-// NB: we don't use proper error/option in this functions because a the input should always be well formed or Arbitrum goes down
-// and 2 for performance.
+/// A decoded Ethereum transaction by envelope type
+#[derive(Debug, PartialEq)]
+pub enum TypedTransaction<'a> {
+    /// Legacy (untyped) transaction
+    Legacy(TransactionInfo<'a>),
+    /// Type 0x01 access list transaction
+    Eip2930(TransactionInfo<'a>),
+    /// Type 0x02 dynamic fee transaction
+    Eip1559(TransactionInfo<'a>),
+    /// Type 0x03 blob transaction
+    Eip4844(TransactionInfo<'a>),
+    /// Contract deployment of the given tx type, it has no `to` address
+    Create(u8),
+    /// Unrecognized tx type
+    Unsupported(u8),
+}
+
+impl<'a> TypedTransaction<'a> {
+    /// Return the tx info if the tx is a call
+    pub fn into_info(self) -> Option<TransactionInfo<'a>> {
+        match self {
+            Self::Legacy(tx_info)
+            | Self::Eip2930(tx_info)
+            | Self::Eip1559(tx_info)
+            | Self::Eip4844(tx_info) => Some(tx_info),
+            Self::Create(_) | Self::Unsupported(_) => None,
+        }
+    }
+}
+
+// NB: reads are unchecked by default because the input should always be well formed or Arbitrum goes down
+// and 2 for performance. Build with the `safe-decode` feature to bounds check them instead.
 /// Decode a `Transaction` from the sequencer feed
-pub(crate) fn decode_arbitrum_tx<'bump: 'a, 'a>(
+///
+/// Errors on truncated messages with the `safe-decode` feature only
+pub fn decode_arbitrum_tx<'bump: 'a, 'a>(
+    buf: &'a [u8],
+    tx_buffer: &mut TxBuffer<'bump, 'a>,
+) -> Result<(), FeedError> {
+    decode_l2_msg(buf, tx_buffer, 0)
+}
+
+/// Decode an L2 message nested in `depth` batches, see `decode_arbitrum_tx`
+///
+/// Errors with `FeedError::BatchLimit` if batches nest deeper than the tx buffer's `BatchLimits`
+fn decode_l2_msg<'bump: 'a, 'a>(
     buf: &'a [u8],
     tx_buffer: &mut TxBuffer<'bump, 'a>,
-) {
-    let kind = L2MsgKind::quick_from(unsafe { *buf.get_unchecked(0) });
+    depth: usize,
+) -> Result<(), FeedError> {
+    let kind = L2MsgKind::quick_from(byte_at(buf, 0)?);
     // debug!("outer kind: {:?}", kind);
     match kind {
-        L2MsgKind::Batch => decode_batch(&buf[1..], tx_buffer),
+        L2MsgKind::Batch => {
+            if depth >= tx_buffer.batch_limits().max_depth {
+                return Err(FeedError::BatchLimit);
+            }
+            decode_batch(&buf[1..], tx_buffer, depth + 1)?
+        }
         L2MsgKind::SignedTx => {
             if let Some(tx_info) = decode_tx_info_legacy(&buf[1..]) {
                 tx_buffer.push(tx_info);
@@ -182,184 +634,219 @@ pub(crate) fn decode_arbitrum_tx<'bump: 'a, 'a>(
             debug!("unhandled l2 msg");
         }
     }
+    Ok(())
 }
 
-/// Decode a batch of RLP encoded transactions from `buf` into `tx_buffer`
-pub(crate) fn decode_batch<'bump: 'a, 'a>(buf: &'a [u8], tx_buffer: &mut TxBuffer<'bump, 'a>) {
+/// Decode a batch of RLP encoded transactions, nested in `depth` batches, from `buf` into `tx_buffer`
+fn decode_batch<'bump: 'a, 'a>(
+    buf: &'a [u8],
+    tx_buffer: &mut TxBuffer<'bump, 'a>,
+    depth: usize,
+) -> Result<(), FeedError> {
+    // entries are length prefixed (u64 BE), lengths come off the wire so are always bounds checked
+    let limits = tx_buffer.batch_limits();
     let mut offset: usize = 0;
-    // The batch size depends on tx size but we don't know how that translates to tx count exactly
-    // MaxL2MessageSize = 256 * 1024
-    let len = buf.len();
-    for _ in 0..128 {
-        let msg_length = as_usize(&buf[offset..]);
-        offset += 8_usize;
-        // let kind: L2MsgKind = L2MsgKind::quick_from(buf[offset]);
+    let mut entries: usize = 0;
+    loop {
+        let length: [u8; 8] = buf
+            .get(offset..offset + 8)
+            .and_then(|length| length.try_into().ok())
+            .ok_or(FeedError::InvalidMessage)?;
+        let msg_length = u64::from_be_bytes(length);
+        entries += 1;
+        if msg_length > limits.max_tx_size as u64 || entries > limits.max_txs {
+            return Err(FeedError::BatchLimit);
+        }
+        offset += 8;
+        // msg_length <= max_tx_size, no overflow
+        let msg = buf
+            .get(offset..offset + msg_length as usize)
+            .ok_or(FeedError::InvalidMessage)?;
+        // let kind: L2MsgKind = L2MsgKind::quick_from(msg[0]);
         // debug!("inner kind: {:?}", kind);
-        if let Some(tx_info) = decode_tx_info_legacy(&buf[offset + 1..]) {
-            tx_buffer.push(tx_info);
+        match msg.split_first() {
+            Some((&BROTLI_MESSAGE_HEADER_BYTE, compressed)) => {
+                match decompress_brotli(compressed, tx_buffer.bump()) {
+                    Some(l2_msg) => decode_l2_msg(l2_msg, tx_buffer, depth)?,
+                    None => debug!("invalid brotli batch"),
+                }
+            }
+            Some((_kind, tx)) => {
+                if let Some(tx_info) = decode_tx_info_legacy(tx) {
+                    tx_buffer.push(tx_info);
+                }
+            }
+            None => return Err(FeedError::InvalidMessage),
+        }
+        if tx_buffer.as_slice().len() > limits.max_txs {
+            return Err(FeedError::BatchLimit);
         }
 
-        offset += msg_length;
-        if offset + 9 > len {
+        offset += msg.len();
+        if offset >= buf.len() {
             break;
         }
     }
+    Ok(())
 }
 
-/// Decode Ethereum Transaction data from RLP `buf`
-/// Matches behaviour of the nitro node
-fn decode_tx_info(buf: &[u8]) -> Option<TransactionInfo> {
-    // list == legacy tx type
-    if buf[0] > 0x7f {
-        return decode_base_legacy(buf);
-    }
-    // if it is not enveloped then we need to use rlp.as_raw instead of rlp.data
-    let data = Rlp::new(buf).data().unwrap();
-    let first_byte = data[0];
-    let rest = &data[1..];
-
-    match first_byte {
-        2 => decode_base_eip1559(rest),
-        1 => decode_base_eip2930(rest),
-        _ => {
-            warn!("unhandled tx: {:02x?}", buf);
-            None
+/// Decompress the brotli encoded `buf` into a `bump` allocated buffer
+///
+/// Returns `None` if `buf` is invalid or decompresses beyond `MAX_L2_MESSAGE_SIZE`
+fn decompress_brotli<'bump>(buf: &[u8], bump: &'bump Bump) -> Option<&'bump [u8]> {
+    let mut decompressor = Decompressor::new(buf, 4096);
+    let mut out = collections::Vec::<u8>::with_capacity_in(buf.len() * 4, bump);
+    let mut chunk = [0_u8; 4096];
+    loop {
+        match decompressor.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => {
+                if out.len() + n > MAX_L2_MESSAGE_SIZE {
+                    return None;
+                }
+                out.extend_from_slice(&chunk[..n]);
+            }
+            Err(_) => return None,
         }
     }
+    if out.is_empty() {
+        return None;
+    }
+
+    Some(out.into_bump_slice())
 }
 
 /// Decode Ethereum Transaction data from RLP `buf`
 /// matches the behaviour of ethers-rs
 pub fn decode_tx_info_legacy(buf: &[u8]) -> Option<TransactionInfo> {
+    match decode_typed_tx(buf) {
+        Ok(tx) => tx.into_info(),
+        Err(err) => {
+            debug!("invalid tx {:?}: {:02x?}", err, buf);
+            None
+        }
+    }
+}
+
+/// Decode a typed transaction envelope from `buf` (legacy, 0x01, 0x02, 0x03)
+///
+/// Typed txs may be given as `type || rlp(fields)` or wrapped in an RLP string as ethers-rs does.
+/// Unrecognized tx types are returned as `TypedTransaction::Unsupported` rather than failing
+pub fn decode_typed_tx(buf: &[u8]) -> Result<TypedTransaction, FeedError> {
+    let first_byte = *buf.first().ok_or(FeedError::InvalidRlp)?;
     // list == legacy tx type
-    if buf[0] >= 0xc0 {
+    if first_byte >= 0xc0 {
         return decode_base_legacy(buf);
     }
-    // if it is not enveloped then we need to use rlp.as_raw instead of rlp.data
-    let buf = Rlp::new(buf);
-    let mut data: &[u8] = buf.as_raw();
-    let mut first_byte = data[0];
-    // tx may have longer bytes
-    if first_byte > 0x7f {
-        match buf.data() {
-            Ok(inner) => data = inner,
-            Err(_err) => {
-                info!("{:02x?}", data);
-                panic!();
-            }
-        }
-        first_byte = data[0];
-    }
-    match first_byte {
-        0x02 => {
-            let rest = &data[1..];
-            decode_base_eip1559(rest)
-        }
-        0x01 => {
-            let rest = &data[1..];
-            decode_base_eip2930(rest)
-        }
-        _ => {
-            info!("{:02x?}", buf);
-            unimplemented!();
-        }
+    // typed tx may be wrapped as an RLP string
+    let data = if first_byte > 0x7f {
+        Rlp::new(buf).data().map_err(|_| FeedError::InvalidRlp)?
+    } else {
+        buf
+    };
+    let (tx_type, rest) = data.split_first().ok_or(FeedError::InvalidRlp)?;
+    match *tx_type {
+        0x01 => decode_base_eip2930(rest),
+        0x02 => decode_base_eip1559(rest),
+        0x03 => decode_base_eip4844(rest),
+        tx_type => Ok(TypedTransaction::Unsupported(tx_type)),
     }
 }
 
+/// Read the byte at `index` of `buf`, unchecked unless the `safe-decode` feature is enabled
 #[inline(always)]
-fn as_usize(buf: &[u8]) -> usize {
-    // OPTIMIZATION: nothing sensible should ever be longer than 2 ** 16 so we ignore the other bytes
-    // ((unsafe { *buf.get_unchecked(28) } as usize) << 24)
-    //     + ((unsafe { *buf.get_unchecked(29) } as usize) << 16)
-    ((unsafe { *buf.get_unchecked(5) } as usize) << 16)
-        + ((unsafe { *buf.get_unchecked(6) } as usize) << 8)
-        + unsafe { *buf.get_unchecked(7) } as usize
+fn byte_at(buf: &[u8], index: usize) -> Result<u8, FeedError> {
+    #[cfg(feature = "safe-decode")]
+    {
+        buf.get(index).copied().ok_or(FeedError::InvalidMessage)
+    }
+    #[cfg(not(feature = "safe-decode"))]
+    {
+        Ok(unsafe { *buf.get_unchecked(index) })
+    }
 }
 
-/// Decodes fields of the type 2 transaction response starting at the RLP offset passed.
-/// Increments the offset for each element parsed.
+/// Decodes the common fields of an RLP tx field list
+///
+/// - `nonce_offset` position of the `nonce` field
+/// - `fee_offset` position of the gas price or max fee per gas field, followed by `gas`, `to`, `value`, and `input`
+///
+/// Returns `None` if the tx is a contract deployment i.e. has no `to` address
 #[inline]
-fn decode_base_eip1559(buf: &[u8]) -> Option<TransactionInfo> {
-    // self.chain_id = Some(buf.val_at(*offset)?);
-    //*offset += 1;
-    // self.nonce = buf.val_at(*offset)?;
-    //*offset += 1;
-    // self.max_priority_fee_per_gas = Some(buf.val_at(*offset)?);
-    //*offset += 1;
-    // self.max_fee_per_gas = Some(buf.val_at(*offset)?);
-    //*offset += 1;
-    // self.gas = buf.val_at(*offset)?;
-    //*offset += 1;
+fn decode_call_fields<'a>(
+    buf: &Rlp<'a>,
+    nonce_offset: usize,
+    fee_offset: usize,
+) -> Result<Option<TransactionInfo<'a>>, FeedError> {
+    let to = buf.at(fee_offset + 2).map_err(|_| FeedError::InvalidRlp)?;
+    if to.is_empty() {
+        return Ok(None);
+    }
+    let to = to.as_val().map_err(|_| FeedError::InvalidRlp)?;
+    let value = buf
+        .val_at(fee_offset + 3)
+        .map_err(|_| FeedError::InvalidRlp)?;
+    let input = buf
+        .at(fee_offset + 4)
+        .and_then(|input| input.data())
+        .map_err(|_| FeedError::InvalidRlp)?;
+    let nonce = buf
+        .val_at(nonce_offset)
+        .map_err(|_| FeedError::InvalidRlp)?;
+    let max_fee_per_gas = buf.val_at(fee_offset).map_err(|_| FeedError::InvalidRlp)?;
+    let gas_limit = buf
+        .val_at(fee_offset + 1)
+        .map_err(|_| FeedError::InvalidRlp)?;
+
+    Ok(Some(TransactionInfo {
+        to,
+        value,
+        input,
+        nonce,
+        gas_limit,
+        max_fee_per_gas,
+    }))
+}
+
+/// Decodes fields of the type 3 (blob) transaction
+#[inline]
+fn decode_base_eip4844(buf: &[u8]) -> Result<TypedTransaction, FeedError> {
+    // chain_id, nonce, max_priority_fee_per_gas, max_fee_per_gas, gas
     let buf = Rlp::new(buf);
-    let mut offset = 5;
-    let to = if let Ok(to) = buf.val_at(offset) {
-        to
-    } else {
-        return None;
-    };
-    offset += 1;
-    let value = buf.val_at(offset).unwrap();
-    offset += 1;
-    let input = Rlp::new(buf.at(offset).unwrap().as_raw())
-        .data()
-        .expect("data");
-    // self.access_list = Some(buf.val_at(*offset)?);
-    //*offset += 1;
-
-    Some(TransactionInfo { to, value, input })
-}
-
-/// Decodes fields of the type 1 transaction response based on the RLP offset passed.
-/// Increments the offset for each element parsed.
-fn decode_base_eip2930(buf: &[u8]) -> Option<TransactionInfo> {
-    // self.chain_id = Some(buf.val_at(*offset)?);
-    // *offset += 1;
-    // // self.nonce = buf.val_at(*offset)?;
-    // *offset += 1;
-    // // self.gas_price = Some(buf.val_at(*offset)?);
-    // *offset += 1;
-    // // self.gas = buf.val_at(*offset)?;
-    // *offset += 1;
+    Ok(match decode_call_fields(&buf, 1, 3)? {
+        Some(tx_info) => TypedTransaction::Eip4844(tx_info),
+        None => TypedTransaction::Create(0x03),
+    })
+}
+
+/// Decodes fields of the type 2 transaction
+#[inline]
+fn decode_base_eip1559(buf: &[u8]) -> Result<TypedTransaction, FeedError> {
+    // chain_id, nonce, max_priority_fee_per_gas, max_fee_per_gas, gas
     let buf = Rlp::new(buf);
-    let mut offset = 4;
-    let to = if let Ok(to) = buf.val_at(offset) {
-        to
-    } else {
-        return None;
-    };
-    offset += 1;
-    let value = buf.val_at(offset).unwrap();
-    offset += 1;
-    let input = buf.at(offset).unwrap().as_raw();
-    // self.access_list = Some(buf.val_at(*offset)?);
-    // *offset += 1;
+    Ok(match decode_call_fields(&buf, 1, 3)? {
+        Some(tx_info) => TypedTransaction::Eip1559(tx_info),
+        None => TypedTransaction::Create(0x02),
+    })
+}
 
-    Some(TransactionInfo { to, value, input })
+/// Decodes fields of the type 1 transaction
+fn decode_base_eip2930(buf: &[u8]) -> Result<TypedTransaction, FeedError> {
+    // chain_id, nonce, gas_price, gas
+    let buf = Rlp::new(buf);
+    Ok(match decode_call_fields(&buf, 1, 2)? {
+        Some(tx_info) => TypedTransaction::Eip2930(tx_info),
+        None => TypedTransaction::Create(0x01),
+    })
 }
 
-/// Decodes a legacy transaction starting at the RLP offset passed.
-/// Increments the offset for each element parsed.
+/// Decodes a legacy transaction
 #[inline]
-fn decode_base_legacy(buf: &[u8]) -> Option<TransactionInfo> {
-    // self.nonce = buf.val_at(*offset)?;
-    //*offset += 1;
-    // self.gas_price = Some(buf.val_at(*offset)?);
-    //*offset += 1;
-    // self.gas = buf.val_at(*offset)?;
-    //*offset += 1;
+fn decode_base_legacy(buf: &[u8]) -> Result<TypedTransaction, FeedError> {
+    // nonce, gas_price, gas
     let buf = Rlp::new(buf);
-    let mut offset = 3;
-    let to = if let Ok(to) = buf.val_at(offset) {
-        to
-    } else {
-        return None;
-    };
-    offset += 1;
-    let value = buf.val_at(offset).unwrap();
-    offset += 1;
-    let input = Rlp::new(buf.at(offset).unwrap().as_raw())
-        .data()
-        .expect("data");
-
-    Some(TransactionInfo { to, value, input })
+    Ok(match decode_call_fields(&buf, 0, 1)? {
+        Some(tx_info) => TypedTransaction::Legacy(tx_info),
+        None => TypedTransaction::Create(0x00),
+    })
 }
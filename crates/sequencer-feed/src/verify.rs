@@ -0,0 +1,213 @@
+//! Sequencer feed message signature verification
+//!
+//! The sequencer signs each broadcast message, a relay (e.g. third-party mirror) can't alter a signed message
+//! without it failing verification.
+//! Messages are re-parsed with serde here as the fast decoder skips the fields covered by the signature
+use ethers::{
+    types::{Address, Bytes, Signature, H256, U256},
+    utils::keccak256,
+};
+use rlp::RlpStream;
+use serde::Deserialize;
+
+use crate::{FeedError, SignatureStatus};
+
+/// Chain Id of Arbitrum One, included in signed message hashes
+pub(crate) const ARBITRUM_ONE_CHAIN_ID: u64 = 42_161;
+
+#[derive(Deserialize)]
+struct SignedBroadcast {
+    messages: Option<Vec<SignedFeedMessage>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignedFeedMessage {
+    sequence_number: u64,
+    message: MessageWithMetadata,
+    signature: Option<Bytes>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageWithMetadata {
+    message: L1IncomingMessage,
+    delayed_messages_read: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct L1IncomingMessage {
+    header: Header,
+    /// base64 encoded
+    #[serde(rename = "l2Msg")]
+    l2_msg: String,
+    batch_gas_cost: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Header {
+    kind: u8,
+    sender: Address,
+    block_number: u64,
+    timestamp: u64,
+    request_id: Option<H256>,
+    #[serde(rename = "baseFeeL1")]
+    base_fee_l1: Option<u128>,
+}
+
+impl SignedFeedMessage {
+    /// The message hash signed by the sequencer i.e. nitro's `MessageWithMetadata.Hash`
+    ///
+    /// keccak256(sequence number || chain Id || delayed messages read || rlp(L1 message))
+    fn hash(&self, chain_id: u64) -> Result<H256, FeedError> {
+        let message = &self.message.message;
+        let header = &message.header;
+        let l2_msg = base64_simd::forgiving_decode_to_vec(message.l2_msg.as_bytes())
+            .map_err(|_| FeedError::InvalidBase64)?;
+
+        let mut stream = RlpStream::new_list(2 + message.batch_gas_cost.is_some() as usize);
+        stream
+            .begin_list(6)
+            .append(&header.kind)
+            .append(&header.sender)
+            .append(&header.block_number)
+            .append(&header.timestamp);
+        // nil pointers encode as empty strings
+        match header.request_id {
+            Some(request_id) => stream.append(&request_id),
+            None => stream.append_empty_data(),
+        };
+        match header.base_fee_l1 {
+            Some(base_fee) => stream.append(&U256::from(base_fee)),
+            None => stream.append_empty_data(),
+        };
+        stream.append(&l2_msg);
+        if let Some(batch_gas_cost) = message.batch_gas_cost {
+            stream.append(&batch_gas_cost);
+        }
+        let rlp = stream.out();
+
+        let mut preimage = Vec::with_capacity(24 + rlp.len());
+        preimage.extend_from_slice(&self.sequence_number.to_be_bytes());
+        preimage.extend_from_slice(&chain_id.to_be_bytes());
+        preimage.extend_from_slice(&self.message.delayed_messages_read.to_be_bytes());
+        preimage.extend_from_slice(&rlp);
+
+        Ok(H256(keccak256(preimage)))
+    }
+    /// Check the message was signed by `signer`
+    fn verify(&self, chain_id: u64, signer: Address) -> SignatureStatus {
+        let signature = match self.signature.as_ref() {
+            Some(signature) => signature,
+            None => return SignatureStatus::Unsigned,
+        };
+        let (signature, hash) = match (Signature::try_from(signature.as_ref()), self.hash(chain_id))
+        {
+            (Ok(signature), Ok(hash)) => (signature, hash),
+            _ => return SignatureStatus::Invalid,
+        };
+        // the sequencer's data signer signs the keccak of the message hash
+        match signature.recover(H256(keccak256(hash))) {
+            Ok(recovered) if recovered == signer => SignatureStatus::Valid,
+            _ => SignatureStatus::Invalid,
+        }
+    }
+}
+
+/// Verify the signature(s) of a sequencer feed message against the sequencer address `signer`
+///
+/// - `payload` the raw JSON feed message, it must be verified before being decoded in place
+///
+/// Returns the status of the first message which is not `Valid`, `Unverified` if there are no messages
+pub fn verify_feed_message(payload: &[u8], chain_id: u64, signer: Address) -> SignatureStatus {
+    let messages = match serde_json::from_slice::<SignedBroadcast>(payload) {
+        Ok(broadcast) => broadcast.messages.unwrap_or_default(),
+        Err(_) => return SignatureStatus::Invalid,
+    };
+    let mut status = SignatureStatus::Unverified;
+    for message in messages.iter() {
+        status = message.verify(chain_id, signer);
+        if status != SignatureStatus::Valid {
+            break;
+        }
+    }
+
+    status
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::signers::{LocalWallet, Signer};
+
+    use super::*;
+
+    /// `deposit.json` signed by `wallet`
+    fn signed_deposit(wallet: &LocalWallet) -> String {
+        let deposit = include_str!("../res/deposit.json");
+        let broadcast: SignedBroadcast = serde_json::from_str(deposit).unwrap();
+        let hash = broadcast.messages.unwrap()[0]
+            .hash(ARBITRUM_ONE_CHAIN_ID)
+            .unwrap();
+        let signature = wallet.sign_hash(H256(keccak256(hash))).unwrap();
+
+        deposit.replace(
+            r#""signature":null"#,
+            &format!(r#""signature":"0x{}""#, hex::encode(signature.to_vec())),
+        )
+    }
+
+    #[test]
+    fn verify_signatures() {
+        let sequencer = LocalWallet::from_bytes(&[1_u8; 32]).unwrap();
+        let imposter = LocalWallet::from_bytes(&[2_u8; 32]).unwrap();
+        let signed = signed_deposit(&sequencer);
+
+        assert_eq!(
+            verify_feed_message(
+                signed.as_bytes(),
+                ARBITRUM_ONE_CHAIN_ID,
+                sequencer.address()
+            ),
+            SignatureStatus::Valid
+        );
+        // signed by someone else
+        assert_eq!(
+            verify_feed_message(signed.as_bytes(), ARBITRUM_ONE_CHAIN_ID, imposter.address()),
+            SignatureStatus::Invalid
+        );
+        // signed for another chain
+        assert_eq!(
+            verify_feed_message(signed.as_bytes(), 1, sequencer.address()),
+            SignatureStatus::Invalid
+        );
+        // tampered deposit amount
+        let tampered = signed.replace("AAABY0V4XYoAAA==", "AAACY0V4XYoAAA==");
+        assert_eq!(
+            verify_feed_message(
+                tampered.as_bytes(),
+                ARBITRUM_ONE_CHAIN_ID,
+                sequencer.address()
+            ),
+            SignatureStatus::Invalid
+        );
+        assert_eq!(
+            verify_feed_message(
+                include_bytes!("../res/deposit.json"),
+                ARBITRUM_ONE_CHAIN_ID,
+                sequencer.address()
+            ),
+            SignatureStatus::Unsigned
+        );
+        // confirmations carry no messages
+        assert_eq!(
+            verify_feed_message(
+                br#"{"version":1,"confirmedSequenceNumberMessage":{"sequenceNumber":68938513}}"#,
+                ARBITRUM_ONE_CHAIN_ID,
+                sequencer.address()
+            ),
+            SignatureStatus::Unverified
+        );
+    }
+}
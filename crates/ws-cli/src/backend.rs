@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use ethers_providers::{ConnectionDetails, WsClientError};
 use futures_util::{
     stream::{Fuse, StreamExt},
@@ -8,6 +10,7 @@ use serde_json::value::RawValue;
 use tokio::{
     select,
     sync::{mpsc, oneshot},
+    time::{self, Instant},
 };
 use tokio_tungstenite::{
     connect_async,
@@ -22,6 +25,23 @@ use super::PubSubItem;
 
 pub type InternalStream = Fuse<WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>>;
 
+/// Ws connection health check, a Ping is sent every `interval` and the backend is torn down
+/// if no Pong arrives within `timeout` i.e. the connection is half-open
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keepalive {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for Keepalive {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
 /// `BackendDriver` drives a specific `WsBackend`. It can be used to issue
 /// requests, receive responses, see errors, and shut down the backend.
 pub struct BackendDriver {
@@ -33,7 +53,7 @@ pub struct BackendDriver {
     // Requests that the backend should dispatch
     pub dispatcher: mpsc::UnboundedSender<Box<RawValue>>,
     // Notify the backend of intentional shutdown
-    shutdown: oneshot::Sender<()>,
+    pub(crate) shutdown: oneshot::Sender<()>,
 }
 
 impl BackendDriver {
@@ -44,8 +64,8 @@ impl BackendDriver {
 }
 
 /// `WsBackend` dispatches requests and routes responses and notifications. It
-/// also has a simple ping-based keepalive (when not compiled to wasm), to
-/// prevent inactivity from triggering server-side closes
+/// also has an optional ping-based keepalive, to prevent inactivity from triggering
+/// server-side closes and detect half-open connections
 ///
 /// The `WsBackend` shuts down when instructed to by the `RequestManager` or
 /// when the `RequestManager` drops (because the inbound channel will close)
@@ -60,14 +80,22 @@ pub struct WsBackend {
     to_dispatch: mpsc::UnboundedReceiver<Box<RawValue>>,
     // notification from manager of intentional shutdown
     shutdown: oneshot::Receiver<()>,
+
+    // connection health check
+    keepalive: Option<Keepalive>,
+    // deadline of the outstanding ping's pong, if any
+    pong_deadline: Option<Instant>,
 }
 
 impl WsBackend {
     pub async fn connect(
         details: ConnectionDetails,
+        keepalive: Option<Keepalive>,
     ) -> Result<(Self, BackendDriver), WsClientError> {
         let (ws, _) = connect_async(details).await?;
-        Ok(Self::new(ws.fuse()))
+        let (mut backend, driver) = Self::new(ws.fuse());
+        backend.keepalive = keepalive;
+        Ok((backend, driver))
     }
 
     pub fn new(client: InternalStream) -> (Self, BackendDriver) {
@@ -83,6 +111,8 @@ impl WsBackend {
                 error: error_tx,
                 to_dispatch,
                 shutdown: shutdown_rx,
+                keepalive: None,
+                pong_deadline: None,
             },
             BackendDriver {
                 to_handle,
@@ -113,7 +143,10 @@ impl WsBackend {
                 Message::Text(t) => self.handle_text(t.as_bytes()).await,
                 // https://github.com/snapview/tungstenite-rs/blob/314feea3055a93e585882fb769854a912a7e6dae/src/protocol/mod.rs#L172-L175
                 Message::Ping(_) => Ok(()),
-                Message::Pong(_) => Ok(()),
+                Message::Pong(_) => {
+                    self.pong_deadline = None;
+                    Ok(())
+                }
                 Message::Frame(_) => Ok(()),
                 Message::Binary(buf) => Err(WsClientError::UnexpectedBinary(buf)),
                 Message::Close(_frame) => Err(WsClientError::UnexpectedClose),
@@ -125,7 +158,12 @@ impl WsBackend {
     pub fn spawn(mut self) {
         let fut = async move {
             let mut err = false;
+            // the first tick is skipped, the connection is fresh
+            let mut ping = self.keepalive.map(|keepalive| {
+                time::interval_at(Instant::now() + keepalive.interval, keepalive.interval)
+            });
             loop {
+                let pong_deadline = self.pong_deadline;
                 select! {
                     biased;
                     resp = self.server.next() => {
@@ -169,6 +207,23 @@ impl WsBackend {
                         error!("ws shutdown");
                         break
                     },
+                    // no pong in time, the connection is dead
+                    _ = time::sleep_until(pong_deadline.unwrap_or_else(Instant::now)), if pong_deadline.is_some() => {
+                        error!("ws pong timeout");
+                        err = true;
+                        break
+                    },
+                    _ = async { ping.as_mut().expect("keepalive").tick().await }, if ping.is_some() => {
+                        if self.pong_deadline.is_none() {
+                            if let Err(e) = self.server.send(Message::Ping(Vec::new())).await {
+                                error!("ws ping: {:?}", e);
+                                err = true;
+                                break
+                            }
+                            let timeout = self.keepalive.expect("keepalive").timeout;
+                            self.pong_deadline = Some(Instant::now() + timeout);
+                        }
+                    },
                 }
             }
             if err {
@@ -179,3 +234,51 @@ impl WsBackend {
         tokio::spawn(fut);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+    use tokio::{net::TcpListener, time};
+    use tokio_tungstenite::accept_async;
+
+    use super::{Keepalive, WsBackend};
+
+    #[tokio::test]
+    async fn keepalive_pong_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            // reading the stream answers pings
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut healthy = accept_async(stream).await.unwrap();
+            tokio::spawn(async move { while let Some(Ok(_)) = healthy.next().await {} });
+            // half-open, pings go unanswered
+            let (stream, _) = listener.accept().await.unwrap();
+            let _stalled = accept_async(stream).await.unwrap();
+            time::sleep(Duration::from_secs(5)).await;
+        });
+        let keepalive = Keepalive {
+            interval: Duration::from_millis(20),
+            timeout: Duration::from_millis(50),
+        };
+
+        let (ws, mut driver) = WsBackend::connect(url.as_str().into(), Some(keepalive))
+            .await
+            .unwrap();
+        ws.spawn();
+        assert!(time::timeout(Duration::from_millis(300), &mut driver.error)
+            .await
+            .is_err());
+
+        let (ws, mut driver) = WsBackend::connect(url.as_str().into(), Some(keepalive))
+            .await
+            .unwrap();
+        ws.spawn();
+        assert!(time::timeout(Duration::from_millis(300), &mut driver.error)
+            .await
+            .unwrap()
+            .is_ok());
+    }
+}
@@ -1,22 +1,37 @@
 //! A stripped down Ethereum JSON-RPC WS client based on ethers-providers `WsClient`
-use std::{fmt, sync::Arc, time::Instant};
+use std::{
+    fmt,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
 
 use async_trait::async_trait;
 use compact_str::CompactString;
-use ethers_providers::{ConnectionDetails, JsonRpcClient, WsClientError};
+use ethers_core::types::U256;
+use ethers_providers::{ConnectionDetails, JsonRpcClient, PubsubClient, WsClientError};
+use futures_util::Stream;
 use log::error;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::value::{to_raw_value, RawValue};
+use tokio::sync::mpsc;
 
-use crate::{manager::RequestManager, types::PreserializedCallRequest};
+use crate::{
+    backend::Keepalive,
+    manager::{Connection, RequestManager, SharedChannelMap},
+    types::{decode_hex_result, Instruction, PreserializedCallRequest},
+};
 
 pub const ETH_CALL: &'static str = "eth_call";
 pub const ETH_BLOCK_NUMBER: &'static str = "eth_blockNumber";
 
 #[derive(Clone)]
 pub struct FastWsClient {
-    // Used to send requests to the `RequestManager`
-    pub(crate) requests: tokio::sync::mpsc::UnboundedSender<PreserializedCallRequest>,
+    // Used to send instructions to the `RequestManager`
+    pub(crate) instructions: mpsc::UnboundedSender<Instruction>,
+    // Used to receive subscription notification channels from the `RequestManager`
+    pub(crate) channel_map: SharedChannelMap,
 }
 
 impl FastWsClient {
@@ -36,6 +51,30 @@ impl FastWsClient {
         man.spawn();
         Ok(this)
     }
+    /// Establishes a new websocket connection with the given `keepalive`, `None` disables pings
+    pub async fn connect_with_keepalive(
+        conn: impl Into<ConnectionDetails>,
+        keepalive: Option<Keepalive>,
+    ) -> Result<Self, WsClientError> {
+        let (man, this) =
+            RequestManager::connect_to(Connection::Ws(conn.into(), keepalive)).await?;
+        man.spawn();
+        Ok(this)
+    }
+
+    /// Shut down the connection once in-flight requests are answered, ending its subscriptions
+    ///
+    /// Later requests fail with `WsClientError::DeadChannel`
+    pub async fn shutdown(&self) {
+        let (done, rx) = tokio::sync::oneshot::channel();
+        if self
+            .instructions
+            .send(Instruction::Shutdown { done })
+            .is_ok()
+        {
+            let _ = rx.await;
+        }
+    }
 
     pub async fn eth_block_number<'a>(&self) -> Result<u64, WsClientError> {
         let (tx, rx) = tokio::sync::oneshot::channel();
@@ -45,8 +84,8 @@ impl FastWsClient {
             sender: tx,
         };
 
-        self.requests
-            .send(call)
+        self.instructions
+            .send(Instruction::Request(call))
             .map_err(|_| WsClientError::DeadChannel)?;
 
         match rx.await {
@@ -88,22 +127,15 @@ impl FastWsClient {
         // TODO: its simpler to call await on the ws backend directly
         // its like this to map responses to requests by id in proper async setup
         // in this implementation we know that requests and responses come sequentially
-        self.requests
-            .send(call)
+        self.instructions
+            .send(Instruction::Request(call))
             .map_err(|_| WsClientError::DeadChannel)?;
 
         match rx.await {
             // TODO: dropping the Box<> here is costly
             // - de-alloc in another thread or avoid the alloc, larger refactor
             Ok(Ok(res)) => {
-                let s = res.get();
-                buffer.resize((s.len() - 4) / 2, 0); // "0x" <- strip these chars
-                                                     // the output is valid hex
-                faster_hex::hex_decode_unchecked(
-                    unsafe { s.get_unchecked(3..s.len() - 1) }.as_bytes(),
-                    buffer,
-                );
-
+                decode_hex_result(&res, buffer);
                 Ok(())
             }
             Ok(Err(err)) => Err(err.into()),
@@ -115,7 +147,11 @@ impl FastWsClient {
     }
 
     // this is taken verbatim from ethers_providers::WsClient for compatibility
-    async fn make_request<R>(&self, method: &str, params: Box<RawValue>) -> Result<R, WsClientError>
+    pub(crate) async fn make_request<R>(
+        &self,
+        method: &str,
+        params: Box<RawValue>,
+    ) -> Result<R, WsClientError>
     where
         R: DeserializeOwned,
     {
@@ -125,8 +161,8 @@ impl FastWsClient {
             params: Arc::new(params),
             sender: tx,
         };
-        self.requests
-            .send(instruction)
+        self.instructions
+            .send(Instruction::Request(instruction))
             .map_err(|_| WsClientError::DeadChannel)?;
 
         let res = rx.await.map_err(|_| WsClientError::UnexpectedClose)??;
@@ -151,6 +187,40 @@ impl JsonRpcClient for FastWsClient {
     }
 }
 
+impl PubsubClient for FastWsClient {
+    type NotificationStream = NotificationStream;
+
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, WsClientError> {
+        // the `RequestManager` inserts the channel before the 'eth_subscribe' request returns
+        let id = id.into();
+        self.channel_map
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .map(NotificationStream)
+            .ok_or(WsClientError::UnknownSubscription(id))
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), WsClientError> {
+        self.instructions
+            .send(Instruction::Unsubscribe { id: id.into() })
+            .map_err(|_| WsClientError::DeadChannel)
+    }
+}
+
+/// Stream of raw notifications of an 'eth_subscribe' subscription
+/// Continues across reconnects, the subscription is re-established by the `RequestManager`
+#[derive(Debug)]
+pub struct NotificationStream(mpsc::UnboundedReceiver<Box<RawValue>>);
+
+impl Stream for NotificationStream {
+    type Item = Box<RawValue>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_recv(cx)
+    }
+}
+
 impl fmt::Debug for FastWsClient {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("FastWsClient").finish_non_exhaustive()
@@ -0,0 +1,221 @@
+//! A minimal Ethereum JSON-RPC HTTP client with the `FastWsClient` request surface
+//! Requests are sent over pooled (HTTP/2 where supported) connections, subscriptions are unsupported
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use ethers_core::types::U64;
+use ethers_providers::{JsonRpcClient, WsClientError};
+use isahc::{config::VersionNegotiation, AsyncReadResponseExt, HttpClient};
+use log::error;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::value::{to_raw_value, RawValue};
+
+use crate::{
+    cli::{ETH_BLOCK_NUMBER, ETH_CALL},
+    http_client, make_http_client,
+    types::{decode_hex_result, PubSubItem, Request},
+};
+
+/// Keep idle connections open for
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+pub struct FastHttpClient {
+    client: HttpClient,
+    url: Arc<str>,
+    // Next JSON-RPC Request ID
+    id: Arc<AtomicU64>,
+}
+
+impl FastHttpClient {
+    /// Create a client for the endpoint at `url`
+    /// HTTPS endpoints use HTTP/2, plaintext endpoints negotiate the version i.e. HTTP/1.1
+    pub fn new(url: impl Into<String>) -> Self {
+        let url: String = url.into();
+        let client = if url.starts_with("https://") {
+            make_http_client(KEEP_ALIVE)
+        } else {
+            http_client(KEEP_ALIVE, VersionNegotiation::latest_compatible())
+        };
+        Self {
+            client,
+            url: url.into(),
+            id: Default::default(),
+        }
+    }
+    /// Crude report on the latency of the http connection
+    pub async fn report_latency(&self) -> f64 {
+        let mut avg_latency = 0_u128;
+        for _ in 0..10 {
+            let t0 = Instant::now();
+            let _: Result<String, WsClientError> = self.request("net_version", [""]).await;
+            avg_latency += (Instant::now() - t0).as_millis();
+        }
+        avg_latency as f64 / 10f64
+    }
+
+    pub async fn eth_block_number(&self) -> Result<u64, WsClientError> {
+        let res = self.post(ETH_BLOCK_NUMBER, &to_raw_value(&())?).await?;
+        let block_number: U64 = serde_json::from_str(res.get())?;
+        Ok(block_number.as_u64())
+    }
+
+    /// Issue an Ethereum JSON-RPC 'eth_call' request with pre-serialized `params`
+    /// - `params` pre-serialized (hexified RLP) payload
+    pub async fn eth_call(
+        &self,
+        params: &Arc<Box<RawValue>>,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), WsClientError> {
+        let res = self.post(ETH_CALL, params).await?;
+        decode_hex_result(&res, buffer);
+        Ok(())
+    }
+
+    pub(crate) async fn make_request<R>(
+        &self,
+        method: &str,
+        params: Box<RawValue>,
+    ) -> Result<R, WsClientError>
+    where
+        R: DeserializeOwned,
+    {
+        let res = self.post(method, &params).await?;
+        Ok(serde_json::from_str(res.get())?)
+    }
+
+    /// POST the JSON-RPC request `method` with `params`, returning its raw result
+    async fn post(&self, method: &str, params: &RawValue) -> Result<Box<RawValue>, WsClientError> {
+        let id = self.id.fetch_add(1, Ordering::Relaxed);
+        let body = serde_json::to_vec(&Request::new(id, method, params))?;
+        let body = async {
+            let mut response = self.client.post_async(self.url.as_ref(), body).await?;
+            response.bytes().await.map_err(isahc::Error::from)
+        };
+        let body = body.await.map_err(|err| {
+            error!("{method} http: {:?}", err);
+            WsClientError::UnexpectedClose
+        })?;
+
+        match serde_json::from_slice(&body)? {
+            PubSubItem::Success { result, .. } => Ok(result),
+            PubSubItem::Error { error, .. } => Err(WsClientError::JsonRpcError(error)),
+            PubSubItem::Notification { params } => {
+                Err(WsClientError::UnknownSubscription(params.subscription))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for FastHttpClient {
+    type Error = WsClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, WsClientError>
+    where
+        T: Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        self.make_request(method, to_raw_value(&params)?).await
+    }
+}
+
+impl fmt::Debug for FastHttpClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FastHttpClient")
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use ethers_providers::WsClientError;
+    use serde_json::value::to_raw_value;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::FastHttpClient;
+    use crate::FastClient;
+
+    /// Serve `bodies` in order, one per request, returning the server URL
+    async fn serve(bodies: Vec<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            for body in bodies {
+                let (mut conn, _) = listener.accept().await.unwrap();
+                // read the whole request before responding
+                let mut request = Vec::<u8>::new();
+                let mut buf = [0_u8; 1024];
+                loop {
+                    let n = conn.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let raw = String::from_utf8_lossy(&request).to_lowercase();
+                    if let Some(header_end) = raw.find("\r\n\r\n") {
+                        let content_length = raw
+                            .lines()
+                            .find_map(|line| line.strip_prefix("content-length: "))
+                            .map(|len| len.trim().parse::<usize>().unwrap())
+                            .unwrap_or_default();
+                        if request.len() >= header_end + 4 + content_length {
+                            break;
+                        }
+                    }
+                    assert!(n > 0, "request closed");
+                }
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                conn.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn http_requests() {
+        let url = serve(vec![
+            r#"{"jsonrpc":"2.0","id":0,"result":"0x0102ff"}"#,
+            r#"{"jsonrpc":"2.0","id":1,"result":"0x1b4"}"#,
+            r#"{"jsonrpc":"2.0","id":2,"error":{"code":-32000,"message":"header not found"}}"#,
+        ])
+        .await;
+        let client = match FastClient::connect(url.as_str()).await.unwrap() {
+            FastClient::Http(client) => client,
+            _ => panic!("expected http transport"),
+        };
+
+        let params = Arc::new(to_raw_value(&["0x"]).unwrap());
+        let mut buffer = Vec::new();
+        client.eth_call(&params, &mut buffer).await.unwrap();
+        assert_eq!(buffer, [0x01, 0x02, 0xff]);
+        assert_eq!(client.eth_block_number().await.unwrap(), 0x1b4);
+        match client.eth_call(&params, &mut buffer).await {
+            Err(WsClientError::JsonRpcError(err)) => assert_eq!(err.code, -32_000),
+            result => panic!("unexpected result: {result:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn http_unreachable() {
+        // nothing listens on the discard port
+        let client = FastHttpClient::new("http://127.0.0.1:9");
+        assert!(matches!(
+            client.eth_block_number().await,
+            Err(WsClientError::UnexpectedClose)
+        ));
+    }
+}
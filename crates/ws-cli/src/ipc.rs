@@ -0,0 +1,268 @@
+//! IPC transport for a colocated node e.g. nitro/geth's `geth.ipc`
+//! Requests and responses are newline-delimited JSON over a Unix domain socket
+use std::{
+    fmt,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use ethers_core::types::U256;
+use ethers_providers::{JsonRpcClient, PubsubClient, WsClientError};
+use log::error;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::value::{to_raw_value, RawValue};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixStream,
+    },
+    select,
+    sync::{mpsc, oneshot},
+};
+
+use crate::{
+    backend::{BackendDriver, WsError},
+    cli::NotificationStream,
+    manager::{Connection, RequestManager},
+    types::PubSubItem,
+    FastWsClient,
+};
+
+/// `IpcBackend` dispatches requests and routes responses and notifications over a Unix domain socket,
+/// the IPC counterpart of `WsBackend`
+pub struct IpcBackend {
+    reader: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    // channel to the manager, through which to send items received via IPC
+    handler: mpsc::UnboundedSender<PubSubItem>,
+    // notify manager of an error causing this task to halt
+    error: oneshot::Sender<()>,
+
+    // channel of inbound requests to dispatch
+    to_dispatch: mpsc::UnboundedReceiver<Box<RawValue>>,
+    // notification from manager of intentional shutdown
+    shutdown: oneshot::Receiver<()>,
+}
+
+impl IpcBackend {
+    pub async fn connect(path: &Path) -> Result<(Self, BackendDriver), WsClientError> {
+        let stream = UnixStream::connect(path).await.map_err(WsError::Io)?;
+        Ok(Self::new(stream))
+    }
+
+    pub fn new(stream: UnixStream) -> (Self, BackendDriver) {
+        let (reader, writer) = stream.into_split();
+        let (handler, to_handle) = mpsc::unbounded_channel();
+        let (dispatcher, to_dispatch) = mpsc::unbounded_channel();
+        let (error_tx, error_rx) = oneshot::channel();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+        (
+            IpcBackend {
+                reader: BufReader::new(reader).lines(),
+                writer,
+                handler,
+                error: error_tx,
+                to_dispatch,
+                shutdown: shutdown_rx,
+            },
+            BackendDriver {
+                to_handle,
+                error: error_rx,
+                dispatcher,
+                shutdown: shutdown_tx,
+            },
+        )
+    }
+
+    /// Handle a JSON `line` from the server
+    async fn handle_line(&mut self, line: &str) -> Result<(), WsClientError> {
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        let item = serde_json::from_str(line)?;
+        self.handler
+            .send(item)
+            .map_err(|_| WsClientError::DeadChannel)
+    }
+
+    /// Write the request `msg` to the server
+    async fn dispatch(&mut self, msg: Box<RawValue>) -> Result<(), WsError> {
+        let mut line = String::from(msg.get());
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+
+    pub fn spawn(mut self) {
+        let fut = async move {
+            let mut err = false;
+            loop {
+                select! {
+                    biased;
+                    line = self.reader.next_line() => {
+                        match line {
+                            Ok(Some(line)) => {
+                                if let Err(e) = self.handle_line(line.as_str()).await {
+                                    error!("handle ipc response: {:?}", e);
+                                    err = true;
+                                    break;
+                                }
+                            }
+                            Ok(None) => {
+                                error!("ipc closed");
+                                err = true;
+                                break;
+                            }
+                            Err(e) => {
+                                error!("ipc read: {:?}", e);
+                                err = true;
+                                break;
+                            }
+                        }
+                    }
+                    inst = self.to_dispatch.recv() => {
+                        match inst {
+                            Some(msg) => {
+                                if let Err(e) = self.dispatch(msg).await {
+                                    error!("ipc write: {:?}", e);
+                                    err = true;
+                                    break;
+                                }
+                            }
+                            // dispatcher has gone away
+                            None => {
+                                err = true;
+                                break;
+                            }
+                        }
+                    }
+                    // break on shutdown recv, or on shutdown recv error
+                    _ = &mut self.shutdown => {
+                        error!("ipc shutdown");
+                        break;
+                    }
+                }
+            }
+            if err {
+                let _ = self.error.send(());
+            }
+        };
+
+        tokio::spawn(fut);
+    }
+}
+
+/// A JSON-RPC client over IPC, shares the request manager (and subscription support) of `FastWsClient`
+#[derive(Clone)]
+pub struct IpcClient(FastWsClient);
+
+impl IpcClient {
+    /// Connect to the node's IPC socket at `path`
+    pub async fn connect(path: impl Into<PathBuf>) -> Result<Self, WsClientError> {
+        let (man, this) = RequestManager::connect_to(Connection::Ipc(path.into())).await?;
+        man.spawn();
+        Ok(Self(this))
+    }
+}
+
+impl Deref for IpcClient {
+    type Target = FastWsClient;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for IpcClient {
+    type Error = WsClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, WsClientError>
+    where
+        T: Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        self.0.make_request(method, to_raw_value(&params)?).await
+    }
+}
+
+impl PubsubClient for IpcClient {
+    type NotificationStream = NotificationStream;
+
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, WsClientError> {
+        self.0.subscribe(id)
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), WsClientError> {
+        self.0.unsubscribe(id)
+    }
+}
+
+impl fmt::Debug for IpcClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IpcClient").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use ethers_core::types::U256;
+    use ethers_providers::{JsonRpcClient, PubsubClient};
+    use futures_util::StreamExt;
+    use serde_json::{json, value::to_raw_value, Value};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::UnixListener,
+    };
+
+    use crate::FastClient;
+
+    #[tokio::test]
+    async fn ipc_requests() {
+        let path = std::env::temp_dir().join(format!("fulcrum-{}.ipc", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        // a minimal node, answering newline-delimited requests
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let result = match request["method"].as_str().unwrap() {
+                    "eth_call" => json!("0x0102ff"),
+                    "eth_subscribe" => json!("0xabc"),
+                    method => panic!("unexpected method: {method}"),
+                };
+                let mut response =
+                    json!({"jsonrpc": "2.0", "id": request["id"], "result": result}).to_string();
+                if request["method"] == "eth_subscribe" {
+                    response.push('\n');
+                    response.push_str(
+                        &json!({"jsonrpc": "2.0", "method": "eth_subscription", "params": {"subscription": "0xabc", "result": {"number": "0x1b4"}}})
+                            .to_string(),
+                    );
+                }
+                response.push('\n');
+                writer.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let client = FastClient::connect_ipc(&path).await.unwrap();
+        let params = Arc::new(to_raw_value(&["0x"]).unwrap());
+        let mut buffer = Vec::new();
+        client.eth_call(&params, &mut buffer).await.unwrap();
+        assert_eq!(buffer, [0x01, 0x02, 0xff]);
+
+        let id: U256 = client.request("eth_subscribe", ["newHeads"]).await.unwrap();
+        let mut heads = client.subscribe(id).unwrap();
+        assert_eq!(heads.next().await.unwrap().get(), r#"{"number":"0x1b4"}"#);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
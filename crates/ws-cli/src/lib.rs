@@ -1,11 +1,16 @@
 //! A stripped down Ethereum JSON-RPC WS client based on ethers-providers
 //! Allows some room for optimization of the networking and serialization steps
-//! It is not fully featured e.g. does not provide subscriptions
+//! Requests may also be sent over pooled HTTP connections, for endpoints without (reliable) ws support,
+//! or IPC for a colocated node
+//! It is not fully featured, subscriptions are limited to 'eth_subscribe' (e.g. newHeads, logs) via `PubsubClient`
 
 #![allow(missing_docs)]
 mod backend;
 mod cli;
+mod http;
+mod ipc;
 mod manager;
+mod transport;
 mod types;
 
 use std::time::Duration;
@@ -16,11 +21,20 @@ use isahc::{
 };
 pub use isahc::{AsyncBody, HttpClient};
 
-pub use cli::FastWsClient;
+pub use backend::Keepalive;
+pub use cli::{FastWsClient, NotificationStream};
+pub use http::FastHttpClient;
+pub use ipc::IpcClient;
+pub use transport::FastClient;
 pub use types::*;
 
 /// Create a pooled HTTP(S) client
 pub fn make_http_client(keep_alive: Duration) -> HttpClient {
+    http_client(keep_alive, VersionNegotiation::http2())
+}
+
+/// Create a pooled HTTP(S) client with HTTP `version` negotiation
+pub(crate) fn http_client(keep_alive: Duration, version: VersionNegotiation) -> HttpClient {
     HttpClient::builder()
         .default_headers(&[("Content-Type", "application/json")])
         .dns_cache(DnsCache::Forever)
@@ -28,7 +42,7 @@ pub fn make_http_client(keep_alive: Duration) -> HttpClient {
         .ssl_options(SslOption::DANGER_ACCEPT_INVALID_CERTS)
         .tcp_keepalive(keep_alive)
         .tcp_nodelay()
-        .version_negotiation(VersionNegotiation::http2())
+        .version_negotiation(version)
         .connection_cache_size(2)
         .connection_cache_ttl(keep_alive)
         .build()
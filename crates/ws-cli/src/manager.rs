@@ -1,24 +1,184 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap},
     ops::Deref,
+    path::PathBuf,
     sync::{
         atomic::{AtomicU64, Ordering},
-        Arc,
+        Arc, Mutex,
     },
+    time::Duration,
 };
 
+use compact_str::CompactString;
+use ethers_core::types::U256;
 use ethers_providers::{ConnectionDetails, WsClientError};
-use log::{debug, error, trace};
-use serde_json::value::to_raw_value;
-use tokio::select;
+use log::{debug, error, trace, warn};
+use serde_json::value::{to_raw_value, RawValue};
+use tokio::{select, sync::mpsc};
 
 use crate::{
-    backend::{BackendDriver, WsBackend},
+    backend::{BackendDriver, Keepalive, WsBackend},
     cli::FastWsClient as WsClient,
-    types::{PreserializedCallRequest, PubSubItem, Request},
+    ipc::IpcBackend,
+    types::{Instruction, Notification, PreserializedCallRequest, PubSubItem, Request},
 };
 
 pub const DEFAULT_RECONNECTS: usize = 5;
+pub const ETH_SUBSCRIBE: &str = "eth_subscribe";
+pub const ETH_UNSUBSCRIBE: &str = "eth_unsubscribe";
+/// Max. duration to await responses to in-flight requests on shutdown
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Notification channels of new subscriptions, by client-side subscription Id
+pub type SharedChannelMap = Arc<Mutex<HashMap<U256, mpsc::UnboundedReceiver<Box<RawValue>>>>>;
+
+/// An 'eth_subscribe' subscription
+struct ActiveSub {
+    // The 'eth_subscribe' params, re-issued on reconnect
+    params: Arc<Box<RawValue>>,
+    // Notifications of the subscription
+    channel: mpsc::UnboundedSender<Box<RawValue>>,
+    // The server-side Id, if subscribed
+    current_server_id: Option<U256>,
+}
+
+/// Manages the relationship between the u64 request Id and U256 server-side subscription Id.
+/// The subscribing request's Id is returned to the caller as the subscription Id and the server Id
+/// is aliased to it, so subscriptions behave consistently across reconnects
+#[derive(Default)]
+struct SubscriptionManager {
+    // Active subs by request Id
+    subs: BTreeMap<u64, ActiveSub>,
+    // Server-side Ids to request Ids
+    aliases: HashMap<U256, u64>,
+    // Shares notification channels with the ws frontend(s)
+    channel_map: SharedChannelMap,
+}
+
+impl SubscriptionManager {
+    fn new(channel_map: SharedChannelMap) -> Self {
+        Self {
+            channel_map,
+            ..Default::default()
+        }
+    }
+
+    fn has(&self, id: u64) -> bool {
+        self.subs.contains_key(&id)
+    }
+
+    /// Register the subscription opened by request `id` with 'eth_subscribe' `params`
+    fn subscribe(&mut self, id: u64, params: Arc<Box<RawValue>>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        // the channel must be available before the request returns
+        self.channel_map.lock().unwrap().insert(id.into(), rx);
+        self.subs.insert(
+            id,
+            ActiveSub {
+                params,
+                channel: tx,
+                current_server_id: None,
+            },
+        );
+    }
+
+    /// Handle the 'eth_subscribe' `result` of request `id`, aliasing the server-side Id
+    ///
+    /// Returns the client-side subscription Id as the request's result
+    fn subscribed(&mut self, id: u64, result: Box<RawValue>) -> Box<RawValue> {
+        let server_id = match serde_json::from_str::<U256>(result.get()) {
+            Ok(server_id) => server_id,
+            Err(err) => {
+                error!("subscription Id: {:?}", err);
+                return result;
+            }
+        };
+        if let Some(sub) = self.subs.get_mut(&id) {
+            if let Some(old_server_id) = sub.current_server_id.replace(server_id) {
+                self.aliases.remove(&old_server_id);
+            }
+            self.aliases.insert(server_id, id);
+        }
+        to_raw_value(&U256::from(id)).expect("it serializes")
+    }
+
+    /// Drop the subscription `id`, its 'eth_subscribe' request failed
+    /// Ends the notification stream of a failed re-subscribe
+    fn failed(&mut self, id: u64) {
+        if self.has(id) {
+            self.end(id);
+            self.channel_map.lock().unwrap().remove(&id.into());
+        }
+    }
+
+    /// Forward `notification` to its subscription channel
+    ///
+    /// Returns the server-side Id of the subscription if its listener has dropped
+    fn notify(&mut self, notification: Notification) -> Option<U256> {
+        let server_id = notification.subscription;
+        let id = match self.aliases.get(&server_id) {
+            Some(id) => *id,
+            None => {
+                debug!("unknown subscription: {:?}", server_id);
+                return None;
+            }
+        };
+        let sub = match self.subs.get(&id) {
+            Some(sub) => sub,
+            None => {
+                self.aliases.remove(&server_id);
+                return None;
+            }
+        };
+        if sub.channel.send(notification.result).is_err() {
+            debug!("subscription listener dropped: {id}");
+            self.end(id)
+        } else {
+            None
+        }
+    }
+
+    /// End the subscription `id`
+    ///
+    /// Returns its server-side Id, if subscribed
+    fn end(&mut self, id: u64) -> Option<U256> {
+        let server_id = self.subs.remove(&id)?.current_server_id?;
+        self.aliases.remove(&server_id);
+        Some(server_id)
+    }
+
+    /// Active subscriptions, to re-issue on reconnect
+    fn to_reissue(&self) -> impl Iterator<Item = (&u64, &Arc<Box<RawValue>>)> {
+        self.subs.iter().map(|(id, sub)| (id, &sub.params))
+    }
+}
+
+/// A JSON-RPC server to connect with
+#[derive(Clone, Debug)]
+pub enum Connection {
+    /// Websocket URL and optional auth info, with the connection keepalive
+    Ws(ConnectionDetails, Option<Keepalive>),
+    /// IPC socket path
+    Ipc(PathBuf),
+}
+
+impl Connection {
+    /// Connect and spawn a backend for the server
+    async fn spawn_backend(&self) -> Result<BackendDriver, WsClientError> {
+        match self {
+            Self::Ws(conn, keepalive) => {
+                let (ws, backend) = WsBackend::connect(conn.clone(), *keepalive).await?;
+                ws.spawn();
+                Ok(backend)
+            }
+            Self::Ipc(path) => {
+                let (ipc, backend) = IpcBackend::connect(path.as_path()).await?;
+                ipc.spawn();
+                Ok(backend)
+            }
+        }
+    }
+}
 
 /// The `RequestManager` holds copies of all pending requests (as `InFlight`),
 /// and active subscriptions (as `ActiveSub`). When reconnection occurs, all
@@ -26,8 +186,8 @@ pub const DEFAULT_RECONNECTS: usize = 5;
 /// are re-subscribed
 ///
 ///  `RequestManager` holds a `BackendDriver`, to communicate with the current
-/// backend. Reconnection is accomplished by instantiating a new `WsBackend` and
-/// swapping out the manager's `BackendDriver`.
+/// backend. Reconnection is accomplished by instantiating a new `WsBackend` (or `IpcBackend`)
+/// and swapping out the manager's `BackendDriver`.
 ///
 /// In order to provide continuity of subscription IDs to the client, the
 /// `RequestManager` also keeps a `SubscriptionManager`. See the
@@ -40,20 +200,22 @@ pub const DEFAULT_RECONNECTS: usize = 5;
 ///
 /// The `RequestManager` shuts down and drops when all `WsClient` instances have
 /// been dropped (because all instruction channel `UnboundedSender` instances
-/// will have dropped).
+/// will have dropped), or on a `Shutdown` instruction once in-flight requests are answered.
 pub struct RequestManager {
     // Next JSON-RPC Request ID
     id: AtomicU64,
     // How many times we should reconnect the backend before erroring
     reconnects: usize,
+    // Subscription manager
+    subs: SubscriptionManager,
     // Requests for which a response has not been received
     reqs: BTreeMap<u64, PreserializedCallRequest>,
-    // Control of the active WS backend
+    // Control of the active WS/IPC backend
     backend: BackendDriver,
-    // The URL and optional auth info for the connection
-    conn: ConnectionDetails,
-    // requests from the user-facing providers
-    requests: tokio::sync::mpsc::UnboundedReceiver<PreserializedCallRequest>,
+    // The server to (re)connect with
+    conn: Connection,
+    // instructions from the user-facing providers
+    instructions: mpsc::UnboundedReceiver<Instruction>,
 }
 
 impl RequestManager {
@@ -69,23 +231,39 @@ impl RequestManager {
         conn: ConnectionDetails,
         reconnects: usize,
     ) -> Result<(Self, WsClient), WsClientError> {
-        let (ws, backend) = WsBackend::connect(conn.clone()).await?;
+        Self::connect_to_with_reconnects(
+            Connection::Ws(conn, Some(Keepalive::default())),
+            reconnects,
+        )
+        .await
+    }
+
+    pub async fn connect_to(conn: Connection) -> Result<(Self, WsClient), WsClientError> {
+        Self::connect_to_with_reconnects(conn, DEFAULT_RECONNECTS).await
+    }
 
-        let (requests_tx, requests_rx) = tokio::sync::mpsc::unbounded_channel();
+    pub async fn connect_to_with_reconnects(
+        conn: Connection,
+        reconnects: usize,
+    ) -> Result<(Self, WsClient), WsClientError> {
+        let backend = conn.spawn_backend().await?;
 
-        ws.spawn();
+        let (instructions_tx, instructions_rx) = mpsc::unbounded_channel();
+        let channel_map = SharedChannelMap::default();
 
         Ok((
             Self {
                 id: Default::default(),
                 reconnects,
+                subs: SubscriptionManager::new(Arc::clone(&channel_map)),
                 reqs: Default::default(),
                 backend,
                 conn,
-                requests: requests_rx,
+                instructions: instructions_rx,
             },
             WsClient {
-                requests: requests_tx,
+                instructions: instructions_tx,
+                channel_map,
             },
         ))
     }
@@ -97,11 +275,8 @@ impl RequestManager {
         }
         self.reconnects -= 1;
 
-        // create the new backend
-        let (s, mut backend) = WsBackend::connect(self.conn.clone()).await?;
-
-        // spawn the new backend
-        s.spawn();
+        // create and spawn the new backend
+        let mut backend = self.conn.spawn_backend().await?;
 
         // swap out the backend
         std::mem::swap(&mut self.backend, &mut backend);
@@ -117,6 +292,25 @@ impl RequestManager {
         // issue a shutdown command (even though it's likely gone)
         old_backend.shutdown();
 
+        // re-subscribe, the response aliases the new server-side Id
+        let resubscribes: Vec<_> = self
+            .subs
+            .to_reissue()
+            .map(|(id, params)| (*id, Arc::clone(params)))
+            .collect();
+        for (id, params) in resubscribes {
+            // pending subscribe requests are reissued as is
+            self.reqs.entry(id).or_insert_with(|| {
+                // nothing awaits the result
+                let (sender, _) = tokio::sync::oneshot::channel();
+                PreserializedCallRequest {
+                    method: CompactString::new(ETH_SUBSCRIBE),
+                    params,
+                    sender,
+                }
+            });
+        }
+
         // reissue requests
         for (id, pre_request) in self.reqs.iter() {
             let req = Request::new(*id, pre_request.method(), Arc::deref(&pre_request.params));
@@ -133,6 +327,11 @@ impl RequestManager {
         match item {
             PubSubItem::Success { id, result } => {
                 if let Some(req) = self.reqs.remove(&id) {
+                    let result = if self.subs.has(id) {
+                        self.subs.subscribed(id, result)
+                    } else {
+                        result
+                    };
                     if let Err(_) = req.sender.send(Ok(result)) {
                         trace!("send to channel: {id}");
                     }
@@ -142,6 +341,7 @@ impl RequestManager {
             }
             PubSubItem::Error { id, error } => {
                 error!("ws response: {id}");
+                self.subs.failed(id);
                 if let Some(req) = self.reqs.remove(&id) {
                     // pending fut has been dropped, this is fine
                     if let Err(_) = req.sender.send(Err(error)) {
@@ -151,6 +351,48 @@ impl RequestManager {
                     error!("lost channel: {id}");
                 }
             }
+            PubSubItem::Notification { params } => {
+                if let Some(server_id) = self.subs.notify(params) {
+                    self.unsubscribe(server_id);
+                }
+            }
+        }
+    }
+
+    /// End the subscription with (client-side) Id `id`, on request of a ws frontend
+    fn end_subscription(&mut self, id: U256) {
+        if let Some(server_id) = self.subs.end(id.low_u64()) {
+            self.unsubscribe(server_id);
+        }
+    }
+
+    /// Dispatch an 'eth_unsubscribe' request for the server-side subscription `server_id`
+    fn unsubscribe(&mut self, server_id: U256) {
+        // nothing awaits the result
+        let (sender, _) = tokio::sync::oneshot::channel();
+        let request = PreserializedCallRequest {
+            method: CompactString::new(ETH_UNSUBSCRIBE),
+            params: Arc::new(to_raw_value(&[server_id]).expect("it serializes")),
+            sender,
+        };
+        // a dead backend is handled by the reconnect, unsubscribing is moot
+        if self.handle_request(request).is_err() {
+            debug!("unsubscribe: {:?}", server_id);
+        }
+    }
+
+    /// Handle responses until no requests are in-flight, for up to `DRAIN_TIMEOUT`
+    async fn drain(&mut self) {
+        let drain = async {
+            while !self.reqs.is_empty() {
+                match self.backend.to_handle.recv().await {
+                    Some(item) => self.handle_response(item),
+                    None => break,
+                }
+            }
+        };
+        if tokio::time::timeout(DRAIN_TIMEOUT, drain).await.is_err() {
+            warn!("ws shutdown: {} requests unanswered", self.reqs.len());
         }
     }
 
@@ -168,6 +410,9 @@ impl RequestManager {
             Arc::deref(&pre_request.params),
         ))
         .unwrap();
+        if pre_request.method() == ETH_SUBSCRIBE {
+            self.subs.subscribe(id, Arc::clone(&pre_request.params));
+        }
 
         self.backend
             .dispatcher
@@ -203,10 +448,16 @@ impl RequestManager {
                             break Err(e);
                         }
                     },
-                    // internal request from ws cli
-                    cli_request = self.requests.recv() => {
-                        match cli_request {
-                            Some(request) => if let Err(e) = self.handle_request(request) { break Err(e)},
+                    // internal instruction from ws cli
+                    instruction = self.instructions.recv() => {
+                        match instruction {
+                            Some(Instruction::Request(request)) => if let Err(e) = self.handle_request(request) { break Err(e)},
+                            Some(Instruction::Unsubscribe { id }) => self.end_subscription(id),
+                            Some(Instruction::Shutdown { done }) => {
+                                self.drain().await;
+                                let _ = done.send(());
+                                break Ok(());
+                            }
                             // User-facing side is gone, so just exit
                             None => break Err(WsClientError::DeadChannel),
                         }
@@ -223,3 +474,65 @@ impl RequestManager {
         tokio::spawn(fut);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use ethers_core::types::U256;
+    use serde_json::value::{to_raw_value, RawValue};
+
+    use super::SubscriptionManager;
+    use crate::types::Notification;
+
+    fn notification(server_id: u64, result: &str) -> Notification {
+        Notification {
+            subscription: server_id.into(),
+            result: RawValue::from_string(result.to_string()).unwrap(),
+        }
+    }
+
+    #[test]
+    fn subscription_aliases() {
+        let mut subs = SubscriptionManager::default();
+        let params = Arc::new(to_raw_value(&["newHeads"]).unwrap());
+        subs.subscribe(7, params);
+        let mut rx = subs
+            .channel_map
+            .lock()
+            .unwrap()
+            .remove(&U256::from(7))
+            .unwrap();
+
+        // the client sees the request Id
+        let result = subs.subscribed(7, to_raw_value(&U256::from(0xabc)).unwrap());
+        assert_eq!(result.get(), r#""0x7""#);
+        assert_eq!(subs.notify(notification(0xabc, "1")), None);
+        assert_eq!(rx.try_recv().unwrap().get(), "1");
+
+        // re-subscribed after reconnect
+        subs.subscribed(7, to_raw_value(&U256::from(0xdef)).unwrap());
+        assert_eq!(subs.notify(notification(0xabc, "2")), None);
+        assert!(rx.try_recv().is_err());
+        subs.notify(notification(0xdef, "3"));
+        assert_eq!(rx.try_recv().unwrap().get(), "3");
+
+        // the listener dropped, unsubscribe
+        drop(rx);
+        assert_eq!(
+            subs.notify(notification(0xdef, "4")),
+            Some(U256::from(0xdef))
+        );
+        assert!(!subs.has(7));
+        assert_eq!(subs.to_reissue().count(), 0);
+    }
+
+    #[test]
+    fn subscription_failed() {
+        let mut subs = SubscriptionManager::default();
+        subs.subscribe(1, Arc::new(to_raw_value(&["newHeads"]).unwrap()));
+        subs.failed(1);
+        assert!(!subs.has(1));
+        assert!(subs.channel_map.lock().unwrap().is_empty());
+    }
+}
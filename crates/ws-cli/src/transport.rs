@@ -0,0 +1,130 @@
+//! Transport selection by endpoint scheme
+use std::{fmt, path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use ethers_core::types::U256;
+use ethers_providers::{JsonRpcClient, PubsubClient, WsClientError};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::value::{to_raw_value, RawValue};
+
+use crate::{cli::NotificationStream, FastHttpClient, FastWsClient, IpcClient, Keepalive};
+
+/// A JSON-RPC client over ws, IPC, or for endpoints without ws support, HTTP
+#[derive(Clone)]
+pub enum FastClient {
+    Ws(FastWsClient),
+    Http(FastHttpClient),
+    Ipc(IpcClient),
+}
+
+impl FastClient {
+    /// Connect to the endpoint at `url`, selecting the transport by its scheme i.e. 'http(s)://' or 'ws(s)://'
+    pub async fn connect(url: &str) -> Result<Self, WsClientError> {
+        Self::connect_with_keepalive(url, Some(Keepalive::default())).await
+    }
+    /// Connect to the endpoint at `url`, ws connections are health checked with `keepalive`
+    pub async fn connect_with_keepalive(
+        url: &str,
+        keepalive: Option<Keepalive>,
+    ) -> Result<Self, WsClientError> {
+        if url.starts_with("http://") || url.starts_with("https://") {
+            Ok(Self::Http(FastHttpClient::new(url)))
+        } else {
+            FastWsClient::connect_with_keepalive(url, keepalive)
+                .await
+                .map(Self::Ws)
+        }
+    }
+    /// Connect to the node's IPC socket at `path`
+    pub async fn connect_ipc(path: impl Into<PathBuf>) -> Result<Self, WsClientError> {
+        IpcClient::connect(path).await.map(Self::Ipc)
+    }
+    /// Crude report on the latency of the connection
+    pub async fn report_latency(&self) -> f64 {
+        match self {
+            Self::Ws(client) => client.report_latency().await,
+            Self::Http(client) => client.report_latency().await,
+            Self::Ipc(client) => client.report_latency().await,
+        }
+    }
+
+    /// Shut down the connection once in-flight requests are answered, HTTP connections are pooled and left as is
+    pub async fn shutdown(&self) {
+        match self {
+            Self::Ws(client) => client.shutdown().await,
+            Self::Http(_) => (),
+            Self::Ipc(client) => client.shutdown().await,
+        }
+    }
+
+    pub async fn eth_block_number(&self) -> Result<u64, WsClientError> {
+        match self {
+            Self::Ws(client) => client.eth_block_number().await,
+            Self::Http(client) => client.eth_block_number().await,
+            Self::Ipc(client) => client.eth_block_number().await,
+        }
+    }
+
+    /// Issue an Ethereum JSON-RPC 'eth_call' request with pre-serialized `params`
+    /// - `params` pre-serialized (hexified RLP) payload
+    pub async fn eth_call(
+        &self,
+        params: &Arc<Box<RawValue>>,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), WsClientError> {
+        match self {
+            Self::Ws(client) => client.eth_call(params, buffer).await,
+            Self::Http(client) => client.eth_call(params, buffer).await,
+            Self::Ipc(client) => client.eth_call(params, buffer).await,
+        }
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for FastClient {
+    type Error = WsClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, WsClientError>
+    where
+        T: Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let params = to_raw_value(&params)?;
+        match self {
+            Self::Ws(client) => client.make_request(method, params).await,
+            Self::Http(client) => client.make_request(method, params).await,
+            Self::Ipc(client) => client.make_request(method, params).await,
+        }
+    }
+}
+
+impl PubsubClient for FastClient {
+    type NotificationStream = NotificationStream;
+
+    /// Subscriptions are unsupported over HTTP
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, WsClientError> {
+        match self {
+            Self::Ws(client) => client.subscribe(id),
+            Self::Ipc(client) => client.subscribe(id),
+            Self::Http(_) => Err(WsClientError::UnknownSubscription(id.into())),
+        }
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), WsClientError> {
+        match self {
+            Self::Ws(client) => client.unsubscribe(id),
+            Self::Ipc(client) => client.unsubscribe(id),
+            Self::Http(_) => Err(WsClientError::UnknownSubscription(id.into())),
+        }
+    }
+}
+
+impl fmt::Debug for FastClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ws(client) => client.fmt(f),
+            Self::Http(client) => client.fmt(f),
+            Self::Ipc(client) => client.fmt(f),
+        }
+    }
+}
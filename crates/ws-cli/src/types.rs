@@ -2,7 +2,7 @@ use core::fmt;
 use std::sync::Arc;
 
 use compact_str::CompactString;
-use ethers_core::types::{Bytes, H256};
+use ethers_core::types::{Bytes, H256, U256};
 use ethers_providers::JsonRpcError;
 use serde::{
     de::{self},
@@ -50,10 +50,19 @@ impl<'a, T> Request<'a, T> {
     }
 }
 
+/// An 'eth_subscription' notification
+#[derive(Deserialize, Debug, Clone)]
+pub struct Notification {
+    /// The server-side subscription Id
+    pub subscription: U256,
+    pub result: Box<RawValue>,
+}
+
 #[derive(Debug, Clone)]
 pub enum PubSubItem {
     Success { id: u64, result: Box<RawValue> },
     Error { id: u64, error: JsonRpcError },
+    Notification { params: Notification },
 }
 
 // FIXME: ideally, this could be auto-derived as an untagged enum, but due to
@@ -74,6 +83,8 @@ impl<'de> de::Visitor<'de> for ResponseVisitor {
         let mut result = None;
         // only error
         let mut error = None;
+        // only notification
+        let mut params = None;
 
         while let Some(key) = map.next_key()? {
             match key {
@@ -87,6 +98,10 @@ impl<'de> de::Visitor<'de> for ResponseVisitor {
                     let value: JsonRpcError = map.next_value()?;
                     error = Some(value);
                 }
+                "params" => {
+                    let value: Notification = map.next_value()?;
+                    params = Some(value);
+                }
                 _ => {
                     let _ = de::MapAccess::next_value::<de::IgnoredAny>(&mut map);
                 }
@@ -95,6 +110,8 @@ impl<'de> de::Visitor<'de> for ResponseVisitor {
 
         if let Some(result) = result {
             Ok(PubSubItem::Success { id, result })
+        } else if let Some(params) = params {
+            Ok(PubSubItem::Notification { params })
         } else {
             Ok(PubSubItem::Error {
                 id,
@@ -121,6 +138,9 @@ impl std::fmt::Display for PubSubItem {
         match self {
             PubSubItem::Success { id, .. } => write!(f, "Req success. ID: {id}"),
             PubSubItem::Error { id, .. } => write!(f, "Req error. ID: {id}"),
+            PubSubItem::Notification { params } => {
+                write!(f, "Notification for sub: {:?}", params.subscription)
+            }
         }
     }
 }
@@ -140,6 +160,19 @@ impl PreserializedCallRequest {
     }
 }
 
+/// An instruction from a ws frontend to the `RequestManager`
+#[derive(Debug)]
+pub enum Instruction {
+    /// Dispatch a JSON-RPC request, 'eth_subscribe' requests also open a subscription
+    Request(PreserializedCallRequest),
+    /// End the subscription with (client-side) Id
+    Unsubscribe { id: U256 },
+    /// Shut down once in-flight requests are answered, notifying `done`
+    Shutdown {
+        done: tokio::sync::oneshot::Sender<()>,
+    },
+}
+
 /// Wrapper type around Bytes to deserialize/serialize "0x" prefixed ethereum hex strings
 #[derive(Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FastBytes(
@@ -174,6 +207,17 @@ impl Into<Bytes> for FastBytes {
     }
 }
 
+/// Decode the hex string JSON-RPC `result` e.g. of 'eth_call' into `buffer`
+/// `result` must be a valid, "0x" prefixed hex string
+pub(crate) fn decode_hex_result(result: &RawValue, buffer: &mut Vec<u8>) {
+    let s = result.get();
+    buffer.resize((s.len() - 4) / 2, 0); // strip the quotes and "0x"
+    faster_hex::hex_decode_unchecked(
+        unsafe { s.get_unchecked(3..s.len() - 1) }.as_bytes(),
+        buffer,
+    );
+}
+
 /// Serialize the given `buf`fer as hex
 pub fn serialize_hex<T: AsRef<[u8]>>(buf: T) -> String {
     faster_hex::hex_string(buf.as_ref())
@@ -227,5 +271,17 @@ mod test {
     fn it_desers_pubsub_items() {
         let a = r#"{"jsonrpc":"2.0","id":1,"result":"0xcd0c3e8af590364c09d0fa6a1210faf5"}"#;
         serde_json::from_str::<PubSubItem>(a).unwrap();
+
+        let n = r#"{"jsonrpc":"2.0","method":"eth_subscription","params":{"subscription":"0x9cef478923ff08bf67fde6c64013158d","result":{"number":"0x1b4"}}}"#;
+        match serde_json::from_str::<PubSubItem>(n).unwrap() {
+            PubSubItem::Notification { params } => {
+                assert_eq!(
+                    params.subscription,
+                    U256::from(0x9cef478923ff08bf67fde6c64013158d_u128)
+                );
+                assert_eq!(params.result.get(), r#"{"number":"0x1b4"}"#);
+            }
+            item => panic!("unexpected item: {item}"),
+        }
     }
 }
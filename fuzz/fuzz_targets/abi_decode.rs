@@ -0,0 +1,6 @@
+//! Router calldata and payload ABI types
+#![no_main]
+use fulcrum_engine::fuzz::decode_abi;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| decode_abi(data));
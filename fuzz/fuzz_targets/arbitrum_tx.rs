@@ -0,0 +1,11 @@
+//! Decoded l2 msgs i.e. signed txs and batches of them
+#![no_main]
+use bumpalo::Bump;
+use fulcrum_sequencer_feed::{fuzz::decode_arbitrum_tx, TxBuffer};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let bump = Bump::new();
+    let mut tx_buffer = TxBuffer::new(&bump);
+    let _ = decode_arbitrum_tx(data, &mut tx_buffer);
+});
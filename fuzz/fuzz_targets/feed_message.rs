@@ -0,0 +1,13 @@
+//! Sequencer feed messages (json, base64 l2 msg) from the wire
+#![no_main]
+use bumpalo::Bump;
+use fulcrum_sequencer_feed::{decode_feed_message, TxBuffer};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // decoded in place
+    let mut payload = data.to_vec();
+    let bump = Bump::new();
+    let mut tx_buffer = TxBuffer::new(&bump);
+    let _ = decode_feed_message(payload.as_mut_slice(), &mut tx_buffer);
+});
@@ -0,0 +1,6 @@
+//! Router calls, selector and params
+#![no_main]
+use fulcrum_engine::fuzz::decode_router_call;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| decode_router_call(data));